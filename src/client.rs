@@ -0,0 +1,317 @@
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Number, Value};
+use std::time::Duration;
+
+use crate::{
+    config::DEFAULT_PORT,
+    discovery::find_discovery_file,
+    mcp::{
+        handlers::handle_tool_call,
+        tools::{get_tools, resolve_tool_name},
+    },
+    protocol::mcp::ToolDefinition,
+    RustAnalyzerMCPServer,
+};
+
+/// Exit codes for `rust-analyzer-server status`.
+pub const STATUS_READY: i32 = 0;
+pub const STATUS_NOT_READY: i32 = 1;
+pub const STATUS_UNREACHABLE: i32 = 2;
+
+/// Request timeout for `call`; tool calls may wait for indexing.
+const CALL_TIMEOUT_SECS: u64 = 300;
+
+/// HTTP client for a running server, used by the CLI subcommands.
+pub struct RemoteServer {
+    http: reqwest::Client,
+    base_url: String,
+    port: u16,
+}
+
+/// Ports to try when none is given: the discovery file nearest the current
+/// directory first, then the default port.
+fn candidate_ports(port: Option<u16>) -> Vec<u16> {
+    if let Some(port) = port {
+        return vec![port];
+    }
+    let mut ports = Vec::new();
+    if let Some(info) = std::env::current_dir()
+        .ok()
+        .and_then(|dir| find_discovery_file(&dir))
+    {
+        ports.push(info.port);
+    }
+    if !ports.contains(&DEFAULT_PORT) {
+        ports.push(DEFAULT_PORT);
+    }
+    ports
+}
+
+impl RemoteServer {
+    /// Connect to the first candidate port with a server answering `/api/v1/health`.
+    pub async fn connect(port: Option<u16>, timeout: Duration) -> Result<Self> {
+        let http = reqwest::Client::builder().timeout(timeout).build()?;
+        let ports = candidate_ports(port);
+
+        for &port in &ports {
+            let base_url = format!("http://127.0.0.1:{}", port);
+            // Any answer counts: a degraded server replies 503 but is still reachable.
+            let probe = http
+                .get(format!("{}/api/v1/health", base_url))
+                .timeout(Duration::from_secs(2))
+                .send()
+                .await;
+            if probe.is_ok() {
+                return Ok(Self {
+                    http,
+                    base_url,
+                    port,
+                });
+            }
+        }
+
+        let tried: Vec<String> = ports.iter().map(u16::to_string).collect();
+        Err(anyhow!(
+            "No rust-analyzer-server reachable (tried port {})",
+            tried.join(", ")
+        ))
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub async fn get(&self, path: &str) -> Result<Value> {
+        let resp = self
+            .http
+            .get(format!("{}{}", self.base_url, path))
+            .send()
+            .await?;
+        unwrap_envelope(resp.json().await?)
+    }
+
+    pub async fn post(&self, path: &str, body: &Value) -> Result<Value> {
+        let resp = self
+            .http
+            .post(format!("{}{}", self.base_url, path))
+            .json(body)
+            .send()
+            .await?;
+        unwrap_envelope(resp.json().await?)
+    }
+}
+
+/// Extract `result` from an `{"ok": ..., "result"/"error": ...}` envelope.
+/// The error is `{code, message, ...}`; older servers sent a bare string.
+fn unwrap_envelope(body: Value) -> Result<Value> {
+    if body["ok"].as_bool() == Some(true) {
+        Ok(body["result"].clone())
+    } else {
+        Err(anyhow!(
+            "{}",
+            body["error"]["message"]
+                .as_str()
+                .or(body["error"].as_str())
+                .unwrap_or("Server returned an error")
+        ))
+    }
+}
+
+fn format_uptime(secs: u64) -> String {
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{}h {:02}m {:02}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+fn print_status(status: &Value) {
+    let field = |key: &str| status[key].as_str().unwrap_or("unknown").to_string();
+
+    println!("Workspace: {}", field("workspace"));
+    println!("State:     {}", field("state"));
+    println!("Backend:   {}", field("backend"));
+    if let Some(uptime) = status["uptime_secs"].as_u64() {
+        println!("Uptime:    {}", format_uptime(uptime));
+    }
+
+    let tasks = status["progress"].as_array().cloned().unwrap_or_default();
+    if !tasks.is_empty() {
+        println!("Indexing:");
+        for task in tasks {
+            let percentage = task["percentage"]
+                .as_u64()
+                .map(|p| format!("{:>3}%", p))
+                .unwrap_or_else(|| "   -".to_string());
+            let title = task["title"].as_str().unwrap_or_default();
+            match task["message"].as_str() {
+                Some(message) => println!("  {} {} ({})", percentage, title, message),
+                None => println!("  {} {}", percentage, title),
+            }
+        }
+    }
+}
+
+/// `rust-analyzer-server status`: print the server state and return the
+/// exit code (ready, not ready, or unreachable).
+pub async fn status(port: Option<u16>, json: bool) -> i32 {
+    let server = match RemoteServer::connect(port, Duration::from_secs(10)).await {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("{}", e);
+            return STATUS_UNREACHABLE;
+        }
+    };
+
+    let status = match server.get("/api/v1/status").await {
+        Ok(status) => status,
+        Err(e) => {
+            eprintln!("Failed to query status on port {}: {}", server.port(), e);
+            return STATUS_UNREACHABLE;
+        }
+    };
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&status).unwrap_or_default()
+        );
+    } else {
+        print_status(&status);
+    }
+
+    if status["state"] == "ready" {
+        STATUS_READY
+    } else {
+        STATUS_NOT_READY
+    }
+}
+
+/// Types of the arguments every tool takes, which tool schemas leave out.
+const COMMON_ARG_TYPES: &[(&str, &str)] = &[
+    ("compact", "boolean"),
+    ("wait_for_index", "boolean"),
+    ("relative_paths", "boolean"),
+    ("timeout_secs", "integer"),
+    ("max_output_bytes", "integer"),
+];
+
+/// Turn `key=value` pairs into arguments for `tool_name`. Values of
+/// parameters its input schema types as integers, numbers or booleans are
+/// converted when they parse as one; everything else is a string, which the
+/// tool rejects if it wanted something else.
+pub fn parse_tool_args(tool_name: &str, pairs: &[String]) -> Result<Value> {
+    let resolved = resolve_tool_name(tool_name);
+    let schema = get_tools()
+        .into_iter()
+        .find(|tool| tool.name == resolved)
+        .map(|tool| tool.input_schema);
+    let mut args = Map::new();
+    for pair in pairs {
+        let Some((key, value)) = pair.split_once('=') else {
+            return Err(anyhow!("Invalid --arg '{}': expected key=value", pair));
+        };
+        let kind = schema
+            .as_ref()
+            .and_then(|schema| schema["properties"][key]["type"].as_str())
+            .or_else(|| {
+                COMMON_ARG_TYPES
+                    .iter()
+                    .find(|(name, _)| *name == key)
+                    .map(|(_, kind)| *kind)
+            });
+        let typed = match kind {
+            Some("integer") => value.parse::<i64>().ok().map(Value::from),
+            // Whole numbers stay integers, as positions and counts need.
+            Some("number") => value.parse::<i64>().ok().map(Value::from).or_else(|| {
+                value
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(Number::from_f64)
+                    .map(Value::Number)
+            }),
+            Some("boolean") => value.parse::<bool>().ok().map(Value::from),
+            _ => None,
+        };
+        args.insert(key.to_string(), typed.unwrap_or_else(|| Value::from(value)));
+    }
+    Ok(Value::Object(args))
+}
+
+/// `rust-analyzer-server call`: run one tool and print its result, either
+/// against a running server or, with `local` set, on that in-process server.
+pub async fn call(
+    tool_name: &str,
+    args: Value,
+    port: Option<u16>,
+    local: Option<RustAnalyzerMCPServer>,
+) -> Result<()> {
+    if let Some(mut server) = local {
+        let result = handle_tool_call(&mut server, tool_name, args).await;
+        server.shutdown().await;
+        for item in result?.content {
+            println!("{}", item.text);
+        }
+        return Ok(());
+    }
+
+    let server = RemoteServer::connect(port, Duration::from_secs(CALL_TIMEOUT_SECS)).await?;
+    let result = server
+        .post(&format!("/api/v1/{}", tool_name), &args)
+        .await?;
+    match result {
+        Value::String(text) => println!("{}", text),
+        other => println!("{}", serde_json::to_string_pretty(&other)?),
+    }
+    Ok(())
+}
+
+/// First sentence of a tool description, for the `tools` table.
+fn summary_line(description: &str) -> &str {
+    description
+        .split(". ")
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches('.')
+}
+
+/// `rust-analyzer-server tools`: list the tools built into this binary or,
+/// with `remote` set, the ones a running server reports.
+pub async fn tools(json: bool, schema: bool, remote: bool, port: Option<u16>) -> Result<()> {
+    let tools: Vec<ToolDefinition> = if remote {
+        let server = RemoteServer::connect(port, Duration::from_secs(10)).await?;
+        let health = server.get("/api/v1/health").await.unwrap_or_default();
+        let version = health["version"].as_str().unwrap_or("unknown");
+        if version != env!("CARGO_PKG_VERSION") {
+            eprintln!(
+                "Note: server on port {} runs version {}, this binary is {}",
+                server.port(),
+                version,
+                env!("CARGO_PKG_VERSION")
+            );
+        }
+        let result = server.get("/api/v1/tools").await?;
+        serde_json::from_value(result["tools"].clone())?
+    } else {
+        get_tools()
+    };
+
+    if schema {
+        println!("{}", serde_json::to_string_pretty(&tools)?);
+    } else if json {
+        let summaries: Vec<Value> = tools
+            .iter()
+            .map(|t| serde_json::json!({ "name": t.name, "description": t.description }))
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&summaries)?);
+    } else {
+        let width = tools.iter().map(|t| t.name.len()).max().unwrap_or(0);
+        for tool in &tools {
+            println!("{:<width$}  {}", tool.name, summary_line(&tool.description));
+        }
+    }
+    Ok(())
+}
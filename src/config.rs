@@ -1,19 +1,611 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Port the server listens on, and clients connect to, by default.
+pub const DEFAULT_PORT: u16 = 15423;
+
+/// Default bind address.
+pub const DEFAULT_BIND: &str = "127.0.0.1";
+
 /// Timeout for LSP requests in seconds.
 pub const LSP_REQUEST_TIMEOUT_SECS: u64 = 30;
 
-/// Delay after opening a document to allow rust-analyzer to process it.
-/// Increased from 200ms to 1000ms to support large files with complex types.
+/// Timeout for the liveness probe sent by `/health?deep=true`.
+pub const HEALTH_PING_TIMEOUT_MILLIS: u64 = 2000;
+
+/// Fixed delay after opening a document, when `wait_for_document_ready` is
+/// off. 0 means no delay.
 pub const DOCUMENT_OPEN_DELAY_MILLIS: u64 = 1000;
 
+/// Longest wait for an opened document to be processed: a base plus an
+/// allowance per KiB of text, capped.
+pub const DOCUMENT_READY_BASE_MILLIS: u64 = 100;
+pub const DOCUMENT_READY_MILLIS_PER_KB: u64 = 2;
+pub const DOCUMENT_READY_MAX_MILLIS: u64 = 10_000;
+
+/// How often the wait for an opened document checks for diagnostics.
+pub const DOCUMENT_READY_POLL_MILLIS: u64 = 10;
+
 /// Timeout for tool calls that may need to wait for indexing to complete.
-/// Detects CI environment and uses longer timeout if needed.
-pub fn get_indexing_timeout_secs() -> u64 {
-    if std::env::var("CI").is_ok() {
-        60 // Longer timeout in CI
-    } else {
-        60 // Longer timeout for large projects
-    }
-}
+/// The same generous timeout is used locally (large projects) and in CI.
+pub const INDEXING_TIMEOUT_SECS: u64 = 60;
 
 /// Interval between retry attempts when waiting for indexing.
 pub const RETRY_INTERVAL_MILLIS: u64 = 500;
+
+/// Number of completed progress tasks kept for `/api/v1/progress`.
+pub const PROGRESS_HISTORY_LEN: usize = 50;
+
+/// Number of `window/showMessage` and `window/logMessage` notifications kept
+/// for `/api/v1/status`.
+pub const SERVER_MESSAGES_LEN: usize = 50;
+
+/// Default and maximum long-poll timeouts for `/api/v1/progress?wait_for=...`.
+pub const PROGRESS_WAIT_DEFAULT_SECS: u64 = 30;
+pub const PROGRESS_WAIT_MAX_SECS: u64 = 300;
+
+/// Documents kept open in rust-analyzer before the least recently used is closed.
+pub const MAX_OPEN_DOCUMENTS: u64 = 100;
+
+/// Files whose published diagnostics are kept, and their approximate total
+/// size, before the least recently published are dropped.
+pub const MAX_DIAGNOSTICS_FILES: u64 = 5000;
+pub const MAX_DIAGNOSTICS_MB: u64 = 64;
+
+/// How long `rust_analyzer_run_test` lets cargo build and run a test.
+pub const TEST_TIMEOUT_SECS: u64 = 300;
+
+/// How long `rust_analyzer_cargo_check` waits for the check to finish.
+pub const CARGO_CHECK_TIMEOUT_SECS: u64 = 300;
+
+/// Tool calls taking at least this long are logged at warn level.
+pub const SLOW_CALL_MILLIS: u64 = 5000;
+
+/// Most recent calls per tool the latency percentiles in `/api/v1/stats`
+/// are taken over.
+pub const CALL_STATS_WINDOW: usize = 200;
+
+/// Tool calls kept for `/api/v1/history` unless `history_len` says otherwise.
+pub const HISTORY_LEN: u64 = 200;
+
+/// Characters of an argument value kept in `/api/v1/history`; longer values,
+/// such as unsaved file contents, are cut.
+pub const HISTORY_ARG_MAX_CHARS: usize = 200;
+
+/// Results of file-scoped tools kept for repeat calls on unchanged files.
+pub const RESULT_CACHE_LEN: usize = 256;
+
+/// Completion items needing an import whose edits are resolved per call;
+/// those past it still name their import.
+pub const COMPLETION_RESOLVE_MAX: usize = 50;
+
+/// Tool output longer than this is truncated unless the call passes its own
+/// `max_output_bytes`.
+pub const DEFAULT_MAX_OUTPUT_BYTES: usize = 200_000;
+
+/// `--preload` waits for indexing to stay idle this long before reporting ready.
+pub const PRELOAD_QUIET_MILLIS: u64 = 2000;
+
+/// `--prewarm` runs once indexing has stayed idle this long.
+pub const PREWARM_QUIET_MILLIS: u64 = 2000;
+
+/// Upper bound on how long `--preload` reports "preloading".
+pub const PRELOAD_MAX_SECS: u64 = 600;
+
+/// Environment variable naming the rust-analyzer executable.
+pub const RUST_ANALYZER_BIN_ENV: &str = "RUST_ANALYZER_BIN";
+
+/// Configuration file looked up in the workspace root.
+pub const CONFIG_FILE_NAME: &str = "rust-analyzer-server.toml";
+
+/// A numeric setting, its `RUST_ANALYZER_*` override, and allowed range.
+struct Tunable {
+    key: &'static str,
+    env: &'static str,
+    min: u64,
+    max: u64,
+}
+
+const TUNABLES: &[Tunable] = &[
+    Tunable {
+        key: "indexing_timeout_secs",
+        env: "RUST_ANALYZER_INDEXING_TIMEOUT_SECS",
+        min: 1,
+        max: 3600,
+    },
+    Tunable {
+        key: "retry_interval_millis",
+        env: "RUST_ANALYZER_RETRY_INTERVAL_MILLIS",
+        min: 10,
+        max: 60_000,
+    },
+    Tunable {
+        key: "document_open_delay_millis",
+        env: "RUST_ANALYZER_DOCUMENT_OPEN_DELAY_MILLIS",
+        min: 0,
+        max: 60_000,
+    },
+    Tunable {
+        key: "request_timeout_secs",
+        env: "RUST_ANALYZER_REQUEST_TIMEOUT_SECS",
+        min: 1,
+        max: 3600,
+    },
+    Tunable {
+        key: "health_ping_timeout_millis",
+        env: "RUST_ANALYZER_HEALTH_PING_TIMEOUT_MILLIS",
+        min: 100,
+        max: 60_000,
+    },
+    Tunable {
+        key: "max_open_documents",
+        env: "RUST_ANALYZER_MAX_OPEN_DOCUMENTS",
+        min: 1,
+        max: 10_000,
+    },
+    Tunable {
+        key: "max_diagnostics_files",
+        env: "RUST_ANALYZER_MAX_DIAGNOSTICS_FILES",
+        min: 1,
+        max: 1_000_000,
+    },
+    Tunable {
+        key: "max_diagnostics_mb",
+        env: "RUST_ANALYZER_MAX_DIAGNOSTICS_MB",
+        min: 1,
+        max: 4096,
+    },
+    Tunable {
+        key: "test_timeout_secs",
+        env: "RUST_ANALYZER_TEST_TIMEOUT_SECS",
+        min: 1,
+        max: 3600,
+    },
+    Tunable {
+        key: "cargo_check_timeout_secs",
+        env: "RUST_ANALYZER_CARGO_CHECK_TIMEOUT_SECS",
+        min: 1,
+        max: 3600,
+    },
+    Tunable {
+        key: "slow_call_millis",
+        env: "RUST_ANALYZER_SLOW_CALL_MILLIS",
+        min: 1,
+        max: 3_600_000,
+    },
+    Tunable {
+        key: "history_len",
+        env: "RUST_ANALYZER_HISTORY_LEN",
+        min: 1,
+        max: 100_000,
+    },
+];
+
+/// How rust-analyzer loads the workspace: features, target, and whether build
+/// scripts run and proc macros expand. The `[cargo]` table of the config file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CargoSettings {
+    pub features: Vec<String>,
+    pub all_features: bool,
+    pub no_default_features: bool,
+    /// Target triple, e.g. `wasm32-unknown-unknown`; the host when unset.
+    pub target: Option<String>,
+    /// Run build scripts; proc macros can't be built without them.
+    pub build_scripts: bool,
+    /// Expand proc macros (derives, attribute macros) with rust-analyzer's
+    /// proc-macro server.
+    pub proc_macros: bool,
+}
+
+impl Default for CargoSettings {
+    fn default() -> Self {
+        Self {
+            features: Vec::new(),
+            all_features: false,
+            no_default_features: false,
+            target: None,
+            build_scripts: true,
+            proc_macros: true,
+        }
+    }
+}
+
+impl CargoSettings {
+    /// Whether proc macros are actually expanded, which needs both settings.
+    pub fn proc_macro_expansion(&self) -> bool {
+        self.proc_macros && self.build_scripts
+    }
+
+    /// The `initializationOptions` that select these settings.
+    pub fn initialization_options(&self) -> Value {
+        let features = if self.all_features {
+            json!("all")
+        } else {
+            json!(self.features)
+        };
+        json!({
+            "cargo": {
+                "features": features,
+                "noDefaultFeatures": self.no_default_features,
+                "target": self.target,
+                "buildScripts": { "enable": self.build_scripts },
+                "runBuildScripts": self.build_scripts
+            },
+            "procMacro": { "enable": self.proc_macro_expansion() }
+        })
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.features.iter().any(|f| f.trim().is_empty()) {
+            return Err(anyhow!("`cargo.features` must not contain empty names"));
+        }
+        if self.target.as_deref().is_some_and(|t| t.trim().is_empty()) {
+            return Err(anyhow!("`cargo.target` must not be empty"));
+        }
+        Ok(())
+    }
+}
+
+/// How rust-analyzer checks the workspace on save; the `[check]` table of
+/// the config file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CheckSettings {
+    /// Run `cargo clippy` instead of `cargo check`, so diagnostics include
+    /// clippy lints with their lint names as codes.
+    pub clippy: bool,
+    /// Extra arguments for the check command, e.g. `["-W", "clippy::pedantic"]`.
+    pub extra_args: Vec<String>,
+}
+
+impl CheckSettings {
+    pub fn command(&self) -> &'static str {
+        if self.clippy {
+            "clippy"
+        } else {
+            "check"
+        }
+    }
+
+    /// The `initializationOptions` that select the check command. Both the
+    /// current `check` keys and the older `checkOnSave` table are set, so
+    /// rust-analyzer releases from either side of the rename agree.
+    pub fn initialization_options(&self) -> Value {
+        json!({
+            "checkOnSave": {
+                "enable": true,
+                "command": self.command(),
+                "extraArgs": self.extra_args,
+                "allTargets": true
+            },
+            "check": {
+                "command": self.command(),
+                "extraArgs": self.extra_args,
+                "allTargets": true
+            }
+        })
+    }
+}
+
+/// Key for an LSP method in the `[timeouts]` table: `textDocument/` is
+/// dropped, then camelCase and separators become snake_case, so
+/// `workspace/symbol` is `workspace_symbol` and `textDocument/documentSymbol`
+/// is `document_symbol`.
+pub fn timeout_key(method: &str) -> String {
+    let method = method.strip_prefix("textDocument/").unwrap_or(method);
+    let mut key = String::with_capacity(method.len() + 4);
+    for ch in method.chars() {
+        match ch {
+            '/' | '-' => key.push('_'),
+            ch if ch.is_ascii_uppercase() => {
+                key.push('_');
+                key.push(ch.to_ascii_lowercase());
+            }
+            ch => key.push(ch),
+        }
+    }
+    key
+}
+
+/// How long to wait for rust-analyzer to process an opened document of
+/// `len` bytes before going ahead anyway.
+pub fn document_ready_deadline(len: usize) -> Duration {
+    let kb = len as u64 / 1024;
+    Duration::from_millis(
+        (DOCUMENT_READY_BASE_MILLIS + kb * DOCUMENT_READY_MILLIS_PER_KB)
+            .min(DOCUMENT_READY_MAX_MILLIS),
+    )
+}
+
+/// Server settings. Values come from, in order of precedence: CLI flags,
+/// environment variables, `rust-analyzer-server.toml`, and the defaults above.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub port: u16,
+    pub bind: String,
+    /// Folders analyzed alongside the workspace root, e.g. a crate that sits
+    /// next to the cargo workspace rather than in it. Relative paths are
+    /// resolved against the workspace root.
+    pub extra_workspaces: Vec<PathBuf>,
+    pub indexing_timeout_secs: u64,
+    pub retry_interval_millis: u64,
+    pub document_open_delay_millis: u64,
+    /// After opening a document, wait until rust-analyzer has processed it
+    /// (see `document_ready_deadline`) rather than `document_open_delay_millis`.
+    pub wait_for_document_ready: bool,
+    pub request_timeout_secs: u64,
+    pub health_ping_timeout_millis: u64,
+    /// Per-method overrides of `request_timeout_secs`, keyed by `timeout_key`.
+    pub timeouts: BTreeMap<String, u64>,
+    pub max_open_documents: u64,
+    pub max_diagnostics_files: u64,
+    pub max_diagnostics_mb: u64,
+    pub test_timeout_secs: u64,
+    pub cargo_check_timeout_secs: u64,
+    /// HTTP tool calls taking at least this long are logged at warn level.
+    pub slow_call_millis: u64,
+    /// HTTP tool calls kept for `/api/v1/history`.
+    pub history_len: u64,
+    /// rust-analyzer executable; discovered in ~/.cargo/bin, the rustup
+    /// toolchain, or on PATH when unset.
+    pub rust_analyzer_path: Option<PathBuf>,
+    pub cargo: CargoSettings,
+    pub check: CheckSettings,
+    /// Merged over the built-in `initializationOptions` sent to rust-analyzer,
+    /// after the `cargo` settings.
+    pub initialization_options: Option<Value>,
+    pub auth_token: Option<String>,
+    /// Start rust-analyzer and warm its indexes as soon as the server starts.
+    pub preload: bool,
+    /// Forward file changes in the workspace to rust-analyzer as they happen.
+    pub watch: bool,
+    /// Query rust-analyzer in the background whenever indexing finishes, so
+    /// the first tool calls afterwards don't pay for its cold caches.
+    pub prewarm: bool,
+    /// Files opened and asked for symbols when prewarming, relative to the
+    /// workspace root. Empty means `src/lib.rs` and `src/main.rs` of every
+    /// workspace member.
+    pub prewarm_files: Vec<PathBuf>,
+    /// Write file paths in tool results relative to the workspace root, for
+    /// calls that don't pass `relative_paths` themselves.
+    pub relative_paths: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            port: DEFAULT_PORT,
+            bind: DEFAULT_BIND.to_string(),
+            extra_workspaces: Vec::new(),
+            indexing_timeout_secs: INDEXING_TIMEOUT_SECS,
+            retry_interval_millis: RETRY_INTERVAL_MILLIS,
+            document_open_delay_millis: DOCUMENT_OPEN_DELAY_MILLIS,
+            wait_for_document_ready: true,
+            request_timeout_secs: LSP_REQUEST_TIMEOUT_SECS,
+            health_ping_timeout_millis: HEALTH_PING_TIMEOUT_MILLIS,
+            timeouts: BTreeMap::new(),
+            max_open_documents: MAX_OPEN_DOCUMENTS,
+            max_diagnostics_files: MAX_DIAGNOSTICS_FILES,
+            max_diagnostics_mb: MAX_DIAGNOSTICS_MB,
+            test_timeout_secs: TEST_TIMEOUT_SECS,
+            cargo_check_timeout_secs: CARGO_CHECK_TIMEOUT_SECS,
+            slow_call_millis: SLOW_CALL_MILLIS,
+            history_len: HISTORY_LEN,
+            rust_analyzer_path: None,
+            cargo: CargoSettings::default(),
+            check: CheckSettings::default(),
+            initialization_options: None,
+            auth_token: None,
+            preload: false,
+            watch: false,
+            prewarm: false,
+            prewarm_files: Vec::new(),
+            relative_paths: false,
+        }
+    }
+}
+
+impl Config {
+    /// Load the file at `path`, or `rust-analyzer-server.toml` in `workspace`
+    /// when no path is given. A missing workspace file means defaults.
+    pub fn load(path: Option<&Path>, workspace: &Path) -> Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => (workspace.join(CONFIG_FILE_NAME), false),
+        };
+        if !required && !path.exists() {
+            let mut config = Self::default();
+            config.apply_env()?;
+            return Ok(config);
+        }
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow!("Failed to read config file {}: {}", path.display(), e))?;
+        let mut config = Self::from_toml(&content)
+            .map_err(|e| anyhow!("Invalid config file {}: {}", path.display(), e))?;
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self> {
+        let config: Self = toml::from_str(content)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Override settings from `RUST_ANALYZER_*` environment variables.
+    pub fn apply_env(&mut self) -> Result<()> {
+        self.apply_env_from(|name| std::env::var(name).ok())
+    }
+
+    /// Like `apply_env`, reading variables through `lookup`.
+    pub fn apply_env_from(&mut self, lookup: impl Fn(&str) -> Option<String>) -> Result<()> {
+        if let Some(bin) = lookup(RUST_ANALYZER_BIN_ENV).filter(|bin| !bin.is_empty()) {
+            self.rust_analyzer_path = Some(PathBuf::from(bin));
+        }
+        for tunable in TUNABLES {
+            let Some(raw) = lookup(tunable.env) else {
+                continue;
+            };
+            let value: u64 = raw
+                .trim()
+                .parse()
+                .map_err(|_| anyhow!("{}={:?} is not a whole number", tunable.env, raw))?;
+            if !(tunable.min..=tunable.max).contains(&value) {
+                return Err(anyhow!(
+                    "{}={} is out of range ({}..={})",
+                    tunable.env,
+                    value,
+                    tunable.min,
+                    tunable.max
+                ));
+            }
+            *self.tunable_mut(tunable.key) = value;
+        }
+        Ok(())
+    }
+
+    fn tunable(&self, key: &str) -> u64 {
+        match key {
+            "indexing_timeout_secs" => self.indexing_timeout_secs,
+            "retry_interval_millis" => self.retry_interval_millis,
+            "document_open_delay_millis" => self.document_open_delay_millis,
+            "request_timeout_secs" => self.request_timeout_secs,
+            "health_ping_timeout_millis" => self.health_ping_timeout_millis,
+            "max_open_documents" => self.max_open_documents,
+            "max_diagnostics_files" => self.max_diagnostics_files,
+            "max_diagnostics_mb" => self.max_diagnostics_mb,
+            "test_timeout_secs" => self.test_timeout_secs,
+            "cargo_check_timeout_secs" => self.cargo_check_timeout_secs,
+            "slow_call_millis" => self.slow_call_millis,
+            "history_len" => self.history_len,
+            _ => unreachable!("unknown tunable {}", key),
+        }
+    }
+
+    fn tunable_mut(&mut self, key: &str) -> &mut u64 {
+        match key {
+            "indexing_timeout_secs" => &mut self.indexing_timeout_secs,
+            "retry_interval_millis" => &mut self.retry_interval_millis,
+            "document_open_delay_millis" => &mut self.document_open_delay_millis,
+            "request_timeout_secs" => &mut self.request_timeout_secs,
+            "health_ping_timeout_millis" => &mut self.health_ping_timeout_millis,
+            "max_open_documents" => &mut self.max_open_documents,
+            "max_diagnostics_files" => &mut self.max_diagnostics_files,
+            "max_diagnostics_mb" => &mut self.max_diagnostics_mb,
+            "test_timeout_secs" => &mut self.test_timeout_secs,
+            "cargo_check_timeout_secs" => &mut self.cargo_check_timeout_secs,
+            "slow_call_millis" => &mut self.slow_call_millis,
+            "history_len" => &mut self.history_len,
+            _ => unreachable!("unknown tunable {}", key),
+        }
+    }
+
+    /// Effective numeric settings, reported by `/api/v1/status`.
+    pub fn tunables(&self) -> Value {
+        let values = TUNABLES
+            .iter()
+            .map(|t| (t.key.to_string(), Value::from(self.tunable(t.key))))
+            .collect();
+        Value::Object(values)
+    }
+
+    fn validate(&self) -> Result<()> {
+        for tunable in TUNABLES {
+            let value = self.tunable(tunable.key);
+            if !(tunable.min..=tunable.max).contains(&value) {
+                return Err(anyhow!(
+                    "`{}` must be between {} and {}",
+                    tunable.key,
+                    tunable.min,
+                    tunable.max
+                ));
+            }
+        }
+        let request_timeout = TUNABLES
+            .iter()
+            .find(|t| t.key == "request_timeout_secs")
+            .expect("request_timeout_secs is tunable");
+        for (key, secs) in &self.timeouts {
+            if !(request_timeout.min..=request_timeout.max).contains(secs) {
+                return Err(anyhow!(
+                    "`timeouts.{}` must be between {} and {}",
+                    key,
+                    request_timeout.min,
+                    request_timeout.max
+                ));
+            }
+        }
+        if self.bind.is_empty() {
+            return Err(anyhow!("`bind` must not be empty"));
+        }
+        self.cargo.validate()?;
+        if let Some(options) = &self.initialization_options {
+            if !options.is_object() {
+                return Err(anyhow!("`initialization_options` must be a table"));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn indexing_timeout(&self) -> Duration {
+        Duration::from_secs(self.indexing_timeout_secs)
+    }
+
+    pub fn retry_interval(&self) -> Duration {
+        Duration::from_millis(self.retry_interval_millis)
+    }
+
+    pub fn slow_call(&self) -> Duration {
+        Duration::from_millis(self.slow_call_millis)
+    }
+
+    pub fn document_open_delay(&self) -> Duration {
+        Duration::from_millis(self.document_open_delay_millis)
+    }
+
+    pub fn test_timeout(&self) -> Duration {
+        Duration::from_secs(self.test_timeout_secs)
+    }
+
+    pub fn cargo_check_timeout(&self) -> Duration {
+        Duration::from_secs(self.cargo_check_timeout_secs)
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs)
+    }
+
+    /// Timeout for one LSP `method`: its `[timeouts]` entry if any, otherwise
+    /// `request_timeout_secs`.
+    pub fn request_timeout_for(&self, method: &str) -> Duration {
+        match self.timeouts.get(&timeout_key(method)) {
+            Some(secs) => Duration::from_secs(*secs),
+            None => self.request_timeout(),
+        }
+    }
+
+    pub fn health_ping_timeout(&self) -> Duration {
+        Duration::from_millis(self.health_ping_timeout_millis)
+    }
+}
+
+/// Recursively merge `overlay` into `base`; objects merge key by key, anything
+/// else in `overlay` replaces the value in `base`.
+pub fn merge_json(base: &mut Value, overlay: &Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                merge_json(base.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        (base, overlay) => *base = overlay.clone(),
+    }
+}
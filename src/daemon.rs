@@ -0,0 +1,275 @@
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use std::{
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+
+/// Environment variables the daemonized child reads its pid and log file paths from.
+const PID_FILE_ENV: &str = "RUST_ANALYZER_PID_FILE";
+const LOG_FILE_ENV: &str = "RUST_ANALYZER_LOG_FILE";
+
+/// How long `stop` waits for a graceful shutdown before sending SIGTERM.
+const STOP_TIMEOUT_SECS: u64 = 10;
+
+/// Directory for runtime files: `$XDG_RUNTIME_DIR`, falling back to the temp dir.
+fn runtime_dir() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+pub fn default_pid_file(port: u16) -> PathBuf {
+    runtime_dir().join(format!("rust-analyzer-server-{}.pid", port))
+}
+
+pub fn default_log_file(port: u16) -> PathBuf {
+    std::env::temp_dir().join(format!("rust-analyzer-server-{}.log", port))
+}
+
+fn read_pid(pid_file: &Path) -> Option<u32> {
+    std::fs::read_to_string(pid_file)
+        .ok()?
+        .lines()
+        .next()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(unix)]
+fn is_process_alive(pid: u32) -> bool {
+    std::process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_process_alive(_pid: u32) -> bool {
+    // No cheap liveness probe; treat existing pid files as stale.
+    false
+}
+
+#[cfg(unix)]
+fn terminate_process(pid: u32) -> Result<()> {
+    let status = std::process::Command::new("kill")
+        .args(["-TERM", &pid.to_string()])
+        .status()?;
+    if !status.success() {
+        return Err(anyhow!("Failed to send SIGTERM to pid {}", pid));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn terminate_process(pid: u32) -> Result<()> {
+    Err(anyhow!("Cannot terminate pid {} on this platform", pid))
+}
+
+/// Fail if `pid_file` names a live process other than us; remove it if stale.
+fn check_pid_file(pid_file: &Path) -> Result<()> {
+    let Some(pid) = read_pid(pid_file) else {
+        return Ok(());
+    };
+    if pid == std::process::id() {
+        return Ok(());
+    }
+    if is_process_alive(pid) {
+        return Err(anyhow!(
+            "rust-analyzer-server is already running (pid {}, pid file {}). \
+            Stop it with `rust-analyzer-server stop`.",
+            pid,
+            pid_file.display()
+        ));
+    }
+    warn!(
+        "Removing stale pid file {} (pid {} is not running)",
+        pid_file.display(),
+        pid
+    );
+    std::fs::remove_file(pid_file)?;
+    Ok(())
+}
+
+/// A pid file owned by this process, removed again on drop.
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    pub fn create(path: PathBuf) -> Result<Self> {
+        check_pid_file(&path)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, format!("{}\n", std::process::id()))?;
+        info!("Wrote pid file {}", path.display());
+        Ok(Self { path })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        // Only remove the file if it still names us.
+        if read_pid(&self.path) == Some(std::process::id()) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Re-launch the current executable detached from the terminal.
+///
+/// The child gets the same arguments minus `--daemon`, writes `pid_file`
+/// itself, and logs to `log_file`, which also receives its stdout/stderr. Re-executing rather than
+/// forking keeps the tokio runtime out of the picture.
+pub fn spawn_daemon(log_file: &Path, pid_file: &Path) -> Result<()> {
+    check_pid_file(pid_file)?;
+
+    let exe = std::env::current_exe()?;
+    let args: Vec<_> = std::env::args_os()
+        .skip(1)
+        .filter(|arg| arg != "--daemon")
+        .collect();
+
+    if let Some(parent) = log_file.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)?;
+
+    let mut cmd = std::process::Command::new(exe);
+    cmd.args(&args)
+        .env(PID_FILE_ENV, pid_file)
+        .env(LOG_FILE_ENV, log_file)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+
+    // Start a new session, leaving the caller's controlling terminal behind,
+    // so neither Ctrl-C nor closing the terminal (SIGHUP) reaches the daemon.
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // SAFETY: setsid is async-signal-safe and the closure allocates nothing.
+        unsafe {
+            cmd.pre_exec(|| {
+                if libc::setsid() == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| anyhow!("Failed to spawn daemon: {}", e))?;
+
+    // Catch immediate failures such as the port already being in use.
+    std::thread::sleep(Duration::from_millis(500));
+    if let Some(status) = child.try_wait()? {
+        return Err(anyhow!(
+            "Daemon exited immediately ({}); see {}",
+            status,
+            log_file.display()
+        ));
+    }
+
+    eprintln!(
+        "rust-analyzer-server started in background (pid {})",
+        child.id()
+    );
+    eprintln!("  pid file: {}", pid_file.display());
+    eprintln!("  log file: {}", log_file.display());
+    Ok(())
+}
+
+/// Ask the server to shut down, confirming with its own workspace path (and
+/// the auth token, when given) as the shutdown endpoint requires.
+async fn request_shutdown(port: u16, auth_token: Option<&str>) -> Result<()> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()?;
+    let base_url = format!("http://127.0.0.1:{}", port);
+
+    let health: serde_json::Value = client
+        .get(format!("{}/api/v1/health", base_url))
+        .send()
+        .await?
+        .json()
+        .await?;
+    let workspace = health["result"]["workspace"].as_str().unwrap_or_default();
+
+    let mut request = client
+        .post(format!("{}/api/v1/shutdown", base_url))
+        .json(&serde_json::json!({ "confirm": workspace }));
+    if let Some(token) = auth_token {
+        request = request.bearer_auth(token);
+    }
+
+    let resp = request.send().await?;
+    if !resp.status().is_success() {
+        return Err(anyhow!("Shutdown request failed: {}", resp.status()));
+    }
+    Ok(())
+}
+
+/// Stop a running server: ask it to shut down over HTTP, then fall back to SIGTERM.
+pub async fn stop(port: u16, pid_file: &Path, auth_token: Option<&str>) -> Result<()> {
+    let pid = read_pid(pid_file);
+
+    if let Some(pid) = pid {
+        if !is_process_alive(pid) {
+            std::fs::remove_file(pid_file)?;
+            eprintln!(
+                "Server (pid {}) is not running; removed stale pid file {}",
+                pid,
+                pid_file.display()
+            );
+            return Ok(());
+        }
+    }
+
+    let requested = request_shutdown(port, auth_token).await;
+
+    let Some(pid) = pid else {
+        return match requested {
+            Ok(()) => {
+                eprintln!("Sent shutdown request to server on port {}", port);
+                Ok(())
+            }
+            Err(e) => Err(anyhow!(
+                "No server running on port {} (no pid file at {}): {}",
+                port,
+                pid_file.display(),
+                e
+            )),
+        };
+    };
+
+    if requested.is_ok() {
+        let deadline = std::time::Instant::now() + Duration::from_secs(STOP_TIMEOUT_SECS);
+        while is_process_alive(pid) && std::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    }
+
+    if is_process_alive(pid) {
+        warn!(
+            "Server (pid {}) did not shut down gracefully, sending SIGTERM",
+            pid
+        );
+        terminate_process(pid)?;
+    }
+
+    let _ = std::fs::remove_file(pid_file);
+    eprintln!("Stopped rust-analyzer-server (pid {})", pid);
+    Ok(())
+}
@@ -1,5 +1,67 @@
+use anyhow::{anyhow, Result};
 use serde_json::{json, Value};
 
+/// LSP severity names, indexed by severity number minus one.
+const SEVERITY_NAMES: [&str; 4] = ["error", "warning", "information", "hint"];
+
+/// The `severity` and `code` arguments of the diagnostics tools.
+#[derive(Debug, Default)]
+pub struct DiagnosticFilter {
+    /// Keep diagnostics at least this severe (1 = error, 4 = hint).
+    max_severity: Option<u64>,
+    /// Keep diagnostics whose code equals this or starts with it, e.g.
+    /// `clippy::` for every clippy lint.
+    code: Option<String>,
+}
+
+impl DiagnosticFilter {
+    pub fn from_args(args: &Value) -> Result<Self> {
+        let max_severity = match args["severity"].as_str() {
+            Some(name) => {
+                let Some(index) = SEVERITY_NAMES.iter().position(|s| *s == name) else {
+                    return Err(anyhow!(
+                        "Unknown severity '{}'; expected one of {}",
+                        name,
+                        SEVERITY_NAMES.join(", ")
+                    ));
+                };
+                Some(index as u64 + 1)
+            }
+            None => None,
+        };
+        let code = args["code"].as_str().map(str::to_string);
+        Ok(Self { max_severity, code })
+    }
+
+    pub fn matches(&self, diag: &Value) -> bool {
+        if let Some(max) = self.max_severity {
+            // A missing severity is up to the client to interpret; treat it as an error.
+            if diag["severity"].as_u64().unwrap_or(1) > max {
+                return false;
+            }
+        }
+        if let Some(wanted) = &self.code {
+            let code = match &diag["code"] {
+                Value::String(code) => code.clone(),
+                Value::Number(code) => code.to_string(),
+                _ => return false,
+            };
+            if !code.starts_with(wanted.as_str()) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Filter an array of LSP diagnostics; other values pass through.
+    pub fn apply(&self, diagnostics: &Value) -> Value {
+        match diagnostics.as_array() {
+            Some(items) => items.iter().filter(|d| self.matches(d)).cloned().collect(),
+            None => diagnostics.clone(),
+        }
+    }
+}
+
 pub fn format_diagnostics(file_path: &str, result: &Value) -> Value {
     let Some(diag_array) = result.as_array() else {
         return json!({
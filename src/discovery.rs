@@ -0,0 +1,62 @@
+use anyhow::Result;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Name of the discovery file written to the workspace root.
+pub const DISCOVERY_FILE_NAME: &str = ".rust-analyzer-server.json";
+
+/// Where a running server can be reached, as advertised in its discovery file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveryInfo {
+    pub port: u16,
+    pub pid: u32,
+    pub workspace: PathBuf,
+}
+
+pub fn default_discovery_file(workspace: &Path) -> PathBuf {
+    workspace.join(DISCOVERY_FILE_NAME)
+}
+
+pub fn read_discovery_file(path: &Path) -> Option<DiscoveryInfo> {
+    let content = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Look for a discovery file in `dir` and its ancestors.
+pub fn find_discovery_file(dir: &Path) -> Option<DiscoveryInfo> {
+    dir.ancestors()
+        .find_map(|dir| read_discovery_file(&dir.join(DISCOVERY_FILE_NAME)))
+}
+
+/// A discovery file owned by this process, removed again on drop.
+pub struct DiscoveryFile {
+    path: PathBuf,
+}
+
+impl DiscoveryFile {
+    pub fn create(path: PathBuf, info: &DiscoveryInfo) -> Result<Self> {
+        if let Some(existing) = read_discovery_file(&path) {
+            if existing.pid != info.pid {
+                warn!(
+                    "Overwriting discovery file {} (was pid {}, port {})",
+                    path.display(),
+                    existing.pid,
+                    existing.port
+                );
+            }
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(info)? + "\n")?;
+        info!("Wrote discovery file {}", path.display());
+        Ok(Self { path })
+    }
+}
+
+impl Drop for DiscoveryFile {
+    fn drop(&mut self) {
+        // Only remove the file if another server hasn't taken it over.
+        if read_discovery_file(&self.path).is_some_and(|info| info.pid == std::process::id()) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use serde::Serialize;
+use std::{path::Path, time::Duration};
+
+use crate::{
+    config::Config,
+    lsp::binary::{find_rust_analyzer, version_of},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// Outcome of a single environment check.
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// How to fix a warning or failure.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Pass,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn problem(
+        name: &'static str,
+        status: CheckStatus,
+        detail: impl Into<String>,
+        hint: impl Into<String>,
+    ) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+pub fn check_rust_analyzer(configured: Option<&Path>) -> Check {
+    const NAME: &str = "rust-analyzer";
+    const HINT: &str = "Install it with `rustup component add rust-analyzer`";
+
+    let binary = match (find_rust_analyzer(configured), configured) {
+        (Ok(binary), _) => binary,
+        (Err(_), Some(configured)) => {
+            return Check::problem(
+                NAME,
+                CheckStatus::Fail,
+                format!("configured path {} not found", configured.display()),
+                "Fix --rust-analyzer-path, RUST_ANALYZER_BIN, or rust_analyzer_path in rust-analyzer-server.toml",
+            )
+        }
+        (Err(e), None) => return Check::problem(NAME, CheckStatus::Fail, e.to_string(), HINT),
+    };
+    match version_of(&binary.path) {
+        Ok(_) => Check::pass(NAME, binary.to_string()),
+        // The rustup proxy exists even when the component isn't installed.
+        Err(e) => Check::problem(
+            NAME,
+            CheckStatus::Fail,
+            format!("{} does not run: {}", binary.path.display(), e),
+            HINT,
+        ),
+    }
+}
+
+/// Check that a toolchain program is on PATH and runs.
+pub fn check_tool(name: &'static str, status_if_missing: CheckStatus, hint: &str) -> Check {
+    match which::which(name) {
+        Ok(path) => match version_of(&path) {
+            Ok(version) => Check::pass(name, version),
+            Err(e) => Check::problem(
+                name,
+                status_if_missing,
+                format!("does not run: {}", e),
+                hint,
+            ),
+        },
+        Err(_) => Check::problem(name, status_if_missing, "not found in PATH", hint),
+    }
+}
+
+pub fn check_workspace_manifest(workspace: &Path) -> Check {
+    const NAME: &str = "workspace";
+
+    if !workspace.is_dir() {
+        return Check::problem(
+            NAME,
+            CheckStatus::Fail,
+            format!("{} is not a directory", workspace.display()),
+            "Pass an existing project directory with --workspace",
+        );
+    }
+    let manifest = workspace.join("Cargo.toml");
+    if manifest.is_file() {
+        Check::pass(NAME, format!("found {}", manifest.display()))
+    } else {
+        Check::problem(
+            NAME,
+            CheckStatus::Fail,
+            format!("no Cargo.toml in {}", workspace.display()),
+            "Point --workspace at the directory containing Cargo.toml",
+        )
+    }
+}
+
+pub fn check_workspace_writable(workspace: &Path) -> Check {
+    const NAME: &str = "write access";
+
+    let probe = workspace.join(".rust-analyzer-server-doctor");
+    match std::fs::write(&probe, b"") {
+        Ok(()) => {
+            let _ = std::fs::remove_file(&probe);
+            Check::pass(NAME, format!("{} is writable", workspace.display()))
+        }
+        Err(e) => Check::problem(
+            NAME,
+            CheckStatus::Warn,
+            format!("cannot write to {}: {}", workspace.display(), e),
+            "The discovery file can't be written; pass --port-file with a writable path",
+        ),
+    }
+}
+
+/// The port must be free, or already serving a healthy rust-analyzer-server.
+pub async fn check_port(port: u16) -> Check {
+    const NAME: &str = "port";
+
+    if std::net::TcpListener::bind(("127.0.0.1", port)).is_ok() {
+        return Check::pass(NAME, format!("{} is free", port));
+    }
+
+    let health = async {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(2))
+            .build()?;
+        let resp = client
+            .get(format!("http://127.0.0.1:{}/api/v1/health", port))
+            .send()
+            .await?;
+        let status = resp.status();
+        let body: serde_json::Value = resp.json().await?;
+        Ok::<_, reqwest::Error>((status, body))
+    };
+
+    match health.await {
+        Ok((status, body)) if status.is_success() => Check::pass(
+            NAME,
+            format!(
+                "{} is serving a healthy instance (workspace {})",
+                port,
+                body["result"]["workspace"].as_str().unwrap_or("unknown")
+            ),
+        ),
+        // A degraded server answers 503.
+        Ok((status, _)) if status == reqwest::StatusCode::SERVICE_UNAVAILABLE => Check::problem(
+            NAME,
+            CheckStatus::Warn,
+            format!("{} is serving a degraded instance ({})", port, status),
+            "Restart it with `rust-analyzer-server stop` and start it again",
+        ),
+        _ => Check::problem(
+            NAME,
+            CheckStatus::Fail,
+            format!("{} is in use by another program", port),
+            "Pick another port with --port (or --port 0 for a free one)",
+        ),
+    }
+}
+
+/// Run every check against `workspace` and the configured port.
+pub async fn run_checks(workspace: &Path, config: &Config) -> Vec<Check> {
+    vec![
+        check_rust_analyzer(config.rust_analyzer_path.as_deref()),
+        check_tool(
+            "cargo",
+            CheckStatus::Fail,
+            "Install a Rust toolchain from https://rustup.rs",
+        ),
+        check_tool(
+            "rustfmt",
+            CheckStatus::Warn,
+            "rust_analyzer_format needs it: `rustup component add rustfmt`",
+        ),
+        check_workspace_manifest(workspace),
+        check_workspace_writable(workspace),
+        check_port(config.port).await,
+    ]
+}
+
+pub fn has_failures(checks: &[Check]) -> bool {
+    checks.iter().any(|c| c.status == CheckStatus::Fail)
+}
+
+pub fn print_report(checks: &[Check]) {
+    for check in checks {
+        let label = match check.status {
+            CheckStatus::Pass => "pass",
+            CheckStatus::Warn => "WARN",
+            CheckStatus::Fail => "FAIL",
+        };
+        println!("[{}] {}: {}", label, check.name, check.detail);
+        if let Some(hint) = &check.hint {
+            println!("       {}", hint);
+        }
+    }
+}
@@ -0,0 +1,33 @@
+#!/bin/sh
+# Claude Code PostToolUse hook installed by `rust-analyzer-server install --hooks`.
+# After Claude edits a .rs file, ask rust-analyzer for the file's errors and
+# feed them back (exit 2). Anything else, or no running server, is a no-op.
+
+INPUT=$(cat)
+FILE=$(printf '%s' "$INPUT" | sed -n 's/.*"file_path": *"\([^"]*\)".*/\1/p' | head -n 1)
+case "$FILE" in
+  *.rs) ;;
+  *) exit 0 ;;
+esac
+
+cd "${CLAUDE_PROJECT_DIR:-.}" || exit 0
+# Port: $RUST_ANALYZER_PORT, else the running server's discovery file, else the default
+PORT="${RUST_ANALYZER_PORT:-$(sed -n 's/.*"port": *\([0-9]*\).*/\1/p' .rust-analyzer-server.json 2>/dev/null)}"
+PORT="${PORT:-{{PORT}}}"
+
+if ! command -v rust-analyzer-server >/dev/null 2>&1; then
+  echo "ra-diagnostics hook: rust-analyzer-server is not on PATH, skipping" >&2
+  exit 0
+fi
+if ! RESULT=$(rust-analyzer-server call rust_analyzer_diagnostics --port "$PORT" \
+  --arg "file_path=$FILE" --arg severity=error 2>/dev/null); then
+  echo "ra-diagnostics hook: no rust-analyzer-server on port $PORT, skipping" >&2
+  exit 0
+fi
+
+case "$RESULT" in
+  *'"errors": 0'*) exit 0 ;;
+esac
+echo "rust-analyzer reports errors in $FILE:" >&2
+echo "$RESULT" >&2
+exit 2
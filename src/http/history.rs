@@ -0,0 +1,119 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use crate::{
+    config::HISTORY_ARG_MAX_CHARS,
+    lsp::progress::unix_millis,
+    protocol::{mcp::ToolResult, ToolError},
+};
+
+/// One finished HTTP tool call, as `/api/v1/history` lists it.
+#[derive(Debug, Clone, Serialize)]
+pub struct CallRecord {
+    /// Milliseconds since the Unix epoch when the call finished.
+    pub timestamp: u64,
+    pub tool: String,
+    /// The arguments, with long values cut by [`sanitize_args`].
+    pub args: Value,
+    pub duration_ms: u64,
+    /// `ok`, or the error code of a failed call.
+    pub outcome: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Bytes of text the tool answered with.
+    pub response_bytes: usize,
+}
+
+impl CallRecord {
+    pub fn new(
+        tool: &str,
+        args: Value,
+        elapsed: Duration,
+        result: &anyhow::Result<ToolResult>,
+    ) -> Self {
+        let (outcome, error, response_bytes) = match result {
+            Ok(result) => {
+                let bytes = result.content.iter().map(|item| item.text.len()).sum();
+                ("ok".to_string(), None, bytes)
+            }
+            Err(e) => {
+                let error = ToolError::from_anyhow(e);
+                let code = serde_json::to_value(error.code).unwrap_or_default();
+                let code = code.as_str().unwrap_or("internal").to_string();
+                (code, Some(error.message), 0)
+            }
+        };
+        Self {
+            timestamp: unix_millis(),
+            tool: tool.to_string(),
+            args,
+            duration_ms: elapsed.as_millis() as u64,
+            outcome,
+            error,
+            response_bytes,
+        }
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.outcome == "ok"
+    }
+}
+
+/// `args` with every string longer than [`HISTORY_ARG_MAX_CHARS`] cut to
+/// that many characters and marked with its full length, so file contents
+/// passed as `content` don't fill the history.
+pub fn sanitize_args(args: &Value) -> Value {
+    match args {
+        Value::String(text) => match text.char_indices().nth(HISTORY_ARG_MAX_CHARS) {
+            None => args.clone(),
+            Some((at, _)) => {
+                Value::String(format!("{}… ({} chars)", &text[..at], text.chars().count()))
+            }
+        },
+        Value::Array(items) => Value::Array(items.iter().map(sanitize_args).collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| (key.clone(), sanitize_args(value)))
+                .collect::<Map<_, _>>(),
+        ),
+        _ => args.clone(),
+    }
+}
+
+/// The most recent HTTP tool calls, oldest first.
+#[derive(Debug)]
+pub struct CallHistory {
+    capacity: usize,
+    calls: VecDeque<CallRecord>,
+}
+
+pub type SharedCallHistory = Arc<std::sync::Mutex<CallHistory>>;
+
+impl CallHistory {
+    /// A history keeping the last `capacity` calls.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            calls: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn push(&mut self, record: CallRecord) {
+        if self.calls.len() >= self.capacity {
+            self.calls.pop_front();
+        }
+        self.calls.push_back(record);
+    }
+
+    /// The kept calls of `tool` (all tools when `None`), only failed ones
+    /// when `errors_only`, oldest first.
+    pub fn query(&self, tool: Option<&str>, errors_only: bool) -> Vec<&CallRecord> {
+        self.calls
+            .iter()
+            .filter(|call| tool.is_none_or(|tool| call.tool == tool))
+            .filter(|call| !errors_only || !call.succeeded())
+            .collect()
+    }
+}
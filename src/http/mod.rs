@@ -1,59 +1,174 @@
+mod history;
+mod openapi;
+mod preload;
+mod pretty;
 pub(crate) mod routes;
 mod state;
+mod stats;
 
-pub use state::AppState;
+pub use history::{sanitize_args, CallHistory, CallRecord, SharedCallHistory};
+pub use openapi::openapi_spec;
+pub use state::{AppState, ServeOptions};
+pub use stats::{CallStats, SharedCallStats};
 
-use std::sync::Arc;
+use std::{
+    net::SocketAddr,
+    sync::{atomic::AtomicBool, Arc},
+    time::Instant,
+};
 use tokio::sync::{watch, Mutex};
 
-use axum::{
-    routing::{get, post},
-    Router,
+use axum::{middleware, routing::get, Router};
+use log::{info, warn};
+use tower_http::compression::CompressionLayer;
+
+use crate::{
+    discovery::{default_discovery_file, DiscoveryFile, DiscoveryInfo},
+    mcp::stdio::serve_stdio,
+    prewarm, watcher, RustAnalyzerMCPServer,
 };
-use log::info;
 
-use crate::RustAnalyzerMCPServer;
+pub async fn serve(
+    bind: &str,
+    port: u16,
+    server: RustAnalyzerMCPServer,
+    options: ServeOptions,
+) -> anyhow::Result<()> {
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
 
-pub async fn serve(bind: &str, port: u16, server: RustAnalyzerMCPServer) -> anyhow::Result<()> {
-    let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
+    let workspace = server.workspace_root.clone();
+    let preload = server.config.preload;
+    let watch = server.config.watch;
+    let prewarm = server.config.prewarm;
+    let slow_call = server.config.slow_call();
+    let history = options
+        .history
+        .then(|| CallHistory::new(server.config.history_len as usize));
+    let port_file = options
+        .port_file
+        .clone()
+        .unwrap_or_else(|| default_discovery_file(&workspace));
+    let stdio = options.stdio;
+    let progress = server.shared_progress();
 
+    let server = Arc::new(Mutex::new(server));
+    let watcher = watch.then(|| watcher::spawn(Arc::clone(&server)));
+    let prewarm = prewarm.then(|| prewarm::spawn(Arc::clone(&server)));
     let state = AppState {
-        server: Arc::new(Mutex::new(server)),
+        server,
+        progress,
         shutdown_tx,
+        options: Arc::new(options),
+        started_at: Instant::now(),
+        preloading: Arc::new(AtomicBool::new(preload)),
+        watcher,
+        prewarm,
+        stats: Arc::new(std::sync::Mutex::new(CallStats::new(slow_call))),
+        history: history.map(|history| Arc::new(std::sync::Mutex::new(history))),
     };
+    if preload {
+        tokio::spawn(preload::preload(state.clone()));
+    }
+    if stdio {
+        let (server, shutdown_tx) = (Arc::clone(&state.server), state.shutdown_tx.clone());
+        tokio::spawn(async move {
+            if let Err(e) = serve_stdio(server).await {
+                warn!("MCP stdio transport failed: {}", e);
+            }
+            let _ = shutdown_tx.send(true);
+        });
+    }
 
-    let router = Router::new()
-        .route("/api/v1/health", get(routes::health))
-        .route("/api/v1/status", get(routes::status))
-        .route("/api/v1/tools", get(routes::list_tools))
-        .route("/api/v1/workspace", get(routes::get_workspace))
-        .route("/api/v1/workspace", post(routes::set_workspace))
-        .route("/api/v1/shutdown", post(routes::shutdown))
-        .route("/api/v1/:tool_name", post(routes::call_tool))
+    let router = openapi::ROUTES
+        .iter()
+        .fold(Router::new(), |router, route| {
+            router.route(route.path, (route.handler)())
+        })
+        .route(
+            "/api/v1/:tool_name",
+            get(routes::get_tool).post(routes::call_tool),
+        )
+        // `?pretty=true` re-indents JSON bodies before they are compressed.
+        .layer(middleware::from_fn(pretty::pretty_json))
+        // gzip/br per the client's Accept-Encoding; small bodies are left alone.
+        .layer(CompressionLayer::new())
         .with_state(state);
 
-    let addr = format!("{}:{}", bind, port);
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    // Port 0 asks the OS for a free port; report the one we actually got,
+    // on stdout so scripts can capture it. With --stdio, stdout belongs to
+    // MCP and the HTTP API is a bonus.
+    let listener = match tokio::net::TcpListener::bind(format!("{}:{}", bind, port)).await {
+        Ok(listener) => listener,
+        Err(e) if stdio => {
+            warn!("HTTP API disabled, can't bind {}:{}: {}", bind, port, e);
+            wait_for_shutdown(shutdown_rx).await;
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let addr = listener.local_addr()?;
+    if !stdio {
+        println!("{}", addr.port());
+    }
     eprintln!("rust-analyzer HTTP server listening on http://{}", addr);
     info!("rust-analyzer HTTP server listening on http://{}", addr);
 
-    axum::serve(listener, router)
-        .with_graceful_shutdown(async move {
-            // Shut down on Ctrl-C or /api/v1/shutdown
-            let ctrl_c = tokio::signal::ctrl_c();
-            let shutdown_signal = async {
-                while shutdown_rx.changed().await.is_ok() {
-                    if *shutdown_rx.borrow() {
-                        return;
-                    }
-                }
-            };
-            tokio::select! {
-                _ = ctrl_c => { info!("Received Ctrl-C, shutting down"); }
-                _ = shutdown_signal => { info!("Received shutdown request"); }
-            }
+    // Removed on drop, i.e. after graceful shutdown.
+    let info = DiscoveryInfo {
+        port: addr.port(),
+        pid: std::process::id(),
+        workspace,
+    };
+    let _discovery_file = DiscoveryFile::create(port_file.clone(), &info)
+        .map_err(|e| {
+            warn!(
+                "Failed to write discovery file {}: {}",
+                port_file.display(),
+                e
+            )
         })
-        .await?;
+        .ok();
+
+    axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(wait_for_shutdown(shutdown_rx))
+    .await?;
 
     Ok(())
 }
+
+/// Resolves on Ctrl-C, SIGTERM, or a shutdown request (`/api/v1/shutdown`,
+/// or stdin closing with `--stdio`).
+async fn wait_for_shutdown(mut shutdown_rx: watch::Receiver<bool>) {
+    let ctrl_c = tokio::signal::ctrl_c();
+    let shutdown_signal = async {
+        while shutdown_rx.changed().await.is_ok() {
+            if *shutdown_rx.borrow() {
+                return;
+            }
+        }
+    };
+    tokio::select! {
+        _ = ctrl_c => { info!("Received Ctrl-C, shutting down"); }
+        _ = terminate_signal() => { info!("Received SIGTERM, shutting down"); }
+        _ = shutdown_signal => { info!("Received shutdown request"); }
+    }
+}
+
+#[cfg(unix)]
+async fn terminate_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+    match signal(SignalKind::terminate()) {
+        Ok(mut sigterm) => {
+            sigterm.recv().await;
+        }
+        Err(_) => std::future::pending().await,
+    }
+}
+
+#[cfg(not(unix))]
+async fn terminate_signal() {
+    std::future::pending::<()>().await
+}
@@ -0,0 +1,287 @@
+use axum::routing::{get, post, MethodRouter};
+use serde_json::{json, Map, Value};
+
+use crate::mcp::tools::get_tools;
+
+use super::{
+    routes::{self, GET_TOOLS},
+    AppState,
+};
+
+/// A fixed (non-tool) route, documented in the generated spec. The router
+/// in `http::serve` is built from `ROUTES`, so each route served is
+/// documented.
+pub(super) struct RouteDoc {
+    method: &'static str,
+    pub path: &'static str,
+    summary: &'static str,
+    /// Name of the request body schema under `components/schemas`, if any.
+    request_schema: Option<&'static str>,
+    pub handler: fn() -> MethodRouter<AppState>,
+}
+
+pub(super) const ROUTES: &[RouteDoc] = &[
+    RouteDoc {
+        method: "get",
+        path: "/api/v1/health",
+        summary: "Server health and workspace info",
+        request_schema: None,
+        handler: || get(routes::health),
+    },
+    RouteDoc {
+        method: "get",
+        path: "/api/v1/status",
+        summary: "Detailed server state (error, degraded, preloading, stopped, indexing, ready), indexing progress, and init trigger",
+        request_schema: None,
+        handler: || get(routes::status),
+    },
+    RouteDoc {
+        method: "get",
+        path: "/api/v1/progress",
+        summary: "Active and recently completed progress tasks; long-polls with ?wait_for=",
+        request_schema: None,
+        handler: || get(routes::progress),
+    },
+    RouteDoc {
+        method: "get",
+        path: "/api/v1/stats",
+        summary: "Per-tool call counts, error counts, slow calls, and p50/p95/max latency over recent calls",
+        request_schema: None,
+        handler: || get(routes::stats),
+    },
+    RouteDoc {
+        method: "get",
+        path: "/api/v1/history",
+        summary: "Recent tool calls with their arguments, duration, outcome and response size; filter with ?tool= and ?errors_only=true",
+        request_schema: None,
+        handler: || get(routes::history),
+    },
+    RouteDoc {
+        method: "get",
+        path: "/api/v1/tools",
+        summary: "List available tools with their input schemas",
+        request_schema: None,
+        handler: || get(routes::list_tools),
+    },
+    RouteDoc {
+        method: "get",
+        path: "/api/v1/workspace",
+        summary: "Current workspace path and initialization status",
+        request_schema: None,
+        handler: || get(routes::get_workspace),
+    },
+    RouteDoc {
+        method: "post",
+        path: "/api/v1/workspace",
+        summary: "Change the workspace root",
+        request_schema: Some("SetWorkspaceRequest"),
+        handler: || post(routes::set_workspace),
+    },
+    RouteDoc {
+        method: "get",
+        path: "/api/v1/config",
+        summary: "Cargo features, target, build scripts, and proc-macro expansion rust-analyzer loads the workspace with",
+        request_schema: None,
+        handler: || get(routes::get_config),
+    },
+    RouteDoc {
+        method: "post",
+        path: "/api/v1/config",
+        summary: "Replace the cargo settings; restarts rust-analyzer to reload the workspace",
+        request_schema: Some("CargoSettings"),
+        handler: || post(routes::set_config),
+    },
+    RouteDoc {
+        method: "post",
+        path: "/api/v1/shutdown",
+        summary: "Gracefully shut down the server (requires the auth token or a workspace confirmation)",
+        request_schema: Some("ShutdownRequest"),
+        handler: || post(routes::shutdown),
+    },
+    RouteDoc {
+        method: "get",
+        path: "/api/v1/openapi.json",
+        summary: "This OpenAPI document",
+        request_schema: None,
+        handler: || get(routes::openapi),
+    },
+];
+
+/// Statuses a tool call fails with besides 500, by error code.
+const TOOL_ERROR_RESPONSES: &[(&str, &str)] = &[
+    ("400", "Invalid parameters (invalid_params)"),
+    ("404", "Unknown tool or missing file (unknown_tool, file_not_found)"),
+    ("503", "Still indexing or backend unavailable; see the Retry-After header (indexing, backend_unavailable)"),
+    ("504", "rust-analyzer didn't answer in time (timeout)"),
+];
+
+fn operation(summary: &str, operation_id: &str, request_body: Option<Value>) -> Value {
+    let response = json!({
+        "content": {
+            "application/json": {
+                "schema": { "$ref": "#/components/schemas/ApiResponse" }
+            }
+        }
+    });
+
+    let mut op = json!({
+        "summary": summary,
+        "operationId": operation_id,
+        "responses": {
+            "200": { "description": "Success", "content": response["content"] },
+            "500": { "description": "Error", "content": response["content"] }
+        }
+    });
+
+    if let Some(schema) = request_body {
+        op["requestBody"] = json!({
+            "required": true,
+            "content": {
+                "application/json": { "schema": schema }
+            }
+        });
+    }
+
+    op
+}
+
+/// The `GET` form of a file-scoped tool: its arguments as query
+/// parameters, and an `ETag` on the answer that `If-None-Match` turns into
+/// a 304.
+fn get_operation(name: &str, post: &Value) -> Value {
+    let input_schema = &post["requestBody"]["content"]["application/json"]["schema"];
+    let required = input_schema["required"].as_array();
+    let mut parameters: Vec<Value> = input_schema["properties"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(param, _)| param.as_str() != "content")
+        .map(|(param, schema)| {
+            json!({
+                "name": param,
+                "in": "query",
+                "required": required.is_some_and(|required| required.contains(&json!(param))),
+                "schema": schema
+            })
+        })
+        .collect();
+    parameters.push(json!({
+        "name": "If-None-Match",
+        "in": "header",
+        "required": false,
+        "schema": { "type": "string" }
+    }));
+
+    let mut get = post.clone();
+    get["operationId"] = json!(format!("get_{}", name));
+    get["parameters"] = json!(parameters);
+    if let Some(get) = get.as_object_mut() {
+        get.remove("requestBody");
+    }
+    get["responses"]["200"]["headers"] = json!({
+        "ETag": { "schema": { "type": "string" }, "description": "Hash of the result" }
+    });
+    get["responses"]["304"] =
+        json!({ "description": "The result still has the ETag in If-None-Match" });
+    get
+}
+
+/// Build an OpenAPI 3.0 document for the HTTP API.
+///
+/// Tool endpoints are generated from `get_tools()`, so new tools show up
+/// without touching this file.
+pub fn openapi_spec() -> Value {
+    let mut paths = Map::new();
+
+    for route in ROUTES {
+        let operation_id = route
+            .path
+            .trim_start_matches("/api/v1/")
+            .replace(['/', '.'], "_");
+        let request_body = route
+            .request_schema
+            .map(|name| json!({ "$ref": format!("#/components/schemas/{}", name) }));
+        let entry = paths
+            .entry(route.path.to_string())
+            .or_insert_with(|| json!({}));
+        entry[route.method] = operation(
+            route.summary,
+            &format!("{}_{}", route.method, operation_id),
+            request_body,
+        );
+    }
+
+    for tool in get_tools() {
+        let mut post = operation(&tool.description, &tool.name, Some(tool.input_schema));
+        let content = post["responses"]["500"]["content"].clone();
+        for (status, description) in TOOL_ERROR_RESPONSES {
+            post["responses"][status] = json!({ "description": description, "content": content });
+        }
+        let mut path = json!({ "post": post });
+        if GET_TOOLS.contains(&tool.name.as_str()) {
+            path["get"] = get_operation(&tool.name, &post);
+        }
+        paths.insert(format!("/api/v1/{}", tool.name), path);
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "rust-analyzer-server",
+            "description": "Standalone HTTP server for rust-analyzer",
+            "version": env!("CARGO_PKG_VERSION")
+        },
+        "paths": paths,
+        "components": {
+            "schemas": {
+                "ApiResponse": {
+                    "type": "object",
+                    "properties": {
+                        "ok": { "type": "boolean" },
+                        "result": { "description": "Tool or endpoint result (present when ok is true)" },
+                        "error": { "$ref": "#/components/schemas/ToolError" }
+                    },
+                    "required": ["ok"]
+                },
+                "ToolError": {
+                    "type": "object",
+                    "description": "Why the call failed (present when ok is false)",
+                    "properties": {
+                        "code": {
+                            "type": "string",
+                            "enum": ["invalid_params", "indexing", "file_not_found", "backend_unavailable", "timeout", "unknown_tool", "forbidden", "internal"]
+                        },
+                        "message": { "type": "string", "description": "Error message" },
+                        "retryable": { "type": "boolean", "description": "Whether the same call may succeed later" },
+                        "details": { "description": "Extra fields for some codes, such as the suggested tool name for unknown_tool" }
+                    },
+                    "required": ["code", "message", "retryable"]
+                },
+                "ShutdownRequest": {
+                    "type": "object",
+                    "properties": {
+                        "confirm": { "type": "string", "description": "Current workspace path; required unless a bearer token is sent" }
+                    }
+                },
+                "CargoSettings": {
+                    "type": "object",
+                    "properties": {
+                        "features": { "type": "array", "items": { "type": "string" }, "description": "Features to enable" },
+                        "all_features": { "type": "boolean", "description": "Enable every feature" },
+                        "no_default_features": { "type": "boolean", "description": "Disable the default features" },
+                        "target": { "type": "string", "nullable": true, "description": "Target triple; the host when unset" },
+                        "build_scripts": { "type": "boolean", "description": "Run build scripts (default true)" },
+                        "proc_macros": { "type": "boolean", "description": "Expand proc macros; needs build_scripts (default true)" }
+                    }
+                },
+                "SetWorkspaceRequest": {
+                    "type": "object",
+                    "properties": {
+                        "workspace_path": { "type": "string", "description": "Path to the workspace root" }
+                    },
+                    "required": ["workspace_path"]
+                }
+            }
+        }
+    })
+}
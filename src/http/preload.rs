@@ -0,0 +1,61 @@
+use log::{info, warn};
+use std::{
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::{PRELOAD_MAX_SECS, PRELOAD_QUIET_MILLIS},
+    lsp::SymbolScope,
+};
+
+use super::state::AppState;
+
+/// Any query will do; it makes rust-analyzer build the workspace symbol index.
+const WARMUP_SYMBOL_QUERY: &str = "main";
+
+/// Start rust-analyzer right away, wait for indexing to go quiet, then build
+/// the symbol index. `/api/v1/status` reports "preloading" until done.
+pub(super) async fn preload(state: AppState) {
+    let start = Instant::now();
+    info!("Preloading rust-analyzer");
+
+    let (progress, retry_interval) = {
+        let mut server = state.server.lock().await;
+        if let Err(e) = server.ensure_client_started().await {
+            warn!("Preload failed to start rust-analyzer: {}", e);
+            state.preloading.store(false, Ordering::SeqCst);
+            return;
+        }
+        (server.progress_handle(), server.config.retry_interval())
+    };
+
+    // Wait without holding the server lock so status and tool calls stay
+    // responsive. Indexing runs as a series of progress tasks with short gaps
+    // between them, so require a quiet period rather than one idle sample.
+    if let Some(progress) = progress {
+        let quiet_period = Duration::from_millis(PRELOAD_QUIET_MILLIS);
+        let deadline = start + Duration::from_secs(PRELOAD_MAX_SECS);
+        let mut quiet_since = None;
+        while Instant::now() < deadline {
+            if progress.lock().await.is_indexing() {
+                quiet_since = None;
+            } else if quiet_since.get_or_insert_with(Instant::now).elapsed() >= quiet_period {
+                break;
+            }
+            tokio::time::sleep(retry_interval).await;
+        }
+    }
+
+    if let Some(client) = &mut state.server.lock().await.client {
+        if let Err(e) = client
+            .workspace_symbol(WARMUP_SYMBOL_QUERY, SymbolScope::Workspace)
+            .await
+        {
+            warn!("Preload symbol query failed: {}", e);
+        }
+    }
+
+    state.preloading.store(false, Ordering::SeqCst);
+    info!("Preload finished in {}s", start.elapsed().as_secs());
+}
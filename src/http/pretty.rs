@@ -0,0 +1,40 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+
+/// Pretty-print the JSON body of a response whose request asked for it
+/// with `?pretty=true`. Bodies are compact JSON otherwise.
+pub async fn pretty_json(request: Request, next: Next) -> Response {
+    let pretty = request
+        .uri()
+        .query()
+        .is_some_and(|query| query.split('&').any(|pair| pair == "pretty=true"));
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+    if !pretty || !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let body = match serde_json::from_slice::<Value>(&bytes)
+        .and_then(|value| serde_json::to_vec_pretty(&value))
+    {
+        Ok(pretty) => {
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Body::from(pretty)
+        }
+        Err(_) => Body::from(bytes),
+    };
+    Response::from_parts(parts, body)
+}
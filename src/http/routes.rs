@@ -1,14 +1,47 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{ConnectInfo, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
+use serde_json::{json, Map, Value};
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    net::SocketAddr,
+    pin::Pin,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::{
+        CargoSettings, PROGRESS_WAIT_DEFAULT_SECS, PROGRESS_WAIT_MAX_SECS, RETRY_INTERVAL_MILLIS,
+    },
+    logging::active_log_file,
+    lsp::content_hash,
+    mcp::{
+        handlers::{handle_shared_tool_call, handle_tool_call, refuse_while_indexing},
+        tools::{get_tools, resolve_tool_name},
+    },
+    protocol::{mcp::ToolResult, ErrorCode, ToolError},
+};
 
-use crate::mcp::{handlers::handle_tool_call, tools::get_tools};
+use super::{
+    history::{sanitize_args, CallRecord},
+    openapi::openapi_spec,
+    state::AppState,
+};
 
-use super::state::AppState;
+/// Tools that only read one file, which `GET /api/v1/{tool_name}` serves.
+pub(crate) const GET_TOOLS: &[&str] = &[
+    "rust_analyzer_symbols",
+    "rust_analyzer_inlay_hint",
+    "rust_analyzer_diagnostics",
+    "rust_analyzer_context",
+];
 
 #[derive(Serialize)]
 pub(crate) struct ApiResponse {
@@ -16,7 +49,7 @@ pub(crate) struct ApiResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     result: Option<Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    error: Option<String>,
+    error: Option<Value>,
 }
 
 impl ApiResponse {
@@ -28,37 +61,91 @@ impl ApiResponse {
         })
     }
 
-    fn error(msg: impl Into<String>) -> (StatusCode, Json<ApiResponse>) {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiResponse {
-                ok: false,
-                result: None,
-                error: Some(msg.into()),
-            }),
-        )
+    /// A failed call, with the error classified by
+    /// [`ToolError::from_anyhow`].
+    fn error(error: &anyhow::Error) -> Response {
+        Self::failure(ToolError::from_anyhow(error))
+    }
+
+    /// A failed call, with the status for its code and, when it's worth
+    /// retrying soon, a `Retry-After` header.
+    fn failure(error: ToolError) -> Response {
+        let status = StatusCode::from_u16(error.code.http_status())
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut headers = HeaderMap::new();
+        if let Some(secs) = error.retry_after_secs() {
+            headers.insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        let body = Json(ApiResponse {
+            ok: false,
+            result: None,
+            error: Some(error.to_json()),
+        });
+        (status, headers, body).into_response()
     }
 }
 
-pub async fn health(State(state): State<AppState>) -> Json<ApiResponse> {
-    let server = state.server.lock().await;
-    ApiResponse::success(json!({
-        "status": "ok",
-        "workspace": server.workspace_root.display().to_string(),
-        "initialized": server.client.is_some(),
-    }))
+#[derive(Deserialize)]
+pub struct HealthQuery {
+    /// Also send a probe request to rust-analyzer, not just check the process.
+    #[serde(default)]
+    pub deep: bool,
 }
 
-pub async fn status(State(state): State<AppState>) -> Json<ApiResponse> {
-    let server = state.server.lock().await;
+/// Health returns 503 when the backend is dead or unresponsive so scripts
+/// using plain HTTP status checks notice.
+pub async fn health(
+    State(state): State<AppState>,
+    Query(query): Query<HealthQuery>,
+) -> (StatusCode, Json<ApiResponse>) {
+    let mut server = state.server.lock().await;
+    let backend = server.backend_status(query.deep).await;
+    let (code, status) = if backend.is_healthy() {
+        (StatusCode::OK, "ok")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "degraded")
+    };
+    (
+        code,
+        ApiResponse::success(json!({
+            "status": status,
+            "version": env!("CARGO_PKG_VERSION"),
+            "backend": backend,
+            "workspace": server.workspace_root.display().to_string(),
+            "initialized": server.client.is_some(),
+        })),
+    )
+}
+
+pub async fn status(
+    State(state): State<AppState>,
+    Query(query): Query<HealthQuery>,
+) -> Json<ApiResponse> {
+    let mut server = state.server.lock().await;
+    let backend = server.backend_status(query.deep).await;
     let has_client = server.client.is_some();
     let is_indexing = server.is_indexing().await;
     let active_tasks = server.active_progress().await;
+    let estimate = server.indexing_estimate().await;
+    let server_messages = server.server_messages().await;
+    let registrations = server.registrations().await;
+    let diagnostic_store = match &server.client {
+        Some(client) => Some(client.diagnostic_store_stats().await),
+        None => None,
+    };
     let workspace_valid = server.workspace_exists();
     let (trigger, previous_workspace) = server.trigger_info();
+    let open_documents = server.open_documents().await;
+    let (documents_opened, cache_hits) = server.document_stats();
+    let (symbol_queries, symbol_hits, symbol_misses) = server.symbol_cache_stats();
+    let (cached_results, result_hits, result_misses) = server.result_cache_stats();
 
     let server_state = if !workspace_valid {
         "error"
+    } else if !backend.is_healthy() {
+        "degraded"
+    } else if state.preloading.load(Ordering::SeqCst) {
+        "preloading"
     } else if !has_client {
         "stopped"
     } else if is_indexing {
@@ -70,17 +157,144 @@ pub async fn status(State(state): State<AppState>) -> Json<ApiResponse> {
     let mut result = json!({
         "workspace": server.workspace_root.display().to_string(),
         "workspace_valid": workspace_valid,
+        "workspace_folders": server.workspace_folders(),
         "state": server_state,
         "initialized": has_client,
+        "backend": backend,
         "indexing": is_indexing,
         "trigger": trigger,
+        "indexing_percent": estimate.percent,
+        "indexing_task": estimate.title,
         "progress": active_tasks,
+        "server_messages": server_messages,
+        "registrations": registrations,
+        "uptime_secs": state.started_at.elapsed().as_secs(),
+        "config": server.config.tunables(),
+        "timeouts": server.config.timeouts,
+        "cargo": server.config.cargo,
+        "proc_macro_expansion": server.config.cargo.proc_macro_expansion(),
+        "position_encoding": server.client.as_ref().map(|c| c.position_encoding()),
+        "diagnostics_store": diagnostic_store,
+        "open_documents": {
+            "count": open_documents.len(),
+            "max": server.config.max_open_documents,
+            "uris": open_documents,
+            "opened": documents_opened,
+            "cache_hits": cache_hits,
+        },
+        "symbol_cache": {
+            "queries": symbol_queries,
+            "hits": symbol_hits,
+            "misses": symbol_misses,
+        },
+        "result_cache": {
+            "results": cached_results,
+            "hits": result_hits,
+            "misses": result_misses,
+        },
     });
 
     if let Some(prev) = previous_workspace {
         result["previous_workspace"] = json!(prev);
     }
+    if let Some(watcher) = &state.watcher {
+        result["watcher"] = json!(*watcher.lock().unwrap());
+    }
+    if let Some(prewarm) = &state.prewarm {
+        result["prewarm"] = json!(*prewarm.lock().unwrap());
+    }
+    if let Some(log_file) = active_log_file() {
+        result["log_file"] = json!(log_file.display().to_string());
+    }
+    result["stats"] = state.stats.lock().unwrap().to_json();
+
+    ApiResponse::success(result)
+}
+
+#[derive(Deserialize)]
+pub struct HistoryQuery {
+    /// Only calls of this tool (a name or alias).
+    pub tool: Option<String>,
+    /// Only calls that failed.
+    #[serde(default)]
+    pub errors_only: bool,
+}
+
+/// The most recent tool calls, oldest first; empty with `--no-history`.
+pub async fn history(
+    State(state): State<AppState>,
+    Query(query): Query<HistoryQuery>,
+) -> Json<ApiResponse> {
+    let Some(history) = &state.history else {
+        return ApiResponse::success(json!({ "enabled": false, "calls": [] }));
+    };
+    let tool = query.tool.as_deref().map(resolve_tool_name);
+    let history = history.lock().unwrap();
+    let calls = history.query(tool.as_deref(), query.errors_only);
+    ApiResponse::success(json!({ "enabled": true, "calls": calls }))
+}
+
+/// Per-tool call counts, error counts and latency percentiles.
+pub async fn stats(State(state): State<AppState>) -> Json<ApiResponse> {
+    ApiResponse::success(state.stats.lock().unwrap().to_json())
+}
+
+#[derive(Deserialize)]
+pub struct ProgressQuery {
+    /// Token or title substring of a task to wait for.
+    pub wait_for: Option<String>,
+    /// Long-poll timeout in seconds.
+    pub timeout: Option<u64>,
+}
+
+pub async fn progress(
+    State(state): State<AppState>,
+    Query(query): Query<ProgressQuery>,
+) -> Json<ApiResponse> {
+    // Grab the progress handle and release the server lock so tool calls
+    // aren't blocked while we long-poll.
+    let handle = state.server.lock().await.progress_handle();
+    let Some(progress) = handle else {
+        return ApiResponse::success(json!({
+            "active": [],
+            "completed": [],
+        }));
+    };
+
+    let mut waited = None;
+    if let Some(pattern) = &query.wait_for {
+        let timeout = Duration::from_secs(
+            query
+                .timeout
+                .unwrap_or(PROGRESS_WAIT_DEFAULT_SECS)
+                .min(PROGRESS_WAIT_MAX_SECS),
+        );
+        let start = Instant::now();
+        let since = progress.lock().await.ended_count();
+        let matched = loop {
+            if let Some(entry) = progress.lock().await.finished_match(pattern, since) {
+                break Some(entry);
+            }
+            if start.elapsed() >= timeout {
+                break None;
+            }
+            tokio::time::sleep(Duration::from_millis(RETRY_INTERVAL_MILLIS)).await;
+        };
+        waited = Some(json!({
+            "pattern": pattern,
+            "timed_out": matched.is_none(),
+            "matched": matched,
+        }));
+    }
 
+    let state = progress.lock().await;
+    let mut result = json!({
+        "active": state.active_tasks(),
+        "completed": state.completed_tasks(),
+    });
+    if let Some(waited) = waited {
+        result["wait"] = waited;
+    }
     ApiResponse::success(result)
 }
 
@@ -89,10 +303,16 @@ pub async fn list_tools() -> Json<ApiResponse> {
     ApiResponse::success(json!({ "tools": tools }))
 }
 
+/// Served as a bare document (no envelope) so OpenAPI tooling can consume it directly.
+pub async fn openapi() -> Json<Value> {
+    Json(openapi_spec())
+}
+
 pub async fn get_workspace(State(state): State<AppState>) -> Json<ApiResponse> {
     let server = state.server.lock().await;
     ApiResponse::success(json!({
         "workspace": server.workspace_root.display().to_string(),
+        "workspace_folders": server.workspace_folders(),
         "initialized": server.client.is_some(),
     }))
 }
@@ -105,7 +325,7 @@ pub struct SetWorkspaceRequest {
 pub async fn set_workspace(
     State(state): State<AppState>,
     Json(body): Json<SetWorkspaceRequest>,
-) -> Result<Json<ApiResponse>, (StatusCode, Json<ApiResponse>)> {
+) -> Result<Json<ApiResponse>, Response> {
     let mut server = state.server.lock().await;
     let args = json!({ "workspace_path": body.workspace_path });
     match handle_tool_call(&mut server, "rust_analyzer_set_workspace", args).await {
@@ -117,24 +337,185 @@ pub async fn set_workspace(
                 .unwrap_or_default();
             Ok(ApiResponse::success(json!({ "message": text })))
         }
-        Err(e) => Err(ApiResponse::error(e.to_string())),
+        Err(e) => Err(ApiResponse::error(&e)),
     }
 }
 
-pub async fn shutdown(State(state): State<AppState>) -> Json<ApiResponse> {
+pub async fn get_config(State(state): State<AppState>) -> Json<ApiResponse> {
+    let server = state.server.lock().await;
+    ApiResponse::success(json!({ "cargo": server.config.cargo }))
+}
+
+/// Replace the cargo settings; omitted fields take their defaults.
+pub async fn set_config(
+    State(state): State<AppState>,
+    Json(cargo): Json<CargoSettings>,
+) -> Result<Json<ApiResponse>, Response> {
     let mut server = state.server.lock().await;
-    server.shutdown().await;
-    let _ = state.shutdown_tx.send(true);
-    ApiResponse::success(json!({ "message": "shutting down" }))
+    match server.set_cargo_settings(cargo).await {
+        Ok(reloaded) => Ok(ApiResponse::success(json!({
+            "cargo": server.config.cargo,
+            "reloaded": reloaded,
+        }))),
+        Err(e) => Err(ApiResponse::error(&e)),
+    }
 }
 
-pub async fn call_tool(
+#[derive(Deserialize, Default)]
+pub struct ShutdownRequest {
+    /// Must equal the current workspace path when no auth token is presented.
+    pub confirm: Option<String>,
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
+/// Shutdown requires the configured auth token or a `confirm` field naming the
+/// current workspace, so a stray request can't kill a server other agents
+/// share. Requests with neither are refused before waiting for the server.
+pub async fn shutdown(
     State(state): State<AppState>,
-    Path(tool_name): Path<String>,
-    Json(args): Json<Value>,
-) -> Result<Json<ApiResponse>, (StatusCode, Json<ApiResponse>)> {
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    body: Option<Json<ShutdownRequest>>,
+) -> Result<Json<ApiResponse>, Response> {
+    if !state.options.remote_shutdown {
+        warn!(
+            "Rejected shutdown request from {}: remote shutdown is disabled",
+            peer
+        );
+        return Err(ApiResponse::failure(ToolError::new(
+            ErrorCode::Forbidden,
+            "Remote shutdown is disabled (--no-remote-shutdown); use Ctrl-C or `rust-analyzer-server stop`",
+        )));
+    }
+
+    let token_ok = match (&state.options.auth_token, bearer_token(&headers)) {
+        (Some(expected), Some(given)) => expected == given,
+        _ => false,
+    };
+    let confirm = body.and_then(|Json(b)| b.confirm);
+    let forbidden = |workspace: &str| {
+        warn!("Rejected unauthorized shutdown request from {}", peer);
+        ApiResponse::failure(ToolError::new(
+            ErrorCode::Forbidden,
+            format!(
+                "Shutdown requires the auth token or {{\"confirm\": \"{}\"}} in the body",
+                workspace
+            ),
+        ))
+    };
+    if !token_ok && confirm.is_none() {
+        return Err(forbidden("<workspace path>"));
+    }
+
     let mut server = state.server.lock().await;
-    match handle_tool_call(&mut server, &tool_name, args).await {
+    let confirm_ok = confirm.is_some_and(|c| {
+        let c = std::path::Path::new(&c);
+        c == server.workspace_root || c.canonicalize().is_ok_and(|c| c == server.workspace_root)
+    });
+    if !token_ok && !confirm_ok {
+        return Err(forbidden(&server.workspace_root.display().to_string()));
+    }
+
+    info!("Shutdown requested by {}", peer);
+    server.shutdown().await;
+    let _ = state.shutdown_tx.send(true);
+    Ok(ApiResponse::success(json!({ "message": "shutting down" })))
+}
+
+/// A tool call that is cancelled if dropped before finishing.
+///
+/// Axum drops the handler future when the HTTP client disconnects. The guard
+/// then drops the unfinished call, which aborts its retry loop, releases the
+/// server lock, and sends `$/cancelRequest` for each in-flight LSP request.
+struct DisconnectGuard<'a> {
+    tool_name: &'a str,
+    call: Option<Pin<Box<dyn Future<Output = anyhow::Result<ToolResult>> + Send + 'a>>>,
+}
+
+impl DisconnectGuard<'_> {
+    async fn run(mut self) -> anyhow::Result<ToolResult> {
+        let result = self.call.as_mut().expect("call already run").await;
+        self.call = None;
+        result
+    }
+}
+
+impl Drop for DisconnectGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(call) = self.call.take() {
+            drop(call);
+            info!("Client disconnected, cancelled {}", self.tool_name);
+        }
+    }
+}
+
+/// Count a finished call in the stats, warning about it when slow, and
+/// keep it in the history with its `args` when that's on. Names that aren't
+/// tools aren't counted, so typos can't grow the table.
+fn record_call(
+    state: &AppState,
+    tool_name: &str,
+    args: Option<Value>,
+    elapsed: Duration,
+    result: &anyhow::Result<ToolResult>,
+    digest: u64,
+) {
+    let tool_name = resolve_tool_name(tool_name);
+    if let (Some(history), Some(args)) = (&state.history, args) {
+        let record = CallRecord::new(&tool_name, args, elapsed, result);
+        history.lock().unwrap().push(record);
+    }
+    if let Err(e) = result {
+        if ToolError::from_anyhow(e).code == ErrorCode::UnknownTool {
+            return;
+        }
+    }
+    let slow = state
+        .stats
+        .lock()
+        .unwrap()
+        .record(&tool_name, elapsed, result.is_ok());
+    if slow {
+        warn!(
+            "Slow call: {} took {}ms (args {:016x})",
+            tool_name,
+            elapsed.as_millis(),
+            digest
+        );
+    }
+}
+
+/// Run a tool for an HTTP caller and return its result as JSON.
+async fn run_tool(state: &AppState, tool_name: &str, mut args: Value) -> Result<Value, Response> {
+    let digest = content_hash(&args.to_string());
+    let logged_args = state.history.as_ref().map(|_| sanitize_args(&args));
+    // Fail fast while indexing unless the call or the server asks to wait,
+    // so a call doesn't hold the server while others queue behind it.
+    if let Some(args) = args.as_object_mut() {
+        args.entry("wait_for_index")
+            .or_insert(json!(state.options.wait_for_index));
+    }
+    let guard = DisconnectGuard {
+        tool_name,
+        call: Some(Box::pin(async move {
+            // Before queueing for the server, which a call may hold for a
+            // while.
+            refuse_while_indexing(&state.progress, tool_name, &args).await?;
+            handle_shared_tool_call(&state.server, tool_name, args).await
+        })),
+    };
+    let start = Instant::now();
+    let result = guard.run().await;
+    let elapsed = start.elapsed();
+    record_call(state, tool_name, logged_args, elapsed, &result, digest);
+    match result {
         Ok(result) => {
             // Parse the text content back to JSON if possible, otherwise return as string
             let value = if let Some(item) = result.content.first() {
@@ -142,8 +523,71 @@ pub async fn call_tool(
             } else {
                 json!(null)
             };
-            Ok(ApiResponse::success(value))
+            Ok(value)
         }
-        Err(e) => Err(ApiResponse::error(e.to_string())),
+        Err(e) => Err(ApiResponse::error(&e)),
+    }
+}
+
+pub async fn call_tool(
+    State(state): State<AppState>,
+    Path(tool_name): Path<String>,
+    Json(args): Json<Value>,
+) -> Result<Json<ApiResponse>, Response> {
+    run_tool(&state, &tool_name, args)
+        .await
+        .map(ApiResponse::success)
+}
+
+/// A query-string value as JSON: numbers and booleans as themselves,
+/// anything else as a string.
+fn query_value(value: &str) -> Value {
+    match serde_json::from_str(value) {
+        Ok(value @ (Value::Number(_) | Value::Bool(_))) => value,
+        _ => json!(value),
+    }
+}
+
+/// `GET /api/v1/{tool_name}?file_path=...` for the read-only file-scoped
+/// tools, with the query string as arguments. The response carries an
+/// `ETag` of the result; a request whose `If-None-Match` has it gets a 304
+/// with no body.
+pub async fn get_tool(
+    State(state): State<AppState>,
+    Path(tool_name): Path<String>,
+    Query(query): Query<BTreeMap<String, String>>,
+    headers: HeaderMap,
+) -> Result<Response, Response> {
+    let resolved = resolve_tool_name(&tool_name);
+    if !GET_TOOLS.contains(&resolved.as_ref()) {
+        return Err(ApiResponse::failure(ToolError::new(
+            ErrorCode::InvalidParams,
+            format!(
+                "{} can't be called with GET; POST its arguments instead",
+                tool_name
+            ),
+        )));
+    }
+    let args: Map<String, Value> = query
+        .iter()
+        .map(|(key, value)| (key.clone(), query_value(value)))
+        .collect();
+    let value = run_tool(&state, &tool_name, Value::Object(args)).await?;
+
+    let etag = format!("\"{:016x}\"", content_hash(&value.to_string()));
+    let mut response_headers = HeaderMap::new();
+    if let Ok(etag) = HeaderValue::from_str(&etag) {
+        response_headers.insert(header::ETAG, etag);
+    }
+    let matches = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|tags| tags.to_str().ok())
+        .is_some_and(|tags| {
+            tags.split(',')
+                .any(|tag| tag.trim() == etag || tag.trim() == "*")
+        });
+    if matches {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
     }
+    Ok((response_headers, ApiResponse::success(value)).into_response())
 }
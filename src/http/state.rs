@@ -1,10 +1,67 @@
-use std::sync::Arc;
+use std::{
+    path::PathBuf,
+    sync::{atomic::AtomicBool, Arc},
+    time::Instant,
+};
 use tokio::sync::{watch, Mutex};
 
-use crate::RustAnalyzerMCPServer;
+use crate::{
+    lsp::SharedProgress, prewarm::SharedPrewarmStatus, watcher::SharedWatcherStatus,
+    RustAnalyzerMCPServer,
+};
+
+use super::{history::SharedCallHistory, stats::SharedCallStats};
 
 #[derive(Clone)]
 pub struct AppState {
     pub server: Arc<Mutex<RustAnalyzerMCPServer>>,
+    /// The server's rust-analyzer progress, readable while a call holds the
+    /// server.
+    pub progress: SharedProgress,
     pub shutdown_tx: watch::Sender<bool>,
+    pub options: Arc<ServeOptions>,
+    pub started_at: Instant,
+    /// Set while `--preload` is still warming up rust-analyzer.
+    pub preloading: Arc<AtomicBool>,
+    /// Present when `--watch` is on.
+    pub watcher: Option<SharedWatcherStatus>,
+    /// Present when `--prewarm` is on.
+    pub prewarm: Option<SharedPrewarmStatus>,
+    /// Counts and latencies of HTTP tool calls.
+    pub stats: SharedCallStats,
+    /// Recent HTTP tool calls; absent with `--no-history`.
+    pub history: Option<SharedCallHistory>,
+}
+
+/// Runtime options for the HTTP server.
+#[derive(Debug, Clone)]
+pub struct ServeOptions {
+    /// Bearer token that authorizes privileged endpoints such as shutdown.
+    pub auth_token: Option<String>,
+    /// Whether `/api/v1/shutdown` is enabled at all.
+    pub remote_shutdown: bool,
+    /// Discovery file to write once the port is bound; defaults to
+    /// `.rust-analyzer-server.json` in the workspace root.
+    pub port_file: Option<PathBuf>,
+    /// Also serve MCP over stdin/stdout, shutting down when stdin closes.
+    /// The HTTP API is then optional: a port that's taken is only a warning.
+    pub stdio: bool,
+    /// Whether HTTP tool calls wait out indexing unless they pass
+    /// `wait_for_index`; otherwise they fail fast with a 503 while it runs.
+    pub wait_for_index: bool,
+    /// Keep recent tool calls, with their arguments, for `/api/v1/history`.
+    pub history: bool,
+}
+
+impl Default for ServeOptions {
+    fn default() -> Self {
+        Self {
+            auth_token: None,
+            remote_shutdown: true,
+            port_file: None,
+            stdio: false,
+            wait_for_index: false,
+            history: true,
+        }
+    }
 }
@@ -0,0 +1,91 @@
+use serde_json::{json, Map, Value};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::config::CALL_STATS_WINDOW;
+
+/// Calls of one tool: totals since the server started, and the latencies
+/// of the most recent ones.
+#[derive(Debug, Default)]
+struct ToolCalls {
+    calls: u64,
+    errors: u64,
+    slow: u64,
+    recent: VecDeque<Duration>,
+}
+
+/// Call counts and latencies per tool, reported by `/api/v1/stats` and
+/// under `stats` in `/api/v1/status`.
+#[derive(Debug)]
+pub struct CallStats {
+    slow_threshold: Duration,
+    tools: BTreeMap<String, ToolCalls>,
+}
+
+pub type SharedCallStats = Arc<std::sync::Mutex<CallStats>>;
+
+/// The `percent`th percentile of `sorted` by nearest rank.
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+impl CallStats {
+    /// Calls taking at least `slow_threshold` count as slow.
+    pub fn new(slow_threshold: Duration) -> Self {
+        Self {
+            slow_threshold,
+            tools: BTreeMap::new(),
+        }
+    }
+
+    /// Count a call of `tool` that took `elapsed`. Returns whether it was
+    /// slow.
+    pub fn record(&mut self, tool: &str, elapsed: Duration, succeeded: bool) -> bool {
+        let calls = self.tools.entry(tool.to_string()).or_default();
+        let slow = elapsed >= self.slow_threshold;
+        calls.calls += 1;
+        calls.errors += u64::from(!succeeded);
+        calls.slow += u64::from(slow);
+        if calls.recent.len() == CALL_STATS_WINDOW {
+            calls.recent.pop_front();
+        }
+        calls.recent.push_back(elapsed);
+        slow
+    }
+
+    /// `{ slow_call_millis, window, tools: { name: { calls, errors, slow,
+    /// p50_ms, p95_ms, max_ms } } }`, the percentiles over each tool's
+    /// last `window` calls.
+    pub fn to_json(&self) -> Value {
+        let tools: Map<String, Value> = self
+            .tools
+            .iter()
+            .map(|(tool, calls)| {
+                let mut sorted: Vec<Duration> = calls.recent.iter().copied().collect();
+                sorted.sort();
+                let stats = json!({
+                    "calls": calls.calls,
+                    "errors": calls.errors,
+                    "slow": calls.slow,
+                    "p50_ms": millis(percentile(&sorted, 50)),
+                    "p95_ms": millis(percentile(&sorted, 95)),
+                    "max_ms": millis(sorted[sorted.len() - 1]),
+                });
+                (tool.clone(), stats)
+            })
+            .collect();
+        json!({
+            "slow_call_millis": self.slow_threshold.as_millis() as u64,
+            "window": CALL_STATS_WINDOW,
+            "tools": tools,
+        })
+    }
+}
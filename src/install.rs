@@ -1,108 +1,377 @@
 use anyhow::Result;
-use std::path::Path;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+};
 
+use crate::{config::DEFAULT_PORT, mcp::tools::get_tools, protocol::mcp::ToolDefinition};
+
+/// A hand-written skill file.
 struct SkillTemplate {
     filename: &'static str,
     content: &'static str,
+    /// The tools the skill calls.
+    tools: &'static [&'static str],
+    /// The command as listed in CLAUDE.md, with its arguments if they help.
+    usage: &'static str,
+    purpose: &'static str,
+    example: &'static str,
+}
+
+impl SkillTemplate {
+    fn name(&self) -> &'static str {
+        self.filename.strip_suffix(".md").unwrap_or(self.filename)
+    }
+}
+
+/// A skill `install` can write: hand-written, or generated from the
+/// definition of a tool none of the hand-written ones calls.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Skill {
+    /// The command name, e.g. `ra-hover`.
+    pub name: String,
+    pub content: String,
+    /// The tools the skill calls.
+    pub tools: Vec<String>,
+    pub generated: bool,
+    usage: String,
+    purpose: String,
+    example: String,
+}
+
+impl Skill {
+    pub fn filename(&self) -> String {
+        format!("{}.md", self.name)
+    }
 }
 
+impl From<&SkillTemplate> for Skill {
+    fn from(template: &SkillTemplate) -> Self {
+        Self {
+            name: template.name().to_string(),
+            content: template.content.to_string(),
+            tools: template.tools.iter().map(|tool| tool.to_string()).collect(),
+            generated: false,
+            usage: template.usage.to_string(),
+            purpose: template.purpose.to_string(),
+            example: template.example.to_string(),
+        }
+    }
+}
+
+/// Skill file for a tool without a hand-written skill, filled in by
+/// `generate_skill`. `{{PORT}}` and `{{WORKSPACE}}` are left for `render`.
+const GENERATED_SKILL_TEMPLATE: &str = r#"{{description}}
+
+Usage: {{usage}}
+
+Example: {{example}}
+
+{{arguments}}
+
+```bash
+# Port: $RUST_ANALYZER_PORT, else the running server's discovery file, else the default
+PORT="${RUST_ANALYZER_PORT:-$(sed -n 's/.*"port": *\([0-9]*\).*/\1/p' .rust-analyzer-server.json 2>/dev/null)}"
+PORT="${PORT:-{{PORT}}}"
+ARGS="$ARGUMENTS"
+{{variables}}
+RESULT=$(curl -s -X POST "http://localhost:${PORT}/api/v1/{{tool}}" \
+  -H 'Content-Type: application/json' \
+  -d "{{body}}" 2>/dev/null)
+
+if [ $? -ne 0 ] || [ -z "$RESULT" ]; then
+  echo "ERROR: rust-analyzer HTTP server is not running."
+  echo "Start it with: rust-analyzer-server --workspace {{WORKSPACE}} --port {{PORT}}"
+  exit 1
+fi
+
+echo "$RESULT" | python3 -m json.tool 2>/dev/null || echo "$RESULT"
+```
+"#;
+
+/// Render a skill for `tool`. Its required arguments are taken from the
+/// command line in schema order; the optional ones are only documented.
+pub fn generate_skill(tool: &ToolDefinition) -> Skill {
+    let name = format!(
+        "ra-{}",
+        tool.name
+            .strip_prefix("rust_analyzer_")
+            .unwrap_or(&tool.name)
+            .replace('_', "-")
+    );
+    let properties = tool.input_schema["properties"]
+        .as_object()
+        .cloned()
+        .unwrap_or_default();
+    let required: Vec<&str> = tool.input_schema["required"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+
+    let mut arguments = vec![
+        "| Argument | Type | Required | Description |".to_string(),
+        "|----------|------|----------|-------------|".to_string(),
+    ];
+    // Required arguments first, in command-line order.
+    let optional = properties
+        .keys()
+        .map(String::as_str)
+        .filter(|arg| !required.contains(arg));
+    for arg in required.iter().copied().chain(optional) {
+        let schema = properties.get(arg).unwrap_or(&Value::Null);
+        arguments.push(format!(
+            "| `{}` | {} | {} | {} |",
+            arg,
+            schema["type"].as_str().unwrap_or("any"),
+            if required.contains(&arg) { "yes" } else { "no" },
+            schema["description"].as_str().unwrap_or("")
+        ));
+    }
+    if properties.is_empty() {
+        arguments = vec!["The tool takes no arguments.".to_string()];
+    }
+
+    let mut variables = String::new();
+    let mut fields = Vec::new();
+    for (i, arg) in required.iter().enumerate() {
+        let variable = arg.to_uppercase();
+        variables.push_str(&format!(
+            "{}=$(echo \"$ARGS\" | awk '{{print ${}}}')\n",
+            variable,
+            i + 1
+        ));
+        // Strings are quoted; numbers, booleans, and JSON pass through.
+        let value = match properties.get(*arg).and_then(|s| s["type"].as_str()) {
+            Some("string") => format!("\\\"${}\\\"", variable),
+            _ => format!("${}", variable),
+        };
+        fields.push(format!("\\\"{}\\\":{}", arg, value));
+    }
+
+    let usage = std::iter::once(format!("/{}", name))
+        .chain(required.iter().map(|arg| format!("<{}>", arg)))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let example = std::iter::once(format!("/{}", name))
+        .chain(required.iter().map(
+            |arg| match properties.get(*arg).and_then(|s| s["type"].as_str()) {
+                _ if *arg == "file_path" => "src/main.rs".to_string(),
+                Some("number" | "integer") => "0".to_string(),
+                Some("boolean") => "true".to_string(),
+                _ => arg.to_string(),
+            },
+        ))
+        .collect::<Vec<_>>()
+        .join(" ");
+    // The first sentence of the description, for the CLAUDE.md table.
+    let purpose = tool
+        .description
+        .split(". ")
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches('.')
+        .to_string();
+
+    let content = GENERATED_SKILL_TEMPLATE
+        .replace("{{description}}", &tool.description)
+        .replace("{{usage}}", &usage)
+        .replace("{{example}}", &example)
+        .replace("{{arguments}}", &arguments.join("\n"))
+        .replace("{{variables}}", &variables)
+        .replace("{{tool}}", &tool.name)
+        .replace("{{body}}", &format!("{{{}}}", fields.join(",")));
+    Skill {
+        usage: format!("/{}", name),
+        name,
+        content,
+        tools: vec![tool.name.clone()],
+        generated: true,
+        purpose,
+        example,
+    }
+}
+
+/// Every skill `install` can write: the hand-written ones in `SKILLS`
+/// order, then one generated for each remaining tool.
+pub fn available_skills() -> Vec<Skill> {
+    let mut skills: Vec<Skill> = SKILLS.iter().map(Skill::from).collect();
+    for tool in get_tools() {
+        if !skills.iter().any(|skill| skill.tools.contains(&tool.name)) {
+            skills.push(generate_skill(&tool));
+        }
+    }
+    skills
+}
+
+/// In the order of the CLAUDE.md skills table.
 const SKILLS: &[SkillTemplate] = &[
+    SkillTemplate {
+        filename: "ra-setup.md",
+        content: include_str!("skills/ra-setup.md"),
+        tools: &["rust_analyzer_get_workspace", "rust_analyzer_set_workspace"],
+        usage: "/ra-setup [path]",
+        purpose: "Health check + set workspace",
+        example: "/ra-setup /path/to/project",
+    },
     SkillTemplate {
         filename: "ra-hover.md",
         content: include_str!("skills/ra-hover.md"),
+        tools: &["rust_analyzer_hover"],
+        usage: "/ra-hover",
+        purpose: "Type info + docs",
+        example: "/ra-hover src/main.rs 5 10",
     },
     SkillTemplate {
         filename: "ra-definition.md",
         content: include_str!("skills/ra-definition.md"),
+        tools: &["rust_analyzer_definition"],
+        usage: "/ra-definition",
+        purpose: "Go to definition",
+        example: "/ra-definition src/main.rs 5 10",
     },
     SkillTemplate {
         filename: "ra-references.md",
         content: include_str!("skills/ra-references.md"),
+        tools: &["rust_analyzer_references"],
+        usage: "/ra-references",
+        purpose: "Find all usages",
+        example: "/ra-references src/main.rs 5 10",
     },
     SkillTemplate {
         filename: "ra-search.md",
         content: include_str!("skills/ra-search.md"),
+        tools: &["rust_analyzer_workspace_symbol"],
+        usage: "/ra-search",
+        purpose: "Workspace symbol search",
+        example: "/ra-search MyStruct",
     },
     SkillTemplate {
         filename: "ra-diagnostics.md",
         content: include_str!("skills/ra-diagnostics.md"),
+        tools: &["rust_analyzer_diagnostics"],
+        usage: "/ra-diagnostics",
+        purpose: "File errors/warnings",
+        example: "/ra-diagnostics src/main.rs",
     },
     SkillTemplate {
         filename: "ra-workspace-diagnostics.md",
         content: include_str!("skills/ra-workspace-diagnostics.md"),
+        tools: &["rust_analyzer_workspace_diagnostics"],
+        usage: "/ra-workspace-diagnostics",
+        purpose: "All project diagnostics",
+        example: "/ra-workspace-diagnostics",
     },
     SkillTemplate {
         filename: "ra-callers.md",
         content: include_str!("skills/ra-callers.md"),
+        tools: &["rust_analyzer_incoming_calls"],
+        usage: "/ra-callers",
+        purpose: "Who calls this function?",
+        example: "/ra-callers src/main.rs 10 4",
     },
     SkillTemplate {
         filename: "ra-callees.md",
         content: include_str!("skills/ra-callees.md"),
+        tools: &["rust_analyzer_outgoing_calls"],
+        usage: "/ra-callees",
+        purpose: "What does this call?",
+        example: "/ra-callees src/main.rs 10 4",
     },
     SkillTemplate {
         filename: "ra-implementations.md",
         content: include_str!("skills/ra-implementations.md"),
-    },
-    SkillTemplate {
-        filename: "ra-setup.md",
-        content: include_str!("skills/ra-setup.md"),
+        tools: &["rust_analyzer_implementation"],
+        usage: "/ra-implementations",
+        purpose: "Trait implementations",
+        example: "/ra-implementations src/main.rs 5 10",
     },
     SkillTemplate {
         filename: "ra-impact.md",
         content: include_str!("skills/ra-impact.md"),
+        tools: &["rust_analyzer_hover", "rust_analyzer_impact"],
+        usage: "/ra-impact",
+        purpose: "Change impact analysis",
+        example: "/ra-impact src/main.rs 10 4",
     },
 ];
 
+/// Steps of the recommended workflow in CLAUDE.md, with the skill each
+/// needs; steps for skills that aren't installed are left out.
+const WORKFLOW: &[(Option<&str>, &str)] = &[
+    (
+        Some("ra-setup"),
+        "/ra-setup              -> verify server is running",
+    ),
+    (
+        Some("ra-search"),
+        "/ra-search MyFunction  -> find symbol location",
+    ),
+    (None, "Read file              -> read the code"),
+    (
+        Some("ra-hover"),
+        "/ra-hover ...          -> check types of external symbols",
+    ),
+    (
+        Some("ra-definition"),
+        "/ra-definition ...     -> jump to definitions",
+    ),
+    (
+        Some("ra-references"),
+        "/ra-references ...     -> find all usages (impact analysis)",
+    ),
+    (
+        Some("ra-callers"),
+        "/ra-callers ...        -> trace call hierarchy",
+    ),
+    (
+        Some("ra-diagnostics"),
+        "/ra-diagnostics ...    -> check for errors",
+    ),
+];
+
 const CLAUDE_MD_SECTION_MARKER: &str = "<!-- rust-analyzer-server -->";
+const CLAUDE_MD_SECTION_END: &str = "<!-- /rust-analyzer-server -->";
+
+/// Heading of a CLAUDE.md created by `install_skills`.
+const CLAUDE_MD_HEADING: &str = "# CLAUDE.md";
 
-const CLAUDE_MD_SNIPPET: &str = r#"<!-- rust-analyzer-server -->
+/// CLAUDE.md section, filled in by `claude_md_snippet` and `render`.
+const CLAUDE_MD_TEMPLATE: &str = r#"<!-- rust-analyzer-server -->
 ## rust-analyzer Server (Code Intelligence)
 
 A rust-analyzer HTTP server provides LSP-powered code intelligence. **Prefer these tools over Grep/Glob for code structure queries.**
 
 ### Server Info
 
-- **Port**: `15423` (default, override with `RUST_ANALYZER_PORT` env var)
-- **Health**: `curl -s http://localhost:${RUST_ANALYZER_PORT:-15423}/api/v1/health`
-- **Status**: `curl -s http://localhost:${RUST_ANALYZER_PORT:-15423}/api/v1/status`
+- **Port**: `{{PORT}}` (override with `RUST_ANALYZER_PORT` env var). The running server records its actual port in `.rust-analyzer-server.json` at the workspace root; the skills read it automatically.
+- **Health**: `curl -s http://localhost:${RUST_ANALYZER_PORT:-{{PORT}}}/api/v1/health`
+- **Status**: `curl -s http://localhost:${RUST_ANALYZER_PORT:-{{PORT}}}/api/v1/status`
 
 ### Starting the Server
 
 ```bash
-# Start (keeps rust-analyzer warm across requests)
-nohup rust-analyzer-server --workspace /path/to/this/project > /tmp/rust-analyzer-server.log 2>&1 &
+# Start in the background (keeps rust-analyzer warm across requests)
+rust-analyzer-server --daemon --workspace {{WORKSPACE}} --port {{PORT}}
 
-# Custom port
-nohup rust-analyzer-server --workspace /path/to/this/project --port 4000 > /tmp/rust-analyzer-server.log 2>&1 &
+# Stop
+rust-analyzer-server stop --port {{PORT}}
 ```
 
 ### Available Skills (slash commands)
 
 | Command | Purpose | Example |
 |---------|---------|---------|
-| `/ra-setup [path]` | Health check + set workspace | `/ra-setup /path/to/project` |
-| `/ra-hover` | Type info + docs | `/ra-hover src/main.rs 5 10` |
-| `/ra-definition` | Go to definition | `/ra-definition src/main.rs 5 10` |
-| `/ra-references` | Find all usages | `/ra-references src/main.rs 5 10` |
-| `/ra-search` | Workspace symbol search | `/ra-search MyStruct` |
-| `/ra-diagnostics` | File errors/warnings | `/ra-diagnostics src/main.rs` |
-| `/ra-workspace-diagnostics` | All project diagnostics | `/ra-workspace-diagnostics` |
-| `/ra-callers` | Who calls this function? | `/ra-callers src/main.rs 10 4` |
-| `/ra-callees` | What does this call? | `/ra-callees src/main.rs 10 4` |
-| `/ra-implementations` | Trait implementations | `/ra-implementations src/main.rs 5 10` |
-| `/ra-impact` | Change impact analysis | `/ra-impact src/main.rs 10 4` |
+{{skills_table}}
 
 ### Recommended Workflow
 
 ```
-1. /ra-setup              -> verify server is running
-2. /ra-search MyFunction  -> find symbol location
-3. Read file              -> read the code
-4. /ra-hover ...          -> check types of external symbols
-5. /ra-definition ...     -> jump to definitions
-6. /ra-references ...     -> find all usages (impact analysis)
-7. /ra-callers ...        -> trace call hierarchy
-8. /ra-diagnostics ...    -> check for errors
+{{workflow}}
 ```
 
 ### When to Use What
@@ -111,68 +380,1132 @@ nohup rust-analyzer-server --workspace /path/to/this/project --port 4000 > /tmp/
 - **Text search** (string literals, comments, config): Use Grep/Glob
 <!-- /rust-analyzer-server -->"#;
 
+/// Lines of diff shown per overwritten file in a dry run.
+const DIFF_PREVIEW_LINES: usize = 8;
+
+/// What installing does to one file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileAction {
+    Create,
+    Overwrite,
+    Unchanged,
+}
+
+/// A file `install` writes, with what is there now.
+#[derive(Debug, Clone)]
+pub struct PlannedFile {
+    pub path: PathBuf,
+    pub content: String,
+    pub current: Option<String>,
+    /// The current file was changed since `install` wrote it (or wasn't
+    /// written by it), so it's kept and the new content goes to `*.new`.
+    pub locally_modified: bool,
+}
+
+impl PlannedFile {
+    fn new(path: PathBuf, content: String) -> Result<Self> {
+        let current = read_if_exists(&path)?;
+        Ok(Self {
+            path,
+            content,
+            current,
+            locally_modified: false,
+        })
+    }
+
+    /// Where the new content goes when the current file is kept.
+    pub fn new_path(&self) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".new");
+        self.path.with_file_name(name)
+    }
+
+    pub fn action(&self) -> FileAction {
+        match &self.current {
+            None => FileAction::Create,
+            Some(current) if *current == self.content => FileAction::Unchanged,
+            Some(_) => FileAction::Overwrite,
+        }
+    }
+}
+
+/// What to install; the defaults install everything.
+#[derive(Debug, Clone, Default)]
+pub struct InstallOptions {
+    /// Skill names (`ra-hover`, with or without `.md`) to install; all when
+    /// empty.
+    pub only: Vec<String>,
+    /// Skill names to leave out.
+    pub except: Vec<String>,
+    /// Directory for the skill files instead of `.claude/commands`, relative
+    /// to the project unless absolute.
+    pub dest: Option<PathBuf>,
+    /// Leave CLAUDE.md alone.
+    pub no_claude_md: bool,
+    /// Port the skills, CLAUDE.md, and generated configs use; the default
+    /// port when `None`.
+    pub port: Option<u16>,
+    /// Workspace the server is started on; the target project when `None`.
+    pub workspace: Option<PathBuf>,
+    /// Register the server in the project's `.mcp.json`.
+    pub mcp: bool,
+    /// Add start/stop tasks and the port setting to `.vscode`.
+    pub vscode: bool,
+    /// Install a hook reporting rust-analyzer errors after Claude edits a
+    /// `.rs` file.
+    pub hooks: bool,
+    /// Overwrite skill files even if they were changed since installed.
+    pub force: bool,
+}
+
+/// Where `--hooks` installs the diagnostics hook script.
+pub const HOOK_SCRIPT: &str = ".claude/hooks/ra-diagnostics.sh";
+
+const DIAGNOSTICS_HOOK: &str = include_str!("hooks/ra-diagnostics.sh");
+
+/// Where the install manifest lives in a project.
+pub const MANIFEST_FILE: &str = ".claude/rust-analyzer-server-manifest.json";
+
+/// Hashes of the skill files `install` wrote, so later installs, `--verify`,
+/// and `uninstall` can tell our files from local edits.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InstallManifest {
+    /// Content hash by path, relative to the project when inside it.
+    pub files: BTreeMap<String, String>,
+}
+
+impl InstallManifest {
+    pub fn load(target: &Path) -> Result<Option<Self>> {
+        let path = target.join(MANIFEST_FILE);
+        read_if_exists(&path)?
+            .map(|content| {
+                serde_json::from_str(&content)
+                    .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", path.display(), e))
+            })
+            .transpose()
+    }
+
+    fn key(target: &Path, path: &Path) -> String {
+        path.strip_prefix(target)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Whether `content` at `path` is what we installed there.
+    pub fn is_installed(&self, target: &Path, path: &Path, content: &str) -> bool {
+        self.files.get(&Self::key(target, path)) == Some(&content_hash(content))
+    }
+
+    pub fn record(&mut self, target: &Path, path: &Path, content: &str) {
+        self.files
+            .insert(Self::key(target, path), content_hash(content));
+    }
+
+    /// Plan writing a file we own. One changed since we installed it is kept
+    /// unless `force`; without a manifest entry, only a file a release wrote
+    /// before the manifest existed counts as ours.
+    fn plan_file(
+        &self,
+        target: &Path,
+        path: PathBuf,
+        content: String,
+        force: bool,
+    ) -> Result<PlannedFile> {
+        let mut file = PlannedFile::new(path, content)?;
+        if let Some(current) = &file.current {
+            file.locally_modified = !force
+                && *current != file.content
+                && !self.is_installed(target, &file.path, current)
+                && !SHIPPED_SKILL_HASHES.contains(&content_hash(current).as_str());
+        }
+        Ok(file)
+    }
+
+    /// Recorded files, as paths.
+    pub fn paths(&self, target: &Path) -> Vec<PathBuf> {
+        self.files.keys().map(|key| target.join(key)).collect()
+    }
+}
+
+/// Content hashes of the skill files 0.3.0 installed, before the manifest
+/// recorded them. A file still matching one is unedited and safe to replace.
+const SHIPPED_SKILL_HASHES: &[&str] = &[
+    "28279cb9a134e3d1", // ra-callees.md
+    "c05e339e274e3530", // ra-callers.md
+    "912955476cbc2dc7", // ra-definition.md
+    "ea29dc7daa5dab87", // ra-diagnostics.md
+    "ef778ab7a5929635", // ra-hover.md
+    "609693de820b3553", // ra-impact.md
+    "8e258e83073375c3", // ra-implementations.md
+    "bcfe6152a5e9340a", // ra-references.md
+    "c04304450e73ea53", // ra-search.md
+    "83075cb31be0e435", // ra-setup.md
+    "514d2f566b562c1e", // ra-workspace-diagnostics.md
+];
+
+/// 64-bit FNV-1a of `content`, in hex. Stable across builds, unlike
+/// `DefaultHasher`; this only detects edits, so it needn't be cryptographic.
+pub fn content_hash(content: &str) -> String {
+    let hash = content.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// Skills selected by `options`, in `available_skills` order. Unknown names
+/// are an error listing the valid ones.
+fn select_skills(options: &InstallOptions) -> Result<Vec<Skill>> {
+    let skills = available_skills();
+    for name in options.only.iter().chain(&options.except) {
+        let name = name.strip_suffix(".md").unwrap_or(name);
+        if !skills.iter().any(|skill| skill.name == name) {
+            let valid: Vec<&str> = skills.iter().map(|skill| skill.name.as_str()).collect();
+            return Err(anyhow::anyhow!(
+                "Unknown skill `{}`; valid names are: {}",
+                name,
+                valid.join(", ")
+            ));
+        }
+    }
+    let named = |names: &[String], skill: &Skill| {
+        names
+            .iter()
+            .any(|name| name.strip_suffix(".md").unwrap_or(name) == skill.name)
+    };
+    let selected: Vec<_> = skills
+        .into_iter()
+        .filter(|skill| options.only.is_empty() || named(&options.only, skill))
+        .filter(|skill| !named(&options.except, skill))
+        .collect();
+    if selected.is_empty() {
+        return Err(anyhow::anyhow!("No skills left to install"));
+    }
+    Ok(selected)
+}
+
+/// Port and workspace substituted for `{{PORT}}` and `{{WORKSPACE}}` in the
+/// skill templates and the CLAUDE.md section.
+#[derive(Debug, Clone)]
+struct TemplateValues {
+    port: u16,
+    workspace: PathBuf,
+}
+
+impl TemplateValues {
+    fn new(target: &Path, options: &InstallOptions) -> Self {
+        Self {
+            port: options.port.unwrap_or(DEFAULT_PORT),
+            workspace: options
+                .workspace
+                .clone()
+                .unwrap_or_else(|| target.to_path_buf()),
+        }
+    }
+
+    fn render(&self, template: &str) -> String {
+        template
+            .replace("{{PORT}}", &self.port.to_string())
+            .replace(
+                "{{WORKSPACE}}",
+                &shell_quote(&self.workspace.to_string_lossy()),
+            )
+    }
+}
+
+/// `word` quoted for a POSIX shell if it needs to be.
+fn shell_quote(word: &str) -> String {
+    let plain = |ch: char| ch.is_ascii_alphanumeric() || "/._-+=:,@%".contains(ch);
+    if !word.is_empty() && word.chars().all(plain) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    }
+}
+
+/// The CLAUDE.md section for `skills`: the skills table and workflow list
+/// only the commands that are installed.
+fn claude_md_snippet(skills: &[Skill]) -> String {
+    let table: Vec<String> = skills
+        .iter()
+        .map(|skill| {
+            format!(
+                "| `{}` | {} | `{}` |",
+                skill.usage, skill.purpose, skill.example
+            )
+        })
+        .collect();
+    let workflow: Vec<String> = WORKFLOW
+        .iter()
+        .filter(|(skill, _)| skill.is_none_or(|name| skills.iter().any(|s| s.name == name)))
+        .enumerate()
+        .map(|(i, (_, step))| format!("{}. {}", i + 1, step))
+        .collect();
+    CLAUDE_MD_TEMPLATE
+        .replace("{{skills_table}}", &table.join("\n"))
+        .replace("{{workflow}}", &workflow.join("\n"))
+}
+
+/// Everything `install` would write into a project. Planning reads but
+/// never writes, so the same plan serves `--dry-run` and the installation.
+#[derive(Debug, Clone)]
+pub struct InstallPlan {
+    pub commands_dir: PathBuf,
+    pub skills: Vec<PlannedFile>,
+    /// `None` with `--no-claude-md`.
+    pub claude_md: Option<PlannedFile>,
+    /// Present with `--mcp`.
+    pub mcp_json: Option<PlannedFile>,
+    /// `.vscode/tasks.json` and `.vscode/settings.json`, with `--vscode`.
+    pub vscode_tasks: Option<PlannedFile>,
+    pub vscode_settings: Option<PlannedFile>,
+    /// The hook script and the `.claude/settings.json` running it, with
+    /// `--hooks`.
+    pub hook_script: Option<PlannedFile>,
+    pub claude_settings: Option<PlannedFile>,
+    /// The install manifest, recording the skill and hook files this plan
+    /// writes.
+    pub manifest: PlannedFile,
+}
+
+impl InstallPlan {
+    /// The JSON files merged into, with what we add to each.
+    fn merged_files(&self) -> impl Iterator<Item = (&PlannedFile, &'static str)> {
+        [
+            (&self.mcp_json, "MCP server entry"),
+            (&self.vscode_tasks, "rust-analyzer-server tasks"),
+            (&self.vscode_settings, "RUST_ANALYZER_PORT terminal setting"),
+            (&self.claude_settings, "diagnostics hook"),
+        ]
+        .into_iter()
+        .filter_map(|(file, what)| Some((file.as_ref()?, what)))
+    }
+}
+
+pub fn plan_install(target: &Path, options: &InstallOptions) -> Result<InstallPlan> {
+    let selected = select_skills(options)?;
+    let values = TemplateValues::new(target, options);
+    let commands_dir = match &options.dest {
+        Some(dest) => std::path::absolute(target.join(dest))?,
+        None => std::path::absolute(target.join(".claude").join("commands"))?,
+    };
+    let manifest = InstallManifest::load(target)?.unwrap_or_default();
+    let skills: Vec<PlannedFile> = selected
+        .iter()
+        .map(|skill| {
+            let path = commands_dir.join(skill.filename());
+            manifest.plan_file(target, path, values.render(&skill.content), options.force)
+        })
+        .collect::<Result<_>>()?;
+    let hook_script = options
+        .hooks
+        .then(|| {
+            let path = target.join(HOOK_SCRIPT);
+            manifest.plan_file(target, path, values.render(DIAGNOSTICS_HOOK), options.force)
+        })
+        .transpose()?;
+
+    let mut recorded = manifest;
+    for file in skills.iter().chain(&hook_script) {
+        if !file.locally_modified {
+            recorded.record(target, &file.path, &file.content);
+        }
+    }
+    let manifest = PlannedFile::new(
+        target.join(MANIFEST_FILE),
+        format!("{}\n", serde_json::to_string_pretty(&recorded)?),
+    )?;
+
+    let claude_md = if options.no_claude_md {
+        None
+    } else {
+        let path = target.join("CLAUDE.md");
+        let current = read_if_exists(&path)?;
+        let snippet = values.render(&claude_md_snippet(&selected));
+        let content = claude_md_content(current.as_deref(), &snippet);
+        Some(PlannedFile {
+            path,
+            content,
+            current,
+            locally_modified: false,
+        })
+    };
+    let mcp_json = options
+        .mcp
+        .then(|| {
+            plan_json_file(target.join(".mcp.json"), |config| {
+                merge_mcp_server(config, &values.workspace, values.port)
+            })
+        })
+        .transpose()?;
+    let vscode = target.join(".vscode");
+    let vscode_tasks = options
+        .vscode
+        .then(|| {
+            plan_json_file(vscode.join("tasks.json"), |config| {
+                merge_vscode_tasks(config, options.workspace.as_deref(), values.port)
+            })
+        })
+        .transpose()?;
+    let vscode_settings = options
+        .vscode
+        .then(|| {
+            plan_json_file(vscode.join("settings.json"), |config| {
+                merge_vscode_settings(config, values.port)
+            })
+        })
+        .transpose()?;
+    let claude_settings = options
+        .hooks
+        .then(|| plan_json_file(target.join(".claude").join("settings.json"), merge_hook))
+        .transpose()?;
+    Ok(InstallPlan {
+        commands_dir,
+        skills,
+        claude_md,
+        mcp_json,
+        vscode_tasks,
+        vscode_settings,
+        hook_script,
+        claude_settings,
+        manifest,
+    })
+}
+
 pub fn install_skills(target: &Path) -> Result<()> {
-    let commands_dir = target.join(".claude").join("commands");
-    std::fs::create_dir_all(&commands_dir)?;
+    apply_install(&plan_install(target, &InstallOptions::default())?)?;
+    Ok(())
+}
 
-    let mut installed = Vec::new();
+/// What `apply_install` did with the skill files.
+#[derive(Debug, Default)]
+pub struct InstallReport {
+    /// Skill files written.
+    pub installed: Vec<PathBuf>,
+    /// Skill files already up to date.
+    pub unchanged: Vec<PathBuf>,
+    /// Skill files changed since they were installed, left in place with
+    /// the bundled version next to them.
+    pub kept: Vec<PathBuf>,
+}
+
+pub fn apply_install(plan: &InstallPlan) -> Result<InstallReport> {
+    std::fs::create_dir_all(&plan.commands_dir)?;
 
-    for skill in SKILLS {
-        let dest = commands_dir.join(skill.filename);
-        std::fs::write(&dest, skill.content)?;
-        installed.push(skill.filename);
+    let mut report = InstallReport::default();
+    let mut kept = Vec::new();
+
+    for skill in &plan.skills {
+        match skill.action() {
+            FileAction::Unchanged => report.unchanged.push(skill.path.clone()),
+            FileAction::Overwrite if skill.locally_modified => {
+                std::fs::write(skill.new_path(), &skill.content)?;
+                report.kept.push(skill.path.clone());
+                kept.push(skill);
+            }
+            _ => {
+                std::fs::write(&skill.path, &skill.content)?;
+                report.installed.push(skill.path.clone());
+            }
+        }
     }
 
-    eprintln!("Installed {} skills into {}", installed.len(), commands_dir.display());
-    for name in &installed {
-        eprintln!("  /{}", name.strip_suffix(".md").unwrap_or(name));
+    eprint!(
+        "Installed {} skills into {}",
+        report.installed.len(),
+        plan.commands_dir.display()
+    );
+    if report.unchanged.is_empty() {
+        eprintln!();
+    } else {
+        eprintln!(" ({} already up to date)", report.unchanged.len());
+    }
+    for path in &report.installed {
+        eprintln!("  /{}", skill_name(path));
+    }
+    if let Some(hook) = &plan.hook_script {
+        match hook.action() {
+            FileAction::Overwrite if hook.locally_modified => {
+                std::fs::write(hook.new_path(), &hook.content)?;
+                kept.push(hook);
+            }
+            FileAction::Unchanged => {
+                make_executable(&hook.path)?;
+                eprintln!("Hook {} is up to date", hook.path.display());
+            }
+            _ => {
+                if let Some(parent) = hook.path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&hook.path, &hook.content)?;
+                make_executable(&hook.path)?;
+                eprintln!("Installed hook {}", hook.path.display());
+            }
+        }
+    }
+    for skill in kept {
+        eprintln!(
+            "warning: kept {}, which was changed since it was installed; the bundled \
+             version is in {} (use --force to overwrite)",
+            skill.path.display(),
+            skill.new_path().display()
+        );
+    }
+    if plan.manifest.action() != FileAction::Unchanged {
+        if let Some(parent) = plan.manifest.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&plan.manifest.path, &plan.manifest.content)?;
     }
 
     // Append rust-analyzer guide to CLAUDE.md
-    install_claude_md_section(target)?;
+    if let Some(claude_md) = &plan.claude_md {
+        if claude_md.action() != FileAction::Unchanged {
+            std::fs::write(&claude_md.path, &claude_md.content)?;
+        }
+        eprintln!("{}", claude_md_message(claude_md, false));
+    }
 
-    Ok(())
+    for (file, what) in plan.merged_files() {
+        if file.action() != FileAction::Unchanged {
+            if let Some(parent) = file.path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&file.path, &file.content)?;
+        }
+        eprintln!("{}", merged_file_message(file, what, false));
+    }
+
+    Ok(report)
 }
 
-fn install_claude_md_section(target: &Path) -> Result<()> {
-    let claude_md = target.join("CLAUDE.md");
+/// How an installed file compares with what `install` would write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileState {
+    UpToDate,
+    /// As installed, but the bundled version has changed since.
+    Outdated,
+    /// Changed locally since it was installed.
+    Modified,
+    Missing,
+}
 
-    if claude_md.exists() {
-        let content = std::fs::read_to_string(&claude_md)?;
-
-        // Already has our section — replace it
-        if content.contains(CLAUDE_MD_SECTION_MARKER) {
-            let start = content.find(CLAUDE_MD_SECTION_MARKER).unwrap();
-            let end_marker = "<!-- /rust-analyzer-server -->";
-            let end = content
-                .find(end_marker)
-                .map(|i| i + end_marker.len())
-                .unwrap_or(content.len());
-
-            let mut new_content = String::new();
-            new_content.push_str(&content[..start]);
-            new_content.push_str(CLAUDE_MD_SNIPPET);
-            new_content.push_str(&content[end..]);
-            std::fs::write(&claude_md, new_content)?;
-            eprintln!("Updated rust-analyzer section in {}", claude_md.display());
-        } else {
-            // Append to existing CLAUDE.md
-            let mut content = content;
-            if !content.ends_with('\n') {
-                content.push('\n');
+impl fmt::Display for FileState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // `pad` so the report can align states with `{:<11}`.
+        f.pad(match self {
+            FileState::UpToDate => "up to date",
+            FileState::Outdated => "outdated",
+            FileState::Modified => "modified",
+            FileState::Missing => "missing",
+        })
+    }
+}
+
+/// One file checked by `install --verify`.
+#[derive(Debug, Clone)]
+pub struct VerifyEntry {
+    pub path: PathBuf,
+    /// What in the file was checked, for files we only own part of.
+    pub part: Option<&'static str>,
+    pub state: FileState,
+}
+
+/// Compare an installation with `plan`, i.e. with the bundled templates
+/// rendered for the same options. Only the rust-analyzer section of
+/// CLAUDE.md is compared.
+pub fn verify_plan(plan: &InstallPlan) -> Vec<VerifyEntry> {
+    let state = |file: &PlannedFile| match file.action() {
+        FileAction::Unchanged => FileState::UpToDate,
+        FileAction::Overwrite if file.locally_modified => FileState::Modified,
+        FileAction::Overwrite => FileState::Outdated,
+        FileAction::Create => FileState::Missing,
+    };
+    let mut entries: Vec<VerifyEntry> = plan
+        .skills
+        .iter()
+        .chain(&plan.hook_script)
+        .map(|skill| VerifyEntry {
+            path: skill.path.clone(),
+            part: None,
+            state: state(skill),
+        })
+        .collect();
+    if let Some(claude_md) = &plan.claude_md {
+        let has_section = claude_md
+            .current
+            .as_deref()
+            .is_some_and(|content| content.contains(CLAUDE_MD_SECTION_MARKER));
+        entries.push(VerifyEntry {
+            path: claude_md.path.clone(),
+            part: Some("rust-analyzer section"),
+            state: match state(claude_md) {
+                FileState::Outdated if !has_section => FileState::Missing,
+                state => state,
+            },
+        });
+    }
+    entries.extend(plan.merged_files().map(|(file, what)| VerifyEntry {
+        path: file.path.clone(),
+        part: Some(what),
+        state: state(file),
+    }));
+    entries
+}
+
+/// The `--verify` report, one line per file and a summary.
+pub fn describe_verification(entries: &[VerifyEntry]) -> String {
+    let mut out = String::new();
+    for entry in entries {
+        out.push_str(&format!("{:<11} {}", entry.state, entry.path.display()));
+        if let Some(part) = entry.part {
+            out.push_str(&format!(" ({})", part));
+        }
+        out.push('\n');
+    }
+    let stale = entries
+        .iter()
+        .filter(|entry| entry.state != FileState::UpToDate)
+        .count();
+    if stale == 0 {
+        out.push_str(&format!(
+            "All {} checked files are up to date\n",
+            entries.len()
+        ));
+    } else {
+        let modified = entries
+            .iter()
+            .any(|entry| entry.state == FileState::Modified);
+        out.push_str(&format!(
+            "{} of {} checked files are out of date; rerun with --fix to update them{}\n",
+            stale,
+            entries.len(),
+            if modified {
+                " (and --force to replace local changes)"
+            } else {
+                ""
+            }
+        ));
+    }
+    out
+}
+
+/// The `--dry-run` report: what each file would go through, with a short
+/// diff for skill files that would be overwritten and JSON files that would
+/// be merged into.
+pub fn describe_plan(plan: &InstallPlan) -> String {
+    let mut out = String::new();
+    for skill in plan.skills.iter().chain(&plan.hook_script) {
+        let path = skill.path.display();
+        match (skill.action(), &skill.current) {
+            (FileAction::Create, _) => out.push_str(&format!("Would create {}\n", path)),
+            (FileAction::Unchanged, _) => out.push_str(&format!("Unchanged {}\n", path)),
+            (FileAction::Overwrite, Some(current)) => {
+                if skill.locally_modified {
+                    out.push_str(&format!(
+                        "Would keep {} (changed since installed) and write {}:\n",
+                        path,
+                        skill.new_path().display()
+                    ));
+                } else {
+                    out.push_str(&format!("Would overwrite {}:\n", path));
+                }
+                for line in short_diff(current, &skill.content, DIFF_PREVIEW_LINES) {
+                    out.push_str(&format!("    {}\n", line));
+                }
+            }
+            (FileAction::Overwrite, None) => unreachable!("overwrite without a current file"),
+        }
+    }
+    if let Some(claude_md) = &plan.claude_md {
+        out.push_str(&claude_md_message(claude_md, true));
+        out.push('\n');
+    }
+    for (file, what) in plan.merged_files() {
+        out.push_str(&merged_file_message(file, what, true));
+        out.push('\n');
+        if let (FileAction::Overwrite, Some(current)) = (file.action(), &file.current) {
+            // Against the file as we'd print it, so only merged-in lines show.
+            let current = serde_json::from_str::<Value>(&strip_jsonc(current))
+                .ok()
+                .and_then(|value| serde_json::to_string_pretty(&value).ok())
+                .unwrap_or_else(|| current.clone());
+            for line in short_diff(&current, &file.content, DIFF_PREVIEW_LINES) {
+                out.push_str(&format!("    {}\n", line));
             }
-            content.push('\n');
-            content.push_str(CLAUDE_MD_SNIPPET);
-            content.push('\n');
-            std::fs::write(&claude_md, content)?;
-            eprintln!("Appended rust-analyzer section to {}", claude_md.display());
         }
+    }
+    out
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut permissions = std::fs::metadata(path)?.permissions();
+    permissions.set_mode(permissions.mode() | 0o755);
+    std::fs::set_permissions(path, permissions)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+fn skill_name(path: &Path) -> String {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    name.strip_suffix(".md").unwrap_or(&name).to_string()
+}
+
+fn read_if_exists(path: &Path) -> Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(anyhow::anyhow!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+/// What installing does to CLAUDE.md: planned when `dry_run`, done
+/// otherwise.
+fn claude_md_message(claude_md: &PlannedFile, dry_run: bool) -> String {
+    let path = claude_md.path.display();
+    let has_section = claude_md
+        .current
+        .as_deref()
+        .is_some_and(|content| content.contains(CLAUDE_MD_SECTION_MARKER));
+    let (verb, done) = match (claude_md.action(), has_section) {
+        (FileAction::Unchanged, _) => {
+            return format!("rust-analyzer section in {} is up to date", path)
+        }
+        (FileAction::Create, _) => ("create", "Created"),
+        (FileAction::Overwrite, true) => ("update", "Updated"),
+        (FileAction::Overwrite, false) => ("append", "Appended"),
+    };
+    let action = if dry_run {
+        format!("Would {}", verb)
     } else {
-        // Create new CLAUDE.md
-        let content = format!("# CLAUDE.md\n\n{}\n", CLAUDE_MD_SNIPPET);
-        std::fs::write(&claude_md, content)?;
-        eprintln!("Created {} with rust-analyzer guide", claude_md.display());
+        done.to_string()
+    };
+    match (claude_md.action(), has_section) {
+        (FileAction::Create, _) => format!("{} {} with rust-analyzer guide", action, path),
+        (_, true) => format!("{} rust-analyzer section in {}", action, path),
+        _ => format!("{} rust-analyzer section to {}", action, path),
+    }
+}
+
+/// CLAUDE.md with our section added to `current`, or replacing the one
+/// already there.
+fn claude_md_content(current: Option<&str>, snippet: &str) -> String {
+    let Some(content) = current else {
+        return format!("{}\n\n{}\n", CLAUDE_MD_HEADING, snippet);
+    };
+
+    // Already has our section — replace it
+    if let Some(start) = content.find(CLAUDE_MD_SECTION_MARKER) {
+        let end = content
+            .find(CLAUDE_MD_SECTION_END)
+            .map(|i| i + CLAUDE_MD_SECTION_END.len())
+            .unwrap_or(content.len());
+
+        let mut new_content = String::new();
+        new_content.push_str(&content[..start]);
+        new_content.push_str(snippet);
+        new_content.push_str(&content[end..]);
+        return new_content;
+    }
+
+    // Append to existing CLAUDE.md
+    let mut content = content.to_string();
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push('\n');
+    content.push_str(snippet);
+    content.push('\n');
+    content
+}
+
+/// Key of our entry under `mcpServers` in `.mcp.json`.
+pub const MCP_SERVER_NAME: &str = "rust-analyzer";
+
+/// The `.mcp.json` entry that starts this server over stdio for
+/// `workspace`, on the port the CLAUDE.md section tells the skills to use.
+pub fn mcp_server_entry(workspace: &Path, port: u16) -> Value {
+    json!({
+        "command": "rust-analyzer-server",
+        "args": [
+            "--stdio",
+            "--workspace", workspace,
+            "--port", port.to_string()
+        ],
+        "env": {}
+    })
+}
+
+/// Add or replace our server under `mcpServers`.
+fn merge_mcp_server(config: &mut Map<String, Value>, workspace: &Path, port: u16) -> Result<()> {
+    config
+        .entry("mcpServers")
+        .or_insert_with(|| json!({}))
+        .as_object_mut()
+        .ok_or_else(|| anyhow::anyhow!("`mcpServers` is not an object"))?
+        .insert(
+            MCP_SERVER_NAME.to_string(),
+            mcp_server_entry(workspace, port),
+        );
+    Ok(())
+}
+
+/// VS Code tasks that start the server as a daemon on `workspace` (the
+/// VS Code workspace folder by default) and stop it again, on the port the
+/// skills use.
+pub fn vscode_tasks(workspace: Option<&Path>, port: u16) -> Vec<Value> {
+    let workspace = workspace.map_or(json!("${workspaceFolder}"), |path| json!(path));
+    let port = port.to_string();
+    vec![
+        json!({
+            "label": "Start rust-analyzer-server",
+            "type": "shell",
+            "command": "rust-analyzer-server",
+            "args": ["--daemon", "--workspace", workspace, "--port", port],
+            "problemMatcher": []
+        }),
+        json!({
+            "label": "Stop rust-analyzer-server",
+            "type": "shell",
+            "command": "rust-analyzer-server",
+            "args": ["stop", "--port", port],
+            "problemMatcher": []
+        }),
+    ]
+}
+
+/// Add our tasks to `tasks`, replacing ones with the same label.
+fn merge_vscode_tasks(
+    config: &mut Map<String, Value>,
+    workspace: Option<&Path>,
+    port: u16,
+) -> Result<()> {
+    config.entry("version").or_insert_with(|| json!("2.0.0"));
+    let tasks = config
+        .entry("tasks")
+        .or_insert_with(|| json!([]))
+        .as_array_mut()
+        .ok_or_else(|| anyhow::anyhow!("`tasks` is not an array"))?;
+    for task in vscode_tasks(workspace, port) {
+        match tasks.iter_mut().find(|t| t["label"] == task["label"]) {
+            Some(existing) => *existing = task,
+            None => tasks.push(task),
+        }
     }
+    Ok(())
+}
 
+/// Set `RUST_ANALYZER_PORT` in the integrated terminal, where the skills,
+/// `status`, and `call` read the server's port from.
+fn merge_vscode_settings(config: &mut Map<String, Value>, port: u16) -> Result<()> {
+    for os in ["linux", "osx", "windows"] {
+        let key = format!("terminal.integrated.env.{}", os);
+        config
+            .entry(key.clone())
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+            .ok_or_else(|| anyhow::anyhow!("`{}` is not an object", key))?
+            .insert("RUST_ANALYZER_PORT".to_string(), json!(port.to_string()));
+    }
     Ok(())
 }
+
+/// How Claude Code runs the hook script, from the project directory.
+const HOOK_COMMAND: &str = "\"$CLAUDE_PROJECT_DIR\"/.claude/hooks/ra-diagnostics.sh";
+
+/// The `PostToolUse` entry running the diagnostics hook after file edits.
+pub fn diagnostics_hook_entry() -> Value {
+    json!({
+        "matcher": "Edit|MultiEdit|Write",
+        "hooks": [{ "type": "command", "command": HOOK_COMMAND }]
+    })
+}
+
+/// Add our `PostToolUse` hook to `.claude/settings.json`, replacing an entry
+/// that already runs the script.
+fn merge_hook(config: &mut Map<String, Value>) -> Result<()> {
+    let entries = config
+        .entry("hooks")
+        .or_insert_with(|| json!({}))
+        .as_object_mut()
+        .ok_or_else(|| anyhow::anyhow!("`hooks` is not an object"))?
+        .entry("PostToolUse")
+        .or_insert_with(|| json!([]))
+        .as_array_mut()
+        .ok_or_else(|| anyhow::anyhow!("`hooks.PostToolUse` is not an array"))?;
+    let ours = |entry: &Value| {
+        entry["hooks"]
+            .as_array()
+            .is_some_and(|hooks| hooks.iter().any(|hook| hook["command"] == HOOK_COMMAND))
+    };
+    match entries.iter_mut().find(|entry| ours(entry)) {
+        Some(entry) => *entry = diagnostics_hook_entry(),
+        None => entries.push(diagnostics_hook_entry()),
+    }
+    Ok(())
+}
+
+/// Plan merging into the JSON object at `path` (created if missing). A file
+/// whose content wouldn't change is left as is, comments and all.
+fn plan_json_file(
+    path: PathBuf,
+    merge: impl FnOnce(&mut Map<String, Value>) -> Result<()>,
+) -> Result<PlannedFile> {
+    let current = read_if_exists(&path)?;
+    let merged = (|| {
+        let existing = match &current {
+            Some(content) => serde_json::from_str(&strip_jsonc(content))?,
+            None => json!({}),
+        };
+        let mut config = match &existing {
+            Value::Object(map) => map.clone(),
+            _ => return Err(anyhow::anyhow!("expected a JSON object")),
+        };
+        merge(&mut config)?;
+        let config = Value::Object(config);
+        Ok(match &current {
+            Some(content) if config == existing => content.clone(),
+            _ => format!("{}\n", serde_json::to_string_pretty(&config)?),
+        })
+    })();
+    let content = merged.map_err(|e| anyhow::anyhow!("Can't update {}: {}", path.display(), e))?;
+    Ok(PlannedFile {
+        path,
+        content,
+        current,
+        locally_modified: false,
+    })
+}
+
+/// `content` without the comments and trailing commas VS Code allows in its
+/// JSON files.
+pub fn strip_jsonc(content: &str) -> String {
+    let chars: Vec<char> = content.chars().collect();
+    let mut out = String::with_capacity(content.len());
+    // A comma is dropped if the next token closes an object or array.
+    let mut pending_comma = None;
+    let mut i = 0;
+    while i < chars.len() {
+        match (chars[i], chars.get(i + 1)) {
+            ('"', _) => {
+                // Copy the string through its closing quote.
+                pending_comma = None;
+                out.push('"');
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    if chars[i] == '\\' && i + 1 < chars.len() {
+                        out.push(chars[i]);
+                        i += 1;
+                    }
+                    out.push(chars[i]);
+                    i += 1;
+                }
+                if i < chars.len() {
+                    out.push('"');
+                }
+                i += 1;
+            }
+            ('/', Some('/')) => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            ('/', Some('*')) => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                i += 2;
+            }
+            (',', _) => {
+                pending_comma = Some(out.len());
+                out.push(',');
+                i += 1;
+            }
+            (ch, _) => {
+                if !ch.is_whitespace() {
+                    if let (Some(comma), '}' | ']') = (pending_comma, ch) {
+                        out.remove(comma);
+                    }
+                    pending_comma = None;
+                }
+                out.push(ch);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+fn merged_file_message(file: &PlannedFile, what: &str, dry_run: bool) -> String {
+    let path = file.path.display();
+    match (file.action(), dry_run) {
+        (FileAction::Unchanged, _) => format!("{} in {} is up to date", what, path),
+        (FileAction::Create, true) => format!("Would create {} with the {}", path, what),
+        (FileAction::Create, false) => format!("Created {} with the {}", path, what),
+        (FileAction::Overwrite, true) => format!("Would add the {} to {}", what, path),
+        (FileAction::Overwrite, false) => format!("Added the {} to {}", what, path),
+    }
+}
+
+/// Changed lines between `old` and `new`, `-` removed and `+` added, from a
+/// longest-common-subsequence alignment. At most `max_lines` are returned,
+/// the last one counting what was left out.
+pub fn short_diff(old: &str, new: &str, max_lines: usize) -> Vec<String> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // lcs[i][j]: length of the common subsequence of old[i..] and new[j..].
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            changes.push(format!("- {}", old[i]).trim_end().to_string());
+            i += 1;
+        } else {
+            changes.push(format!("+ {}", new[j]).trim_end().to_string());
+            j += 1;
+        }
+    }
+
+    if changes.len() > max_lines {
+        let hidden = changes.len() - (max_lines - 1);
+        changes.truncate(max_lines - 1);
+        changes.push(format!("... {} more changed lines", hidden));
+    }
+    changes
+}
+
+/// What `uninstall_skills` removed, and what it expected but didn't find.
+#[derive(Debug, Default)]
+pub struct UninstallReport {
+    /// Files deleted.
+    pub removed: Vec<PathBuf>,
+    pub skipped: Vec<PathBuf>,
+    /// Skill files changed since they were installed, left in place.
+    pub kept: Vec<PathBuf>,
+    /// Whether CLAUDE.md had our section, whether or not the file was kept.
+    pub section_removed: bool,
+}
+
+/// Undo `install_skills`: remove our skill files (other files in
+/// `.claude/commands` are left alone) and our CLAUDE.md section. CLAUDE.md
+/// itself is deleted only when nothing but the heading we created is left.
+/// With a manifest, exactly the recorded skill files are removed, wherever
+/// they were installed, unless they were changed since.
+pub fn uninstall_skills(target: &Path) -> Result<UninstallReport> {
+    let mut report = UninstallReport::default();
+
+    let manifest = InstallManifest::load(target)?;
+    let skill_files = match &manifest {
+        Some(manifest) => manifest.paths(target),
+        None => {
+            let commands_dir = target.join(".claude").join("commands");
+            available_skills()
+                .iter()
+                .map(|skill| commands_dir.join(skill.filename()))
+                .collect()
+        }
+    };
+    for path in skill_files {
+        // The bundled version written next to a file that was kept.
+        let mut new_file = path.clone().into_os_string();
+        new_file.push(".new");
+        let new_file = PathBuf::from(new_file);
+
+        match read_if_exists(&path)? {
+            None => report.skipped.push(path),
+            Some(content)
+                if manifest
+                    .as_ref()
+                    .is_some_and(|manifest| !manifest.is_installed(target, &path, &content)) =>
+            {
+                report.kept.push(path)
+            }
+            Some(_) => {
+                std::fs::remove_file(&path)?;
+                report.removed.push(path);
+            }
+        }
+        if new_file.is_file() {
+            std::fs::remove_file(&new_file)?;
+            report.removed.push(new_file);
+        }
+    }
+    if manifest.is_some() {
+        std::fs::remove_file(target.join(MANIFEST_FILE))?;
+    }
+
+    let claude_md = target.join("CLAUDE.md");
+    match read_if_exists(&claude_md)?
+        .as_deref()
+        .and_then(strip_claude_md_section)
+    {
+        Some(rest) if rest.trim().is_empty() || rest.trim() == CLAUDE_MD_HEADING => {
+            std::fs::remove_file(&claude_md)?;
+            report.removed.push(claude_md.clone());
+            report.section_removed = true;
+        }
+        Some(rest) => {
+            std::fs::write(&claude_md, rest)?;
+            report.section_removed = true;
+        }
+        None => report.skipped.push(claude_md.clone()),
+    }
+
+    for path in &report.removed {
+        eprintln!("Removed {}", path.display());
+    }
+    if report.section_removed && claude_md.exists() {
+        eprintln!("Removed rust-analyzer section from {}", claude_md.display());
+    }
+    for path in &report.skipped {
+        eprintln!("Skipped {} (nothing to remove)", path.display());
+    }
+    for path in &report.kept {
+        eprintln!("Kept {} (changed since it was installed)", path.display());
+    }
+    Ok(report)
+}
+
+/// `content` without our section and the blank line separating it, or
+/// `None` if it has no section.
+pub fn strip_claude_md_section(content: &str) -> Option<String> {
+    let start = content.find(CLAUDE_MD_SECTION_MARKER)?;
+    let end = content[start..]
+        .find(CLAUDE_MD_SECTION_END)
+        .map_or(content.len(), |i| start + i + CLAUDE_MD_SECTION_END.len());
+
+    let before = content[..start].trim_end();
+    let after = content[end..].trim_start_matches(['\r', '\n']);
+    let mut stripped = before.to_string();
+    if !after.is_empty() {
+        if !stripped.is_empty() {
+            stripped.push_str("\n\n");
+        }
+        stripped.push_str(after);
+    } else if !stripped.is_empty() {
+        stripped.push('\n');
+    }
+    Some(stripped)
+}
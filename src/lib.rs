@@ -1,9 +1,16 @@
+pub mod client;
 pub mod config;
+pub mod daemon;
 pub mod diagnostics;
+pub mod discovery;
+pub mod doctor;
 pub mod http;
 pub mod install;
+pub mod logging;
 pub mod lsp;
 pub mod mcp;
+pub mod prewarm;
 pub mod protocol;
+pub mod watcher;
 
 pub use mcp::RustAnalyzerMCPServer;
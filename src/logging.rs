@@ -0,0 +1,146 @@
+use anyhow::{anyhow, Result};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// Log file in use, reported by `/api/v1/status`.
+static ACTIVE_LOG_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+pub fn active_log_file() -> Option<&'static Path> {
+    ACTIVE_LOG_FILE.get().map(PathBuf::as_path)
+}
+
+#[derive(Debug, Clone)]
+pub struct LogOptions {
+    /// Write logs to this file (rotated by size) instead of stderr.
+    pub file: Option<PathBuf>,
+    /// Also log to stderr when a file is set.
+    pub stderr: bool,
+    /// Filter such as `debug` or `rust_analyzer_server=trace`; overrides `RUST_LOG`.
+    pub level: Option<String>,
+    pub max_size_mb: u64,
+    /// Rotated files kept besides the active one (`.1` is the newest).
+    pub max_files: usize,
+}
+
+/// Install the global logger.
+pub fn init(options: &LogOptions) -> Result<()> {
+    let mut builder = match &options.level {
+        Some(level) => {
+            let mut builder = env_logger::Builder::new();
+            builder.parse_filters(level);
+            builder
+        }
+        None => env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")),
+    };
+
+    if let Some(path) = &options.file {
+        let file = RotatingFile::open(
+            path.clone(),
+            options.max_size_mb.saturating_mul(1024 * 1024),
+            options.max_files,
+        )
+        .map_err(|e| anyhow!("Failed to open log file {}: {}", path.display(), e))?;
+        let target: Box<dyn Write + Send> = if options.stderr {
+            Box::new(Tee(file))
+        } else {
+            Box::new(file)
+        };
+        builder.target(env_logger::Target::Pipe(target));
+        let _ = ACTIVE_LOG_FILE.set(path.clone());
+    }
+
+    builder
+        .try_init()
+        .map_err(|e| anyhow!("Failed to initialize logging: {}", e))
+}
+
+/// Append-only log file rotated once it grows past `max_size`.
+///
+/// Rotation copies the file to `<path>.1` and truncates it in place rather than
+/// renaming, so a daemon's stderr (redirected to the same file) keeps landing
+/// in the active log.
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl RotatingFile {
+    /// Open `path` for appending; `max_size` is in bytes (0 disables rotation).
+    pub fn open(path: PathBuf, max_size: u64, max_files: usize) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_size,
+            max_files,
+        })
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.max_files == 0 {
+            self.file.set_len(0)?;
+        } else {
+            let _ = std::fs::remove_file(self.rotated(self.max_files));
+            for n in (1..self.max_files).rev() {
+                let from = self.rotated(n);
+                if from.exists() {
+                    std::fs::rename(&from, self.rotated(n + 1))?;
+                }
+            }
+            std::fs::copy(&self.path, self.rotated(1))?;
+            self.file.set_len(0)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.max_size > 0 && self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            // Keep logging even if rotation fails (e.g. the directory became read-only).
+            let _ = self.rotate();
+        }
+        let n = self.file.write(buf)?;
+        self.size += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Writes each record to the log file and to stderr.
+struct Tee(RotatingFile);
+
+impl Write for Tee {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let _ = io::stderr().write_all(buf);
+        self.0.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()?;
+        self.0.flush()
+    }
+}
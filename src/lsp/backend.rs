@@ -0,0 +1,226 @@
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use serde_json::{json, Value};
+use std::{collections::HashMap, future::Future, pin::Pin, sync::Arc, time::Duration};
+use tokio::{
+    process::Child,
+    sync::{oneshot, Mutex},
+};
+
+use super::{
+    client::RequestTimeout,
+    connection::{write_message, SharedStdin},
+};
+use crate::protocol::lsp::LSPRequest;
+
+pub type BackendFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// What `RustAnalyzerClient` sends a language server through: requests,
+/// notifications, and the process's lifecycle. `ProcessBackend` talks to a
+/// rust-analyzer process; tests substitute canned responses.
+pub trait LspBackend: Send + Sync {
+    /// Send a request and wait up to `timeout` for its result. A timeout is
+    /// a `RequestTimeout` error. The future doesn't borrow the backend, so a
+    /// caller can let go of the client while waiting.
+    fn request(
+        &mut self,
+        method: &str,
+        params: Option<Value>,
+        timeout: Duration,
+    ) -> BackendFuture<'static, Value>;
+
+    fn notify<'a>(&'a mut self, method: &'a str, params: Option<Value>) -> BackendFuture<'a, ()>;
+
+    /// Whether the server is still running.
+    fn is_alive(&mut self) -> bool;
+
+    /// Stop the server, after `shutdown` and `exit` were sent.
+    fn kill(&mut self) -> BackendFuture<'_, ()>;
+}
+
+pub(super) type PendingRequests = Arc<Mutex<HashMap<u64, oneshot::Sender<Value>>>>;
+
+/// A rust-analyzer child process, spoken to over stdin. Responses arrive
+/// through `pending_requests`, filled in by the connection's stdout handler.
+pub(super) struct ProcessBackend {
+    pub process: Child,
+    pub stdin: SharedStdin,
+    pub pending_requests: PendingRequests,
+    /// Ids of requests whose caller gave up and whose `$/cancelRequest`
+    /// hasn't been written yet.
+    pub abandoned_requests: Arc<std::sync::Mutex<Vec<u64>>>,
+    pub next_id: u64,
+}
+
+/// Tracks an in-flight request. If dropped before the response arrives (the
+/// HTTP caller disconnected, or the request timed out), `$/cancelRequest` is
+/// sent right away from a spawned task so rust-analyzer stops working on it.
+/// Without a runtime to spawn on, the id stays queued and the cancel goes out
+/// before the next message.
+struct InFlightRequest {
+    id: u64,
+    channel: RequestChannel,
+    completed: bool,
+}
+
+impl Drop for InFlightRequest {
+    fn drop(&mut self) {
+        if self.completed {
+            return;
+        }
+        info!("Abandoning LSP request {}", self.id);
+        if let Ok(mut abandoned) = self.channel.abandoned_requests.lock() {
+            abandoned.push(self.id);
+        }
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let channel = self.channel.clone();
+            runtime.spawn(async move {
+                if let Err(e) = channel.cancel_abandoned_requests().await {
+                    warn!("Failed to send $/cancelRequest: {}", e);
+                }
+            });
+        }
+    }
+}
+
+/// What a request needs from `ProcessBackend` once its id is assigned, so
+/// the request can run without borrowing the backend.
+#[derive(Clone)]
+struct RequestChannel {
+    stdin: SharedStdin,
+    pending_requests: PendingRequests,
+    abandoned_requests: Arc<std::sync::Mutex<Vec<u64>>>,
+}
+
+impl RequestChannel {
+    async fn write_message(&self, content: &str) -> Result<()> {
+        write_message(&self.stdin, content).await
+    }
+
+    /// Send `$/cancelRequest` for every request whose caller went away.
+    async fn cancel_abandoned_requests(&self) -> Result<()> {
+        let abandoned: Vec<u64> = match self.abandoned_requests.lock() {
+            Ok(mut abandoned) => abandoned.drain(..).collect(),
+            Err(_) => return Ok(()),
+        };
+        if abandoned.is_empty() {
+            return Ok(());
+        }
+
+        let mut pending = self.pending_requests.lock().await;
+        for id in &abandoned {
+            pending.remove(id);
+        }
+        drop(pending);
+
+        for id in abandoned {
+            info!("Sending $/cancelRequest for request {}", id);
+            let cancel = json!({
+                "jsonrpc": "2.0",
+                "method": "$/cancelRequest",
+                "params": { "id": id }
+            });
+            self.write_message(&serde_json::to_string(&cancel)?).await?;
+        }
+        Ok(())
+    }
+
+    async fn send_request(
+        self,
+        id: u64,
+        method: String,
+        params: Option<Value>,
+        timeout: Duration,
+    ) -> Result<Value> {
+        info!("Sending LSP request: {} with params: {:?}", method, params);
+
+        let request = LSPRequest {
+            jsonrpc: "2.0".to_string(),
+            id,
+            method: method.clone(),
+            params,
+        };
+
+        self.cancel_abandoned_requests().await?;
+
+        // Set up the response channel before writing so a fast response can't be missed.
+        let (tx, rx) = oneshot::channel();
+        self.pending_requests.lock().await.insert(id, tx);
+        let mut in_flight = InFlightRequest {
+            id,
+            channel: self.clone(),
+            completed: false,
+        };
+
+        self.write_message(&serde_json::to_string(&request)?)
+            .await?;
+
+        // Wait for response with timeout; dropping `in_flight` on timeout
+        // sends the cancel.
+        let response = match tokio::time::timeout(timeout, rx).await {
+            Ok(response) => response.map_err(|_| anyhow!("Request cancelled"))?,
+            Err(_) => return Err(RequestTimeout { method, timeout }.into()),
+        };
+        in_flight.completed = true;
+        Ok(response)
+    }
+}
+
+impl ProcessBackend {
+    fn channel(&self) -> RequestChannel {
+        RequestChannel {
+            stdin: Arc::clone(&self.stdin),
+            pending_requests: Arc::clone(&self.pending_requests),
+            abandoned_requests: Arc::clone(&self.abandoned_requests),
+        }
+    }
+
+    async fn send_notification(&mut self, method: &str, params: Option<Value>) -> Result<()> {
+        let notification = json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params.unwrap_or(json!({}))
+        });
+
+        info!("Sending LSP notification: {}", method);
+
+        let channel = self.channel();
+        channel.cancel_abandoned_requests().await?;
+        channel
+            .write_message(&serde_json::to_string(&notification)?)
+            .await
+    }
+}
+
+impl LspBackend for ProcessBackend {
+    fn request(
+        &mut self,
+        method: &str,
+        params: Option<Value>,
+        timeout: Duration,
+    ) -> BackendFuture<'static, Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+        Box::pin(
+            self.channel()
+                .send_request(id, method.to_string(), params, timeout),
+        )
+    }
+
+    fn notify<'a>(&'a mut self, method: &'a str, params: Option<Value>) -> BackendFuture<'a, ()> {
+        Box::pin(self.send_notification(method, params))
+    }
+
+    fn is_alive(&mut self) -> bool {
+        matches!(self.process.try_wait(), Ok(None))
+    }
+
+    fn kill(&mut self) -> BackendFuture<'_, ()> {
+        Box::pin(async move {
+            // Kill the process and wait for it to actually exit.
+            let _ = self.process.kill().await;
+            let _ = self.process.wait().await;
+            Ok(())
+        })
+    }
+}
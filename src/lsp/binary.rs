@@ -0,0 +1,156 @@
+use anyhow::{anyhow, Result};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use super::paths::cargo_home;
+
+/// Where the rust-analyzer executable was found. Discovery tries these in
+/// order: an explicit path (`--rust-analyzer-path`, `RUST_ANALYZER_BIN`, or
+/// `rust_analyzer_path` in the config file), `~/.cargo/bin`, the active
+/// rustup toolchain's component, and finally PATH.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinarySource {
+    Configured,
+    CargoBin,
+    Rustup,
+    Path,
+}
+
+impl fmt::Display for BinarySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BinarySource::Configured => "configured",
+            BinarySource::CargoBin => "~/.cargo/bin",
+            BinarySource::Rustup => "rustup component",
+            BinarySource::Path => "PATH",
+        })
+    }
+}
+
+/// The rust-analyzer executable the server will spawn.
+#[derive(Debug, Clone)]
+pub struct RustAnalyzerBinary {
+    pub path: PathBuf,
+    pub source: BinarySource,
+    /// First line of `--version`, when it runs.
+    pub version: Option<String>,
+}
+
+impl fmt::Display for RustAnalyzerBinary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.version {
+            Some(version) => write!(f, "{} at {}", version, self.path.display())?,
+            None => write!(f, "{}", self.path.display())?,
+        }
+        write!(f, " (from {})", self.source)
+    }
+}
+
+/// Run `program --version` and return its first line of output.
+pub(crate) fn version_of(program: &Path) -> Result<String, String> {
+    let output = Command::new(program)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr
+            .lines()
+            .next()
+            .unwrap_or("exited with an error")
+            .to_string());
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout.lines().next().unwrap_or_default().trim().to_string())
+}
+
+/// Finds a discovery candidate, or says why there is none.
+type Candidate = fn() -> Result<PathBuf, String>;
+
+fn cargo_bin_candidate() -> Result<PathBuf, String> {
+    let cargo_home = cargo_home().ok_or("HOME is not set")?;
+    Ok(cargo_home.join("bin/rust-analyzer"))
+}
+
+fn path_candidate() -> Result<PathBuf, String> {
+    which::which("rust-analyzer").map_err(|e| e.to_string())
+}
+
+fn rustup_candidate() -> Result<PathBuf, String> {
+    let output = Command::new("rustup")
+        .args(["which", "rust-analyzer"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err("component not installed".to_string());
+    }
+    let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok(PathBuf::from(path))
+}
+
+/// Locate rust-analyzer. An explicit path (a file or a name on PATH) is used
+/// as-is; otherwise the first discovered candidate that actually runs wins,
+/// which skips rustup proxies for toolchains without the component.
+pub fn find_rust_analyzer(configured: Option<&Path>) -> Result<RustAnalyzerBinary> {
+    if let Some(path) = configured {
+        let path = which::which(path).map_err(|e| {
+            anyhow!(
+                "Configured rust-analyzer {} is not usable: {}",
+                path.display(),
+                e
+            )
+        })?;
+        return Ok(RustAnalyzerBinary {
+            version: version_of(&path).ok().filter(|v| !v.is_empty()),
+            path,
+            source: BinarySource::Configured,
+        });
+    }
+
+    let candidates: [(BinarySource, Candidate); 3] = [
+        (BinarySource::CargoBin, cargo_bin_candidate),
+        (BinarySource::Rustup, rustup_candidate),
+        (BinarySource::Path, path_candidate),
+    ];
+
+    let mut tried = Vec::new();
+    for (source, candidate) in candidates {
+        let path = match candidate() {
+            Ok(path) if path.is_file() => path,
+            Ok(path) => {
+                tried.push(format!("{}: {} does not exist", source, path.display()));
+                continue;
+            }
+            Err(e) => {
+                tried.push(format!("{}: {}", source, e));
+                continue;
+            }
+        };
+        match version_of(&path) {
+            Ok(version) => {
+                return Ok(RustAnalyzerBinary {
+                    path,
+                    source,
+                    version: Some(version).filter(|v| !v.is_empty()),
+                })
+            }
+            Err(e) => tried.push(format!(
+                "{}: {} does not run ({})",
+                source,
+                path.display(),
+                e
+            )),
+        }
+    }
+
+    Err(anyhow!(
+        "Failed to find a working rust-analyzer ({}). Install it with `rustup component add rust-analyzer` or pass --rust-analyzer-path.",
+        tried.join("; ")
+    ))
+}
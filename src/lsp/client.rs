@@ -1,40 +1,157 @@
 use anyhow::{anyhow, Result};
-use log::info;
+use log::{debug, info};
 use serde_json::{json, Value};
 use std::{
-    collections::{HashMap, HashSet},
-    path::PathBuf,
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
     process::Stdio,
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
-use tokio::{
-    io::{AsyncWriteExt, BufWriter},
-    process::{Child, Command},
-    sync::{oneshot, Mutex},
+use tokio::{io::BufWriter, process::Command, sync::Mutex};
+
+use crate::config::{
+    document_ready_deadline, merge_json, timeout_key, Config, CONFIG_FILE_NAME,
+    DOCUMENT_READY_POLL_MILLIS,
 };
 
-use crate::{
-    config::{DOCUMENT_OPEN_DELAY_MILLIS, LSP_REQUEST_TIMEOUT_SECS},
-    protocol::lsp::LSPRequest,
+use super::{
+    backend::{BackendFuture, LspBackend, ProcessBackend},
+    binary::find_rust_analyzer,
+    connection::Handlers,
+    diagnostic_store::{DiagnosticStore, DiagnosticStoreStats, SharedDiagnostics},
+    messages::{new_shared_messages, SharedMessages},
+    paths::{file_uri, language_id, uri_path, PathStyle},
+    position::{DocumentColumns, PositionEncoding},
+    progress::{new_shared_progress, SharedProgress},
+    registrations::SharedRegistrations,
 };
 
-use super::progress::{new_shared_progress, SharedProgress};
+/// An LSP request got no response within its timeout. Tools give up on this
+/// instead of retrying, since the full timeout has already been spent.
+#[derive(Debug)]
+pub struct RequestTimeout {
+    pub method: String,
+    pub timeout: Duration,
+}
+
+impl std::fmt::Display for RequestTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} timed out after {:?} (raise timeouts.{} in {} or pass timeout_secs)",
+            self.method,
+            self.timeout,
+            timeout_key(&self.method),
+            CONFIG_FILE_NAME
+        )
+    }
+}
+
+impl std::error::Error for RequestTimeout {}
 
 pub struct RustAnalyzerClient {
-    pub(super) process: Option<Child>,
-    pub(super) request_id: Arc<Mutex<u64>>,
+    /// The language server, once started.
+    pub(super) backend: Option<Box<dyn LspBackend>>,
     pub(super) workspace_root: PathBuf,
-    pub(super) stdin: Option<BufWriter<tokio::process::ChildStdin>>,
-    pub(super) pending_requests: Arc<Mutex<HashMap<u64, oneshot::Sender<Value>>>>,
     pub(super) initialized: bool,
-    pub(super) open_documents: Arc<Mutex<HashSet<String>>>,
-    pub(super) diagnostics: Arc<Mutex<HashMap<String, Vec<Value>>>>,
+    pub(super) open_documents: Arc<Mutex<HashMap<String, OpenDocument>>>,
+    /// Open documents held open by a `PinnedDocuments`, with how many hold
+    /// each.
+    pinned_documents: Arc<std::sync::Mutex<HashMap<String, usize>>>,
+    pub(super) diagnostics: SharedDiagnostics,
+    /// Number of `didOpen` notifications sent since the client started.
+    pub(super) documents_opened: u64,
+    /// Number of notifications sent that change files or folders
+    /// rust-analyzer sees, to notice when results computed earlier are stale.
+    pub(super) workspace_changes: u64,
+    /// Column encoding rust-analyzer chose at initialize.
+    pub(super) position_encoding: PositionEncoding,
+    /// `serverInfo` from the initialize result: rust-analyzer's name and
+    /// version.
+    server_info: Option<Value>,
+    /// Whether rust-analyzer takes `searchScope` in `workspace/symbol`
+    /// requests, per its `workspaceSymbolScopeKindFiltering` capability.
+    pub(super) symbol_scope_filtering: bool,
+    /// Timeout from a tool call's `timeout_secs`, which beats the config for
+    /// every request made during that call.
+    pub(crate) timeout_override: Option<Duration>,
+    /// False when the tool call being served would rather fail than wait
+    /// for indexing.
+    pub(crate) wait_for_index: bool,
+    /// How the tool call being served wants file paths written.
+    pub(crate) path_style: PathStyle,
     pub progress: SharedProgress,
+    /// `window/showMessage` and `window/logMessage` notifications.
+    pub messages: SharedMessages,
+    /// Capabilities rust-analyzer registered with `client/registerCapability`.
+    pub registrations: SharedRegistrations,
+    pub(super) config: Arc<Config>,
+}
+
+/// A document rust-analyzer has been sent with `didOpen`.
+pub(super) struct OpenDocument {
+    version: i32,
+    /// Hash of the text last sent, to detect when a resync is needed.
+    content_hash: u64,
+    /// The text last sent, for translating columns.
+    text: String,
+    /// Opened with caller-supplied content rather than the file on disk.
+    is_virtual: bool,
+    /// Last time a tool used the document, for closing the least recently used.
+    last_used: Instant,
+}
+
+/// Documents kept open until this is dropped: closing the least recently
+/// used skips them, even past `max_open_documents`. Dropping it needs no
+/// lock on the server, so a cancelled call still releases them.
+pub struct PinnedDocuments {
+    uris: Vec<String>,
+    pinned: Arc<std::sync::Mutex<HashMap<String, usize>>>,
+}
+
+impl Drop for PinnedDocuments {
+    fn drop(&mut self) {
+        let Ok(mut pinned) = self.pinned.lock() else {
+            return;
+        };
+        for uri in &self.uris {
+            if let Some(count) = pinned.get_mut(uri) {
+                *count -= 1;
+                if *count == 0 {
+                    pinned.remove(uri);
+                }
+            }
+        }
+    }
+}
+
+/// An LSP `WorkspaceFolder`, named after the directory.
+fn workspace_folder(path: &Path) -> Value {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string());
+    json!({ "uri": file_uri(path), "name": name })
+}
+
+/// Notifications after which rust-analyzer may answer differently.
+const WORKSPACE_CHANGE_NOTIFICATIONS: &[&str] = &[
+    "textDocument/didChange",
+    "textDocument/didSave",
+    "workspace/didChangeWatchedFiles",
+    "workspace/didChangeWorkspaceFolders",
+];
+
+pub(crate) fn content_hash(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
 }
 
 impl RustAnalyzerClient {
-    pub fn new(workspace_root: PathBuf) -> Self {
+    pub fn new(workspace_root: PathBuf, config: Arc<Config>) -> Self {
         // Ensure the workspace root is absolute.
         let workspace_root = workspace_root.canonicalize().unwrap_or_else(|_| {
             if workspace_root.is_absolute() {
@@ -47,15 +164,27 @@ impl RustAnalyzerClient {
         });
 
         Self {
-            process: None,
-            request_id: Arc::new(Mutex::new(1)),
+            backend: None,
             workspace_root,
-            stdin: None,
-            pending_requests: Arc::new(Mutex::new(HashMap::new())),
             initialized: false,
-            open_documents: Arc::new(Mutex::new(HashSet::new())),
-            diagnostics: Arc::new(Mutex::new(HashMap::new())),
+            open_documents: Arc::new(Mutex::new(HashMap::new())),
+            pinned_documents: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            diagnostics: Arc::new(Mutex::new(DiagnosticStore::new(
+                config.max_diagnostics_files as usize,
+                (config.max_diagnostics_mb * 1024 * 1024) as usize,
+            ))),
+            documents_opened: 0,
+            workspace_changes: 0,
+            timeout_override: None,
+            wait_for_index: true,
+            path_style: PathStyle::absolute(),
+            position_encoding: PositionEncoding::default(),
+            server_info: None,
+            symbol_scope_filtering: false,
             progress: new_shared_progress(),
+            messages: new_shared_messages(),
+            registrations: SharedRegistrations::default(),
+            config,
         }
     }
 
@@ -65,14 +194,11 @@ impl RustAnalyzerClient {
             self.workspace_root.display()
         );
 
-        // Clear any existing diagnostics from previous sessions.
-        self.diagnostics.lock().await.clear();
-
-        // Find rust-analyzer executable.
-        let rust_analyzer_path = find_rust_analyzer()?;
-        info!("Using rust-analyzer at: {}", rust_analyzer_path.display());
+        // Find rust-analyzer executable
+        let binary = find_rust_analyzer(self.config.rust_analyzer_path.as_deref())?;
+        info!("Using rust-analyzer {}", binary);
 
-        let mut cmd = Command::new(rust_analyzer_path);
+        let mut cmd = Command::new(&binary.path);
         cmd.current_dir(&self.workspace_root)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
@@ -89,9 +215,13 @@ impl RustAnalyzerClient {
             cmd.env("TMPDIR", tmpdir);
         }
 
-        let mut child = cmd
-            .spawn()
-            .map_err(|e| anyhow!("Failed to start rust-analyzer: {}", e))?;
+        let mut child = cmd.spawn().map_err(|e| {
+            anyhow!(
+                "Failed to start rust-analyzer at {}: {}",
+                binary.path.display(),
+                e
+            )
+        })?;
 
         let stdin = child
             .stdin
@@ -106,18 +236,38 @@ impl RustAnalyzerClient {
             .take()
             .ok_or_else(|| anyhow!("Failed to get stderr"))?;
 
-        self.stdin = Some(BufWriter::new(stdin));
+        let backend = ProcessBackend {
+            process: child,
+            stdin: Arc::new(Mutex::new(Some(BufWriter::new(stdin)))),
+            pending_requests: Arc::new(Mutex::new(HashMap::new())),
+            abandoned_requests: Arc::new(std::sync::Mutex::new(Vec::new())),
+            next_id: 1,
+        };
 
         // Start connection handlers.
         super::connection::start_handlers(
             stdout,
             stderr,
-            Arc::clone(&self.pending_requests),
-            Arc::clone(&self.diagnostics),
-            Arc::clone(&self.progress),
+            Handlers {
+                pending_requests: Arc::clone(&backend.pending_requests),
+                diagnostics: Arc::clone(&self.diagnostics),
+                progress: Arc::clone(&self.progress),
+                messages: Arc::clone(&self.messages),
+                registrations: Arc::clone(&self.registrations),
+                stdin: Arc::clone(&backend.stdin),
+            },
         );
 
-        self.process = Some(child);
+        self.start_with_backend(Box::new(backend)).await
+    }
+
+    /// Initialize a language server other than a rust-analyzer process, such
+    /// as a mock in tests.
+    pub async fn start_with_backend(&mut self, backend: Box<dyn LspBackend>) -> Result<()> {
+        // Clear any state from previous sessions.
+        self.diagnostics.lock().await.clear();
+        self.registrations.lock().await.clear();
+        self.backend = Some(backend);
 
         // Initialize LSP.
         self.initialize().await?;
@@ -126,13 +276,7 @@ impl RustAnalyzerClient {
         // Send workspace/didChangeConfiguration to ensure settings are applied.
         let config_params = json!({
             "settings": {
-                "rust-analyzer": {
-                    "checkOnSave": {
-                        "enable": true,
-                        "command": "check",
-                        "allTargets": true
-                    }
-                }
+                "rust-analyzer": self.config.check.initialization_options()
             }
         });
         let _ = self
@@ -148,24 +292,10 @@ impl RustAnalyzerClient {
         method: &str,
         params: Option<Value>,
     ) -> Result<()> {
-        let notification = json!({
-            "jsonrpc": "2.0",
-            "method": method,
-            "params": params.unwrap_or(json!({}))
-        });
-
-        let content = serde_json::to_string(&notification)?;
-        let message = format!("Content-Length: {}\r\n\r\n{}", content.len(), content);
-
-        info!("Sending LSP notification: {}", method);
-
-        let Some(stdin) = &mut self.stdin else {
-            return Err(anyhow!("No stdin available"));
-        };
-
-        stdin.write_all(message.as_bytes()).await?;
-        stdin.flush().await?;
-        Ok(())
+        if WORKSPACE_CHANGE_NOTIFICATIONS.contains(&method) {
+            self.workspace_changes += 1;
+        }
+        self.backend()?.notify(method, params).await
     }
 
     pub(super) async fn send_request(
@@ -173,65 +303,60 @@ impl RustAnalyzerClient {
         method: &str,
         params: Option<Value>,
     ) -> Result<Value> {
-        let mut request_id_lock = self.request_id.lock().await;
-        let id = *request_id_lock;
-        *request_id_lock += 1;
-        drop(request_id_lock);
-
-        let request = LSPRequest {
-            jsonrpc: "2.0".to_string(),
-            id,
-            method: method.to_string(),
-            params: params.clone(),
-        };
-
-        let content = serde_json::to_string(&request)?;
-        let message = format!("Content-Length: {}\r\n\r\n{}", content.len(), content);
-
-        info!("Sending LSP request: {} with params: {:?}", method, params);
+        let timeout = self.request_timeout_for(method);
+        self.send_request_with_timeout(method, params, timeout)
+            .await
+    }
 
-        let Some(stdin) = &mut self.stdin else {
-            return Err(anyhow!("No stdin available"));
-        };
+    /// The tool call's `timeout_secs` if it gave one, otherwise the
+    /// configured timeout for `method`.
+    fn request_timeout_for(&self, method: &str) -> Duration {
+        self.timeout_override
+            .unwrap_or_else(|| self.config.request_timeout_for(method))
+    }
 
-        stdin.write_all(message.as_bytes()).await?;
-        stdin.flush().await?;
+    pub(super) async fn send_request_with_timeout(
+        &mut self,
+        method: &str,
+        params: Option<Value>,
+        timeout: Duration,
+    ) -> Result<Value> {
+        self.backend()?.request(method, params, timeout).await
+    }
 
-        // Set up response channel.
-        let (tx, rx) = oneshot::channel();
-        self.pending_requests.lock().await.insert(id, tx);
+    /// Send a request and return its result as a future that doesn't borrow
+    /// the client, so the caller can release the server lock while waiting.
+    pub fn request_detached(
+        &mut self,
+        method: &str,
+        params: Option<Value>,
+    ) -> Result<BackendFuture<'static, Value>> {
+        let timeout = self.request_timeout_for(method);
+        Ok(self.backend()?.request(method, params, timeout))
+    }
 
-        // Wait for response with timeout.
-        tokio::time::timeout(Duration::from_secs(LSP_REQUEST_TIMEOUT_SECS), rx)
-            .await
-            .map_err(|_| anyhow!("Request timeout"))?
-            .map_err(|_| anyhow!("Request cancelled"))
+    fn backend(&mut self) -> Result<&mut Box<dyn LspBackend>> {
+        self.backend
+            .as_mut()
+            .ok_or_else(|| anyhow!("rust-analyzer is not running"))
     }
 
     async fn initialize(&mut self) -> Result<()> {
-        let init_params = json!({
+        let workspace_folders: Vec<Value> = std::iter::once(&self.workspace_root)
+            .chain(&self.config.extra_workspaces)
+            .map(|folder| workspace_folder(folder))
+            .collect();
+        let mut init_params = json!({
             "processId": std::process::id(),
-            "rootUri": format!("file://{}", self.workspace_root.display()),
+            "rootUri": file_uri(&self.workspace_root),
+            "workspaceFolders": workspace_folders,
             "initializationOptions": {
-                "cargo": {
-                    "buildScripts": {
-                        "enable": true
-                    }
-                },
-                "checkOnSave": {
-                    "enable": true,
-                    "command": "check",
-                    "allTargets": true
-                },
                 "diagnostics": {
                     "enable": true,
                     "experimental": {
                         "enable": true
                     }
                 },
-                "procMacro": {
-                    "enable": true
-                },
                 "hover": {
                     "documentation": {
                         "enable": true
@@ -242,13 +367,26 @@ impl RustAnalyzerClient {
                 }
             },
             "capabilities": {
+                // Byte columns need no surrogate counting; UTF-16 is the
+                // protocol default for servers without the choice.
+                "general": {
+                    "positionEncodings": ["utf-8", "utf-16"]
+                },
                 "textDocument": {
                     "hover": {
                         "contentFormat": ["markdown", "plaintext"]
                     },
+                    // Snippets and markdown are turned into plain text by
+                    // `simplify_completion_item`.
+                    // Resolving the import edits lazily is what makes
+                    // rust-analyzer offer items that aren't in scope yet.
                     "completion": {
                         "completionItem": {
-                            "snippetSupport": true
+                            "snippetSupport": true,
+                            "documentationFormat": ["markdown", "plaintext"],
+                            "resolveSupport": {
+                                "properties": ["additionalTextEdits"]
+                            }
                         }
                     },
                     "definition": {
@@ -272,6 +410,20 @@ impl RustAnalyzerClient {
                         },
                         "resolveSupport": {
                             "properties": ["edit"]
+                        },
+                        "dataSupport": true
+                    },
+                    // Hints are reduced to position, label, and kind, so the
+                    // rest can be left for a resolve that never comes.
+                    "inlayHint": {
+                        "resolveSupport": {
+                            "properties": [
+                                "tooltip",
+                                "textEdits",
+                                "label.tooltip",
+                                "label.location",
+                                "label.command"
+                            ]
                         }
                     },
                     "publishDiagnostics": {
@@ -282,15 +434,49 @@ impl RustAnalyzerClient {
                     },
                     "formatting": {}
                 },
+                // Without it rust-analyzer reports no progress, so indexing
+                // and cargo check runs go unseen.
+                "window": {
+                    "workDoneProgress": true
+                },
                 "workspace": {
+                    "workspaceFolders": true,
+                    // rust-analyzer watches files itself unless the client
+                    // can take watcher registrations, which needs `--watch`.
+                    "didChangeWatchedFiles": {
+                        "dynamicRegistration": self.config.watch,
+                        "relativePatternSupport": true
+                    },
                     "didChangeConfiguration": {
                         "dynamicRegistration": false
+                    },
+                    "workspaceEdit": {
+                        "documentChanges": true
                     }
                 }
             }
         });
+        merge_json(
+            &mut init_params["initializationOptions"],
+            &self.config.check.initialization_options(),
+        );
+        merge_json(
+            &mut init_params["initializationOptions"],
+            &self.config.cargo.initialization_options(),
+        );
+        if let Some(options) = &self.config.initialization_options {
+            merge_json(&mut init_params["initializationOptions"], options);
+        }
 
-        self.send_request("initialize", Some(init_params)).await?;
+        let result = self.send_request("initialize", Some(init_params)).await?;
+        self.position_encoding =
+            PositionEncoding::from_lsp(result["capabilities"]["positionEncoding"].as_str());
+        info!("Position encoding: {}", self.position_encoding);
+        self.server_info = result.get("serverInfo").cloned();
+        self.symbol_scope_filtering = result["capabilities"]["experimental"]
+            ["workspaceSymbolScopeKindFiltering"]
+            .as_bool()
+            .unwrap_or(false);
         self.send_notification("initialized", Some(json!({})))
             .await?;
 
@@ -302,62 +488,416 @@ impl RustAnalyzerClient {
         Ok(())
     }
 
-    pub async fn open_document(&mut self, uri: &str, content: &str) -> Result<()> {
-        // Check if document is already open.
-        {
-            let open_docs = self.open_documents.lock().await;
-            if open_docs.contains(uri) {
-                info!("Document already open: {}", uri);
-                return Ok(());
+    /// Open `uri` with `content`, or send the new text with `didChange` if the
+    /// document is already open with different content. `is_virtual` marks
+    /// content passed by the caller instead of read from disk, so the next
+    /// call without it resyncs from the file.
+    pub async fn open_document(
+        &mut self,
+        uri: &str,
+        content: &str,
+        is_virtual: bool,
+    ) -> Result<()> {
+        let ended_before = self.progress.lock().await.ended_count();
+        if !self.sync_document(uri, content, is_virtual).await? {
+            return Ok(());
+        }
+
+        // Note: didSave removed to speed up hover/definition/references.
+        // cargo check is triggered separately via diagnostics tool.
+        // This allows faster response times for navigation features.
+
+        // Give rust-analyzer time to process the document.
+        if self.config.wait_for_document_ready {
+            self.wait_for_document_ready(uri, content.len(), ended_before)
+                .await;
+        } else {
+            tokio::time::sleep(self.config.document_open_delay()).await;
+        }
+
+        Ok(())
+    }
+
+    /// Like `open_document` for a file read from disk, without waiting for
+    /// rust-analyzer to process it.
+    pub async fn send_document(&mut self, uri: &str, content: &str) -> Result<()> {
+        self.sync_document(uri, content, false).await?;
+        Ok(())
+    }
+
+    /// Wait until rust-analyzer has processed a document just sent to it:
+    /// until it publishes diagnostics for `uri`, a progress task ends, or a
+    /// deadline scaled by the document's size passes.
+    async fn wait_for_document_ready(&self, uri: &str, len: usize, ended_before: u64) {
+        let deadline = document_ready_deadline(len);
+        let start = Instant::now();
+        while start.elapsed() < deadline {
+            if self.diagnostics.lock().await.get(uri).is_some() {
+                debug!(
+                    "{} ready after {:?}: diagnostics published",
+                    uri,
+                    start.elapsed()
+                );
+                return;
+            }
+            if self.progress.lock().await.ended_count() > ended_before {
+                debug!("{} ready after {:?}: progress ended", uri, start.elapsed());
+                return;
             }
+            tokio::time::sleep(Duration::from_millis(DOCUMENT_READY_POLL_MILLIS)).await;
         }
+        debug!(
+            "{} not acknowledged within {:?}; going ahead",
+            uri, deadline
+        );
+    }
+
+    /// Send `didOpen` or `didChange` unless rust-analyzer already has this
+    /// exact content, and start cargo check after a change read from disk.
+    /// Returns whether anything was sent.
+    async fn sync_document(&mut self, uri: &str, content: &str, is_virtual: bool) -> Result<bool> {
+        let hash = content_hash(content);
 
-        // Clear any existing diagnostics for this URI to ensure fresh data.
+        // Check if document is already open, and whether its text changed.
+        let version = {
+            let mut open_docs = self.open_documents.lock().await;
+            match open_docs.get_mut(uri) {
+                Some(doc) if doc.content_hash == hash => {
+                    info!("Document already open: {}", uri);
+                    doc.is_virtual = is_virtual;
+                    doc.last_used = Instant::now();
+                    return Ok(false);
+                }
+                Some(doc) => Some(doc.version + 1),
+                None => None,
+            }
+        };
+
+        // Clear any existing diagnostics for this URI to ensure fresh data,
+        // and keep the ones about to be published while it stays open.
         {
             let mut diag_lock = self.diagnostics.lock().await;
             diag_lock.remove(uri);
+            diag_lock.pin(uri);
         }
 
-        info!("Opening document: {}", uri);
-        let params = json!({
-            "textDocument": {
-                "uri": uri,
-                "languageId": "rust",
-                "version": 1,
-                "text": content
+        let version = match version {
+            Some(version) => {
+                let source = if is_virtual {
+                    "unsaved content"
+                } else {
+                    "disk"
+                };
+                info!("Updating document from {}: {} (v{})", source, uri, version);
+                self.send_did_change(uri, content, version).await?;
+                // The file changed on disk since rust-analyzer last saw it,
+                // and cargo check's diagnostics of the old text stay until
+                // the next check. Not `didSave`: rust-analyzer drops the
+                // check it triggers when indexing cancels its crate lookup.
+                if !is_virtual {
+                    self.run_flycheck().await?;
+                }
+                version
             }
-        });
-
-        self.send_notification("textDocument/didOpen", Some(params.clone()))
-            .await?;
+            None => {
+                info!("Opening document: {}", uri);
+                let params = json!({
+                    "textDocument": {
+                        "uri": uri,
+                        "languageId": language_id(&uri_path(uri)).unwrap_or("rust"),
+                        "version": 1,
+                        "text": content
+                    }
+                });
+                self.send_notification("textDocument/didOpen", Some(params))
+                    .await?;
+                self.documents_opened += 1;
+                1
+            }
+        };
 
         // Mark document as open.
         {
             let mut open_docs = self.open_documents.lock().await;
-            open_docs.insert(uri.to_string());
+            open_docs.insert(
+                uri.to_string(),
+                OpenDocument {
+                    version,
+                    content_hash: hash,
+                    text: content.to_string(),
+                    is_virtual,
+                    last_used: Instant::now(),
+                },
+            );
         }
+        self.close_least_recently_used(uri).await?;
 
-        // Note: didSave removed to speed up hover/definition/references.
-        // cargo check is triggered separately via diagnostics tool.
-        // This allows faster response times for navigation features.
+        Ok(true)
+    }
 
-        // Give rust-analyzer time to process the document.
-        // Increased delay for large files with complex types.
-        tokio::time::sleep(Duration::from_millis(DOCUMENT_OPEN_DELAY_MILLIS)).await;
+    /// Mark `uri` as used if rust-analyzer has it open with the on-disk text
+    /// hashing to `content_hash`. Returns false when it needs a resync.
+    pub async fn touch_document(&self, uri: &str, content_hash: u64) -> bool {
+        let mut open_docs = self.open_documents.lock().await;
+        match open_docs.get_mut(uri) {
+            Some(doc) if !doc.is_virtual && doc.content_hash == content_hash => {
+                doc.last_used = Instant::now();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Hash of the text rust-analyzer has for `uri`, if it's open.
+    pub async fn document_hash(&self, uri: &str) -> Option<u64> {
+        let open_docs = self.open_documents.lock().await;
+        open_docs.get(uri).map(|doc| doc.content_hash)
+    }
+
+    /// The text rust-analyzer has for `uri`, if it's open.
+    pub async fn document_text(&self, uri: &str) -> Option<String> {
+        let open_docs = self.open_documents.lock().await;
+        open_docs.get(uri).map(|doc| doc.text.clone())
+    }
+
+    /// Text of `uris` for column translation: what rust-analyzer has for open
+    /// documents, otherwise the file on disk. Unreadable files are left out.
+    pub async fn document_columns<I, S>(&self, uris: I) -> DocumentColumns
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut columns = DocumentColumns::new(self.position_encoding);
+        for uri in uris {
+            let uri = uri.as_ref();
+            let open_text = self
+                .open_documents
+                .lock()
+                .await
+                .get(uri)
+                .map(|doc| doc.text.clone());
+            let text = match open_text {
+                Some(text) => Some(text),
+                None if uri.starts_with("file://") => {
+                    tokio::fs::read_to_string(uri_path(uri)).await.ok()
+                }
+                None => None,
+            };
+            if let Some(text) = text {
+                columns.insert(uri, text);
+            }
+        }
+        columns
+    }
+
+    pub fn position_encoding(&self) -> PositionEncoding {
+        self.position_encoding
+    }
+
+    pub fn server_info(&self) -> Option<&Value> {
+        self.server_info.as_ref()
+    }
+
+    /// Number of `didOpen` notifications sent since the client started.
+    pub fn documents_opened(&self) -> u64 {
+        self.documents_opened
+    }
+
+    /// Number of file and folder changes sent since the client started.
+    pub fn workspace_changes(&self) -> u64 {
+        self.workspace_changes
+    }
+
+    /// Write `content` to `path` on behalf of a tool, then tell rust-analyzer:
+    /// the new text via `didChange` (or `didOpen`), followed by `didSave` so
+    /// `checkOnSave` runs and diagnostics catch up.
+    pub async fn write_document(&mut self, path: &Path, content: &str) -> Result<()> {
+        tokio::fs::write(path, content)
+            .await
+            .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+
+        let uri = file_uri(path);
+        let was_open = self.open_documents.lock().await.contains_key(&uri);
+        let sent = self.sync_document(&uri, content, false).await?;
+        // A change to an open document already started cargo check.
+        if was_open && sent {
+            return Ok(());
+        }
+        let params = json!({ "textDocument": { "uri": uri } });
+        self.send_notification("textDocument/didSave", Some(params))
+            .await
+    }
+
+    async fn send_did_change(&mut self, uri: &str, content: &str, version: i32) -> Result<()> {
+        let params = json!({
+            "textDocument": { "uri": uri, "version": version },
+            "contentChanges": [{ "text": content }]
+        });
+        self.send_notification("textDocument/didChange", Some(params))
+            .await
+    }
+
+    /// Resend an open document whose file changed on disk. Documents opened
+    /// with unsaved content are left alone. Returns whether it was resent.
+    pub async fn refresh_document(&mut self, uri: &str, content: &str) -> Result<bool> {
+        let hash = content_hash(content);
+        let version = {
+            let mut open_docs = self.open_documents.lock().await;
+            let Some(doc) = open_docs.get_mut(uri) else {
+                return Ok(false);
+            };
+            if doc.is_virtual || doc.content_hash == hash {
+                return Ok(false);
+            }
+            doc.version += 1;
+            doc.content_hash = hash;
+            doc.text = content.to_string();
+            doc.version
+        };
+        self.diagnostics.lock().await.remove(uri);
+
+        info!("Refreshing document from disk: {} (v{})", uri, version);
+        self.send_did_change(uri, content, version).await?;
+        Ok(true)
+    }
+
+    /// Report workspace folders added or removed since initialize.
+    pub async fn change_workspace_folders(
+        &mut self,
+        added: &[PathBuf],
+        removed: &[PathBuf],
+    ) -> Result<()> {
+        let folders = |paths: &[PathBuf]| -> Vec<Value> {
+            paths.iter().map(|path| workspace_folder(path)).collect()
+        };
+        self.send_notification(
+            "workspace/didChangeWorkspaceFolders",
+            Some(json!({
+                "event": { "added": folders(added), "removed": folders(removed) }
+            })),
+        )
+        .await
+    }
+
+    /// Ask rust-analyzer to run `cargo check` on every workspace now.
+    pub async fn run_flycheck(&mut self) -> Result<()> {
+        self.send_notification(
+            "rust-analyzer/runFlycheck",
+            Some(json!({ "textDocument": null })),
+        )
+        .await
+    }
+
+    /// Report file system changes; `changes` are LSP `FileEvent`s.
+    pub async fn did_change_watched_files(&mut self, changes: Vec<Value>) -> Result<()> {
+        self.send_notification(
+            "workspace/didChangeWatchedFiles",
+            Some(json!({ "changes": changes })),
+        )
+        .await
+    }
+
+    /// Send `didClose` for `uri` and drop its cached diagnostics. Returns
+    /// whether the document was open.
+    pub async fn close_document(&mut self, uri: &str) -> Result<bool> {
+        if self.open_documents.lock().await.remove(uri).is_none() {
+            return Ok(false);
+        }
+        {
+            let mut diag_lock = self.diagnostics.lock().await;
+            diag_lock.remove(uri);
+            diag_lock.unpin(uri);
+        }
+
+        info!("Closing document: {}", uri);
+        let params = json!({ "textDocument": { "uri": uri } });
+        self.send_notification("textDocument/didClose", Some(params))
+            .await?;
+        Ok(true)
+    }
 
+    /// Keep `uris` open until the returned guard is dropped.
+    pub fn pin_documents(&self, uris: &[String]) -> PinnedDocuments {
+        if let Ok(mut pinned) = self.pinned_documents.lock() {
+            for uri in uris {
+                *pinned.entry(uri.clone()).or_default() += 1;
+            }
+        }
+        PinnedDocuments {
+            uris: uris.to_vec(),
+            pinned: Arc::clone(&self.pinned_documents),
+        }
+    }
+
+    /// Close documents beyond `max_open_documents`, least recently used
+    /// first. `keep` (the document just opened) and pinned documents are
+    /// never closed.
+    async fn close_least_recently_used(&mut self, keep: &str) -> Result<()> {
+        let max = self.config.max_open_documents as usize;
+        let evicted: Vec<String> = {
+            let open_docs = self.open_documents.lock().await;
+            if open_docs.len() <= max {
+                return Ok(());
+            }
+            let pinned = match self.pinned_documents.lock() {
+                Ok(pinned) => pinned.clone(),
+                Err(_) => HashMap::new(),
+            };
+            let mut candidates: Vec<(&String, Instant)> = open_docs
+                .iter()
+                .filter(|(uri, _)| uri.as_str() != keep && !pinned.contains_key(uri.as_str()))
+                .map(|(uri, doc)| (uri, doc.last_used))
+                .collect();
+            candidates.sort_by_key(|(_, last_used)| *last_used);
+            candidates
+                .into_iter()
+                .take(open_docs.len() - max)
+                .map(|(uri, _)| uri.clone())
+                .collect()
+        };
+        for uri in evicted {
+            self.close_document(&uri).await?;
+        }
         Ok(())
     }
 
+    /// URIs of open documents, most recently used first.
+    pub async fn open_document_uris(&self) -> Vec<String> {
+        let open_docs = self.open_documents.lock().await;
+        let mut docs: Vec<(&String, Instant)> = open_docs
+            .iter()
+            .map(|(uri, doc)| (uri, doc.last_used))
+            .collect();
+        docs.sort_by_key(|(_, last_used)| std::cmp::Reverse(*last_used));
+        docs.into_iter().map(|(uri, _)| uri.clone()).collect()
+    }
+
+    pub async fn diagnostic_store_stats(&self) -> DiagnosticStoreStats {
+        self.diagnostics.lock().await.stats()
+    }
+
+    /// Whether the rust-analyzer child process is still running.
+    pub fn is_process_alive(&mut self) -> bool {
+        self.backend
+            .as_mut()
+            .is_some_and(|backend| backend.is_alive())
+    }
+
+    /// Send a cheap request and report whether rust-analyzer answered in time.
+    pub async fn ping(&mut self, timeout: Duration) -> bool {
+        self.send_request_with_timeout("rust-analyzer/analyzerStatus", Some(json!({})), timeout)
+            .await
+            .is_ok()
+    }
+
     pub async fn shutdown(&mut self) -> Result<()> {
         if self.initialized {
             let _ = self.send_request("shutdown", None).await;
             let _ = self.send_notification("exit", None).await;
         }
 
-        if let Some(mut process) = self.process.take() {
-            // Kill the process and wait for it to actually exit.
-            let _ = process.kill().await;
-            let _ = process.wait().await;
+        if let Some(mut backend) = self.backend.take() {
+            let _ = backend.kill().await;
         }
 
         // Clear open documents and diagnostics.
@@ -367,22 +907,3 @@ impl RustAnalyzerClient {
         Ok(())
     }
 }
-
-fn find_rust_analyzer() -> Result<PathBuf> {
-    which::which("rust-analyzer").or_else(|_| {
-        // Try common installation locations if not in PATH.
-        let home = std::env::var("HOME").unwrap_or_else(|_| String::from("~"));
-        let cargo_bin = PathBuf::from(home).join(".cargo/bin/rust-analyzer");
-        if cargo_bin.exists() {
-            Ok(cargo_bin)
-        } else {
-            which::which("rust-analyzer")
-        }
-    })
-    .map_err(|e| {
-        anyhow!(
-            "Failed to find rust-analyzer in PATH or ~/.cargo/bin: {}. Please ensure rust-analyzer is installed.",
-            e
-        )
-    })
-}
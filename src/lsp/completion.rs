@@ -0,0 +1,121 @@
+use serde_json::{json, Value};
+use std::{iter::Peekable, str::Chars};
+
+/// `InsertTextFormat.Snippet`.
+const SNIPPET_FORMAT: u64 = 2;
+
+/// The text a snippet inserts once its placeholders are accepted: tabstops
+/// and variables are dropped, placeholders keep their default, and choices
+/// keep the first option.
+pub fn snippet_to_text(snippet: &str) -> String {
+    let mut text = String::with_capacity(snippet.len());
+    append_snippet(&mut snippet.chars().peekable(), &mut text, false);
+    text
+}
+
+/// Copy snippet text into `out` up to the end of the input, or up to the
+/// closing `}` when `nested` inside a placeholder.
+fn append_snippet(chars: &mut Peekable<Chars>, out: &mut String, nested: bool) {
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.peek() {
+                Some(&escaped @ ('$' | '}' | '\\')) => {
+                    out.push(escaped);
+                    chars.next();
+                }
+                _ => out.push('\\'),
+            },
+            '}' if nested => return,
+            '$' => append_snippet_element(chars, out),
+            ch => out.push(ch),
+        }
+    }
+}
+
+/// Handle what follows a `$`: `$1`, `$name`, `${1}`, `${1:default}`, or
+/// `${1|one,two|}`.
+fn append_snippet_element(chars: &mut Peekable<Chars>, out: &mut String) {
+    let is_name = |ch: &char| ch.is_ascii_alphanumeric() || *ch == '_';
+    match chars.peek() {
+        Some(ch) if is_name(ch) => while chars.next_if(is_name).is_some() {},
+        Some('{') => {
+            chars.next();
+            while chars.next_if(is_name).is_some() {}
+            match chars.next() {
+                Some(':') => append_snippet(chars, out, true),
+                Some('|') => append_first_choice(chars, out),
+                _ => {}
+            }
+        }
+        _ => out.push('$'),
+    }
+}
+
+fn append_first_choice(chars: &mut Peekable<Chars>, out: &mut String) {
+    let mut first = true;
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    if first {
+                        out.push(escaped);
+                    }
+                }
+            }
+            ',' => first = false,
+            '|' => {
+                chars.next_if_eq(&'}');
+                return;
+            }
+            ch if first => out.push(ch),
+            _ => {}
+        }
+    }
+}
+
+/// The `use` statement a completion of an item not yet in scope needs:
+/// from the paths rust-analyzer keeps under `data.imports` for resolving
+/// the item, else from a `use` line among its `additionalTextEdits`. `None`
+/// for items already in scope, and for edits that only add to an existing
+/// `use` tree without saying the path.
+pub fn auto_import(item: &Value) -> Option<String> {
+    let imports: Vec<String> = item["data"]["imports"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|import| import["full_import_path"].as_str())
+        .map(|path| format!("use {};", path))
+        .collect();
+    if !imports.is_empty() {
+        return Some(imports.join("\n"));
+    }
+    item["additionalTextEdits"]
+        .as_array()?
+        .iter()
+        .filter_map(|edit| edit["newText"].as_str())
+        .flat_map(str::lines)
+        .map(str::trim)
+        .find(|line| line.starts_with("use ") && line.ends_with(';'))
+        .map(str::to_string)
+}
+
+/// Rewrite a completion item for callers that insert text verbatim: snippet
+/// insert texts become plain text, and markdown documentation a string.
+/// Items that need an import say which under `auto_import`.
+pub fn simplify_completion_item(item: &mut Value) {
+    if let Some(import) = auto_import(item) {
+        item["auto_import"] = json!(import);
+    }
+    if item["insertTextFormat"].as_u64() == Some(SNIPPET_FORMAT) {
+        if let Some(text) = item["insertText"].as_str() {
+            item["insertText"] = json!(snippet_to_text(text));
+        }
+        if let Some(text) = item["textEdit"]["newText"].as_str() {
+            item["textEdit"]["newText"] = json!(snippet_to_text(text));
+        }
+        item["insertTextFormat"] = json!(1);
+    }
+    if let Some(value) = item["documentation"]["value"].as_str() {
+        item["documentation"] = json!(value);
+    }
+}
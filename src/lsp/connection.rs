@@ -1,26 +1,59 @@
-use log::{debug, error, info};
-use serde_json::Value;
+use anyhow::{anyhow, Result};
+use log::{debug, error, info, warn};
+use serde_json::{json, Value};
 use std::{collections::HashMap, sync::Arc};
 use tokio::{
-    io::{AsyncBufReadExt, AsyncReadExt, BufReader},
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, BufWriter},
+    process::ChildStdin,
     sync::{oneshot, Mutex},
 };
 
-use super::progress::SharedProgress;
+use super::{
+    diagnostic_store::SharedDiagnostics,
+    messages::{MessageType, SharedMessages},
+    progress::SharedProgress,
+    registrations::SharedRegistrations,
+};
 use crate::protocol::lsp::LSPResponse;
 
-pub fn start_handlers(
+/// rust-analyzer's stdin, shared by the client and the replies to requests
+/// rust-analyzer sends us.
+pub(super) type SharedStdin = Arc<Mutex<Option<BufWriter<ChildStdin>>>>;
+
+/// State the stdout handler updates from what rust-analyzer sends.
+pub(super) struct Handlers {
+    pub pending_requests: Arc<Mutex<HashMap<u64, oneshot::Sender<Value>>>>,
+    pub diagnostics: SharedDiagnostics,
+    pub progress: SharedProgress,
+    pub messages: SharedMessages,
+    pub registrations: SharedRegistrations,
+    pub stdin: SharedStdin,
+}
+
+pub(super) fn start_handlers(
     stdout: tokio::process::ChildStdout,
     stderr: tokio::process::ChildStderr,
-    pending_requests: Arc<Mutex<HashMap<u64, oneshot::Sender<Value>>>>,
-    diagnostics: Arc<Mutex<HashMap<String, Vec<Value>>>>,
-    progress: SharedProgress,
+    handlers: Handlers,
 ) {
     // Log stderr in background.
     tokio::spawn(handle_stderr(stderr));
 
     // Start response handler task.
-    tokio::spawn(handle_stdout(stdout, pending_requests, diagnostics, progress));
+    tokio::spawn(handle_stdout(stdout, handlers));
+}
+
+/// Write one framed JSON-RPC message.
+pub(super) async fn write_message(stdin: &SharedStdin, content: &str) -> Result<()> {
+    let message = format!("Content-Length: {}\r\n\r\n{}", content.len(), content);
+
+    let mut stdin = stdin.lock().await;
+    let Some(stdin) = stdin.as_mut() else {
+        return Err(anyhow!("No stdin available"));
+    };
+
+    stdin.write_all(message.as_bytes()).await?;
+    stdin.flush().await?;
+    Ok(())
 }
 
 async fn handle_stderr(stderr: tokio::process::ChildStderr) {
@@ -48,12 +81,7 @@ async fn handle_stderr(stderr: tokio::process::ChildStderr) {
     }
 }
 
-async fn handle_stdout(
-    stdout: tokio::process::ChildStdout,
-    pending: Arc<Mutex<HashMap<u64, oneshot::Sender<Value>>>>,
-    diagnostics: Arc<Mutex<HashMap<String, Vec<Value>>>>,
-    progress: SharedProgress,
-) {
+async fn handle_stdout(stdout: tokio::process::ChildStdout, handlers: Handlers) {
     let mut reader = BufReader::new(stdout);
     let mut buffer = String::new();
 
@@ -93,7 +121,7 @@ async fn handle_stdout(
         let response_str = String::from_utf8_lossy(&json_buffer);
         debug!("Received LSP message: {}", response_str);
 
-        handle_lsp_message(&json_buffer, &pending, &diagnostics, &progress).await;
+        handle_lsp_message(&json_buffer, &handlers).await;
     }
 }
 
@@ -103,12 +131,7 @@ fn parse_content_length(header: &str) -> Option<usize> {
         .and_then(|s| s.trim().parse().ok())
 }
 
-async fn handle_lsp_message(
-    json_buffer: &[u8],
-    pending: &Arc<Mutex<HashMap<u64, oneshot::Sender<Value>>>>,
-    diagnostics: &Arc<Mutex<HashMap<String, Vec<Value>>>>,
-    progress: &SharedProgress,
-) {
+async fn handle_lsp_message(json_buffer: &[u8], handlers: &Handlers) {
     let Ok(json_value) = serde_json::from_slice::<Value>(json_buffer) else {
         error!(
             "Failed to parse LSP message: {}",
@@ -117,9 +140,13 @@ async fn handle_lsp_message(
         return;
     };
 
-    // Check if it's a notification (has method but no id).
-    if json_value.get("method").is_some() && json_value.get("id").is_none() {
-        handle_notification(json_value, diagnostics, progress).await;
+    // A method with no id is a notification; with an id, a request to us.
+    if json_value.get("method").is_some() {
+        if json_value.get("id").is_none() {
+            handle_notification(json_value, handlers).await;
+        } else {
+            handle_server_request(json_value, handlers).await;
+        }
         return;
     }
 
@@ -132,7 +159,7 @@ async fn handle_lsp_message(
         return;
     };
 
-    let mut pending_lock = pending.lock().await;
+    let mut pending_lock = handlers.pending_requests.lock().await;
     let Some(sender) = pending_lock.remove(&id) else {
         return;
     };
@@ -147,11 +174,55 @@ async fn handle_lsp_message(
     }
 }
 
-async fn handle_notification(
-    json_value: Value,
-    diagnostics: &Arc<Mutex<HashMap<String, Vec<Value>>>>,
-    progress: &SharedProgress,
-) {
+/// Answer a request rust-analyzer sent us. Registrations are recorded;
+/// requests that only need an acknowledgement get a null result, and
+/// anything else `MethodNotFound`.
+async fn handle_server_request(json_value: Value, handlers: &Handlers) {
+    let id = json_value["id"].clone();
+    let method = json_value["method"].as_str().unwrap_or("");
+    let params = &json_value["params"];
+    debug!("Received request {}: {}", id, method);
+
+    let reply = match method {
+        "client/registerCapability" => {
+            info!("rust-analyzer registered: {}", params["registrations"]);
+            handlers.registrations.lock().await.register(params);
+            json!({ "jsonrpc": "2.0", "id": id, "result": null })
+        }
+        "client/unregisterCapability" => {
+            info!("rust-analyzer unregistered: {}", params);
+            handlers.registrations.lock().await.unregister(params);
+            json!({ "jsonrpc": "2.0", "id": id, "result": null })
+        }
+        "window/workDoneProgress/create"
+        | "workspace/semanticTokens/refresh"
+        | "workspace/inlayHint/refresh"
+        | "workspace/codeLens/refresh"
+        | "workspace/diagnostic/refresh" => {
+            json!({ "jsonrpc": "2.0", "id": id, "result": null })
+        }
+        _ => {
+            debug!("Unhandled request from rust-analyzer: {}", method);
+            json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": -32601, "message": format!("Unhandled method {}", method) }
+            })
+        }
+    };
+
+    if let Err(e) = write_message(&handlers.stdin, &reply.to_string()).await {
+        warn!("Failed to answer {} from rust-analyzer: {}", method, e);
+    }
+}
+
+async fn handle_notification(json_value: Value, handlers: &Handlers) {
+    let Handlers {
+        diagnostics,
+        progress,
+        messages,
+        ..
+    } = handlers;
     let Some(method) = json_value.get("method").and_then(|m| m.as_str()) else {
         return;
     };
@@ -170,8 +241,7 @@ async fn handle_notification(
                 return;
             };
 
-            let mut diag_lock = diagnostics.lock().await;
-            diag_lock.insert(uri.to_string(), diags.clone());
+            diagnostics.lock().await.publish(uri, diags.clone());
             info!("Stored {} diagnostics for {}", diags.len(), uri);
         }
         "$/progress" => {
@@ -211,6 +281,20 @@ async fn handle_notification(
                 _ => {}
             }
         }
+        "window/showMessage" | "window/logMessage" => {
+            let Some(params) = json_value.get("params") else {
+                return;
+            };
+            let kind = MessageType::from_lsp(params["type"].as_u64().unwrap_or(4));
+            let message = params["message"].as_str().unwrap_or("").to_string();
+            match kind {
+                MessageType::Error => error!("rust-analyzer: {}", message),
+                MessageType::Warning => warn!("rust-analyzer: {}", message),
+                MessageType::Info => info!("rust-analyzer: {}", message),
+                MessageType::Log | MessageType::Debug => debug!("rust-analyzer: {}", message),
+            }
+            messages.lock().await.push(kind, message);
+        }
         _ => {}
     }
 }
@@ -0,0 +1,137 @@
+use log::debug;
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+use tokio::sync::Mutex;
+
+struct StoredDiagnostics {
+    diagnostics: Vec<Value>,
+    /// Serialized size, as an estimate of the memory held.
+    bytes: usize,
+    /// Publish sequence number; the lowest is evicted first.
+    published: u64,
+}
+
+/// Size of the store, reported by `/api/v1/status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DiagnosticStoreStats {
+    pub files: usize,
+    pub bytes: usize,
+    pub max_files: usize,
+    pub max_bytes: usize,
+    /// Files dropped to stay within the limits since the client started.
+    pub evicted: u64,
+}
+
+/// Diagnostics from `publishDiagnostics`, by URI. Past `max_files` entries or
+/// `max_bytes`, the least recently published URIs are dropped, except those
+/// pinned because the document is open.
+pub struct DiagnosticStore {
+    entries: HashMap<String, StoredDiagnostics>,
+    pinned: HashSet<String>,
+    bytes: usize,
+    sequence: u64,
+    evicted: u64,
+    max_files: usize,
+    max_bytes: usize,
+}
+
+impl DiagnosticStore {
+    pub fn new(max_files: usize, max_bytes: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            pinned: HashSet::new(),
+            bytes: 0,
+            sequence: 0,
+            evicted: 0,
+            max_files,
+            max_bytes,
+        }
+    }
+
+    pub fn publish(&mut self, uri: &str, diagnostics: Vec<Value>) {
+        let bytes = serde_json::to_string(&diagnostics).map_or(0, |s| s.len());
+        self.sequence += 1;
+        let entry = StoredDiagnostics {
+            diagnostics,
+            bytes,
+            published: self.sequence,
+        };
+        if let Some(old) = self.entries.insert(uri.to_string(), entry) {
+            self.bytes -= old.bytes;
+        }
+        self.bytes += bytes;
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.entries.len() > self.max_files || self.bytes > self.max_bytes {
+            let oldest = self
+                .entries
+                .iter()
+                .filter(|(uri, _)| !self.pinned.contains(*uri))
+                .min_by_key(|(_, entry)| entry.published)
+                .map(|(uri, _)| uri.clone());
+            let Some(uri) = oldest else {
+                // Everything left is open; those stay regardless.
+                return;
+            };
+            self.remove(&uri);
+            self.evicted += 1;
+            debug!("Evicted diagnostics for {}", uri);
+        }
+    }
+
+    pub fn get(&self, uri: &str) -> Option<&Vec<Value>> {
+        self.entries.get(uri).map(|entry| &entry.diagnostics)
+    }
+
+    pub fn remove(&mut self, uri: &str) {
+        if let Some(entry) = self.entries.remove(uri) {
+            self.bytes -= entry.bytes;
+        }
+    }
+
+    /// Keep `uri` through eviction while its document is open.
+    pub fn pin(&mut self, uri: &str) {
+        self.pinned.insert(uri.to_string());
+    }
+
+    pub fn unpin(&mut self, uri: &str) {
+        self.pinned.remove(uri);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.pinned.clear();
+        self.bytes = 0;
+        self.evicted = 0;
+    }
+
+    pub fn uris(&self) -> Vec<&String> {
+        self.entries.keys().collect()
+    }
+
+    /// Copy of every stored entry.
+    pub fn snapshot(&self) -> HashMap<String, Vec<Value>> {
+        self.entries
+            .iter()
+            .map(|(uri, entry)| (uri.clone(), entry.diagnostics.clone()))
+            .collect()
+    }
+
+    pub fn stats(&self) -> DiagnosticStoreStats {
+        DiagnosticStoreStats {
+            files: self.entries.len(),
+            bytes: self.bytes,
+            max_files: self.max_files,
+            max_bytes: self.max_bytes,
+            evicted: self.evicted,
+        }
+    }
+}
+
+pub type SharedDiagnostics = Arc<Mutex<DiagnosticStore>>;
@@ -0,0 +1,116 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use super::position::PositionEncoding;
+
+/// Byte offset of an LSP position (line, column in `encoding`) in `content`.
+/// Positions past the end of a line or of the file are clamped.
+pub fn position_to_offset(
+    content: &str,
+    line: u32,
+    character: u32,
+    encoding: PositionEncoding,
+) -> usize {
+    let mut line_start = 0;
+    for _ in 0..line {
+        match content[line_start..].find('\n') {
+            Some(newline) => line_start += newline + 1,
+            None => return content.len(),
+        }
+    }
+    let line_end = content[line_start..]
+        .find('\n')
+        .map_or(content.len(), |newline| line_start + newline);
+
+    let mut units = 0;
+    for (offset, ch) in content[line_start..line_end].char_indices() {
+        if units >= character as usize {
+            return line_start + offset;
+        }
+        units += encoding.len(ch) as usize;
+    }
+    line_end
+}
+
+fn edit_range(content: &str, edit: &Value, encoding: PositionEncoding) -> Result<(usize, usize)> {
+    let position = |key: &str| -> Result<usize> {
+        let pos = &edit["range"][key];
+        let (Some(line), Some(character)) = (pos["line"].as_u64(), pos["character"].as_u64())
+        else {
+            return Err(anyhow!("Text edit has no valid range: {}", edit));
+        };
+        Ok(position_to_offset(
+            content,
+            line as u32,
+            character as u32,
+            encoding,
+        ))
+    };
+    let (start, end) = (position("start")?, position("end")?);
+    if start > end {
+        return Err(anyhow!("Text edit range is reversed: {}", edit));
+    }
+    Ok((start, end))
+}
+
+/// Apply LSP `TextEdit`s to `content`. Edits must not overlap; ranges refer
+/// to the original text, as the protocol requires.
+pub fn apply_text_edits(
+    content: &str,
+    edits: &[Value],
+    encoding: PositionEncoding,
+) -> Result<String> {
+    let mut ranges = Vec::with_capacity(edits.len());
+    for edit in edits {
+        let (start, end) = edit_range(content, edit, encoding)?;
+        let Some(new_text) = edit["newText"].as_str() else {
+            return Err(anyhow!("Text edit has no newText: {}", edit));
+        };
+        ranges.push((start, end, new_text));
+    }
+    // Stable sort keeps inserts at the same position in the order given.
+    ranges.sort_by_key(|(start, end, _)| (*start, *end));
+    if ranges.windows(2).any(|pair| pair[0].1 > pair[1].0) {
+        return Err(anyhow!("Text edits overlap"));
+    }
+
+    let mut result = String::with_capacity(content.len());
+    let mut copied = 0;
+    for (start, end, new_text) in ranges {
+        result.push_str(&content[copied..start]);
+        result.push_str(new_text);
+        copied = end;
+    }
+    result.push_str(&content[copied..]);
+    Ok(result)
+}
+
+/// Text edits per document URI in an LSP `WorkspaceEdit`. File create,
+/// rename, and delete operations are not supported.
+pub fn workspace_edit_changes(edit: &Value) -> Result<BTreeMap<String, Vec<Value>>> {
+    let mut changes: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+
+    if let Some(document_changes) = edit["documentChanges"].as_array() {
+        for change in document_changes {
+            let (Some(uri), Some(edits)) = (
+                change["textDocument"]["uri"].as_str(),
+                change["edits"].as_array(),
+            ) else {
+                let kind = change["kind"].as_str().unwrap_or("unknown");
+                return Err(anyhow!("Unsupported workspace edit operation: {}", kind));
+            };
+            changes
+                .entry(uri.to_string())
+                .or_default()
+                .extend(edits.iter().cloned());
+        }
+    } else if let Some(by_uri) = edit["changes"].as_object() {
+        for (uri, edits) in by_uri {
+            let edits = edits.as_array().cloned().unwrap_or_default();
+            changes.entry(uri.clone()).or_default().extend(edits);
+        }
+    }
+
+    Ok(changes)
+}
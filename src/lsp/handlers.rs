@@ -1,11 +1,43 @@
 use anyhow::Result;
-use log::info;
+use log::{debug, info};
 use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+use crate::config::COMPLETION_RESOLVE_MAX;
+
+use super::{
+    client::RustAnalyzerClient,
+    completion::simplify_completion_item,
+    paths::{uri_path, ModulePaths},
+    position::uris_in,
+    runnables::simplify_test_runnable,
+};
+
+/// Which symbols a `workspace/symbol` search returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolScope {
+    /// Symbols defined in the workspace folders.
+    Workspace,
+    /// Those of dependencies and the standard library as well.
+    All,
+}
 
-use super::client::RustAnalyzerClient;
+impl SymbolScope {
+    /// rust-analyzer's `searchScope` for the scope.
+    fn search_scope(self) -> &'static str {
+        match self {
+            SymbolScope::Workspace => "workspace",
+            SymbolScope::All => "workspaceAndDependencies",
+        }
+    }
+}
 
 impl RustAnalyzerClient {
     pub async fn hover(&mut self, uri: &str, line: u32, character: u32) -> Result<Value> {
+        let character = self
+            .document_columns([uri])
+            .await
+            .to_lsp(uri, line, character);
         let params = json!({
             "textDocument": { "uri": uri },
             "position": { "line": line, "character": character }
@@ -15,6 +47,10 @@ impl RustAnalyzerClient {
     }
 
     pub async fn definition(&mut self, uri: &str, line: u32, character: u32) -> Result<Value> {
+        let character = self
+            .document_columns([uri])
+            .await
+            .to_lsp(uri, line, character);
         let params = json!({
             "textDocument": { "uri": uri },
             "position": { "line": line, "character": character }
@@ -24,7 +60,39 @@ impl RustAnalyzerClient {
             .await
     }
 
+    pub async fn declaration(&mut self, uri: &str, line: u32, character: u32) -> Result<Value> {
+        let character = self
+            .document_columns([uri])
+            .await
+            .to_lsp(uri, line, character);
+        let params = json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character }
+        });
+
+        self.send_request("textDocument/declaration", Some(params))
+            .await
+    }
+
+    pub async fn type_definition(&mut self, uri: &str, line: u32, character: u32) -> Result<Value> {
+        let character = self
+            .document_columns([uri])
+            .await
+            .to_lsp(uri, line, character);
+        let params = json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character }
+        });
+
+        self.send_request("textDocument/typeDefinition", Some(params))
+            .await
+    }
+
     pub async fn references(&mut self, uri: &str, line: u32, character: u32) -> Result<Value> {
+        let character = self
+            .document_columns([uri])
+            .await
+            .to_lsp(uri, line, character);
         let params = json!({
             "textDocument": { "uri": uri },
             "position": { "line": line, "character": character },
@@ -35,14 +103,80 @@ impl RustAnalyzerClient {
             .await
     }
 
+    /// The `WorkspaceEdit` renaming the symbol at a position to `new_name`.
+    pub async fn rename(
+        &mut self,
+        uri: &str,
+        line: u32,
+        character: u32,
+        new_name: &str,
+    ) -> Result<Value> {
+        let character = self
+            .document_columns([uri])
+            .await
+            .to_lsp(uri, line, character);
+        let params = json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character },
+            "newName": new_name
+        });
+
+        self.send_request("textDocument/rename", Some(params)).await
+    }
+
     pub async fn completion(&mut self, uri: &str, line: u32, character: u32) -> Result<Value> {
+        let character = self
+            .document_columns([uri])
+            .await
+            .to_lsp(uri, line, character);
         let params = json!({
             "textDocument": { "uri": uri },
             "position": { "line": line, "character": character }
         });
 
-        self.send_request("textDocument/completion", Some(params))
-            .await
+        let mut result = self
+            .send_request("textDocument/completion", Some(params))
+            .await?;
+
+        // Either a bare item array or a CompletionList.
+        let items = match &mut result {
+            Value::Array(items) => Some(items),
+            Value::Object(list) => list.get_mut("items").and_then(Value::as_array_mut),
+            _ => None,
+        };
+        let Some(items) = items else {
+            return Ok(result);
+        };
+
+        // Items that need an import leave its edits to `completionItem/resolve`.
+        let mut resolves = Vec::new();
+        for (index, item) in items.iter().enumerate() {
+            if resolves.len() == COMPLETION_RESOLVE_MAX {
+                break;
+            }
+            if item["data"]["imports"]
+                .as_array()
+                .is_some_and(|imports| !imports.is_empty())
+            {
+                let request =
+                    self.request_detached("completionItem/resolve", Some(item.clone()))?;
+                resolves.push((index, request));
+            }
+        }
+        for (index, request) in resolves {
+            match request.await {
+                Ok(resolved) if resolved["additionalTextEdits"].is_array() => {
+                    items[index]["additionalTextEdits"] = resolved["additionalTextEdits"].clone();
+                }
+                Ok(_) => {}
+                Err(e) => debug!("Failed to resolve completion item: {}", e),
+            }
+        }
+
+        for item in items.iter_mut() {
+            simplify_completion_item(item);
+        }
+        Ok(result)
     }
 
     pub async fn document_symbols(&mut self, uri: &str) -> Result<Value> {
@@ -71,10 +205,7 @@ impl RustAnalyzerClient {
         // First check if we have stored diagnostics from publishDiagnostics.
         let diag_lock = self.diagnostics.lock().await;
         info!("Looking for diagnostics for URI: {}", uri);
-        info!(
-            "Available URIs with diagnostics: {:?}",
-            diag_lock.keys().collect::<Vec<_>>()
-        );
+        info!("Available URIs with diagnostics: {:?}", diag_lock.uris());
         if let Some(diags) = diag_lock.get(uri) {
             info!("Found {} stored diagnostics for {}", diags.len(), uri);
             return Ok(json!(diags));
@@ -119,13 +250,13 @@ impl RustAnalyzerClient {
         let mut all_diagnostics = json!({});
 
         // 1) Use cached publishDiagnostics data.
-        let cached = self.diagnostics.lock().await.clone();
+        let cached = self.diagnostics.lock().await.snapshot();
         for (uri, diags) in &cached {
             all_diagnostics[uri] = json!(diags);
         }
 
         // 2) Try open documents not already in cache.
-        let open_docs = self.open_documents.lock().await.clone();
+        let open_docs: Vec<String> = self.open_documents.lock().await.keys().cloned().collect();
         for doc_uri in open_docs.iter() {
             if all_diagnostics.get(doc_uri).is_none() {
                 if let Ok(diag) = self.diagnostics(doc_uri).await {
@@ -138,6 +269,10 @@ impl RustAnalyzerClient {
     }
 
     pub async fn implementation(&mut self, uri: &str, line: u32, character: u32) -> Result<Value> {
+        let character = self
+            .document_columns([uri])
+            .await
+            .to_lsp(uri, line, character);
         let params = json!({
             "textDocument": { "uri": uri },
             "position": { "line": line, "character": character }
@@ -148,6 +283,10 @@ impl RustAnalyzerClient {
     }
 
     pub async fn parent_module(&mut self, uri: &str, line: u32, character: u32) -> Result<Value> {
+        let character = self
+            .document_columns([uri])
+            .await
+            .to_lsp(uri, line, character);
         let params = json!({
             "textDocument": { "uri": uri },
             "position": { "line": line, "character": character }
@@ -157,7 +296,32 @@ impl RustAnalyzerClient {
             .await
     }
 
-    pub async fn prepare_call_hierarchy(&mut self, uri: &str, line: u32, character: u32) -> Result<Value> {
+    /// `experimental/externalDocs`: a link to the documentation of the item
+    /// at a position.
+    pub async fn external_docs(&mut self, uri: &str, line: u32, character: u32) -> Result<Value> {
+        let character = self
+            .document_columns([uri])
+            .await
+            .to_lsp(uri, line, character);
+        let params = json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character }
+        });
+
+        self.send_request("experimental/externalDocs", Some(params))
+            .await
+    }
+
+    pub async fn prepare_call_hierarchy(
+        &mut self,
+        uri: &str,
+        line: u32,
+        character: u32,
+    ) -> Result<Value> {
+        let character = self
+            .document_columns([uri])
+            .await
+            .to_lsp(uri, line, character);
         let params = json!({
             "textDocument": { "uri": uri },
             "position": { "line": line, "character": character }
@@ -185,7 +349,47 @@ impl RustAnalyzerClient {
             .await
     }
 
+    pub async fn prepare_type_hierarchy(
+        &mut self,
+        uri: &str,
+        line: u32,
+        character: u32,
+    ) -> Result<Value> {
+        let character = self
+            .document_columns([uri])
+            .await
+            .to_lsp(uri, line, character);
+        let params = json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character }
+        });
+
+        self.send_request("textDocument/prepareTypeHierarchy", Some(params))
+            .await
+    }
+
+    pub async fn supertypes(&mut self, item: Value) -> Result<Value> {
+        let params = json!({
+            "item": item
+        });
+
+        self.send_request("typeHierarchy/supertypes", Some(params))
+            .await
+    }
+
+    pub async fn subtypes(&mut self, item: Value) -> Result<Value> {
+        let params = json!({
+            "item": item
+        });
+
+        self.send_request("typeHierarchy/subtypes", Some(params))
+            .await
+    }
+
     pub async fn inlay_hint(&mut self, uri: &str, start_line: u32, start_char: u32, end_line: u32, end_char: u32) -> Result<Value> {
+        let columns = self.document_columns([uri]).await;
+        let start_char = columns.to_lsp(uri, start_line, start_char);
+        let end_char = columns.to_lsp(uri, end_line, end_char);
         let params = json!({
             "textDocument": { "uri": uri },
             "range": {
@@ -198,62 +402,131 @@ impl RustAnalyzerClient {
             .await
     }
 
-    pub async fn workspace_symbol(&mut self, query: &str) -> Result<Value> {
+    /// The tests in a document, or those enclosing `position`, from
+    /// `experimental/runnables`, with the cargo invocation that runs each.
+    pub async fn tests(&mut self, uri: &str, position: Option<(u32, u32)>) -> Result<Value> {
+        let position = match position {
+            Some((line, character)) => {
+                let character = self
+                    .document_columns([uri])
+                    .await
+                    .to_lsp(uri, line, character);
+                json!({ "line": line, "character": character })
+            }
+            None => Value::Null,
+        };
         let params = json!({
+            "textDocument": { "uri": uri },
+            "position": position
+        });
+
+        let result = self
+            .send_request("experimental/runnables", Some(params))
+            .await?;
+        let columns = self.document_columns(uris_in(&result)).await;
+
+        let tests: Vec<Value> = result
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|runnable| {
+                let mut test = simplify_test_runnable(runnable)?;
+                let link = &test["location"];
+                test["location"] = match link["targetUri"].as_str() {
+                    Some(uri) => {
+                        let start = &link["targetSelectionRange"]["start"];
+                        let line = start["line"].as_u64()?;
+                        let character = columns.to_user(uri, line, start["character"].as_u64()?);
+                        let path = self.path_style.display(uri);
+                        json!(format!("{}:{}:{}", path, line, character))
+                    }
+                    None => Value::Null,
+                };
+                Some(test)
+            })
+            .collect();
+        Ok(json!(tests))
+    }
+
+    /// Symbols matching `query`, simplified. For `SymbolScope::Workspace`,
+    /// those outside the workspace folders are left out, and the result is
+    /// `{ symbols, hidden_external }` when any were; rust-analyzers that
+    /// advertise `workspaceSymbolScopeKindFiltering` are asked to skip
+    /// them in the first place.
+    pub async fn workspace_symbol(&mut self, query: &str, scope: SymbolScope) -> Result<Value> {
+        let mut params = json!({
             "query": query
         });
+        if self.symbol_scope_filtering {
+            params["searchScope"] = json!(scope.search_scope());
+        }
 
         let result = self.send_request("workspace/symbol", Some(params)).await?;
-
-        // Simplify the result to reduce token usage
-        if let Some(symbols) = result.as_array() {
-            let simplified: Vec<Value> = symbols
-                .iter()
-                .filter_map(|s| {
-                    let name = s["name"].as_str()?;
-                    let kind = s["kind"].as_u64()?;
-                    let uri = s["location"]["uri"].as_str()?;
-                    let line = s["location"]["range"]["start"]["line"].as_u64()?;
-                    let character = s["location"]["range"]["start"]["character"].as_u64()?;
-
-                    // Extract file path from URI
-                    let path = uri.strip_prefix("file://").unwrap_or(uri);
-
-                    // Convert kind number to readable string
-                    let kind_str = match kind {
-                        1 => "file",
-                        2 => "module",
-                        3 => "namespace",
-                        4 => "package",
-                        5 => "class",
-                        6 => "method",
-                        7 => "property",
-                        8 => "field",
-                        9 => "constructor",
-                        10 => "enum",
-                        11 => "interface",
-                        12 => "function",
-                        13 => "variable",
-                        14 => "constant",
-                        15 => "string",
-                        16 => "number",
-                        17 => "boolean",
-                        18 => "array",
-                        23 => "struct",
-                        _ => "other",
-                    };
-
-                    Some(json!({
-                        "name": name,
-                        "kind": kind_str,
-                        "location": format!("{}:{}:{}", path, line, character)
-                    }))
-                })
-                .collect();
-
-            Ok(json!(simplified))
-        } else {
-            Ok(result)
+        let columns = self.document_columns(uris_in(&result)).await;
+
+        let Some(symbols) = result.as_array() else {
+            return Ok(result);
+        };
+
+        let folders: Vec<PathBuf> = std::iter::once(&self.workspace_root)
+            .chain(&self.config.extra_workspaces)
+            .map(|folder| folder.canonicalize().unwrap_or_else(|_| folder.clone()))
+            .collect();
+        let file_of = |s: &Value| {
+            let uri = s["location"]["uri"].as_str().unwrap_or_default();
+            uri_path(uri)
+        };
+        let in_workspace = |file: &Path| folders.iter().any(|folder| file.starts_with(folder));
+        let (symbols, hidden): (Vec<&Value>, Vec<&Value>) = symbols
+            .iter()
+            .partition(|s| scope == SymbolScope::All || in_workspace(&file_of(s)));
+
+        // Simplify the result to reduce token usage. `container` tells apart
+        // the many symbols sharing a name: rust-analyzer's `containerName`,
+        // else the module of the file.
+        let mut module_paths = ModulePaths::default();
+        let mut simplified: Vec<((u8, bool), Value)> = symbols
+            .iter()
+            .filter_map(|s| {
+                let name = s["name"].as_str()?;
+                let kind = s["kind"].as_u64()?;
+                let uri = s["location"]["uri"].as_str()?;
+                let line = s["location"]["range"]["start"]["line"].as_u64()?;
+                let character = s["location"]["range"]["start"]["character"].as_u64()?;
+                let character = columns.to_user(uri, line, character);
+
+                // Extract file path from URI
+                let path = self.path_style.display(uri);
+                let file = file_of(s);
+                let container = match s["containerName"].as_str() {
+                    Some(container) if !container.is_empty() => Some(container.to_string()),
+                    _ => module_paths.module_path(&file),
+                };
+
+                let mut symbol = json!({
+                    "name": name,
+                    "kind": symbol_kind_name(kind),
+                    "location": format!("{}:{}:{}", path, line, character)
+                });
+                if let Some(container) = container {
+                    symbol["container"] = json!(container);
+                }
+                // Exact matches first, then ones differing only in case,
+                // then fuzzy ones; workspace symbols ahead of others.
+                let rank = match name {
+                    name if name == query => 0,
+                    name if name.eq_ignore_ascii_case(query) => 1,
+                    _ => 2,
+                };
+                Some(((rank, !in_workspace(&file)), symbol))
+            })
+            .collect();
+        simplified.sort_by_key(|(key, _)| *key);
+        let simplified: Vec<Value> = simplified.into_iter().map(|(_, symbol)| symbol).collect();
+
+        match hidden.len() {
+            0 => Ok(json!(simplified)),
+            hidden => Ok(json!({ "symbols": simplified, "hidden_external": hidden })),
         }
     }
 
@@ -271,6 +544,10 @@ impl RustAnalyzerClient {
         // Filter diagnostics to only those in the requested range.
         let filtered_diagnostics = filter_diagnostics_in_range(&diagnostics, start_line, end_line);
 
+        let columns = self.document_columns([uri]).await;
+        let start_char = columns.to_lsp(uri, start_line, start_char);
+        let end_char = columns.to_lsp(uri, end_line, end_char);
+
         let params = json!({
             "textDocument": { "uri": uri },
             "range": {
@@ -286,6 +563,12 @@ impl RustAnalyzerClient {
         self.send_request("textDocument/codeAction", Some(params))
             .await
     }
+
+    /// Fill in the `edit` of a code action returned without one.
+    pub async fn resolve_code_action(&mut self, action: &Value) -> Result<Value> {
+        self.send_request("codeAction/resolve", Some(action.clone()))
+            .await
+    }
 }
 
 fn filter_diagnostics_in_range(diagnostics: &Value, start_line: u32, end_line: u32) -> Value {
@@ -317,3 +600,32 @@ fn filter_diagnostics_in_range(diagnostics: &Value, start_line: u32, end_line: u
 
     json!(filtered)
 }
+
+/// Readable name of an LSP `SymbolKind`.
+pub fn symbol_kind_name(kind: u64) -> &'static str {
+    match kind {
+        1 => "file",
+        2 => "module",
+        3 => "namespace",
+        4 => "package",
+        5 => "class",
+        6 => "method",
+        7 => "property",
+        8 => "field",
+        9 => "constructor",
+        10 => "enum",
+        11 => "interface",
+        12 => "function",
+        13 => "variable",
+        14 => "constant",
+        15 => "string",
+        16 => "number",
+        17 => "boolean",
+        18 => "array",
+        19 => "object",
+        22 => "enum_member",
+        23 => "struct",
+        26 => "type_parameter",
+        _ => "other",
+    }
+}
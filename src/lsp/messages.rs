@@ -0,0 +1,78 @@
+use serde::Serialize;
+use std::{collections::VecDeque, sync::Arc};
+use tokio::sync::Mutex;
+
+use super::progress::unix_millis;
+use crate::config::SERVER_MESSAGES_LEN;
+
+/// LSP `MessageType` of a `window/showMessage` or `window/logMessage`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageType {
+    Error,
+    Warning,
+    Info,
+    Log,
+    Debug,
+}
+
+impl MessageType {
+    pub fn from_lsp(value: u64) -> Self {
+        match value {
+            1 => MessageType::Error,
+            2 => MessageType::Warning,
+            3 => MessageType::Info,
+            5 => MessageType::Debug,
+            _ => MessageType::Log,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerMessage {
+    #[serde(rename = "type")]
+    pub kind: MessageType,
+    pub message: String,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+/// The most recent messages rust-analyzer sent to the user, oldest first.
+#[derive(Debug, Default)]
+pub struct ServerMessages {
+    messages: VecDeque<ServerMessage>,
+}
+
+impl ServerMessages {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, kind: MessageType, message: String) {
+        if self.messages.len() >= SERVER_MESSAGES_LEN {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(ServerMessage {
+            kind,
+            message,
+            timestamp: unix_millis(),
+        });
+    }
+
+    pub fn all(&self) -> Vec<ServerMessage> {
+        self.messages.iter().cloned().collect()
+    }
+
+    /// The latest message, if it reports an error.
+    pub fn last_error(&self) -> Option<&ServerMessage> {
+        self.messages
+            .back()
+            .filter(|message| message.kind == MessageType::Error)
+    }
+}
+
+pub type SharedMessages = Arc<Mutex<ServerMessages>>;
+
+pub fn new_shared_messages() -> SharedMessages {
+    Arc::new(Mutex::new(ServerMessages::new()))
+}
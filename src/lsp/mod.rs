@@ -1,7 +1,24 @@
+pub mod backend;
+pub mod binary;
 mod client;
+pub mod completion;
 mod connection;
+pub mod diagnostic_store;
+pub mod edits;
 mod handlers;
+pub mod messages;
+pub mod paths;
+pub mod position;
 pub mod progress;
+pub mod registrations;
+pub mod runnables;
 
-pub use client::RustAnalyzerClient;
+pub use backend::{BackendFuture, LspBackend};
+pub(crate) use client::content_hash;
+pub use client::{PinnedDocuments, RequestTimeout, RustAnalyzerClient};
+pub use diagnostic_store::{DiagnosticStore, SharedDiagnostics};
+pub use handlers::{symbol_kind_name, SymbolScope};
+pub use messages::{new_shared_messages, SharedMessages};
+pub use paths::PathStyle;
 pub use progress::{new_shared_progress, SharedProgress};
+pub use registrations::{FileWatcher, SharedRegistrations};
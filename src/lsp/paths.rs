@@ -0,0 +1,310 @@
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// Prefix for files under `CARGO_HOME`, such as registry sources.
+const CARGO_MARKER: &str = "~cargo/";
+
+/// Prefix for files of the toolchain's sysroot, such as the standard library.
+const RUST_MARKER: &str = "~rust/";
+
+/// Where crates from a registry are unpacked in `CARGO_HOME`, one directory
+/// per registry index.
+const REGISTRY_SOURCES: &str = "registry/src";
+
+/// Where the standard library's sources sit in the sysroot.
+const SYSROOT_LIBRARY: &str = "lib/rustlib/src/rust/library";
+
+/// `CARGO_HOME`, or `~/.cargo` when unset.
+pub fn cargo_home() -> Option<PathBuf> {
+    match std::env::var_os("CARGO_HOME") {
+        Some(cargo_home) => Some(PathBuf::from(cargo_home)),
+        None => Some(PathBuf::from(std::env::var_os("HOME")?).join(".cargo")),
+    }
+}
+
+/// Bytes of a path written as-is in a `file://` URI: the unreserved
+/// characters, `/`, and the delimiters `url`'s path encoding leaves alone,
+/// so URIs rust-analyzer echoes back match the ones sent byte for byte.
+fn is_uri_path_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"-._~/!$&'()*+,;=:@[]|^\\".contains(&byte)
+}
+
+/// The `file://` URI of `path`, percent-encoding spaces, `#`, `%` and
+/// anything else that can't appear in a URI's path.
+pub fn file_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for &byte in path.to_string_lossy().as_bytes() {
+        if is_uri_path_byte(byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+/// The path a `file://` URI names, percent-decoded; anything else is taken
+/// as a path already and returned unchanged.
+pub fn uri_path(uri: &str) -> PathBuf {
+    let Some(encoded) = uri.strip_prefix("file://") else {
+        return PathBuf::from(uri);
+    };
+    let encoded = encoded.strip_prefix("localhost").unwrap_or(encoded);
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut at = 0;
+    while at < bytes.len() {
+        let hex = bytes
+            .get(at + 1..at + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[at], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                at += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                at += 1;
+            }
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&decoded).into_owned())
+}
+
+/// Whether `path` is a Cargo manifest, which rust-analyzer answers hover,
+/// completion and diagnostics for.
+pub fn is_manifest(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == "Cargo.toml")
+}
+
+/// The LSP `languageId` rust-analyzer opens `path` as: `rust` for source
+/// files, `toml` for manifests, `None` for anything it doesn't read.
+pub fn language_id(path: &Path) -> Option<&'static str> {
+    if is_manifest(path) {
+        Some("toml")
+    } else if path.extension().is_some_and(|extension| extension == "rs") {
+        Some("rust")
+    } else {
+        None
+    }
+}
+
+/// How simplified results write file paths: absolute, as rust-analyzer
+/// reports them, or relative to the workspace root. Relative paths mark
+/// files outside the workspace by where they come from:
+/// `~cargo/tokio-1.38.0/src/lib.rs` for a registry crate and
+/// `~rust/core/src/option.rs` for the standard library.
+#[derive(Debug, Clone, Default)]
+pub struct PathStyle {
+    relative: bool,
+    workspace_root: PathBuf,
+    cargo_home: Option<PathBuf>,
+    sysroot: Option<PathBuf>,
+}
+
+impl PathStyle {
+    pub fn new(
+        relative: bool,
+        workspace_root: PathBuf,
+        cargo_home: Option<PathBuf>,
+        sysroot: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            relative,
+            workspace_root,
+            cargo_home,
+            sysroot,
+        }
+    }
+
+    /// Paths exactly as rust-analyzer reports them.
+    pub fn absolute() -> Self {
+        Self::default()
+    }
+
+    pub fn is_relative(&self) -> bool {
+        self.relative
+    }
+
+    /// The path of `uri` (a `file://` URI or a plain path) as results show it.
+    pub fn display(&self, uri: &str) -> String {
+        let path = uri_path(uri);
+        if !self.relative {
+            return path.display().to_string();
+        }
+        let path = path.as_path();
+        if let Ok(relative) = path.strip_prefix(&self.workspace_root) {
+            return match relative.as_os_str().is_empty() {
+                true => ".".to_string(),
+                false => relative.display().to_string(),
+            };
+        }
+        if let Some(rest) = within(path, self.cargo_home.as_deref()) {
+            // `registry/src/index.crates.io-<hash>/tokio-1.38.0/...`: the
+            // index directory says nothing the crate's name doesn't.
+            let rest = match rest.strip_prefix(REGISTRY_SOURCES) {
+                Ok(index) => index.components().skip(1).collect(),
+                Err(_) => rest.to_path_buf(),
+            };
+            return format!("{}{}", CARGO_MARKER, rest.display());
+        }
+        if let Some(rest) = within(path, self.sysroot.as_deref()) {
+            let rest = rest.strip_prefix(SYSROOT_LIBRARY).unwrap_or(rest);
+            return format!("{}{}", RUST_MARKER, rest.display());
+        }
+        path.display().to_string()
+    }
+
+    /// The file a path written with a `~cargo/` or `~rust/` marker stands
+    /// for, if it exists; `None` for unmarked paths.
+    pub fn expand(&self, path: &str) -> Option<PathBuf> {
+        if let Some(rest) = path.strip_prefix(CARGO_MARKER) {
+            let cargo_home = self.cargo_home.as_ref()?;
+            let registry = std::fs::read_dir(cargo_home.join(REGISTRY_SOURCES)).ok();
+            return registry
+                .into_iter()
+                .flatten()
+                .filter_map(|index| Some(index.ok()?.path().join(rest)))
+                .chain([cargo_home.join(rest)])
+                .find(|candidate| candidate.exists());
+        }
+        if let Some(rest) = path.strip_prefix(RUST_MARKER) {
+            let sysroot = self.sysroot.as_ref()?;
+            return [sysroot.join(SYSROOT_LIBRARY).join(rest), sysroot.join(rest)]
+                .into_iter()
+                .find(|candidate| candidate.exists());
+        }
+        None
+    }
+}
+
+/// What's left of `path` under `root`, if it's there.
+fn within<'a>(path: &'a Path, root: Option<&Path>) -> Option<&'a Path> {
+    path.strip_prefix(root?).ok()
+}
+
+/// Module paths of source files, worked out from where they sit in their
+/// package: `src/lib.rs` is the crate root and `src/net/http.rs` (or
+/// `src/net/http/mod.rs`) the module `<crate>::net::http`. Crate names are
+/// read from each package's `Cargo.toml` once.
+#[derive(Debug, Default)]
+pub struct ModulePaths {
+    crate_names: HashMap<PathBuf, Option<String>>,
+}
+
+impl ModulePaths {
+    /// The module `file` defines, or `None` when it isn't under a package's
+    /// `src` directory.
+    pub fn module_path(&mut self, file: &Path) -> Option<String> {
+        let package = file
+            .ancestors()
+            .skip(1)
+            .find(|dir| dir.join("Cargo.toml").is_file())?;
+        let modules: Vec<String> = file
+            .strip_prefix(package.join("src"))
+            .ok()?
+            .with_extension("")
+            .iter()
+            .map(|component| component.to_string_lossy().into_owned())
+            .collect();
+        let modules = match modules.as_slice() {
+            [root] if root == "lib" || root == "main" => &[],
+            [modules @ .., last] if last == "mod" => modules,
+            modules => modules,
+        };
+        let crate_name = self
+            .crate_names
+            .entry(package.to_path_buf())
+            .or_insert_with(|| crate_name(package))
+            .clone()?;
+        Some(
+            std::iter::once(crate_name)
+                .chain(modules.iter().cloned())
+                .collect::<Vec<_>>()
+                .join("::"),
+        )
+    }
+}
+
+/// The crate name of the package in `dir`: its `package.name` with dashes
+/// made underscores.
+fn crate_name(dir: &Path) -> Option<String> {
+    let manifest = std::fs::read_to_string(dir.join("Cargo.toml")).ok()?;
+    let manifest: toml::Value = toml::from_str(&manifest).ok()?;
+    let name = manifest.get("package")?.get("name")?.as_str()?;
+    Some(name.replace('-', "_"))
+}
+
+/// A file of a registry crate or of the standard library, told by where it
+/// sits: `registry/src/<index>/<crate>-<version>/` in `CARGO_HOME`, or
+/// `lib/rustlib/src/rust/library/<crate>/` in a sysroot.
+#[derive(Debug, PartialEq)]
+pub struct ExternalFile {
+    pub krate: String,
+    /// The crate's version; the sysroot's path doesn't say it.
+    pub version: Option<String>,
+    /// The file's path within the crate.
+    pub path: PathBuf,
+}
+
+impl ExternalFile {
+    pub fn of(file: &Path) -> Option<Self> {
+        let components: Vec<&str> = file.iter().filter_map(|c| c.to_str()).collect();
+        let find = |pattern: &[&str]| {
+            components
+                .windows(pattern.len())
+                .rposition(|window| window == pattern)
+                .map(|at| at + pattern.len())
+        };
+        let (krate, version, rest) = if let Some(at) = find(&["registry", "src"]) {
+            let (krate, version) = split_version(components.get(at + 1)?)?;
+            (krate, Some(version), &components[at + 2..])
+        } else {
+            let at = find(&["lib", "rustlib", "src", "rust", "library"])?;
+            (*components.get(at)?, None, &components[at + 1..])
+        };
+        if rest.is_empty() {
+            return None;
+        }
+        Some(Self {
+            krate: krate.to_string(),
+            version: version.map(str::to_string),
+            path: rest.iter().collect(),
+        })
+    }
+
+    /// A location in the file for results: the crate, version and path in
+    /// it, whether the file can be read here, and the absolute path as
+    /// rust-analyzer reported it.
+    pub fn location(&self, file: &Path, line: u64, col: u64) -> Value {
+        let mut location = json!({ "crate": self.krate });
+        if let Some(version) = &self.version {
+            location["version"] = json!(version);
+        }
+        location["path"] = json!(self.path.display().to_string());
+        location["line"] = json!(line);
+        location["col"] = json!(col);
+        location["readable"] = json!(std::fs::File::open(file).is_ok());
+        location["abs_path"] = json!(file.display().to_string());
+        location
+    }
+}
+
+/// `tokio-1.38.0` as `("tokio", "1.38.0")`: the version starts at the first
+/// dash followed by `<major>.<minor>.`, since crate names have dashes and
+/// digits too (`sha-1-0.10.1`).
+fn split_version(dir: &str) -> Option<(&str, &str)> {
+    dir.match_indices('-').find_map(|(at, _)| {
+        let version = &dir[at + 1..];
+        let mut parts = version.splitn(3, '.');
+        let numeric = |part: Option<&str>| {
+            part.is_some_and(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()))
+        };
+        (numeric(parts.next()) && numeric(parts.next()) && parts.next().is_some())
+            .then(|| (&dir[..at], version))
+    })
+}
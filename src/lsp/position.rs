@@ -0,0 +1,147 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+};
+
+/// What LSP columns count, as agreed at initialize. UTF-16 unless the
+/// server picks one of the others we offer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub enum PositionEncoding {
+    #[serde(rename = "utf-8")]
+    Utf8,
+    #[default]
+    #[serde(rename = "utf-16")]
+    Utf16,
+    #[serde(rename = "utf-32")]
+    Utf32,
+}
+
+impl PositionEncoding {
+    /// The `positionEncoding` from the server's capabilities.
+    pub fn from_lsp(kind: Option<&str>) -> Self {
+        match kind {
+            Some("utf-8") => PositionEncoding::Utf8,
+            Some("utf-32") => PositionEncoding::Utf32,
+            _ => PositionEncoding::Utf16,
+        }
+    }
+
+    /// Columns taken up by `ch`.
+    pub fn len(self, ch: char) -> u32 {
+        match self {
+            PositionEncoding::Utf8 => ch.len_utf8() as u32,
+            PositionEncoding::Utf16 => ch.len_utf16() as u32,
+            PositionEncoding::Utf32 => 1,
+        }
+    }
+
+    /// Offset of the `character`th character of `line`. Columns past the
+    /// end of the line count one per missing character.
+    pub fn char_to_units(self, line: &str, character: u32) -> u32 {
+        let mut units = 0;
+        let mut chars = line.chars();
+        for _ in 0..character {
+            units += chars.next().map_or(1, |ch| self.len(ch));
+        }
+        units
+    }
+
+    /// Character column of an offset into `line`. An offset inside a
+    /// character maps to that character.
+    pub fn units_to_char(self, line: &str, units: u32) -> u32 {
+        let mut seen = 0;
+        let mut characters = 0;
+        for ch in line.chars() {
+            if seen >= units {
+                return characters;
+            }
+            seen += self.len(ch);
+            characters += 1;
+        }
+        characters + units.saturating_sub(seen)
+    }
+}
+
+impl fmt::Display for PositionEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PositionEncoding::Utf8 => "utf-8",
+            PositionEncoding::Utf16 => "utf-16",
+            PositionEncoding::Utf32 => "utf-32",
+        })
+    }
+}
+
+/// Text of 0-based `line` in `content`, without its line ending.
+pub fn line_text(content: &str, line: u32) -> Option<&str> {
+    let text = content.split('\n').nth(line as usize)?;
+    Some(text.strip_suffix('\r').unwrap_or(text))
+}
+
+/// Document URIs referenced anywhere in an LSP response (`uri`,
+/// `targetUri`).
+pub fn uris_in(value: &Value) -> BTreeSet<String> {
+    fn collect(value: &Value, uris: &mut BTreeSet<String>) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    match (key.as_str(), value.as_str()) {
+                        ("uri" | "targetUri", Some(uri)) => {
+                            uris.insert(uri.to_string());
+                        }
+                        _ => collect(value, uris),
+                    }
+                }
+            }
+            Value::Array(values) => values.iter().for_each(|v| collect(v, uris)),
+            _ => {}
+        }
+    }
+    let mut uris = BTreeSet::new();
+    collect(value, &mut uris);
+    uris
+}
+
+/// Document text by URI, for translating between the character columns
+/// tools use and the columns of the protocol. Columns in documents it
+/// doesn't have are passed through unchanged.
+#[derive(Debug, Default)]
+pub struct DocumentColumns {
+    texts: HashMap<String, String>,
+    encoding: PositionEncoding,
+}
+
+impl DocumentColumns {
+    pub fn new(encoding: PositionEncoding) -> Self {
+        Self {
+            texts: HashMap::new(),
+            encoding,
+        }
+    }
+
+    pub fn insert(&mut self, uri: &str, text: String) {
+        self.texts.insert(uri.to_string(), text);
+    }
+
+    fn line(&self, uri: &str, line: u32) -> Option<&str> {
+        line_text(self.texts.get(uri)?, line)
+    }
+
+    /// Protocol column for a tool's `character` argument.
+    pub fn to_lsp(&self, uri: &str, line: u32, character: u32) -> u32 {
+        match self.line(uri, line) {
+            Some(text) => self.encoding.char_to_units(text, character),
+            None => character,
+        }
+    }
+
+    /// Character column for a position in an LSP response.
+    pub fn to_user(&self, uri: &str, line: u64, character: u64) -> u64 {
+        match self.line(uri, line as u32) {
+            Some(text) => self.encoding.units_to_char(text, character as u32) as u64,
+            None => character,
+        }
+    }
+}
@@ -1,8 +1,13 @@
 use serde::Serialize;
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
 use tokio::sync::Mutex;
 
+use crate::config::PROGRESS_HISTORY_LEN;
+
 #[derive(Debug, Clone, Serialize)]
 pub struct ProgressEntry {
     pub token: String,
@@ -11,18 +16,93 @@ pub struct ProgressEntry {
     pub message: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub percentage: Option<u32>,
+    /// Milliseconds since the Unix epoch.
+    pub started_at: u64,
+    #[serde(skip)]
+    started: Option<Instant>,
+}
+
+/// A progress task that has ended.
+#[derive(Debug, Clone, Serialize)]
+pub struct CompletedEntry {
+    pub token: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Milliseconds since the Unix epoch.
+    pub started_at: u64,
+    /// Milliseconds since the Unix epoch.
+    pub ended_at: u64,
+    pub duration_ms: u64,
+}
+
+/// How an indexing session is going overall: a 0-100 estimate across the
+/// active tasks and the title of the task that matters most.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IndexingEstimate {
+    pub percent: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// Weight of rust-analyzer's main startup tasks in the estimate; anything
+/// else (fetching metadata, `cargo check`, ...) counts once.
+const TASK_WEIGHTS: &[(&str, u32)] = &[
+    ("indexing", 6),
+    ("building crategraph", 2),
+    ("loading proc-macros", 2),
+];
+
+fn task_weight(entry: &ProgressEntry) -> u32 {
+    let token = entry.token.to_lowercase();
+    let title = entry.title.to_lowercase();
+    TASK_WEIGHTS
+        .iter()
+        .find(|(name, _)| title.contains(name) || token.contains(name))
+        .map_or(1, |(_, weight)| *weight)
+}
+
+/// Token prefixes of rust-analyzer's `cargo check` tasks: one per workspace
+/// (`rust-analyzer/flycheck/0`, ...), or before 2023, named for the command
+/// (`rustAnalyzer/cargo check`, `rustAnalyzer/cargo clippy`).
+const FLYCHECK_TOKEN_PREFIXES: &[&str] = &["rust-analyzer/flycheck/", "rustAnalyzer/cargo "];
+
+fn is_flycheck(token: &str) -> bool {
+    FLYCHECK_TOKEN_PREFIXES
+        .iter()
+        .any(|prefix| token.starts_with(prefix))
 }
 
-#[derive(Debug)]
+/// Match a task by exact token or case-insensitive title substring.
+fn task_matches(token: &str, title: &str, pattern: &str) -> bool {
+    token == pattern || title.to_lowercase().contains(&pattern.to_lowercase())
+}
+
+pub(crate) fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Default)]
 pub struct ProgressState {
     active: HashMap<String, ProgressEntry>,
+    /// Most recently completed tasks, oldest first.
+    completed: VecDeque<CompletedEntry>,
+    /// Highest estimate seen since tasks became active, so the reported
+    /// percentage never goes backwards when rust-analyzer restarts a phase.
+    session_percent: u32,
+    /// Tasks begun and ended since the server started; unlike `completed`,
+    /// never capped.
+    begun: u64,
+    ended: u64,
+    flychecks_begun: u64,
 }
 
 impl ProgressState {
     pub fn new() -> Self {
-        Self {
-            active: HashMap::new(),
-        }
+        Self::default()
     }
 
     pub fn begin(
@@ -32,6 +112,10 @@ impl ProgressState {
         message: Option<String>,
         percentage: Option<u32>,
     ) {
+        self.begun += 1;
+        if is_flycheck(&token) {
+            self.flychecks_begun += 1;
+        }
         self.active.insert(
             token.clone(),
             ProgressEntry {
@@ -39,8 +123,11 @@ impl ProgressState {
                 title,
                 message,
                 percentage,
+                started_at: unix_millis(),
+                started: Some(Instant::now()),
             },
         );
+        self.update_estimate();
     }
 
     pub fn report(&mut self, token: &str, message: Option<String>, percentage: Option<u32>) {
@@ -48,19 +135,139 @@ impl ProgressState {
             entry.message = message;
             entry.percentage = percentage;
         }
+        self.update_estimate();
     }
 
     pub fn end(&mut self, token: &str) {
-        self.active.remove(token);
+        let Some(entry) = self.active.remove(token) else {
+            return;
+        };
+        self.ended += 1;
+        let duration_ms = entry
+            .started
+            .map(|s| s.elapsed().as_millis() as u64)
+            .unwrap_or(0);
+        if self.completed.len() >= PROGRESS_HISTORY_LEN {
+            self.completed.pop_front();
+        }
+        self.completed.push_back(CompletedEntry {
+            token: entry.token,
+            title: entry.title,
+            message: entry.message,
+            started_at: entry.started_at,
+            ended_at: unix_millis(),
+            duration_ms,
+        });
+        self.update_estimate();
+    }
+
+    /// Weighted mean of the active tasks' percentages; tasks that report
+    /// none count as just started.
+    fn raw_percent(&self) -> u32 {
+        let (done, total) = self.active.values().fold((0, 0), |(done, total), entry| {
+            let weight = task_weight(entry);
+            let percent = entry.percentage.unwrap_or(0).min(100);
+            (done + weight * percent, total + weight)
+        });
+        done.checked_div(total).unwrap_or(100)
+    }
+
+    fn update_estimate(&mut self) {
+        self.session_percent = if self.active.is_empty() {
+            0
+        } else {
+            self.session_percent.max(self.raw_percent())
+        };
+    }
+
+    /// Overall progress of the current indexing session; 100 when idle.
+    pub fn indexing_estimate(&self) -> IndexingEstimate {
+        // The heaviest task, and among equals the one furthest behind.
+        let dominant = self.active.values().max_by_key(|entry| {
+            (
+                task_weight(entry),
+                std::cmp::Reverse(entry.percentage.unwrap_or(0)),
+            )
+        });
+        match dominant {
+            Some(entry) => IndexingEstimate {
+                percent: self.session_percent,
+                title: Some(entry.title.clone()),
+            },
+            None => IndexingEstimate {
+                percent: 100,
+                title: None,
+            },
+        }
+    }
+
+    /// Number of tasks ended so far, to notice one ending after a point.
+    pub fn ended_count(&self) -> u64 {
+        self.ended
+    }
+
+    /// Number of tasks begun so far.
+    pub fn begun_count(&self) -> u64 {
+        self.begun
+    }
+
+    /// Number of tasks other than cargo check begun so far.
+    pub fn indexing_begun(&self) -> u64 {
+        self.begun - self.flychecks_begun
+    }
+
+    /// Whether rust-analyzer has indexed and is idle: an indexing task has
+    /// begun, and no task is running.
+    pub fn has_indexed(&self) -> bool {
+        self.indexing_begun() > 0 && self.active.is_empty()
+    }
+
+    /// Number of `cargo check` tasks begun so far.
+    pub fn flychecks_begun(&self) -> u64 {
+        self.flychecks_begun
+    }
+
+    pub fn is_flychecking(&self) -> bool {
+        self.active.keys().any(|token| is_flycheck(token))
     }
 
     pub fn is_indexing(&self) -> bool {
         !self.active.is_empty()
     }
 
+    /// Whether a task other than cargo check is running, which is what
+    /// holds up index-backed tool calls.
+    pub fn is_indexing_workspace(&self) -> bool {
+        self.active.keys().any(|token| !is_flycheck(token))
+    }
+
     pub fn active_tasks(&self) -> Vec<ProgressEntry> {
         self.active.values().cloned().collect()
     }
+
+    pub fn completed_tasks(&self) -> Vec<CompletedEntry> {
+        self.completed.iter().cloned().collect()
+    }
+
+    /// The latest task matching `pattern` among those ended since
+    /// `ended_count()` returned `since`, once no matching task is still
+    /// active. Tasks that finished before then don't count.
+    pub fn finished_match(&self, pattern: &str, since: u64) -> Option<CompletedEntry> {
+        if self
+            .active
+            .values()
+            .any(|e| task_matches(&e.token, &e.title, pattern))
+        {
+            return None;
+        }
+        let ended_since = self.ended.saturating_sub(since) as usize;
+        self.completed
+            .iter()
+            .rev()
+            .take(ended_since)
+            .find(|e| task_matches(&e.token, &e.title, pattern))
+            .cloned()
+    }
 }
 
 pub type SharedProgress = Arc<Mutex<ProgressState>>;
@@ -0,0 +1,229 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::{collections::BTreeMap, path::Path, sync::Arc};
+use tokio::sync::Mutex;
+
+use super::paths::uri_path;
+
+/// `WatchKind` bits: create, change, delete. Watchers without a kind get all.
+const WATCH_ALL: u8 = 7;
+
+/// A file watcher rust-analyzer registered for
+/// `workspace/didChangeWatchedFiles`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileWatcher {
+    /// LSP glob, with a `RelativePattern`'s base path prepended.
+    pub glob: String,
+    /// `WatchKind` bitmask.
+    pub kind: u8,
+}
+
+impl FileWatcher {
+    /// Parse one `FileSystemWatcher`. The pattern is either a glob string or
+    /// a `RelativePattern` whose `baseUri` is a URI or a `WorkspaceFolder`.
+    pub fn from_lsp(watcher: &Value) -> Option<Self> {
+        let pattern = &watcher["globPattern"];
+        let glob = match pattern.as_str() {
+            Some(glob) => glob.to_string(),
+            None => {
+                let base = pattern["baseUri"]
+                    .as_str()
+                    .or_else(|| pattern["baseUri"]["uri"].as_str())?;
+                let base = uri_path(base);
+                format!(
+                    "{}/{}",
+                    base.to_string_lossy().trim_end_matches('/'),
+                    pattern["pattern"].as_str()?
+                )
+            }
+        };
+        let kind = watcher["kind"]
+            .as_u64()
+            .map_or(WATCH_ALL, |kind| kind as u8);
+        Some(Self { glob, kind })
+    }
+
+    /// Whether this watcher wants `change` (an LSP `FileChangeType`) to `path`.
+    pub fn matches(&self, path: &Path, change: u8) -> bool {
+        // FileChangeType 1, 2, 3 map onto WatchKind 1, 2, 4.
+        let bit = 1 << change.saturating_sub(1);
+        self.kind & bit != 0 && glob_matches(&self.glob, &path.to_string_lossy())
+    }
+}
+
+/// Whether `path` matches an LSP glob: `*` and `?` within a segment, `**`
+/// across segments, `{a,b}` alternatives, and `[a-z]` / `[!a-z]` classes. A
+/// pattern without a leading `/` (like `**/*.rs`) can match at any depth.
+pub fn glob_matches(glob: &str, path: &str) -> bool {
+    let path: Vec<char> = path.chars().collect();
+    expand_braces(glob).iter().any(|pattern| {
+        let pattern: Vec<char> = pattern.chars().collect();
+        if pattern.first() == Some(&'/') || path.first() != Some(&'/') {
+            return match_here(&pattern, &path);
+        }
+        // Relative patterns are tried against every suffix starting a segment.
+        path.iter()
+            .enumerate()
+            .filter(|(_, ch)| **ch == '/')
+            .any(|(i, _)| match_here(&pattern, &path[i + 1..]))
+    })
+}
+
+/// Every pattern a glob's `{a,b}` groups expand to.
+fn expand_braces(glob: &str) -> Vec<String> {
+    let Some(open) = glob.find('{') else {
+        return vec![glob.to_string()];
+    };
+    let mut depth = 0;
+    let mut alternatives = Vec::new();
+    let mut start = open + 1;
+    for (i, ch) in glob[open..].char_indices().map(|(i, ch)| (i + open, ch)) {
+        match ch {
+            '{' => depth += 1,
+            ',' if depth == 1 => {
+                alternatives.push(&glob[start..i]);
+                start = i + 1;
+            }
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    alternatives.push(&glob[start..i]);
+                    let (prefix, rest) = (&glob[..open], &glob[i + 1..]);
+                    return alternatives
+                        .into_iter()
+                        .flat_map(|alt| expand_braces(&format!("{}{}{}", prefix, alt, rest)))
+                        .collect();
+                }
+            }
+            _ => {}
+        }
+    }
+    // An unclosed `{` is matched literally.
+    vec![glob.to_string()]
+}
+
+fn match_here(pattern: &[char], path: &[char]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        ['*', '*'] => true,
+        ['*', '*', '/', rest @ ..] => {
+            match_here(rest, path)
+                || path
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, ch)| **ch == '/')
+                    .any(|(i, _)| match_here(rest, &path[i + 1..]))
+        }
+        ['*', rest @ ..] => {
+            let segment = path.iter().take_while(|ch| **ch != '/').count();
+            (0..=segment).any(|skip| match_here(rest, &path[skip..]))
+        }
+        ['?', rest @ ..] => {
+            matches!(path.first(), Some(ch) if *ch != '/') && match_here(rest, &path[1..])
+        }
+        ['[', rest @ ..] => {
+            let Some(close) = rest.iter().skip(1).position(|ch| *ch == ']') else {
+                return path.first() == Some(&'[') && match_here(rest, &path[1..]);
+            };
+            let (class, rest) = (&rest[..close + 1], &rest[close + 2..]);
+            match path.first() {
+                Some(&ch) if ch != '/' && class_matches(class, ch) => match_here(rest, &path[1..]),
+                _ => false,
+            }
+        }
+        [ch, rest @ ..] => path.first() == Some(ch) && match_here(rest, &path[1..]),
+    }
+}
+
+fn class_matches(class: &[char], ch: char) -> bool {
+    let (negated, class) = match class {
+        ['!' | '^', rest @ ..] => (true, rest),
+        _ => (false, class),
+    };
+    let mut found = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            found |= (class[i]..=class[i + 2]).contains(&ch);
+            i += 3;
+        } else {
+            found |= class[i] == ch;
+            i += 1;
+        }
+    }
+    found != negated
+}
+
+/// One `Registration` from `client/registerCapability`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Registration {
+    pub id: String,
+    pub method: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub file_watchers: Vec<FileWatcher>,
+}
+
+/// Capabilities rust-analyzer registered after initialize, by registration id.
+#[derive(Debug, Default)]
+pub struct Registrations {
+    registrations: BTreeMap<String, Registration>,
+}
+
+impl Registrations {
+    /// Record the `RegistrationParams` of a `client/registerCapability`.
+    pub fn register(&mut self, params: &Value) {
+        let Some(registrations) = params["registrations"].as_array() else {
+            return;
+        };
+        for registration in registrations {
+            let (Some(id), Some(method)) =
+                (registration["id"].as_str(), registration["method"].as_str())
+            else {
+                continue;
+            };
+            let file_watchers = registration["registerOptions"]["watchers"]
+                .as_array()
+                .map(|watchers| watchers.iter().filter_map(FileWatcher::from_lsp).collect())
+                .unwrap_or_default();
+            self.registrations.insert(
+                id.to_string(),
+                Registration {
+                    id: id.to_string(),
+                    method: method.to_string(),
+                    file_watchers,
+                },
+            );
+        }
+    }
+
+    /// Drop the registrations named by `client/unregisterCapability`, whose
+    /// list the protocol spells `unregisterations`.
+    pub fn unregister(&mut self, params: &Value) {
+        let list = params
+            .get("unregisterations")
+            .or_else(|| params.get("unregistrations"));
+        for unregistration in list.and_then(Value::as_array).into_iter().flatten() {
+            if let Some(id) = unregistration["id"].as_str() {
+                self.registrations.remove(id);
+            }
+        }
+    }
+
+    pub fn all(&self) -> Vec<Registration> {
+        self.registrations.values().cloned().collect()
+    }
+
+    /// Every file watcher currently registered.
+    pub fn file_watchers(&self) -> Vec<FileWatcher> {
+        self.registrations
+            .values()
+            .flat_map(|registration| registration.file_watchers.iter().cloned())
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.registrations.clear();
+    }
+}
+
+pub type SharedRegistrations = Arc<Mutex<Registrations>>;
@@ -0,0 +1,53 @@
+use serde_json::{json, Value};
+
+/// Label prefixes of the runnables that run tests, and the kind reported
+/// for each. Others (`run`, `bench`, `cargo check`) are not tests.
+const TEST_KINDS: &[(&str, &str)] = &[
+    ("test ", "test"),
+    ("test-mod ", "test_module"),
+    ("doctest ", "doctest"),
+];
+
+/// A test from `experimental/runnables`: its module path, kind, whether
+/// it's `#[ignore]`d, and the cargo invocation rust-analyzer computed, both
+/// as arguments and as a command line. `None` for runnables that aren't
+/// tests. The location is left as a `LocationLink` for the caller to format.
+pub fn simplify_test_runnable(runnable: &Value) -> Option<Value> {
+    let label = runnable["label"].as_str()?;
+    let (name, kind) = TEST_KINDS
+        .iter()
+        .find_map(|(prefix, kind)| Some((label.strip_prefix(prefix)?, *kind)))?;
+
+    let args = &runnable["args"];
+    let strings = |value: &Value| -> Vec<String> {
+        value
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|arg| arg.as_str().map(str::to_string))
+            .collect()
+    };
+    // `cargoExtraArgs` is folded into `cargoArgs` by newer releases.
+    let mut cargo_args = strings(&args["cargoArgs"]);
+    cargo_args.extend(strings(&args["cargoExtraArgs"]));
+    let executable_args = strings(&args["executableArgs"]);
+    let program = args["overrideCargo"].as_str().unwrap_or("cargo");
+
+    let mut command = vec![program.to_string()];
+    command.extend(cargo_args.iter().cloned());
+    if !executable_args.is_empty() {
+        command.push("--".to_string());
+        command.extend(executable_args.iter().cloned());
+    }
+
+    Some(json!({
+        "name": name,
+        "kind": kind,
+        "ignored": executable_args.iter().any(|arg| arg == "--ignored"),
+        "location": runnable["location"],
+        "cwd": args["cwd"].as_str().or(args["workspaceRoot"].as_str()),
+        "cargo_args": cargo_args,
+        "executable_args": executable_args,
+        "command": command.join(" ")
+    }))
+}
@@ -1,8 +1,16 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
-use rust_analyzer_server::RustAnalyzerMCPServer;
+use rust_analyzer_server::{
+    client::RemoteServer,
+    config::{Config, DEFAULT_PORT},
+    daemon, doctor,
+    http::ServeOptions,
+    install::{self, FileState, InstallOptions},
+    logging::{self, LogOptions},
+    RustAnalyzerMCPServer,
+};
 
 #[derive(Parser)]
 #[command(name = "rust-analyzer-server", about = "Standalone HTTP server for rust-analyzer")]
@@ -11,13 +19,125 @@ struct Cli {
     #[arg(short, long)]
     workspace: Option<PathBuf>,
 
-    /// Port to listen on
-    #[arg(short, long, default_value = "15423", env = "RUST_ANALYZER_PORT")]
-    port: u16,
+    /// Additional workspace folder rust-analyzer analyzes alongside the
+    /// workspace (repeatable)
+    #[arg(long = "extra-workspace", value_name = "PATH")]
+    extra_workspaces: Vec<PathBuf>,
 
-    /// Bind address
-    #[arg(short, long, default_value = "127.0.0.1")]
-    bind: String,
+    /// Configuration file (defaults to rust-analyzer-server.toml in the workspace root)
+    #[arg(short, long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Port to listen on [default: 15423] (0 picks a free port)
+    #[arg(short, long, env = "RUST_ANALYZER_PORT")]
+    port: Option<u16>,
+
+    /// Discovery file recording the bound port, pid, and workspace
+    /// (defaults to .rust-analyzer-server.json in the workspace root)
+    #[arg(long)]
+    port_file: Option<PathBuf>,
+
+    /// Bind address [default: 127.0.0.1]
+    #[arg(short, long)]
+    bind: Option<String>,
+
+    /// Run in the background, detached from the terminal
+    #[arg(long)]
+    daemon: bool,
+
+    /// Serve MCP over stdin/stdout for MCP clients (e.g. from .mcp.json);
+    /// the HTTP API is still served on the port when it's free
+    #[arg(long, conflicts_with = "daemon")]
+    stdio: bool,
+
+    /// Write logs to this file instead of stderr, rotated by size
+    /// (daemon mode defaults to a per-port file in the temp dir)
+    #[arg(long, global = true, env = "RUST_ANALYZER_LOG_FILE")]
+    log_file: Option<PathBuf>,
+
+    /// Log filter, e.g. `debug` or `rust_analyzer_server=trace` (overrides RUST_LOG)
+    #[arg(long, global = true)]
+    log_level: Option<String>,
+
+    /// With --log-file, also log to stderr
+    #[arg(long, global = true)]
+    log_stderr: bool,
+
+    /// Rotate the log file once it exceeds this many megabytes
+    #[arg(long, global = true, default_value_t = 10)]
+    log_max_size_mb: u64,
+
+    /// Number of rotated log files to keep
+    #[arg(long, global = true, default_value_t = 5)]
+    log_max_files: usize,
+
+    /// Pid file (daemon mode defaults to $XDG_RUNTIME_DIR/rust-analyzer-server-<port>.pid)
+    #[arg(long, env = "RUST_ANALYZER_PID_FILE")]
+    pid_file: Option<PathBuf>,
+
+    /// Token that authorizes privileged requests (sent as `Authorization: Bearer <token>`)
+    #[arg(long, env = "RUST_ANALYZER_AUTH_TOKEN", hide_env_values = true)]
+    auth_token: Option<String>,
+
+    /// rust-analyzer executable to run (overrides RUST_ANALYZER_BIN and the config file)
+    #[arg(long, global = true)]
+    rust_analyzer_path: Option<PathBuf>,
+
+    /// Start rust-analyzer immediately and warm its indexes instead of on the first tool call
+    #[arg(long)]
+    preload: bool,
+
+    /// Watch the workspace and send file changes made on disk to rust-analyzer
+    #[arg(long)]
+    watch: bool,
+
+    /// Warm rust-analyzer's caches in the background each time indexing finishes
+    #[arg(long)]
+    prewarm: bool,
+
+    /// Write file paths in tool results relative to the workspace root
+    #[arg(long)]
+    relative_paths: bool,
+
+    /// Cargo features rust-analyzer analyzes with (comma-separated or repeated)
+    #[arg(long, value_delimiter = ',')]
+    features: Vec<String>,
+
+    /// Analyze with every cargo feature enabled
+    #[arg(long)]
+    all_features: bool,
+
+    /// Analyze without the default cargo features
+    #[arg(long)]
+    no_default_features: bool,
+
+    /// Target triple rust-analyzer analyzes for (defaults to the host)
+    #[arg(long)]
+    target: Option<String>,
+
+    /// Don't run build scripts (also disables proc-macro expansion)
+    #[arg(long)]
+    no_build_scripts: bool,
+
+    /// Don't expand proc macros, e.g. where the proc-macro server can't run
+    #[arg(long)]
+    no_proc_macros: bool,
+
+    /// Run clippy instead of `cargo check` for diagnostics
+    #[arg(long)]
+    clippy: bool,
+
+    /// Make HTTP tool calls wait for indexing to finish instead of failing fast with a 503
+    #[arg(long)]
+    wait_for_index: bool,
+
+    /// Don't keep recent tool calls and their arguments for /api/v1/history
+    #[arg(long)]
+    no_history: bool,
+
+    /// Disable the /api/v1/shutdown endpoint (stop with Ctrl-C or SIGTERM only)
+    #[arg(long)]
+    no_remote_shutdown: bool,
 
     #[command(subcommand)]
     command: Option<Commands>,
@@ -29,26 +149,478 @@ enum Commands {
     Install {
         /// Target project path
         path: PathBuf,
+
+        /// Show which files would be created, overwritten (with a diff), or
+        /// left unchanged, without writing anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Install only these skills (comma-separated), e.g. ra-diagnostics,ra-hover
+        #[arg(long, value_delimiter = ',')]
+        only: Vec<String>,
+
+        /// Install every skill except these (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        except: Vec<String>,
+
+        /// Directory for the skill files instead of .claude/commands
+        /// (relative to the project unless absolute)
+        #[arg(long, value_name = "DIR")]
+        dest: Option<PathBuf>,
+
+        /// Don't create or edit CLAUDE.md
+        #[arg(long)]
+        no_claude_md: bool,
+
+        /// Port written into the skills, CLAUDE.md, and generated configs
+        #[arg(long, default_value_t = DEFAULT_PORT)]
+        port: u16,
+
+        /// Workspace the documented start commands use (defaults to the
+        /// target project)
+        #[arg(long)]
+        workspace: Option<PathBuf>,
+
+        /// Register the server (over stdio) in the project's .mcp.json,
+        /// keeping any other servers there
+        #[arg(long)]
+        mcp: bool,
+
+        /// Add start/stop tasks to .vscode/tasks.json and the server's port to
+        /// the terminal environment in .vscode/settings.json (merged)
+        #[arg(long)]
+        vscode: bool,
+
+        /// Install a Claude Code hook (.claude/hooks, merged into
+        /// .claude/settings.json) that reports rust-analyzer errors after
+        /// each edit to a .rs file
+        #[arg(long)]
+        hooks: bool,
+
+        /// Overwrite skill files changed since they were installed instead of
+        /// writing the new version next to them as *.md.new
+        #[arg(long)]
+        force: bool,
+
+        /// Check an existing installation against the bundled templates
+        /// instead of installing; exits 1 if anything is missing or stale
+        #[arg(long, conflicts_with = "dry_run")]
+        verify: bool,
+
+        /// With --verify, update the files that are missing or stale
+        #[arg(long, requires = "verify")]
+        fix: bool,
+
+        /// With --verify, also check that a server answers on the port
+        #[arg(long, requires = "verify")]
+        check_server: bool,
+    },
+    /// Remove installed skills and the CLAUDE.md section from a project
+    Uninstall {
+        /// Target project path
+        path: PathBuf,
+    },
+    /// Show the state of a running server (exit code 0 ready, 1 not ready, 2 unreachable)
+    Status {
+        /// Port of the server (defaults to the workspace discovery file, then the default port)
+        #[arg(short, long, env = "RUST_ANALYZER_PORT")]
+        port: Option<u16>,
+
+        /// Print the raw status JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Call a tool once and print the result
+    Call {
+        /// Tool name, e.g. rust_analyzer_hover
+        tool_name: String,
+
+        /// Tool argument as key=value (repeatable); numbers and booleans are coerced where the
+        /// tool takes them
+        #[arg(long = "arg", value_name = "KEY=VALUE")]
+        args: Vec<String>,
+
+        /// Port of a running server (defaults to the workspace discovery file, then the default
+        /// port)
+        #[arg(short, long, env = "RUST_ANALYZER_PORT")]
+        port: Option<u16>,
+
+        /// Run the tool in-process instead of calling a running server
+        #[arg(long)]
+        local: bool,
+
+        /// Workspace for --local (defaults to current directory)
+        #[arg(short, long)]
+        workspace: Option<PathBuf>,
+    },
+    /// List available tools
+    Tools {
+        /// Print names and descriptions as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Print full JSON including input schemas
+        #[arg(long)]
+        schema: bool,
+
+        /// Ask the running server instead of this binary (shows version skew)
+        #[arg(long)]
+        remote: bool,
+
+        /// Port of the server for --remote (defaults to the workspace discovery file, then the
+        /// default port)
+        #[arg(short, long, env = "RUST_ANALYZER_PORT")]
+        port: Option<u16>,
+    },
+    /// Check the environment: rust-analyzer, toolchain, workspace, and port
+    Doctor {
+        /// Workspace to check (defaults to current directory)
+        #[arg(short, long)]
+        workspace: Option<PathBuf>,
+
+        /// Port the server will use (defaults to the configured port)
+        #[arg(short, long, env = "RUST_ANALYZER_PORT")]
+        port: Option<u16>,
+    },
+    /// Write a ctags file of the workspace's symbols, using rust-analyzer in-process
+    Tags {
+        /// Workspace to index (defaults to current directory)
+        #[arg(short, long)]
+        workspace: Option<PathBuf>,
+
+        /// Tags file to write, inside the workspace (defaults to `tags` there)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Glob of files or directories to leave out, relative to the
+        /// workspace (repeatable)
+        #[arg(long, value_name = "GLOB")]
+        exclude: Vec<String>,
+    },
+    /// Write a SCIP index of the workspace's definitions and references
+    Index {
+        /// Workspace to index (defaults to current directory)
+        #[arg(short, long)]
+        workspace: Option<PathBuf>,
+
+        /// Index format; only scip is supported
+        #[arg(long, default_value = "scip")]
+        format: String,
+
+        /// Index file to write, inside the workspace (defaults to `index.scip`
+        /// there)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Glob of files or directories to leave out, relative to the
+        /// workspace (repeatable)
+        #[arg(long, value_name = "GLOB")]
+        exclude: Vec<String>,
+
+        /// Port of a running server (defaults to the workspace discovery file, then the default
+        /// port)
+        #[arg(short, long, env = "RUST_ANALYZER_PORT")]
+        port: Option<u16>,
+
+        /// Index with an in-process rust-analyzer instead of a running server
+        #[arg(long)]
+        local: bool,
+    },
+    /// Stop a running server
+    Stop {
+        /// Port the server is listening on
+        #[arg(short, long, default_value_t = DEFAULT_PORT, env = "RUST_ANALYZER_PORT")]
+        port: u16,
+
+        /// Pid file (defaults to $XDG_RUNTIME_DIR/rust-analyzer-server-<port>.pid)
+        #[arg(long)]
+        pid_file: Option<PathBuf>,
+
+        /// Auth token of the running server
+        #[arg(long, env = "RUST_ANALYZER_AUTH_TOKEN", hide_env_values = true)]
+        auth_token: Option<String>,
     },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
-
     let cli = Cli::parse();
 
+    // A daemonizing parent only spawns the child, which opens the log file itself.
+    logging::init(&LogOptions {
+        file: cli.log_file.clone().filter(|_| !cli.daemon),
+        stderr: cli.log_stderr,
+        level: cli.log_level.clone(),
+        max_size_mb: cli.log_max_size_mb,
+        max_files: cli.log_max_files,
+    })?;
+
     match cli.command {
-        Some(Commands::Install { path }) => {
+        Some(Commands::Install {
+            path,
+            dry_run,
+            only,
+            except,
+            dest,
+            no_claude_md,
+            port,
+            workspace,
+            mcp,
+            vscode,
+            hooks,
+            force,
+            verify,
+            fix,
+            check_server,
+        }) => {
             let target = path.canonicalize().unwrap_or(path);
-            rust_analyzer_server::install::install_skills(&target)?;
+            let workspace = workspace.map(|path| path.canonicalize().unwrap_or(path));
+            let options = InstallOptions {
+                only,
+                except,
+                dest,
+                no_claude_md,
+                port: Some(port),
+                workspace,
+                mcp,
+                vscode,
+                hooks,
+                force,
+            };
+            let plan = rust_analyzer_server::install::plan_install(&target, &options)?;
+            if verify {
+                let entries = install::verify_plan(&plan);
+                print!("{}", install::describe_verification(&entries));
+                let mut ok = entries.iter().all(|e| e.state == FileState::UpToDate);
+                if fix && !ok {
+                    install::apply_install(&plan)?;
+                    // Locally modified files are only replaced with --force.
+                    ok = force || entries.iter().all(|e| e.state != FileState::Modified);
+                }
+                if check_server {
+                    match RemoteServer::connect(Some(port), Duration::from_secs(5)).await {
+                        Ok(_) => println!("Server is reachable on port {}", port),
+                        Err(e) => {
+                            println!("{}", e);
+                            ok = false;
+                        }
+                    }
+                }
+                if !ok {
+                    std::process::exit(1);
+                }
+            } else if dry_run {
+                print!("{}", rust_analyzer_server::install::describe_plan(&plan));
+            } else {
+                rust_analyzer_server::install::apply_install(&plan)?;
+            }
+        }
+        Some(Commands::Uninstall { path }) => {
+            let target = path.canonicalize().unwrap_or(path);
+            rust_analyzer_server::install::uninstall_skills(&target)?;
+        }
+        Some(Commands::Status { port, json }) => {
+            std::process::exit(rust_analyzer_server::client::status(port, json).await);
+        }
+        Some(Commands::Call {
+            tool_name,
+            args,
+            port,
+            local,
+            workspace,
+        }) => {
+            let args = rust_analyzer_server::client::parse_tool_args(&tool_name, &args)?;
+            let local = if local {
+                let workspace = workspace.unwrap_or_else(|| {
+                    std::env::current_dir().expect("Failed to get current directory")
+                });
+                let mut config = Config::load(cli.config.as_deref(), &workspace)?;
+                if cli.rust_analyzer_path.is_some() {
+                    config.rust_analyzer_path = cli.rust_analyzer_path;
+                }
+                Some(RustAnalyzerMCPServer::with_config(workspace, config))
+            } else {
+                None
+            };
+            rust_analyzer_server::client::call(&tool_name, args, port, local).await?;
+        }
+        Some(Commands::Tools {
+            json,
+            schema,
+            remote,
+            port,
+        }) => {
+            rust_analyzer_server::client::tools(json, schema, remote, port).await?;
+        }
+        Some(Commands::Doctor { workspace, port }) => {
+            let workspace = workspace.unwrap_or_else(|| {
+                std::env::current_dir().expect("Failed to get current directory")
+            });
+            let mut config = Config::load(cli.config.as_deref(), &workspace)?;
+            if let Some(port) = port {
+                config.port = port;
+            }
+            if cli.rust_analyzer_path.is_some() {
+                config.rust_analyzer_path = cli.rust_analyzer_path;
+            }
+            let checks = doctor::run_checks(&workspace, &config).await;
+            doctor::print_report(&checks);
+            if doctor::has_failures(&checks) {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Tags {
+            workspace,
+            output,
+            exclude,
+        }) => {
+            let workspace = workspace.unwrap_or_else(|| {
+                std::env::current_dir().expect("Failed to get current directory")
+            });
+            let mut config = Config::load(cli.config.as_deref(), &workspace)?;
+            if cli.rust_analyzer_path.is_some() {
+                config.rust_analyzer_path = cli.rust_analyzer_path;
+            }
+            // Relative to where the command runs, unlike the tool's argument.
+            let output = match output {
+                Some(output) => std::env::current_dir()?.join(output),
+                None => workspace.join("tags"),
+            };
+            let args = serde_json::json!({ "output": output, "exclude": exclude });
+            let server = RustAnalyzerMCPServer::with_config(workspace, config);
+            rust_analyzer_server::client::call(
+                "rust_analyzer_export_tags",
+                args,
+                None,
+                Some(server),
+            )
+            .await?;
+        }
+        Some(Commands::Index {
+            workspace,
+            format,
+            output,
+            exclude,
+            port,
+            local,
+        }) => {
+            let workspace = workspace.unwrap_or_else(|| {
+                std::env::current_dir().expect("Failed to get current directory")
+            });
+            // Relative to where the command runs, unlike the tool's argument.
+            let output = match output {
+                Some(output) => std::env::current_dir()?.join(output),
+                None => workspace.join("index.scip"),
+            };
+            let args =
+                serde_json::json!({ "output": output, "format": format, "exclude": exclude });
+            let local = if local {
+                let mut config = Config::load(cli.config.as_deref(), &workspace)?;
+                if cli.rust_analyzer_path.is_some() {
+                    config.rust_analyzer_path = cli.rust_analyzer_path;
+                }
+                Some(RustAnalyzerMCPServer::with_config(workspace, config))
+            } else {
+                None
+            };
+            rust_analyzer_server::client::call("rust_analyzer_export_index", args, port, local)
+                .await?;
+        }
+        Some(Commands::Stop {
+            port,
+            pid_file,
+            auth_token,
+        }) => {
+            let pid_file = pid_file.unwrap_or_else(|| daemon::default_pid_file(port));
+            daemon::stop(port, &pid_file, auth_token.as_deref()).await?;
         }
         None => {
             let workspace = cli
                 .workspace
                 .unwrap_or_else(|| std::env::current_dir().expect("Failed to get current directory"));
-            let server = RustAnalyzerMCPServer::with_workspace(workspace);
-            rust_analyzer_server::http::serve(&cli.bind, cli.port, server).await?;
+
+            // CLI flags and env vars (via clap) override the config file.
+            let mut config = Config::load(cli.config.as_deref(), &workspace)?;
+            if let Some(port) = cli.port {
+                config.port = port;
+            }
+            if let Some(bind) = cli.bind {
+                config.bind = bind;
+            }
+            if cli.auth_token.is_some() {
+                config.auth_token = cli.auth_token;
+            }
+            if cli.rust_analyzer_path.is_some() {
+                config.rust_analyzer_path = cli.rust_analyzer_path;
+            }
+            if !cli.extra_workspaces.is_empty() {
+                // Relative to where the server was started, unlike the
+                // config file's, which are relative to the workspace.
+                config.extra_workspaces = cli
+                    .extra_workspaces
+                    .into_iter()
+                    .map(|path| path.canonicalize().unwrap_or(path))
+                    .collect();
+            }
+            if cli.preload {
+                config.preload = true;
+            }
+            if cli.watch {
+                config.watch = true;
+            }
+            if cli.prewarm {
+                config.prewarm = true;
+            }
+            if cli.relative_paths {
+                config.relative_paths = true;
+            }
+            if !cli.features.is_empty() {
+                config.cargo.features = cli.features;
+            }
+            if cli.all_features {
+                config.cargo.all_features = true;
+            }
+            if cli.no_default_features {
+                config.cargo.no_default_features = true;
+            }
+            if cli.target.is_some() {
+                config.cargo.target = cli.target;
+            }
+            if cli.no_build_scripts {
+                config.cargo.build_scripts = false;
+            }
+            if cli.no_proc_macros {
+                config.cargo.proc_macros = false;
+            }
+            config.cargo.validate()?;
+            if cli.clippy {
+                config.check.clippy = true;
+            }
+
+            if cli.daemon {
+                let pid_file = cli
+                    .pid_file
+                    .unwrap_or_else(|| daemon::default_pid_file(config.port));
+                let log_file = cli
+                    .log_file
+                    .unwrap_or_else(|| daemon::default_log_file(config.port));
+                daemon::spawn_daemon(&log_file, &pid_file)?;
+                return Ok(());
+            }
+
+            let _pid_file = cli.pid_file.map(daemon::PidFile::create).transpose()?;
+
+            let (bind, port) = (config.bind.clone(), config.port);
+            let options = ServeOptions {
+                auth_token: config.auth_token.clone(),
+                remote_shutdown: !cli.no_remote_shutdown,
+                port_file: cli.port_file,
+                stdio: cli.stdio,
+                wait_for_index: cli.wait_for_index,
+                history: !cli.no_history,
+            };
+            let server = RustAnalyzerMCPServer::with_config(workspace, config);
+            rust_analyzer_server::http::serve(&bind, port, server, options).await?;
         }
     }
 
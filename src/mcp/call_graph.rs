@@ -0,0 +1,410 @@
+use anyhow::Result;
+use log::{debug, info};
+use serde_json::{json, Map, Value};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    path::PathBuf,
+    time::Instant,
+};
+
+use crate::{
+    lsp::{
+        paths::{cargo_home, uri_path},
+        PathStyle, RequestTimeout, RustAnalyzerClient,
+    },
+    protocol::{
+        mcp::{ContentItem, ToolResult},
+        tool_error,
+    },
+};
+
+use super::{
+    cargo_metadata::{cargo_metadata, owning_package},
+    handlers::{check_retry_timeout, ToolParams},
+    server::RustAnalyzerMCPServer,
+};
+
+/// How many calls away from the root `rust_analyzer_call_graph` goes by
+/// default.
+const CALL_GRAPH_DEPTH: usize = 2;
+
+/// Most functions and calls a graph holds; the walk stops at either.
+const CALL_GRAPH_MAX_NODES: usize = 100;
+const CALL_GRAPH_MAX_EDGES: usize = 300;
+
+/// Which way the walk follows calls from the root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Direction {
+    Callers,
+    Callees,
+}
+
+/// A function in the graph: its name, and where it's defined.
+struct Node {
+    name: String,
+    uri: String,
+    line: u64,
+}
+
+/// Functions and calls found so far, up to the caps.
+#[derive(Default)]
+struct CallGraph {
+    nodes: Vec<Node>,
+    /// Node of each call hierarchy item, by its name's position.
+    ids: HashMap<(String, u64, u64), usize>,
+    /// Calls, as (caller, callee).
+    edges: BTreeSet<(usize, usize)>,
+    truncated: bool,
+}
+
+impl CallGraph {
+    /// The node of a call hierarchy item, added if new; `None` when it's
+    /// new and the graph is full.
+    fn node(&mut self, item: &Value) -> Option<usize> {
+        let uri = item["uri"].as_str()?;
+        let start = &item["selectionRange"]["start"];
+        let line = start["line"].as_u64()?;
+        let key = (uri.to_string(), line, start["character"].as_u64()?);
+        if let Some(id) = self.ids.get(&key) {
+            return Some(*id);
+        }
+        if self.nodes.len() >= CALL_GRAPH_MAX_NODES {
+            self.truncated = true;
+            return None;
+        }
+        self.nodes.push(Node {
+            name: item["name"].as_str().unwrap_or("?").to_string(),
+            uri: uri.to_string(),
+            line,
+        });
+        self.ids.insert(key, self.nodes.len() - 1);
+        Some(self.nodes.len() - 1)
+    }
+
+    /// Add a call; `false` when the graph is full.
+    fn edge(&mut self, caller: usize, callee: usize) -> bool {
+        if self.edges.len() >= CALL_GRAPH_MAX_EDGES && !self.edges.contains(&(caller, callee)) {
+            self.truncated = true;
+            return false;
+        }
+        self.edges.insert((caller, callee));
+        true
+    }
+}
+
+/// The call hierarchy items on the other end of `item`'s calls in
+/// `direction`. Requests that fail, other than by timing out, count as no
+/// calls, so one unanswerable function doesn't lose the rest of the graph.
+async fn calls_of(
+    client: &mut RustAnalyzerClient,
+    item: &Value,
+    direction: Direction,
+) -> Result<Vec<Value>> {
+    let (answer, end) = match direction {
+        Direction::Callers => (client.incoming_calls(item.clone()).await, "from"),
+        Direction::Callees => (client.outgoing_calls(item.clone()).await, "to"),
+    };
+    match answer {
+        Ok(calls) => Ok(calls
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|call| call[end].clone())
+            .collect()),
+        Err(e) if e.is::<RequestTimeout>() => Err(e),
+        Err(e) => {
+            debug!("call_graph: no {:?} of {}: {}", direction, item["name"], e);
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// Walk calls from `root` breadth first, `depth` calls deep in each of
+/// `directions`. Each function is expanded once per direction, so cycles
+/// and recursion end the walk instead of repeating it.
+async fn walk(
+    client: &mut RustAnalyzerClient,
+    root: &Value,
+    directions: &[Direction],
+    depth: usize,
+) -> Result<CallGraph> {
+    let mut graph = CallGraph::default();
+    let Some(root_id) = graph.node(root) else {
+        return Ok(graph);
+    };
+    let mut queue: VecDeque<(Value, usize, usize, Direction)> = directions
+        .iter()
+        .map(|direction| (root.clone(), root_id, 0, *direction))
+        .collect();
+    let mut expanded = HashSet::new();
+
+    'walk: while let Some((item, id, distance, direction)) = queue.pop_front() {
+        if distance >= depth || !expanded.insert((id, direction)) {
+            continue;
+        }
+        for other in calls_of(client, &item, direction).await? {
+            let Some(other_id) = graph.node(&other) else {
+                continue;
+            };
+            let (caller, callee) = match direction {
+                Direction::Callers => (other_id, id),
+                Direction::Callees => (id, other_id),
+            };
+            if !graph.edge(caller, callee) {
+                break 'walk;
+            }
+            queue.push_back((other, other_id, distance + 1, direction));
+        }
+    }
+    Ok(graph)
+}
+
+/// `"..."` for DOT, with quotes and backslashes escaped.
+fn dot_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Where a graph's nodes are: their paths relative to the workspace, for
+/// labels, and the workspace's cargo metadata, for crate clusters when it
+/// can be had.
+pub(super) struct GraphPlaces {
+    path_style: PathStyle,
+    metadata: Option<Value>,
+}
+
+impl GraphPlaces {
+    pub(super) async fn of(server: &RustAnalyzerMCPServer, tool: &str) -> Self {
+        let metadata = cargo_metadata(&server.workspace_root, &server.metadata_cache)
+            .await
+            .map(|(metadata, _)| metadata)
+            .map_err(|e| debug!("{}: no crate clusters: {}", tool, e))
+            .ok();
+        let canonical = |path: PathBuf| path.canonicalize().unwrap_or(path);
+        let path_style = PathStyle::new(
+            true,
+            canonical(server.workspace_root.clone()),
+            cargo_home().map(canonical),
+            None,
+        );
+        Self {
+            path_style,
+            metadata,
+        }
+    }
+
+    /// `path:line` of a node.
+    pub(super) fn location(&self, uri: &str, line: u64) -> String {
+        format!("{}:{}", self.path_style.display(uri), line)
+    }
+
+    /// The workspace package `uri` belongs to.
+    pub(super) fn package(&self, uri: &str) -> Option<String> {
+        owning_package(self.metadata.as_ref()?, &uri_path(uri)).map(str::to_string)
+    }
+}
+
+/// A GraphViz DOT digraph of boxes labeled `labels`, `n0` (the root) in
+/// bold, each grouped in a cluster for its package, with `edges` (`n1 ->
+/// n0` and any attributes) and a graph label saying when the graph was cut
+/// short.
+pub(super) fn dot_graph(
+    name: &str,
+    labels: &[String],
+    packages: &[Option<String>],
+    edges: &[String],
+    notice: Option<&str>,
+) -> String {
+    let node = |id: usize| {
+        let bold = if id == 0 { ", style=bold" } else { "" };
+        let label = dot_string(&labels[id]).replace('\n', "\\n");
+        format!("n{} [label={}{}];", id, label, bold)
+    };
+
+    let mut dot = vec![
+        format!("digraph {} {{", name),
+        "  rankdir=LR;".to_string(),
+        "  node [shape=box, fontname=\"monospace\"];".to_string(),
+    ];
+    let mut clusters: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (id, package) in packages.iter().enumerate() {
+        match package {
+            Some(package) => clusters.entry(package).or_default().push(id),
+            None => dot.push(format!("  {}", node(id))),
+        }
+    }
+    for (index, (package, ids)) in clusters.iter().enumerate() {
+        dot.push(format!("  subgraph cluster_{} {{", index));
+        dot.push(format!("    label={};", dot_string(package)));
+        dot.extend(ids.iter().map(|id| format!("    {}", node(*id))));
+        dot.push("  }".to_string());
+    }
+    dot.extend(edges.iter().map(|edge| format!("  {};", edge)));
+    if let Some(notice) = notice {
+        dot.push(format!("  label={};", dot_string(notice)));
+    }
+    dot.push("}".to_string());
+    dot.join("\n")
+}
+
+/// The `format` argument of a graph tool: whether it's DOT, the default,
+/// rather than JSON.
+pub(super) fn dot_format_arg(args: &Value) -> Result<bool> {
+    match args["format"].as_str() {
+        None | Some("dot") => Ok(true),
+        Some("json") => Ok(false),
+        Some(other) => Err(tool_error!(
+            InvalidParams,
+            "Unknown format '{}'; expected dot or json",
+            other
+        )),
+    }
+}
+
+/// The `depth` argument of a graph tool.
+pub(super) fn depth_arg(args: &Value, default: usize) -> Result<usize> {
+    match &args["depth"] {
+        Value::Null => Ok(default),
+        depth => depth
+            .as_u64()
+            .map(|depth| depth as usize)
+            .ok_or_else(|| tool_error!(InvalidParams, "depth must be a whole number")),
+    }
+}
+
+/// The graph as JSON: the functions, and for each caller the functions it
+/// calls.
+fn to_json(graph: &CallGraph, places: &GraphPlaces, notice: Option<&str>) -> Value {
+    let nodes: Vec<Value> = graph
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(id, node)| {
+            let mut value = json!({
+                "id": format!("n{}", id),
+                "name": node.name,
+                "location": places.location(&node.uri, node.line)
+            });
+            if let Some(package) = places.package(&node.uri) {
+                value["crate"] = json!(package);
+            }
+            value
+        })
+        .collect();
+    let mut calls: Map<String, Value> = Map::new();
+    for (caller, callee) in &graph.edges {
+        let callees = calls
+            .entry(format!("n{}", caller))
+            .or_insert_with(|| json!([]));
+        if let Some(callees) = callees.as_array_mut() {
+            callees.push(json!(format!("n{}", callee)));
+        }
+    }
+    let mut result = json!({ "root": "n0", "nodes": nodes, "calls": calls });
+    if let Some(notice) = notice {
+        result["truncated"] = json!(notice);
+    }
+    result
+}
+
+/// `rust_analyzer_call_graph`: the functions calling, or called by, the
+/// one at a position, `depth` calls deep, as GraphViz DOT or JSON.
+pub(crate) async fn handle_call_graph(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let (line, character) = ToolParams::extract_position(&args)?;
+    let directions: &[Direction] = match args["direction"].as_str() {
+        None | Some("both") => &[Direction::Callers, Direction::Callees],
+        Some("callers") => &[Direction::Callers],
+        Some("callees") => &[Direction::Callees],
+        Some(other) => {
+            return Err(tool_error!(
+                InvalidParams,
+                "Unknown direction '{}'; expected callers, callees or both",
+                other
+            ))
+        }
+    };
+    let dot = dot_format_arg(&args)?;
+    let depth = depth_arg(&args, CALL_GRAPH_DEPTH)?;
+
+    let uri = server
+        .open_document_if_needed(&file_path, args["content"].as_str())
+        .await?;
+    let places = GraphPlaces::of(server, "call_graph").await;
+
+    let Some(client) = &mut server.client else {
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
+    };
+
+    // Retry logic: wait for indexing to complete
+    let retry_interval = server.config.retry_interval();
+    let indexing_timeout = server.config.indexing_timeout();
+    let start = Instant::now();
+    let mut logged_waiting = false;
+
+    let root = loop {
+        match client.prepare_call_hierarchy(&uri, line, character).await {
+            Ok(items) if items.as_array().is_some_and(|items| !items.is_empty()) => {
+                if logged_waiting {
+                    info!("call_graph: Indexing complete, returning results");
+                }
+                break items[0].clone();
+            }
+            Err(e) if e.is::<RequestTimeout>() => return Err(e),
+            Ok(_) | Err(_) => {
+                check_retry_timeout(
+                    "call_graph",
+                    &start,
+                    indexing_timeout,
+                    &mut logged_waiting,
+                    client,
+                )?;
+                tokio::time::sleep(retry_interval).await;
+            }
+        }
+    };
+
+    let graph = walk(client, &root, directions, depth).await?;
+    let notice = graph.truncated.then(|| {
+        format!(
+            "Truncated at {} functions or {} calls; lower depth or pick one direction for a complete graph",
+            CALL_GRAPH_MAX_NODES, CALL_GRAPH_MAX_EDGES
+        )
+    });
+
+    let text = match dot {
+        true => {
+            let labels: Vec<String> = graph
+                .nodes
+                .iter()
+                .map(|node| {
+                    format!(
+                        "fn {}\n{}",
+                        node.name,
+                        places.location(&node.uri, node.line)
+                    )
+                })
+                .collect();
+            let packages: Vec<Option<String>> = graph
+                .nodes
+                .iter()
+                .map(|node| places.package(&node.uri))
+                .collect();
+            let edges: Vec<String> = graph
+                .edges
+                .iter()
+                .map(|(caller, callee)| format!("n{} -> n{}", caller, callee))
+                .collect();
+            dot_graph("calls", &labels, &packages, &edges, notice.as_deref())
+        }
+        false => serde_json::to_string_pretty(&to_json(&graph, &places, notice.as_deref()))?,
+    };
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text,
+        }],
+    })
+}
@@ -0,0 +1,203 @@
+use anyhow::Result;
+use log::debug;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::{
+    lsp::{paths::uri_path, position::DocumentColumns, RustAnalyzerClient},
+    protocol::tool_error,
+};
+
+use super::{context::numbered_lines, describe::parse_hover};
+
+/// What `rust_analyzer_incoming_calls` adds to each caller beyond its name
+/// and location, and which page of callers it returns.
+#[derive(Debug, Default)]
+pub(crate) struct CallerOptions {
+    /// The caller's signature.
+    with_signature: bool,
+    /// The lines of the calls, read from disk.
+    with_snippet: bool,
+    limit: Option<usize>,
+    offset: Option<usize>,
+}
+
+impl CallerOptions {
+    pub(crate) fn parse(args: &Value) -> Result<Self> {
+        let flag = |name: &str| match &args[name] {
+            Value::Null => Ok(false),
+            Value::Bool(flag) => Ok(*flag),
+            _ => Err(tool_error!(InvalidParams, "{} must be true or false", name)),
+        };
+        let count = |name: &str| match &args[name] {
+            Value::Null => Ok(None),
+            value => value
+                .as_u64()
+                .map(|count| Some(count as usize))
+                .ok_or_else(|| tool_error!(InvalidParams, "{} must be a whole number", name)),
+        };
+        Ok(Self {
+            with_signature: flag("with_signature")?,
+            with_snippet: flag("with_snippet")?,
+            limit: count("limit")?,
+            offset: count("offset")?,
+        })
+    }
+
+    /// The calls of the requested page.
+    pub(crate) fn page<'a>(&self, calls: &'a [Value]) -> &'a [Value] {
+        let start = self.offset.unwrap_or(0).min(calls.len());
+        let end = match self.limit {
+            Some(limit) => start.saturating_add(limit).min(calls.len()),
+            None => calls.len(),
+        };
+        &calls[start..end]
+    }
+
+    /// The callers as returned: the list itself, or with `limit` or
+    /// `offset`, the page with how many callers there are in all and where
+    /// the next page starts.
+    pub(crate) fn shape(&self, callers: Vec<Value>, total: usize) -> Value {
+        if self.limit.is_none() && self.offset.is_none() {
+            return json!(callers);
+        }
+        let end = self.offset.unwrap_or(0).saturating_add(callers.len());
+        let mut page = json!({ "callers": callers, "total": total });
+        if end < total {
+            page["next_offset"] = json!(end);
+        }
+        page
+    }
+
+    /// Add the `signature` and `call_sites` asked for to `caller`, the
+    /// simplified form of `call`. Whatever can't be found is left out.
+    pub(crate) async fn add_details(
+        &self,
+        client: &mut RustAnalyzerClient,
+        call: &Value,
+        caller: &mut Value,
+        lookups: &mut CallerLookups,
+    ) {
+        let from = &call["from"];
+        let Some(uri) = from["uri"].as_str() else {
+            return;
+        };
+        if self.with_signature {
+            if let Some(signature) = lookups.signature(client, uri, from).await {
+                caller["signature"] = json!(signature);
+            }
+        }
+        if self.with_snippet {
+            let call_sites = lookups.call_sites(uri, &call["fromRanges"]).await;
+            if !call_sites.is_empty() {
+                caller["call_sites"] = json!(call_sites);
+            }
+        }
+    }
+}
+
+/// Document symbols and file lines looked up for callers, kept for the
+/// other callers in the same file.
+#[derive(Default)]
+pub(crate) struct CallerLookups {
+    symbols: HashMap<String, Value>,
+    files: HashMap<String, Option<String>>,
+}
+
+impl CallerLookups {
+    /// The caller's signature: the call hierarchy item's `detail`, else
+    /// that of the document symbol at the caller, else the signature its
+    /// hover shows.
+    async fn signature(
+        &mut self,
+        client: &mut RustAnalyzerClient,
+        uri: &str,
+        from: &Value,
+    ) -> Option<String> {
+        if let Some(detail) = from["detail"].as_str().filter(|d| !d.is_empty()) {
+            return Some(detail.to_string());
+        }
+
+        let start = &from["selectionRange"]["start"];
+        if !self.symbols.contains_key(uri) {
+            let symbols = client.document_symbols(uri).await.unwrap_or_else(|e| {
+                debug!("No document symbols for {}: {}", uri, e);
+                Value::Null
+            });
+            self.symbols.insert(uri.to_string(), symbols);
+        }
+        if let Some(detail) = symbol_detail_at(&self.symbols[uri], start) {
+            return Some(detail);
+        }
+
+        let line = start["line"].as_u64()?;
+        let character = start["character"].as_u64()?;
+        let character = client
+            .document_columns([uri])
+            .await
+            .to_user(uri, line, character);
+        let hover = client
+            .hover(uri, line as u32, character as u32)
+            .await
+            .ok()?;
+        parse_hover(&hover).signature
+    }
+
+    /// The numbered lines of the calls in `from_ranges`, each line once.
+    async fn call_sites(&mut self, uri: &str, from_ranges: &Value) -> Vec<String> {
+        if !self.files.contains_key(uri) {
+            let content = tokio::fs::read_to_string(uri_path(uri)).await.ok();
+            self.files.insert(uri.to_string(), content);
+        }
+        let Some(content) = &self.files[uri] else {
+            return Vec::new();
+        };
+        let lines: Vec<&str> = content.lines().collect();
+
+        let mut numbers: Vec<u64> = from_ranges
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|range| range["start"]["line"].as_u64())
+            .collect();
+        numbers.sort_unstable();
+        numbers.dedup();
+        numbers
+            .into_iter()
+            .filter(|&number| (number as usize) < lines.len())
+            .map(|number| numbered_lines(&lines, number, number, number))
+            .collect()
+    }
+}
+
+/// The `detail` of the document symbol, among `symbols` and their
+/// children, whose name starts at `start`.
+fn symbol_detail_at(symbols: &Value, start: &Value) -> Option<String> {
+    symbols.as_array()?.iter().find_map(|symbol| {
+        if symbol["selectionRange"]["start"] == *start {
+            let detail = symbol["detail"].as_str().filter(|d| !d.is_empty());
+            return detail.map(str::to_string);
+        }
+        symbol_detail_at(&symbol["children"], start)
+    })
+}
+
+/// A call from `callHierarchy/incomingCalls` as `{caller, location}`.
+pub(crate) fn simplify_incoming_call(
+    call: &Value,
+    columns: &DocumentColumns,
+    client: &RustAnalyzerClient,
+) -> Option<Value> {
+    let from = &call["from"];
+    let name = from["name"].as_str()?;
+    let uri = from["uri"].as_str()?;
+    let line = from["range"]["start"]["line"].as_u64()?;
+    let char = from["range"]["start"]["character"].as_u64()?;
+    let char = columns.to_user(uri, line, char);
+    let path = client.path_style.display(uri);
+
+    Some(json!({
+        "caller": name,
+        "location": format!("{}:{}:{}", path, line, char)
+    }))
+}
@@ -0,0 +1,115 @@
+use anyhow::Result;
+use log::info;
+use serde_json::{json, Value};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{lsp::SharedProgress, protocol::tool_error};
+
+use super::server::RustAnalyzerMCPServer;
+
+/// The `cargo check` a `rust_analyzer_cargo_check` call started, which later
+/// calls join instead of starting their own while it runs.
+pub(crate) struct PendingFlycheck {
+    progress: SharedProgress,
+    /// `flychecks_begun` when it was requested; the check is the first to
+    /// begin after.
+    baseline: u64,
+    started: Instant,
+}
+
+/// A requested check, waited on without the server lock.
+pub(crate) struct FlycheckWait {
+    progress: SharedProgress,
+    baseline: u64,
+    started: Instant,
+    coalesced: bool,
+    timeout: Duration,
+    poll_interval: Duration,
+}
+
+/// Ask rust-analyzer to check the workspace, or join the check an earlier
+/// call asked for if it hasn't finished.
+pub(crate) async fn start_cargo_check(
+    server: &mut RustAnalyzerMCPServer,
+    timeout: Option<Duration>,
+) -> Result<FlycheckWait> {
+    let timeout = timeout.unwrap_or_else(|| server.config.cargo_check_timeout());
+    let poll_interval = server.config.retry_interval();
+    let Some(progress) = server.progress_handle() else {
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
+    };
+
+    if let Some(pending) = &server.pending_flycheck {
+        // A check outliving its caller's timeout is stuck; start over.
+        if Arc::ptr_eq(&pending.progress, &progress)
+            && pending.started.elapsed() < timeout
+            && !finished(&progress, pending.baseline).await
+        {
+            return Ok(FlycheckWait {
+                progress,
+                baseline: pending.baseline,
+                started: pending.started,
+                coalesced: true,
+                timeout,
+                poll_interval,
+            });
+        }
+    }
+
+    let baseline = progress.lock().await.flychecks_begun();
+    let Some(client) = &mut server.client else {
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
+    };
+    info!("Running cargo check");
+    client.run_flycheck().await?;
+    let started = Instant::now();
+    server.pending_flycheck = Some(PendingFlycheck {
+        progress: Arc::clone(&progress),
+        baseline,
+        started,
+    });
+
+    Ok(FlycheckWait {
+        progress,
+        baseline,
+        started,
+        coalesced: false,
+        timeout,
+        poll_interval,
+    })
+}
+
+/// Whether a check began after `baseline` and none is running now.
+async fn finished(progress: &SharedProgress, baseline: u64) -> bool {
+    let progress = progress.lock().await;
+    progress.flychecks_begun() > baseline && !progress.is_flychecking()
+}
+
+impl FlycheckWait {
+    /// Wait for the check to finish or the timeout to pass, and describe
+    /// how it went.
+    pub(crate) async fn wait(self) -> Value {
+        let deadline = self.started + self.timeout;
+        let mut completed = finished(&self.progress, self.baseline).await;
+        while !completed && Instant::now() < deadline {
+            tokio::time::sleep(self.poll_interval).await;
+            completed = finished(&self.progress, self.baseline).await;
+        }
+
+        let mut flycheck = json!({
+            "completed": completed,
+            "duration_ms": self.started.elapsed().as_millis() as u64,
+            "coalesced": self.coalesced,
+        });
+        if !completed {
+            flycheck["note"] = json!(format!(
+                "cargo check did not finish within {} seconds; diagnostics may be stale",
+                self.timeout.as_secs()
+            ));
+        }
+        flycheck
+    }
+}
@@ -0,0 +1,513 @@
+use anyhow::{anyhow, Result};
+use log::info;
+use serde_json::{json, Value};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use crate::{
+    config::CargoSettings,
+    protocol::{
+        mcp::{ContentItem, ToolResult},
+        tool_error,
+    },
+};
+
+/// Library target kinds of every flavour. Libraries and binaries are the
+/// targets worth listing; tests, examples, benches and build scripts are
+/// left to `package` detail.
+const LIB_TARGET_KINDS: &[&str] = &["lib", "rlib", "dylib", "cdylib", "staticlib", "proc-macro"];
+
+/// The last `cargo metadata` output, and the manifests it was computed from
+/// with their modification times.
+struct CachedMetadata {
+    workspace: PathBuf,
+    manifests: Vec<(PathBuf, Option<SystemTime>)>,
+    metadata: Value,
+}
+
+#[derive(Default)]
+pub struct MetadataCache(Option<CachedMetadata>);
+
+pub type SharedMetadataCache = Arc<std::sync::Mutex<MetadataCache>>;
+
+/// `Cargo.toml` and `Cargo.lock` of the workspace root, plus each member's
+/// manifest once they are known.
+fn manifests(workspace: &Path, metadata: Option<&Value>) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let mut paths = vec![workspace.join("Cargo.toml"), workspace.join("Cargo.lock")];
+    if let Some(metadata) = metadata {
+        paths.extend(
+            members(metadata)
+                .filter_map(|package| package["manifest_path"].as_str())
+                .map(PathBuf::from),
+        );
+    }
+    paths.sort();
+    paths.dedup();
+    paths
+        .into_iter()
+        .map(|path| {
+            let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+            (path, modified)
+        })
+        .collect()
+}
+
+/// `cargo metadata` for `workspace`, reused until a manifest or the lock
+/// file changes. The cache lock is never held while cargo runs. Returns
+/// whether the result came from the cache.
+pub async fn cargo_metadata(
+    workspace: &Path,
+    cache: &SharedMetadataCache,
+) -> Result<(Value, bool)> {
+    {
+        let cache = cache.lock().unwrap();
+        if let Some(cached) = &cache.0 {
+            if cached.workspace == workspace
+                && manifests(workspace, Some(&cached.metadata)) == cached.manifests
+            {
+                return Ok((cached.metadata.clone(), true));
+            }
+        }
+    }
+
+    info!("Running cargo metadata in {}", workspace.display());
+    let output = tokio::process::Command::new("cargo")
+        .args(["metadata", "--format-version", "1"])
+        .current_dir(workspace)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await
+        .map_err(|e| anyhow!("Failed to run cargo: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "cargo metadata failed:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let metadata: Value = serde_json::from_slice(&output.stdout)?;
+
+    // Taken after cargo ran, since it writes a missing Cargo.lock.
+    cache.lock().unwrap().0 = Some(CachedMetadata {
+        workspace: workspace.to_path_buf(),
+        manifests: manifests(workspace, Some(&metadata)),
+        metadata: metadata.clone(),
+    });
+    Ok((metadata, false))
+}
+
+/// The workspace's member packages.
+fn members(metadata: &Value) -> impl Iterator<Item = &Value> {
+    let ids: Vec<&Value> = metadata["workspace_members"]
+        .as_array()
+        .map(|ids| ids.iter().collect())
+        .unwrap_or_default();
+    metadata["packages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(move |package| ids.contains(&&package["id"]))
+}
+
+/// Directories of the workspace's member packages.
+pub fn member_dirs(metadata: &Value) -> Vec<PathBuf> {
+    members(metadata)
+        .filter_map(|package| package["manifest_path"].as_str())
+        .filter_map(|manifest| Path::new(manifest).parent().map(Path::to_path_buf))
+        .collect()
+}
+
+/// Each workspace member's name, version, manifest, lib and bin targets, and
+/// direct dependencies as written in its manifest.
+pub fn summarize_metadata(metadata: &Value) -> Value {
+    let members: Vec<Value> = members(metadata)
+        .map(|package| {
+            let targets: Vec<Value> = package["targets"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|target| {
+                    let kind = target["kind"].as_array()?.iter().find_map(|kind| {
+                        kind.as_str()
+                            .filter(|kind| LIB_TARGET_KINDS.contains(kind) || *kind == "bin")
+                    })?;
+                    Some(json!({
+                        "name": target["name"],
+                        "kind": kind,
+                        "src_path": target["src_path"]
+                    }))
+                })
+                .collect();
+            let dependencies: Vec<Value> = package["dependencies"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|dependency| {
+                    let mut summary = json!({
+                        "name": dependency["name"],
+                        "req": dependency["req"]
+                    });
+                    // Normal, required, and unrenamed dependencies are the
+                    // common case; only say when one isn't.
+                    if let Some(kind) = dependency["kind"].as_str() {
+                        summary["kind"] = json!(kind);
+                    }
+                    if dependency["optional"] == true {
+                        summary["optional"] = json!(true);
+                    }
+                    if let Some(rename) = dependency["rename"].as_str() {
+                        summary["rename"] = json!(rename);
+                    }
+                    summary
+                })
+                .collect();
+            json!({
+                "name": package["name"],
+                "version": package["version"],
+                "manifest_path": package["manifest_path"],
+                "targets": targets,
+                "dependencies": dependencies
+            })
+        })
+        .collect();
+
+    json!({
+        "workspace_root": metadata["workspace_root"],
+        "target_directory": metadata["target_directory"],
+        "members": members
+    })
+}
+
+/// `cargo metadata`'s full entry for the member named `name`.
+pub fn member_detail(metadata: &Value, name: &str) -> Result<Value> {
+    member(metadata, name).cloned()
+}
+
+fn member<'a>(metadata: &'a Value, name: &str) -> Result<&'a Value> {
+    if let Some(package) = members(metadata).find(|package| package["name"] == name) {
+        return Ok(package);
+    }
+    let names: Vec<&str> = members(metadata)
+        .filter_map(|package| package["name"].as_str())
+        .collect();
+    Err(tool_error!(
+        InvalidParams,
+        "No workspace member named {}; members: {}",
+        name,
+        names.join(", ")
+    ))
+}
+
+/// `rust_analyzer_features`: declared and active features of each member,
+/// or with `package`, of one member with what its features enable.
+pub(crate) async fn handle_features(
+    workspace: &Path,
+    cache: &SharedMetadataCache,
+    cargo: &CargoSettings,
+    args: &Value,
+) -> Result<ToolResult> {
+    let (metadata, _) = cargo_metadata(workspace, cache).await?;
+    let report = feature_report(&metadata, cargo, args["package"].as_str())?;
+
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text: serde_json::to_string_pretty(&report)?,
+        }],
+    })
+}
+
+/// `rust_analyzer_cargo_metadata`: the summary, or with `package`, one
+/// member in full.
+pub(crate) async fn handle_cargo_metadata(
+    workspace: &Path,
+    cache: &SharedMetadataCache,
+    args: &Value,
+) -> Result<ToolResult> {
+    let (metadata, cached) = cargo_metadata(workspace, cache).await?;
+    let mut result = match args["package"].as_str() {
+        Some(name) => json!({ "package": member_detail(&metadata, name)? }),
+        None => summarize_metadata(&metadata),
+    };
+    result["cached"] = json!(cached);
+
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text: serde_json::to_string_pretty(&result)?,
+        }],
+    })
+}
+
+/// Features named by a feature's values, leaving out `dep:` and `crate/feature`
+/// entries, which enable dependencies rather than features of this package.
+fn implied_features(values: &Value) -> impl Iterator<Item = &str> {
+    values
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .filter(|value| !value.starts_with("dep:") && !value.contains('/'))
+}
+
+/// The optional dependencies feature values turn on: `dep:name`,
+/// `name/feature`, and `name` itself when it's an optional dependency's
+/// implicit feature. `name?/feature` only adds to a dependency enabled
+/// elsewhere, so it doesn't count.
+fn enabled_dependencies<'a>(
+    values: impl Iterator<Item = &'a Value>,
+    optional: &[&str],
+) -> Vec<&'a str> {
+    let mut enabled: Vec<&str> = values
+        .filter_map(|values| values.as_array())
+        .flatten()
+        .filter_map(Value::as_str)
+        .filter_map(|value| {
+            let name = match value.strip_prefix("dep:") {
+                Some(name) => name,
+                None => match value.split_once('/') {
+                    Some((name, _)) if !name.ends_with('?') => name,
+                    Some(_) => return None,
+                    None => value,
+                },
+            };
+            optional.contains(&name).then_some(name)
+        })
+        .collect();
+    enabled.sort_unstable();
+    enabled.dedup();
+    enabled
+}
+
+/// The features of `package` that `cargo` enables: `default` unless turned
+/// off, the requested ones it declares, and everything those imply.
+fn active_features<'a>(package: &'a Value, cargo: &CargoSettings) -> Vec<&'a str> {
+    let Some(declared) = package["features"].as_object() else {
+        return Vec::new();
+    };
+    let name = package["name"].as_str().unwrap_or_default();
+    let requested: Vec<&str> = if cargo.all_features {
+        declared.keys().map(String::as_str).collect()
+    } else {
+        let mut requested: Vec<&str> = cargo
+            .features
+            .iter()
+            .map(|feature| match feature.split_once('/') {
+                Some((package, feature)) if package == name => feature,
+                _ => feature.as_str(),
+            })
+            .filter_map(|feature| declared.get_key_value(feature).map(|(k, _)| k.as_str()))
+            .collect();
+        if !cargo.no_default_features && declared.contains_key("default") {
+            requested.push("default");
+        }
+        requested
+    };
+
+    feature_closure(declared, requested)
+}
+
+/// `features` and every feature they imply, sorted.
+fn feature_closure<'a>(
+    declared: &'a serde_json::Map<String, Value>,
+    mut pending: Vec<&'a str>,
+) -> Vec<&'a str> {
+    let mut closure = Vec::new();
+    while let Some(feature) = pending.pop() {
+        if closure.contains(&feature) {
+            continue;
+        }
+        closure.push(feature);
+        pending.extend(
+            implied_features(&declared[feature])
+                .filter_map(|implied| declared.get_key_value(implied).map(|(k, _)| k.as_str())),
+        );
+    }
+    closure.sort_unstable();
+    closure
+}
+
+/// Each member's declared features and which of them rust-analyzer analyzes
+/// with under `cargo`. With `package`, only that member, along with the
+/// optional dependencies each of its features enables.
+pub fn feature_report(
+    metadata: &Value,
+    cargo: &CargoSettings,
+    package: Option<&str>,
+) -> Result<Value> {
+    let detailed = package.is_some();
+    let packages: Vec<&Value> = match package {
+        Some(name) => vec![member(metadata, name)?],
+        None => members(metadata).collect(),
+    };
+
+    let reports: Vec<Value> = packages
+        .iter()
+        .map(|package| {
+            let mut declared: Vec<&str> = package["features"]
+                .as_object()
+                .map(|features| features.keys().map(String::as_str).collect())
+                .unwrap_or_default();
+            declared.sort_unstable();
+            let active = active_features(package, cargo);
+            let inactive: Vec<&str> = declared
+                .iter()
+                .copied()
+                .filter(|feature| !active.contains(feature))
+                .collect();
+            let mut report = json!({
+                "name": package["name"],
+                "declared": declared,
+                "default": package["features"]["default"].as_array().cloned().unwrap_or_default(),
+                "active": active,
+                "inactive": inactive
+            });
+
+            if detailed {
+                let optional: Vec<&str> = package["dependencies"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter(|dependency| dependency["optional"] == true)
+                    .filter_map(|dependency| {
+                        dependency["rename"]
+                            .as_str()
+                            .or(dependency["name"].as_str())
+                    })
+                    .collect();
+                let empty = serde_json::Map::new();
+                let features = package["features"].as_object().unwrap_or(&empty);
+                let enables: serde_json::Map<String, Value> = features
+                    .keys()
+                    .map(|feature| {
+                        let implied = feature_closure(features, vec![feature.as_str()]);
+                        let values = implied.iter().map(|implied| &features[*implied]);
+                        (
+                            feature.clone(),
+                            json!(enabled_dependencies(values, &optional)),
+                        )
+                    })
+                    .collect();
+                report["enables_optional_dependencies"] = Value::Object(enables);
+            }
+            report
+        })
+        .collect();
+
+    // A requested feature no member declares is most likely a typo, and
+    // cargo would refuse it.
+    let unknown: Vec<&str> = cargo
+        .features
+        .iter()
+        .map(String::as_str)
+        .filter(|requested| {
+            !members(metadata).any(|package| {
+                let feature = match requested.split_once('/') {
+                    Some((name, feature)) if package["name"] == name => feature,
+                    Some(_) => return false,
+                    None => requested,
+                };
+                package["features"].get(feature).is_some()
+            })
+        })
+        .collect();
+
+    let mut report = json!({
+        "analysis": {
+            "features": cargo.features,
+            "all_features": cargo.all_features,
+            "no_default_features": cargo.no_default_features
+        },
+        "members": reports
+    });
+    if !unknown.is_empty() {
+        report["unknown_features"] = json!(unknown);
+    }
+    Ok(report)
+}
+
+/// The root file of a crate: `target` of member `package`, defaulting to the
+/// member whose manifest is in `workspace` (or the only member) and to its
+/// library, else its first binary. Returns the package, target, and file.
+pub fn crate_root(
+    metadata: &Value,
+    workspace: &Path,
+    package: Option<&str>,
+    target: Option<&str>,
+) -> Result<(String, String, PathBuf)> {
+    let package = match package {
+        Some(name) => member(metadata, name)?,
+        None => {
+            let candidates: Vec<&Value> = members(metadata).collect();
+            let in_workspace = candidates.iter().find(|package| {
+                package["manifest_path"].as_str().map(Path::new)
+                    == Some(workspace.join("Cargo.toml").as_path())
+            });
+            match (in_workspace, candidates.as_slice()) {
+                (Some(package), _) | (None, [package]) => *package,
+                _ => {
+                    let names: Vec<&str> = candidates
+                        .iter()
+                        .filter_map(|package| package["name"].as_str())
+                        .collect();
+                    return Err(anyhow!("Pass package, one of: {}", names.join(", ")));
+                }
+            }
+        }
+    };
+
+    let targets: Vec<&Value> = package["targets"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .collect();
+    let is_kind = |target: &Value, kinds: &[&str]| {
+        target["kind"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .any(|kind| kind.as_str().is_some_and(|kind| kinds.contains(&kind)))
+    };
+    let found = match target {
+        Some(name) => targets.iter().find(|target| target["name"] == name),
+        None => targets
+            .iter()
+            .find(|target| is_kind(target, LIB_TARGET_KINDS))
+            .or_else(|| targets.iter().find(|target| is_kind(target, &["bin"]))),
+    };
+    let package_name = package["name"].as_str().unwrap_or_default();
+    let Some(found) = found else {
+        return Err(anyhow!(
+            "{} has no {} target",
+            package_name,
+            target.unwrap_or("lib or bin")
+        ));
+    };
+    Ok((
+        package_name.to_string(),
+        found["name"].as_str().unwrap_or_default().to_string(),
+        PathBuf::from(found["src_path"].as_str().unwrap_or_default()),
+    ))
+}
+
+/// The name of the package whose directory holds `file`.
+pub fn owning_package<'a>(metadata: &'a Value, file: &Path) -> Option<&'a str> {
+    package_of(metadata, file)?["name"].as_str()
+}
+
+/// The package whose directory holds `file`: the innermost, so a member
+/// nested in another member's directory owns its own files.
+pub fn package_of<'a>(metadata: &'a Value, file: &Path) -> Option<&'a Value> {
+    metadata["packages"]
+        .as_array()?
+        .iter()
+        .filter_map(|package| {
+            let dir = Path::new(package["manifest_path"].as_str()?).parent()?;
+            file.starts_with(dir)
+                .then(|| (dir.components().count(), package))
+        })
+        .max_by_key(|(depth, _)| *depth)
+        .map(|(_, package)| package)
+}
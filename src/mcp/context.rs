@@ -0,0 +1,229 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::path::Path;
+
+use crate::{
+    lsp::{
+        paths::{file_uri, uri_path},
+        symbol_kind_name,
+    },
+    protocol::{
+        mcp::{ContentItem, ToolResult},
+        tool_error, ToolError,
+    },
+};
+
+use super::{
+    cargo_metadata::{cargo_metadata, owning_package},
+    handlers::ToolParams,
+    markdown::symbol_label,
+    server::RustAnalyzerMCPServer,
+    toolchain::rustc_info,
+};
+
+/// Lines shown before and after the position unless `before`/`after` say.
+const CONTEXT_DEFAULT_LINES: u64 = 10;
+
+/// Path, line and column of a `path:line:column` location as the other
+/// tools report them. The column may be left off.
+pub fn parse_location(location: &str) -> Option<(&str, u32, u32)> {
+    let (rest, last) = location.rsplit_once(':')?;
+    let last = last.parse().ok()?;
+    let (path, line, character) = match rest
+        .rsplit_once(':')
+        .and_then(|(path, line)| Some((path, line.parse().ok()?)))
+    {
+        Some((path, line)) => (path, line, last),
+        None => (rest, last, 0),
+    };
+    (!path.is_empty()).then_some((path, line, character))
+}
+
+/// The range a symbol covers: a document symbol's `range`, or a flat
+/// symbol's `location.range`.
+fn symbol_range(symbol: &Value) -> &Value {
+    match &symbol["range"] {
+        Value::Null => &symbol["location"]["range"],
+        range => range,
+    }
+}
+
+fn position_key(position: &Value) -> (u64, u64) {
+    (
+        position["line"].as_u64().unwrap_or(0),
+        position["character"].as_u64().unwrap_or(0),
+    )
+}
+
+/// The symbols of a `textDocument/documentSymbol` result that hold
+/// `position`, outermost first. Among flat symbols, the one starting last
+/// is taken as the innermost.
+pub fn enclosing_symbols(symbols: &Value, position: (u64, u64)) -> Vec<&Value> {
+    let mut chain = Vec::new();
+    let mut level = symbols;
+    while let Some(symbol) = level
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|symbol| {
+            let range = symbol_range(symbol);
+            position_key(&range["start"]) <= position && position <= position_key(&range["end"])
+        })
+        .max_by_key(|symbol| position_key(&symbol_range(symbol)["start"]))
+    {
+        chain.push(symbol);
+        level = &symbol["children"];
+    }
+    chain
+}
+
+/// Lines `first` to `last` of a file, numbered from 0 like every location,
+/// with `>` marking line `marked`.
+pub(crate) fn numbered_lines(lines: &[&str], first: u64, last: u64, marked: u64) -> String {
+    let width = last.to_string().len();
+    let numbered: Vec<String> = (first..=last)
+        .filter_map(|number| {
+            let text = lines.get(number as usize)?;
+            let marker = if number == marked { '>' } else { '|' };
+            Some(format!("{:>width$} {} {}", number, marker, text))
+        })
+        .collect();
+    numbered.join("\n")
+}
+
+/// Whether `path` is source rust-analyzer loads from outside the workspace:
+/// a file of a package in the dependency graph, such as one in the cargo
+/// registry, or of the toolchain's sysroot.
+async fn is_dependency_source(server: &RustAnalyzerMCPServer, path: &Path) -> bool {
+    let workspace = &server.workspace_root;
+    if let Ok((metadata, _)) = cargo_metadata(workspace, &server.metadata_cache).await {
+        if owning_package(&metadata, path).is_some() {
+            return true;
+        }
+    }
+    let rustc = rustc_info(workspace, &server.toolchain_cache).await;
+    rustc["sysroot"].as_str().is_some_and(|sysroot| {
+        let sysroot = Path::new(sysroot);
+        path.starts_with(sysroot.canonicalize().as_deref().unwrap_or(sysroot))
+    })
+}
+
+/// The URI to read for `file_path`. Workspace files are opened so
+/// rust-analyzer has their current text; dependency sources are already
+/// loaded and are only read, and may be given as relative paths show them
+/// (`~cargo/...`, `~rust/...`). Other files are refused as every tool
+/// refuses files outside the workspace.
+async fn context_uri(server: &mut RustAnalyzerMCPServer, file_path: &str) -> Result<String> {
+    let outside = match server.resolve_file_path(file_path) {
+        Ok(_) => return server.open_document_if_needed(file_path, None).await,
+        Err(e) => e,
+    };
+    let marked = server.path_style(true).await.expand(file_path);
+    let Ok(path) = marked
+        .unwrap_or_else(|| server.workspace_root.join(file_path))
+        .canonicalize()
+    else {
+        return Err(outside);
+    };
+    if !is_dependency_source(server, &path).await {
+        return Err(outside);
+    }
+    Ok(file_uri(&path))
+}
+
+/// `rust_analyzer_context`: the numbered source lines around a location,
+/// the item enclosing it, and the file's length, so a `path:line:column`
+/// from another tool can be read without reading the whole file.
+pub(crate) async fn handle_context(
+    server: &mut RustAnalyzerMCPServer,
+    args: &Value,
+) -> Result<ToolResult> {
+    let (file_path, line, character) = match (args["location"].as_str(), &args["file_path"]) {
+        (Some(location), _) => parse_location(location).ok_or_else(|| {
+            tool_error!(
+                InvalidParams,
+                "Invalid location '{}'; expected path:line:column",
+                location
+            )
+        })?,
+        (None, Value::String(file_path)) => {
+            let line = ToolParams::extract_u32(args, "line")?;
+            let character = match args["character"] {
+                Value::Null => 0,
+                _ => ToolParams::extract_u32(args, "character")?,
+            };
+            (file_path.as_str(), line, character)
+        }
+        (None, _) => return Err(tool_error!(InvalidParams, "Missing location or file_path")),
+    };
+    let before = args["before"].as_u64().unwrap_or(CONTEXT_DEFAULT_LINES);
+    let after = args["after"].as_u64().unwrap_or(CONTEXT_DEFAULT_LINES);
+
+    let uri = context_uri(server, file_path).await?;
+    let path = uri_path(&uri);
+    let content = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| ToolError::io(&e, format!("Failed to read file {}: {}", path.display(), e)))?;
+    let lines: Vec<&str> = content.lines().collect();
+    let total_lines = lines.len() as u64;
+    let line = u64::from(line);
+    if line >= total_lines.max(1) {
+        return Err(tool_error!(
+            InvalidParams,
+            "Line {} is past the end of {} ({} lines)",
+            line,
+            path.display(),
+            total_lines
+        ));
+    }
+
+    let first = line.saturating_sub(before);
+    let last = (line + after).min(total_lines.saturating_sub(1));
+    let source = numbered_lines(&lines, first, last, line);
+
+    let Some(client) = &mut server.client else {
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
+    };
+    let symbols = client.document_symbols(&uri).await?;
+    let columns = client.document_columns([&uri]).await;
+    let character = columns.to_lsp(&uri, line as u32, character);
+    let chain = enclosing_symbols(&symbols, (line, u64::from(character)));
+    let shown = client.path_style.display(&uri);
+    let enclosing = chain.last().map(|symbol| {
+        let start = match &symbol["selectionRange"] {
+            Value::Null => &symbol_range(symbol)["start"],
+            range => &range["start"],
+        };
+        let (symbol_line, symbol_character) = position_key(start);
+        let symbol_character = columns.to_user(&uri, symbol_line, symbol_character);
+        let mut enclosing = json!({
+            "name": symbol["name"],
+            "kind": symbol_kind_name(symbol["kind"].as_u64().unwrap_or(0)),
+            "signature": symbol_label(symbol),
+            "location": format!("{}:{}:{}", shown, symbol_line, symbol_character)
+        });
+        if chain.len() > 1 {
+            let within: Vec<String> = chain[..chain.len() - 1]
+                .iter()
+                .map(|symbol| symbol_label(symbol))
+                .collect();
+            enclosing["within"] = json!(within);
+        }
+        enclosing
+    });
+
+    let result = json!({
+        "file": shown,
+        "line": line,
+        "total_lines": total_lines,
+        "enclosing": enclosing,
+        "source": source
+    });
+
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text: serde_json::to_string_pretty(&result)?,
+        }],
+    })
+}
@@ -0,0 +1,308 @@
+use anyhow::Result;
+use log::info;
+use serde_json::{json, Map, Value};
+use std::time::Instant;
+
+use crate::{
+    lsp::{paths::file_uri, RequestTimeout, RustAnalyzerClient},
+    protocol::{
+        mcp::{ContentItem, ToolResult},
+        tool_error,
+    },
+};
+
+use super::{
+    context::numbered_lines,
+    docs_links::hover_links,
+    handlers::{check_retry_timeout, ToolParams},
+    module_tree::definition_location,
+    server::RustAnalyzerMCPServer,
+};
+
+/// Characters of documentation kept unless `max_doc_chars` says otherwise.
+const DESCRIBE_MAX_DOC_CHARS: usize = 600;
+
+/// Most lines of the definition shown unless `snippet_lines` says otherwise.
+const DESCRIBE_SNIPPET_LINES: u64 = 12;
+
+/// The parts of a rust-analyzer hover: the item's module path, its
+/// signature, its memory layout for types, and its documentation.
+#[derive(Debug, Default, PartialEq)]
+pub struct HoverInfo {
+    pub path: Option<String>,
+    pub signature: Option<String>,
+    pub layout: Option<String>,
+    pub docs: Option<String>,
+}
+
+/// The text of a hover's `contents`: markup, a plain string, or a list of
+/// marked strings.
+fn hover_text(hover: &Value) -> String {
+    match &hover["contents"] {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| part.as_str().or(part["value"].as_str()))
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        contents => contents["value"].as_str().unwrap_or_default().to_string(),
+    }
+}
+
+/// Split a hover into its parts. rust-analyzer sends code blocks for the
+/// module path and the signature, then `---`-separated sections: the
+/// layout (`size = 4, align = 0x4`) and the docs.
+pub fn parse_hover(hover: &Value) -> HoverInfo {
+    let text = hover_text(hover);
+    let mut sections = text.split("\n---\n").map(str::trim);
+    let mut info = HoverInfo::default();
+    let mut docs = Vec::new();
+
+    let mut blocks = Vec::new();
+    let mut block: Option<Vec<&str>> = None;
+    for line in sections.next().unwrap_or_default().lines() {
+        match (&mut block, line.trim_start().starts_with("```")) {
+            (None, true) => block = Some(Vec::new()),
+            (Some(lines), true) => {
+                blocks.push(lines.join("\n"));
+                block = None;
+            }
+            (Some(lines), false) => lines.push(line),
+            (None, false) if !line.trim().is_empty() => docs.push(line),
+            (None, false) => {}
+        }
+    }
+    match blocks.len() {
+        0 => {}
+        1 => info.signature = blocks.pop(),
+        _ => {
+            info.signature = blocks.pop();
+            info.path = Some(blocks.remove(0));
+        }
+    }
+    let mut docs = vec![docs.join("\n")];
+    for section in sections {
+        if info.layout.is_none() && section.starts_with("size = ") {
+            info.layout = Some(section.to_string());
+        } else {
+            docs.push(section.to_string());
+        }
+    }
+    docs.retain(|section| !section.is_empty());
+    if !docs.is_empty() {
+        info.docs = Some(docs.join("\n\n"));
+    }
+    info
+}
+
+/// The first `max_chars` characters of `docs`, and whether any were cut.
+fn truncate_docs(docs: &str, max_chars: usize) -> (String, bool) {
+    match docs.char_indices().nth(max_chars) {
+        None => (docs.to_string(), false),
+        Some((at, _)) => (format!("{}…", docs[..at].trim_end()), true),
+    }
+}
+
+/// Ask for the hover and the definition at a position at once.
+async fn request_answers(
+    client: &mut RustAnalyzerClient,
+    uri: &str,
+    line: u32,
+    character: u32,
+) -> Result<(Result<Value>, Result<Value>)> {
+    let character = client
+        .document_columns([uri])
+        .await
+        .to_lsp(uri, line, character);
+    let position = json!({
+        "textDocument": { "uri": uri },
+        "position": { "line": line, "character": character }
+    });
+    let hover = client.request_detached("textDocument/hover", Some(position.clone()))?;
+    let definition = client.request_detached("textDocument/definition", Some(position))?;
+    Ok(tokio::join!(hover, definition))
+}
+
+/// Whether an answer has something in it: not an error, `null` or `[]`.
+fn has_answer(answer: &Result<Value>) -> bool {
+    match answer {
+        Ok(Value::Null) | Err(_) => false,
+        Ok(Value::Array(items)) => !items.is_empty(),
+        Ok(_) => true,
+    }
+}
+
+/// The last line of the item a definition points to, when the result says
+/// (a `LocationLink`'s `targetRange`).
+fn definition_end_line(definition: &Value) -> Option<u64> {
+    let target = match definition {
+        Value::Array(locations) => locations.first()?,
+        target => target,
+    };
+    target["targetRange"]["end"]["line"].as_u64()
+}
+
+/// `rust_analyzer_describe`: the hover signature and docs, the definition
+/// location, and the definition's first lines for one position, in one
+/// call. Each part rust-analyzer can't answer is listed under
+/// `unavailable` instead.
+pub(crate) async fn handle_describe(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let (line, character) = ToolParams::extract_position(&args)?;
+    let max_doc_chars = args["max_doc_chars"]
+        .as_u64()
+        .map_or(DESCRIBE_MAX_DOC_CHARS, |chars| chars as usize);
+    let snippet_lines = args["snippet_lines"]
+        .as_u64()
+        .unwrap_or(DESCRIBE_SNIPPET_LINES);
+
+    let uri = server
+        .open_document_if_needed(&file_path, args["content"].as_str())
+        .await?;
+
+    let Some(client) = &mut server.client else {
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
+    };
+
+    // Retry logic: wait for indexing to complete. Once the index is idle,
+    // an empty answer is final.
+    let retry_interval = server.config.retry_interval();
+    let indexing_timeout = server.config.indexing_timeout();
+    let start = Instant::now();
+    let mut logged_waiting = false;
+
+    let (hover, definition) = loop {
+        let (hover, definition) = request_answers(client, &uri, line, character).await?;
+        let timed_out = [&hover, &definition]
+            .into_iter()
+            .any(|answer| answer.as_ref().is_err_and(|e| e.is::<RequestTimeout>()));
+        if has_answer(&hover) || has_answer(&definition) || timed_out {
+            if logged_waiting {
+                info!("describe: Indexing complete, returning results");
+            }
+            break (hover, definition);
+        }
+        if hover.is_ok() && definition.is_ok() && !client.progress.lock().await.is_indexing() {
+            break (hover, definition);
+        }
+        check_retry_timeout(
+            "describe",
+            &start,
+            indexing_timeout,
+            &mut logged_waiting,
+            client,
+        )?;
+        tokio::time::sleep(retry_interval).await;
+    };
+
+    let mut report = Map::new();
+    let mut unavailable = Map::new();
+    let hover_info = match &hover {
+        Ok(Value::Null) | Err(_) => None,
+        Ok(hover) => Some(parse_hover(hover)),
+    };
+
+    match hover {
+        Ok(Value::Null) => {
+            unavailable.insert(
+                "hover".to_string(),
+                json!("No hover information at this position"),
+            );
+        }
+        Ok(hover) => {
+            let info = parse_hover(&hover);
+            let mut described = Map::new();
+            if let Some(path) = info.path {
+                described.insert("path".to_string(), json!(path));
+            }
+            if let Some(signature) = info.signature {
+                described.insert("signature".to_string(), json!(signature));
+            }
+            if let Some(layout) = info.layout {
+                described.insert("layout".to_string(), json!(layout));
+            }
+            if let Some(docs) = info.docs.filter(|_| max_doc_chars > 0) {
+                let (docs, truncated) = truncate_docs(&docs, max_doc_chars);
+                described.insert("docs".to_string(), json!(docs));
+                if truncated {
+                    described.insert("docs_truncated".to_string(), json!(true));
+                }
+            }
+            report.insert("hover".to_string(), Value::Object(described));
+        }
+        Err(e) => {
+            unavailable.insert("hover".to_string(), json!(e.to_string()));
+        }
+    }
+
+    let target = match &definition {
+        Ok(definition) => definition_location(definition),
+        Err(_) => None,
+    };
+    match (&definition, target) {
+        (Ok(definition), Some((file, target_line, target_character))) => {
+            let target_uri = file_uri(&file);
+            let columns = client.document_columns([&target_uri]).await;
+            let target_character = columns.to_user(&target_uri, target_line, target_character);
+            report.insert(
+                "definition".to_string(),
+                json!(format!(
+                    "{}:{}:{}",
+                    client.path_style.display(&file.display().to_string()),
+                    target_line,
+                    target_character
+                )),
+            );
+
+            if snippet_lines > 0 {
+                match tokio::fs::read_to_string(&file).await {
+                    Ok(content) => {
+                        let lines: Vec<&str> = content.lines().collect();
+                        let last = definition_end_line(definition)
+                            .unwrap_or(u64::MAX)
+                            .min(target_line + snippet_lines - 1)
+                            .min((lines.len() as u64).saturating_sub(1));
+                        report.insert(
+                            "snippet".to_string(),
+                            json!(numbered_lines(&lines, target_line, last, target_line)),
+                        );
+                    }
+                    Err(e) => {
+                        unavailable.insert(
+                            "snippet".to_string(),
+                            json!(format!("Failed to read file {}: {}", file.display(), e)),
+                        );
+                    }
+                }
+            }
+        }
+        (Ok(_), None) => {
+            unavailable.insert("definition".to_string(), json!("No definition found"));
+        }
+        (Err(e), _) => {
+            unavailable.insert("definition".to_string(), json!(e.to_string()));
+        }
+    }
+
+    if let Some(info) = &hover_info {
+        let definition = definition.as_ref().unwrap_or(&Value::Null);
+        let links = hover_links(server, &uri, line, character, info, definition).await;
+        if let Some(Value::Object(described)) = report.get_mut("hover") {
+            described.extend(links);
+        }
+    }
+
+    if !unavailable.is_empty() {
+        report.insert("unavailable".to_string(), Value::Object(unavailable));
+    }
+
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text: serde_json::to_string_pretty(&Value::Object(report))?,
+        }],
+    })
+}
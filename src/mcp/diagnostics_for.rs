@@ -0,0 +1,163 @@
+use anyhow::Result;
+use log::info;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+use crate::{
+    diagnostics::DiagnosticFilter,
+    lsp::{registrations::glob_matches, PinnedDocuments},
+    protocol::{
+        mcp::{ContentItem, ToolResult},
+        tool_error,
+    },
+};
+
+use super::{
+    handlers::{format_workspace_diagnostics, ToolParams},
+    server::RustAnalyzerMCPServer,
+    tags::rust_files,
+};
+
+/// Files `rust_analyzer_diagnostics_for` checks when the call doesn't say.
+const DEFAULT_MAX_FILES: u64 = 50;
+
+/// Whether a `path` argument is a glob rather than a directory.
+fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '[', '{'])
+}
+
+/// The files a `rust_analyzer_diagnostics_for` call opened, waited on
+/// without the server lock while rust-analyzer checks them. They stay
+/// pinned open until the scope is dropped, so calls served meanwhile can't
+/// close them.
+pub(crate) struct DiagnosticsScope {
+    path: String,
+    uris: Vec<String>,
+    _pinned: Option<PinnedDocuments>,
+    /// Files matching `path`, of which the first `uris.len()` were opened.
+    matched: usize,
+}
+
+/// The `.rs` files `path` names: those under a directory, those whose path
+/// relative to the workspace root matches a glob, or a single file.
+fn matching_files(server: &RustAnalyzerMCPServer, path: &str) -> Result<Vec<PathBuf>> {
+    if is_glob(path) {
+        let root = &server.workspace_root;
+        let root_prefix = format!("{}/", root.display());
+        let glob = path.strip_prefix(&root_prefix).unwrap_or(path);
+        return Ok(rust_files(root, &[])
+            .into_iter()
+            .filter(|file| {
+                let relative = file.strip_prefix(root).unwrap_or(file);
+                glob_matches(glob, &relative.to_string_lossy())
+            })
+            .collect());
+    }
+
+    let resolved = server.resolve_file_path(path)?;
+    if resolved.is_dir() {
+        Ok(rust_files(&resolved, &[]))
+    } else if resolved.is_file() {
+        Ok(vec![resolved])
+    } else {
+        Err(tool_error!(
+            FileNotFound,
+            "No such directory: {}",
+            resolved.display()
+        ))
+    }
+}
+
+/// Open the `.rs` files `path` matches in rust-analyzer, up to `max_files`
+/// and never more than stay open at once, so none is closed before its
+/// diagnostics are read.
+pub(crate) async fn open_matching_files(
+    server: &mut RustAnalyzerMCPServer,
+    args: &Value,
+) -> Result<DiagnosticsScope> {
+    let Some(path) = args["path"].as_str().filter(|path| !path.is_empty()) else {
+        return Err(tool_error!(InvalidParams, "Missing path"));
+    };
+    DiagnosticFilter::from_args(args)?;
+    let max_files = match &args["max_files"] {
+        Value::Null => DEFAULT_MAX_FILES,
+        _ => u64::from(ToolParams::extract_u32(args, "max_files")?),
+    };
+    if max_files == 0 {
+        return Err(tool_error!(InvalidParams, "max_files must be at least 1"));
+    }
+    let max_files = max_files.min(server.config.max_open_documents) as usize;
+
+    let files = matching_files(server, path)?;
+    if files.is_empty() {
+        return Err(tool_error!(FileNotFound, "No .rs files match {}", path));
+    }
+    info!(
+        "diagnostics_for: {} files match {}, opening {}",
+        files.len(),
+        path,
+        files.len().min(max_files)
+    );
+
+    let mut uris = Vec::new();
+    for file in files.iter().take(max_files) {
+        let uri = server
+            .open_document_if_needed(&file.to_string_lossy(), None)
+            .await?;
+        uris.push(uri);
+    }
+    let pinned = server
+        .client
+        .as_ref()
+        .map(|client| client.pin_documents(&uris));
+    Ok(DiagnosticsScope {
+        path: path.to_string(),
+        uris,
+        _pinned: pinned,
+        matched: files.len(),
+    })
+}
+
+/// The diagnostics of the files in `scope` once rust-analyzer's check has
+/// finished (or timed out), grouped per file with a combined summary, and
+/// how the check went under `flycheck`.
+pub(crate) async fn diagnostics_for_result(
+    server: &mut RustAnalyzerMCPServer,
+    args: &Value,
+    scope: DiagnosticsScope,
+    flycheck: Value,
+) -> Result<ToolResult> {
+    let filter = DiagnosticFilter::from_args(args)?;
+    let Some(client) = &mut server.client else {
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
+    };
+
+    let mut by_uri = json!({});
+    for uri in &scope.uris {
+        let diagnostics = client.diagnostics(uri).await?;
+        by_uri[uri] = filter.apply(&diagnostics);
+    }
+    let mut report =
+        format_workspace_diagnostics(&server.workspace_root, &client.path_style, &by_uri);
+    report["path"] = json!(scope.path);
+    report["summary"]["files_checked"] = json!(scope.uris.len());
+    if scope.matched > scope.uris.len() {
+        report["truncated"] = json!(true);
+        report["matched_files"] = json!(scope.matched);
+        report["note"] = json!(format!(
+            "{} files match {}; only the first {} were checked. Narrow path or raise max_files (at most max_open_documents, {})",
+            scope.matched,
+            scope.path,
+            scope.uris.len(),
+            server.config.max_open_documents
+        ));
+    }
+    report["flycheck"] = flycheck;
+
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text: serde_json::to_string_pretty(&report)?,
+        }],
+    })
+}
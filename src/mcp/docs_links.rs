@@ -0,0 +1,194 @@
+use log::debug;
+use serde_json::{json, Map, Value};
+use std::path::PathBuf;
+
+use crate::lsp::{paths::file_uri, PathStyle};
+
+use super::{
+    cargo_metadata::{cargo_metadata, package_of},
+    describe::HoverInfo,
+    module_tree::definition_location,
+    server::RustAnalyzerMCPServer,
+};
+
+/// Words that may come before an item's keyword in a signature. `const`
+/// is one only before `fn`.
+const QUALIFIERS: &[&str] = &["async", "default", "extern", "safe", "unsafe"];
+
+/// The item a signature line declares: rustdoc's name for its kind and the
+/// item's name. `None` for lines declaring no item, such as fields.
+fn declared_item(line: &str) -> Option<(&'static str, &str)> {
+    let mut words = line.split_whitespace().peekable();
+    while let Some(word) = words.peek() {
+        let qualifier = word.starts_with("pub")
+            || word.starts_with('"')
+            || QUALIFIERS.contains(word)
+            || (*word == "const" && line.contains(" fn "));
+        if !qualifier {
+            break;
+        }
+        words.next();
+    }
+    let kind = match words.next()? {
+        "struct" => "struct",
+        "enum" => "enum",
+        "union" => "union",
+        "trait" => "trait",
+        "type" => "type",
+        "fn" => "fn",
+        "const" => "constant",
+        "static" => "static",
+        "mod" => "mod",
+        "macro_rules!" | "macro" => "macro",
+        "impl" => "impl",
+        _ => return None,
+    };
+    if kind == "impl" {
+        return Some((kind, ""));
+    }
+    let name = words.next()?;
+    let end = name
+        .find(|c: char| !c.is_alphanumeric() && c != '_')
+        .unwrap_or(name.len());
+    match &name[..end] {
+        "" => None,
+        name => Some((kind, name)),
+    }
+}
+
+/// The docs.rs page of a hovered item of `package` (a `cargo metadata`
+/// package from a registry): the crate and module come from the hover's
+/// path, the kind and name from its signature. `None` when the page can't
+/// be worked out, such as for methods, fields, or a path that doesn't start
+/// with the package's library.
+pub fn docs_rs_url(package: &Value, info: &HoverInfo) -> Option<String> {
+    if !package["source"].as_str()?.starts_with("registry+") {
+        return None;
+    }
+    let lib = package["targets"].as_array()?.iter().find(|target| {
+        target["kind"].as_array().is_some_and(|kinds| {
+            kinds
+                .iter()
+                .any(|kind| kind == "lib" || kind == "rlib" || kind == "proc-macro")
+        })
+    })?["name"]
+        .as_str()?
+        .replace('-', "_");
+
+    let path = info.path.as_deref()?;
+    let mut modules = path.split("::");
+    if modules.next()? != lib {
+        return None;
+    }
+
+    // A method's hover puts its `impl` or trait line above it; only
+    // methods and associated functions mention `self` or `Self`.
+    let signature = info.signature.as_deref()?;
+    let mut lines = signature.lines();
+    let (kind, name) = declared_item(lines.next()?)?;
+    let associated = lines.any(|line| declared_item(line).is_some());
+    let mentions_self = signature
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .any(|word| word == "self" || word == "Self");
+    if kind == "impl" || (kind == "trait" && associated) || (kind == "fn" && mentions_self) {
+        return None;
+    }
+
+    let mut url = format!(
+        "https://docs.rs/{}/{}/{}/",
+        package["name"].as_str()?,
+        package["version"].as_str()?,
+        lib
+    );
+    for module in modules {
+        url.push_str(module);
+        url.push('/');
+    }
+    match kind {
+        "mod" => url.push_str(&format!("{}/index.html", name)),
+        kind => url.push_str(&format!("{}.{}.html", kind, name)),
+    }
+    Some(url)
+}
+
+/// The web link in an `experimental/externalDocs` answer: a URL, or
+/// `{ web, local }` for clients that asked for local docs too.
+fn external_docs_url(answer: &Value) -> Option<String> {
+    answer
+        .as_str()
+        .or(answer["web"].as_str())
+        .map(str::to_string)
+}
+
+/// Where to read more about a hovered item, from its definition: for items
+/// of the workspace, `defined_in`, the definition relative to the workspace
+/// root; for those of dependencies, `docs_url`, the item's docs.rs page,
+/// else whatever `experimental/externalDocs` suggests. Every part is best
+/// effort and left out when it can't be found.
+pub(crate) async fn hover_links(
+    server: &mut RustAnalyzerMCPServer,
+    uri: &str,
+    line: u32,
+    character: u32,
+    info: &HoverInfo,
+    definition: &Value,
+) -> Map<String, Value> {
+    let mut links = Map::new();
+    let Some(client) = &mut server.client else {
+        return links;
+    };
+
+    let folders: Vec<PathBuf> = std::iter::once(&server.workspace_root)
+        .chain(&server.config.extra_workspaces)
+        .map(|folder| folder.canonicalize().unwrap_or_else(|_| folder.clone()))
+        .collect();
+    let target = definition_location(definition);
+    if let Some((file, target_line, target_character)) = &target {
+        if folders.iter().any(|folder| file.starts_with(folder)) {
+            let target_uri = file_uri(file);
+            let columns = client.document_columns([&target_uri]).await;
+            let target_character = columns.to_user(&target_uri, *target_line, *target_character);
+            let relative = PathStyle::new(true, folders[0].clone(), None, None);
+            links.insert(
+                "defined_in".to_string(),
+                json!(format!(
+                    "{}:{}:{}",
+                    relative.display(&file.display().to_string()),
+                    target_line,
+                    target_character
+                )),
+            );
+            return links;
+        }
+    }
+
+    let package_url = match &target {
+        Some((file, _, _)) => {
+            match cargo_metadata(&server.workspace_root, &server.metadata_cache).await {
+                Ok((metadata, _)) => {
+                    package_of(&metadata, file).and_then(|package| docs_rs_url(package, info))
+                }
+                Err(e) => {
+                    debug!("No cargo metadata for docs links: {}", e);
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+    if let Some(url) = package_url {
+        links.insert("docs_url".to_string(), json!(url));
+        return links;
+    }
+
+    let answer = client.external_docs(uri, line, character).await;
+    match answer {
+        Ok(answer) => {
+            if let Some(url) = external_docs_url(&answer) {
+                links.insert("docs_url".to_string(), json!(url));
+            }
+        }
+        Err(e) => debug!("experimental/externalDocs failed: {}", e),
+    }
+    links
+}
@@ -0,0 +1,180 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+use crate::{
+    lsp::{
+        position::{uris_in, DocumentColumns},
+        symbol_kind_name, PathStyle,
+    },
+    protocol::{
+        mcp::{ContentItem, ToolResult},
+        tool_error,
+    },
+};
+
+use super::{
+    server::RustAnalyzerMCPServer,
+    trait_impls::{named_candidates, search_symbols},
+};
+
+/// Whether the `kind` argument names an LSP `SymbolKind`: by the name
+/// `symbol_kind_name` gives it, or as Rust calls the item (`trait`, `fn`,
+/// `type`, `mod`, `variant`).
+fn kind_matches(kind_arg: &str, kind: u64) -> bool {
+    symbol_kind_name(kind) == kind_arg
+        || matches!(
+            (kind_arg, kind),
+            ("trait", 11) | ("fn", 6 | 12) | ("type", 26) | ("mod", 2) | ("variant", 22)
+        )
+}
+
+/// `path:line:column` of `position` in `uri`, the path as `path_style`
+/// writes it.
+fn location(
+    columns: &DocumentColumns,
+    path_style: &PathStyle,
+    uri: &str,
+    position: &Value,
+) -> String {
+    let line = position["line"].as_u64().unwrap_or(0);
+    let character = columns.to_user(uri, line, position["character"].as_u64().unwrap_or(0));
+    format!("{}:{}:{}", path_style.display(uri), line, character)
+}
+
+/// A `workspace/symbol` result as a candidate: name, kind, container and
+/// `path:line:column`.
+fn describe(symbol: &Value, columns: &DocumentColumns, path_style: &PathStyle) -> Value {
+    let uri = symbol["location"]["uri"].as_str().unwrap_or_default();
+    let mut candidate = json!({
+        "name": symbol["name"],
+        "kind": symbol_kind_name(symbol["kind"].as_u64().unwrap_or(0)),
+        "location": location(columns, path_style, uri, &symbol["location"]["range"]["start"])
+    });
+    if let Some(container) = symbol["containerName"].as_str() {
+        candidate["container"] = json!(container);
+    }
+    candidate
+}
+
+/// References grouped by file, in path order, each file with its count and
+/// `path:line:column` locations.
+fn group_by_file(
+    references: &Value,
+    columns: &DocumentColumns,
+    path_style: &PathStyle,
+) -> Vec<Value> {
+    let mut by_file: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for reference in references.as_array().into_iter().flatten() {
+        let Some(uri) = reference["uri"].as_str() else {
+            continue;
+        };
+        by_file
+            .entry(path_style.display(uri))
+            .or_default()
+            .push(location(
+                columns,
+                path_style,
+                uri,
+                &reference["range"]["start"],
+            ));
+    }
+    by_file
+        .into_iter()
+        .map(|(file, locations)| {
+            json!({ "file": file, "count": locations.len(), "locations": locations })
+        })
+        .collect()
+}
+
+/// `rust_analyzer_find_references`: references to the symbol named
+/// `symbol`, found through `workspace/symbol` as
+/// `rust_analyzer_find_implementations` finds traits, then
+/// `textDocument/references` at its definition. When more than one symbol
+/// matches, the candidates are returned instead, to pick from with `kind`
+/// or `path`.
+pub(crate) async fn handle_find_references(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let Some(name) = args["symbol"].as_str().filter(|name| !name.is_empty()) else {
+        return Err(tool_error!(InvalidParams, "Missing symbol"));
+    };
+    let kind = args["kind"].as_str();
+    let path = args["path"].as_str();
+
+    let Some(client) = &mut server.client else {
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
+    };
+
+    // `#` asks rust-analyzer for functions and values too, not only types.
+    let query = format!("{}#", name);
+    let symbols = search_symbols(client, &server.config, "find_references", &query).await?;
+    let candidates = named_candidates(
+        &symbols,
+        name,
+        |symbol_kind| kind.is_none_or(|kind| kind_matches(kind, symbol_kind)),
+        path,
+    );
+    let columns = client.document_columns(uris_in(&symbols)).await;
+
+    let found = match candidates.as_slice() {
+        [found] => *found,
+        [] => {
+            return Err(tool_error!(
+                InvalidParams,
+                "No symbol named {}{}{}",
+                name,
+                kind.map(|kind| format!(" of kind {}", kind))
+                    .unwrap_or_default(),
+                path.map(|path| format!(" matching {}", path))
+                    .unwrap_or_default()
+            ))
+        }
+        _ => {
+            let listed: Vec<Value> = candidates
+                .iter()
+                .map(|symbol| describe(symbol, &columns, &client.path_style))
+                .collect();
+            let report = json!({
+                "ambiguous": format!(
+                    "{} symbols are named {}; pass kind or path (a container path like geometry::Shape, or part of the file path) to pick one",
+                    candidates.len(),
+                    name
+                ),
+                "candidates": listed
+            });
+            return Ok(ToolResult {
+                content: vec![ContentItem {
+                    content_type: "text".to_string(),
+                    text: serde_json::to_string_pretty(&report)?,
+                }],
+            });
+        }
+    };
+    let symbol_info = describe(found, &columns, &client.path_style);
+
+    let params = json!({
+        "textDocument": { "uri": found["location"]["uri"] },
+        "position": found["location"]["range"]["start"],
+        "context": { "includeDeclaration": true }
+    });
+    let references = client
+        .request_detached("textDocument/references", Some(params))?
+        .await?;
+
+    let columns = client.document_columns(uris_in(&references)).await;
+    let files = group_by_file(&references, &columns, &client.path_style);
+    let report = json!({
+        "symbol": symbol_info,
+        "total": files.iter().filter_map(|file| file["count"].as_u64()).sum::<u64>(),
+        "references": files
+    });
+
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text: serde_json::to_string_pretty(&report)?,
+        }],
+    })
+}
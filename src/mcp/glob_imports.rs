@@ -0,0 +1,235 @@
+use anyhow::{anyhow, Result};
+use log::debug;
+use serde_json::{json, Value};
+
+use crate::{
+    install::short_diff,
+    lsp::{
+        edits::{apply_text_edits, workspace_edit_changes},
+        paths::uri_path,
+        position::line_text,
+        RustAnalyzerClient,
+    },
+    protocol::{
+        mcp::{ContentItem, ToolResult},
+        tool_error,
+    },
+};
+
+use super::{handlers::ToolParams, server::RustAnalyzerMCPServer};
+
+/// Titles of rust-analyzer's assist replacing a glob with the items used:
+/// "Expand glob import", or "Expand glob reexport" on `pub use`.
+const EXPAND_GLOB_TITLE: &str = "Expand glob";
+
+/// Positions (line, character) of the `*` of every glob import in `text`:
+/// each `*` in a `use` item, which runs from a line starting with `use` (or
+/// `pub use`) to the line with its `;`.
+pub fn find_glob_imports(text: &str) -> Vec<(u32, u32)> {
+    let mut globs = Vec::new();
+    let mut in_use = false;
+    for (line, code) in text.lines().enumerate() {
+        let code = code.split("//").next().unwrap_or_default();
+        if !in_use {
+            let item = code.trim_start();
+            let item = match item.strip_prefix("pub") {
+                Some(rest) if rest.starts_with('(') => rest
+                    .split_once(')')
+                    .map_or("", |(_, rest)| rest.trim_start()),
+                Some(rest) => rest.trim_start(),
+                None => item,
+            };
+            in_use = item.starts_with("use ") || item.starts_with("use{");
+        }
+        if !in_use {
+            continue;
+        }
+        globs.extend(
+            code.chars()
+                .enumerate()
+                .filter(|(_, c)| *c == '*')
+                .map(|(character, _)| (line as u32, character as u32)),
+        );
+        if code.contains(';') {
+            in_use = false;
+        }
+    }
+    globs
+}
+
+/// `text` with the glob at a position expanded by rust-analyzer's assist,
+/// or why it couldn't be.
+async fn expand_glob_at(
+    client: &mut RustAnalyzerClient,
+    uri: &str,
+    text: &str,
+    line: u32,
+    character: u32,
+) -> Result<std::result::Result<String, String>> {
+    let actions = client
+        .code_actions(uri, line, character, line, character)
+        .await?;
+    let actions = actions.as_array().cloned().unwrap_or_default();
+    let Some(mut action) = actions
+        .iter()
+        .find(|a| {
+            a["title"]
+                .as_str()
+                .is_some_and(|t| t.starts_with(EXPAND_GLOB_TITLE))
+        })
+        .cloned()
+    else {
+        let titles: Vec<&str> = actions.iter().filter_map(|a| a["title"].as_str()).collect();
+        return Ok(Err(match titles.as_slice() {
+            [] => "rust-analyzer offers no code actions here".to_string(),
+            titles => format!("no glob import here; available: {}", titles.join(", ")),
+        }));
+    };
+    if action["edit"].is_null() && !action["data"].is_null() {
+        action = client.resolve_code_action(&action).await?;
+    }
+
+    let mut changes = workspace_edit_changes(&action["edit"])?;
+    let Some(edits) = changes.remove(uri) else {
+        return Ok(Err("the assist returned no edit for this file".to_string()));
+    };
+    if !changes.is_empty() {
+        return Ok(Err("the assist edits other files too".to_string()));
+    }
+    apply_text_edits(text, &edits, client.position_encoding())
+        .map(Ok)
+        .map_err(|e| anyhow!("Cannot apply the assist's edits: {}", e))
+}
+
+/// Lines changed between `old` and `new`, looking only between the first
+/// and last lines that differ.
+fn changed_lines(old: &str, new: &str) -> Vec<String> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    short_diff(
+        &old[prefix..old.len() - suffix].join("\n"),
+        &new[prefix..new.len() - suffix].join("\n"),
+        usize::MAX,
+    )
+}
+
+/// `rust_analyzer_expand_glob_import`: replace the glob import at a
+/// position, or with `all` every one in the file, by the items the code
+/// uses, through rust-analyzer's "Expand glob import" assist. Returns the
+/// changed lines, and writes the file when `apply` is set. With `all`, the
+/// actions are asked for again after each expansion, since each one moves
+/// the text after it.
+pub(crate) async fn handle_expand_glob_import(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let all = args["all"].as_bool().unwrap_or(false);
+    let apply = args["apply"].as_bool().unwrap_or(false);
+    let position = match all {
+        true => None,
+        false => Some(ToolParams::extract_position(&args)?),
+    };
+    let unsaved = args["content"].as_str();
+
+    let uri = server.open_document_if_needed(&file_path, unsaved).await?;
+    let path = uri_path(&uri);
+    let original = match unsaved {
+        Some(content) => content.to_string(),
+        None => tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?,
+    };
+
+    let Some(client) = &mut server.client else {
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
+    };
+
+    let mut text = original.clone();
+    let mut expanded = Vec::new();
+    let mut skipped = Vec::new();
+    match position {
+        Some((line, character)) => {
+            let import = line_text(&text, line)
+                .unwrap_or_default()
+                .trim()
+                .to_string();
+            match expand_glob_at(client, &uri, &text, line, character).await? {
+                Ok(new_text) => {
+                    text = new_text;
+                    expanded.push(json!({ "line": line, "import": import }));
+                }
+                Err(reason) => {
+                    return Err(tool_error!(
+                        InvalidParams,
+                        "Cannot expand a glob import at {}:{}: {}",
+                        line,
+                        character,
+                        reason
+                    ));
+                }
+            }
+        }
+        None => {
+            // Globs that couldn't be expanded stay in the text, ahead of
+            // the ones still to try.
+            let rounds = find_glob_imports(&text).len();
+            for _ in 0..rounds {
+                let Some(&(line, character)) = find_glob_imports(&text).get(skipped.len()) else {
+                    break;
+                };
+                let import = line_text(&text, line)
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string();
+                match expand_glob_at(client, &uri, &text, line, character).await? {
+                    Ok(new_text) => {
+                        text = new_text;
+                        client.open_document(&uri, &text, true).await?;
+                        expanded.push(json!({ "line": line, "import": import }));
+                    }
+                    Err(reason) => {
+                        debug!(
+                            "Glob import at {}:{} not expanded: {}",
+                            line, character, reason
+                        );
+                        skipped.push(json!({ "line": line, "import": import, "reason": reason }));
+                    }
+                }
+            }
+        }
+    }
+
+    let mut result = json!({
+        "expanded": expanded,
+        "diff": changed_lines(&original, &text),
+    });
+    if !skipped.is_empty() {
+        result["skipped"] = json!(skipped);
+    }
+    if apply && text != original {
+        client.write_document(&path, &text).await?;
+        result["applied"] = json!(true);
+        result["files"] = json!([client.path_style.display(&uri)]);
+    } else if all && text != original {
+        // The expansions were only shown; rust-analyzer goes back to the
+        // text the call started from.
+        client
+            .open_document(&uri, &original, unsaved.is_some())
+            .await?;
+    }
+
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text: serde_json::to_string_pretty(&result)?,
+        }],
+    })
+}
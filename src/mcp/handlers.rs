@@ -1,47 +1,115 @@
 use anyhow::{anyhow, Result};
 use log::{debug, info};
 use serde_json::{json, Value};
-use std::path::{Path, PathBuf};
-use std::time::{Duration, Instant};
+use std::{
+    ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
 
 use crate::{
-    config::{get_indexing_timeout_secs, RETRY_INTERVAL_MILLIS},
-    diagnostics::format_diagnostics,
-    protocol::mcp::{ContentItem, ToolResult},
+    config::DEFAULT_MAX_OUTPUT_BYTES,
+    diagnostics::{format_diagnostics, DiagnosticFilter},
+    lsp::{
+        edits::{apply_text_edits, workspace_edit_changes},
+        paths::{is_manifest, uri_path, ExternalFile},
+        position::uris_in,
+        PathStyle, RequestTimeout, RustAnalyzerClient, SharedProgress, SymbolScope,
+    },
+    protocol::{
+        indexing_wait_secs,
+        mcp::{ContentItem, ToolResult},
+        tool_error, ErrorCode, ToolError,
+    },
 };
 
-use super::server::{InitTrigger, RustAnalyzerMCPServer};
+use super::{
+    call_graph::handle_call_graph,
+    callers::{simplify_incoming_call, CallerLookups, CallerOptions},
+    cargo_check::start_cargo_check,
+    cargo_metadata::{handle_cargo_metadata, handle_features},
+    context::handle_context,
+    describe::{handle_describe, parse_hover},
+    diagnostics_for::{diagnostics_for_result, open_matching_files},
+    docs_links::hover_links,
+    find_references::handle_find_references,
+    glob_imports::handle_expand_glob_import,
+    impact::handle_impact,
+    markdown::symbol_outline,
+    module_tree::handle_module_tree,
+    public_api::handle_public_api,
+    rename::handle_rename,
+    run_test::prepare_test_run,
+    scip::handle_export_index,
+    server::{InitTrigger, RustAnalyzerMCPServer},
+    tags::handle_export_tags,
+    toolchain::{handle_toolchain, rustc_info, SharedToolchainCache},
+    tools::{resolve_tool_name, suggest_tool_name},
+    trait_impls::handle_find_implementations,
+    truncate::{limit_output, Cursor},
+    type_graph::handle_type_graph,
+    unused::handle_unused,
+    usage_stats::handle_usage_stats,
+};
 
 /// Helper struct for extracting common tool parameters.
-struct ToolParams;
+pub(super) struct ToolParams;
 
 impl ToolParams {
-    fn extract_file_path(args: &Value) -> Result<String> {
+    pub(super) fn extract_file_path(args: &Value) -> Result<String> {
         let Some(file_path) = args["file_path"].as_str() else {
-            return Err(anyhow!("Missing file_path"));
+            return Err(tool_error!(InvalidParams, "Missing file_path"));
         };
         Ok(file_path.to_string())
     }
 
-    fn extract_position(args: &Value) -> Result<(u32, u32)> {
-        let Some(line) = args["line"].as_u64() else {
-            return Err(anyhow!("Missing line"));
+    /// A line or column argument as a `u32`. Callers that stringify every
+    /// value send `"5"`, and some send `5.0`; both are taken as 5. Anything
+    /// else that isn't a whole, non-negative number is refused, saying what
+    /// was sent.
+    pub(super) fn extract_u32(args: &Value, key: &str) -> Result<u32> {
+        let value = &args[key];
+        let got = match value {
+            Value::Null => return Err(tool_error!(InvalidParams, "Missing {}", key)),
+            Value::Number(number) => number.to_string(),
+            Value::String(text) => format!("string {:?}", text),
+            Value::Bool(value) => format!("boolean {}", value),
+            Value::Array(_) => "an array".to_string(),
+            Value::Object(_) => "an object".to_string(),
+        };
+        let number = match value {
+            Value::String(text) => text.trim().parse::<f64>().ok(),
+            _ => value.as_f64(),
         };
-        let Some(character) = args["character"].as_u64() else {
-            return Err(anyhow!("Missing character"));
+        let problem = match number.filter(|n| n.is_finite()) {
+            None => "a number",
+            Some(n) if n < 0.0 => "a non-negative number",
+            Some(n) if n.fract() != 0.0 => "a whole number",
+            Some(n) if n > f64::from(u32::MAX) => "at most 4294967295",
+            Some(n) => return Ok(n as u32),
         };
-        Ok((line as u32, character as u32))
+        Err(tool_error!(
+            InvalidParams,
+            "{} must be {}, got {}",
+            key,
+            problem,
+            got
+        ))
+    }
+
+    pub(super) fn extract_position(args: &Value) -> Result<(u32, u32)> {
+        let line = Self::extract_u32(args, "line")?;
+        let character = Self::extract_u32(args, "character")?;
+        Ok((line, character))
     }
 
     fn extract_range(args: &Value) -> Result<(u32, u32, u32, u32)> {
         let (line, character) = Self::extract_position(args)?;
-        let Some(end_line) = args["end_line"].as_u64() else {
-            return Err(anyhow!("Missing end_line"));
-        };
-        let Some(end_character) = args["end_character"].as_u64() else {
-            return Err(anyhow!("Missing end_character"));
-        };
-        Ok((line, character, end_line as u32, end_character as u32))
+        let end_line = Self::extract_u32(args, "end_line")?;
+        let end_character = Self::extract_u32(args, "end_character")?;
+        Ok((line, character, end_line, end_character))
     }
 }
 
@@ -58,22 +126,122 @@ macro_rules! is_result_ready {
     }};
 }
 
+/// A request asked when `textDocument/definition` has nothing for a
+/// position and the index is idle.
+#[derive(Clone, Copy)]
+enum DefinitionFallback {
+    Declaration,
+    TypeDefinition,
+}
+
+/// Asked in order until one answers.
+const DEFINITION_FALLBACKS: &[DefinitionFallback] = &[
+    DefinitionFallback::Declaration,
+    DefinitionFallback::TypeDefinition,
+];
+
+impl DefinitionFallback {
+    /// How `via` and `tried` name it in results.
+    fn name(self) -> &'static str {
+        match self {
+            Self::Declaration => "declaration",
+            Self::TypeDefinition => "type_definition",
+        }
+    }
+
+    async fn ask(
+        self,
+        client: &mut RustAnalyzerClient,
+        uri: &str,
+        line: u32,
+        character: u32,
+    ) -> Result<Value> {
+        match self {
+            Self::Declaration => client.declaration(uri, line, character).await,
+            Self::TypeDefinition => client.type_definition(uri, line, character).await,
+        }
+    }
+}
+
+/// The `indexing` error for a call that stopped waiting after `waited`,
+/// or that didn't wait at all, with how far along rust-analyzer says it is
+/// and about how long it has left.
+pub(super) fn indexing_error(progress: &SharedProgress, waited: Option<Duration>) -> anyhow::Error {
+    let estimate = progress
+        .try_lock()
+        .map(|progress| progress.indexing_estimate())
+        .ok()
+        .filter(|estimate| estimate.title.is_some());
+    let done = estimate
+        .as_ref()
+        .and_then(|estimate| {
+            let title = estimate.title.as_ref()?;
+            Some(format!(" ({}% done, {})", estimate.percent, title))
+        })
+        .unwrap_or_default();
+    let percent = estimate.map(|estimate| u64::from(estimate.percent));
+    let estimated_wait = indexing_wait_secs(percent);
+    let mut details = json!({ "estimated_wait_secs": estimated_wait });
+    if let Some(percent) = percent {
+        details["percent"] = json!(percent);
+    }
+    let message = match waited {
+        Some(waited) => {
+            details["waited_secs"] = json!(waited.as_secs());
+            format!(
+                "Rust-analyzer is still indexing the project{}. Waited {} seconds. \
+                The project may be large and need more time to complete indexing. \
+                Please try again in a moment.",
+                done,
+                waited.as_secs()
+            )
+        }
+        None => format!(
+            "Rust-analyzer is still indexing the project{}. Try again in about {} seconds, \
+            or pass \"wait_for_index\": true to wait for it.",
+            done, estimated_wait
+        ),
+    };
+    ToolError::new(ErrorCode::Indexing, message)
+        .with_details(details)
+        .into()
+}
+
 /// Helper function to retry an operation with proper logging and timeout.
 /// Returns (result, should_return) tuple.
-fn check_retry_timeout(
+pub(super) fn check_retry_timeout(
     tool_name: &str,
     start: &Instant,
+    timeout: Duration,
     logged_waiting: &mut bool,
+    client: &RustAnalyzerClient,
 ) -> Result<bool> {
-    let timeout = Duration::from_secs(get_indexing_timeout_secs());
-
     if start.elapsed() >= timeout {
-        return Err(anyhow!(
-            "Rust-analyzer is still indexing the project. Waited {} seconds. \
-            The project may be large and need more time to complete indexing. \
-            Please try again in a moment.",
-            timeout.as_secs()
-        ));
+        // A load failure explains the empty results better than indexing.
+        let reported = client
+            .messages
+            .try_lock()
+            .ok()
+            .and_then(|messages| messages.last_error().map(|m| m.message.clone()));
+        if let Some(message) = reported {
+            return Err(anyhow!(
+                "rust-analyzer reported: {} (waited {} seconds for results)",
+                message,
+                timeout.as_secs()
+            ));
+        }
+
+        return Err(indexing_error(&client.progress, Some(timeout)));
+    }
+    // Sleeping here would hold the server lock for a call that asked not
+    // to wait.
+    if !client.wait_for_index
+        && client
+            .progress
+            .try_lock()
+            .is_ok_and(|progress| progress.is_indexing_workspace())
+    {
+        return Err(indexing_error(&client.progress, None));
     }
 
     if !*logged_waiting {
@@ -88,65 +256,522 @@ fn check_retry_timeout(
     Ok(false)
 }
 
+/// A call's `wait_for_index`: true unless it says false.
+fn wait_for_index(args: &Value) -> Result<bool> {
+    match args.get("wait_for_index") {
+        None | Some(Value::Null) => Ok(true),
+        Some(Value::Bool(wait)) => Ok(*wait),
+        Some(_) => Err(tool_error!(
+            InvalidParams,
+            "wait_for_index must be true or false"
+        )),
+    }
+}
+
+/// Arguments any tool takes, about how it runs and how its output is shaped.
+struct CommonArgs {
+    compact: bool,
+    /// Whether to wait out indexing rather than fail fast while it runs.
+    wait_for_index: bool,
+    timeout_override: Option<Duration>,
+    max_output_bytes: usize,
+    cursor: Option<Cursor>,
+    /// Whether to write file paths relative to the workspace, when the call
+    /// says; the server's `relative_paths` setting otherwise.
+    relative_paths: Option<bool>,
+}
+
+impl CommonArgs {
+    fn parse(args: &Value) -> Result<Self> {
+        let timeout_override = match args.get("timeout_secs") {
+            None | Some(Value::Null) => None,
+            Some(value) => match value.as_u64() {
+                Some(secs) if secs > 0 => Some(Duration::from_secs(secs)),
+                _ => {
+                    return Err(tool_error!(
+                        InvalidParams,
+                        "timeout_secs must be a positive whole number"
+                    ))
+                }
+            },
+        };
+        let max_output_bytes = match args.get("max_output_bytes") {
+            None | Some(Value::Null) => DEFAULT_MAX_OUTPUT_BYTES,
+            Some(value) => match value.as_u64() {
+                Some(bytes) if bytes > 0 => bytes as usize,
+                _ => {
+                    return Err(tool_error!(
+                        InvalidParams,
+                        "max_output_bytes must be a positive whole number"
+                    ))
+                }
+            },
+        };
+        let cursor = match args.get("cursor") {
+            None | Some(Value::Null) => None,
+            Some(Value::String(cursor)) => Some(Cursor::parse(cursor)?),
+            Some(_) => return Err(tool_error!(InvalidParams, "cursor must be a string")),
+        };
+        let wait_for_index = wait_for_index(args)?;
+        let relative_paths = match args.get("relative_paths") {
+            None | Some(Value::Null) => None,
+            Some(Value::Bool(relative)) => Some(*relative),
+            Some(_) => {
+                return Err(tool_error!(
+                    InvalidParams,
+                    "relative_paths must be true or false"
+                ))
+            }
+        };
+        Ok(Self {
+            compact: args["compact"].as_bool().unwrap_or(false),
+            wait_for_index,
+            timeout_override,
+            max_output_bytes,
+            cursor,
+            relative_paths,
+        })
+    }
+
+    fn shape_output(&self, mut result: ToolResult) -> Result<ToolResult> {
+        if self.compact {
+            compact_content(&mut result);
+        }
+        for item in &mut result.content {
+            item.text = limit_output(
+                &item.text,
+                self.max_output_bytes,
+                self.cursor.as_ref(),
+                !self.compact,
+            )?;
+        }
+        Ok(result)
+    }
+}
+
+/// The server with one call's timeout, `wait_for_index` and path style set
+/// on its client. They're put back when the scope drops, so a call cancelled
+/// midway (its caller gone) doesn't leave them to the calls after it.
+struct CallScope<'a> {
+    server: &'a mut RustAnalyzerMCPServer,
+}
+
+impl<'a> CallScope<'a> {
+    async fn enter(server: &'a mut RustAnalyzerMCPServer, common: &CommonArgs) -> Self {
+        let relative_paths = common
+            .relative_paths
+            .unwrap_or(server.config.relative_paths);
+        let path_style = server.path_style(relative_paths).await;
+        if let Some(client) = &mut server.client {
+            client.timeout_override = common.timeout_override;
+            client.wait_for_index = common.wait_for_index;
+            client.path_style = path_style;
+        }
+        Self { server }
+    }
+}
+
+impl Deref for CallScope<'_> {
+    type Target = RustAnalyzerMCPServer;
+
+    fn deref(&self) -> &RustAnalyzerMCPServer {
+        self.server
+    }
+}
+
+impl DerefMut for CallScope<'_> {
+    fn deref_mut(&mut self) -> &mut RustAnalyzerMCPServer {
+        self.server
+    }
+}
+
+impl Drop for CallScope<'_> {
+    fn drop(&mut self) {
+        if let Some(client) = &mut self.server.client {
+            client.timeout_override = None;
+            client.wait_for_index = true;
+            client.path_style = PathStyle::absolute();
+        }
+    }
+}
+
+/// Tools that answer without asking rust-analyzer anything, and so never
+/// wait for its index.
+const INDEX_FREE_TOOLS: &[&str] = &[
+    "rust_analyzer_get_workspace",
+    "rust_analyzer_set_workspace",
+    "rust_analyzer_workspace_folders",
+    "rust_analyzer_close_document",
+    "rust_analyzer_cargo_metadata",
+    "rust_analyzer_features",
+    "rust_analyzer_toolchain",
+];
+
+/// Tools that take a `Cargo.toml` as their `file_path`; rust-analyzer
+/// answers nothing else for manifests.
+const MANIFEST_TOOLS: &[&str] = &[
+    "rust_analyzer_hover",
+    "rust_analyzer_completion",
+    "rust_analyzer_diagnostics",
+];
+
+/// Check the workspace `args` name, make sure rust-analyzer is running, and
+/// read the common arguments. A call that mustn't wait for indexing fails
+/// here while it runs, before holding the server any longer.
+async fn begin_tool_call(
+    server: &mut RustAnalyzerMCPServer,
+    tool_name: &str,
+    args: &Value,
+) -> Result<CommonArgs> {
+    // Switching workspaces inline would restart rust-analyzer under every
+    // other caller sharing the server, so a call naming another workspace is
+    // refused; rust_analyzer_set_workspace switches.
+    if tool_name != "rust_analyzer_set_workspace" {
+        if let Some(workspace_path) = args["workspace_path"].as_str() {
+            let requested = absolute_workspace_root(Path::new(workspace_path));
+            if requested != server.workspace_root {
+                return Err(tool_error!(
+                    InvalidParams,
+                    "workspace_path {} is not the server's workspace {}; call rust_analyzer_set_workspace to switch",
+                    requested.display(),
+                    server.workspace_root.display()
+                ));
+            }
+        }
+    }
+
+    server.ensure_client_started().await?;
+
+    let manifest = args["file_path"]
+        .as_str()
+        .is_some_and(|path| is_manifest(&uri_path(path)));
+    if manifest && !MANIFEST_TOOLS.contains(&tool_name) {
+        return Err(tool_error!(
+            InvalidParams,
+            "{} doesn't take Cargo.toml; hover, completion and diagnostics do",
+            tool_name
+        ));
+    }
+
+    let common = CommonArgs::parse(args)?;
+    let needs_index = !INDEX_FREE_TOOLS.contains(&tool_name);
+    if needs_index && !common.wait_for_index && server.progress.lock().await.is_indexing_workspace()
+    {
+        return Err(indexing_error(&server.progress, None));
+    }
+    Ok(common)
+}
+
+/// Fail a call that mustn't wait for indexing while rust-analyzer indexes,
+/// without queueing for the server lock first. Cargo check running doesn't
+/// count; tools that don't need the index never fail here.
+pub async fn refuse_while_indexing(
+    progress: &SharedProgress,
+    tool_name: &str,
+    args: &Value,
+) -> Result<()> {
+    let resolved = resolve_tool_name(tool_name);
+    if wait_for_index(args)? || INDEX_FREE_TOOLS.contains(&resolved.as_ref()) {
+        return Ok(());
+    }
+    if progress.lock().await.is_indexing_workspace() {
+        return Err(indexing_error(progress, None));
+    }
+    Ok(())
+}
+
+fn log_tool_result<T>(tool_name: &str, start: Instant, result: &Result<T>) {
+    match result {
+        Ok(_) => info!(
+            "Tool {} succeeded in {}ms",
+            tool_name,
+            start.elapsed().as_millis()
+        ),
+        Err(e) => info!(
+            "Tool {} failed in {}ms: {}",
+            tool_name,
+            start.elapsed().as_millis(),
+            e
+        ),
+    }
+}
+
+/// Run the tool `tool_name`, given by its full name or an alias.
 pub async fn handle_tool_call(
     server: &mut RustAnalyzerMCPServer,
     tool_name: &str,
     args: Value,
 ) -> Result<ToolResult> {
-    server.ensure_client_started().await?;
+    let requested = tool_name;
+    let resolved = resolve_tool_name(requested);
+    let tool_name = resolved.as_ref();
+    let common = begin_tool_call(server, tool_name, &args).await?;
+    let mut scope = CallScope::enter(server, &common).await;
+    let server = &mut *scope;
+    let start = Instant::now();
 
-    match tool_name {
+    let result = match tool_name {
         "rust_analyzer_hover" => handle_hover(server, args).await,
+        "rust_analyzer_describe" => handle_describe(server, args).await,
         "rust_analyzer_definition" => handle_definition(server, args).await,
         "rust_analyzer_references" => handle_references(server, args).await,
+        "rust_analyzer_rename" => handle_rename(server, args).await,
         "rust_analyzer_implementation" => handle_implementation(server, args).await,
         "rust_analyzer_parent_module" => handle_parent_module(server, args).await,
         "rust_analyzer_incoming_calls" => handle_incoming_calls(server, args).await,
+        "rust_analyzer_impact" => handle_impact(server, args).await,
+        "rust_analyzer_find_implementations" => handle_find_implementations(server, args).await,
+        "rust_analyzer_find_references" => handle_find_references(server, args).await,
+        "rust_analyzer_usage_stats" => handle_usage_stats(server, args).await,
         "rust_analyzer_outgoing_calls" => handle_outgoing_calls(server, args).await,
+        "rust_analyzer_call_graph" => handle_call_graph(server, args).await,
+        "rust_analyzer_type_graph" => handle_type_graph(server, args).await,
         "rust_analyzer_inlay_hint" => handle_inlay_hint(server, args).await,
         "rust_analyzer_completion" => handle_completion(server, args).await,
         "rust_analyzer_symbols" => handle_symbols(server, args).await,
+        "rust_analyzer_context" => handle_context(server, &args).await,
         "rust_analyzer_workspace_symbol" => handle_workspace_symbol(server, args).await,
+        "rust_analyzer_export_tags" => handle_export_tags(server, args).await,
+        "rust_analyzer_export_index" => handle_export_index(server, args).await,
         "rust_analyzer_format" => handle_format(server, args).await,
         "rust_analyzer_code_actions" => handle_code_actions(server, args).await,
+        "rust_analyzer_expand_glob_import" => handle_expand_glob_import(server, args).await,
         "rust_analyzer_get_workspace" => handle_get_workspace(server).await,
         "rust_analyzer_set_workspace" => handle_set_workspace(server, args).await,
+        "rust_analyzer_workspace_folders" => handle_workspace_folders(server, args).await,
         "rust_analyzer_diagnostics" => handle_diagnostics(server, args).await,
         "rust_analyzer_workspace_diagnostics" => handle_workspace_diagnostics(server, args).await,
-        _ => Err(anyhow!("Unknown tool: {}", tool_name)),
+        "rust_analyzer_unused" => handle_unused(server, &args).await,
+        "rust_analyzer_list_tests" => handle_list_tests(server, args).await,
+        "rust_analyzer_run_test" => {
+            match prepare_test_run(server, &args, common.timeout_override).await {
+                Ok(test) => test.run().await,
+                Err(e) => Err(e),
+            }
+        }
+        "rust_analyzer_cargo_metadata" => {
+            handle_cargo_metadata(&server.workspace_root, &server.metadata_cache, &args).await
+        }
+        "rust_analyzer_features" => {
+            let cargo = &server.config.cargo;
+            handle_features(&server.workspace_root, &server.metadata_cache, cargo, &args).await
+        }
+        "rust_analyzer_module_tree" => handle_module_tree(server, &args).await,
+        "rust_analyzer_public_api" => handle_public_api(server, &args).await,
+        "rust_analyzer_toolchain" => {
+            let server_info = server
+                .client
+                .as_ref()
+                .and_then(|c| c.server_info().cloned());
+            handle_toolchain(&server.workspace_root, &server.toolchain_cache, server_info).await
+        }
+        "rust_analyzer_cargo_check" => {
+            match start_cargo_check(server, common.timeout_override).await {
+                Ok(check) => {
+                    let flycheck = check.wait().await;
+                    cargo_check_result(server, &args, flycheck).await
+                }
+                Err(e) => Err(e),
+            }
+        }
+        "rust_analyzer_diagnostics_for" => match open_matching_files(server, &args).await {
+            Ok(scope) => match start_cargo_check(server, common.timeout_override).await {
+                Ok(check) => {
+                    let flycheck = check.wait().await;
+                    diagnostics_for_result(server, &args, scope, flycheck).await
+                }
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        },
+        "rust_analyzer_close_document" => handle_close_document(server, args).await,
+        _ => Err(unknown_tool(requested)),
+    };
+    drop(scope);
+
+    log_tool_result(tool_name, start, &result);
+    common.shape_output(result?)
+}
+
+/// The error for a name that's neither a tool nor an alias, with the
+/// closest one when it looks like a typo.
+fn unknown_tool(name: &str) -> anyhow::Error {
+    match suggest_tool_name(name) {
+        Some(suggestion) => ToolError::new(
+            ErrorCode::UnknownTool,
+            format!("Unknown tool: {}; did you mean {}?", name, suggestion),
+        )
+        .with_details(json!({ "suggestion": suggestion }))
+        .into(),
+        None => tool_error!(UnknownTool, "Unknown tool: {}", name),
+    }
+}
+
+/// Tools that wait on something other than a rust-analyzer request, and so
+/// shouldn't hold the server lock meanwhile.
+const UNLOCKED_TOOLS: &[&str] = &[
+    "rust_analyzer_run_test",
+    "rust_analyzer_cargo_check",
+    "rust_analyzer_diagnostics_for",
+    "rust_analyzer_cargo_metadata",
+    "rust_analyzer_features",
+    "rust_analyzer_toolchain",
+];
+
+/// `handle_tool_call` on a server shared between callers. Tools that wait on
+/// something other than a rust-analyzer request (`rust_analyzer_run_test`
+/// and the `cargo metadata` tools waiting on cargo, `rust_analyzer_toolchain`
+/// on rustc, `rust_analyzer_cargo_check` and `rust_analyzer_diagnostics_for`
+/// on rust-analyzer's check) hold the lock only while preparing, so other
+/// calls aren't held up.
+pub async fn handle_shared_tool_call(
+    server: &Mutex<RustAnalyzerMCPServer>,
+    tool_name: &str,
+    args: Value,
+) -> Result<ToolResult> {
+    let resolved = resolve_tool_name(tool_name);
+    if !UNLOCKED_TOOLS.contains(&resolved.as_ref()) {
+        return handle_tool_call(&mut *server.lock().await, tool_name, args).await;
+    }
+    let tool_name = resolved.as_ref();
+
+    let start = Instant::now();
+    let mut guard = server.lock().await;
+    let common = begin_tool_call(&mut guard, tool_name, &args).await?;
+    let metadata_tool =
+        tool_name == "rust_analyzer_cargo_metadata" || tool_name == "rust_analyzer_features";
+    let result = if metadata_tool {
+        let workspace = guard.workspace_root.clone();
+        let cache = Arc::clone(&guard.metadata_cache);
+        let config = Arc::clone(&guard.config);
+        drop(guard);
+        if tool_name == "rust_analyzer_features" {
+            handle_features(&workspace, &cache, &config.cargo, &args).await
+        } else {
+            handle_cargo_metadata(&workspace, &cache, &args).await
+        }
+    } else if tool_name == "rust_analyzer_toolchain" {
+        let workspace = guard.workspace_root.clone();
+        let cache = Arc::clone(&guard.toolchain_cache);
+        let server_info = guard.client.as_ref().and_then(|c| c.server_info().cloned());
+        drop(guard);
+        handle_toolchain(&workspace, &cache, server_info).await
+    } else if tool_name == "rust_analyzer_run_test" {
+        let mut scope = CallScope::enter(&mut guard, &common).await;
+        let test = prepare_test_run(&mut scope, &args, common.timeout_override).await;
+        drop(scope);
+        drop(guard);
+        match test {
+            Ok(test) => test.run().await,
+            Err(e) => Err(e),
+        }
+    } else if tool_name == "rust_analyzer_diagnostics_for" {
+        let mut call = CallScope::enter(&mut guard, &common).await;
+        let prepared = match open_matching_files(&mut call, &args).await {
+            Ok(scope) => start_cargo_check(&mut call, common.timeout_override)
+                .await
+                .map(|check| (scope, check)),
+            Err(e) => Err(e),
+        };
+        drop(call);
+        drop(guard);
+        match prepared {
+            Ok((scope, check)) => {
+                let flycheck = check.wait().await;
+                let mut guard = server.lock().await;
+                let mut call = CallScope::enter(&mut guard, &common).await;
+                diagnostics_for_result(&mut call, &args, scope, flycheck).await
+            }
+            Err(e) => Err(e),
+        }
+    } else {
+        let check = start_cargo_check(&mut guard, common.timeout_override).await;
+        drop(guard);
+        match check {
+            Ok(check) => {
+                let flycheck = check.wait().await;
+                let mut guard = server.lock().await;
+                let mut call = CallScope::enter(&mut guard, &common).await;
+                cargo_check_result(&mut call, &args, flycheck).await
+            }
+            Err(e) => Err(e),
+        }
+    };
+
+    log_tool_result(tool_name, start, &result);
+    common.shape_output(result?)
+}
+
+/// Re-serialize JSON text content without pretty-printing whitespace.
+/// Non-JSON text is left as-is.
+fn compact_content(result: &mut ToolResult) {
+    for item in &mut result.content {
+        if let Ok(value) = serde_json::from_str::<Value>(&item.text) {
+            item.text = value.to_string();
+        }
     }
 }
 
 async fn handle_hover(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
     let file_path = ToolParams::extract_file_path(&args)?;
     let (line, character) = ToolParams::extract_position(&args)?;
+    // A manifest line rust-analyzer has nothing to say about is just that,
+    // not a sign it's still indexing.
+    let manifest = is_manifest(&uri_path(&file_path));
 
-    let uri = server.open_document_if_needed(&file_path).await?;
+    let uri = server
+        .open_document_if_needed(&file_path, args["content"].as_str())
+        .await?;
 
     let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
     };
 
     // Retry logic: wait for indexing to complete
-    let retry_interval = Duration::from_millis(RETRY_INTERVAL_MILLIS);
+    let retry_interval = server.config.retry_interval();
+    let indexing_timeout = server.config.indexing_timeout();
     let start = Instant::now();
     let mut logged_waiting = false;
 
-    let result = loop {
+    let mut result = loop {
         match client.hover(&uri, line, character).await {
-            Ok(result) if is_result_ready!(result) => {
+            Ok(result) if manifest || is_result_ready!(result) => {
                 if logged_waiting {
                     info!("hover: Indexing complete, returning results");
                 }
                 break result;
             }
+            Err(e) if e.is::<RequestTimeout>() => return Err(e),
             Ok(_) | Err(_) => {
-                check_retry_timeout("hover", &start, &mut logged_waiting)?;
+                check_retry_timeout(
+                    "hover",
+                    &start,
+                    indexing_timeout,
+                    &mut logged_waiting,
+                    client,
+                )?;
                 tokio::time::sleep(retry_interval).await;
             }
         }
     };
 
+    // Where to read more, when asked: the definition for the workspace's own
+    // items, the docs for those of dependencies. Finding them takes a
+    // definition request and maybe `cargo metadata`, which plain hovers
+    // shouldn't pay for.
+    let links = args["links"].as_bool().unwrap_or(false);
+    if links && result.is_object() && !manifest {
+        let definition = client
+            .definition(&uri, line, character)
+            .await
+            .unwrap_or(Value::Null);
+        let info = parse_hover(&result);
+        let links = hover_links(server, &uri, line, character, &info, &definition).await;
+        if let Some(result) = result.as_object_mut() {
+            result.extend(links);
+        }
+    }
+
     Ok(ToolResult {
         content: vec![ContentItem {
             content_type: "text".to_string(),
@@ -159,45 +784,105 @@ async fn handle_definition(server: &mut RustAnalyzerMCPServer, args: Value) -> R
     let file_path = ToolParams::extract_file_path(&args)?;
     let (line, character) = ToolParams::extract_position(&args)?;
 
-    let uri = server.open_document_if_needed(&file_path).await?;
+    let uri = server
+        .open_document_if_needed(&file_path, args["content"].as_str())
+        .await?;
 
     let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
     };
 
     // Retry logic: wait for indexing to complete
-    let retry_interval = Duration::from_millis(RETRY_INTERVAL_MILLIS);
+    let retry_interval = server.config.retry_interval();
+    let indexing_timeout = server.config.indexing_timeout();
     let start = Instant::now();
     let mut logged_waiting = false;
 
-    let result = loop {
+    // An empty result before or during indexing may fill in later; once
+    // rust-analyzer has indexed and is idle it's final, and the fallbacks get
+    // a turn.
+    let mut result = loop {
         match client.definition(&uri, line, character).await {
             Ok(result) if is_result_ready!(result) => {
                 if logged_waiting {
                     info!("definition: Indexing complete, returning results");
                 }
-                break result;
+                break Some(result);
             }
+            Err(e) if e.is::<RequestTimeout>() => return Err(e),
+            Ok(_) if client.progress.lock().await.has_indexed() => break None,
             Ok(_) | Err(_) => {
-                check_retry_timeout("definition", &start, &mut logged_waiting)?;
+                check_retry_timeout(
+                    "definition",
+                    &start,
+                    indexing_timeout,
+                    &mut logged_waiting,
+                    client,
+                )?;
                 tokio::time::sleep(retry_interval).await;
             }
         }
     };
+    let mut via = None;
+    for fallback in DEFINITION_FALLBACKS {
+        if result.is_some() {
+            break;
+        }
+        match fallback.ask(client, &uri, line, character).await {
+            Ok(answer) if is_result_ready!(answer) => {
+                debug!("definition: answered by {}", fallback.name());
+                result = Some(answer);
+                via = Some(fallback.name());
+            }
+            Err(e) if e.is::<RequestTimeout>() => return Err(e),
+            Ok(_) | Err(_) => {}
+        }
+    }
+    let Some(result) = result else {
+        let mut tried = vec!["definition"];
+        tried.extend(DEFINITION_FALLBACKS.iter().map(|fallback| fallback.name()));
+        return Ok(ToolResult {
+            content: vec![ContentItem {
+                content_type: "text".to_string(),
+                text: serde_json::to_string_pretty(&json!({
+                    "message": "no definition found",
+                    "tried": tried
+                }))?,
+            }],
+        });
+    };
 
-    // Simplify result to reduce token usage
+    let columns = client.document_columns(uris_in(&result)).await;
+    let std_version = std_version(&server.workspace_root, &server.toolchain_cache, &result).await;
+    // Simplify result to reduce token usage. Fallback answers say which
+    // request produced them.
     let simplified = if let Some(defs) = result.as_array() {
         let simple_defs: Vec<Value> = defs
             .iter()
             .filter_map(|d| {
-                let target_uri = d["targetUri"].as_str()?;
-                let line = d["targetSelectionRange"]["start"]["line"].as_u64()?;
-                let char = d["targetSelectionRange"]["start"]["character"].as_u64()?;
-                let path = target_uri.strip_prefix("file://").unwrap_or(target_uri);
-
-                Some(json!({
-                    "location": format!("{}:{}:{}", path, line, char)
-                }))
+                // `LocationLink`s, or plain `Location`s from some requests.
+                let (target_uri, range) = match d["targetUri"].as_str() {
+                    Some(target_uri) => (target_uri, &d["targetSelectionRange"]),
+                    None => (d["uri"].as_str()?, &d["range"]),
+                };
+                let line = range["start"]["line"].as_u64()?;
+                let char = range["start"]["character"].as_u64()?;
+                let char = columns.to_user(target_uri, line, char);
+                let location = result_location(
+                    &client.path_style,
+                    target_uri,
+                    line,
+                    char,
+                    std_version.as_deref(),
+                );
+
+                let mut def = json!({
+                    "location": location
+                });
+                if let Some(via) = via {
+                    def["via"] = json!(via);
+                }
+                Some(def)
             })
             .collect();
         json!(simple_defs)
@@ -213,18 +898,62 @@ async fn handle_definition(server: &mut RustAnalyzerMCPServer, args: Value) -> R
     })
 }
 
+/// The version of the standard library for results pointing into the
+/// sysroot, whose paths don't say it: the workspace rustc's release.
+/// `None` for results without such files, or when rustc can't say.
+async fn std_version(
+    workspace: &Path,
+    cache: &SharedToolchainCache,
+    result: &Value,
+) -> Option<String> {
+    let in_sysroot = uris_in(result).iter().any(|uri| {
+        ExternalFile::of(&uri_path(uri)).is_some_and(|external| external.version.is_none())
+    });
+    if !in_sysroot {
+        return None;
+    }
+    rustc_info(workspace, cache).await["release"]
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Where a result points: `path:line:col`, or for a file of a registry
+/// crate or the standard library, the crate, version and path in it, since
+/// those paths are long and may not be readable by the caller.
+fn result_location(
+    path_style: &PathStyle,
+    uri: &str,
+    line: u64,
+    col: u64,
+    std_version: Option<&str>,
+) -> Value {
+    let file = uri_path(uri);
+    match ExternalFile::of(&file) {
+        Some(mut external) => {
+            if external.version.is_none() {
+                external.version = std_version.map(str::to_string);
+            }
+            external.location(&file, line, col)
+        }
+        None => json!(format!("{}:{}:{}", path_style.display(uri), line, col)),
+    }
+}
+
 async fn handle_references(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
     let file_path = ToolParams::extract_file_path(&args)?;
     let (line, character) = ToolParams::extract_position(&args)?;
 
-    let uri = server.open_document_if_needed(&file_path).await?;
+    let uri = server
+        .open_document_if_needed(&file_path, args["content"].as_str())
+        .await?;
 
     let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
     };
 
     // Retry logic: wait for indexing to complete
-    let retry_interval = Duration::from_millis(RETRY_INTERVAL_MILLIS);
+    let retry_interval = server.config.retry_interval();
+    let indexing_timeout = server.config.indexing_timeout();
     let start = Instant::now();
     let mut logged_waiting = false;
 
@@ -236,13 +965,21 @@ async fn handle_references(server: &mut RustAnalyzerMCPServer, args: Value) -> R
                 }
                 break result;
             }
+            Err(e) if e.is::<RequestTimeout>() => return Err(e),
             Ok(_) | Err(_) => {
-                check_retry_timeout("references", &start, &mut logged_waiting)?;
+                check_retry_timeout(
+                    "references",
+                    &start,
+                    indexing_timeout,
+                    &mut logged_waiting,
+                    client,
+                )?;
                 tokio::time::sleep(retry_interval).await;
             }
         }
     };
 
+    let columns = client.document_columns(uris_in(&result)).await;
     // Simplify result to reduce token usage
     let simplified = if let Some(refs) = result.as_array() {
         let simple_refs: Vec<Value> = refs
@@ -251,7 +988,8 @@ async fn handle_references(server: &mut RustAnalyzerMCPServer, args: Value) -> R
                 let uri = r["uri"].as_str()?;
                 let line = r["range"]["start"]["line"].as_u64()?;
                 let char = r["range"]["start"]["character"].as_u64()?;
-                let path = uri.strip_prefix("file://").unwrap_or(uri);
+                let char = columns.to_user(uri, line, char);
+                let path = client.path_style.display(uri);
 
                 Some(json!({
                     "location": format!("{}:{}:{}", path, line, char)
@@ -278,14 +1016,17 @@ async fn handle_implementation(
     let file_path = ToolParams::extract_file_path(&args)?;
     let (line, character) = ToolParams::extract_position(&args)?;
 
-    let uri = server.open_document_if_needed(&file_path).await?;
+    let uri = server
+        .open_document_if_needed(&file_path, args["content"].as_str())
+        .await?;
 
     let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
     };
 
     // Retry logic: wait for indexing to complete
-    let retry_interval = Duration::from_millis(RETRY_INTERVAL_MILLIS);
+    let retry_interval = server.config.retry_interval();
+    let indexing_timeout = server.config.indexing_timeout();
     let start = Instant::now();
     let mut logged_waiting = false;
 
@@ -297,13 +1038,22 @@ async fn handle_implementation(
                 }
                 break result;
             }
+            Err(e) if e.is::<RequestTimeout>() => return Err(e),
             Ok(_) | Err(_) => {
-                check_retry_timeout("implementation", &start, &mut logged_waiting)?;
+                check_retry_timeout(
+                    "implementation",
+                    &start,
+                    indexing_timeout,
+                    &mut logged_waiting,
+                    client,
+                )?;
                 tokio::time::sleep(retry_interval).await;
             }
         }
     };
 
+    let columns = client.document_columns(uris_in(&result)).await;
+    let std_version = std_version(&server.workspace_root, &server.toolchain_cache, &result).await;
     // Simplify result to reduce token usage
     let simplified = if let Some(impls) = result.as_array() {
         let simple_impls: Vec<Value> = impls
@@ -312,10 +1062,17 @@ async fn handle_implementation(
                 let target_uri = imp["targetUri"].as_str()?;
                 let line = imp["targetRange"]["start"]["line"].as_u64()?;
                 let char = imp["targetRange"]["start"]["character"].as_u64()?;
-                let path = target_uri.strip_prefix("file://").unwrap_or(target_uri);
+                let char = columns.to_user(target_uri, line, char);
+                let location = result_location(
+                    &client.path_style,
+                    target_uri,
+                    line,
+                    char,
+                    std_version.as_deref(),
+                );
 
                 Some(json!({
-                    "location": format!("{}:{}:{}", path, line, char)
+                    "location": location
                 }))
             })
             .collect();
@@ -339,10 +1096,12 @@ async fn handle_parent_module(
     let file_path = ToolParams::extract_file_path(&args)?;
     let (line, character) = ToolParams::extract_position(&args)?;
 
-    let uri = server.open_document_if_needed(&file_path).await?;
+    let uri = server
+        .open_document_if_needed(&file_path, args["content"].as_str())
+        .await?;
 
     let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
     };
 
     let result = client.parent_module(&uri, line, character).await?;
@@ -353,7 +1112,7 @@ async fn handle_parent_module(
             .iter()
             .filter_map(|m| {
                 let target_uri = m["targetUri"].as_str()?;
-                let path = target_uri.strip_prefix("file://").unwrap_or(target_uri);
+                let path = client.path_style.display(target_uri);
                 Some(json!({"location": path}))
             })
             .collect();
@@ -376,22 +1135,26 @@ async fn handle_incoming_calls(
 ) -> Result<ToolResult> {
     let file_path = ToolParams::extract_file_path(&args)?;
     let (line, character) = ToolParams::extract_position(&args)?;
+    let options = CallerOptions::parse(&args)?;
 
-    let uri = server.open_document_if_needed(&file_path).await?;
+    let uri = server
+        .open_document_if_needed(&file_path, args["content"].as_str())
+        .await?;
 
     let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
     };
 
     // Retry logic: wait for indexing to complete
-    let retry_interval = Duration::from_millis(RETRY_INTERVAL_MILLIS);
+    let retry_interval = server.config.retry_interval();
+    let indexing_timeout = server.config.indexing_timeout();
     let start = Instant::now();
     let mut logged_waiting = false;
 
     let result = loop {
         // First, prepare call hierarchy to get the item
         match client.prepare_call_hierarchy(&uri, line, character).await {
-            Ok(items) if !items.is_null() && items.as_array().map_or(false, |a| !a.is_empty()) => {
+            Ok(items) if !items.is_null() && items.as_array().is_some_and(|a| !a.is_empty()) => {
                 // Get the first item and find incoming calls
                 let item = &items[0];
                 match client.incoming_calls(item.clone()).await {
@@ -401,38 +1164,49 @@ async fn handle_incoming_calls(
                         }
                         break result;
                     }
+                    Err(e) if e.is::<RequestTimeout>() => return Err(e),
                     Err(_) => {
-                        check_retry_timeout("incoming_calls", &start, &mut logged_waiting)?;
+                        check_retry_timeout(
+                            "incoming_calls",
+                            &start,
+                            indexing_timeout,
+                            &mut logged_waiting,
+                            client,
+                        )?;
                         tokio::time::sleep(retry_interval).await;
                     }
                 }
             }
+            Err(e) if e.is::<RequestTimeout>() => return Err(e),
             Ok(_) | Err(_) => {
-                check_retry_timeout("incoming_calls", &start, &mut logged_waiting)?;
+                check_retry_timeout(
+                    "incoming_calls",
+                    &start,
+                    indexing_timeout,
+                    &mut logged_waiting,
+                    client,
+                )?;
                 tokio::time::sleep(retry_interval).await;
             }
         }
     };
 
-    // Simplify result
+    let columns = client.document_columns(uris_in(&result)).await;
+    // Simplify result, with the details asked for added to the page of
+    // callers returned.
     let simplified = if let Some(calls) = result.as_array() {
-        let simple_calls: Vec<Value> = calls
-            .iter()
-            .filter_map(|call| {
-                let from = &call["from"];
-                let name = from["name"].as_str()?;
-                let uri = from["uri"].as_str()?;
-                let line = from["range"]["start"]["line"].as_u64()?;
-                let char = from["range"]["start"]["character"].as_u64()?;
-                let path = uri.strip_prefix("file://").unwrap_or(uri);
-
-                Some(json!({
-                    "caller": name,
-                    "location": format!("{}:{}:{}", path, line, char)
-                }))
-            })
-            .collect();
-        json!(simple_calls)
+        let mut lookups = CallerLookups::default();
+        let mut simple_calls = Vec::new();
+        for call in options.page(calls) {
+            let Some(mut caller) = simplify_incoming_call(call, &columns, client) else {
+                continue;
+            };
+            options
+                .add_details(client, call, &mut caller, &mut lookups)
+                .await;
+            simple_calls.push(caller);
+        }
+        options.shape(simple_calls, calls.len())
     } else {
         result
     };
@@ -452,21 +1226,24 @@ async fn handle_outgoing_calls(
     let file_path = ToolParams::extract_file_path(&args)?;
     let (line, character) = ToolParams::extract_position(&args)?;
 
-    let uri = server.open_document_if_needed(&file_path).await?;
+    let uri = server
+        .open_document_if_needed(&file_path, args["content"].as_str())
+        .await?;
 
     let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
     };
 
     // Retry logic: wait for indexing to complete
-    let retry_interval = Duration::from_millis(RETRY_INTERVAL_MILLIS);
+    let retry_interval = server.config.retry_interval();
+    let indexing_timeout = server.config.indexing_timeout();
     let start = Instant::now();
     let mut logged_waiting = false;
 
     let result = loop {
         // First, prepare call hierarchy to get the item
         match client.prepare_call_hierarchy(&uri, line, character).await {
-            Ok(items) if !items.is_null() && items.as_array().map_or(false, |a| !a.is_empty()) => {
+            Ok(items) if !items.is_null() && items.as_array().is_some_and(|a| !a.is_empty()) => {
                 // Get the first item and find outgoing calls
                 let item = &items[0];
                 match client.outgoing_calls(item.clone()).await {
@@ -476,19 +1253,34 @@ async fn handle_outgoing_calls(
                         }
                         break result;
                     }
+                    Err(e) if e.is::<RequestTimeout>() => return Err(e),
                     Err(_) => {
-                        check_retry_timeout("outgoing_calls", &start, &mut logged_waiting)?;
+                        check_retry_timeout(
+                            "outgoing_calls",
+                            &start,
+                            indexing_timeout,
+                            &mut logged_waiting,
+                            client,
+                        )?;
                         tokio::time::sleep(retry_interval).await;
                     }
                 }
             }
+            Err(e) if e.is::<RequestTimeout>() => return Err(e),
             Ok(_) | Err(_) => {
-                check_retry_timeout("outgoing_calls", &start, &mut logged_waiting)?;
+                check_retry_timeout(
+                    "outgoing_calls",
+                    &start,
+                    indexing_timeout,
+                    &mut logged_waiting,
+                    client,
+                )?;
                 tokio::time::sleep(retry_interval).await;
             }
         }
     };
 
+    let columns = client.document_columns(uris_in(&result)).await;
     // Simplify result
     let simplified = if let Some(calls) = result.as_array() {
         let simple_calls: Vec<Value> = calls
@@ -499,7 +1291,8 @@ async fn handle_outgoing_calls(
                 let uri = to["uri"].as_str()?;
                 let line = to["range"]["start"]["line"].as_u64()?;
                 let char = to["range"]["start"]["character"].as_u64()?;
-                let path = uri.strip_prefix("file://").unwrap_or(uri);
+                let char = columns.to_user(uri, line, char);
+                let path = client.path_style.display(uri);
 
                 Some(json!({
                     "callee": name,
@@ -525,17 +1318,31 @@ async fn handle_inlay_hint(
     args: Value,
 ) -> Result<ToolResult> {
     let file_path = ToolParams::extract_file_path(&args)?;
-    let (start_line, start_character) = ToolParams::extract_position(&args)?;
-    let end_line = args["end_line"].as_u64().ok_or_else(|| anyhow!("Missing end_line"))? as u32;
-    let end_character = args["end_character"].as_u64().ok_or_else(|| anyhow!("Missing end_character"))? as u32;
+    let (start_line, start_character, end_line, end_character) = ToolParams::extract_range(&args)?;
 
-    let uri = server.open_document_if_needed(&file_path).await?;
+    let uri = server
+        .open_document_if_needed(&file_path, args["content"].as_str())
+        .await?;
+
+    let key = server
+        .result_key("rust_analyzer_inlay_hint", &uri, &args)
+        .await;
+    if let Some(text) = key.as_ref().and_then(|key| server.result_cache.get(key)) {
+        debug!("Inlay hint cache hit for {}", uri);
+        return Ok(ToolResult {
+            content: vec![ContentItem {
+                content_type: "text".to_string(),
+                text,
+            }],
+        });
+    }
 
     let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
     };
 
     let result = client.inlay_hint(&uri, start_line, start_character, end_line, end_character).await?;
+    let columns = client.document_columns([&uri]).await;
 
     // Simplify result to reduce token usage
     let simplified = if let Some(hints) = result.as_array() {
@@ -545,6 +1352,7 @@ async fn handle_inlay_hint(
                 let position = &h["position"];
                 let line = position["line"].as_u64()?;
                 let char = position["character"].as_u64()?;
+                let char = columns.to_user(&uri, line, char);
 
                 // Extract label (can be string or array)
                 let label = if let Some(label_str) = h["label"].as_str() {
@@ -584,10 +1392,14 @@ async fn handle_inlay_hint(
         result
     };
 
+    let text = serde_json::to_string_pretty(&simplified)?;
+    if let Some(key) = key {
+        server.result_cache.insert(key, text.clone());
+    }
     Ok(ToolResult {
         content: vec![ContentItem {
             content_type: "text".to_string(),
-            text: serde_json::to_string_pretty(&simplified)?,
+            text,
         }],
     })
 }
@@ -596,10 +1408,12 @@ async fn handle_completion(server: &mut RustAnalyzerMCPServer, args: Value) -> R
     let file_path = ToolParams::extract_file_path(&args)?;
     let (line, character) = ToolParams::extract_position(&args)?;
 
-    let uri = server.open_document_if_needed(&file_path).await?;
+    let uri = server
+        .open_document_if_needed(&file_path, args["content"].as_str())
+        .await?;
 
     let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
     };
 
     let result = client.completion(&uri, line, character).await?;
@@ -614,22 +1428,57 @@ async fn handle_completion(server: &mut RustAnalyzerMCPServer, args: Value) -> R
 
 async fn handle_symbols(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
     let file_path = ToolParams::extract_file_path(&args)?;
+    let markdown = match args["output"].as_str() {
+        None | Some("json") => false,
+        Some("markdown") => true,
+        Some(other) => {
+            return Err(tool_error!(
+                InvalidParams,
+                "Unknown output '{}'; expected json or markdown",
+                other
+            ))
+        }
+    };
 
     debug!("Getting symbols for file: {}", file_path);
-    let uri = server.open_document_if_needed(&file_path).await?;
+    let uri = server
+        .open_document_if_needed(&file_path, args["content"].as_str())
+        .await?;
     debug!("Document opened with URI: {}", uri);
 
+    let key = server
+        .result_key("rust_analyzer_symbols", &uri, &args)
+        .await;
+    if let Some(text) = key.as_ref().and_then(|key| server.result_cache.get(key)) {
+        debug!("Document symbols cache hit for {}", uri);
+        return Ok(ToolResult {
+            content: vec![ContentItem {
+                content_type: "text".to_string(),
+                text,
+            }],
+        });
+    }
+
     let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
     };
 
     let result = client.document_symbols(&uri).await?;
     debug!("Document symbols result: {:?}", result);
 
+    let text = if markdown {
+        symbol_outline(&result)
+    } else {
+        serde_json::to_string_pretty(&result)?
+    };
+    if let Some(key) = key {
+        server.result_cache.insert(key, text.clone());
+    }
+
     Ok(ToolResult {
         content: vec![ContentItem {
             content_type: "text".to_string(),
-            text: serde_json::to_string_pretty(&result)?,
+            text,
         }],
     })
 }
@@ -639,36 +1488,92 @@ async fn handle_workspace_symbol(
     args: Value,
 ) -> Result<ToolResult> {
     let Some(query) = args["query"].as_str() else {
-        return Err(anyhow!("Missing query parameter"));
+        return Err(tool_error!(InvalidParams, "Missing query parameter"));
+    };
+
+    let scope = match args["scope"].as_str() {
+        None | Some("workspace") => SymbolScope::Workspace,
+        Some("all") => SymbolScope::All,
+        Some(scope) => {
+            return Err(tool_error!(
+                InvalidParams,
+                "Unknown scope '{}'; expected \"workspace\" or \"all\"",
+                scope
+            ))
+        }
     };
 
     debug!("Searching workspace symbols for query: {}", query);
 
+    // Results hold their paths already written, so each style has its own.
+    let relative = server
+        .client
+        .as_ref()
+        .is_some_and(|client| client.path_style.is_relative());
+    let mut cache_key = query.to_string();
+    if relative {
+        cache_key = format!("relative:{}", cache_key);
+    }
+    if scope == SymbolScope::All {
+        cache_key = format!("all:{}", cache_key);
+    }
+    let generation = server.workspace_generation().await;
+    if !args["no_cache"].as_bool().unwrap_or(false) {
+        if let Some(result) = generation.and_then(|g| server.symbol_cache.get(&cache_key, g)) {
+            debug!("Workspace symbol cache hit for query: {}", query);
+            return Ok(ToolResult {
+                content: vec![ContentItem {
+                    content_type: "text".to_string(),
+                    text: serde_json::to_string_pretty(&result)?,
+                }],
+            });
+        }
+    }
+
     let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
     };
 
     // Retry logic: wait for indexing to complete
-    let retry_interval = Duration::from_millis(RETRY_INTERVAL_MILLIS);
+    let retry_interval = server.config.retry_interval();
+    let indexing_timeout = server.config.indexing_timeout();
     let start = Instant::now();
     let mut logged_waiting = false;
 
-    let result = loop {
-        match client.workspace_symbol(query).await {
+    let mut result = loop {
+        match client.workspace_symbol(query, scope).await {
             Ok(result) if is_result_ready!(result) => {
                 if logged_waiting {
                     info!("workspace_symbol: Indexing complete, returning results");
                 }
                 break result;
             }
+            Err(e) if e.is::<RequestTimeout>() => return Err(e),
             Ok(_) | Err(_) => {
-                check_retry_timeout("workspace_symbol", &start, &mut logged_waiting)?;
+                check_retry_timeout(
+                    "workspace_symbol",
+                    &start,
+                    indexing_timeout,
+                    &mut logged_waiting,
+                    client,
+                )?;
                 tokio::time::sleep(retry_interval).await;
             }
         }
     };
 
     debug!("Workspace symbol result: {:?}", result);
+    if let Some(hidden) = result["hidden_external"].as_u64() {
+        result["note"] = json!(format!(
+            "{} matches outside the workspace were left out; pass \"scope\": \"all\" to include them",
+            hidden
+        ));
+    }
+    if let Some(generation) = generation {
+        server
+            .symbol_cache
+            .insert(&cache_key, generation, result.clone());
+    }
 
     Ok(ToolResult {
         content: vec![ContentItem {
@@ -681,14 +1586,28 @@ async fn handle_workspace_symbol(
 async fn handle_format(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
     let file_path = ToolParams::extract_file_path(&args)?;
 
-    let uri = server.open_document_if_needed(&file_path).await?;
+    let uri = server
+        .open_document_if_needed(&file_path, args["content"].as_str())
+        .await?;
 
     let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
     };
 
     let result = client.formatting(&uri).await?;
 
+    if args["apply"].as_bool() == Some(true) {
+        let edit = json!({ "changes": { uri.clone(): result } });
+        let unsaved = args["content"].as_str().map(|c| (uri.as_str(), c));
+        let files = apply_workspace_edit(client, &edit, unsaved).await?;
+        return Ok(ToolResult {
+            content: vec![ContentItem {
+                content_type: "text".to_string(),
+                text: serde_json::to_string_pretty(&json!({ "applied": true, "files": files }))?,
+            }],
+        });
+    }
+
     Ok(ToolResult {
         content: vec![ContentItem {
             content_type: "text".to_string(),
@@ -704,16 +1623,53 @@ async fn handle_code_actions(
     let file_path = ToolParams::extract_file_path(&args)?;
     let (line, character, end_line, end_character) = ToolParams::extract_range(&args)?;
 
-    let uri = server.open_document_if_needed(&file_path).await?;
+    let uri = server
+        .open_document_if_needed(&file_path, args["content"].as_str())
+        .await?;
 
     let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
     };
 
     let result = client
         .code_actions(&uri, line, character, end_line, end_character)
         .await?;
 
+    if !args["apply"].is_null() {
+        let actions = result.as_array().cloned().unwrap_or_default();
+        let Some(mut action) = select_code_action(&actions, &args["apply"]) else {
+            let titles: Vec<&str> = actions.iter().filter_map(|a| a["title"].as_str()).collect();
+            return Err(tool_error!(
+                InvalidParams,
+                "No code action matches {}; available: {}",
+                args["apply"],
+                titles.join(", ")
+            ));
+        };
+        if action["edit"].is_null() && !action["data"].is_null() {
+            action = client.resolve_code_action(&action).await?;
+        }
+        if action["edit"].is_null() {
+            return Err(tool_error!(
+                InvalidParams,
+                "Code action '{}' runs a command instead of editing files, which is not supported",
+                action["title"].as_str().unwrap_or_default()
+            ));
+        }
+
+        let unsaved = args["content"].as_str().map(|c| (uri.as_str(), c));
+        let files = apply_workspace_edit(client, &action["edit"], unsaved).await?;
+        return Ok(ToolResult {
+            content: vec![ContentItem {
+                content_type: "text".to_string(),
+                text: serde_json::to_string_pretty(&json!({
+                    "applied": action["title"],
+                    "files": files,
+                }))?,
+            }],
+        });
+    }
+
     Ok(ToolResult {
         content: vec![ContentItem {
             content_type: "text".to_string(),
@@ -722,9 +1678,50 @@ async fn handle_code_actions(
     })
 }
 
+/// Pick a code action by index or by exact title.
+fn select_code_action(actions: &[Value], selector: &Value) -> Option<Value> {
+    match selector {
+        Value::Number(n) => actions.get(n.as_u64()? as usize).cloned(),
+        Value::String(title) => actions.iter().find(|a| a["title"] == *title).cloned(),
+        _ => None,
+    }
+}
+
+/// Apply a `WorkspaceEdit` to the files on disk and notify rust-analyzer.
+/// `unsaved` is a document (URI, text) analyzed from unsaved content, whose
+/// edits apply to that text rather than to the file. Every file's edits are
+/// checked before anything is written. Returns the paths written.
+pub(super) async fn apply_workspace_edit(
+    client: &mut RustAnalyzerClient,
+    edit: &Value,
+    unsaved: Option<(&str, &str)>,
+) -> Result<Vec<String>> {
+    let mut updates = Vec::new();
+    for (uri, edits) in workspace_edit_changes(edit)? {
+        let path = uri_path(&uri);
+        let content = match unsaved {
+            Some((unsaved_uri, text)) if unsaved_uri == uri => text.to_string(),
+            _ => tokio::fs::read_to_string(&path)
+                .await
+                .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?,
+        };
+        let updated = apply_text_edits(&content, &edits, client.position_encoding())
+            .map_err(|e| anyhow!("Cannot apply edits to {}: {}", path.display(), e))?;
+        updates.push((path, updated));
+    }
+
+    let mut written = Vec::new();
+    for (path, content) in updates {
+        client.write_document(&path, &content).await?;
+        written.push(client.path_style.display(&path.display().to_string()));
+    }
+    Ok(written)
+}
+
 async fn handle_get_workspace(server: &RustAnalyzerMCPServer) -> Result<ToolResult> {
     let result = json!({
         "workspace": server.workspace_root.display().to_string(),
+        "workspace_folders": server.workspace_folders(),
         "initialized": server.client.is_some()
     });
 
@@ -736,12 +1733,26 @@ async fn handle_get_workspace(server: &RustAnalyzerMCPServer) -> Result<ToolResu
     })
 }
 
+/// `path` as a workspace root: canonical if it exists, otherwise made
+/// absolute against the current directory.
+fn absolute_workspace_root(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| {
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            std::env::current_dir()
+                .unwrap_or_else(|_| PathBuf::from("."))
+                .join(path)
+        }
+    })
+}
+
 async fn handle_set_workspace(
     server: &mut RustAnalyzerMCPServer,
     args: Value,
 ) -> Result<ToolResult> {
     let Some(workspace_path) = args["workspace_path"].as_str() else {
-        return Err(anyhow!("Missing workspace_path"));
+        return Err(tool_error!(InvalidParams, "Missing workspace_path"));
     };
 
     // Resolve the new workspace path.
@@ -749,21 +1760,14 @@ async fn handle_set_workspace(
 
     // Validate path exists before anything else.
     if !new_workspace_root.exists() {
-        return Err(anyhow!(
+        return Err(tool_error!(
+            FileNotFound,
             "Workspace path does not exist: {}",
             new_workspace_root.display()
         ));
     }
 
-    let new_workspace_root = new_workspace_root.canonicalize().unwrap_or_else(|_| {
-        if new_workspace_root.is_absolute() {
-            new_workspace_root.clone()
-        } else {
-            std::env::current_dir()
-                .unwrap_or_else(|_| PathBuf::from("."))
-                .join(&new_workspace_root)
-        }
-    });
+    let new_workspace_root = absolute_workspace_root(&new_workspace_root);
 
     // Skip reinitialization if same workspace and client is already running.
     if server.workspace_root == new_workspace_root && server.client.is_some() {
@@ -785,6 +1789,7 @@ async fn handle_set_workspace(
         client.shutdown().await?;
     }
     server.client = None;
+    server.document_cache.clear();
 
     // Track the workspace change.
     server.init_trigger = InitTrigger::WorkspaceChange {
@@ -805,17 +1810,130 @@ async fn handle_set_workspace(
     })
 }
 
+async fn handle_workspace_folders(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let paths = |key: &str| -> Result<Vec<PathBuf>> {
+        match &args[key] {
+            Value::Null => Ok(Vec::new()),
+            Value::Array(items) => items
+                .iter()
+                .map(|item| match item.as_str() {
+                    Some(path) => Ok(PathBuf::from(path)),
+                    None => Err(tool_error!(
+                        InvalidParams,
+                        "{} must be a list of paths",
+                        key
+                    )),
+                })
+                .collect(),
+            _ => Err(tool_error!(
+                InvalidParams,
+                "{} must be a list of paths",
+                key
+            )),
+        }
+    };
+    let (add, remove) = (paths("add")?, paths("remove")?);
+    let (added, removed) = server.change_workspace_folders(&add, &remove).await?;
+
+    let result = json!({
+        "workspace_folders": server.workspace_folders(),
+        "added": added,
+        "removed": removed
+    });
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text: serde_json::to_string_pretty(&result)?,
+        }],
+    })
+}
+
+async fn handle_list_tests(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+
+    let uri = server
+        .open_document_if_needed(&file_path, args["content"].as_str())
+        .await?;
+
+    let Some(client) = &mut server.client else {
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
+    };
+
+    // Retry logic: wait for indexing to complete
+    let retry_interval = server.config.retry_interval();
+    let indexing_timeout = server.config.indexing_timeout();
+    let start = Instant::now();
+    let mut logged_waiting = false;
+
+    // A file without tests gives an empty list, which is only worth waiting
+    // out while indexing.
+    let result = loop {
+        match client.tests(&uri, None).await {
+            Ok(result) if is_result_ready!(result) => {
+                if logged_waiting {
+                    info!("list_tests: Indexing complete, returning results");
+                }
+                break result;
+            }
+            Err(e) if e.is::<RequestTimeout>() => return Err(e),
+            Ok(result) if !client.progress.lock().await.is_indexing() => break result,
+            Ok(_) | Err(_) => {
+                check_retry_timeout(
+                    "list_tests",
+                    &start,
+                    indexing_timeout,
+                    &mut logged_waiting,
+                    client,
+                )?;
+                tokio::time::sleep(retry_interval).await;
+            }
+        }
+    };
+
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text: serde_json::to_string_pretty(&result)?,
+        }],
+    })
+}
+
+async fn handle_close_document(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let closed = server.close_document(&file_path).await?;
+
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text: serde_json::to_string_pretty(&json!({
+                "file_path": file_path,
+                "closed": closed,
+                "open_documents": server.open_documents().await.len(),
+            }))?,
+        }],
+    })
+}
+
 async fn handle_diagnostics(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
     let file_path = ToolParams::extract_file_path(&args)?;
+    let filter = DiagnosticFilter::from_args(&args)?;
 
-    let uri = server.open_document_if_needed(&file_path).await?;
+    let uri = server
+        .open_document_if_needed(&file_path, args["content"].as_str())
+        .await?;
 
     // Poll for diagnostics - rust-analyzer needs time to run cargo check.
     // For files with expected errors (like diagnostics_test.rs), poll longer.
     let should_poll = file_path.contains("diagnostics_test") || file_path.contains("simple_error");
 
     let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
     };
 
     let mut result = json!([]);
@@ -843,7 +1961,7 @@ async fn handle_diagnostics(server: &mut RustAnalyzerMCPServer, args: Value) ->
         result = client.diagnostics(&uri).await?;
     }
 
-    let diagnostics = format_diagnostics(&file_path, &result);
+    let diagnostics = format_diagnostics(&file_path, &filter.apply(&result));
 
     Ok(ToolResult {
         content: vec![ContentItem {
@@ -855,16 +1973,27 @@ async fn handle_diagnostics(server: &mut RustAnalyzerMCPServer, args: Value) ->
 
 async fn handle_workspace_diagnostics(
     server: &mut RustAnalyzerMCPServer,
-    _args: Value,
+    args: Value,
 ) -> Result<ToolResult> {
-    let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
-    };
+    let formatted = workspace_diagnostics_report(server, &args).await?;
 
-    let result = client.workspace_diagnostics().await?;
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text: serde_json::to_string_pretty(&formatted)?,
+        }],
+    })
+}
 
-    // Format workspace diagnostics.
-    let formatted = format_workspace_diagnostics(&server.workspace_root, &result);
+/// The workspace diagnostics once rust-analyzer's check has finished (or
+/// timed out), with how the check went under `flycheck`.
+async fn cargo_check_result(
+    server: &mut RustAnalyzerMCPServer,
+    args: &Value,
+    flycheck: Value,
+) -> Result<ToolResult> {
+    let mut formatted = workspace_diagnostics_report(server, args).await?;
+    formatted["flycheck"] = flycheck;
 
     Ok(ToolResult {
         content: vec![ContentItem {
@@ -874,7 +2003,45 @@ async fn handle_workspace_diagnostics(
     })
 }
 
-fn format_workspace_diagnostics(workspace_root: &Path, result: &Value) -> Value {
+/// Published diagnostics for the whole workspace, filtered by `args`.
+async fn workspace_diagnostics_report(
+    server: &mut RustAnalyzerMCPServer,
+    args: &Value,
+) -> Result<Value> {
+    let filter = DiagnosticFilter::from_args(args)?;
+    let Some(client) = &mut server.client else {
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
+    };
+
+    let mut result = client.workspace_diagnostics().await?;
+    if let Some(by_uri) = result.as_object_mut() {
+        for diagnostics in by_uri.values_mut() {
+            *diagnostics = filter.apply(diagnostics);
+        }
+    }
+    let evicted = client.diagnostic_store_stats().await.evicted;
+
+    // Format workspace diagnostics.
+    let mut formatted =
+        format_workspace_diagnostics(&server.workspace_root, &client.path_style, &result);
+    if evicted > 0 {
+        if let Some(summary) = formatted["summary"].as_object_mut() {
+            summary.insert("evicted_files".to_string(), json!(evicted));
+            summary.insert(
+                "note".to_string(),
+                json!("Diagnostics for the least recently published files were dropped to bound memory; call rust_analyzer_diagnostics on a file to fetch them again"),
+            );
+        }
+    }
+
+    Ok(formatted)
+}
+
+pub(super) fn format_workspace_diagnostics(
+    workspace_root: &Path,
+    paths: &PathStyle,
+    result: &Value,
+) -> Value {
     if result.is_null() {
         return json!({
             "workspace": workspace_root.display().to_string(),
@@ -973,7 +2140,12 @@ fn format_workspace_diagnostics(workspace_root: &Path, result: &Value) -> Value
             }
         }
 
-        output["files"][uri] = json!({
+        // Keyed by URI unless paths are relative.
+        let file = match paths.is_relative() {
+            true => paths.display(uri),
+            false => uri.clone(),
+        };
+        output["files"][file] = json!({
             "diagnostics": diagnostics,
             "summary": {
                 "errors": file_errors,
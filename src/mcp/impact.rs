@@ -0,0 +1,293 @@
+use anyhow::Result;
+use log::info;
+use serde_json::{json, Map, Value};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    time::Instant,
+};
+
+use crate::{
+    lsp::{
+        paths::{file_uri, uri_path},
+        position::{uris_in, DocumentColumns},
+        RequestTimeout, RustAnalyzerClient,
+    },
+    protocol::{
+        mcp::{ContentItem, ToolResult},
+        tool_error,
+    },
+};
+
+use super::{
+    cargo_metadata::{cargo_metadata, owning_package},
+    handlers::{check_retry_timeout, ToolParams},
+    module_tree::definition_location,
+    server::RustAnalyzerMCPServer,
+};
+
+/// What rust-analyzer answered for each part of the report. `calls` is
+/// `None` when there's no call hierarchy at the position, as on a struct.
+struct Answers {
+    references: Result<Value>,
+    implementations: Result<Value>,
+    definition: Result<Value>,
+    calls: Result<Option<Value>>,
+}
+
+/// Send every request the report needs at once. Incoming calls follow the
+/// call hierarchy item as soon as it arrives.
+async fn request_answers(
+    client: &mut RustAnalyzerClient,
+    uri: &str,
+    line: u32,
+    character: u32,
+) -> Result<Answers> {
+    let character = client
+        .document_columns([uri])
+        .await
+        .to_lsp(uri, line, character);
+    let position = json!({
+        "textDocument": { "uri": uri },
+        "position": { "line": line, "character": character }
+    });
+    let mut with_declaration = position.clone();
+    with_declaration["context"] = json!({ "includeDeclaration": true });
+
+    let references = client.request_detached("textDocument/references", Some(with_declaration))?;
+    let implementations =
+        client.request_detached("textDocument/implementation", Some(position.clone()))?;
+    let definition = client.request_detached("textDocument/definition", Some(position.clone()))?;
+    let prepare = client.request_detached("textDocument/prepareCallHierarchy", Some(position))?;
+    let calls = async {
+        let items = prepare.await?;
+        let Some(item) = items.as_array().and_then(|items| items.first()) else {
+            return Ok(None);
+        };
+        let params = json!({ "item": item });
+        let calls = client
+            .request_detached("callHierarchy/incomingCalls", Some(params))?
+            .await?;
+        Ok(Some(calls))
+    };
+
+    let (references, implementations, definition, calls) =
+        tokio::join!(references, implementations, definition, calls);
+    Ok(Answers {
+        references,
+        implementations,
+        definition,
+        calls,
+    })
+}
+
+/// `path:line:column` of the start of `range` in `uri`.
+fn location(columns: &DocumentColumns, uri: &str, range: &Value) -> Option<String> {
+    let line = range["start"]["line"].as_u64()?;
+    let character = columns.to_user(uri, line, range["start"]["character"].as_u64()?);
+    Some(format!(
+        "{}:{}:{}",
+        uri_path(uri).display(),
+        line,
+        character
+    ))
+}
+
+/// `rust_analyzer_impact`: references grouped by file, direct callers, and
+/// implementations of the symbol at a position, in one report, with
+/// whether anything outside the symbol's own crate uses it. A part
+/// rust-analyzer can't answer is left out and listed under `unavailable`.
+pub(crate) async fn handle_impact(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let (line, character) = ToolParams::extract_position(&args)?;
+
+    let uri = server
+        .open_document_if_needed(&file_path, args["content"].as_str())
+        .await?;
+    let metadata = cargo_metadata(&server.workspace_root, &server.metadata_cache).await;
+
+    let Some(client) = &mut server.client else {
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
+    };
+
+    // Retry logic: wait for indexing to complete. References always include
+    // the declaration, so they're empty only until then.
+    let retry_interval = server.config.retry_interval();
+    let indexing_timeout = server.config.indexing_timeout();
+    let start = Instant::now();
+    let mut logged_waiting = false;
+
+    let answers = loop {
+        let answers = request_answers(client, &uri, line, character).await?;
+        match &answers.references {
+            Ok(references) if references.as_array().is_some_and(|refs| !refs.is_empty()) => {
+                if logged_waiting {
+                    info!("impact: Indexing complete, returning results");
+                }
+                break answers;
+            }
+            Err(e) if e.is::<RequestTimeout>() => break answers,
+            Ok(_) | Err(_) => {
+                check_retry_timeout(
+                    "impact",
+                    &start,
+                    indexing_timeout,
+                    &mut logged_waiting,
+                    client,
+                )?;
+                tokio::time::sleep(retry_interval).await;
+            }
+        }
+    };
+
+    let mut uris = BTreeSet::new();
+    for answer in [
+        &answers.references,
+        &answers.implementations,
+        &answers.definition,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        uris.extend(uris_in(answer));
+    }
+    if let Ok(Some(calls)) = &answers.calls {
+        uris.extend(uris_in(calls));
+    }
+    let columns = client.document_columns(uris).await;
+
+    let mut report = Map::new();
+    let mut unavailable = Map::new();
+
+    let definition = answers
+        .definition
+        .as_ref()
+        .ok()
+        .and_then(definition_location);
+    if let Some((file, line, character)) = &definition {
+        let uri = file_uri(file);
+        let character = columns.to_user(&uri, *line, *character);
+        report.insert(
+            "definition".to_string(),
+            json!(format!("{}:{}:{}", file.display(), line, character)),
+        );
+    }
+    let metadata = match metadata {
+        Ok((metadata, _)) => Some(metadata),
+        Err(e) => {
+            unavailable.insert("used_outside_crate".to_string(), json!(e.to_string()));
+            None
+        }
+    };
+    let crate_of = |file: &Path| {
+        metadata
+            .as_ref()
+            .and_then(|metadata| owning_package(metadata, file))
+    };
+    // Without a definition, the symbol is taken to be the queried file's.
+    let queried_file = uri_path(&uri);
+    let symbol_file = definition
+        .as_ref()
+        .map_or(queried_file.as_path(), |(file, _, _)| file);
+    let symbol_crate = crate_of(symbol_file);
+    if let Some(name) = symbol_crate {
+        report.insert("crate".to_string(), json!(name));
+    }
+
+    match &answers.references {
+        Ok(references) => {
+            let mut by_file: BTreeMap<String, usize> = BTreeMap::new();
+            for reference in references.as_array().into_iter().flatten() {
+                if let Some(uri) = reference["uri"].as_str() {
+                    *by_file
+                        .entry(uri_path(uri).display().to_string())
+                        .or_default() += 1;
+                }
+            }
+            let mut outside = BTreeSet::new();
+            let files: Vec<Value> = by_file
+                .iter()
+                .map(|(path, count)| {
+                    let owner = crate_of(Path::new(path));
+                    if owner != symbol_crate {
+                        outside.insert(owner.unwrap_or("(no package)"));
+                    }
+                    json!({ "file": path, "crate": owner, "count": count })
+                })
+                .collect();
+            report.insert(
+                "references".to_string(),
+                json!({ "total": by_file.values().sum::<usize>(), "by_file": files }),
+            );
+            if metadata.is_some() && symbol_crate.is_some() {
+                report.insert("used_outside_crate".to_string(), json!(!outside.is_empty()));
+                report.insert("outside_crates".to_string(), json!(outside));
+            }
+        }
+        Err(e) => {
+            unavailable.insert("references".to_string(), json!(e.to_string()));
+        }
+    }
+
+    match &answers.calls {
+        Ok(Some(calls)) => {
+            let callers: Vec<Value> = calls
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|call| {
+                    let from = &call["from"];
+                    Some(json!({
+                        "caller": from["name"].as_str()?,
+                        "location": location(&columns, from["uri"].as_str()?, &from["range"])?
+                    }))
+                })
+                .collect();
+            report.insert("callers".to_string(), json!(callers));
+        }
+        Ok(None) => {
+            unavailable.insert(
+                "callers".to_string(),
+                json!("No call hierarchy at this position"),
+            );
+        }
+        Err(e) => {
+            unavailable.insert("callers".to_string(), json!(e.to_string()));
+        }
+    }
+
+    match &answers.implementations {
+        Ok(implementations) => {
+            let implementations: Vec<Value> = implementations
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|imp| {
+                    let (uri, range) = match imp["targetUri"].as_str() {
+                        Some(uri) => (uri, &imp["targetRange"]),
+                        None => (imp["uri"].as_str()?, &imp["range"]),
+                    };
+                    Some(json!({ "location": location(&columns, uri, range)? }))
+                })
+                .collect();
+            report.insert("implementations".to_string(), json!(implementations));
+        }
+        Err(e) => {
+            unavailable.insert("implementations".to_string(), json!(e.to_string()));
+        }
+    }
+
+    if !unavailable.is_empty() {
+        report.insert("unavailable".to_string(), Value::Object(unavailable));
+    }
+
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text: serde_json::to_string_pretty(&Value::Object(report))?,
+        }],
+    })
+}
@@ -0,0 +1,85 @@
+use serde_json::Value;
+use std::fmt::Write;
+
+use crate::lsp::symbol_kind_name;
+
+/// The keyword an item of `kind` is declared with, if it has one.
+fn keyword(kind: u64) -> Option<&'static str> {
+    match symbol_kind_name(kind) {
+        "module" => Some("mod"),
+        "struct" => Some("struct"),
+        "enum" => Some("enum"),
+        "interface" => Some("trait"),
+        "function" | "method" => Some("fn"),
+        "constant" => Some("const"),
+        "type_parameter" => Some("type"),
+        _ => None,
+    }
+}
+
+/// How a symbol reads in source: `fn new() -> Foo` from the name `new` and
+/// rust-analyzer's detail `fn() -> Foo`, `value: i32` for a field, or the
+/// name as is for an `impl` block.
+pub fn symbol_label(symbol: &Value) -> String {
+    let name = symbol["name"].as_str().unwrap_or_default();
+    if name.starts_with("impl ") {
+        return name.to_string();
+    }
+    let detail = symbol["detail"]
+        .as_str()
+        .filter(|detail| !detail.is_empty());
+    let keyword = symbol["kind"].as_u64().and_then(keyword);
+    if let (Some(detail), Some("fn")) = (detail, keyword) {
+        // `fn(..)`, `const fn<T>(..)`: the name goes after `fn`.
+        if let Some(at) = detail.find("fn(").or_else(|| detail.find("fn<")) {
+            return format!("{}fn {}{}", &detail[..at], name, &detail[at + 2..]);
+        }
+    }
+    let mut label = match keyword {
+        Some(keyword) => format!("{} {}", keyword, name),
+        None => name.to_string(),
+    };
+    match (detail, keyword) {
+        (Some(detail), Some("type")) => write!(label, " = {}", detail).unwrap(),
+        (Some(detail), _) => write!(label, ": {}", detail).unwrap(),
+        (None, _) => {}
+    }
+    label
+}
+
+/// The line a symbol's name is on: `selectionRange` of a document symbol,
+/// else its `range`, else the range of a flat symbol's `location`.
+fn symbol_line(symbol: &Value) -> Option<u64> {
+    [
+        &symbol["selectionRange"],
+        &symbol["range"],
+        &symbol["location"]["range"],
+    ]
+    .into_iter()
+    .find_map(|range| range["start"]["line"].as_u64())
+}
+
+/// A `textDocument/documentSymbol` or `workspace/symbol` result as an
+/// indented markdown list, one `- label  (line N)` per symbol with
+/// children nested under their parent. Lines are 0-based, as in every
+/// other tool's locations.
+pub fn symbol_outline(symbols: &Value) -> String {
+    let mut outline = String::new();
+    let mut stack: Vec<(usize, &Value)> = symbols
+        .as_array()
+        .into_iter()
+        .flatten()
+        .rev()
+        .map(|symbol| (0, symbol))
+        .collect();
+    while let Some((depth, symbol)) = stack.pop() {
+        write!(outline, "{}- {}", "  ".repeat(depth), symbol_label(symbol)).unwrap();
+        if let Some(line) = symbol_line(symbol) {
+            write!(outline, "  (line {})", line).unwrap();
+        }
+        outline.push('\n');
+        let children = symbol["children"].as_array().into_iter().flatten();
+        stack.extend(children.rev().map(|child| (depth + 1, child)));
+    }
+    outline
+}
@@ -1,5 +1,31 @@
+mod call_graph;
+mod callers;
+mod cargo_check;
+pub mod cargo_metadata;
+pub mod context;
+pub mod describe;
+mod diagnostics_for;
+pub mod docs_links;
+mod find_references;
+pub mod glob_imports;
 pub(crate) mod handlers;
+mod impact;
+pub mod markdown;
+pub mod module_tree;
+mod public_api;
+pub mod rename;
+pub mod run_test;
+pub mod scip;
 mod server;
-pub(crate) mod tools;
+pub mod stdio;
+pub mod tags;
+pub mod toolchain;
+pub mod tools;
+mod trait_impls;
+pub mod truncate;
+pub mod type_graph;
+mod unused;
+mod usage_stats;
 
-pub use server::{InitTrigger, RustAnalyzerMCPServer};
+pub use handlers::{handle_shared_tool_call, handle_tool_call, refuse_while_indexing};
+pub use server::{BackendStatus, InitTrigger, RustAnalyzerMCPServer};
@@ -0,0 +1,605 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::{
+    collections::HashSet,
+    future::Future,
+    path::{Path, PathBuf},
+    pin::Pin,
+};
+
+use crate::{
+    lsp::{
+        paths::uri_path,
+        position::{line_text, PositionEncoding},
+    },
+    protocol::{
+        mcp::{ContentItem, ToolResult},
+        tool_error,
+    },
+};
+
+use super::{
+    cargo_metadata::{cargo_metadata, crate_root},
+    server::RustAnalyzerMCPServer,
+};
+
+/// How many modules deep `rust_analyzer_module_tree` goes by default.
+const MODULE_TREE_MAX_DEPTH: usize = 8;
+
+/// LSP `SymbolKind::Module`.
+const MODULE_KIND: u64 = 2;
+
+/// The items declared directly in a module: how many, how many are `pub`,
+/// the `pub` ones and `pub use` re-exports, and its submodules, each in
+/// declaration order.
+#[derive(Debug, Default, PartialEq)]
+pub struct Outline {
+    pub items: usize,
+    pub public_items: usize,
+    pub exports: Vec<Export>,
+    pub reexports: Vec<Reexport>,
+    pub modules: Vec<ModuleDecl>,
+}
+
+/// A `pub` item: its name, LSP `SymbolKind`, where its name is (line and
+/// character column), and whether it's `#[doc(hidden)]`.
+#[derive(Debug, PartialEq)]
+pub struct Export {
+    pub name: String,
+    pub kind: u64,
+    pub line: u32,
+    pub character: u32,
+    pub hidden: bool,
+}
+
+/// A name a `pub use` exports: the path as written, the name it's exported
+/// as (`*` for a glob), where the path's last segment is, and whether the
+/// `use` is `#[doc(hidden)]`.
+#[derive(Debug, PartialEq)]
+pub struct Reexport {
+    pub path: String,
+    pub name: String,
+    pub line: u32,
+    pub character: u32,
+    pub hidden: bool,
+}
+
+/// A `mod` item: where its name is (line and character column), whether
+/// it's `pub`, and for `mod name { ... }`, the outline of its body.
+#[derive(Debug, PartialEq)]
+pub struct ModuleDecl {
+    pub name: String,
+    pub line: u32,
+    pub character: u32,
+    pub public: bool,
+    pub body: Option<Outline>,
+}
+
+/// A symbol's name, kind, and byte range in the document.
+struct Span {
+    name: String,
+    kind: u64,
+    start: usize,
+    end: usize,
+}
+
+/// Byte offset of an LSP position in `content`.
+fn byte_offset(content: &str, encoding: PositionEncoding, position: &Value) -> Option<usize> {
+    let line = position["line"].as_u64()? as u32;
+    let text = line_text(content, line)?;
+    let line_start = content
+        .split('\n')
+        .take(line as usize)
+        .map(|line| line.len() + 1)
+        .sum::<usize>();
+    let character = encoding.units_to_char(text, position["character"].as_u64()? as u32);
+    let column = text
+        .char_indices()
+        .nth(character as usize)
+        .map_or(text.len(), |(offset, _)| offset);
+    Some(line_start + column)
+}
+
+/// Every symbol in a `textDocument/documentSymbol` result, nested
+/// (`DocumentSymbol`) or flat (`SymbolInformation`).
+fn spans(content: &str, encoding: PositionEncoding, symbols: &Value, out: &mut Vec<Span>) {
+    for symbol in symbols.as_array().into_iter().flatten() {
+        let range = match symbol.get("location") {
+            Some(location) => &location["range"],
+            None => &symbol["range"],
+        };
+        let (Some(start), Some(end)) = (
+            byte_offset(content, encoding, &range["start"]),
+            byte_offset(content, encoding, &range["end"]),
+        ) else {
+            continue;
+        };
+        if end < start {
+            continue;
+        }
+        out.push(Span {
+            name: symbol["name"].as_str().unwrap_or_default().to_string(),
+            kind: symbol["kind"].as_u64().unwrap_or_default(),
+            start,
+            end,
+        });
+        spans(content, encoding, &symbol["children"], out);
+    }
+}
+
+/// `text` past leading whitespace, comments, and attributes.
+fn skip_trivia(mut text: &str) -> &str {
+    loop {
+        text = text.trim_start();
+        if let Some(rest) = text.strip_prefix("//") {
+            text = rest.split_once('\n').map_or("", |(_, rest)| rest);
+        } else if let Some(rest) = text.strip_prefix("/*") {
+            text = rest.split_once("*/").map_or("", |(_, rest)| rest);
+        } else if let Some(rest) = text.strip_prefix('#') {
+            let rest = rest.trim_start_matches('!').trim_start();
+            let mut depth = 0;
+            let end = rest.char_indices().find_map(|(offset, ch)| {
+                match ch {
+                    '[' => depth += 1,
+                    ']' => depth -= 1,
+                    _ => {}
+                }
+                (depth == 0).then_some(offset + 1)
+            });
+            text = &rest[end.unwrap_or(rest.len())..];
+        } else {
+            return text;
+        }
+    }
+}
+
+/// Whether an item's source, attributes and all, declares it plain `pub`
+/// rather than private or `pub(crate)` and the like.
+fn is_public(source: &str) -> bool {
+    skip_trivia(source)
+        .strip_prefix("pub")
+        .is_some_and(|rest| rest.starts_with(char::is_whitespace))
+}
+
+/// Whether an item's attributes include `#[doc(hidden)]`.
+fn is_doc_hidden(attributes: &str) -> bool {
+    let attributes: String = attributes.split_whitespace().collect();
+    attributes.contains("doc(hidden)")
+}
+
+/// Byte offset of the item's name in its source: the first occurrence of
+/// `name` as a whole word past the attributes.
+fn name_offset(source: &str, name: &str) -> Option<usize> {
+    let is_ident = |ch: char| ch.is_alphanumeric() || ch == '_';
+    let start = source.len() - skip_trivia(source).len();
+    source[start..]
+        .match_indices(name)
+        .map(|(offset, _)| start + offset)
+        .find(|&offset| {
+            !source[..offset].ends_with(is_ident)
+                && !source[offset + name.len()..].starts_with(is_ident)
+        })
+}
+
+/// 0-based line and character column of a byte offset into `content`.
+fn line_character(content: &str, offset: usize) -> (u32, u32) {
+    let before = &content[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (
+        before.matches('\n').count() as u32,
+        content[line_start..offset].chars().count() as u32,
+    )
+}
+
+/// The names exported by the use tree in `content[start..end]`, each with
+/// the path to it prefixed by `prefix`.
+fn use_tree_leaves(
+    content: &str,
+    start: usize,
+    end: usize,
+    prefix: &str,
+    hidden: bool,
+    out: &mut Vec<Reexport>,
+) {
+    let join = |head: &str| match prefix {
+        "" => head.to_string(),
+        _ => format!("{}::{}", prefix, head),
+    };
+
+    // Split on the commas outside braces.
+    let mut pieces = Vec::new();
+    let (mut depth, mut piece_start) = (0, start);
+    for (offset, ch) in content[start..end].char_indices() {
+        match ch {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                pieces.push((piece_start, start + offset));
+                piece_start = start + offset + 1;
+            }
+            _ => {}
+        }
+    }
+    pieces.push((piece_start, end));
+
+    for (piece_start, piece_end) in pieces {
+        let piece = &content[piece_start..piece_end];
+        let piece_start = piece_start + (piece.len() - piece.trim_start().len());
+        let piece = piece.trim();
+        if piece.is_empty() {
+            continue;
+        }
+        if let (Some(open), Some(close)) = (piece.find('{'), piece.rfind('}')) {
+            let head = piece[..open].trim().trim_end_matches("::");
+            let prefix = join(head);
+            let (open, close) = (piece_start + open + 1, piece_start + close);
+            use_tree_leaves(content, open, close, &prefix, hidden, out);
+            continue;
+        }
+
+        let (path, alias) = match piece.split_once(" as ") {
+            Some((path, alias)) => (path.trim_end(), Some(alias.trim())),
+            None => (piece, None),
+        };
+        let last = path.rsplit("::").next().unwrap_or(path);
+        let (line, character) = line_character(content, piece_start + path.len() - last.len());
+        let (path, name) = match last {
+            // `use a::b::{self}` exports `b`.
+            "self" => (
+                prefix.to_string(),
+                prefix.rsplit("::").next().unwrap_or(prefix),
+            ),
+            _ => (join(path), alias.unwrap_or(last)),
+        };
+        // `as _` only brings a trait's methods into scope.
+        if name == "_" || name.is_empty() {
+            continue;
+        }
+        out.push(Reexport {
+            path,
+            name: name.to_string(),
+            line,
+            character,
+            hidden,
+        });
+    }
+}
+
+/// The `pub use` items in `content[start..end]` outside `members`, which
+/// hide nested scopes like function bodies.
+fn reexports(content: &str, start: usize, end: usize, members: &[&Span]) -> Vec<Reexport> {
+    let mut reexports = Vec::new();
+    let mut offset = start;
+    while let Some(found) = content[offset..end].find("pub use ") {
+        let at = offset + found;
+        offset = at + "pub use ".len();
+        let line_start = content[..at].rfind('\n').map_or(0, |newline| newline + 1);
+        if members
+            .iter()
+            .any(|member| member.start <= at && at < member.end)
+            || !content[line_start..at].trim().is_empty()
+        {
+            continue;
+        }
+        let Some(length) = content[offset..end].find(';') else {
+            break;
+        };
+        // Attributes are between the previous item and this one.
+        let previous_end = content[start..line_start]
+            .rfind([';', '}', '{'])
+            .map_or(start, |found| start + found + 1);
+        let hidden = is_doc_hidden(&content[previous_end..at]);
+        use_tree_leaves(content, offset, offset + length, "", hidden, &mut reexports);
+        offset += length;
+    }
+    reexports
+}
+
+/// The items directly inside `scope` (the whole file when `None`), leaving
+/// out those nested in another symbol, like methods and fields.
+fn outline_scope(content: &str, spans: &[Span], scope: Option<&Span>) -> Outline {
+    let inside = |outer: &Span, inner: &Span| {
+        outer.start <= inner.start
+            && inner.end <= outer.end
+            && (outer.start, outer.end) != (inner.start, inner.end)
+    };
+    let in_scope = |span: &&Span| scope.is_none_or(|scope| inside(scope, span));
+    let members: Vec<&Span> = spans
+        .iter()
+        .filter(in_scope)
+        .filter(|span| {
+            !spans
+                .iter()
+                .filter(in_scope)
+                .any(|other| inside(other, span))
+        })
+        .collect();
+
+    let (start, end) = scope.map_or((0, content.len()), |scope| (scope.start, scope.end));
+    let mut outline = Outline {
+        reexports: reexports(content, start, end, &members),
+        ..Outline::default()
+    };
+    for span in &members {
+        let source = &content[span.start..span.end];
+        let public = is_public(source);
+        let name_offset = span.start + name_offset(source, &span.name).unwrap_or(0);
+        let (line, character) = line_character(content, name_offset);
+        outline.items += 1;
+        if public {
+            outline.public_items += 1;
+            let attributes = &source[..source.len() - skip_trivia(source).len()];
+            outline.exports.push(Export {
+                name: span.name.clone(),
+                kind: span.kind,
+                line,
+                character,
+                hidden: is_doc_hidden(attributes),
+            });
+        }
+        if span.kind != MODULE_KIND {
+            continue;
+        }
+
+        let body = source[name_offset - span.start..]
+            .contains('{')
+            .then(|| outline_scope(content, spans, Some(span)));
+        outline.modules.push(ModuleDecl {
+            name: span.name.clone(),
+            line,
+            character,
+            public,
+            body,
+        });
+    }
+    outline
+}
+
+/// The top level of a file from its `textDocument/documentSymbol` result,
+/// with inline modules outlined in place.
+pub fn outline_file(content: &str, symbols: &Value, encoding: PositionEncoding) -> Outline {
+    let mut all = Vec::new();
+    spans(content, encoding, symbols, &mut all);
+    outline_scope(content, &all, None)
+}
+
+/// Where `mod name;` in a module whose children live in `dir` is, by the
+/// usual layout: `dir/name.rs` or `dir/name/mod.rs`.
+fn conventional_module_file(dir: &Path, name: &str) -> Option<PathBuf> {
+    [
+        dir.join(format!("{}.rs", name)),
+        dir.join(name).join("mod.rs"),
+    ]
+    .into_iter()
+    .find(|file| file.is_file())
+}
+
+/// Directory holding the files of submodules declared in `file`.
+fn child_module_dir(file: &Path, is_crate_root: bool) -> PathBuf {
+    let dir = file.parent().unwrap_or(Path::new("/")).to_path_buf();
+    match file.file_stem().and_then(|stem| stem.to_str()) {
+        Some(stem) if !is_crate_root && stem != "mod" => dir.join(stem),
+        _ => dir,
+    }
+}
+
+/// A module the walk reached: its outline, less the submodules, which are
+/// in `children`.
+pub(crate) struct ModuleNode {
+    pub name: String,
+    pub file: PathBuf,
+    pub uri: String,
+    pub inline: bool,
+    pub outline: Outline,
+    pub children: Vec<Child>,
+}
+
+/// A submodule: walked, past the depth limit, or not walked for `reason`.
+pub(crate) enum Child {
+    Module(ModuleNode),
+    Truncated(String),
+    Failed(String, String),
+}
+
+impl ModuleNode {
+    /// The node as `rust_analyzer_module_tree` reports it.
+    fn to_json(&self) -> Value {
+        let mut node = json!({ "name": self.name, "file": self.file });
+        if self.inline {
+            node["inline"] = json!(true);
+        }
+        node["items"] = json!(self.outline.items);
+        node["public_items"] = json!(self.outline.public_items);
+        node["modules"] = self
+            .children
+            .iter()
+            .map(|child| match child {
+                Child::Module(module) => module.to_json(),
+                Child::Truncated(name) => json!({ "name": name, "truncated": true }),
+                Child::Failed(name, reason) => json!({ "name": name, "error": reason }),
+            })
+            .collect();
+        node
+    }
+}
+
+type NodeFuture<'a> = Pin<Box<dyn Future<Output = Result<ModuleNode>> + Send + 'a>>;
+
+/// Walks a crate's modules from its root file.
+pub(crate) struct ModuleWalk<'a> {
+    pub server: &'a mut RustAnalyzerMCPServer,
+    pub max_depth: usize,
+    /// Skip modules that aren't `pub`, and so everything in them.
+    pub only_public: bool,
+    pub visited: HashSet<PathBuf>,
+}
+
+impl ModuleWalk<'_> {
+    /// The module whose source is `file`, and everything below it.
+    pub fn file_node(
+        &mut self,
+        name: String,
+        file: PathBuf,
+        depth: usize,
+        root: bool,
+    ) -> NodeFuture<'_> {
+        Box::pin(async move {
+            if !self.visited.insert(file.clone()) {
+                return Err(anyhow!("already included"));
+            }
+            let content = tokio::fs::read_to_string(&file)
+                .await
+                .map_err(|e| anyhow!("Failed to read {}: {}", file.display(), e))?;
+            let uri = self
+                .server
+                .open_document_if_needed(&file.to_string_lossy(), None)
+                .await?;
+            let Some(client) = &mut self.server.client else {
+                return Err(tool_error!(BackendUnavailable, "Client not initialized"));
+            };
+            let symbols = client.document_symbols(&uri).await?;
+            let outline = outline_file(&content, &symbols, client.position_encoding());
+
+            let dir = child_module_dir(&file, root);
+            let node = ModuleNode {
+                name,
+                file,
+                uri,
+                inline: false,
+                outline,
+                children: Vec::new(),
+            };
+            self.module_node(node, dir, depth).await
+        })
+    }
+
+    /// `node` with its submodules, whose files live in `dir`, walked.
+    fn module_node(&mut self, mut node: ModuleNode, dir: PathBuf, depth: usize) -> NodeFuture<'_> {
+        Box::pin(async move {
+            for module in std::mem::take(&mut node.outline.modules) {
+                if self.only_public && !module.public {
+                    continue;
+                }
+                if depth + 1 > self.max_depth {
+                    node.children.push(Child::Truncated(module.name));
+                    continue;
+                }
+                let child = match module.body {
+                    Some(body) => {
+                        let child = ModuleNode {
+                            name: module.name.clone(),
+                            file: node.file.clone(),
+                            uri: node.uri.clone(),
+                            inline: true,
+                            outline: body,
+                            children: Vec::new(),
+                        };
+                        let child_dir = dir.join(&module.name);
+                        self.module_node(child, child_dir, depth + 1).await
+                    }
+                    None => match self.module_file(&node.uri, &module, &dir).await {
+                        Some(file) => {
+                            self.file_node(module.name.clone(), file, depth + 1, false)
+                                .await
+                        }
+                        None => Err(anyhow!("file not found")),
+                    },
+                };
+                node.children.push(match child {
+                    Ok(child) => Child::Module(child),
+                    Err(e) => Child::Failed(module.name, e.to_string()),
+                });
+            }
+            Ok(node)
+        })
+    }
+
+    /// The file of `mod name;`: where rust-analyzer says it is, which
+    /// honours `#[path]`, or else where it usually is.
+    async fn module_file(&mut self, uri: &str, module: &ModuleDecl, dir: &Path) -> Option<PathBuf> {
+        let client = self.server.client.as_mut()?;
+        let definition = client
+            .definition(uri, module.line, module.character)
+            .await
+            .unwrap_or(Value::Null);
+        definition_location(&definition)
+            .map(|(file, _, _)| file)
+            .filter(|file| file.is_file())
+            .or_else(|| conventional_module_file(dir, &module.name))
+    }
+}
+
+/// The first location a `textDocument/definition` result points to, as a
+/// file path and 0-based line and character column.
+pub(crate) fn definition_location(definition: &Value) -> Option<(PathBuf, u64, u64)> {
+    let target = match definition {
+        Value::Array(locations) => locations.first()?,
+        Value::Object(_) => definition,
+        _ => return None,
+    };
+    let file = target["targetUri"].as_str().or(target["uri"].as_str())?;
+    if !file.starts_with("file://") {
+        return None;
+    }
+    let range = match &target["targetSelectionRange"] {
+        Value::Null => &target["range"],
+        range => range,
+    };
+    Some((
+        uri_path(file),
+        range["start"]["line"].as_u64().unwrap_or(0),
+        range["start"]["character"].as_u64().unwrap_or(0),
+    ))
+}
+
+/// The `max_depth` argument of a tool walking modules.
+pub(crate) fn max_depth_arg(args: &Value) -> Result<usize> {
+    match args.get("max_depth") {
+        None | Some(Value::Null) => Ok(MODULE_TREE_MAX_DEPTH),
+        Some(value) => value
+            .as_u64()
+            .map(|depth| depth as usize)
+            .ok_or_else(|| tool_error!(InvalidParams, "max_depth must be a whole number")),
+    }
+}
+
+/// `rust_analyzer_module_tree`: the crate's modules, file and inline, with
+/// how many items each declares and how many of those are `pub`.
+pub(crate) async fn handle_module_tree(
+    server: &mut RustAnalyzerMCPServer,
+    args: &Value,
+) -> Result<ToolResult> {
+    let max_depth = max_depth_arg(args)?;
+    let (metadata, _) = cargo_metadata(&server.workspace_root, &server.metadata_cache).await?;
+    let (package, target, root) = crate_root(
+        &metadata,
+        &server.workspace_root,
+        args["package"].as_str(),
+        args["target"].as_str(),
+    )?;
+
+    let mut walk = ModuleWalk {
+        server,
+        max_depth,
+        only_public: false,
+        visited: HashSet::new(),
+    };
+    let tree = walk
+        .file_node("crate".to_string(), root, 0, true)
+        .await?
+        .to_json();
+    let result = json!({
+        "package": package,
+        "target": target,
+        "files": walk.visited.len(),
+        "max_depth": max_depth,
+        "tree": tree
+    });
+
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text: serde_json::to_string_pretty(&result)?,
+        }],
+    })
+}
@@ -0,0 +1,155 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+use crate::{
+    lsp::{paths::file_uri, position::uris_in, symbol_kind_name},
+    protocol::{
+        mcp::{ContentItem, ToolResult},
+        tool_error,
+    },
+};
+
+use super::{
+    cargo_metadata::{cargo_metadata, crate_root},
+    module_tree::{definition_location, max_depth_arg, Child, ModuleNode, ModuleWalk},
+    server::RustAnalyzerMCPServer,
+};
+
+/// Collects the exported items of a module tree, path by path.
+struct ApiListing<'a> {
+    server: &'a mut RustAnalyzerMCPServer,
+    items: Vec<Value>,
+    /// Modules the walk didn't reach, by path, with why.
+    incomplete: Vec<Value>,
+}
+
+impl ApiListing<'_> {
+    /// Add what `node`, at `path`, exports, then what its submodules do.
+    /// Everything under a `#[doc(hidden)]` module is hidden too.
+    async fn add_module(&mut self, node: &ModuleNode, path: &str, hidden: bool) -> Result<()> {
+        let file = node.file.display();
+        for export in &node.outline.exports {
+            let mut item = json!({
+                "path": format!("{}::{}", path, export.name),
+                "kind": symbol_kind_name(export.kind),
+                "location": format!("{}:{}:{}", file, export.line, export.character)
+            });
+            if hidden || export.hidden {
+                item["hidden"] = json!(true);
+            }
+            self.items.push(item);
+        }
+
+        for reexport in &node.outline.reexports {
+            let mut item = json!({
+                "path": format!("{}::{}", path, reexport.name),
+                "kind": "reexport",
+                "source": reexport.path
+            });
+            // A glob names no single item to go to.
+            if reexport.name != "*" {
+                item["target"] = json!(
+                    self.resolve(&node.uri, reexport.line, reexport.character)
+                        .await?
+                );
+            }
+            if hidden || reexport.hidden {
+                item["hidden"] = json!(true);
+            }
+            self.items.push(item);
+        }
+
+        for child in &node.children {
+            match child {
+                Child::Module(module) => {
+                    let module_hidden = node
+                        .outline
+                        .exports
+                        .iter()
+                        .any(|export| export.name == module.name && export.hidden);
+                    let module_path = format!("{}::{}", path, module.name);
+                    Box::pin(self.add_module(module, &module_path, hidden || module_hidden))
+                        .await?;
+                }
+                Child::Truncated(name) => self.incomplete.push(json!({
+                    "path": format!("{}::{}", path, name),
+                    "reason": "deeper than max_depth"
+                })),
+                Child::Failed(name, reason) => self.incomplete.push(json!({
+                    "path": format!("{}::{}", path, name),
+                    "reason": reason
+                })),
+            }
+        }
+        Ok(())
+    }
+
+    /// Where the name at `line`:`character` of `uri` is defined, as
+    /// `file:line:column`.
+    async fn resolve(&mut self, uri: &str, line: u32, character: u32) -> Result<Option<String>> {
+        let Some(client) = &mut self.server.client else {
+            return Err(tool_error!(BackendUnavailable, "Client not initialized"));
+        };
+        let definition = client.definition(uri, line, character).await?;
+        let Some((file, line, character)) = definition_location(&definition) else {
+            return Ok(None);
+        };
+        let columns = client.document_columns(uris_in(&definition)).await;
+        let target_uri = file_uri(&file);
+        let character = columns.to_user(&target_uri, line, character);
+        Ok(Some(format!("{}:{}:{}", file.display(), line, character)))
+    }
+}
+
+/// `rust_analyzer_public_api`: every item a crate exports, by the path
+/// users name it with: `pub` items in `pub` modules, and `pub use`
+/// re-exports with where they lead.
+pub(crate) async fn handle_public_api(
+    server: &mut RustAnalyzerMCPServer,
+    args: &Value,
+) -> Result<ToolResult> {
+    let max_depth = max_depth_arg(args)?;
+    let (metadata, _) = cargo_metadata(&server.workspace_root, &server.metadata_cache).await?;
+    let (package, target, root) = crate_root(
+        &metadata,
+        &server.workspace_root,
+        args["package"].as_str(),
+        args["target"].as_str(),
+    )?;
+    let crate_name = target.replace('-', "_");
+
+    let mut walk = ModuleWalk {
+        server,
+        max_depth,
+        only_public: true,
+        visited: HashSet::new(),
+    };
+    let tree = walk.file_node("crate".to_string(), root, 0, true).await?;
+
+    let mut listing = ApiListing {
+        server: walk.server,
+        items: Vec::new(),
+        incomplete: Vec::new(),
+    };
+    listing.add_module(&tree, &crate_name, false).await?;
+    let mut items = listing.items;
+    items.sort_by(|a, b| a["path"].as_str().cmp(&b["path"].as_str()));
+
+    let mut result = json!({
+        "package": package,
+        "target": target,
+        "count": items.len(),
+        "items": items
+    });
+    if !listing.incomplete.is_empty() {
+        result["incomplete"] = json!(listing.incomplete);
+    }
+
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text: serde_json::to_string_pretty(&result)?,
+        }],
+    })
+}
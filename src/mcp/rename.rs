@@ -0,0 +1,274 @@
+use anyhow::{anyhow, Result};
+use log::info;
+use serde_json::{json, Value};
+use std::time::Instant;
+
+use crate::{
+    lsp::{
+        edits::{apply_text_edits, workspace_edit_changes},
+        paths::uri_path,
+        RequestTimeout, RustAnalyzerClient,
+    },
+    protocol::{
+        mcp::{ContentItem, ToolResult},
+        tool_error,
+    },
+};
+
+use super::{
+    handlers::{apply_workspace_edit, check_retry_timeout, ToolParams},
+    server::RustAnalyzerMCPServer,
+};
+
+/// Unchanged lines shown around each change in a preview's diffs.
+const DIFF_CONTEXT_LINES: usize = 3;
+
+/// Largest old-by-new line product aligned line by line; a bigger changed
+/// region is shown as removed, then added, whole.
+const DIFF_MAX_CELLS: usize = 4_000_000;
+
+/// Lines of `old` and `new` aligned: `' '` kept, `'-'` removed, `'+'`
+/// added. Lines common to both ends are kept without aligning them.
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(char, &'a str)> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_middle, new_middle) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let mut lines: Vec<(char, &str)> = old[..prefix].iter().map(|line| (' ', *line)).collect();
+    if old_middle.len().saturating_mul(new_middle.len()) > DIFF_MAX_CELLS {
+        lines.extend(old_middle.iter().map(|line| ('-', *line)));
+        lines.extend(new_middle.iter().map(|line| ('+', *line)));
+    } else {
+        // lcs[i][j]: length of the common subsequence of old[i..] and new[j..].
+        let (n, m) = (old_middle.len(), new_middle.len());
+        let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if old_middle[i] == new_middle[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && old_middle[i] == new_middle[j] {
+                lines.push((' ', old_middle[i]));
+                i += 1;
+                j += 1;
+            } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+                lines.push(('-', old_middle[i]));
+                i += 1;
+            } else {
+                lines.push(('+', new_middle[j]));
+                j += 1;
+            }
+        }
+    }
+    lines.extend(old[old.len() - suffix..].iter().map(|line| (' ', *line)));
+    lines
+}
+
+/// A unified diff of `old` and `new` for `path`, with `context` unchanged
+/// lines around each change, or an empty string when they're the same.
+pub fn unified_diff(old: &str, new: &str, path: &str, context: usize) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let lines = diff_lines(&old_lines, &new_lines);
+
+    // Line numbers in old and new before each aligned line.
+    let mut numbers = Vec::with_capacity(lines.len() + 1);
+    let (mut old_number, mut new_number) = (0, 0);
+    for (tag, _) in &lines {
+        numbers.push((old_number, new_number));
+        match tag {
+            '-' => old_number += 1,
+            '+' => new_number += 1,
+            _ => {
+                old_number += 1;
+                new_number += 1;
+            }
+        }
+    }
+    numbers.push((old_number, new_number));
+
+    let changes: Vec<usize> = (0..lines.len()).filter(|&i| lines[i].0 != ' ').collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+    let mut diff = format!("--- {}\n+++ {}\n", path, path);
+    let mut index = 0;
+    while index < changes.len() {
+        let start = changes[index].saturating_sub(context);
+        let mut end = changes[index] + 1;
+        // Changes close enough to share context go in one hunk.
+        while index < changes.len() && changes[index] <= end + 2 * context {
+            end = changes[index] + 1;
+            index += 1;
+        }
+        let end = (end + context).min(lines.len());
+
+        let (old_start, new_start) = numbers[start];
+        let (old_end, new_end) = numbers[end];
+        let range = |start: usize, len: usize| match len {
+            0 => format!("{},0", start),
+            _ => format!("{},{}", start + 1, len),
+        };
+        diff.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(old_start, old_end - old_start),
+            range(new_start, new_end - new_start)
+        ));
+        for (tag, line) in &lines[start..end] {
+            diff.push_str(&format!("{}{}\n", tag, line));
+        }
+    }
+    diff
+}
+
+/// What a rename would change, file by file, without writing anything. The
+/// edits apply to the text rust-analyzer has, which for an open file may
+/// differ from the file on disk; such files are flagged, since applying the
+/// rename later could conflict with those changes.
+async fn preview_workspace_edit(
+    client: &RustAnalyzerClient,
+    edit: &Value,
+    unsaved: Option<(&str, &str)>,
+) -> Result<Value> {
+    let mut files = Vec::new();
+    let mut occurrences = 0;
+    let mut any_modified = false;
+    for (uri, edits) in workspace_edit_changes(edit)? {
+        let path = uri_path(&uri);
+        let (content, modified) = match unsaved {
+            Some((unsaved_uri, text)) if unsaved_uri == uri => (text.to_string(), false),
+            _ => {
+                let on_disk = tokio::fs::read_to_string(&path).await.ok();
+                match (client.document_text(&uri).await, on_disk) {
+                    (Some(seen), on_disk) => {
+                        let modified = on_disk.as_deref() != Some(seen.as_str());
+                        (seen, modified)
+                    }
+                    (None, Some(on_disk)) => (on_disk, false),
+                    (None, None) => return Err(anyhow!("Failed to read {}", path.display())),
+                }
+            }
+        };
+        let updated = apply_text_edits(&content, &edits, client.position_encoding())
+            .map_err(|e| anyhow!("Cannot apply edits to {}: {}", path.display(), e))?;
+
+        let display = client.path_style.display(&uri);
+        let mut file = json!({
+            "file": display,
+            "occurrences": edits.len(),
+            "diff": unified_diff(&content, &updated, &display, DIFF_CONTEXT_LINES),
+        });
+        if modified {
+            file["modified"] = json!(true);
+            any_modified = true;
+        }
+        occurrences += edits.len();
+        files.push(file);
+    }
+
+    let mut result = json!({
+        "preview": true,
+        "files_touched": files.len(),
+        "occurrences": occurrences,
+        "files": files,
+    });
+    if any_modified {
+        result["warning"] = json!(
+            "Files marked modified changed on disk since rust-analyzer last read them; applying the rename may conflict with those changes"
+        );
+    }
+    Ok(result)
+}
+
+/// `rust_analyzer_rename`: rename the symbol at a position everywhere it's
+/// used, through rust-analyzer's `textDocument/rename`. With `preview`, the
+/// edit is only shown, as per-file unified diffs; otherwise it's written.
+pub(crate) async fn handle_rename(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let (line, character) = ToolParams::extract_position(&args)?;
+    let Some(new_name) = args["new_name"].as_str().filter(|name| !name.is_empty()) else {
+        return Err(tool_error!(InvalidParams, "Missing new_name"));
+    };
+    let preview = args["preview"].as_bool().unwrap_or(false);
+    let unsaved = args["content"].as_str();
+
+    let uri = server.open_document_if_needed(&file_path, unsaved).await?;
+
+    let Some(client) = &mut server.client else {
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
+    };
+
+    // Retry logic: wait for indexing to complete
+    let retry_interval = server.config.retry_interval();
+    let indexing_timeout = server.config.indexing_timeout();
+    let start = Instant::now();
+    let mut logged_waiting = false;
+
+    let edit = loop {
+        match client.rename(&uri, line, character, new_name).await {
+            Ok(edit) if !edit.is_null() => {
+                if logged_waiting {
+                    info!("rename: Indexing complete, returning results");
+                }
+                break edit;
+            }
+            Err(e) if e.is::<RequestTimeout>() => return Err(e),
+            Err(e) => {
+                return Err(tool_error!(
+                    InvalidParams,
+                    "Cannot rename at {}:{}: {}",
+                    line,
+                    character,
+                    e
+                ));
+            }
+            Ok(_) => {
+                check_retry_timeout(
+                    "rename",
+                    &start,
+                    indexing_timeout,
+                    &mut logged_waiting,
+                    client,
+                )?;
+                tokio::time::sleep(retry_interval).await;
+            }
+        }
+    };
+
+    let unsaved = unsaved.map(|content| (uri.as_str(), content));
+    let result = if preview {
+        preview_workspace_edit(client, &edit, unsaved).await?
+    } else {
+        let occurrences: usize = workspace_edit_changes(&edit)?.values().map(Vec::len).sum();
+        let files = apply_workspace_edit(client, &edit, unsaved).await?;
+        json!({
+            "renamed": new_name,
+            "files": files,
+            "occurrences": occurrences,
+        })
+    };
+
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text: serde_json::to_string_pretty(&result)?,
+        }],
+    })
+}
@@ -0,0 +1,255 @@
+use anyhow::{anyhow, Result};
+use log::info;
+use serde_json::{json, Value};
+use std::{
+    path::PathBuf,
+    process::Stdio,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Semaphore;
+
+use crate::protocol::{
+    mcp::{ContentItem, ToolResult},
+    tool_error,
+};
+
+use super::{handlers::ToolParams, server::RustAnalyzerMCPServer};
+
+/// Lines of failure output kept around the panic.
+const FAILURE_EXCERPT_LINES: usize = 40;
+
+/// A test ready to run: the cargo invocation rust-analyzer computed for it,
+/// and what limits the run.
+pub(crate) struct TestRun {
+    name: String,
+    cargo_args: Vec<String>,
+    executable_args: Vec<String>,
+    cwd: PathBuf,
+    timeout: Duration,
+    /// Shared by all runs, so they take turns on cargo's build lock.
+    permits: Arc<Semaphore>,
+}
+
+/// The test named by `args`: a `runnable` from `rust_analyzer_list_tests`,
+/// or the innermost test at `file_path`, `line`, `character`.
+pub(crate) async fn prepare_test_run(
+    server: &mut RustAnalyzerMCPServer,
+    args: &Value,
+    timeout: Option<Duration>,
+) -> Result<TestRun> {
+    let timeout = timeout.unwrap_or_else(|| server.config.test_timeout());
+    let permits = Arc::clone(&server.test_runs);
+
+    let runnable = match &args["runnable"] {
+        Value::Null => test_at_position(server, args).await?,
+        runnable => runnable.clone(),
+    };
+
+    let strings = |key: &str| -> Result<Vec<String>> {
+        runnable[key]
+            .as_array()
+            .ok_or_else(|| anyhow!("runnable is missing {}", key))?
+            .iter()
+            .map(|arg| {
+                arg.as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| tool_error!(InvalidParams, "runnable {} must be strings", key))
+            })
+            .collect()
+    };
+    let cargo_args = strings("cargo_args")?;
+    if cargo_args.first().map(String::as_str) != Some("test") {
+        return Err(anyhow!(
+            "runnable is not a test: cargo {}",
+            cargo_args.join(" ")
+        ));
+    }
+    let executable_args = strings("executable_args")?;
+
+    // Tests run in the workspace, never somewhere a caller points at.
+    let cwd = match runnable["cwd"].as_str() {
+        Some(cwd) => {
+            let cwd = PathBuf::from(cwd);
+            if !server
+                .workspace_folders()
+                .iter()
+                .any(|f| cwd.starts_with(f))
+            {
+                return Err(anyhow!(
+                    "runnable cwd {} is outside the workspace",
+                    cwd.display()
+                ));
+            }
+            cwd
+        }
+        None => server.workspace_root.clone(),
+    };
+
+    Ok(TestRun {
+        name: runnable["name"].as_str().unwrap_or_default().to_string(),
+        cargo_args,
+        executable_args,
+        cwd,
+        timeout,
+        permits,
+    })
+}
+
+/// The innermost test whose code contains the position in `args`.
+async fn test_at_position(server: &mut RustAnalyzerMCPServer, args: &Value) -> Result<Value> {
+    let Some(file_path) = args["file_path"].as_str() else {
+        return Err(anyhow!(
+            "Pass a runnable from rust_analyzer_list_tests, or file_path, line, and character"
+        ));
+    };
+    let (line, character) = ToolParams::extract_position(args)?;
+
+    let uri = server
+        .open_document_if_needed(file_path, args["content"].as_str())
+        .await?;
+    let Some(client) = &mut server.client else {
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
+    };
+    let tests = client.tests(&uri, Some((line, character))).await?;
+
+    // Runnables enclosing the position run it and its neighbours too; a
+    // single test is the narrowest.
+    let tests = tests.as_array().cloned().unwrap_or_default();
+    tests
+        .iter()
+        .find(|test| test["kind"] != "test_module")
+        .or_else(|| tests.last())
+        .cloned()
+        .ok_or_else(|| {
+            tool_error!(
+                InvalidParams,
+                "No test at {}:{}:{}",
+                file_path,
+                line,
+                character
+            )
+        })
+}
+
+impl TestRun {
+    fn command_line(&self) -> String {
+        let mut command = vec!["cargo".to_string()];
+        command.extend(self.cargo_args.iter().cloned());
+        if !self.executable_args.is_empty() {
+            command.push("--".to_string());
+            command.extend(self.executable_args.iter().cloned());
+        }
+        command.join(" ")
+    }
+
+    /// Run the test, killing cargo if it outlasts the timeout, and report
+    /// whether it passed along with the relevant part of any failure.
+    pub(crate) async fn run(self) -> Result<ToolResult> {
+        let queued = Instant::now();
+        let _permit = Arc::clone(&self.permits).acquire_owned().await?;
+        let queued_ms = queued.elapsed().as_millis() as u64;
+
+        info!("Running test {}: {}", self.name, self.command_line());
+        let mut command = tokio::process::Command::new("cargo");
+        command
+            .args(&self.cargo_args)
+            .current_dir(&self.cwd)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if !self.executable_args.is_empty() {
+            command.arg("--").args(&self.executable_args);
+        }
+        let child = command
+            .spawn()
+            .map_err(|e| anyhow!("Failed to run cargo: {}", e))?;
+
+        let start = Instant::now();
+        // On timeout the child is dropped, which kills it.
+        let output = tokio::time::timeout(self.timeout, child.wait_with_output()).await;
+        let duration_ms = start.elapsed().as_millis() as u64;
+
+        let mut result = json!({
+            "name": self.name,
+            "command": self.command_line(),
+            "duration_ms": duration_ms,
+            "queued_ms": queued_ms,
+        });
+        match output {
+            Err(_) => {
+                result["status"] = json!("timed_out");
+                result["passed"] = json!(false);
+                result["failure"] =
+                    json!(format!("Killed after {} seconds", self.timeout.as_secs()));
+            }
+            Ok(output) => {
+                let output = output?;
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                let status = match (output.status.success(), tests_run(&stdout)) {
+                    (true, 0) => "no_tests_run",
+                    (true, _) => "passed",
+                    (false, _) => "failed",
+                };
+                result["status"] = json!(status);
+                result["passed"] = json!(status == "passed");
+                result["exit_code"] = json!(output.status.code());
+                if status == "failed" {
+                    result["failure"] = json!(failure_excerpt(&stdout, &stderr));
+                }
+            }
+        }
+
+        Ok(ToolResult {
+            content: vec![ContentItem {
+                content_type: "text".to_string(),
+                text: serde_json::to_string_pretty(&result)?,
+            }],
+        })
+    }
+}
+
+/// Tests that passed or failed, summed over the `test result:` lines of
+/// libtest output.
+pub fn tests_run(stdout: &str) -> u64 {
+    stdout
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("test result: "))
+        .flat_map(|summary| summary.split(['.', ';']))
+        .filter_map(|count| {
+            let (count, what) = count.trim().split_once(' ')?;
+            if what == "passed" || what == "failed" {
+                count.parse::<u64>().ok()
+            } else {
+                None
+            }
+        })
+        .sum()
+}
+
+/// The part of a failed run worth reading: from the test's panic to the end
+/// of its message, or else the compiler errors, or else the end of stderr.
+pub fn failure_excerpt(stdout: &str, stderr: &str) -> String {
+    let lines: Vec<&str> = stdout.lines().collect();
+    if let Some(start) = lines.iter().position(|line| line.contains("panicked at")) {
+        let end = lines[start + 1..]
+            .iter()
+            .position(|line| {
+                line.starts_with("note: run with `RUST_BACKTRACE")
+                    || line.starts_with("---- ")
+                    || line.trim().is_empty()
+                    || *line == "failures:"
+            })
+            .map_or(lines.len(), |offset| start + 1 + offset);
+        return lines[start..end.min(start + FAILURE_EXCERPT_LINES)].join("\n");
+    }
+
+    let lines: Vec<&str> = stderr.lines().collect();
+    let start = lines
+        .iter()
+        .position(|line| line.starts_with("error"))
+        .unwrap_or_else(|| lines.len().saturating_sub(FAILURE_EXCERPT_LINES));
+    lines[start..lines.len().min(start + FAILURE_EXCERPT_LINES)].join("\n")
+}
@@ -0,0 +1,553 @@
+use anyhow::{anyhow, Result};
+use log::debug;
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    lsp::{paths::file_uri, position::PositionEncoding},
+    protocol::{
+        mcp::{ContentItem, ToolResult},
+        tool_error,
+    },
+};
+
+use super::{
+    cargo_metadata::{cargo_metadata, package_of},
+    server::RustAnalyzerMCPServer,
+    tags::{exclude_arg, output_arg, rust_files, SymbolWalk},
+    trait_impls::implementing_type,
+};
+
+/// Scheme of the symbols in an index, naming the indexer as SCIP asks.
+const SCIP_SCHEME: &str = "rust-analyzer";
+
+/// `SymbolRole.Definition` of an occurrence.
+const ROLE_DEFINITION: u64 = 1;
+
+/// A protobuf message being encoded: the few wire types SCIP's schema
+/// needs. Fields holding their default value are left out, as protobuf
+/// encoders do.
+#[derive(Debug, Default)]
+pub struct ProtoMessage {
+    bytes: Vec<u8>,
+}
+
+impl ProtoMessage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+
+    fn key(&mut self, field: u32, wire_type: u8) {
+        self.varint(u64::from(field) << 3 | u64::from(wire_type));
+    }
+
+    /// An integer or enum field.
+    pub fn uint(&mut self, field: u32, value: u64) -> &mut Self {
+        if value != 0 {
+            self.key(field, 0);
+            self.varint(value);
+        }
+        self
+    }
+
+    pub fn string(&mut self, field: u32, value: &str) -> &mut Self {
+        if !value.is_empty() {
+            self.bytes(field, value.as_bytes());
+        }
+        self
+    }
+
+    /// An embedded message, written even when empty.
+    pub fn message(&mut self, field: u32, message: &ProtoMessage) -> &mut Self {
+        self.bytes(field, &message.bytes);
+        self
+    }
+
+    /// A packed `repeated int32` field.
+    pub fn packed(&mut self, field: u32, values: &[i32]) -> &mut Self {
+        if !values.is_empty() {
+            let mut packed = ProtoMessage::new();
+            for value in values {
+                // Negative int32s take ten bytes, sign-extended.
+                packed.varint(i64::from(*value) as u64);
+            }
+            self.bytes(field, &packed.bytes);
+        }
+        self
+    }
+
+    fn bytes(&mut self, field: u32, bytes: &[u8]) {
+        self.key(field, 2);
+        self.varint(bytes.len() as u64);
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+/// An LSP range as SCIP has it: `[line, start, end]` when it's on one line,
+/// `[startLine, start, endLine, end]` otherwise.
+pub fn scip_range(range: &Value) -> Option<Vec<i32>> {
+    let field = |point: &str, name: &str| -> Option<i32> {
+        i32::try_from(range[point][name].as_u64()?).ok()
+    };
+    let (start_line, start) = (field("start", "line")?, field("start", "character")?);
+    let (end_line, end) = (field("end", "line")?, field("end", "character")?);
+    if start_line == end_line {
+        Some(vec![start_line, start, end])
+    } else {
+        Some(vec![start_line, start, end_line, end])
+    }
+}
+
+/// A descriptor name, in backticks unless it's all identifier characters.
+fn escape_name(name: &str) -> String {
+    if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        name.to_string()
+    } else {
+        format!("`{}`", name.replace('`', "``"))
+    }
+}
+
+/// The namespace descriptors of the module a file is, from its path in its
+/// package: `src/shapes/round.rs` is `shapes/round/`, and the crate roots
+/// and `mod.rs` files are their directory's module.
+pub fn module_descriptors(relative: &Path) -> String {
+    let mut names: Vec<String> = relative
+        .iter()
+        .map(|part| part.to_string_lossy().to_string())
+        .collect();
+    if names.first().map(String::as_str) == Some("src") {
+        names.remove(0);
+    }
+    if let Some(last) = names.pop() {
+        if !matches!(last.as_str(), "lib.rs" | "main.rs" | "mod.rs") {
+            names.push(last.strip_suffix(".rs").unwrap_or(&last).to_string());
+        }
+    }
+    names
+        .iter()
+        .map(|name| format!("{}/", escape_name(name)))
+        .collect()
+}
+
+/// The descriptor suffix of an LSP `SymbolKind`, as rust-analyzer reports
+/// items: namespaces for modules, types for ADTs, traits and aliases,
+/// methods for functions, terms for everything else.
+fn descriptor(kind: u64, name: &str) -> String {
+    let name = escape_name(name);
+    match kind {
+        2 => format!("{}/", name),
+        5 | 10 | 11 | 23 | 26 => format!("{}#", name),
+        6 | 9 | 12 => format!("{}().", name),
+        _ => format!("{}.", name),
+    }
+}
+
+/// A definition found in a file's document symbols.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition {
+    /// Index of the symbol in [`SymbolTable::symbols`].
+    pub symbol: usize,
+    pub name: String,
+    /// Where the name is: the definition's occurrence.
+    pub range: Vec<i32>,
+    /// The whole item, for the occurrence's enclosing range.
+    pub enclosing: Vec<i32>,
+    /// LSP position of the name, to ask for references at.
+    pub position: (u64, u64),
+    /// The signature rust-analyzer reports, if any.
+    pub detail: Option<String>,
+    /// Whether this is the first definition of its symbol, which gets the
+    /// symbol's information.
+    pub first: bool,
+}
+
+/// The symbols of an index: their SCIP strings, unique per item as far as
+/// document symbols tell items apart.
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    pub symbols: Vec<String>,
+    ids: HashMap<String, usize>,
+}
+
+impl SymbolTable {
+    /// The id of `symbol`, and whether it's new. Functions named alike in
+    /// one scope (trait methods of several impls) get `(+n)`
+    /// disambiguators; other repeated names are the same symbol.
+    fn add(&mut self, symbol: String) -> (usize, bool) {
+        let mut candidate = symbol.clone();
+        let mut n = 0;
+        while let Some(&id) = self.ids.get(&candidate) {
+            if !symbol.ends_with("().") {
+                return (id, false);
+            }
+            n += 1;
+            candidate = format!("{}(+{}).", symbol.strip_suffix("().").unwrap_or(&symbol), n);
+        }
+        let id = self.symbols.len();
+        self.symbols.push(candidate.clone());
+        self.ids.insert(candidate, id);
+        (id, true)
+    }
+
+    /// Definitions, with their symbols, for a `textDocument/documentSymbol`
+    /// result. `prefix` is what the file's symbols start with: the scheme,
+    /// the package and the file's module. Items in an `impl` go under the
+    /// type it's for.
+    pub fn definitions(&mut self, symbols: &Value, prefix: &str) -> Vec<Definition> {
+        let mut definitions = Vec::new();
+        let mut stack: Vec<(&Value, String)> = symbols
+            .as_array()
+            .into_iter()
+            .flatten()
+            .rev()
+            .map(|symbol| (symbol, prefix.to_string()))
+            .collect();
+        while let Some((symbol, scope)) = stack.pop() {
+            let Some(name) = symbol["name"].as_str() else {
+                continue;
+            };
+            let kind = symbol["kind"].as_u64().unwrap_or(0);
+            let inner = if kind == 19 {
+                let self_type = implementing_type(name.strip_prefix("impl ").unwrap_or(name));
+                let self_type = self_type.split('<').next().unwrap_or(self_type).trim();
+                format!("{}{}#", scope, escape_name(self_type))
+            } else {
+                let selection = match &symbol["selectionRange"] {
+                    Value::Null => &symbol["location"]["range"],
+                    range => range,
+                };
+                let enclosing = match &symbol["range"] {
+                    Value::Null => &symbol["location"]["range"],
+                    range => range,
+                };
+                let symbol_name = format!("{}{}", scope, descriptor(kind, name));
+                if let Some(range) = scip_range(selection) {
+                    let (id, first) = self.add(symbol_name.clone());
+                    definitions.push(Definition {
+                        symbol: id,
+                        name: name.to_string(),
+                        range,
+                        enclosing: scip_range(enclosing).unwrap_or_default(),
+                        position: (
+                            selection["start"]["line"].as_u64().unwrap_or(0),
+                            selection["start"]["character"].as_u64().unwrap_or(0),
+                        ),
+                        detail: symbol["detail"]
+                            .as_str()
+                            .filter(|detail| !detail.is_empty())
+                            .map(str::to_string),
+                        first,
+                    });
+                }
+                symbol_name
+            };
+            let children = symbol["children"].as_array().into_iter().flatten().rev();
+            stack.extend(children.map(|child| (child, inner.clone())));
+        }
+        definitions
+    }
+}
+
+/// A file going into the index.
+struct IndexedFile {
+    path: PathBuf,
+    definitions: Vec<Definition>,
+    /// Ranges of references to symbols, by symbol id.
+    references: Vec<(Vec<i32>, usize)>,
+}
+
+/// The `PositionEncoding` enum of a SCIP document.
+fn scip_position_encoding(encoding: PositionEncoding) -> u64 {
+    match encoding {
+        PositionEncoding::Utf8 => 1,
+        PositionEncoding::Utf16 => 2,
+        PositionEncoding::Utf32 => 3,
+    }
+}
+
+/// The `Document` message of a file. Occurrences are sorted by range,
+/// definitions before references at the same place.
+fn document_message(
+    file: &IndexedFile,
+    relative_path: &str,
+    symbols: &[String],
+    encoding: u64,
+) -> ProtoMessage {
+    let mut occurrences: Vec<(&[i32], usize, u64, &[i32])> = file
+        .definitions
+        .iter()
+        .map(|definition| {
+            (
+                definition.range.as_slice(),
+                definition.symbol,
+                ROLE_DEFINITION,
+                definition.enclosing.as_slice(),
+            )
+        })
+        .chain(
+            file.references
+                .iter()
+                .map(|(range, symbol)| (range.as_slice(), *symbol, 0, &[][..])),
+        )
+        .collect();
+    occurrences.sort_by(|a, b| a.0.cmp(b.0).then(b.2.cmp(&a.2)).then(a.1.cmp(&b.1)));
+    occurrences.dedup_by(|a, b| a.0 == b.0 && a.1 == b.1);
+
+    let mut document = ProtoMessage::new();
+    document
+        .string(1, relative_path)
+        .string(4, "rust")
+        .uint(6, encoding);
+    for (range, symbol, roles, enclosing) in occurrences {
+        let mut occurrence = ProtoMessage::new();
+        occurrence
+            .packed(1, range)
+            .string(2, &symbols[symbol])
+            .uint(3, roles)
+            .packed(7, enclosing);
+        document.message(2, &occurrence);
+    }
+    for definition in file.definitions.iter().filter(|d| d.first) {
+        let mut information = ProtoMessage::new();
+        information.string(1, &symbols[definition.symbol]);
+        if let Some(detail) = &definition.detail {
+            information.string(3, &format!("```rust\n{}\n```", detail));
+        }
+        information.string(6, &definition.name);
+        document.message(3, &information);
+    }
+    document
+}
+
+/// The `Metadata` message of an index.
+fn metadata_message(root: &Path) -> ProtoMessage {
+    let mut tool_info = ProtoMessage::new();
+    tool_info
+        .string(1, "rust-analyzer-server")
+        .string(2, env!("CARGO_PKG_VERSION"));
+    let mut metadata = ProtoMessage::new();
+    metadata
+        .message(2, &tool_info)
+        .string(3, &file_uri(root))
+        // UTF8 text documents.
+        .uint(4, 1);
+    metadata
+}
+
+/// Write the `Index` message: its metadata, then its documents, each as one
+/// occurrence of the repeated `documents` field so they needn't be held
+/// together. The file is written under a temporary name and renamed into
+/// place.
+fn write_index(
+    output: &Path,
+    root: &Path,
+    files: &[IndexedFile],
+    symbols: &[String],
+    encoding: u64,
+) -> Result<()> {
+    let mut name = output.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    let temporary = output.with_file_name(name);
+    let write = || -> Result<()> {
+        let mut writer = BufWriter::new(File::create(&temporary)?);
+        let mut header = ProtoMessage::new();
+        header.message(1, &metadata_message(root));
+        writer.write_all(header.as_bytes())?;
+        for file in files {
+            let relative = file.path.strip_prefix(root).unwrap_or(&file.path);
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            let mut field = ProtoMessage::new();
+            field.message(2, &document_message(file, &relative, symbols, encoding));
+            writer.write_all(field.as_bytes())?;
+        }
+        writer.flush()?;
+        Ok(())
+    };
+    match write() {
+        Ok(()) => Ok(std::fs::rename(&temporary, output)?),
+        Err(e) => {
+            let _ = std::fs::remove_file(&temporary);
+            Err(e)
+        }
+    }
+}
+
+/// The scheme, package and module descriptors that the symbols of `file`
+/// start with. The package is the cargo package owning the file, `. .` for
+/// files outside every package; modules come from the path in it.
+fn symbol_prefix(metadata: Option<&Value>, root: &Path, file: &Path) -> String {
+    let package = metadata.and_then(|metadata| package_of(metadata, file));
+    let name = package.and_then(|package| package["name"].as_str());
+    let version = package.and_then(|package| package["version"].as_str());
+    let dir = package
+        .and_then(|package| Path::new(package["manifest_path"].as_str()?).parent())
+        .unwrap_or(root);
+    format!(
+        "{} cargo {} {} {}",
+        SCIP_SCHEME,
+        name.unwrap_or("."),
+        version.unwrap_or("."),
+        module_descriptors(file.strip_prefix(dir).unwrap_or(file))
+    )
+}
+
+/// `rust_analyzer_export_index`: write a SCIP index of the workspace's `.rs`
+/// files, holding definitions, from document symbols, and references to
+/// them between workspace files. Dependencies' symbols, hover text, and
+/// locals aren't indexed.
+pub(crate) async fn handle_export_index(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    match args["format"].as_str().unwrap_or("scip") {
+        "scip" => {}
+        "lsif" => {
+            return Err(tool_error!(
+                InvalidParams,
+                "LSIF output isn't supported; use format 'scip'"
+            ))
+        }
+        other => {
+            return Err(tool_error!(
+                InvalidParams,
+                "Unknown format '{}': use 'scip'",
+                other
+            ))
+        }
+    }
+    let exclude = exclude_arg(&args)?;
+    let (output, _) = output_arg(server, &args, "index.scip")?;
+
+    let root = server.workspace_root.clone();
+    let root = root.canonicalize().unwrap_or(root);
+    let paths = rust_files(&root, &exclude);
+    let metadata = cargo_metadata(&root, &server.metadata_cache)
+        .await
+        .map(|(metadata, _)| metadata)
+        .map_err(|e| debug!("export_index: no packages: {}", e))
+        .ok();
+
+    let mut walk = SymbolWalk::new("export_index", &server.config);
+    let Some(client) = &mut server.client else {
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
+    };
+
+    // Definitions first, so references can name their symbols.
+    let mut table = SymbolTable::default();
+    let mut files = Vec::new();
+    let mut skipped = Vec::new();
+    for path in paths {
+        let Some(symbols) = walk.document_symbols(client, &path).await? else {
+            skipped.push(
+                path.strip_prefix(&root)
+                    .unwrap_or(&path)
+                    .display()
+                    .to_string(),
+            );
+            continue;
+        };
+        let prefix = symbol_prefix(metadata.as_ref(), &root, &path);
+        files.push(IndexedFile {
+            definitions: table.definitions(&symbols, &prefix),
+            path,
+            references: Vec::new(),
+        });
+    }
+
+    // References in workspace files, asked for every definition of a file
+    // at once.
+    let by_uri: HashMap<String, usize> = files
+        .iter()
+        .enumerate()
+        .map(|(index, file)| (file_uri(&file.path), index))
+        .collect();
+    for index in 0..files.len() {
+        let uri = file_uri(&files[index].path);
+        let mut pending = Vec::new();
+        for definition in &files[index].definitions {
+            let params = json!({
+                "textDocument": { "uri": uri },
+                "position": { "line": definition.position.0, "character": definition.position.1 },
+                "context": { "includeDeclaration": false }
+            });
+            let request = client.request_detached("textDocument/references", Some(params))?;
+            pending.push((definition.symbol, request));
+        }
+        let mut found = HashSet::new();
+        for (symbol, request) in pending {
+            let locations = match request.await {
+                Ok(locations) => locations,
+                Err(e) => {
+                    debug!("export_index: no references in {}: {}", uri, e);
+                    continue;
+                }
+            };
+            for location in locations.as_array().into_iter().flatten() {
+                let target = location["uri"].as_str().and_then(|uri| by_uri.get(uri));
+                let range = scip_range(&location["range"]);
+                let (Some(&target), Some(range)) = (target, range) else {
+                    continue;
+                };
+                let defined = files[target]
+                    .definitions
+                    .iter()
+                    .any(|definition| definition.symbol == symbol && definition.range == range);
+                if !defined {
+                    found.insert((target, range, symbol));
+                }
+            }
+        }
+        for (target, range, symbol) in found {
+            files[target].references.push((range, symbol));
+        }
+    }
+
+    let encoding = scip_position_encoding(client.position_encoding());
+    let occurrences: usize = files
+        .iter()
+        .map(|file| file.definitions.len() + file.references.len())
+        .sum();
+    let symbols = table.symbols.len();
+    let written = output.clone();
+    let documents = files.len();
+    tokio::task::spawn_blocking(move || {
+        write_index(&written, &root, &files, &table.symbols, encoding)
+    })
+    .await
+    .map_err(|e| anyhow!("Writing the index failed: {}", e))?
+    .map_err(|e| anyhow!("Cannot write {}: {}", output.display(), e))?;
+
+    let mut result = json!({
+        "file": client.path_style.display(&output.display().to_string()),
+        "format": "scip",
+        "documents": documents,
+        "symbols": symbols,
+        "occurrences": occurrences
+    });
+    if !skipped.is_empty() {
+        result["skipped"] = json!(skipped);
+    }
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text: serde_json::to_string_pretty(&result)?,
+        }],
+    })
+}
@@ -1,9 +1,52 @@
 use anyhow::Result;
 use log::info;
-use std::path::PathBuf;
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+use tokio::sync::Semaphore;
 
-use crate::lsp::progress::ProgressEntry;
-use crate::lsp::RustAnalyzerClient;
+use crate::{
+    config::{CargoSettings, Config, RESULT_CACHE_LEN},
+    lsp::{
+        content_hash,
+        messages::ServerMessage,
+        paths::{cargo_home, file_uri, language_id, uri_path},
+        progress::{new_shared_progress, IndexingEstimate, ProgressEntry, ProgressState},
+        registrations::Registration,
+        FileWatcher, LspBackend, PathStyle, RustAnalyzerClient, SharedProgress,
+    },
+    protocol::{tool_error, ErrorCode, ToolError},
+};
+
+use super::{
+    cargo_check::PendingFlycheck,
+    cargo_metadata::SharedMetadataCache,
+    toolchain::{rustc_info, SharedToolchainCache},
+};
+
+/// Liveness of the rust-analyzer backend process.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendStatus {
+    /// No client has been started yet.
+    NotStarted,
+    Alive,
+    /// The child process has exited.
+    Dead,
+    /// The process is running but did not answer a probe request in time.
+    Unresponsive,
+}
+
+impl BackendStatus {
+    pub fn is_healthy(self) -> bool {
+        matches!(self, BackendStatus::NotStarted | BackendStatus::Alive)
+    }
+}
 
 /// Tracks why the server is in its current state.
 #[derive(Debug, Clone, PartialEq)]
@@ -16,10 +59,144 @@ pub enum InitTrigger {
     WorkspaceChange { previous: PathBuf },
 }
 
+/// What was last sent to rust-analyzer for a file read from disk, so repeat
+/// tool calls on an unchanged file can skip reading it.
+pub(crate) struct CachedDocument {
+    modified: SystemTime,
+    len: u64,
+    content_hash: u64,
+    uri: String,
+}
+
+/// Changes rust-analyzer has been told of and progress tasks begun and
+/// ended; results computed at one generation may be stale at another.
+pub(crate) type WorkspaceGeneration = (u64, u64, u64);
+
+/// Simplified `workspace/symbol` results by query, all computed at one
+/// workspace generation and dropped together when it moves on.
+#[derive(Default)]
+pub(crate) struct SymbolCache {
+    generation: Option<WorkspaceGeneration>,
+    results: HashMap<String, Value>,
+    hits: u64,
+    misses: u64,
+}
+
+impl SymbolCache {
+    pub(crate) fn get(&mut self, query: &str, generation: WorkspaceGeneration) -> Option<Value> {
+        let result = match self.generation == Some(generation) {
+            true => self.results.get(query).cloned(),
+            false => None,
+        };
+        match result {
+            Some(_) => self.hits += 1,
+            None => self.misses += 1,
+        }
+        result
+    }
+
+    pub(crate) fn insert(&mut self, query: &str, generation: WorkspaceGeneration, result: Value) {
+        if self.generation != Some(generation) {
+            self.results.clear();
+            self.generation = Some(generation);
+        }
+        self.results.insert(query.to_string(), result);
+    }
+
+    /// Drop the results, keeping the counters.
+    pub(crate) fn clear(&mut self) {
+        self.results.clear();
+        self.generation = None;
+    }
+}
+
+/// What a file-scoped tool's result depends on: the tool and its
+/// arguments, the text rust-analyzer has for the file, and the workspace
+/// generation.
+pub(crate) struct ResultKey {
+    call: String,
+    content_hash: u64,
+    generation: WorkspaceGeneration,
+}
+
+struct CachedResult {
+    content_hash: u64,
+    generation: WorkspaceGeneration,
+    text: String,
+}
+
+/// Results of file-scoped tools by tool and arguments, so a repeat call on
+/// an unchanged file skips rust-analyzer. An entry is stale once its file's
+/// text or the workspace generation moves on (`didChange`, watched-file
+/// changes); at most `RESULT_CACHE_LEN` are kept, the oldest dropped first.
+#[derive(Default)]
+pub(crate) struct ResultCache {
+    results: HashMap<String, CachedResult>,
+    order: VecDeque<String>,
+    hits: u64,
+    misses: u64,
+}
+
+impl ResultCache {
+    pub(crate) fn get(&mut self, key: &ResultKey) -> Option<String> {
+        let text = self
+            .results
+            .get(&key.call)
+            .filter(|cached| {
+                cached.content_hash == key.content_hash && cached.generation == key.generation
+            })
+            .map(|cached| cached.text.clone());
+        match text {
+            Some(_) => self.hits += 1,
+            None => self.misses += 1,
+        }
+        text
+    }
+
+    pub(crate) fn insert(&mut self, key: ResultKey, text: String) {
+        let cached = CachedResult {
+            content_hash: key.content_hash,
+            generation: key.generation,
+            text,
+        };
+        if self.results.insert(key.call.clone(), cached).is_none() {
+            self.order.push_back(key.call);
+        }
+        while self.order.len() > RESULT_CACHE_LEN {
+            if let Some(oldest) = self.order.pop_front() {
+                self.results.remove(&oldest);
+            }
+        }
+    }
+
+    /// Drop the results, keeping the counters.
+    pub(crate) fn clear(&mut self) {
+        self.results.clear();
+        self.order.clear();
+    }
+}
+
 pub struct RustAnalyzerMCPServer {
     pub(crate) client: Option<RustAnalyzerClient>,
     pub(crate) workspace_root: PathBuf,
     pub(crate) init_trigger: InitTrigger,
+    pub(crate) config: Arc<Config>,
+    /// Keyed by canonical path.
+    pub(crate) document_cache: HashMap<PathBuf, CachedDocument>,
+    /// Tool calls that found their document already in sync without a read.
+    pub(crate) document_cache_hits: u64,
+    pub(crate) symbol_cache: SymbolCache,
+    pub(crate) result_cache: ResultCache,
+    /// One permit: `rust_analyzer_run_test` calls take turns, since
+    /// concurrent cargo runs only wait on each other's build lock.
+    pub(crate) test_runs: Arc<Semaphore>,
+    /// The check `rust_analyzer_cargo_check` calls are waiting on.
+    pub(crate) pending_flycheck: Option<PendingFlycheck>,
+    pub(crate) metadata_cache: SharedMetadataCache,
+    pub(crate) toolchain_cache: SharedToolchainCache,
+    /// Handed to every client the server starts, so callers can read it
+    /// without the server lock.
+    pub(crate) progress: SharedProgress,
 }
 
 impl Default for RustAnalyzerMCPServer {
@@ -34,10 +211,24 @@ impl RustAnalyzerMCPServer {
             client: None,
             workspace_root: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
             init_trigger: InitTrigger::None,
+            config: Arc::new(Config::default()),
+            document_cache: HashMap::new(),
+            document_cache_hits: 0,
+            symbol_cache: SymbolCache::default(),
+            result_cache: ResultCache::default(),
+            test_runs: Arc::new(Semaphore::new(1)),
+            pending_flycheck: None,
+            metadata_cache: SharedMetadataCache::default(),
+            toolchain_cache: SharedToolchainCache::default(),
+            progress: new_shared_progress(),
         }
     }
 
     pub fn with_workspace(workspace_root: PathBuf) -> Self {
+        Self::with_config(workspace_root, Config::default())
+    }
+
+    pub fn with_config(workspace_root: PathBuf, mut config: Config) -> Self {
         // Ensure the workspace root is absolute.
         let workspace_root = workspace_root.canonicalize().unwrap_or_else(|_| {
             // If canonicalize fails, try to make it absolute.
@@ -50,10 +241,26 @@ impl RustAnalyzerMCPServer {
             }
         });
 
+        config.extra_workspaces = config
+            .extra_workspaces
+            .iter()
+            .map(|folder| resolve_folder(&workspace_root, folder))
+            .collect();
+
         Self {
             client: None,
             workspace_root,
             init_trigger: InitTrigger::None,
+            config: Arc::new(config),
+            document_cache: HashMap::new(),
+            document_cache_hits: 0,
+            symbol_cache: SymbolCache::default(),
+            result_cache: ResultCache::default(),
+            test_runs: Arc::new(Semaphore::new(1)),
+            pending_flycheck: None,
+            metadata_cache: SharedMetadataCache::default(),
+            toolchain_cache: SharedToolchainCache::default(),
+            progress: new_shared_progress(),
         }
     }
 
@@ -61,39 +268,329 @@ impl RustAnalyzerMCPServer {
         if self.client.is_none() {
             // Validate workspace path exists.
             if !self.workspace_root.exists() {
-                return Err(anyhow::anyhow!(
+                return Err(tool_error!(
+                    FileNotFound,
                     "Workspace path does not exist: {}",
                     self.workspace_root.display()
                 ));
             }
+            if let Some(folder) = self.config.extra_workspaces.iter().find(|f| !f.exists()) {
+                return Err(tool_error!(
+                    FileNotFound,
+                    "Extra workspace folder does not exist: {}",
+                    folder.display()
+                ));
+            }
             if self.init_trigger == InitTrigger::None {
                 self.init_trigger = InitTrigger::InitialStart;
             }
-            info!("Starting rust-analyzer for workspace: {}", self.workspace_root.display());
-            let mut client = RustAnalyzerClient::new(self.workspace_root.clone());
-            client.start().await?;
+            info!(
+                "Starting rust-analyzer for workspace: {}",
+                self.workspace_root.display()
+            );
+            let mut client = self.new_client().await;
+            client
+                .start()
+                .await
+                .map_err(|e| ToolError::new(ErrorCode::BackendUnavailable, e.to_string()))?;
             self.client = Some(client);
+            self.symbol_cache.clear();
+            self.result_cache.clear();
         }
         Ok(())
     }
 
-    pub(crate) async fn open_document_if_needed(&mut self, file_path: &str) -> Result<String> {
-        let absolute_path = self.workspace_root.join(file_path);
+    /// Use `backend` instead of starting rust-analyzer, so tests can run
+    /// tool calls against canned responses.
+    pub async fn start_with_backend(&mut self, backend: Box<dyn LspBackend>) -> Result<()> {
+        let mut client = self.new_client().await;
+        client.start_with_backend(backend).await?;
+        self.init_trigger = InitTrigger::InitialStart;
+        self.client = Some(client);
+        self.symbol_cache.clear();
+        self.result_cache.clear();
+        Ok(())
+    }
+
+    /// A client for the workspace reporting into the server's progress,
+    /// which forgets the tasks of any rust-analyzer it replaces.
+    async fn new_client(&self) -> RustAnalyzerClient {
+        *self.progress.lock().await = ProgressState::new();
+        let mut client =
+            RustAnalyzerClient::new(self.workspace_root.clone(), Arc::clone(&self.config));
+        client.progress = Arc::clone(&self.progress);
+        client
+    }
+
+    /// The workspace root followed by the extra workspace folders.
+    pub fn workspace_folders(&self) -> Vec<PathBuf> {
+        std::iter::once(&self.workspace_root)
+            .chain(&self.config.extra_workspaces)
+            .cloned()
+            .collect()
+    }
+
+    /// Add and remove extra workspace folders, telling a running
+    /// rust-analyzer with `workspace/didChangeWorkspaceFolders`. Relative
+    /// paths are resolved against the workspace root. Returns the folders
+    /// actually added and removed.
+    pub async fn change_workspace_folders(
+        &mut self,
+        add: &[PathBuf],
+        remove: &[PathBuf],
+    ) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+        let mut folders = self.config.extra_workspaces.clone();
+        let mut removed = Vec::new();
+        for folder in remove {
+            let folder = resolve_folder(&self.workspace_root, folder);
+            if folder == self.workspace_root {
+                return Err(tool_error!(
+                    InvalidParams,
+                    "Cannot remove the workspace root {}; call rust_analyzer_set_workspace instead",
+                    folder.display()
+                ));
+            }
+            if let Some(index) = folders.iter().position(|f| *f == folder) {
+                removed.push(folders.remove(index));
+            }
+        }
+        let mut added = Vec::new();
+        for folder in add {
+            let folder = resolve_folder(&self.workspace_root, folder);
+            if !folder.is_dir() {
+                return Err(tool_error!(
+                    FileNotFound,
+                    "Workspace folder does not exist: {}",
+                    folder.display()
+                ));
+            }
+            if folder != self.workspace_root && !folders.contains(&folder) {
+                folders.push(folder.clone());
+                added.push(folder);
+            }
+        }
+        if added.is_empty() && removed.is_empty() {
+            return Ok((added, removed));
+        }
+
+        info!(
+            "Workspace folders added: {:?}, removed: {:?}",
+            added, removed
+        );
+        let mut config = (*self.config).clone();
+        config.extra_workspaces = folders;
+        self.config = Arc::new(config);
+        if let Some(client) = &mut self.client {
+            client.change_workspace_folders(&added, &removed).await?;
+        }
+        Ok((added, removed))
+    }
+
+    /// Resolve a tool-supplied `file_path` to an absolute path inside the workspace.
+    ///
+    /// A `file://` URI, as results and rust-analyzer write them, is taken
+    /// as the (percent-decoded) path it names. Relative paths are joined
+    /// onto the first workspace folder containing them, the workspace root
+    /// when none does; absolute paths are used as-is. Paths that end up
+    /// outside every folder are rejected with a hint naming the Cargo
+    /// workspace that actually owns the file.
+    pub(crate) fn resolve_file_path(&self, file_path: &str) -> Result<PathBuf> {
+        let path = uri_path(file_path);
+        let path = path.as_path();
+        let folders = self.workspace_folders();
+        let absolute_path = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            folders
+                .iter()
+                .map(|folder| folder.join(path))
+                .find(|candidate| candidate.exists())
+                .unwrap_or_else(|| self.workspace_root.join(path))
+        };
         // Ensure we have an absolute path for the URI.
         let absolute_path = absolute_path
             .canonicalize()
             .unwrap_or_else(|_| absolute_path.clone());
-        let uri = format!("file://{}", absolute_path.display());
+
+        if folders
+            .iter()
+            .any(|folder| absolute_path.starts_with(folder))
+        {
+            return Ok(absolute_path);
+        }
+
+        let hint = match find_workspace_root(&absolute_path) {
+            Some(root) => format!(" (it belongs to {})", root.display()),
+            None => String::new(),
+        };
+        Err(tool_error!(
+            InvalidParams,
+            "File {} is not inside workspace {}{}; call rust_analyzer_set_workspace or add its folder with rust_analyzer_workspace_folders",
+            absolute_path.display(),
+            self.workspace_root.display(),
+            hint
+        ))
+    }
+
+    /// Open `file_path` in rust-analyzer, syncing it with `content` when given
+    /// (unsaved text from the caller) or with the file on disk otherwise.
+    pub(crate) async fn open_document_if_needed(
+        &mut self,
+        file_path: &str,
+        content: Option<&str>,
+    ) -> Result<String> {
+        let absolute_path = self.resolve_file_path(file_path)?;
+        if language_id(&absolute_path).is_none() {
+            return Err(tool_error!(
+                InvalidParams,
+                "{} is neither a Rust source file nor a Cargo.toml manifest",
+                absolute_path.display()
+            ));
+        }
+        let Some(client) = &mut self.client else {
+            return Err(tool_error!(BackendUnavailable, "Client not initialized"));
+        };
+
+        if let Some(content) = content {
+            let uri = file_uri(&absolute_path);
+            self.document_cache.remove(&absolute_path);
+            client.open_document(&uri, content, true).await?;
+            return Ok(uri);
+        }
+
+        // Skip the read when the file is unchanged since it was last sent.
+        let metadata = tokio::fs::metadata(&absolute_path)
+            .await
+            .map_err(|e| ToolError::io(&e, format!("Failed to read file {}: {}", file_path, e)))?;
+        let modified = metadata.modified()?;
+        if let Some(cached) = self.document_cache.get(&absolute_path) {
+            if cached.modified == modified
+                && cached.len == metadata.len()
+                && client
+                    .touch_document(&cached.uri, cached.content_hash)
+                    .await
+            {
+                self.document_cache_hits += 1;
+                return Ok(cached.uri.clone());
+            }
+        }
+
         let content = tokio::fs::read_to_string(&absolute_path)
             .await
-            .map_err(|e| anyhow::anyhow!("Failed to read file {}: {}", file_path, e))?;
+            .map_err(|e| ToolError::io(&e, format!("Failed to read file {}: {}", file_path, e)))?;
+        let uri = file_uri(&absolute_path);
+        client.open_document(&uri, &content, false).await?;
+        self.document_cache.insert(
+            absolute_path,
+            CachedDocument {
+                modified,
+                len: metadata.len(),
+                content_hash: content_hash(&content),
+                uri: uri.clone(),
+            },
+        );
+        Ok(uri)
+    }
+
+    /// Close `file_path` in rust-analyzer. Returns whether it was open.
+    pub(crate) async fn close_document(&mut self, file_path: &str) -> Result<bool> {
+        let absolute_path = self.resolve_file_path(file_path)?;
+        let uri = file_uri(&absolute_path);
 
         let Some(client) = &mut self.client else {
-            return Err(anyhow::anyhow!("Client not initialized"));
+            return Err(tool_error!(BackendUnavailable, "Client not initialized"));
         };
 
-        client.open_document(&uri, &content).await?;
-        Ok(uri)
+        client.close_document(&uri).await
+    }
+
+    /// Document sync counters reported by `/api/v1/status`: `didOpen`
+    /// notifications sent and tool calls that skipped reading the file.
+    pub fn document_stats(&self) -> (u64, u64) {
+        let opened = self.client.as_ref().map_or(0, |c| c.documents_opened());
+        (opened, self.document_cache_hits)
+    }
+
+    /// `workspace_symbol` cache counters reported by `/api/v1/status`:
+    /// cached queries, hits, and misses.
+    pub fn symbol_cache_stats(&self) -> (usize, u64, u64) {
+        let cache = &self.symbol_cache;
+        (cache.results.len(), cache.hits, cache.misses)
+    }
+
+    /// File-scoped result cache counters reported by `/api/v1/status`:
+    /// cached results, hits, and misses.
+    pub fn result_cache_stats(&self) -> (usize, u64, u64) {
+        let cache = &self.result_cache;
+        (cache.results.len(), cache.hits, cache.misses)
+    }
+
+    /// The key a file-scoped tool's result for the open document `uri` is
+    /// cached under, or `None` when it can't be: while indexing, when the
+    /// call passes `no_cache`, or when the document isn't open.
+    pub(crate) async fn result_key(
+        &self,
+        tool: &str,
+        uri: &str,
+        args: &Value,
+    ) -> Option<ResultKey> {
+        if args["no_cache"].as_bool().unwrap_or(false) {
+            return None;
+        }
+        let generation = self.workspace_generation().await?;
+        let content_hash = self.client.as_ref()?.document_hash(uri).await?;
+        // Unsaved text is covered by its hash; no need to keep it around.
+        let mut args = args.clone();
+        if let Some(args) = args.as_object_mut() {
+            args.remove("content");
+        }
+        Some(ResultKey {
+            call: format!("{} {}", tool, args),
+            content_hash,
+            generation,
+        })
+    }
+
+    /// How a call writes file paths: as rust-analyzer reports them, or
+    /// relative to the workspace root with registry and standard library
+    /// files marked. The sysroot comes from `rustc`, so this is only asked
+    /// for when `relative`.
+    pub(crate) async fn path_style(&self, relative: bool) -> PathStyle {
+        if !relative {
+            return PathStyle::absolute();
+        }
+        let canonical = |path: PathBuf| path.canonicalize().unwrap_or(path);
+        let rustc = rustc_info(&self.workspace_root, &self.toolchain_cache).await;
+        let sysroot = rustc["sysroot"].as_str().map(PathBuf::from);
+        PathStyle::new(
+            true,
+            canonical(self.workspace_root.clone()),
+            cargo_home().map(canonical),
+            sysroot.map(canonical),
+        )
+    }
+
+    /// Where rust-analyzer's view of the workspace stands, or `None` while
+    /// it's indexing and results are still changing.
+    pub(crate) async fn workspace_generation(&self) -> Option<WorkspaceGeneration> {
+        let client = self.client.as_ref()?;
+        let progress = client.progress.lock().await;
+        if progress.is_indexing() {
+            return None;
+        }
+        Some((
+            client.workspace_changes(),
+            progress.begun_count(),
+            progress.ended_count(),
+        ))
+    }
+
+    /// URIs of documents open in rust-analyzer, most recently used first.
+    pub async fn open_documents(&self) -> Vec<String> {
+        match &self.client {
+            Some(client) => client.open_document_uris().await,
+            None => Vec::new(),
+        }
     }
 
     pub async fn is_indexing(&self) -> bool {
@@ -110,6 +607,50 @@ impl RustAnalyzerMCPServer {
         }
     }
 
+    pub async fn indexing_estimate(&self) -> IndexingEstimate {
+        match &self.client {
+            Some(client) => client.progress.lock().await.indexing_estimate(),
+            None => IndexingEstimate {
+                percent: 0,
+                title: None,
+            },
+        }
+    }
+
+    pub async fn server_messages(&self) -> Vec<ServerMessage> {
+        match &self.client {
+            Some(client) => client.messages.lock().await.all(),
+            None => vec![],
+        }
+    }
+
+    /// File watchers rust-analyzer registered; empty until it registers any.
+    pub async fn file_watchers(&self) -> Vec<FileWatcher> {
+        match &self.client {
+            Some(client) => client.registrations.lock().await.file_watchers(),
+            None => vec![],
+        }
+    }
+
+    /// Capabilities rust-analyzer registered after initialize.
+    pub async fn registrations(&self) -> Vec<Registration> {
+        match &self.client {
+            Some(client) => client.registrations.lock().await.all(),
+            None => vec![],
+        }
+    }
+
+    /// Shared progress state, usable after releasing the server lock.
+    pub fn progress_handle(&self) -> Option<SharedProgress> {
+        self.client.as_ref().map(|client| client.progress.clone())
+    }
+
+    /// Progress of whichever rust-analyzer the server runs, now or later;
+    /// unlike `progress_handle`, available before the client starts.
+    pub fn shared_progress(&self) -> SharedProgress {
+        Arc::clone(&self.progress)
+    }
+
     pub fn trigger_info(&self) -> (&str, Option<String>) {
         match &self.init_trigger {
             InitTrigger::None => ("none", None),
@@ -120,10 +661,45 @@ impl RustAnalyzerMCPServer {
         }
     }
 
+    /// Probe the backend. `deep` additionally sends a request with a short timeout.
+    pub async fn backend_status(&mut self, deep: bool) -> BackendStatus {
+        let Some(client) = &mut self.client else {
+            return BackendStatus::NotStarted;
+        };
+        if !client.is_process_alive() {
+            return BackendStatus::Dead;
+        }
+        if deep && !client.ping(self.config.health_ping_timeout()).await {
+            return BackendStatus::Unresponsive;
+        }
+        BackendStatus::Alive
+    }
+
     pub fn workspace_exists(&self) -> bool {
         self.workspace_root.exists()
     }
 
+    /// Switch the features and target rust-analyzer analyzes with. It only
+    /// reads them at initialize, so a running instance is restarted, which
+    /// reloads the workspace. Returns whether anything changed.
+    pub async fn set_cargo_settings(&mut self, cargo: CargoSettings) -> Result<bool> {
+        cargo.validate()?;
+        if cargo == self.config.cargo {
+            return Ok(false);
+        }
+        info!("Cargo settings changed: {:?}", cargo);
+        let mut config = (*self.config).clone();
+        config.cargo = cargo;
+        self.config = Arc::new(config);
+
+        if let Some(mut client) = self.client.take() {
+            client.shutdown().await?;
+            self.document_cache.clear();
+            self.ensure_client_started().await?;
+        }
+        Ok(true)
+    }
+
     pub async fn shutdown(&mut self) {
         info!("Shutting down rust-analyzer");
         if let Some(client) = &mut self.client {
@@ -131,3 +707,36 @@ impl RustAnalyzerMCPServer {
         }
     }
 }
+
+/// Absolute form of a workspace folder, relative to `workspace_root`.
+fn resolve_folder(workspace_root: &Path, folder: &Path) -> PathBuf {
+    let folder = workspace_root.join(folder);
+    folder.canonicalize().unwrap_or(folder)
+}
+
+/// Find the Cargo workspace root that owns `path`.
+///
+/// Walks up from `path` looking for `Cargo.toml`. The outermost manifest with a
+/// `[workspace]` table wins; otherwise the nearest package manifest is used.
+pub(crate) fn find_workspace_root(path: &Path) -> Option<PathBuf> {
+    let mut nearest = None;
+    let mut workspace = None;
+
+    for dir in path.ancestors() {
+        let manifest = dir.join("Cargo.toml");
+        if !manifest.is_file() {
+            continue;
+        }
+        if nearest.is_none() {
+            nearest = Some(dir.to_path_buf());
+        }
+        let is_workspace = std::fs::read_to_string(&manifest)
+            .map(|content| content.contains("[workspace]"))
+            .unwrap_or(false);
+        if is_workspace {
+            workspace = Some(dir.to_path_buf());
+        }
+    }
+
+    workspace.or(nearest)
+}
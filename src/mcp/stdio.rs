@@ -0,0 +1,98 @@
+use anyhow::Result;
+use log::{debug, warn};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    sync::Mutex,
+};
+
+use super::{handlers::handle_shared_tool_call, tools::get_tools, RustAnalyzerMCPServer};
+
+/// MCP revision we answer with when the client doesn't name one.
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// Serve MCP over stdin/stdout, one JSON-RPC message per line, until the
+/// client closes stdin. This is how MCP clients run servers declared in
+/// `.mcp.json`.
+pub async fn serve_stdio(server: Arc<Mutex<RustAnalyzerMCPServer>>) -> Result<()> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => handle_message(&server, &message).await,
+            Err(e) => {
+                warn!("Invalid MCP message on stdin: {}", e);
+                Some(error_response(
+                    Value::Null,
+                    -32700,
+                    &format!("Parse error: {}", e),
+                ))
+            }
+        };
+        if let Some(reply) = reply {
+            stdout.write_all(format!("{}\n", reply).as_bytes()).await?;
+            stdout.flush().await?;
+        }
+    }
+    debug!("MCP client closed stdin");
+    Ok(())
+}
+
+/// Reply to one MCP message; notifications get none.
+pub async fn handle_message(
+    server: &Mutex<RustAnalyzerMCPServer>,
+    message: &Value,
+) -> Option<Value> {
+    let id = message.get("id")?.clone();
+    let params = &message["params"];
+    let result = match message["method"].as_str() {
+        Some("initialize") => json!({
+            "protocolVersion": params["protocolVersion"].as_str().unwrap_or(PROTOCOL_VERSION),
+            "capabilities": { "tools": {} },
+            "serverInfo": {
+                "name": "rust-analyzer-server",
+                "version": env!("CARGO_PKG_VERSION")
+            }
+        }),
+        Some("ping") => json!({}),
+        Some("tools/list") => json!({ "tools": get_tools() }),
+        Some("tools/call") => {
+            let Some(name) = params["name"].as_str() else {
+                return Some(error_response(id, -32602, "tools/call needs a tool name"));
+            };
+            let args = match &params["arguments"] {
+                Value::Null => json!({}),
+                args => args.clone(),
+            };
+            match handle_shared_tool_call(server, name, args).await {
+                Ok(result) => serde_json::to_value(result).unwrap_or_default(),
+                // Tool failures are results the model can read, not protocol errors.
+                Err(e) => json!({
+                    "content": [{ "type": "text", "text": e.to_string() }],
+                    "isError": true
+                }),
+            }
+        }
+        Some(method) => {
+            return Some(error_response(
+                id,
+                -32601,
+                &format!("Method not found: {}", method),
+            ))
+        }
+        None => return Some(error_response(id, -32600, "Invalid request")),
+    };
+    Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message }
+    })
+}
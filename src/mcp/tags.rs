@@ -0,0 +1,447 @@
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+use serde_json::{json, Value};
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::Config,
+    lsp::{paths::file_uri, registrations::glob_matches, RequestTimeout, RustAnalyzerClient},
+    protocol::{
+        mcp::{ContentItem, ToolResult},
+        tool_error,
+    },
+};
+
+use super::{
+    handlers::check_retry_timeout, server::RustAnalyzerMCPServer, trait_impls::implementing_type,
+};
+
+/// Tag lines `rust_analyzer_export_tags` holds in memory; past this many
+/// they're sorted and written to a run file, and the runs merged at the end.
+pub const TAGS_RUN_LINES: usize = 20_000;
+
+/// Directories never searched for sources: build output, and hidden ones
+/// such as `.git`.
+const SKIPPED_DIRS: &[&str] = &["target"];
+
+/// The universal-ctags Rust kind of an LSP `SymbolKind`, as rust-analyzer
+/// reports items.
+pub fn ctags_kind(kind: u64) -> &'static str {
+    match kind {
+        2 => "module",
+        6 => "method",
+        8 => "field",
+        10 => "enum",
+        11 => "interface",
+        12 => "function",
+        13 => "variable",
+        14 => "constant",
+        19 => "implementation",
+        22 => "enumerator",
+        23 => "struct",
+        26 => "typedef",
+        _ => "unknown",
+    }
+}
+
+/// The name a symbol goes by in its children's `scope` field: the type an
+/// `impl` is for, the symbol's name otherwise.
+fn scope_name(symbol: &Value) -> &str {
+    let name = symbol["name"].as_str().unwrap_or_default();
+    match symbol["kind"].as_u64() {
+        Some(19) => implementing_type(name.strip_prefix("impl ").unwrap_or(name)),
+        _ => name,
+    }
+}
+
+/// Tag lines, in the extended ctags format, for a `textDocument/documentSymbol`
+/// result of `file`: the name, file and line, then `kind` and, for items
+/// inside another, `scope` (`struct:Point`, `module:geometry::shapes`).
+/// Flat results, which have no nesting, get no `scope`.
+pub fn tag_lines(symbols: &Value, file: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut stack: Vec<(&Value, Option<(&str, String)>)> = symbols
+        .as_array()
+        .into_iter()
+        .flatten()
+        .rev()
+        .map(|symbol| (symbol, None))
+        .collect();
+    while let Some((symbol, scope)) = stack.pop() {
+        let Some(name) = symbol["name"].as_str() else {
+            continue;
+        };
+        let range = match &symbol["selectionRange"] {
+            Value::Null => &symbol["location"]["range"],
+            range => range,
+        };
+        let line = range["start"]["line"].as_u64().unwrap_or(0) + 1;
+        let kind = ctags_kind(symbol["kind"].as_u64().unwrap_or(0));
+
+        // Impl blocks are scopes, not tags; the items in them are.
+        if kind != "implementation" {
+            let mut tag = format!("{}\t{}\t{};\"\tkind:{}", name, file, line, kind);
+            if let Some((scope_kind, path)) = &scope {
+                tag.push_str(&format!("\t{}:{}", scope_kind, path));
+            }
+            lines.push(tag);
+        }
+
+        let path = match &scope {
+            Some((_, path)) if kind == "module" => format!("{}::{}", path, scope_name(symbol)),
+            _ => scope_name(symbol).to_string(),
+        };
+        let children = symbol["children"].as_array().into_iter().flatten().rev();
+        stack.extend(children.map(|child| (child, Some((kind, path.clone())))));
+    }
+    lines
+}
+
+/// The `.rs` files under `root`, sorted, leaving out `target` and hidden
+/// directories and whatever matches one of the `exclude` globs (matched
+/// against paths relative to `root`).
+pub fn rust_files(root: &Path, exclude: &[String]) -> Vec<PathBuf> {
+    let excluded = |path: &Path| {
+        let relative = path.strip_prefix(root).unwrap_or(path).to_string_lossy();
+        exclude.iter().any(|glob| glob_matches(glob, &relative))
+    };
+    let mut files = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            debug!("Cannot read {}", dir.display());
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if excluded(&path) {
+                continue;
+            }
+            if file_type.is_dir() {
+                if !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_str()) {
+                    dirs.push(path);
+                }
+            } else if name.ends_with(".rs") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Writes a sorted tags file without holding all of it: lines are kept up
+/// to a limit, then sorted into a run file next to the output, and the
+/// runs merged into the output by [`TagSorter::finish`].
+pub struct TagSorter {
+    output: PathBuf,
+    run_lines: usize,
+    lines: Vec<String>,
+    runs: Vec<PathBuf>,
+    count: usize,
+}
+
+impl TagSorter {
+    pub fn new(output: &Path, run_lines: usize) -> Self {
+        Self {
+            output: output.to_path_buf(),
+            run_lines: run_lines.max(1),
+            lines: Vec::new(),
+            runs: Vec::new(),
+            count: 0,
+        }
+    }
+
+    /// A file next to the output, named after it.
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        let mut name = self.output.file_name().unwrap_or_default().to_os_string();
+        name.push(suffix);
+        self.output.with_file_name(name)
+    }
+
+    pub fn push(&mut self, line: String) -> Result<()> {
+        self.lines.push(line);
+        self.count += 1;
+        if self.lines.len() >= self.run_lines {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Sort the lines held and write them to a new run file.
+    fn spill(&mut self) -> Result<()> {
+        self.lines.sort_unstable();
+        let run = self.sibling(&format!(".run{}", self.runs.len()));
+        let mut writer = BufWriter::new(File::create(&run)?);
+        for line in self.lines.drain(..) {
+            writeln!(writer, "{}", line)?;
+        }
+        writer.flush()?;
+        self.runs.push(run);
+        Ok(())
+    }
+
+    /// Write the tags file: the header, then every line in byte order,
+    /// which is what `!_TAG_FILE_SORTED 1` promises readers. The file is
+    /// written under a temporary name and renamed into place. Returns how
+    /// many tags it holds.
+    pub fn finish(mut self) -> Result<usize> {
+        self.lines.sort_unstable();
+        let temporary = self.sibling(".tmp");
+        match self.merge(&temporary) {
+            Ok(()) => {
+                std::fs::rename(&temporary, &self.output)?;
+                Ok(self.count)
+            }
+            Err(e) => {
+                let _ = std::fs::remove_file(&temporary);
+                Err(e)
+            }
+        }
+    }
+
+    fn merge(&self, temporary: &Path) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(temporary)?);
+        writeln!(writer, "!_TAG_FILE_FORMAT\t2\t/extended format/")?;
+        writeln!(
+            writer,
+            "!_TAG_FILE_SORTED\t1\t/0=unsorted, 1=sorted, 2=foldcase/"
+        )?;
+        writeln!(writer, "!_TAG_PROGRAM_NAME\trust-analyzer-server\t//")?;
+        writeln!(
+            writer,
+            "!_TAG_PROGRAM_VERSION\t{}\t//",
+            env!("CARGO_PKG_VERSION")
+        )?;
+
+        // The lines still held are one more run, read from memory.
+        let mut readers = Vec::new();
+        for run in &self.runs {
+            readers.push(BufReader::new(File::open(run)?).lines());
+        }
+        let mut held = self.lines.iter();
+        let mut heap = BinaryHeap::new();
+        for (index, reader) in readers.iter_mut().enumerate() {
+            if let Some(line) = reader.next().transpose()? {
+                heap.push(Reverse((line, index)));
+            }
+        }
+        let held_index = readers.len();
+        if let Some(line) = held.next() {
+            heap.push(Reverse((line.clone(), held_index)));
+        }
+        while let Some(Reverse((line, index))) = heap.pop() {
+            writeln!(writer, "{}", line)?;
+            let next = match readers.get_mut(index) {
+                Some(reader) => reader.next().transpose()?,
+                None => held.next().cloned(),
+            };
+            if let Some(line) = next {
+                heap.push(Reverse((line, index)));
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Run files go when the sorter does, finished or not.
+impl Drop for TagSorter {
+    fn drop(&mut self) {
+        for run in &self.runs {
+            let _ = std::fs::remove_file(run);
+        }
+    }
+}
+
+/// The `exclude` argument: one glob or a list of them.
+pub(super) fn exclude_arg(args: &Value) -> Result<Vec<String>> {
+    match &args["exclude"] {
+        Value::Null => Ok(Vec::new()),
+        Value::String(glob) => Ok(vec![glob.clone()]),
+        Value::Array(globs) => globs
+            .iter()
+            .map(|glob| glob.as_str().map(str::to_string))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| tool_error!(InvalidParams, "exclude must hold glob strings")),
+        _ => Err(tool_error!(
+            InvalidParams,
+            "exclude must be a glob or a list of globs"
+        )),
+    }
+}
+
+/// Where a workspace export is written: `output` from the arguments,
+/// relative to the workspace root, or `default` there. Returns the file and
+/// its directory, canonical so sources can be made relative to it. Like
+/// file arguments, the file must be inside a workspace folder, so a caller
+/// can't have the server overwrite files elsewhere.
+pub(super) fn output_arg(
+    server: &RustAnalyzerMCPServer,
+    args: &Value,
+    default: &str,
+) -> Result<(PathBuf, PathBuf)> {
+    let output = server
+        .workspace_root
+        .join(args["output"].as_str().unwrap_or(default));
+    let (Some(dir), Some(file_name)) = (output.parent(), output.file_name()) else {
+        return Err(tool_error!(
+            InvalidParams,
+            "{} doesn't name a file",
+            output.display()
+        ));
+    };
+    let output_dir = match dir.canonicalize() {
+        Ok(dir) if dir.is_dir() => dir,
+        _ => {
+            return Err(tool_error!(
+                InvalidParams,
+                "Directory {} doesn't exist",
+                dir.display()
+            ))
+        }
+    };
+    // A symlink already there is followed when writing.
+    let output = output_dir.join(file_name);
+    let output = output.canonicalize().unwrap_or(output);
+    let inside = server.workspace_folders().iter().any(|folder| {
+        let folder = folder.canonicalize().unwrap_or_else(|_| folder.clone());
+        output.starts_with(folder)
+    });
+    if !inside {
+        return Err(tool_error!(
+            InvalidParams,
+            "Output {} is not inside workspace {}",
+            output.display(),
+            server.workspace_root.display()
+        ));
+    }
+    Ok((output, output_dir))
+}
+
+/// Document symbols for a walk over the workspace's files. Until a file has
+/// answered, a null answer or an error means the workspace is still loading,
+/// and the request is retried up to the indexing timeout; after that, a file
+/// without symbols is left out.
+pub(super) struct SymbolWalk {
+    tool: &'static str,
+    start: Instant,
+    retry_interval: Duration,
+    indexing_timeout: Duration,
+    logged_waiting: bool,
+    loaded: bool,
+}
+
+impl SymbolWalk {
+    pub(super) fn new(tool: &'static str, config: &Config) -> Self {
+        Self {
+            tool,
+            start: Instant::now(),
+            retry_interval: config.retry_interval(),
+            indexing_timeout: config.indexing_timeout(),
+            logged_waiting: false,
+            loaded: false,
+        }
+    }
+
+    /// The symbols of `file`, or None when rust-analyzer has none for it.
+    pub(super) async fn document_symbols(
+        &mut self,
+        client: &mut RustAnalyzerClient,
+        file: &Path,
+    ) -> Result<Option<Value>> {
+        let uri = file_uri(file);
+        loop {
+            match client.document_symbols(&uri).await {
+                Ok(symbols) if self.loaded || !symbols.is_null() => {
+                    if self.logged_waiting {
+                        info!("{}: Indexing complete, returning results", self.tool);
+                        self.logged_waiting = false;
+                    }
+                    self.loaded = true;
+                    return Ok(Some(symbols));
+                }
+                Err(e) if e.is::<RequestTimeout>() => return Err(e),
+                Err(e) if self.loaded => {
+                    debug!("{}: no symbols for {}: {}", self.tool, file.display(), e);
+                    return Ok(None);
+                }
+                Ok(_) | Err(_) => {
+                    check_retry_timeout(
+                        self.tool,
+                        &self.start,
+                        self.indexing_timeout,
+                        &mut self.logged_waiting,
+                        client,
+                    )?;
+                    tokio::time::sleep(self.retry_interval).await;
+                }
+            }
+        }
+    }
+}
+
+/// `rust_analyzer_export_tags`: write a ctags file of the workspace's
+/// symbols, from rust-analyzer's document symbols of every `.rs` file. Paths
+/// in it are relative to the tags file's directory when the sources are
+/// under it.
+pub(crate) async fn handle_export_tags(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let exclude = exclude_arg(&args)?;
+    let (output, output_dir) = output_arg(server, &args, "tags")?;
+
+    let root = server.workspace_root.clone();
+    let root = root.canonicalize().unwrap_or(root);
+    let files = rust_files(&root, &exclude);
+
+    let mut walk = SymbolWalk::new("export_tags", &server.config);
+    let Some(client) = &mut server.client else {
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
+    };
+
+    let mut sorter = TagSorter::new(&output, TAGS_RUN_LINES);
+    let mut skipped = Vec::new();
+    for file in &files {
+        let relative = file.strip_prefix(&output_dir).unwrap_or(file);
+        let Some(symbols) = walk.document_symbols(client, file).await? else {
+            skipped.push(relative.display().to_string());
+            continue;
+        };
+        for line in tag_lines(&symbols, &relative.display().to_string()) {
+            sorter.push(line)?;
+        }
+    }
+
+    let count = tokio::task::spawn_blocking(move || sorter.finish())
+        .await
+        .map_err(|e| anyhow!("Writing tags failed: {}", e))?
+        .map_err(|e| anyhow!("Cannot write {}: {}", output.display(), e))?;
+
+    let mut result = json!({
+        "file": client.path_style.display(&output.display().to_string()),
+        "tags": count,
+        "files": files.len() - skipped.len()
+    });
+    if !skipped.is_empty() {
+        result["skipped"] = json!(skipped);
+    }
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text: serde_json::to_string_pretty(&result)?,
+        }],
+    })
+}
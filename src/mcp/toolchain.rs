@@ -0,0 +1,186 @@
+use anyhow::{anyhow, Result};
+use log::info;
+use serde_json::{json, Map, Value};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::Arc,
+};
+
+use crate::protocol::mcp::{ContentItem, ToolResult};
+
+/// Environment variables that change which toolchain builds the workspace,
+/// or how.
+const TOOLCHAIN_ENV: &[&str] = &[
+    "RUSTUP_TOOLCHAIN",
+    "RUSTFLAGS",
+    "CARGO_ENCODED_RUSTFLAGS",
+    "CARGO_TARGET_DIR",
+    "CARGO_BUILD_TARGET",
+];
+
+/// What `rustc` reported for each workspace, kept for the server's lifetime:
+/// the toolchain only changes when someone installs one, and rustup may
+/// take minutes to install a pinned toolchain the first time.
+pub type SharedToolchainCache = Arc<std::sync::Mutex<HashMap<PathBuf, Value>>>;
+
+/// The `key: value` lines of `rustc --version --verbose`, plus the release
+/// channel.
+pub fn parse_rustc_verbose(stdout: &str) -> Value {
+    let mut lines = stdout.lines();
+    let mut parsed = Map::new();
+    if let Some(version) = lines.next() {
+        parsed.insert("version".to_string(), json!(version.trim()));
+    }
+    for line in lines {
+        if let Some((key, value)) = line.split_once(':') {
+            let key = key.trim().to_lowercase().replace([' ', '-'], "_");
+            parsed.insert(key, json!(value.trim()));
+        }
+    }
+    let channel = match parsed.get("release").and_then(Value::as_str) {
+        Some(release) if release.contains("-nightly") || release.contains("-dev") => "nightly",
+        Some(release) if release.contains("-beta") => "beta",
+        Some(_) => "stable",
+        None => "unknown",
+    };
+    parsed.insert("channel".to_string(), json!(channel));
+    Value::Object(parsed)
+}
+
+/// The toolchain a `rust-toolchain.toml` (or legacy `rust-toolchain`) file
+/// in `workspace` or a parent directory pins, as rustup reads it.
+pub fn pinned_toolchain(workspace: &Path) -> Result<Option<Value>> {
+    for dir in workspace.ancestors() {
+        for name in ["rust-toolchain.toml", "rust-toolchain"] {
+            let path = dir.join(name);
+            let Ok(content) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let file = path.display().to_string();
+            // A legacy file is either TOML or just the channel.
+            if name == "rust-toolchain" && !content.contains('[') {
+                return Ok(Some(json!({ "file": file, "channel": content.trim() })));
+            }
+            let parsed: toml::Value =
+                toml::from_str(&content).map_err(|e| anyhow!("Failed to parse {}: {}", file, e))?;
+            let mut toolchain = serde_json::to_value(&parsed["toolchain"])?;
+            toolchain["file"] = json!(file);
+            return Ok(Some(toolchain));
+        }
+    }
+    Ok(None)
+}
+
+/// Whether `rustc` is the toolchain `pinned` asks for. Only the channel and
+/// an exact release can be compared; a date or custom name can't be.
+pub fn pin_mismatch(pinned: &Value, rustc: &Value) -> Option<String> {
+    let wanted = pinned["channel"].as_str()?;
+    let release = rustc["release"].as_str()?;
+    let channel = rustc["channel"].as_str()?;
+    let base = wanted.split('-').next().unwrap_or(wanted);
+    let matches = match base {
+        "stable" | "beta" | "nightly" => base == channel,
+        version if version.starts_with(|c: char| c.is_ascii_digit()) => {
+            release == version || release.starts_with(&format!("{}.", version))
+        }
+        _ => return None,
+    };
+    (!matches).then(|| {
+        format!(
+            "The workspace pins {} but rustc there is {} ({}); diagnostics may not match what cargo reports",
+            wanted, release, channel
+        )
+    })
+}
+
+/// Run `rustc` in `workspace`, where rustup applies the pinned toolchain.
+async fn rustc(workspace: &Path, args: &[&str]) -> Result<String> {
+    let output = tokio::process::Command::new("rustc")
+        .args(args)
+        .current_dir(workspace)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|e| anyhow!("Failed to run rustc: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "rustc {} failed:\n{}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// `rustc`'s version and sysroot in `workspace`, from the cache when it has
+/// run there before. Failures are reported in place rather than cached.
+pub(crate) async fn rustc_info(workspace: &Path, cache: &SharedToolchainCache) -> Value {
+    if let Some(cached) = cache.lock().unwrap().get(workspace) {
+        return cached.clone();
+    }
+
+    info!("Querying rustc in {}", workspace.display());
+    let mut info = match rustc(workspace, &["--version", "--verbose"]).await {
+        Ok(stdout) => parse_rustc_verbose(&stdout),
+        Err(e) => return json!({ "error": e.to_string() }),
+    };
+    match rustc(workspace, &["--print", "sysroot"]).await {
+        Ok(sysroot) => info["sysroot"] = json!(sysroot),
+        Err(e) => return json!({ "error": e.to_string() }),
+    }
+    cache
+        .lock()
+        .unwrap()
+        .insert(workspace.to_path_buf(), info.clone());
+    info
+}
+
+/// `rust_analyzer_toolchain`: the rustc the workspace builds with, the
+/// toolchain it pins, the rust-analyzer answering, and the environment
+/// that bears on them, with any mismatch between the pin and rustc.
+pub(crate) async fn handle_toolchain(
+    workspace: &Path,
+    cache: &SharedToolchainCache,
+    server_info: Option<Value>,
+) -> Result<ToolResult> {
+    let rustc = rustc_info(workspace, cache).await;
+    let pinned = pinned_toolchain(workspace)?;
+
+    let env: Map<String, Value> = TOOLCHAIN_ENV
+        .iter()
+        .map(|name| (name.to_string(), json!(std::env::var(name).ok())))
+        .collect();
+
+    let mut warnings = Vec::new();
+    if let Some(pinned) = &pinned {
+        if let Some(mismatch) = pin_mismatch(pinned, &rustc) {
+            warnings.push(mismatch);
+        }
+        if let Ok(toolchain) = std::env::var("RUSTUP_TOOLCHAIN") {
+            warnings.push(format!(
+                "RUSTUP_TOOLCHAIN={} overrides the toolchain pinned in {}",
+                toolchain,
+                pinned["file"].as_str().unwrap_or_default()
+            ));
+        }
+    }
+
+    let result = json!({
+        "workspace": workspace.display().to_string(),
+        "rustc": rustc,
+        "host": rustc["host"],
+        "pinned_toolchain": pinned,
+        "rust_analyzer": server_info,
+        "env": env,
+        "warnings": warnings
+    });
+
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text: serde_json::to_string_pretty(&result)?,
+        }],
+    })
+}
@@ -1,7 +1,108 @@
 use crate::protocol::mcp::ToolDefinition;
 use serde_json::json;
+use std::borrow::Cow;
+
+/// The prefix every tool name starts with. Calls may leave it off, so
+/// `hover` is `rust_analyzer_hover`.
+pub const TOOL_PREFIX: &str = "rust_analyzer_";
+
+/// Short names for tools, beyond leaving off [`TOOL_PREFIX`].
+pub const TOOL_ALIASES: &[(&str, &str)] = &[
+    ("def", "rust_analyzer_definition"),
+    ("refs", "rust_analyzer_references"),
+    ("impl", "rust_analyzer_implementation"),
+    ("impls", "rust_analyzer_find_implementations"),
+    ("search", "rust_analyzer_workspace_symbol"),
+    ("syms", "rust_analyzer_symbols"),
+    ("ctx", "rust_analyzer_context"),
+    ("diag", "rust_analyzer_diagnostics"),
+    ("wdiag", "rust_analyzer_workspace_diagnostics"),
+    ("callers", "rust_analyzer_incoming_calls"),
+    ("callees", "rust_analyzer_outgoing_calls"),
+    ("parent", "rust_analyzer_parent_module"),
+    ("hints", "rust_analyzer_inlay_hint"),
+    ("complete", "rust_analyzer_completion"),
+    ("fmt", "rust_analyzer_format"),
+    ("actions", "rust_analyzer_code_actions"),
+    ("modules", "rust_analyzer_module_tree"),
+    ("api", "rust_analyzer_public_api"),
+    ("metadata", "rust_analyzer_cargo_metadata"),
+    ("check", "rust_analyzer_cargo_check"),
+    ("tests", "rust_analyzer_list_tests"),
+    ("test", "rust_analyzer_run_test"),
+    ("folders", "rust_analyzer_workspace_folders"),
+    ("close", "rust_analyzer_close_document"),
+];
+
+/// The full name of the tool `name` calls: an alias's tool, or `name` with
+/// [`TOOL_PREFIX`] added when it's left off. Whether that tool exists is
+/// up to the caller.
+pub fn resolve_tool_name(name: &str) -> Cow<'_, str> {
+    if let Some((_, tool)) = TOOL_ALIASES.iter().find(|(alias, _)| *alias == name) {
+        return Cow::Borrowed(tool);
+    }
+    if name.starts_with(TOOL_PREFIX) {
+        Cow::Borrowed(name)
+    } else {
+        Cow::Owned(format!("{}{}", TOOL_PREFIX, name))
+    }
+}
+
+/// Levenshtein distance between `a` and `b`, by character.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The known tool name or alias closest to the unknown `name`, when one is
+/// close enough to be a likely typo.
+pub fn suggest_tool_name(name: &str) -> Option<String> {
+    let bare = name.strip_prefix(TOOL_PREFIX).unwrap_or(name);
+    let tools: Vec<String> = get_tools()
+        .into_iter()
+        .map(|tool| tool.name)
+        .chain(TOOL_ALIASES.iter().map(|(_, tool)| tool.to_string()))
+        .collect();
+    let candidates = tools
+        .iter()
+        .map(|tool| tool.strip_prefix(TOOL_PREFIX).unwrap_or(tool))
+        .chain(TOOL_ALIASES.iter().map(|(alias, _)| *alias));
+    candidates
+        .map(|candidate| (edit_distance(bare, candidate), candidate))
+        .filter(|(distance, candidate)| *distance <= (candidate.len() / 3).max(2))
+        .min()
+        .map(|(_, candidate)| candidate.to_string())
+}
 
 pub fn get_tools() -> Vec<ToolDefinition> {
+    let mut tools = tool_definitions();
+    // Every tool can be called without the prefix; short aliases are added
+    // from `TOOL_ALIASES`.
+    for tool in &mut tools {
+        let bare = tool.name.strip_prefix(TOOL_PREFIX).unwrap_or(&tool.name);
+        tool.aliases = std::iter::once(bare)
+            .chain(
+                TOOL_ALIASES
+                    .iter()
+                    .filter(|(_, name)| *name == tool.name)
+                    .map(|(alias, _)| *alias),
+            )
+            .map(str::to_string)
+            .collect();
+    }
+    tools
+}
+
+fn tool_definitions() -> Vec<ToolDefinition> {
     vec![
         // 1-2. Workspace management (must be first)
         ToolDefinition {
@@ -11,6 +112,7 @@ pub fn get_tools() -> Vec<ToolDefinition> {
                 "type": "object",
                 "properties": {}
             }),
+            aliases: Vec::new(),
         },
         ToolDefinition {
             name: "rust_analyzer_set_workspace".to_string(),
@@ -22,34 +124,69 @@ pub fn get_tools() -> Vec<ToolDefinition> {
                 },
                 "required": ["workspace_path"]
             }),
+            aliases: Vec::new(),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_workspace_folders".to_string(),
+            description: "Add or remove folders rust-analyzer analyzes alongside the workspace root, such as a sibling crate outside the cargo workspace. Returns all folders; call without arguments to list them.".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "add": { "type": "array", "items": { "type": "string" }, "description": "Folders to add (relative paths are resolved against the workspace root)" },
+                    "remove": { "type": "array", "items": { "type": "string" }, "description": "Extra folders to remove" }
+                }
+            }),
+            aliases: Vec::new(),
         },
         // 2-5. Most frequently used - core navigation
         ToolDefinition {
             name: "rust_analyzer_hover".to_string(),
-            description: "Get hover information for a symbol at a specific position in a Rust file"
+            description: "Get hover information for a symbol at a specific position in a Rust file, or in a Cargo.toml whatever rust-analyzer reports there (null when it has nothing). With links, items of dependencies get a docs_url to their documentation and items of the workspace get defined_in, their definition relative to the workspace root"
                 .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the Rust file or Cargo.toml" },
+                    "content": { "type": "string", "description": "Unsaved file content to analyze instead of the file on disk" },
+                    "line": { "type": "number", "description": "Line number (0-based)" },
+                    "character": { "type": "number", "description": "Character position (0-based)" },
+                    "links": { "type": "boolean", "description": "Also find docs_url or defined_in, which takes a definition request (default false)" }
+                },
+                "required": ["file_path", "line", "character"]
+            }),
+            aliases: Vec::new(),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_describe".to_string(),
+            description: "Describe the symbol at a position in one call: its hover signature and docs, its definition location, and the first lines of the definition. Parts rust-analyzer can't answer are listed under unavailable.".to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
                     "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "content": { "type": "string", "description": "Unsaved file content to analyze instead of the file on disk" },
                     "line": { "type": "number", "description": "Line number (0-based)" },
-                    "character": { "type": "number", "description": "Character position (0-based)" }
+                    "character": { "type": "number", "description": "Character position (0-based)" },
+                    "max_doc_chars": { "type": "number", "description": "Cut the docs after this many characters (default 600; 0 leaves them out)" },
+                    "snippet_lines": { "type": "number", "description": "Show at most this many lines of the definition (default 12; 0 leaves the snippet out)" }
                 },
                 "required": ["file_path", "line", "character"]
             }),
+            aliases: Vec::new(),
         },
         ToolDefinition {
             name: "rust_analyzer_definition".to_string(),
-            description: "Go to definition of a symbol at a specific position".to_string(),
+            description: "Go to definition of a symbol at a specific position, falling back to its declaration or type definition (marked with \"via\")".to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
                     "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "content": { "type": "string", "description": "Unsaved file content to analyze instead of the file on disk" },
                     "line": { "type": "number", "description": "Line number (0-based)" },
                     "character": { "type": "number", "description": "Character position (0-based)" }
                 },
                 "required": ["file_path", "line", "character"]
             }),
+            aliases: Vec::new(),
         },
         ToolDefinition {
             name: "rust_analyzer_references".to_string(),
@@ -58,15 +195,50 @@ pub fn get_tools() -> Vec<ToolDefinition> {
                 "type": "object",
                 "properties": {
                     "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "content": { "type": "string", "description": "Unsaved file content to analyze instead of the file on disk" },
                     "line": { "type": "number", "description": "Line number (0-based)" },
                     "character": { "type": "number", "description": "Character position (0-based)" }
                 },
                 "required": ["file_path", "line", "character"]
             }),
+            aliases: Vec::new(),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_rename".to_string(),
+            description: "Rename the symbol at a position everywhere it's used, writing every affected file. With preview, nothing is written: returns a unified diff per file, how many occurrences change in each, and the totals, flagging files changed on disk since rust-analyzer last read them, where applying later could conflict".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "content": { "type": "string", "description": "Unsaved file content to analyze instead of the file on disk" },
+                    "line": { "type": "number", "description": "Line number (0-based)" },
+                    "character": { "type": "number", "description": "Character position (0-based)" },
+                    "new_name": { "type": "string", "description": "The symbol's new name" },
+                    "preview": { "type": "boolean", "description": "Only show the per-file diffs, writing nothing (default: false)" }
+                },
+                "required": ["file_path", "line", "character", "new_name"]
+            }),
+            aliases: Vec::new(),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_context".to_string(),
+            description: "Read the source around a location, such as a path:line:column from definition or references: numbered lines, the enclosing item's name and signature, and the file's line count. Works on dependency and standard library sources too.".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "location": { "type": "string", "description": "path:line:column as other tools report it; the column may be left off" },
+                    "file_path": { "type": "string", "description": "Path to the Rust file, instead of location" },
+                    "line": { "type": "number", "description": "Line number (0-based), with file_path" },
+                    "character": { "type": "number", "description": "Character position (0-based), with file_path; picks the enclosing item on a line holding several" },
+                    "before": { "type": "number", "description": "Lines to show before the location (default 10)" },
+                    "after": { "type": "number", "description": "Lines to show after the location (default 10)" }
+                }
+            }),
+            aliases: Vec::new(),
         },
         ToolDefinition {
             name: "rust_analyzer_workspace_symbol".to_string(),
-            description: "Search for symbols across the entire workspace using a query (supports fuzzy matching)"
+            description: "Search for symbols across the entire workspace using a query (supports fuzzy matching). Each result names its container (enclosing type or module); exact name matches are listed first"
                 .to_string(),
             input_schema: json!({
                 "type": "object",
@@ -74,23 +246,70 @@ pub fn get_tools() -> Vec<ToolDefinition> {
                     "query": {
                         "type": "string",
                         "description": "Search query for symbol names (e.g., 'TradeData', 'calculate')"
+                    },
+                    "scope": {
+                        "type": "string",
+                        "enum": ["workspace", "all"],
+                        "description": "\"workspace\" (default): only symbols defined in the workspace, with a count of the matches left out; \"all\": dependencies and the standard library too"
+                    },
+                    "no_cache": {
+                        "type": "boolean",
+                        "description": "Search again even if this query's results are cached and no files have changed since"
                     }
                 },
                 "required": ["query"]
             }),
+            aliases: Vec::new(),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_export_tags".to_string(),
+            description: "Write a ctags file of the workspace's symbols, from rust-analyzer's document symbols of every .rs file (target and hidden directories are skipped). The file is sorted, in the extended format, with kind and scope fields; paths in it are relative to its directory. Returns the file, how many tags and files it covers, and any files skipped".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "output": { "type": "string", "description": "Tags file to write inside the workspace, relative to its root (default: tags)" },
+                    "exclude": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Globs of files and directories to leave out, relative to the workspace root, e.g. [\"benches/**\", \"**/generated\"]"
+                    }
+                }
+            }),
+            aliases: Vec::new(),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_export_index".to_string(),
+            description: "Write a SCIP index (protobuf) of the workspace's .rs files for code-intelligence tools: a document per file with the definitions from rust-analyzer's document symbols, their signatures, and the references to them from workspace files. Symbols are named after the cargo package and module path. Not covered yet: references into dependencies, hover documentation, local variables and macro-generated items. Returns the file and how many documents, symbols and occurrences it holds".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "output": { "type": "string", "description": "Index file to write inside the workspace, relative to its root (default: index.scip)" },
+                    "format": { "type": "string", "enum": ["scip"], "description": "Index format (default: scip; LSIF isn't supported)" },
+                    "exclude": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Globs of files and directories to leave out, relative to the workspace root, e.g. [\"benches/**\"]"
+                    }
+                }
+            }),
+            aliases: Vec::new(),
         },
         // 6-8. Frequently used
         ToolDefinition {
             name: "rust_analyzer_diagnostics".to_string(),
-            description: "Get compiler diagnostics (errors, warnings, hints) for a Rust file"
+            description: "Get compiler diagnostics (errors, warnings, hints) for a Rust file or Cargo.toml"
                 .to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
-                    "file_path": { "type": "string", "description": "Path to the Rust file" }
+                    "file_path": { "type": "string", "description": "Path to the Rust file or Cargo.toml" },
+                    "content": { "type": "string", "description": "Unsaved file content to analyze instead of the file on disk" },
+                    "severity": { "type": "string", "enum": ["error", "warning", "information", "hint"], "description": "Only diagnostics at least this severe" },
+                    "code": { "type": "string", "description": "Only diagnostics whose code starts with this, e.g. \"clippy::\" or \"E0308\"" }
                 },
                 "required": ["file_path"]
             }),
+            aliases: Vec::new(),
         },
         ToolDefinition {
             name: "rust_analyzer_inlay_hint".to_string(),
@@ -99,13 +318,16 @@ pub fn get_tools() -> Vec<ToolDefinition> {
                 "type": "object",
                 "properties": {
                     "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "content": { "type": "string", "description": "Unsaved file content to analyze instead of the file on disk" },
                     "line": { "type": "number", "description": "Start line number (0-based)" },
                     "character": { "type": "number", "description": "Start character position (0-based)" },
                     "end_line": { "type": "number", "description": "End line number (0-based)" },
-                    "end_character": { "type": "number", "description": "End character position (0-based)" }
+                    "end_character": { "type": "number", "description": "End character position (0-based)" },
+                    "no_cache": { "type": "boolean", "description": "Ask rust-analyzer again even if the hints for this unchanged file are cached" }
                 },
                 "required": ["file_path", "line", "character", "end_line", "end_character"]
             }),
+            aliases: Vec::new(),
         },
         // 8-11. Code structure analysis
         ToolDefinition {
@@ -115,24 +337,89 @@ pub fn get_tools() -> Vec<ToolDefinition> {
                 "type": "object",
                 "properties": {
                     "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "content": { "type": "string", "description": "Unsaved file content to analyze instead of the file on disk" },
                     "line": { "type": "number", "description": "Line number (0-based)" },
                     "character": { "type": "number", "description": "Character position (0-based)" }
                 },
                 "required": ["file_path", "line", "character"]
             }),
+            aliases: Vec::new(),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_find_implementations".to_string(),
+            description: "Find all implementations of a trait by name, without a file position: the trait is looked up workspace-wide, and each implementation is returned with its location and the type it is for. Errors with the candidates when several traits share the name; pass path to pick one.".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "trait_name": { "type": "string", "description": "Trait name, e.g. \"Handler\"" },
+                    "path": { "type": "string", "description": "Disambiguator: a module path the trait's ends with (e.g. \"server::Handler\") or part of its file path" }
+                },
+                "required": ["trait_name"]
+            }),
+            aliases: Vec::new(),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_find_references".to_string(),
+            description: "Find references to a symbol by name, without a file position: the symbol is looked up workspace-wide (types, functions, methods, constants and more), then its references are returned grouped by file with counts and path:line:column locations, the definition included. When several symbols share the name, returns their kind, container and location as candidates instead; pass kind or path to pick one.".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "symbol": { "type": "string", "description": "Symbol name, e.g. \"parse_config\"" },
+                    "kind": { "type": "string", "description": "Disambiguator: the symbol's kind, e.g. \"struct\", \"function\", \"method\", \"trait\", \"enum\", \"constant\"" },
+                    "path": { "type": "string", "description": "Disambiguator: a module path the symbol's ends with (e.g. \"config::parse_config\") or part of its file path" }
+                },
+                "required": ["symbol"]
+            }),
+            aliases: Vec::new(),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_usage_stats".to_string(),
+            description: "Count references to each top-level pub item in a file, least used first: symbol, kind, definition location, total references, how many files they are in, and whether all are in the defining file (local). Useful for deciding what to make private or deprecate. At most 100 items are counted; truncated says whether some were left out.".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "content": { "type": "string", "description": "Unsaved file content to analyze instead of the file on disk" },
+                    "symbols": { "type": "array", "items": { "type": "string" }, "description": "Only these items, by name" },
+                    "max_symbols": { "type": "number", "description": "Count at most this many items (default and maximum 100)" }
+                },
+                "required": ["file_path"]
+            }),
+            aliases: Vec::new(),
         },
         ToolDefinition {
             name: "rust_analyzer_incoming_calls".to_string(),
-            description: "Find all functions that call this function".to_string(),
+            description: "Find all functions that call this function. with_signature and with_snippet add each caller's signature and the lines of its calls; limit and offset page through many callers.".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "content": { "type": "string", "description": "Unsaved file content to analyze instead of the file on disk" },
+                    "line": { "type": "number", "description": "Line number (0-based)" },
+                    "character": { "type": "number", "description": "Character position (0-based)" },
+                    "with_signature": { "type": "boolean", "description": "Add each caller's signature" },
+                    "with_snippet": { "type": "boolean", "description": "Add the numbered lines where each caller makes the calls" },
+                    "limit": { "type": "number", "description": "Most callers to return; the result then says the total and the next offset" },
+                    "offset": { "type": "number", "description": "Callers to skip, for the next page" }
+                },
+                "required": ["file_path", "line", "character"]
+            }),
+            aliases: Vec::new(),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_impact".to_string(),
+            description: "Impact of changing the symbol at a position, in one call: references grouped by file with a total, direct callers, implementations, and whether any usage lies outside the symbol's own crate. Sections rust-analyzer can't answer (e.g. callers of a struct) are listed under unavailable instead of failing the call.".to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
                     "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "content": { "type": "string", "description": "Unsaved file content to analyze instead of the file on disk" },
                     "line": { "type": "number", "description": "Line number (0-based)" },
                     "character": { "type": "number", "description": "Character position (0-based)" }
                 },
                 "required": ["file_path", "line", "character"]
             }),
+            aliases: Vec::new(),
         },
         ToolDefinition {
             name: "rust_analyzer_outgoing_calls".to_string(),
@@ -141,11 +428,49 @@ pub fn get_tools() -> Vec<ToolDefinition> {
                 "type": "object",
                 "properties": {
                     "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "content": { "type": "string", "description": "Unsaved file content to analyze instead of the file on disk" },
                     "line": { "type": "number", "description": "Line number (0-based)" },
                     "character": { "type": "number", "description": "Character position (0-based)" }
                 },
                 "required": ["file_path", "line", "character"]
             }),
+            aliases: Vec::new(),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_call_graph".to_string(),
+            description: "Call graph around the function at a position, as GraphViz DOT (or JSON with format: \"json\"): its callers, its callees or both, up to depth calls away (default 2). Each function is a node labeled with its name and file:line (0-based), grouped in a cluster per crate of the workspace; recursion and cycles appear once. Graphs stop at 100 functions or 300 calls and then say they were truncated".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "content": { "type": "string", "description": "Unsaved file content to analyze instead of the file on disk" },
+                    "line": { "type": "number", "description": "Line number (0-based)" },
+                    "character": { "type": "number", "description": "Character position (0-based)" },
+                    "direction": { "type": "string", "enum": ["callers", "callees", "both"], "description": "Follow calls into the function, out of it, or both (default both)" },
+                    "depth": { "type": "number", "description": "How many calls away from the function to go (default 2)" },
+                    "format": { "type": "string", "enum": ["dot", "json"], "description": "GraphViz DOT (default), or JSON with the nodes and each caller's callees" }
+                },
+                "required": ["file_path", "line", "character"]
+            }),
+            aliases: Vec::new(),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_type_graph".to_string(),
+            description: "Trait and type hierarchy around the trait or type at a position, as GraphViz DOT (or JSON with format: \"json\"): the traits a type implements and a trait's supertraits, the types implementing a trait, or both, up to depth relations away (default 2). Edges read \"Type implements Trait\" and \"Trait supertrait Supertrait\"; each node is labeled with its kind, name and file:line (0-based), grouped in a cluster per crate of the workspace. Graphs stop at 100 types or 300 relations and then say they were truncated".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "content": { "type": "string", "description": "Unsaved file content to analyze instead of the file on disk" },
+                    "line": { "type": "number", "description": "Line number (0-based)" },
+                    "character": { "type": "number", "description": "Character position (0-based)" },
+                    "direction": { "type": "string", "enum": ["supertypes", "subtypes", "both"], "description": "Follow relations up to implemented traits and supertraits, down to implementors, or both (default both)" },
+                    "depth": { "type": "number", "description": "How many relations away from the trait or type to go (default 2)" },
+                    "format": { "type": "string", "enum": ["dot", "json"], "description": "GraphViz DOT (default), or JSON with the nodes and their relations" }
+                },
+                "required": ["file_path", "line", "character"]
+            }),
+            aliases: Vec::new(),
         },
         ToolDefinition {
             name: "rust_analyzer_parent_module".to_string(),
@@ -154,20 +479,179 @@ pub fn get_tools() -> Vec<ToolDefinition> {
                 "type": "object",
                 "properties": {
                     "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "content": { "type": "string", "description": "Unsaved file content to analyze instead of the file on disk" },
                     "line": { "type": "number", "description": "Line number (0-based)" },
                     "character": { "type": "number", "description": "Character position (0-based)" }
                 },
                 "required": ["file_path", "line", "character"]
             }),
+            aliases: Vec::new(),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_expand_glob_import".to_string(),
+            description: "Replace a glob import (use foo::*;) with the items the code actually uses, via rust-analyzer's \"Expand glob import\" assist. Give the position of the *, or all: true for every glob import in the file. Returns the changed lines; apply: true writes the file.".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "content": { "type": "string", "description": "Unsaved file content to analyze instead of the file on disk" },
+                    "line": { "type": "number", "description": "Line of the glob import (0-based); not needed with all" },
+                    "character": { "type": "number", "description": "Character position of its * (0-based); not needed with all" },
+                    "all": { "type": "boolean", "description": "Expand every glob import in the file, one after another" },
+                    "apply": { "type": "boolean", "description": "Write the expanded imports to the file instead of only returning the changed lines" }
+                },
+                "required": ["file_path"]
+            }),
+            aliases: Vec::new(),
         },
         // 12. Workspace-wide utilities
         ToolDefinition {
             name: "rust_analyzer_workspace_diagnostics".to_string(),
             description: "Get all compiler diagnostics across the entire workspace".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "severity": { "type": "string", "enum": ["error", "warning", "information", "hint"], "description": "Only diagnostics at least this severe" },
+                    "code": { "type": "string", "description": "Only diagnostics whose code starts with this, e.g. \"clippy::\" or \"E0308\"" }
+                }
+            }),
+            aliases: Vec::new(),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_diagnostics_for".to_string(),
+            description: "Get compiler diagnostics for the .rs files under a directory or matching a glob (relative to the workspace root, e.g. \"crates/server/src\" or \"src/**/handlers*.rs\"). Opens the files, waits for rust-analyzer's cargo check, and returns diagnostics per file with a combined summary and how the check went under flycheck. Only the first max_files files are checked; more matches are reported with truncated and a note.".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "A directory, a glob, or a single file" },
+                    "max_files": { "type": "number", "description": "Most files to open and check (default 50, at most max_open_documents)" },
+                    "severity": { "type": "string", "enum": ["error", "warning", "information", "hint"], "description": "Only diagnostics at least this severe" },
+                    "code": { "type": "string", "description": "Only diagnostics whose code starts with this, e.g. \"clippy::\" or \"E0308\"" }
+                },
+                "required": ["path"]
+            }),
+            aliases: Vec::new(),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_unused".to_string(),
+            description: "List unused code across the workspace as a ranked cleanup list: dead_code, unused_variables, unused_imports and clippy's unused_* lints, one item per warning with its location and how many diagnostics removing it clears, plus per-file totals. min_confidence \"high\" first confirms each dead_code item with a references search, keeping only items nothing but tests uses.".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "min_confidence": { "type": "string", "enum": ["low", "high"], "description": "low (default) lists every warning; high runs the slower references check on dead_code items and lists those used outside tests separately, under used_elsewhere" }
+                }
+            }),
+            aliases: Vec::new(),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_cargo_metadata".to_string(),
+            description: "Summarize cargo metadata for the workspace: each member's name, version, manifest path, lib and bin targets, and direct dependencies. Pass package for one member's full metadata. Cached until a Cargo.toml or Cargo.lock changes.".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "package": { "type": "string", "description": "Name of a workspace member to return in full" }
+                }
+            }),
+            aliases: Vec::new(),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_features".to_string(),
+            description: "List each workspace member's cargo features and which are active in rust-analyzer's analysis, given the configured features. Code behind an inactive feature's #[cfg] is not analyzed. Pass package for one member, with the optional dependencies each feature enables.".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "package": { "type": "string", "description": "Name of a workspace member" }
+                }
+            }),
+            aliases: Vec::new(),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_module_tree".to_string(),
+            description: "Map a crate's modules, starting from its root file: each module's file, whether it is inline (mod name { ... }), how many items it declares and how many are pub, and its submodules".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "package": { "type": "string", "description": "Workspace member; defaults to the one at the workspace root" },
+                    "target": { "type": "string", "description": "Target name; defaults to the library, else the first binary" },
+                    "max_depth": { "type": "number", "description": "How many modules deep to go (default 8)" }
+                }
+            }),
+            aliases: Vec::new(),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_public_api".to_string(),
+            description: "List everything a crate exports, by full path (crate::module::Item): pub items in pub modules with their kind and location, and pub use re-exports with the item they lead to. Items marked #[doc(hidden)], or inside such a module, are flagged hidden.".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "package": { "type": "string", "description": "Workspace member; defaults to the one at the workspace root" },
+                    "target": { "type": "string", "description": "Target name; defaults to the library, else the first binary" },
+                    "max_depth": { "type": "number", "description": "How many modules deep to go (default 8)" }
+                }
+            }),
+            aliases: Vec::new(),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_toolchain".to_string(),
+            description: "Report the toolchain the workspace builds with: rustc version, channel, host triple and sysroot, the toolchain rust-toolchain.toml pins, the rust-analyzer version, and RUSTFLAGS, CARGO_TARGET_DIR and similar environment. Warns when rustc doesn't match the pinned toolchain.".to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {}
             }),
+            aliases: Vec::new(),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_cargo_check".to_string(),
+            description: "Run cargo check (rust-analyzer's check on save) on the workspace now, wait for it to finish, and return the fresh workspace diagnostics with how long the check took. Calls made while a check runs wait for that one; timeout_secs overrides how long to wait.".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "severity": { "type": "string", "enum": ["error", "warning", "information", "hint"], "description": "Only diagnostics at least this severe" },
+                    "code": { "type": "string", "description": "Only diagnostics whose code starts with this, e.g. \"clippy::\" or \"E0308\"" }
+                }
+            }),
+            aliases: Vec::new(),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_list_tests".to_string(),
+            description: "List the tests in a file (including test modules and doctests) with each one's module path, location, whether it is #[ignore]d, and the exact cargo command that runs it".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "content": { "type": "string", "description": "Unsaved file content to analyze instead of the file on disk" }
+                },
+                "required": ["file_path"]
+            }),
+            aliases: Vec::new(),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_run_test".to_string(),
+            description: "Run one test with the cargo command rust-analyzer computed for it, and report whether it passed, how long it took, and the panic of a failure. Pass a runnable from rust_analyzer_list_tests, or a position inside the test. Runs one at a time; timeout_secs overrides the test timeout.".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "runnable": { "type": "object", "description": "A test as listed by rust_analyzer_list_tests" },
+                    "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "content": { "type": "string", "description": "Unsaved file content to analyze instead of the file on disk" },
+                    "line": { "type": "number", "description": "Line number inside the test (0-based)" },
+                    "character": { "type": "number", "description": "Character position (0-based)" }
+                }
+            }),
+            aliases: Vec::new(),
+        },
+        // 13. Document management
+        ToolDefinition {
+            name: "rust_analyzer_close_document".to_string(),
+            description: "Close a file in rust-analyzer to free memory; it is reopened on the next tool call that uses it. The least recently used files are closed automatically past the open-document limit.".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the Rust file" }
+                },
+                "required": ["file_path"]
+            }),
+            aliases: Vec::new(),
         },
     ]
 }
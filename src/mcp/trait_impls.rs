@@ -0,0 +1,248 @@
+use anyhow::{anyhow, Result};
+use log::info;
+use serde_json::{json, Value};
+use std::{collections::HashMap, time::Instant};
+
+use crate::{
+    config::Config,
+    lsp::{
+        paths::uri_path,
+        position::{uris_in, DocumentColumns},
+        RequestTimeout, RustAnalyzerClient,
+    },
+    protocol::{
+        mcp::{ContentItem, ToolResult},
+        tool_error,
+    },
+};
+
+use super::{handlers::check_retry_timeout, server::RustAnalyzerMCPServer};
+
+/// LSP `SymbolKind::Interface`, which rust-analyzer reports traits as.
+const TRAIT_KIND: u64 = 11;
+
+/// `path:line:column` of `position` in `uri`.
+pub(super) fn location(columns: &DocumentColumns, uri: &str, position: &Value) -> String {
+    let line = position["line"].as_u64().unwrap_or(0);
+    let character = columns.to_user(uri, line, position["character"].as_u64().unwrap_or(0));
+    format!("{}:{}:{}", uri_path(uri).display(), line, character)
+}
+
+/// Whether a `workspace/symbol` result is the symbol `path` picks out: one
+/// whose `container::name` ends with `path`, or whose file path contains it.
+fn matches_path(symbol: &Value, path: &str) -> bool {
+    let name = symbol["name"].as_str().unwrap_or_default();
+    let qualified = match symbol["containerName"].as_str() {
+        Some(container) => format!("{}::{}", container, name),
+        None => name.to_string(),
+    };
+    let file = symbol["location"]["uri"].as_str().unwrap_or_default();
+    qualified.ends_with(path) || file.contains(path)
+}
+
+/// Whether `position` lies in `range`.
+fn contains(range: &Value, position: &Value) -> bool {
+    let key = |position: &Value| {
+        (
+            position["line"].as_u64().unwrap_or(0),
+            position["character"].as_u64().unwrap_or(0),
+        )
+    };
+    key(&range["start"]) <= key(position) && key(position) <= key(&range["end"])
+}
+
+/// The innermost `impl` block in a `textDocument/documentSymbol` result,
+/// flat or nested, that holds `position`, by name (`impl Trait for Type`).
+fn enclosing_impl<'a>(symbols: &'a Value, position: &Value) -> Option<&'a str> {
+    let mut best: Option<(&str, (u64, u64))> = None;
+    let mut stack: Vec<&Value> = symbols.as_array().into_iter().flatten().collect();
+    while let Some(symbol) = stack.pop() {
+        stack.extend(symbol["children"].as_array().into_iter().flatten());
+        let range = match &symbol["range"] {
+            Value::Null => &symbol["location"]["range"],
+            range => range,
+        };
+        let Some(name) = symbol["name"].as_str() else {
+            continue;
+        };
+        if !name.starts_with("impl") || !contains(range, position) {
+            continue;
+        }
+        let start = (
+            range["start"]["line"].as_u64().unwrap_or(0),
+            range["start"]["character"].as_u64().unwrap_or(0),
+        );
+        if best.is_none_or(|(_, best_start)| start > best_start) {
+            best = Some((name, start));
+        }
+    }
+    best.map(|(name, _)| name)
+}
+
+/// The implementing type in an impl's name: `Type` of `impl Trait for Type`.
+pub(super) fn implementing_type(impl_name: &str) -> &str {
+    impl_name
+        .split_once(" for ")
+        .map_or(impl_name, |(_, self_type)| self_type)
+        .trim()
+}
+
+/// `workspace/symbol` results for `query`, waiting for indexing to
+/// complete while there are none.
+pub(super) async fn search_symbols(
+    client: &mut RustAnalyzerClient,
+    config: &Config,
+    tool: &str,
+    query: &str,
+) -> Result<Value> {
+    // Retry logic: wait for indexing to complete
+    let retry_interval = config.retry_interval();
+    let indexing_timeout = config.indexing_timeout();
+    let start = Instant::now();
+    let mut logged_waiting = false;
+
+    let params = json!({ "query": query });
+    loop {
+        match client
+            .request_detached("workspace/symbol", Some(params.clone()))?
+            .await
+        {
+            Ok(result) if result.as_array().is_some_and(|symbols| !symbols.is_empty()) => {
+                if logged_waiting {
+                    info!("{}: Indexing complete, returning results", tool);
+                }
+                return Ok(result);
+            }
+            Err(e) if e.is::<RequestTimeout>() => return Err(e),
+            Ok(_) | Err(_) => {
+                check_retry_timeout(tool, &start, indexing_timeout, &mut logged_waiting, client)?;
+                tokio::time::sleep(retry_interval).await;
+            }
+        }
+    }
+}
+
+/// The `workspace/symbol` results named exactly `name`, of a kind `kind`
+/// accepts, that `path` picks out when given.
+pub(super) fn named_candidates<'a>(
+    symbols: &'a Value,
+    name: &str,
+    kind: impl Fn(u64) -> bool,
+    path: Option<&str>,
+) -> Vec<&'a Value> {
+    symbols
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|symbol| symbol["name"] == name && symbol["kind"].as_u64().is_some_and(&kind))
+        .filter(|symbol| path.is_none_or(|path| matches_path(symbol, path)))
+        .collect()
+}
+
+/// `rust_analyzer_find_implementations`: every implementation of the trait
+/// named `trait_name`, found through `workspace/symbol` without a position,
+/// with the type each one is for.
+pub(crate) async fn handle_find_implementations(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let Some(trait_name) = args["trait_name"].as_str() else {
+        return Err(tool_error!(InvalidParams, "Missing trait_name"));
+    };
+    let path = args["path"].as_str();
+
+    let Some(client) = &mut server.client else {
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
+    };
+
+    let symbols =
+        search_symbols(client, &server.config, "find_implementations", trait_name).await?;
+    let candidates = named_candidates(&symbols, trait_name, |kind| kind == TRAIT_KIND, path);
+    let columns = client.document_columns(uris_in(&symbols)).await;
+    let describe = |symbol: &Value| {
+        let uri = symbol["location"]["uri"].as_str().unwrap_or_default();
+        let at = location(&columns, uri, &symbol["location"]["range"]["start"]);
+        match symbol["containerName"].as_str() {
+            Some(container) => format!("{}::{} ({})", container, trait_name, at),
+            None => format!("{} ({})", trait_name, at),
+        }
+    };
+    let found = match candidates.as_slice() {
+        [found] => *found,
+        [] => {
+            return Err(tool_error!(
+                InvalidParams,
+                "No trait named {}{}",
+                trait_name,
+                path.map(|path| format!(" matching {}", path))
+                    .unwrap_or_default()
+            ))
+        }
+        _ => {
+            let listed: Vec<String> = candidates.iter().map(|symbol| describe(symbol)).collect();
+            return Err(anyhow!(
+                "{} traits are named {}; pass path to pick one of:\n{}",
+                candidates.len(),
+                trait_name,
+                listed.join("\n")
+            ));
+        }
+    };
+
+    let trait_uri = found["location"]["uri"].as_str().unwrap_or_default();
+    let mut trait_info = json!({
+        "name": trait_name,
+        "location": location(&columns, trait_uri, &found["location"]["range"]["start"])
+    });
+    if let Some(container) = found["containerName"].as_str() {
+        trait_info["container"] = json!(container);
+    }
+    let params = json!({
+        "textDocument": { "uri": trait_uri },
+        "position": found["location"]["range"]["start"]
+    });
+    let result = client
+        .request_detached("textDocument/implementation", Some(params))?
+        .await?;
+
+    // One documentSymbol request per file the implementations are in.
+    let mut file_symbols: HashMap<String, Value> = HashMap::new();
+    let mut implementations = Vec::new();
+    for imp in result.as_array().into_iter().flatten() {
+        let (uri, position) = match imp["targetUri"].as_str() {
+            Some(uri) => (uri, &imp["targetSelectionRange"]["start"]),
+            None => match imp["uri"].as_str() {
+                Some(uri) => (uri, &imp["range"]["start"]),
+                None => continue,
+            },
+        };
+        if !file_symbols.contains_key(uri) {
+            let symbols = client.document_symbols(uri).await.unwrap_or(Value::Null);
+            file_symbols.insert(uri.to_string(), symbols);
+        }
+        let self_type = enclosing_impl(&file_symbols[uri], position)
+            .map(|impl_name| implementing_type(impl_name).to_string());
+        implementations.push((uri, position, self_type));
+    }
+
+    let columns = client.document_columns(uris_in(&result)).await;
+    let implementations: Vec<Value> = implementations
+        .into_iter()
+        .map(|(uri, position, self_type)| {
+            json!({ "type": self_type, "location": location(&columns, uri, position) })
+        })
+        .collect();
+
+    let report = json!({
+        "trait": trait_info,
+        "count": implementations.len(),
+        "implementations": implementations
+    });
+
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text: serde_json::to_string_pretty(&report)?,
+        }],
+    })
+}
@@ -0,0 +1,182 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Map, Value};
+
+use crate::protocol::tool_error;
+
+/// Where a truncated result resumes: the collection that was cut (`None`
+/// for a result that is itself an array) and the index of its first
+/// omitted item. Passed back by callers as `"cursor": "field:offset"`, or
+/// just the offset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cursor {
+    pub field: Option<String>,
+    pub offset: usize,
+}
+
+impl Cursor {
+    pub fn parse(cursor: &str) -> Result<Self> {
+        let (field, offset) = match cursor.rsplit_once(':') {
+            Some((field, offset)) => (Some(field.to_string()), offset),
+            None => (None, cursor),
+        };
+        let offset = offset
+            .parse()
+            .map_err(|_| tool_error!(InvalidParams, "Invalid cursor {:?}", cursor))?;
+        Ok(Self { field, offset })
+    }
+}
+
+impl std::fmt::Display for Cursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.field {
+            Some(field) => write!(f, "{}:{}", field, self.offset),
+            None => write!(f, "{}", self.offset),
+        }
+    }
+}
+
+/// The items of the collection being paged through: array elements, or the
+/// entries of an object keyed by file.
+enum Items {
+    Array(Vec<Value>),
+    Entries(Vec<(String, Value)>),
+}
+
+impl Items {
+    fn take(value: Value) -> Option<Self> {
+        match value {
+            Value::Array(items) => Some(Items::Array(items)),
+            Value::Object(entries) => Some(Items::Entries(entries.into_iter().collect())),
+            _ => None,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Items::Array(items) => items.len(),
+            Items::Entries(entries) => entries.len(),
+        }
+    }
+
+    fn page(&self, start: usize, count: usize) -> Value {
+        match self {
+            Items::Array(items) => {
+                Value::Array(items.iter().skip(start).take(count).cloned().collect())
+            }
+            Items::Entries(entries) => Value::Object(
+                entries
+                    .iter()
+                    .skip(start)
+                    .take(count)
+                    .cloned()
+                    .collect::<Map<_, _>>(),
+            ),
+        }
+    }
+}
+
+/// Keep `text`, a tool's output, within `max_bytes`. JSON output is cut
+/// between items of its largest array (or per-file object) and gains
+/// `truncated`, `omitted_items`, and a `cursor` to pass back for the rest;
+/// a result that is itself an array is wrapped as `{"items": [...], ...}`.
+/// Output without a collection to cut is shortened to a `text` prefix.
+/// Output within budget and without a cursor is returned unchanged.
+pub fn limit_output(
+    text: &str,
+    max_bytes: usize,
+    cursor: Option<&Cursor>,
+    pretty: bool,
+) -> Result<String> {
+    if cursor.is_none() && text.len() <= max_bytes {
+        return Ok(text.to_string());
+    }
+    let serialize = |value: &Value| -> Result<String> {
+        Ok(if pretty {
+            serde_json::to_string_pretty(value)?
+        } else {
+            value.to_string()
+        })
+    };
+
+    let value: Value = match serde_json::from_str(text) {
+        Ok(value) => value,
+        Err(_) if cursor.is_none() => return serialize(&truncated_text(text, max_bytes)),
+        Err(_) => return Err(anyhow!("This result can't be resumed with a cursor")),
+    };
+    let field = match cursor {
+        Some(cursor) => cursor.field.clone(),
+        None => largest_collection(&value),
+    };
+
+    // Split the result into the collection and what surrounds it.
+    let (outer, collection) = match (&field, value) {
+        (None, Value::Array(items)) => (Map::new(), Items::Array(items)),
+        (Some(field), Value::Object(mut outer)) => {
+            let collection = outer.remove(field).and_then(Items::take).ok_or_else(|| {
+                anyhow!("Cursor names {:?}, which this result doesn't have", field)
+            })?;
+            (outer, collection)
+        }
+        _ if cursor.is_none() => return serialize(&truncated_text(text, max_bytes)),
+        _ => return Err(anyhow!("This result can't be resumed with a cursor")),
+    };
+
+    let start = cursor.map_or(0, |c| c.offset).min(collection.len());
+    let remaining = collection.len() - start;
+    let page = |count: usize| -> Result<String> {
+        let omitted = remaining - count;
+        let mut output = outer.clone();
+        output.insert(
+            field.clone().unwrap_or_else(|| "items".to_string()),
+            collection.page(start, count),
+        );
+        output.insert("truncated".to_string(), json!(omitted > 0));
+        output.insert("omitted_items".to_string(), json!(omitted));
+        if omitted > 0 {
+            let next = Cursor {
+                field: field.clone(),
+                offset: start + count,
+            };
+            output.insert("cursor".to_string(), json!(next.to_string()));
+        }
+        serialize(&Value::Object(output))
+    };
+
+    // The most items that fit, but at least one so each call makes progress.
+    let (mut fits, mut too_many) = (remaining.min(1), remaining + 1);
+    let mut best = page(fits)?;
+    while fits + 1 < too_many {
+        let count = (fits + too_many) / 2;
+        let candidate = page(count)?;
+        if candidate.len() <= max_bytes {
+            fits = count;
+            best = candidate;
+        } else {
+            too_many = count;
+        }
+    }
+    Ok(best)
+}
+
+/// The top-level field holding the most output, if it's an array or object.
+fn largest_collection(value: &Value) -> Option<String> {
+    value
+        .as_object()?
+        .iter()
+        .filter(|(_, field)| field.is_array() || field.is_object())
+        .max_by_key(|(_, field)| field.to_string().len())
+        .map(|(key, _)| key.clone())
+}
+
+/// The start of `text`, cut at a character boundary to about `max_bytes`.
+fn truncated_text(text: &str, max_bytes: usize) -> Value {
+    let mut end = max_bytes.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    json!({
+        "text": &text[..end],
+        "truncated": true,
+        "omitted_bytes": text.len() - end
+    })
+}
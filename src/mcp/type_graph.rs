@@ -0,0 +1,725 @@
+use anyhow::Result;
+use log::{debug, info};
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    sync::Arc,
+    time::Instant,
+};
+
+use crate::{
+    lsp::{paths::uri_path, symbol_kind_name, RequestTimeout, RustAnalyzerClient},
+    protocol::{
+        mcp::{ContentItem, ToolResult},
+        tool_error,
+    },
+};
+
+use super::{
+    call_graph::{depth_arg, dot_format_arg, dot_graph, GraphPlaces},
+    handlers::{check_retry_timeout, ToolParams},
+    server::RustAnalyzerMCPServer,
+};
+
+/// How many relations away from the root `rust_analyzer_type_graph` goes by
+/// default.
+const TYPE_GRAPH_DEPTH: usize = 2;
+
+/// Most types and relations a graph holds; the walk stops at either.
+const TYPE_GRAPH_MAX_NODES: usize = 100;
+const TYPE_GRAPH_MAX_EDGES: usize = 300;
+
+/// Keywords declaring the items a type graph holds, which are also the
+/// kinds it shows.
+const TYPE_KEYWORDS: &[&str] = &["trait", "struct", "enum", "union", "type"];
+
+/// A type implements a trait.
+const IMPLEMENTS: &str = "implements";
+/// A trait requires another: `Trait: Supertrait`.
+const SUPERTRAIT: &str = "supertrait";
+
+/// Which way the walk follows relations from the root: to the traits a type
+/// implements and a trait's supertraits, or to a trait's implementors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Direction {
+    Supertypes,
+    Subtypes,
+}
+
+/// Positions of the characters of `text` from `(line, character)` on, with
+/// a `'\n'` ending each line.
+fn chars_from(text: &str, line: u32, character: u32) -> Vec<(u32, u32, char)> {
+    text.lines()
+        .enumerate()
+        .skip(line as usize)
+        .flat_map(|(number, code)| {
+            let number = number as u32;
+            let skip = if number == line {
+                character as usize
+            } else {
+                0
+            };
+            let length = code.chars().count() as u32;
+            code.chars()
+                .enumerate()
+                .skip(skip)
+                .map(move |(column, c)| (number, column as u32, c))
+                .chain([(number, length, '\n')])
+        })
+        .collect()
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Whether `word` starts at `chars[at]` as a whole word.
+fn word_at(chars: &[(u32, u32, char)], at: usize, word: &str) -> bool {
+    let before = at.checked_sub(1).map(|i| chars[i].2);
+    let after = chars.get(at + word.chars().count()).map(|c| c.2);
+    !before.is_some_and(is_ident)
+        && !after.is_some_and(is_ident)
+        && chars[at..]
+            .iter()
+            .map(|c| c.2)
+            .take(word.len())
+            .eq(word.chars())
+}
+
+/// Index of the first non-whitespace character at or after `at`.
+fn skip_whitespace(chars: &[(u32, u32, char)], mut at: usize) -> usize {
+    while chars.get(at).is_some_and(|c| c.2.is_whitespace()) {
+        at += 1;
+    }
+    at
+}
+
+/// Index just past the `<...>` starting at `at`.
+fn skip_generics(chars: &[(u32, u32, char)], mut at: usize) -> usize {
+    let mut depth = 0;
+    while let Some((_, _, c)) = chars.get(at) {
+        match c {
+            '<' => depth += 1,
+            '>' if at > 0 && chars[at - 1].2 != '-' => depth -= 1,
+            _ => {}
+        }
+        at += 1;
+        if depth == 0 {
+            break;
+        }
+    }
+    at
+}
+
+/// `chars` up to the `{` or `;` ending an item's header, or its `where`
+/// clause, split where `separator` (a word, or `+`) stands outside brackets.
+fn header_parts<'a>(chars: &'a [(u32, u32, char)], separator: &str) -> Vec<&'a [(u32, u32, char)]> {
+    let separates = |at: usize, c: char| match separator {
+        "+" => c == '+',
+        word => word_at(chars, at, word),
+    };
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    let mut at = 0;
+    while let Some((_, _, c)) = chars.get(at) {
+        match c {
+            '<' | '(' | '[' => depth += 1,
+            '>' if at > 0 && chars[at - 1].2 == '-' => {}
+            '>' | ')' | ']' => depth -= 1,
+            '{' | ';' if depth == 0 => break,
+            _ if depth == 0 && word_at(chars, at, "where") => break,
+            _ if depth == 0 && separates(at, *c) => {
+                parts.push(&chars[start..at]);
+                at += separator.len();
+                start = at;
+                continue;
+            }
+            _ => {}
+        }
+        at += 1;
+    }
+    parts.push(&chars[start..at]);
+    parts
+}
+
+/// Position of the name a type or bound in `part` is for: the last segment
+/// of its path, past any `&`, lifetime, `mut` or `dyn`. `None` for lifetime
+/// and `?Sized` bounds, negative impls, and types without a path.
+fn path_name(part: &[(u32, u32, char)]) -> Option<(u32, u32)> {
+    let mut at = skip_whitespace(part, 0);
+    loop {
+        match part.get(at)?.2 {
+            '&' => at = skip_whitespace(part, at + 1),
+            '\'' => {
+                at += 1;
+                while part.get(at).is_some_and(|c| is_ident(c.2)) {
+                    at += 1;
+                }
+                at = skip_whitespace(part, at);
+            }
+            _ if word_at(part, at, "mut") => at = skip_whitespace(part, at + 3),
+            _ if word_at(part, at, "dyn") => at = skip_whitespace(part, at + 3),
+            _ => break,
+        }
+    }
+
+    let mut segment = None;
+    while let Some((line, column, c)) = part.get(at) {
+        if is_ident(*c) {
+            if segment.is_none() {
+                segment = Some((*line, *column));
+            }
+            at += 1;
+        } else if *c == ':' && part.get(at + 1).is_some_and(|c| c.2 == ':') {
+            segment = None;
+            at += 2;
+        } else {
+            break;
+        }
+    }
+    segment
+}
+
+/// Positions of the supertraits of the trait whose name is at `(line,
+/// character)` in `text`: the bounds after `trait Name<...>:`, up to its
+/// body or `where` clause.
+pub fn supertrait_bounds(text: &str, line: u32, character: u32) -> Vec<(u32, u32)> {
+    let chars = chars_from(text, line, character);
+    let mut at = 0;
+    while chars.get(at).is_some_and(|c| is_ident(c.2)) {
+        at += 1;
+    }
+    at = skip_whitespace(&chars, at);
+    if chars.get(at).is_some_and(|c| c.2 == '<') {
+        at = skip_whitespace(&chars, skip_generics(&chars, at));
+    }
+    if chars.get(at).map(|c| c.2) != Some(':') {
+        return Vec::new();
+    }
+    header_parts(&chars[at + 1..], "+")
+        .into_iter()
+        .filter(|bound| {
+            let start = skip_whitespace(bound, 0);
+            !bound.get(start).is_some_and(|c| c.2 == '?' || c.2 == '\'')
+        })
+        .filter_map(path_name)
+        .collect()
+}
+
+/// The names in an `impl` header: of the trait implemented, if any, and of
+/// the type it's for.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ImplHeader {
+    pub trait_name: Option<(u32, u32)>,
+    pub self_type: Option<(u32, u32)>,
+}
+
+/// How many lines above a position the `impl` it's in may start.
+const IMPL_LOOKBACK: u32 = 3;
+
+/// The header of the `impl` at `(line, character)` in `text`, which is
+/// where rust-analyzer puts implementations: the `impl` keyword, or the type
+/// in its header. `None` when there's no `impl` there.
+pub fn impl_header(text: &str, line: u32, character: u32) -> Option<ImplHeader> {
+    let lines: Vec<&str> = text.lines().collect();
+    let (impl_line, impl_column) =
+        (line.saturating_sub(IMPL_LOOKBACK)..=line)
+            .rev()
+            .find_map(|number| {
+                let code = lines.get(number as usize)?;
+                let chars = chars_from(code, 0, 0);
+                (0..chars.len())
+                    .rev()
+                    .filter(|at| number < line || chars[*at].1 <= character)
+                    .find(|at| word_at(&chars, *at, "impl"))
+                    .map(|at| (number, chars[at].1))
+            })?;
+
+    let chars = chars_from(text, impl_line, impl_column + 4);
+    let mut at = skip_whitespace(&chars, 0);
+    if chars.get(at).is_some_and(|c| c.2 == '<') {
+        at = skip_generics(&chars, at);
+    }
+    match header_parts(&chars[at..], "for").as_slice() {
+        [self_type] => Some(ImplHeader {
+            trait_name: None,
+            self_type: path_name(self_type),
+        }),
+        [trait_name, self_type, ..] => {
+            let negative = trait_name
+                .get(skip_whitespace(trait_name, 0))
+                .is_some_and(|c| c.2 == '!');
+            Some(ImplHeader {
+                trait_name: if negative {
+                    None
+                } else {
+                    path_name(trait_name)
+                },
+                self_type: path_name(self_type),
+            })
+        }
+        [] => None,
+    }
+}
+
+/// A trait or type in the graph.
+struct Node {
+    name: String,
+    kind: String,
+    uri: String,
+    line: u32,
+    /// Where the name starts, in characters.
+    character: u32,
+    /// The `textDocument/prepareTypeHierarchy` item, when the server
+    /// answers type hierarchy requests.
+    item: Value,
+}
+
+/// Traits, types and the relations between them found so far, up to the
+/// caps.
+#[derive(Default)]
+struct TypeGraph {
+    nodes: Vec<Node>,
+    ids: HashMap<(String, u32, u32), usize>,
+    /// Relations, as (subtype, supertype, relation).
+    edges: BTreeSet<(usize, usize, &'static str)>,
+    truncated: bool,
+}
+
+impl TypeGraph {
+    /// The id of `node`, added if new; `None` when it's new and the graph
+    /// is full.
+    fn node(&mut self, node: Node) -> Option<usize> {
+        let key = (node.uri.clone(), node.line, node.character);
+        if let Some(id) = self.ids.get(&key) {
+            return Some(*id);
+        }
+        if self.nodes.len() >= TYPE_GRAPH_MAX_NODES {
+            self.truncated = true;
+            return None;
+        }
+        self.nodes.push(node);
+        self.ids.insert(key, self.nodes.len() - 1);
+        Some(self.nodes.len() - 1)
+    }
+
+    /// Add a relation; `false` when the graph is full.
+    fn edge(&mut self, edge: (usize, usize, &'static str)) -> bool {
+        if self.edges.len() >= TYPE_GRAPH_MAX_EDGES && !self.edges.contains(&edge) {
+            self.truncated = true;
+            return false;
+        }
+        self.edges.insert(edge);
+        true
+    }
+}
+
+/// Finds the traits and types related to one, through type hierarchy
+/// requests when the server answers them, else through implementations,
+/// definitions and the source of trait and `impl` headers.
+struct TypeWalker<'a> {
+    client: &'a mut RustAnalyzerClient,
+    files: HashMap<String, Option<Arc<str>>>,
+}
+
+impl TypeWalker<'_> {
+    async fn text(&mut self, uri: &str) -> Option<Arc<str>> {
+        if !self.files.contains_key(uri) {
+            let content = tokio::fs::read_to_string(uri_path(uri)).await.ok();
+            self.files.insert(uri.to_string(), content.map(Arc::from));
+        }
+        self.files[uri].clone()
+    }
+
+    /// `(uri, line, character)` of an LSP location or location link, with
+    /// the character in characters.
+    async fn target(&mut self, location: &Value) -> Option<(String, u32, u32)> {
+        let uri = location["targetUri"]
+            .as_str()
+            .or(location["uri"].as_str())?;
+        let range = match &location["targetSelectionRange"] {
+            Value::Null => &location["range"],
+            range => range,
+        };
+        let line = range["start"]["line"].as_u64()?;
+        let character = range["start"]["character"].as_u64()?;
+        let character = self
+            .client
+            .document_columns([uri])
+            .await
+            .to_user(uri, line, character);
+        Some((uri.to_string(), line as u32, character as u32))
+    }
+
+    /// Where the name at a position is defined. Requests that fail, other
+    /// than by timing out, count as no definition.
+    async fn definition(
+        &mut self,
+        uri: &str,
+        line: u32,
+        character: u32,
+    ) -> Result<Option<(String, u32, u32)>> {
+        let definition = match self.client.definition(uri, line, character).await {
+            Ok(definition) => definition,
+            Err(e) if e.is::<RequestTimeout>() => return Err(e),
+            Err(e) => {
+                debug!(
+                    "type_graph: no definition at {}:{}:{}: {}",
+                    uri, line, character, e
+                );
+                return Ok(None);
+            }
+        };
+        let location = match &definition {
+            Value::Array(locations) => locations.first().cloned().unwrap_or_default(),
+            location => location.clone(),
+        };
+        Ok(self.target(&location).await)
+    }
+
+    /// The trait or type whose name is at a position, from its declaration.
+    async fn declared_at(&mut self, uri: &str, line: u32, character: u32) -> Option<Node> {
+        let text = self.text(uri).await?;
+        let code = text.lines().nth(line as usize)?;
+        let name: String = code
+            .chars()
+            .skip(character as usize)
+            .take_while(|c| is_ident(*c))
+            .collect();
+        let before: String = code.chars().take(character as usize).collect();
+        let kind = before.split_whitespace().last()?;
+        if name.is_empty() || !TYPE_KEYWORDS.contains(&kind) {
+            return None;
+        }
+        Some(Node {
+            name,
+            kind: kind.to_string(),
+            uri: uri.to_string(),
+            line,
+            character,
+            item: Value::Null,
+        })
+    }
+
+    /// The trait or type the name at a position refers to.
+    async fn resolve(&mut self, uri: &str, line: u32, character: u32) -> Result<Option<Node>> {
+        Ok(match self.definition(uri, line, character).await? {
+            Some((uri, line, character)) => self.declared_at(&uri, line, character).await,
+            None => None,
+        })
+    }
+
+    /// The node of a type hierarchy item.
+    async fn item_node(&mut self, item: &Value) -> Option<Node> {
+        let (uri, line, character) = self
+            .target(&json!({ "uri": item["uri"], "range": item["selectionRange"] }))
+            .await?;
+        let kind = match item["kind"].as_u64()? {
+            11 => "trait",
+            kind => symbol_kind_name(kind),
+        };
+        Some(Node {
+            name: item["name"].as_str()?.to_string(),
+            kind: kind.to_string(),
+            uri,
+            line,
+            character,
+            item: item.clone(),
+        })
+    }
+
+    /// The impl blocks of a trait or type, as positions.
+    async fn implementations(&mut self, node: &Node) -> Result<Vec<(String, u32, u32)>> {
+        let found = self
+            .client
+            .implementation(&node.uri, node.line, node.character)
+            .await;
+        let locations = match found {
+            Ok(locations) => locations,
+            Err(e) if e.is::<RequestTimeout>() => return Err(e),
+            Err(e) => {
+                debug!("type_graph: no implementations of {}: {}", node.name, e);
+                return Ok(Vec::new());
+            }
+        };
+        let mut targets = Vec::new();
+        for location in locations.as_array().into_iter().flatten() {
+            targets.extend(self.target(location).await);
+        }
+        Ok(targets)
+    }
+
+    /// The traits and types one relation away from `node` in `direction`.
+    async fn neighbors(&mut self, node: &Node, direction: Direction) -> Result<Vec<Node>> {
+        if !node.item.is_null() {
+            let answer = match direction {
+                Direction::Supertypes => self.client.supertypes(node.item.clone()).await,
+                Direction::Subtypes => self.client.subtypes(node.item.clone()).await,
+            };
+            let items = match answer {
+                Ok(items) => items,
+                Err(e) if e.is::<RequestTimeout>() => return Err(e),
+                Err(e) => {
+                    debug!("type_graph: no {:?} of {}: {}", direction, node.name, e);
+                    Value::Null
+                }
+            };
+            let mut nodes = Vec::new();
+            for item in items.as_array().into_iter().flatten() {
+                nodes.extend(self.item_node(item).await);
+            }
+            return Ok(nodes);
+        }
+
+        let trait_node = node.kind == "trait";
+        let names: Vec<(String, u32, u32)> = match direction {
+            Direction::Supertypes if trait_node => {
+                let Some(text) = self.text(&node.uri).await else {
+                    return Ok(Vec::new());
+                };
+                supertrait_bounds(&text, node.line, node.character)
+                    .into_iter()
+                    .map(|(line, character)| (node.uri.clone(), line, character))
+                    .collect()
+            }
+            Direction::Subtypes if !trait_node => return Ok(Vec::new()),
+            _ => {
+                let mut names = Vec::new();
+                for (uri, line, character) in self.implementations(node).await? {
+                    let Some(text) = self.text(&uri).await else {
+                        continue;
+                    };
+                    let Some(header) = impl_header(&text, line, character) else {
+                        continue;
+                    };
+                    let name = match direction {
+                        Direction::Supertypes => header.trait_name,
+                        Direction::Subtypes => header.self_type,
+                    };
+                    names.extend(name.map(|(line, character)| (uri.clone(), line, character)));
+                }
+                names
+            }
+        };
+
+        let mut nodes = Vec::new();
+        for (uri, line, character) in names {
+            nodes.extend(self.resolve(&uri, line, character).await?);
+        }
+        Ok(nodes)
+    }
+}
+
+/// Walk relations from `root` breadth first, `depth` relations deep in each
+/// of `directions`. Each trait or type is expanded once per direction, so
+/// cycles end the walk instead of repeating it.
+async fn walk(
+    walker: &mut TypeWalker<'_>,
+    root: Node,
+    directions: &[Direction],
+    depth: usize,
+) -> Result<TypeGraph> {
+    let mut graph = TypeGraph::default();
+    let Some(root_id) = graph.node(root) else {
+        return Ok(graph);
+    };
+    let mut queue: VecDeque<(usize, usize, Direction)> = directions
+        .iter()
+        .map(|direction| (root_id, 0, *direction))
+        .collect();
+    let mut expanded = HashSet::new();
+
+    'walk: while let Some((id, distance, direction)) = queue.pop_front() {
+        if distance >= depth || !expanded.insert((id, direction)) {
+            continue;
+        }
+        let neighbors = walker.neighbors(&graph.nodes[id], direction).await?;
+        for other in neighbors {
+            let Some(other_id) = graph.node(other) else {
+                continue;
+            };
+            let (sub, sup) = match direction {
+                Direction::Supertypes => (id, other_id),
+                Direction::Subtypes => (other_id, id),
+            };
+            let relation = match graph.nodes[sub].kind.as_str() {
+                "trait" => SUPERTRAIT,
+                _ => IMPLEMENTS,
+            };
+            if !graph.edge((sub, sup, relation)) {
+                break 'walk;
+            }
+            queue.push_back((other_id, distance + 1, direction));
+        }
+    }
+    Ok(graph)
+}
+
+/// The graph as JSON: the traits and types, and the relations between them.
+fn to_json(graph: &TypeGraph, places: &GraphPlaces, notice: Option<&str>) -> Value {
+    let nodes: Vec<Value> = graph
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(id, node)| {
+            let mut value = json!({
+                "id": format!("n{}", id),
+                "name": node.name,
+                "kind": node.kind,
+                "location": places.location(&node.uri, node.line as u64)
+            });
+            if let Some(package) = places.package(&node.uri) {
+                value["crate"] = json!(package);
+            }
+            value
+        })
+        .collect();
+    let edges: Vec<Value> = graph
+        .edges
+        .iter()
+        .map(|(sub, sup, relation)| {
+            json!({
+                "from": format!("n{}", sub),
+                "to": format!("n{}", sup),
+                "relation": relation
+            })
+        })
+        .collect();
+    let mut result = json!({ "root": "n0", "nodes": nodes, "edges": edges });
+    if let Some(notice) = notice {
+        result["truncated"] = json!(notice);
+    }
+    result
+}
+
+/// `rust_analyzer_type_graph`: the traits a type or trait at a position
+/// implements or requires, and the types implementing it, `depth` relations
+/// deep, as GraphViz DOT or JSON.
+pub(crate) async fn handle_type_graph(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let (line, character) = ToolParams::extract_position(&args)?;
+    let directions: &[Direction] = match args["direction"].as_str() {
+        None | Some("both") => &[Direction::Supertypes, Direction::Subtypes],
+        Some("supertypes") => &[Direction::Supertypes],
+        Some("subtypes") => &[Direction::Subtypes],
+        Some(other) => {
+            return Err(tool_error!(
+                InvalidParams,
+                "Unknown direction '{}'; expected supertypes, subtypes or both",
+                other
+            ))
+        }
+    };
+    let dot = dot_format_arg(&args)?;
+    let depth = depth_arg(&args, TYPE_GRAPH_DEPTH)?;
+
+    let uri = server
+        .open_document_if_needed(&file_path, args["content"].as_str())
+        .await?;
+    let places = GraphPlaces::of(server, "type_graph").await;
+
+    let Some(client) = &mut server.client else {
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
+    };
+    let mut walker = TypeWalker {
+        client,
+        files: HashMap::new(),
+    };
+
+    // Retry logic: wait for indexing to complete
+    let retry_interval = server.config.retry_interval();
+    let indexing_timeout = server.config.indexing_timeout();
+    let start = Instant::now();
+    let mut logged_waiting = false;
+
+    let root = loop {
+        let items = match walker
+            .client
+            .prepare_type_hierarchy(&uri, line, character)
+            .await
+        {
+            Ok(items) => items,
+            Err(e) if e.is::<RequestTimeout>() => return Err(e),
+            Err(_) => Value::Null,
+        };
+        let root = match items.as_array().and_then(|items| items.first()) {
+            Some(item) => walker.item_node(item).await,
+            None => match walker.definition(&uri, line, character).await? {
+                Some((target, target_line, target_character)) => {
+                    let declared = walker
+                        .declared_at(&target, target_line, target_character)
+                        .await;
+                    if declared.is_none() {
+                        return Err(tool_error!(
+                            InvalidParams,
+                            "No trait or type at {}:{}",
+                            line,
+                            character
+                        ));
+                    }
+                    declared
+                }
+                None => None,
+            },
+        };
+        if let Some(root) = root {
+            if logged_waiting {
+                info!("type_graph: Indexing complete, returning results");
+            }
+            break root;
+        }
+        check_retry_timeout(
+            "type_graph",
+            &start,
+            indexing_timeout,
+            &mut logged_waiting,
+            walker.client,
+        )?;
+        tokio::time::sleep(retry_interval).await;
+    };
+
+    let graph = walk(&mut walker, root, directions, depth).await?;
+    let notice = graph.truncated.then(|| {
+        format!(
+            "Truncated at {} types or {} relations; lower depth or pick one direction for a complete graph",
+            TYPE_GRAPH_MAX_NODES, TYPE_GRAPH_MAX_EDGES
+        )
+    });
+
+    let text = match dot {
+        true => {
+            let labels: Vec<String> = graph
+                .nodes
+                .iter()
+                .map(|node| {
+                    let location = places.location(&node.uri, node.line as u64);
+                    format!("{} {}\n{}", node.kind, node.name, location)
+                })
+                .collect();
+            let packages: Vec<Option<String>> = graph
+                .nodes
+                .iter()
+                .map(|node| places.package(&node.uri))
+                .collect();
+            let edges: Vec<String> = graph
+                .edges
+                .iter()
+                .map(|(sub, sup, relation)| {
+                    format!("n{} -> n{} [label=\"{}\"]", sub, sup, relation)
+                })
+                .collect();
+            dot_graph("types", &labels, &packages, &edges, notice.as_deref())
+        }
+        false => serde_json::to_string_pretty(&to_json(&graph, &places, notice.as_deref()))?,
+    };
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text,
+        }],
+    })
+}
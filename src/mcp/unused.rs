@@ -0,0 +1,311 @@
+use anyhow::Result;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    lsp::{
+        paths::uri_path,
+        position::{uris_in, DocumentColumns},
+    },
+    protocol::{
+        mcp::{ContentItem, ToolResult},
+        tool_error,
+    },
+};
+
+use super::server::RustAnalyzerMCPServer;
+
+/// Lint codes that mean some code can be removed; clippy's `unused_*`
+/// lints are matched by prefix.
+const UNUSED_CODES: &[&str] = &["dead_code", "unused_variables", "unused_imports"];
+const CLIPPY_UNUSED_PREFIX: &str = "clippy::unused_";
+
+fn is_unused_code(code: &str) -> bool {
+    UNUSED_CODES.contains(&code) || code.starts_with(CLIPPY_UNUSED_PREFIX)
+}
+
+/// One thing to remove: the diagnostics one message raised in one file,
+/// which may point at several spans (`fields `a` and `b` are never read`).
+struct UnusedItem {
+    uri: String,
+    code: String,
+    message: String,
+    /// LSP start positions of every diagnostic it clears, first the primary.
+    positions: Vec<Value>,
+    confidence: &'static str,
+    used_in_tests: usize,
+    used_at: Vec<String>,
+}
+
+impl UnusedItem {
+    fn new(uri: &str, code: &str, message: &str, position: Value) -> Self {
+        UnusedItem {
+            uri: uri.to_string(),
+            code: code.to_string(),
+            message: message.to_string(),
+            positions: vec![position],
+            // The compiler is sure of the others; `dead_code` misses uses
+            // from other targets and macros until references confirm it.
+            confidence: if code == "dead_code" { "low" } else { "high" },
+            used_in_tests: 0,
+            used_at: Vec::new(),
+        }
+    }
+
+    /// The names the message quotes, e.g. `a, b` for "fields `a` and `b`
+    /// are never read"; the message itself when it quotes none.
+    fn name(&self) -> String {
+        let quoted: Vec<&str> = self.message.split('`').skip(1).step_by(2).collect();
+        match quoted.as_slice() {
+            [] => self.message.clone(),
+            names => names.join(", "),
+        }
+    }
+}
+
+/// The unused-code diagnostics in a `{ uri: [diagnostic] }` map, one item
+/// per message per file. Hints a lint attaches to another span count
+/// toward the item their related information points at.
+fn unused_items(by_uri: &Value) -> Vec<UnusedItem> {
+    let mut items: Vec<UnusedItem> = Vec::new();
+    let mut hints = Vec::new();
+    for (uri, diagnostics) in by_uri.as_object().into_iter().flatten() {
+        for diagnostic in diagnostics.as_array().into_iter().flatten() {
+            let code = match &diagnostic["code"] {
+                Value::String(code) => code.as_str(),
+                _ => continue,
+            };
+            if !is_unused_code(code) {
+                continue;
+            }
+            if diagnostic["severity"].as_u64() == Some(4) {
+                hints.push((uri.as_str(), diagnostic));
+                continue;
+            }
+            let message = diagnostic["message"].as_str().unwrap_or_default();
+            let position = diagnostic["range"]["start"].clone();
+            match items
+                .iter_mut()
+                .find(|item| item.uri == *uri && item.code == code && item.message == message)
+            {
+                Some(item) => item.positions.push(position),
+                None => items.push(UnusedItem::new(uri, code, message, position)),
+            }
+        }
+    }
+
+    for (uri, hint) in hints {
+        let mut related = hint["relatedInformation"].as_array().into_iter().flatten();
+        let owner = related.find_map(|info| {
+            let related_uri = info["location"]["uri"].as_str()?;
+            let start = &info["location"]["range"]["start"];
+            items.iter().position(|item| {
+                item.uri == related_uri && item.positions.iter().any(|p| p == start)
+            })
+        });
+        match owner {
+            Some(index) => items[index].positions.push(hint["range"]["start"].clone()),
+            None => items.push(UnusedItem::new(
+                uri,
+                hint["code"].as_str().unwrap_or_default(),
+                hint["message"].as_str().unwrap_or_default(),
+                hint["range"]["start"].clone(),
+            )),
+        }
+    }
+    items
+}
+
+/// Whether a reference is in test code: a file under `tests/` or
+/// `benches/`, a `tests.rs` or `*_test(s).rs`, or past a `#[cfg(test)]`
+/// line, where the usual trailing test module starts.
+fn is_test_reference(path: &str, line: u64, sources: &mut HashMap<String, String>) -> bool {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    if path.contains("/tests/")
+        || path.contains("/benches/")
+        || file_name == "tests.rs"
+        || file_name.ends_with("_test.rs")
+        || file_name.ends_with("_tests.rs")
+    {
+        return true;
+    }
+    let source = sources
+        .entry(path.to_string())
+        .or_insert_with(|| std::fs::read_to_string(path).unwrap_or_default());
+    source
+        .lines()
+        .position(|text| text.trim_start().starts_with("#[cfg(test)]"))
+        .is_some_and(|cfg_test| line as usize > cfg_test)
+}
+
+/// `path:line:column` of `position` in `uri`.
+fn location(columns: &DocumentColumns, uri: &str, position: &Value) -> String {
+    let line = position["line"].as_u64().unwrap_or(0);
+    let character = columns.to_user(uri, line, position["character"].as_u64().unwrap_or(0));
+    format!("{}:{}:{}", uri_path(uri).display(), line, character)
+}
+
+/// `rust_analyzer_unused`: the workspace's unused-code diagnostics as a
+/// cleanup list, most diagnostics cleared first. With `min_confidence`
+/// `high`, `dead_code` items are checked with a references request and
+/// listed only when nothing but tests uses them; the others are reported
+/// under `used_elsewhere`.
+pub(crate) async fn handle_unused(
+    server: &mut RustAnalyzerMCPServer,
+    args: &Value,
+) -> Result<ToolResult> {
+    let confirm = match args["min_confidence"].as_str() {
+        None | Some("low") => false,
+        Some("high") => true,
+        Some(other) => {
+            return Err(tool_error!(
+                InvalidParams,
+                "Unknown min_confidence '{}'; expected low or high",
+                other
+            ))
+        }
+    };
+
+    let Some(client) = &mut server.client else {
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
+    };
+    let diagnostics = client.workspace_diagnostics().await?;
+    let mut items = unused_items(&diagnostics);
+
+    if confirm {
+        // Awaited one at a time, so a long list doesn't flood rust-analyzer.
+        let mut requests = Vec::new();
+        for (index, item) in items.iter().enumerate() {
+            if item.code != "dead_code" {
+                continue;
+            }
+            let params = json!({
+                "textDocument": { "uri": item.uri },
+                "position": item.positions[0],
+                "context": { "includeDeclaration": false }
+            });
+            requests.push((
+                index,
+                client.request_detached("textDocument/references", Some(params))?,
+            ));
+        }
+        let mut sources = HashMap::new();
+        for (index, request) in requests {
+            let Ok(references) = request.await else {
+                continue;
+            };
+            let item = &mut items[index];
+            let mut used_at = Vec::new();
+            for reference in references.as_array().into_iter().flatten() {
+                let Some(uri) = reference["uri"].as_str() else {
+                    continue;
+                };
+                let start = &reference["range"]["start"];
+                let path = uri_path(uri);
+                let line = start["line"].as_u64().unwrap_or(0);
+                if is_test_reference(&path.to_string_lossy(), line, &mut sources) {
+                    item.used_in_tests += 1;
+                } else {
+                    used_at.push((uri.to_string(), start.clone()));
+                }
+            }
+            if used_at.is_empty() {
+                item.confidence = "high";
+            }
+            let columns = client.document_columns(uris_in(&references)).await;
+            item.used_at = used_at
+                .iter()
+                .map(|(uri, start)| location(&columns, uri, start))
+                .collect();
+        }
+    }
+
+    // Confirmed items are `high`; the rest are used outside tests.
+    let (mut items, used_elsewhere): (Vec<UnusedItem>, Vec<UnusedItem>) = items
+        .into_iter()
+        .partition(|item| !confirm || item.confidence == "high");
+    items.sort_by(|a, b| {
+        b.positions
+            .len()
+            .cmp(&a.positions.len())
+            .then_with(|| a.uri.cmp(&b.uri))
+            .then_with(|| {
+                let line = |item: &UnusedItem| item.positions[0]["line"].as_u64();
+                line(a).cmp(&line(b))
+            })
+    });
+
+    let columns = client.document_columns(uris_in(&diagnostics)).await;
+    let mut by_code: BTreeMap<&str, usize> = BTreeMap::new();
+    // Items and diagnostics per file.
+    let mut by_file: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    let mut listed = Vec::new();
+    for item in &items {
+        *by_code.entry(&item.code).or_default() += item.positions.len();
+        let file = by_file
+            .entry(uri_path(&item.uri).display().to_string())
+            .or_default();
+        file.0 += 1;
+        file.1 += item.positions.len();
+
+        let mut entry = json!({
+            "item": item.name(),
+            "code": item.code,
+            "location": location(&columns, &item.uri, &item.positions[0]),
+            "clears": item.positions.len(),
+            "confidence": item.confidence,
+            "message": item.message
+        });
+        if item.used_in_tests > 0 {
+            entry["used_in_tests"] = json!(item.used_in_tests);
+        }
+        listed.push(entry);
+    }
+    let used_elsewhere: Vec<Value> = used_elsewhere
+        .iter()
+        .map(|item| {
+            json!({
+                "item": item.name(),
+                "location": location(&columns, &item.uri, &item.positions[0]),
+                "used_at": item.used_at
+            })
+        })
+        .collect();
+
+    let mut files: Vec<(String, usize, usize)> = by_file
+        .into_iter()
+        .map(|(file, (items, clears))| (file, items, clears))
+        .collect();
+    files.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+    let files: Vec<Value> = files
+        .into_iter()
+        .map(|(file, items, clears)| json!({ "file": file, "items": items, "clears": clears }))
+        .collect();
+    let by_code: Map<String, Value> = by_code
+        .into_iter()
+        .map(|(code, count)| (code.to_string(), json!(count)))
+        .collect();
+
+    let mut result = json!({
+        "workspace": server.workspace_root.display().to_string(),
+        "confirmed": confirm,
+        "summary": {
+            "items": listed.len(),
+            "diagnostics": by_code.values().filter_map(Value::as_u64).sum::<u64>(),
+            "files": files.len(),
+            "by_code": by_code
+        },
+        "files": files,
+        "items": listed
+    });
+    if !used_elsewhere.is_empty() {
+        result["used_elsewhere"] = json!(used_elsewhere);
+    }
+
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text: serde_json::to_string_pretty(&result)?,
+        }],
+    })
+}
@@ -0,0 +1,148 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use tokio::task::JoinSet;
+
+use crate::{
+    lsp::{paths::uri_path, symbol_kind_name},
+    protocol::{
+        mcp::{ContentItem, ToolResult},
+        tool_error,
+    },
+};
+
+use super::{handlers::ToolParams, module_tree::outline_file, server::RustAnalyzerMCPServer};
+
+/// Most items `rust_analyzer_usage_stats` counts references for in one call.
+const USAGE_STATS_MAX_SYMBOLS: usize = 100;
+
+/// References requests in flight at once.
+const USAGE_STATS_CONCURRENCY: usize = 8;
+
+/// `rust_analyzer_usage_stats`: how much each top-level `pub` item of a
+/// file is used, least used first, to find what could be private or go.
+pub(crate) async fn handle_usage_stats(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let max_symbols = match args.get("max_symbols") {
+        None | Some(Value::Null) => USAGE_STATS_MAX_SYMBOLS,
+        Some(value) => value
+            .as_u64()
+            .ok_or_else(|| tool_error!(InvalidParams, "max_symbols must be a whole number"))?
+            .min(USAGE_STATS_MAX_SYMBOLS as u64) as usize,
+    };
+    let wanted: Option<Vec<&str>> = match &args["symbols"] {
+        Value::Null => None,
+        Value::Array(names) => Some(names.iter().filter_map(Value::as_str).collect()),
+        _ => {
+            return Err(tool_error!(
+                InvalidParams,
+                "symbols must be a list of names"
+            ))
+        }
+    };
+
+    let uri = server
+        .open_document_if_needed(&file_path, args["content"].as_str())
+        .await?;
+    let content = match args["content"].as_str() {
+        Some(content) => content.to_string(),
+        None => {
+            let path = uri_path(&uri);
+            tokio::fs::read_to_string(&path)
+                .await
+                .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?
+        }
+    };
+
+    let Some(client) = &mut server.client else {
+        return Err(tool_error!(BackendUnavailable, "Client not initialized"));
+    };
+    let symbols = client.document_symbols(&uri).await?;
+    let outline = outline_file(&content, &symbols, client.position_encoding());
+    let mut exports: Vec<_> = outline
+        .exports
+        .into_iter()
+        .filter(|export| {
+            wanted
+                .as_ref()
+                .is_none_or(|names| names.contains(&export.name.as_str()))
+        })
+        .collect();
+    let public_items = exports.len();
+    let truncated = exports.len() > max_symbols;
+    exports.truncate(max_symbols);
+
+    // A window of requests: another is sent as each one answers.
+    let columns = client.document_columns([&uri]).await;
+    let mut pending = exports.iter().enumerate();
+    let mut in_flight = JoinSet::new();
+    let mut references: Vec<Result<Value>> = exports.iter().map(|_| Ok(Value::Null)).collect();
+    loop {
+        while in_flight.len() < USAGE_STATS_CONCURRENCY {
+            let Some((index, export)) = pending.next() else {
+                break;
+            };
+            let character = columns.to_lsp(&uri, export.line, export.character);
+            let params = json!({
+                "textDocument": { "uri": uri },
+                "position": { "line": export.line, "character": character },
+                "context": { "includeDeclaration": false }
+            });
+            let request = client.request_detached("textDocument/references", Some(params))?;
+            in_flight.spawn(async move { (index, request.await) });
+        }
+        let Some(answered) = in_flight.join_next().await else {
+            break;
+        };
+        let (index, result) = answered?;
+        references[index] = result;
+    }
+
+    let path = uri_path(&uri);
+    let mut rows: Vec<Value> = exports
+        .iter()
+        .zip(references)
+        .map(|(export, result)| {
+            let mut row = json!({
+                "symbol": export.name,
+                "kind": symbol_kind_name(export.kind),
+                "location": format!("{}:{}:{}", path.display(), export.line, export.character),
+            });
+            match result {
+                Ok(references) => {
+                    let files: BTreeSet<&str> = references
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|reference| reference["uri"].as_str())
+                        .collect();
+                    row["references"] = json!(references.as_array().map_or(0, Vec::len));
+                    row["files"] = json!(files.len());
+                    row["local"] = json!(files.iter().all(|file| *file == uri));
+                }
+                Err(e) => row["error"] = json!(e.to_string()),
+            }
+            row
+        })
+        .collect();
+    // Failed rows last; they say nothing about usage.
+    rows.sort_by_key(|row| row["references"].as_u64().unwrap_or(u64::MAX));
+
+    let result = json!({
+        "file": path,
+        "public_items": public_items,
+        "analyzed": rows.len(),
+        "truncated": truncated,
+        "symbols": rows
+    });
+
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text: serde_json::to_string_pretty(&result)?,
+        }],
+    })
+}
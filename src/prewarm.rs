@@ -0,0 +1,176 @@
+use anyhow::{anyhow, Result};
+use log::{debug, info, warn};
+use serde::Serialize;
+use serde_json::json;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    config::PREWARM_QUIET_MILLIS,
+    lsp::{paths::file_uri, SharedProgress},
+    mcp::cargo_metadata::{cargo_metadata, member_dirs, SharedMetadataCache},
+    RustAnalyzerMCPServer,
+};
+
+/// Any query will do; it makes rust-analyzer build the workspace symbol index.
+const PREWARM_SYMBOL_QUERY: &str = "main";
+
+/// Prewarm activity, reported by `/api/v1/status`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PrewarmStatus {
+    pub running: bool,
+    /// Prewarms finished since the server started.
+    pub runs: u64,
+    /// Hot files warmed by the last run.
+    pub files: Vec<PathBuf>,
+    pub last_duration_ms: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+pub type SharedPrewarmStatus = Arc<std::sync::Mutex<PrewarmStatus>>;
+
+/// Each time rust-analyzer finishes indexing (cargo check doesn't count),
+/// search workspace symbols and
+/// open the hot files and ask for their symbols, so the first tool calls
+/// afterwards find its caches warm.
+pub fn spawn(server: Arc<Mutex<RustAnalyzerMCPServer>>) -> SharedPrewarmStatus {
+    let status: SharedPrewarmStatus = Arc::default();
+    tokio::spawn(run(server, Arc::clone(&status)));
+    status
+}
+
+async fn run(server: Arc<Mutex<RustAnalyzerMCPServer>>, status: SharedPrewarmStatus) {
+    let poll_interval = server.lock().await.config.retry_interval();
+    let quiet_period = Duration::from_millis(PREWARM_QUIET_MILLIS);
+
+    // The client being watched, and how many of its indexing tasks had begun
+    // when it was last warmed.
+    let mut watched: Option<SharedProgress> = None;
+    let mut warmed_at = 0;
+    let mut quiet_since: Option<Instant> = None;
+    loop {
+        tokio::time::sleep(poll_interval).await;
+
+        let Some(progress) = server.lock().await.progress_handle() else {
+            watched = None;
+            continue;
+        };
+        if !watched.as_ref().is_some_and(|w| Arc::ptr_eq(w, &progress)) {
+            watched = Some(Arc::clone(&progress));
+            warmed_at = 0;
+        }
+
+        let (indexing, begun) = {
+            let progress = progress.lock().await;
+            (progress.is_indexing_workspace(), progress.indexing_begun())
+        };
+        if indexing || begun == warmed_at {
+            quiet_since = None;
+            continue;
+        }
+        if quiet_since.get_or_insert_with(Instant::now).elapsed() < quiet_period {
+            continue;
+        }
+
+        quiet_since = None;
+        warmed_at = begun;
+        prewarm(&server, &status).await;
+    }
+}
+
+/// Warm rust-analyzer once, holding the server lock only to send requests,
+/// not while waiting for their results.
+pub async fn prewarm(server: &Mutex<RustAnalyzerMCPServer>, status: &SharedPrewarmStatus) {
+    let start = Instant::now();
+    status.lock().unwrap().running = true;
+    info!("Prewarming rust-analyzer");
+
+    let (workspace, configured, metadata_cache) = {
+        let server = server.lock().await;
+        (
+            server.workspace_root.clone(),
+            server.config.prewarm_files.clone(),
+            Arc::clone(&server.metadata_cache),
+        )
+    };
+    let files = hot_files(&workspace, &configured, &metadata_cache).await;
+    let result = warm(server, &files).await;
+
+    let mut status = status.lock().unwrap();
+    status.running = false;
+    status.runs += 1;
+    status.files = files;
+    status.last_duration_ms = Some(start.elapsed().as_millis() as u64);
+    status.last_error = result.err().map(|e| {
+        warn!("Prewarm failed: {}", e);
+        e.to_string()
+    });
+    info!("Prewarm finished in {}ms", start.elapsed().as_millis());
+}
+
+async fn warm(server: &Mutex<RustAnalyzerMCPServer>, files: &[PathBuf]) -> Result<()> {
+    let mut contents = Vec::new();
+    for path in files {
+        match tokio::fs::read_to_string(path).await {
+            Ok(content) => contents.push((file_uri(path), content)),
+            Err(e) => debug!("Not prewarming {}: {}", path.display(), e),
+        }
+    }
+
+    let requests = {
+        let mut server = server.lock().await;
+        let Some(client) = &mut server.client else {
+            return Err(anyhow!("rust-analyzer is not running"));
+        };
+        let mut requests = vec![client.request_detached(
+            "workspace/symbol",
+            Some(json!({ "query": PREWARM_SYMBOL_QUERY })),
+        )?];
+        for (uri, content) in &contents {
+            client.send_document(uri, content).await?;
+            requests.push(client.request_detached(
+                "textDocument/documentSymbol",
+                Some(json!({ "textDocument": { "uri": uri } })),
+            )?);
+        }
+        requests
+    };
+
+    for request in requests {
+        request.await?;
+    }
+    Ok(())
+}
+
+/// `configured` resolved against `workspace`, or when empty, the
+/// `src/lib.rs` and `src/main.rs` of each member `cargo metadata` lists (of
+/// `workspace` itself if that fails). Only files that exist are returned.
+pub async fn hot_files(
+    workspace: &Path,
+    configured: &[PathBuf],
+    metadata_cache: &SharedMetadataCache,
+) -> Vec<PathBuf> {
+    let candidates: Vec<PathBuf> = if !configured.is_empty() {
+        configured.iter().map(|file| workspace.join(file)).collect()
+    } else {
+        let members = match cargo_metadata(workspace, metadata_cache).await {
+            Ok((metadata, _)) => member_dirs(&metadata),
+            Err(e) => {
+                debug!("cargo metadata failed, prewarming the root crate: {}", e);
+                vec![workspace.to_path_buf()]
+            }
+        };
+        members
+            .iter()
+            .flat_map(|member| [member.join("src/lib.rs"), member.join("src/main.rs")])
+            .collect()
+    };
+    candidates
+        .into_iter()
+        .filter(|file| file.is_file())
+        .collect()
+}
@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::lsp::RequestTimeout;
+
+/// What kind of failure a tool call hit, so clients can decide whether to
+/// retry without matching on messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// An argument is missing or malformed, or names nothing.
+    InvalidParams,
+    /// rust-analyzer was still indexing when the tool stopped waiting.
+    Indexing,
+    /// A file or directory the call needs doesn't exist.
+    FileNotFound,
+    /// rust-analyzer isn't running or couldn't be started.
+    BackendUnavailable,
+    /// An LSP request got no answer within its timeout.
+    Timeout,
+    /// No tool or alias has the name.
+    UnknownTool,
+    /// The caller isn't allowed to make the request.
+    Forbidden,
+    /// Anything else.
+    Internal,
+}
+
+/// Seconds a client is asked to wait before retrying when there's no
+/// indexing progress to go by.
+const RETRY_AFTER_DEFAULT_SECS: u64 = 5;
+
+impl ErrorCode {
+    /// Whether the same call may succeed if made again later.
+    pub fn retryable(self) -> bool {
+        matches!(
+            self,
+            ErrorCode::Indexing | ErrorCode::BackendUnavailable | ErrorCode::Timeout
+        )
+    }
+
+    /// The HTTP status the API answers with: 400 for bad arguments, 404 for
+    /// unknown tools and missing files, 503 while rust-analyzer is busy or
+    /// down, 504 for LSP timeouts.
+    pub fn http_status(self) -> u16 {
+        match self {
+            ErrorCode::InvalidParams => 400,
+            ErrorCode::Forbidden => 403,
+            ErrorCode::UnknownTool | ErrorCode::FileNotFound => 404,
+            ErrorCode::Indexing | ErrorCode::BackendUnavailable => 503,
+            ErrorCode::Timeout => 504,
+            ErrorCode::Internal => 500,
+        }
+    }
+}
+
+/// A guess at the seconds indexing has left: a second per tenth of it left
+/// when `percent` done is known.
+pub fn indexing_wait_secs(percent: Option<u64>) -> u64 {
+    match percent {
+        Some(percent) => (100u64.saturating_sub(percent) / 10).max(1),
+        None => RETRY_AFTER_DEFAULT_SECS,
+    }
+}
+
+/// A failed tool call, as API responses report it. Handlers return these
+/// inside `anyhow::Error` through [`tool_error!`]; errors without one are
+/// classified by [`ToolError::from_anyhow`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolError {
+    pub code: ErrorCode,
+    pub message: String,
+    pub details: Option<Value>,
+}
+
+impl ToolError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// A failed file read: `file_not_found` when the file is missing,
+    /// `internal` otherwise.
+    pub fn io(error: &std::io::Error, message: impl Into<String>) -> Self {
+        let code = match error.kind() {
+            std::io::ErrorKind::NotFound => ErrorCode::FileNotFound,
+            _ => ErrorCode::Internal,
+        };
+        ToolError::new(code, message)
+    }
+
+    /// The `ToolError` in `error`, even under added context; a
+    /// [`RequestTimeout`] is a `timeout`, and anything else `internal`. The
+    /// message is `error`'s own text, as it read before there were codes.
+    pub fn from_anyhow(error: &anyhow::Error) -> Self {
+        let message = error.to_string();
+        if let Some(tool_error) = error.downcast_ref::<ToolError>() {
+            return ToolError {
+                message,
+                ..tool_error.clone()
+            };
+        }
+        if let Some(timeout) = error.downcast_ref::<RequestTimeout>() {
+            return ToolError::new(ErrorCode::Timeout, message).with_details(json!({
+                "method": timeout.method,
+                "timeout_secs": timeout.timeout.as_secs_f64()
+            }));
+        }
+        ToolError::new(ErrorCode::Internal, message)
+    }
+
+    /// Seconds to wait before retrying a 503, by [`indexing_wait_secs`]
+    /// when the error says how far along indexing is.
+    pub fn retry_after_secs(&self) -> Option<u64> {
+        if self.code.http_status() != 503 {
+            return None;
+        }
+        let percent = self
+            .details
+            .as_ref()
+            .and_then(|details| details["percent"].as_u64());
+        Some(indexing_wait_secs(percent))
+    }
+
+    /// `{ code, message, retryable, details }`, leaving out empty details.
+    pub fn to_json(&self) -> Value {
+        let mut error = json!({
+            "code": self.code,
+            "message": self.message,
+            "retryable": self.code.retryable()
+        });
+        if let Some(details) = &self.details {
+            error["details"] = details.clone();
+        }
+        error
+    }
+}
+
+impl std::fmt::Display for ToolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ToolError {}
+
+/// `anyhow!` for a [`ToolError`] with the given [`ErrorCode`] variant:
+/// `tool_error!(InvalidParams, "Missing {}", name)`.
+macro_rules! tool_error {
+    ($code:ident, $($arg:tt)+) => {
+        anyhow::Error::from($crate::protocol::ToolError::new(
+            $crate::protocol::ErrorCode::$code,
+            format!($($arg)+),
+        ))
+    };
+}
+pub(crate) use tool_error;
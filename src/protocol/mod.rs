@@ -1,5 +1,8 @@
+pub mod error;
 pub mod lsp;
 pub mod mcp;
 
+pub(crate) use error::tool_error;
+pub use error::{indexing_wait_secs, ErrorCode, ToolError};
 pub use lsp::{LSPRequest, LSPResponse};
 pub use mcp::{ContentItem, ToolDefinition, ToolResult};
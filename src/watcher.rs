@@ -0,0 +1,230 @@
+use anyhow::Result;
+use log::{debug, info, warn};
+use notify::{event::ModifyKind, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::{mpsc, Mutex};
+
+use crate::{
+    lsp::{paths::file_uri, FileWatcher},
+    RustAnalyzerMCPServer,
+};
+
+/// Events arriving within this window of each other are sent as one batch.
+pub const WATCH_DEBOUNCE_MILLIS: u64 = 200;
+
+/// How often the watcher checks whether the workspace was switched.
+const WORKSPACE_CHECK_SECS: u64 = 2;
+
+/// Directories whose contents never matter to rust-analyzer.
+const IGNORED_DIRS: &[&str] = &["target", ".git"];
+
+/// LSP `FileChangeType` values.
+const FILE_CREATED: u8 = 1;
+const FILE_CHANGED: u8 = 2;
+const FILE_DELETED: u8 = 3;
+
+/// Watcher health, reported by `/api/v1/status`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WatcherStatus {
+    pub running: bool,
+    pub workspace: Option<PathBuf>,
+    /// File events forwarded to rust-analyzer.
+    pub events_processed: u64,
+    /// Open documents resent because their file changed.
+    pub documents_refreshed: u64,
+    pub last_error: Option<String>,
+}
+
+pub type SharedWatcherStatus = Arc<std::sync::Mutex<WatcherStatus>>;
+
+/// Whether `path` is outside `root` or in a directory that is never watched.
+fn is_ignored(root: &Path, path: &Path) -> bool {
+    let Ok(relative) = path.strip_prefix(root) else {
+        return true;
+    };
+    relative
+        .components()
+        .any(|c| IGNORED_DIRS.iter().any(|dir| c.as_os_str() == *dir))
+}
+
+/// Whether a change to `path` (inside `root`) should reach rust-analyzer,
+/// before it has registered any file watchers.
+pub fn is_relevant(root: &Path, path: &Path) -> bool {
+    if is_ignored(root, path) {
+        return false;
+    }
+    path.extension().is_some_and(|ext| ext == "rs")
+        || path.file_name().is_some_and(|name| name == "Cargo.toml")
+}
+
+/// Whether `change` to `path` should reach rust-analyzer: it must match one
+/// of the `watchers` rust-analyzer registered, or pass `is_relevant` while
+/// there are none.
+pub fn should_forward(root: &Path, path: &Path, change: u8, watchers: &[FileWatcher]) -> bool {
+    if watchers.is_empty() {
+        return is_relevant(root, path);
+    }
+    !is_ignored(root, path) && watchers.iter().any(|w| w.matches(path, change))
+}
+
+fn change_type(kind: &EventKind) -> Option<u8> {
+    match kind {
+        EventKind::Create(_) => Some(FILE_CREATED),
+        EventKind::Modify(ModifyKind::Metadata(_)) => None,
+        EventKind::Modify(_) => Some(FILE_CHANGED),
+        EventKind::Remove(_) => Some(FILE_DELETED),
+        _ => None,
+    }
+}
+
+/// Watch the server's workspace and keep rust-analyzer in sync with changes
+/// made on disk. Follows the server when its workspace is switched.
+pub fn spawn(server: Arc<Mutex<RustAnalyzerMCPServer>>) -> SharedWatcherStatus {
+    let status: SharedWatcherStatus = Arc::default();
+    tokio::spawn(run(server, Arc::clone(&status)));
+    status
+}
+
+async fn run(server: Arc<Mutex<RustAnalyzerMCPServer>>, status: SharedWatcherStatus) {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let _ = tx.send(res);
+    });
+    let mut watcher = match watcher {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            warn!("Failed to start file watcher: {}", e);
+            status.lock().unwrap().last_error = Some(e.to_string());
+            return;
+        }
+    };
+
+    let mut watched: Option<PathBuf> = None;
+    loop {
+        let root = server.lock().await.workspace_root.clone();
+        if watched.as_ref() != Some(&root) {
+            watch_workspace(&mut watcher, watched.as_deref(), &root, &status);
+            watched = Some(root.clone());
+        }
+
+        // Wait for the first event, then collect until the burst settles.
+        let first = tokio::time::timeout(Duration::from_secs(WORKSPACE_CHECK_SECS), rx.recv());
+        let mut events = match first.await {
+            Ok(Some(event)) => vec![event],
+            Ok(None) => break,
+            Err(_) => continue,
+        };
+        let debounce = Duration::from_millis(WATCH_DEBOUNCE_MILLIS);
+        while let Ok(Some(event)) = tokio::time::timeout(debounce, rx.recv()).await {
+            events.push(event);
+        }
+
+        // Last change type per path wins, except that a file created and
+        // then written in the same burst is still reported as created.
+        let watchers = server.lock().await.file_watchers().await;
+        let mut changes = BTreeMap::new();
+        for event in events {
+            match event {
+                Ok(event) => {
+                    let Some(change) = change_type(&event.kind) else {
+                        continue;
+                    };
+                    for path in event.paths {
+                        if !should_forward(&root, &path, change, &watchers) {
+                            continue;
+                        }
+                        let previous = changes.insert(path.clone(), change);
+                        if previous == Some(FILE_CREATED) && change == FILE_CHANGED {
+                            changes.insert(path, FILE_CREATED);
+                        }
+                    }
+                }
+                Err(e) => {
+                    warn!("File watcher error: {}", e);
+                    status.lock().unwrap().last_error = Some(e.to_string());
+                }
+            }
+        }
+        if changes.is_empty() {
+            continue;
+        }
+
+        if let Err(e) = forward_changes(&server, &changes, &status).await {
+            warn!("Failed to forward file changes: {}", e);
+            status.lock().unwrap().last_error = Some(e.to_string());
+        }
+    }
+
+    status.lock().unwrap().running = false;
+}
+
+fn watch_workspace(
+    watcher: &mut RecommendedWatcher,
+    previous: Option<&Path>,
+    workspace: &Path,
+    status: &SharedWatcherStatus,
+) {
+    if let Some(previous) = previous {
+        let _ = watcher.unwatch(previous);
+    }
+    let mut status = status.lock().unwrap();
+    match watcher.watch(workspace, RecursiveMode::Recursive) {
+        Ok(()) => {
+            info!("Watching {} for changes", workspace.display());
+            status.running = true;
+            status.workspace = Some(workspace.to_path_buf());
+        }
+        Err(e) => {
+            warn!("Failed to watch {}: {}", workspace.display(), e);
+            status.running = false;
+            status.last_error = Some(e.to_string());
+        }
+    }
+}
+
+async fn forward_changes(
+    server: &Mutex<RustAnalyzerMCPServer>,
+    changes: &BTreeMap<PathBuf, u8>,
+    status: &SharedWatcherStatus,
+) -> Result<()> {
+    let mut server = server.lock().await;
+    // Nothing to sync until rust-analyzer is started; it reads files on startup.
+    let Some(client) = &mut server.client else {
+        return Ok(());
+    };
+
+    let events: Vec<Value> = changes
+        .iter()
+        .map(|(path, change)| {
+            let uri = file_uri(path);
+            json!({ "uri": uri, "type": change })
+        })
+        .collect();
+    debug!("Forwarding {} file changes", events.len());
+    client.did_change_watched_files(events).await?;
+
+    let mut refreshed = 0;
+    for (path, change) in changes {
+        let uri = file_uri(path);
+        if *change == FILE_DELETED {
+            client.close_document(&uri).await?;
+        } else if let Ok(content) = tokio::fs::read_to_string(path).await {
+            if client.refresh_document(&uri, &content).await? {
+                refreshed += 1;
+            }
+        }
+    }
+
+    let mut status = status.lock().unwrap();
+    status.events_processed += changes.len() as u64;
+    status.documents_refreshed += refreshed;
+    status.last_error = None;
+    Ok(())
+}
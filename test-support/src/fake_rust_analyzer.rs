@@ -0,0 +1,60 @@
+use serde_json::{json, Value};
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+/// A rust-analyzer executable for tests that run the real client over stdio:
+/// a shell script that answers `--version`, waits for the client's first
+/// byte, writes `initialize`'s result and any scripted messages, and then
+/// records everything it is sent. Requests it isn't scripted to answer hang
+/// until they time out.
+pub struct FakeRustAnalyzer {
+    path: PathBuf,
+    log: PathBuf,
+}
+
+impl FakeRustAnalyzer {
+    /// Write the script into `dir`, to send `messages` after answering
+    /// `initialize`.
+    pub fn new(dir: &Path, messages: &[Value]) -> Self {
+        let path = dir.join("rust-analyzer");
+        let log = dir.join("rust-analyzer.log");
+        let initialized = json!({ "jsonrpc": "2.0", "id": 1, "result": { "capabilities": {} } });
+
+        // The byte read before answering goes to the log too, so it holds
+        // every message in full.
+        let mut script = format!(
+            "#!/bin/sh\n\
+             [ \"$1\" = --version ] && {{ echo 'rust-analyzer 0.0.0 (fake)'; exit 0; }}\n\
+             head -c 1 > '{}'\n",
+            log.display()
+        );
+        for message in std::iter::once(&initialized).chain(messages) {
+            let message = message.to_string();
+            script.push_str(&format!(
+                "printf 'Content-Length: {}\\r\\n\\r\\n%s' '{}'\n",
+                message.len(),
+                message
+            ));
+        }
+        script.push_str(&format!("exec cat >> '{}'\n", log.display()));
+
+        fs::write(&path, script).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        Self { path, log }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The messages the client has sent so far.
+    pub fn received(&self) -> Vec<Value> {
+        let log = fs::read_to_string(&self.log).unwrap_or_default();
+        log.split("Content-Length: ")
+            .filter_map(|message| serde_json::from_str(message.split_once("\r\n\r\n")?.1).ok())
+            .collect()
+    }
+}
@@ -3,9 +3,14 @@ use serde_json::{json, Value};
 use std::{
     path::{Path, PathBuf},
     process::Stdio,
-    time::Duration,
+    time::{Duration, Instant},
 };
-use tokio::process::Child;
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::Child,
+};
+
+use crate::timeouts;
 
 /// Client that connects to the HTTP MCP server
 pub struct IpcClient {
@@ -13,8 +18,8 @@ pub struct IpcClient {
     base_url: String,
     port: u16,
     workspace_path: PathBuf,
-    /// Keeps the server process alive; dropped when client is dropped.
-    _process: Option<Child>,
+    /// A server of this client's own, shut down when the client drops.
+    process: Option<Child>,
 }
 
 impl IpcClient {
@@ -35,67 +40,105 @@ impl IpcClient {
             _ => return Err(anyhow::anyhow!("Unknown project type: {}", project_type)),
         };
 
-        // Deterministic port based on project type
-        let port = deterministic_port(project_type);
-        let base_url = format!("http://127.0.0.1:{}", port);
-
         let http_client = reqwest::Client::builder()
             .timeout(Duration::from_secs(120))
             .build()?;
 
-        // Try to connect to existing server (with retries since another test may be starting it)
-        for attempt in 0..30 {
-            if let Ok(resp) = http_client
-                .get(format!("{}/api/v1/health", base_url))
-                .send()
-                .await
-            {
-                if resp.status().is_success() {
-                    if attempt > 0 {
-                        eprintln!("Connected to HTTP server for {} on port {} (attempt {})", project_type, port, attempt + 1);
-                    } else {
-                        eprintln!("Connected to existing HTTP server for {} on port {}", project_type, port);
-                    }
-                    return Ok(Self {
-                        http_client,
-                        base_url,
-                        port,
-                        workspace_path,
-                        _process: None,
-                    });
+        // Prefer a server advertised in the workspace's discovery file, then
+        // the one an earlier test started for this project type
+        let port_file = workspace_path.join(format!(".test-support-{}.json", project_type));
+        for file in [
+            workspace_path.join(".rust-analyzer-server.json"),
+            port_file.clone(),
+        ] {
+            if let Some(port) = discovered_port(&file) {
+                if is_healthy(&http_client, port).await {
+                    eprintln!(
+                        "Connected to discovered HTTP server for {} on port {}",
+                        project_type, port
+                    );
+                    return Ok(Self::connected(http_client, port, workspace_path));
                 }
             }
+        }
 
-            // Only try to start server on first attempt
-            if attempt == 0 {
-                // Check if the port is already in use (another test may be starting the server)
-                let port_in_use = std::net::TcpStream::connect_timeout(
-                    &format!("127.0.0.1:{}", port).parse().unwrap(),
-                    Duration::from_millis(50),
-                )
-                .is_ok();
-
-                if port_in_use {
-                    // Port is bound but health check failed — server is still starting up
-                    eprintln!("Port {} is in use, waiting for server to be ready...", port);
-                } else {
-                    // Port is free — start the server
-                    eprintln!("Starting new HTTP server for {} on port {}", project_type, port);
-                    if let Err(e) = start_server(&workspace_path, port) {
-                        eprintln!("Failed to start server: {}", e);
-                    }
-                }
-            }
+        let binary = server_binary()?;
+        let port = if supports_port_file(&binary) {
+            negotiate_port(&http_client, &binary, &workspace_path, &port_file).await?
+        } else {
+            // Older binaries can't report an OS-assigned port
+            eprintln!(
+                "{:?} has no --port-file; falling back to a hashed port",
+                binary
+            );
+            hashed_port(&http_client, &binary, &workspace_path, project_type).await?
+        };
+        eprintln!(
+            "Connected to HTTP server for {} on port {}",
+            project_type, port
+        );
+        Ok(Self::connected(http_client, port, workspace_path))
+    }
+
+    /// Start a dedicated server on `workspace_path`, isolated from other
+    /// tests. It is shut down, with its rust-analyzer, when the client drops.
+    pub async fn owned(workspace_path: &Path) -> Result<Self> {
+        Self::start(workspace_path, &[]).await
+    }
 
+    /// Start a server of this client's own on `workspace_path`, on a port the
+    /// OS picks, passing it extra `args`. It is shut down when the client
+    /// drops.
+    pub async fn start(workspace_path: &Path, args: &[String]) -> Result<Self> {
+        let binary = server_binary()?;
+        let mut process = tokio::process::Command::new(&binary)
+            .arg("--workspace")
+            .arg(workspace_path)
+            .args(["--port", "0", "--bind", "127.0.0.1"])
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        // The first line on stdout is the bound port.
+        let stdout = process.stdout.take().unwrap();
+        let mut line = String::new();
+        BufReader::new(stdout).read_line(&mut line).await?;
+        let port = line
+            .trim()
+            .parse()
+            .map_err(|_| anyhow::anyhow!("{:?} didn't report a port: {:?}", binary, line))?;
+
+        let http_client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(120))
+            .build()?;
+        for _ in 0..STARTUP_ATTEMPTS {
+            if is_healthy(&http_client, port).await {
+                let mut client = Self::connected(http_client, port, workspace_path.to_path_buf());
+                client.process = Some(process);
+                return Ok(client);
+            }
             tokio::time::sleep(Duration::from_millis(500)).await;
         }
-
         Err(anyhow::anyhow!(
-            "Failed to connect to HTTP server after 15 seconds (port {})",
+            "HTTP server for {:?} did not become healthy on port {}",
+            workspace_path,
             port
         ))
     }
 
+    fn connected(http_client: reqwest::Client, port: u16, workspace_path: PathBuf) -> Self {
+        Self {
+            http_client,
+            base_url: format!("http://127.0.0.1:{}", port),
+            port,
+            workspace_path,
+            process: None,
+        }
+    }
+
     /// Send a request to the server (backward compatible with MCP-style method names)
     pub async fn send_request(&mut self, method: &str, params: Option<Value>) -> Result<Value> {
         match method {
@@ -111,7 +154,7 @@ impl IpcClient {
                 } else {
                     Err(anyhow::anyhow!(
                         "Server error: {}",
-                        body["error"].as_str().unwrap_or("unknown")
+                        body["error"]["message"].as_str().unwrap_or("unknown")
                     ))
                 }
             }
@@ -141,44 +184,86 @@ impl IpcClient {
     /// Call a tool on the server.
     /// Returns a backward-compatible MCP ToolResult shape:
     /// `{"content": [{"type": "text", "text": "..."}]}`
+    ///
+    /// A 503 (still indexing, backend restarting) is retried after the
+    /// server's `Retry-After` for as long as initialization may take.
     pub async fn call_tool(&mut self, name: &str, arguments: Value) -> Result<Value> {
-        let resp = self
-            .http_client
-            .post(format!("{}/api/v1/{}", self.base_url, name))
-            .json(&arguments)
-            .send()
-            .await?;
+        let deadline = Instant::now() + timeouts::init_wait();
+        loop {
+            let resp = self
+                .http_client
+                .post(format!("{}/api/v1/{}", self.base_url, name))
+                .json(&arguments)
+                .send()
+                .await?;
 
-        let status = resp.status();
-        let body: Value = resp.json().await?;
+            let status = resp.status();
+            let retry_after = resp
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok()?.parse().ok())
+                .map(Duration::from_secs);
+            let body: Value = resp.json().await?;
 
-        if body["ok"].as_bool() == Some(true) {
-            // Wrap in MCP-compatible ToolResult format for backward compatibility
-            let result = &body["result"];
-            let text = if result.is_string() {
-                result.as_str().unwrap().to_string()
-            } else {
-                serde_json::to_string_pretty(result)?
-            };
-            Ok(json!({
-                "content": [{
-                    "type": "text",
-                    "text": text
-                }]
-            }))
-        } else {
-            let error_msg = body["error"]
+            if status.is_success() {
+                // Wrap in MCP-compatible ToolResult format for backward compatibility
+                let result = &body["result"];
+                let text = if result.is_string() {
+                    result.as_str().unwrap().to_string()
+                } else {
+                    serde_json::to_string_pretty(result)?
+                };
+                return Ok(json!({
+                    "content": [{
+                        "type": "text",
+                        "text": text
+                    }]
+                }));
+            }
+
+            let error_msg = body["error"]["message"]
                 .as_str()
                 .unwrap_or("unknown error")
                 .to_string();
-            if status.is_server_error() || status.is_client_error() {
-                Err(anyhow::anyhow!("{}", error_msg))
-            } else {
-                Err(anyhow::anyhow!("Server error: {}", error_msg))
+            match retry_after {
+                Some(wait)
+                    if status == reqwest::StatusCode::SERVICE_UNAVAILABLE
+                        && Instant::now() + wait < deadline =>
+                {
+                    tokio::time::sleep(wait).await;
+                }
+                _ if status.is_client_error() => {
+                    return Err(anyhow::anyhow!("{}", error_msg));
+                }
+                _ => return Err(anyhow::anyhow!("Server error: {}", error_msg)),
             }
         }
     }
 
+    /// The server's `/api/v1/status` result.
+    pub async fn status(&self) -> Result<Value> {
+        let body: Value = self
+            .http_client
+            .get(format!("{}/api/v1/status", self.base_url))
+            .send()
+            .await?
+            .json()
+            .await?;
+        Ok(body["result"].clone())
+    }
+
+    /// Another client of the same server, for calls made in parallel. It
+    /// doesn't keep a server from `start` alive.
+    pub fn connection(&self) -> Self {
+        Self {
+            http_client: self.http_client.clone(),
+            base_url: self.base_url.clone(),
+            port: self.port,
+            workspace_path: self.workspace_path.clone(),
+            process: None,
+        }
+    }
+
     /// Get the workspace path
     pub fn workspace_path(&self) -> &Path {
         &self.workspace_path
@@ -192,13 +277,201 @@ impl IpcClient {
 
 impl Drop for IpcClient {
     fn drop(&mut self) {
-        // Don't kill the process — it's shared across tests.
-        // The server will shut down on its own when no longer needed.
+        // A shared server from `get_or_create` is used across tests and left
+        // running.
+        let Some(mut process) = self.process.take() else {
+            return;
+        };
+        // Killing the server outright would orphan its rust-analyzer, so ask
+        // it to shut down first. Drop can't await; this blocks briefly.
+        request_shutdown(self.port, &self.workspace_path);
+        let deadline = std::time::Instant::now() + SHUTDOWN_GRACE;
+        while std::time::Instant::now() < deadline {
+            if matches!(process.try_wait(), Ok(Some(_))) {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        eprintln!("Server on port {} ignored shutdown; killing it", self.port);
+        let _ = process.start_kill();
     }
 }
 
-/// Start the server binary as a background process
-fn start_server(workspace_path: &Path, port: u16) -> Result<()> {
+/// How long a dropped client's server gets to exit before it is killed.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+/// POST `/api/v1/shutdown` over a plain socket, confirming with the
+/// workspace path, and wait for the reply.
+fn request_shutdown(port: u16, workspace_path: &Path) {
+    use std::io::{Read, Write};
+
+    let workspace = workspace_path
+        .canonicalize()
+        .unwrap_or_else(|_| workspace_path.to_path_buf());
+    let body = json!({ "confirm": workspace }).to_string();
+    let addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
+    let Ok(mut stream) = std::net::TcpStream::connect_timeout(&addr, Duration::from_secs(1)) else {
+        return;
+    };
+    let _ = stream.set_read_timeout(Some(SHUTDOWN_GRACE));
+    let request = format!(
+        "POST /api/v1/shutdown HTTP/1.1\r\n\
+         Host: 127.0.0.1:{}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        port,
+        body.len(),
+        body
+    );
+    if stream.write_all(request.as_bytes()).is_ok() {
+        let _ = stream.read_to_end(&mut Vec::new());
+    }
+}
+
+/// Polls for the server to start, every 500ms.
+const STARTUP_ATTEMPTS: u32 = 60;
+
+/// A start lock older than this was left by a test that died while starting
+/// the server.
+const STALE_LOCK: Duration = Duration::from_secs(30);
+
+/// Port from a discovery file like `.rust-analyzer-server.json`, if any.
+fn discovered_port(path: &Path) -> Option<u16> {
+    let content = std::fs::read_to_string(path).ok()?;
+    let info: Value = serde_json::from_str(&content).ok()?;
+    info["port"]
+        .as_u64()
+        .and_then(|port| u16::try_from(port).ok())
+}
+
+/// Whether a server answers on `port`. One whose rust-analyzer died answers
+/// 503 ("degraded") and is not reused.
+async fn is_healthy(http_client: &reqwest::Client, port: u16) -> bool {
+    http_client
+        .get(format!("http://127.0.0.1:{}/api/v1/health", port))
+        .send()
+        .await
+        .is_ok_and(|resp| resp.status().is_success())
+}
+
+/// Start the project's server on a port the OS picks and learn it from
+/// `port_file`, which the server writes once it's listening. Tests racing
+/// here converge on one server: only the one that creates the lock file
+/// starts it, and the others wait for the port file.
+async fn negotiate_port(
+    http_client: &reqwest::Client,
+    binary: &Path,
+    workspace_path: &Path,
+    port_file: &Path,
+) -> Result<u16> {
+    let lock_file = port_file.with_extension("lock");
+    let mut started = false;
+    for _ in 0..STARTUP_ATTEMPTS {
+        if let Some(port) = discovered_port(port_file) {
+            if is_healthy(http_client, port).await {
+                if started {
+                    let _ = std::fs::remove_file(&lock_file);
+                }
+                return Ok(port);
+            }
+        }
+
+        if !started {
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&lock_file)
+            {
+                Ok(_) => {
+                    // Whatever server wrote the port file is gone.
+                    let _ = std::fs::remove_file(port_file);
+                    eprintln!("Starting new HTTP server for {:?}", workspace_path);
+                    if let Err(e) = start_server(binary, workspace_path, 0, Some(port_file)) {
+                        let _ = std::fs::remove_file(&lock_file);
+                        return Err(e);
+                    }
+                    started = true;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let age = std::fs::metadata(&lock_file)
+                        .and_then(|m| m.modified())
+                        .ok()
+                        .and_then(|modified| modified.elapsed().ok());
+                    if age.is_some_and(|age| age > STALE_LOCK) {
+                        eprintln!("Removing stale lock {:?}", lock_file);
+                        let _ = std::fs::remove_file(&lock_file);
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+
+    if started {
+        let _ = std::fs::remove_file(&lock_file);
+    }
+    Err(anyhow::anyhow!(
+        "HTTP server for {:?} did not come up within {} seconds",
+        workspace_path,
+        STARTUP_ATTEMPTS / 2
+    ))
+}
+
+/// Connect to or start the project's server on a port hashed from the
+/// project type, for binaries without `--port-file`.
+async fn hashed_port(
+    http_client: &reqwest::Client,
+    binary: &Path,
+    workspace_path: &Path,
+    project_type: &str,
+) -> Result<u16> {
+    let port = deterministic_port(project_type);
+
+    // Try to connect to existing server (with retries since another test may be starting it)
+    for attempt in 0..30 {
+        if is_healthy(http_client, port).await {
+            return Ok(port);
+        }
+
+        // Only try to start server on first attempt
+        if attempt == 0 {
+            // Check if the port is already in use (another test may be starting the server)
+            let port_in_use = std::net::TcpStream::connect_timeout(
+                &format!("127.0.0.1:{}", port).parse().unwrap(),
+                Duration::from_millis(50),
+            )
+            .is_ok();
+
+            if port_in_use {
+                // Port is bound but health check failed — server is still starting up
+                eprintln!("Port {} is in use, waiting for server to be ready...", port);
+            } else {
+                // Port is free — start the server
+                eprintln!(
+                    "Starting new HTTP server for {} on port {}",
+                    project_type, port
+                );
+                if let Err(e) = start_server(binary, workspace_path, port, None) {
+                    eprintln!("Failed to start server: {}", e);
+                }
+            }
+        }
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+
+    Err(anyhow::anyhow!(
+        "Failed to connect to HTTP server after 15 seconds (port {})",
+        port
+    ))
+}
+
+/// The server binary to start: the most recently built of the debug and
+/// release builds.
+fn server_binary() -> Result<PathBuf> {
     let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_string());
     let project_root = Path::new(&manifest_dir);
 
@@ -228,16 +501,41 @@ fn start_server(workspace_path: &Path, port: u16) -> Result<()> {
             ));
         }
     };
+    Ok(binary)
+}
+
+/// Whether `binary` can write its port to a `--port-file`.
+fn supports_port_file(binary: &Path) -> bool {
+    std::process::Command::new(binary)
+        .arg("--help")
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains("--port-file"))
+}
 
+/// Start the server binary as a background process
+fn start_server(
+    binary: &Path,
+    workspace_path: &Path,
+    port: u16,
+    port_file: Option<&Path>,
+) -> Result<()> {
     // Use std::process::Command (not tokio) so the process is detached from the async runtime
-    eprintln!("Spawning binary: {:?} --workspace {:?} --port {}", binary, workspace_path, port);
-    std::process::Command::new(&binary)
+    eprintln!(
+        "Spawning binary: {:?} --workspace {:?} --port {}",
+        binary, workspace_path, port
+    );
+    let mut command = std::process::Command::new(binary);
+    command
         .arg("--workspace")
         .arg(workspace_path.to_str().unwrap())
         .arg("--port")
         .arg(port.to_string())
         .arg("--bind")
-        .arg("127.0.0.1")
+        .arg("127.0.0.1");
+    if let Some(port_file) = port_file {
+        command.arg("--port-file").arg(port_file);
+    }
+    command
         .stdin(Stdio::null())
         .stdout(Stdio::null())
         .stderr(Stdio::null())
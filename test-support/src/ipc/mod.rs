@@ -1,4 +1,6 @@
 pub mod client;
 pub mod server;
+mod tools;
 
 pub use client::IpcClient;
+pub use tools::{DiagnosticSummary, FileDiagnostics};
@@ -0,0 +1,187 @@
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::Path;
+
+use super::IpcClient;
+
+/// Counts from a diagnostics `summary`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct DiagnosticSummary {
+    pub errors: u64,
+    pub warnings: u64,
+    pub information: u64,
+    pub hints: u64,
+}
+
+impl DiagnosticSummary {
+    pub fn total(&self) -> u64 {
+        self.errors + self.warnings + self.information + self.hints
+    }
+}
+
+/// Result of `rust_analyzer_diagnostics`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FileDiagnostics {
+    pub file: String,
+    pub diagnostics: Vec<Value>,
+    pub summary: DiagnosticSummary,
+}
+
+/// Typed wrappers around `call_tool`, one per tool. Each builds the
+/// arguments and returns the tool's result parsed from `content[0].text`;
+/// a result that isn't JSON comes back as a string.
+impl IpcClient {
+    /// Call `name` and parse its result.
+    pub async fn call_tool_json(&mut self, name: &str, arguments: Value) -> Result<Value> {
+        let response = self.call_tool(name, arguments).await?;
+        let text = response["content"][0]["text"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("No text in {} response: {}", name, response))?;
+        Ok(serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_string())))
+    }
+
+    async fn call_at(
+        &mut self,
+        name: &str,
+        path: &Path,
+        line: u32,
+        character: u32,
+    ) -> Result<Value> {
+        self.call_tool_json(
+            name,
+            json!({
+                "file_path": path.to_string_lossy(),
+                "line": line,
+                "character": character
+            }),
+        )
+        .await
+    }
+
+    pub async fn hover(&mut self, path: &Path, line: u32, character: u32) -> Result<Value> {
+        self.call_at("rust_analyzer_hover", path, line, character)
+            .await
+    }
+
+    pub async fn definition(&mut self, path: &Path, line: u32, character: u32) -> Result<Value> {
+        self.call_at("rust_analyzer_definition", path, line, character)
+            .await
+    }
+
+    pub async fn references(&mut self, path: &Path, line: u32, character: u32) -> Result<Value> {
+        self.call_at("rust_analyzer_references", path, line, character)
+            .await
+    }
+
+    pub async fn implementation(
+        &mut self,
+        path: &Path,
+        line: u32,
+        character: u32,
+    ) -> Result<Value> {
+        self.call_at("rust_analyzer_implementation", path, line, character)
+            .await
+    }
+
+    pub async fn completion(&mut self, path: &Path, line: u32, character: u32) -> Result<Value> {
+        self.call_at("rust_analyzer_completion", path, line, character)
+            .await
+    }
+
+    pub async fn incoming_calls(
+        &mut self,
+        path: &Path,
+        line: u32,
+        character: u32,
+    ) -> Result<Value> {
+        self.call_at("rust_analyzer_incoming_calls", path, line, character)
+            .await
+    }
+
+    pub async fn outgoing_calls(
+        &mut self,
+        path: &Path,
+        line: u32,
+        character: u32,
+    ) -> Result<Value> {
+        self.call_at("rust_analyzer_outgoing_calls", path, line, character)
+            .await
+    }
+
+    pub async fn parent_module(&mut self, path: &Path, line: u32, character: u32) -> Result<Value> {
+        self.call_at("rust_analyzer_parent_module", path, line, character)
+            .await
+    }
+
+    /// Code actions for the range from `start` to `end`, as (line, character).
+    pub async fn code_actions(
+        &mut self,
+        path: &Path,
+        start: (u32, u32),
+        end: (u32, u32),
+    ) -> Result<Value> {
+        self.call_tool_json(
+            "rust_analyzer_code_actions",
+            json!({
+                "file_path": path.to_string_lossy(),
+                "line": start.0,
+                "character": start.1,
+                "end_line": end.0,
+                "end_character": end.1
+            }),
+        )
+        .await
+    }
+
+    pub async fn symbols(&mut self, path: &Path) -> Result<Value> {
+        self.call_tool_json(
+            "rust_analyzer_symbols",
+            json!({ "file_path": path.to_string_lossy() }),
+        )
+        .await
+    }
+
+    pub async fn format(&mut self, path: &Path) -> Result<Value> {
+        self.call_tool_json(
+            "rust_analyzer_format",
+            json!({ "file_path": path.to_string_lossy() }),
+        )
+        .await
+    }
+
+    pub async fn workspace_symbol(&mut self, query: &str) -> Result<Value> {
+        self.call_tool_json("rust_analyzer_workspace_symbol", json!({ "query": query }))
+            .await
+    }
+
+    pub async fn diagnostics(&mut self, path: &Path) -> Result<FileDiagnostics> {
+        self.diagnostics_with(json!({ "file_path": path.to_string_lossy() }))
+            .await
+    }
+
+    /// Diagnostics for `path` with its text replaced by unsaved `content`.
+    pub async fn diagnostics_for_content(
+        &mut self,
+        path: &Path,
+        content: &str,
+    ) -> Result<FileDiagnostics> {
+        self.diagnostics_with(json!({
+            "file_path": path.to_string_lossy(),
+            "content": content
+        }))
+        .await
+    }
+
+    async fn diagnostics_with(&mut self, arguments: Value) -> Result<FileDiagnostics> {
+        let result = self
+            .call_tool_json("rust_analyzer_diagnostics", arguments)
+            .await?;
+        Ok(serde_json::from_value(result)?)
+    }
+
+    pub async fn workspace_diagnostics(&mut self) -> Result<Value> {
+        self.call_tool_json("rust_analyzer_workspace_diagnostics", json!({}))
+            .await
+    }
+}
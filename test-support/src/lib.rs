@@ -1,12 +1,24 @@
+#[cfg(unix)]
+pub mod fake_rust_analyzer;
 pub mod ipc;
 pub mod isolated_project;
+pub mod mock_backend;
+pub mod snapshot;
+pub mod stress;
+pub mod temp_workspace;
 pub mod test_client;
 pub mod timeouts;
 pub mod workspace_ready;
 
 // Re-export commonly used items
+#[cfg(unix)]
+pub use fake_rust_analyzer::FakeRustAnalyzer;
 pub use ipc::IpcClient;
 pub use isolated_project::IsolatedProject;
+pub use mock_backend::{server_with_backend, write_file, MockBackend};
+pub use snapshot::{normalize_for_snapshot, SnapshotNormalizer};
+pub use stress::{stress, StressCall, StressOptions, StressReport};
+pub use temp_workspace::{TempWorkspace, TempWorkspaceBuilder};
 pub use test_client::MCPTestClient;
 pub use workspace_ready::WorkspaceReadiness;
 
@@ -0,0 +1,233 @@
+use anyhow::anyhow;
+use rust_analyzer_server::{
+    lsp::{BackendFuture, LspBackend, RequestTimeout},
+    mcp::RustAnalyzerMCPServer,
+};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// A language server that answers from canned JSON, for testing tool
+/// handlers without rust-analyzer. Clones share their state, so keep one to
+/// inspect what was sent after boxing another into the server.
+///
+/// Methods without a response answer `null`, except `initialize`.
+#[derive(Clone)]
+pub struct MockBackend {
+    state: Arc<Mutex<MockState>>,
+}
+
+#[derive(Default)]
+struct MockState {
+    responses: HashMap<String, Value>,
+    /// Responses for one document, by method and `textDocument.uri`.
+    document_responses: HashMap<(String, String), Value>,
+    /// Responses for one line of one document, by method, uri and line.
+    position_responses: HashMap<(String, String, u64), Value>,
+    errors: HashMap<String, String>,
+    delays: HashMap<String, Duration>,
+    /// Requests per method still to be answered `null`, as rust-analyzer
+    /// does while indexing.
+    indexing: HashMap<String, usize>,
+    requests: Vec<(String, Value)>,
+    notifications: Vec<(String, Value)>,
+    /// Requests being answered now, and the most there have been at once.
+    in_flight: usize,
+    max_in_flight: usize,
+    alive: bool,
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        let backend = Self {
+            state: Arc::default(),
+        };
+        backend
+            .with_state(|state| state.alive = true)
+            .respond("initialize", json!({ "capabilities": {} }))
+    }
+
+    /// Answer every `method` request with `response`.
+    pub fn respond(self, method: &str, response: Value) -> Self {
+        self.with_state(|state| {
+            state.responses.insert(method.to_string(), response);
+        })
+    }
+
+    /// Answer `method` requests about the document `uri` with `response`,
+    /// ahead of any `respond` for the method.
+    pub fn respond_for(self, method: &str, uri: &str, response: Value) -> Self {
+        self.with_state(|state| {
+            state
+                .document_responses
+                .insert((method.to_string(), uri.to_string()), response);
+        })
+    }
+
+    /// Answer `method` requests at `line` of the document `uri` (their
+    /// `position`, or the start of their `range`) with `response`, ahead of
+    /// `respond_for` and `respond`.
+    pub fn respond_at(self, method: &str, uri: &str, line: u64, response: Value) -> Self {
+        self.with_state(|state| {
+            state
+                .position_responses
+                .insert((method.to_string(), uri.to_string(), line), response);
+        })
+    }
+
+    /// Answer every `method` request with an error.
+    pub fn fail(self, method: &str, message: &str) -> Self {
+        self.with_state(|state| {
+            state.errors.insert(method.to_string(), message.to_string());
+        })
+    }
+
+    /// Wait before answering `method`. A delay past the request's timeout
+    /// makes it time out instead.
+    pub fn delay(self, method: &str, delay: Duration) -> Self {
+        self.with_state(|state| {
+            state.delays.insert(method.to_string(), delay);
+        })
+    }
+
+    /// Answer the next `requests` requests for `method` with `null`, as
+    /// while still indexing.
+    pub fn indexing(self, method: &str, requests: usize) -> Self {
+        self.with_state(|state| {
+            state.indexing.insert(method.to_string(), requests);
+        })
+    }
+
+    /// Params of every `method` request so far.
+    pub fn requests(&self, method: &str) -> Vec<Value> {
+        sent(&self.state.lock().unwrap().requests, method)
+    }
+
+    /// The most requests that were being answered at once.
+    pub fn max_in_flight(&self) -> usize {
+        self.state.lock().unwrap().max_in_flight
+    }
+
+    /// Params of every `method` notification so far.
+    pub fn notifications(&self, method: &str) -> Vec<Value> {
+        sent(&self.state.lock().unwrap().notifications, method)
+    }
+
+    fn with_state(self, update: impl FnOnce(&mut MockState)) -> Self {
+        update(&mut self.state.lock().unwrap());
+        self
+    }
+}
+
+fn sent(messages: &[(String, Value)], method: &str) -> Vec<Value> {
+    messages
+        .iter()
+        .filter(|(sent, _)| sent == method)
+        .map(|(_, params)| params.clone())
+        .collect()
+}
+
+impl LspBackend for MockBackend {
+    fn request(
+        &mut self,
+        method: &str,
+        params: Option<Value>,
+        timeout: Duration,
+    ) -> BackendFuture<'static, Value> {
+        let (delay, answer) = {
+            let mut state = self.state.lock().unwrap();
+            let params = params.unwrap_or(Value::Null);
+            let document = params["textDocument"]["uri"].as_str().and_then(|uri| {
+                let line = params["position"]["line"]
+                    .as_u64()
+                    .or(params["range"]["start"]["line"].as_u64());
+                let at = line.and_then(|line| {
+                    let key = (method.to_string(), uri.to_string(), line);
+                    state.position_responses.get(&key).cloned()
+                });
+                let key = (method.to_string(), uri.to_string());
+                at.or_else(|| state.document_responses.get(&key).cloned())
+            });
+            state.requests.push((method.to_string(), params));
+            let delay = state.delays.get(method).copied().unwrap_or_default();
+            let answer = match state.indexing.get_mut(method) {
+                Some(remaining) if *remaining > 0 => {
+                    *remaining -= 1;
+                    Ok(Value::Null)
+                }
+                _ => match state.errors.get(method) {
+                    Some(message) => Err(message.clone()),
+                    None => Ok(document
+                        .or_else(|| state.responses.get(method).cloned())
+                        .unwrap_or(Value::Null)),
+                },
+            };
+            (delay, answer)
+        };
+        let method = method.to_string();
+        let state = Arc::clone(&self.state);
+        Box::pin(async move {
+            {
+                let mut state = state.lock().unwrap();
+                state.in_flight += 1;
+                state.max_in_flight = state.max_in_flight.max(state.in_flight);
+            }
+            let answer = if delay > timeout {
+                tokio::time::sleep(timeout).await;
+                Err(RequestTimeout { method, timeout }.into())
+            } else {
+                tokio::time::sleep(delay).await;
+                answer.map_err(|message| anyhow!(message))
+            };
+            state.lock().unwrap().in_flight -= 1;
+            answer
+        })
+    }
+
+    fn notify<'a>(&'a mut self, method: &'a str, params: Option<Value>) -> BackendFuture<'a, ()> {
+        self.state
+            .lock()
+            .unwrap()
+            .notifications
+            .push((method.to_string(), params.unwrap_or(Value::Null)));
+        Box::pin(async { Ok(()) })
+    }
+
+    fn is_alive(&mut self) -> bool {
+        self.state.lock().unwrap().alive
+    }
+
+    fn kill(&mut self) -> BackendFuture<'_, ()> {
+        self.state.lock().unwrap().alive = false;
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// A server on `root` answering through `backend`.
+pub async fn server_with_backend(root: &Path, backend: &MockBackend) -> RustAnalyzerMCPServer {
+    let mut server = RustAnalyzerMCPServer::with_workspace(root.to_path_buf());
+    server
+        .start_with_backend(Box::new(backend.clone()))
+        .await
+        .unwrap();
+    server
+}
+
+/// Write `content` to `path` under `root`, creating its directories, and
+/// return the file's URI.
+pub fn write_file(root: &Path, path: &str, content: &str) -> String {
+    let file = root.join(path);
+    std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+    std::fs::write(&file, content).unwrap();
+    format!("file://{}", file.display())
+}
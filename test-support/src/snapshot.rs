@@ -0,0 +1,113 @@
+use serde_json::{Map, Value};
+use std::path::Path;
+
+/// Fields whose values change between runs or rust-analyzer versions
+/// without the shape of the output changing.
+pub const VOLATILE_FIELDS: &[&str] = &["data", "resultId", "uptime_secs", "pid", "port"];
+
+/// Rewrites tool results into a form stable enough to snapshot: the
+/// workspace path becomes `<WS>`, arrays of locations are sorted, and
+/// volatile fields are replaced with `"<redacted>"`.
+#[derive(Debug, Clone)]
+pub struct SnapshotNormalizer {
+    workspaces: Vec<String>,
+    redacted: Vec<String>,
+}
+
+impl SnapshotNormalizer {
+    pub fn new(workspace: &Path) -> Self {
+        let mut workspaces = vec![workspace.to_string_lossy().into_owned()];
+        // Servers report the canonical path, which may differ (symlinked
+        // temp dirs on macOS).
+        if let Ok(canonical) = workspace.canonicalize() {
+            workspaces.push(canonical.to_string_lossy().into_owned());
+        }
+        // Longest first, so a path isn't half-replaced by its prefix.
+        workspaces.sort_by_key(|path| std::cmp::Reverse(path.len()));
+        workspaces.dedup();
+        Self {
+            workspaces,
+            redacted: VOLATILE_FIELDS.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    /// Also redact `field` wherever it appears.
+    pub fn redact(mut self, field: &str) -> Self {
+        self.redacted.push(field.to_string());
+        self
+    }
+
+    pub fn normalize(&self, value: &Value) -> Value {
+        match value {
+            Value::String(s) => Value::String(self.normalize_str(s)),
+            Value::Array(items) => {
+                let mut items: Vec<Value> = items.iter().map(|item| self.normalize(item)).collect();
+                if items.iter().all(is_location) {
+                    items.sort_by_key(|item| item.to_string());
+                }
+                Value::Array(items)
+            }
+            Value::Object(fields) => Value::Object(
+                fields
+                    .iter()
+                    .map(|(key, field)| {
+                        let field = if self.redacted.iter().any(|r| r == key) {
+                            Value::String("<redacted>".to_string())
+                        } else {
+                            self.normalize(field)
+                        };
+                        (self.normalize_str(key), field)
+                    })
+                    .collect::<Map<_, _>>(),
+            ),
+            other => other.clone(),
+        }
+    }
+
+    fn normalize_str(&self, s: &str) -> String {
+        self.workspaces.iter().fold(s.to_string(), |s, workspace| {
+            s.replace(workspace.as_str(), "<WS>")
+        })
+    }
+}
+
+/// `SnapshotNormalizer::new(workspace).normalize(value)`.
+pub fn normalize_for_snapshot(value: &Value, workspace: &Path) -> Value {
+    SnapshotNormalizer::new(workspace).normalize(value)
+}
+
+/// A simplified location (`{"location": ...}`) or an LSP `Location`; their
+/// order in a response isn't meaningful.
+fn is_location(value: &Value) -> bool {
+    value.get("location").is_some_and(Value::is_string)
+        || (value.get("uri").is_some_and(Value::is_string) && value.get("range").is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_normalize_paths_order_and_volatile_fields() {
+        let result = json!({
+            "file:///ws/project/src/lib.rs": [{ "message": "unused", "data": { "id": 17 } }],
+            "locations": [
+                { "location": "/ws/project/src/main.rs:4:8" },
+                { "location": "/ws/project/src/lib.rs:1:0" }
+            ],
+            "lines": [3, 1, 2]
+        });
+        assert_eq!(
+            normalize_for_snapshot(&result, Path::new("/ws/project")),
+            json!({
+                "file://<WS>/src/lib.rs": [{ "message": "unused", "data": "<redacted>" }],
+                "locations": [
+                    { "location": "<WS>/src/lib.rs:1:0" },
+                    { "location": "<WS>/src/main.rs:4:8" }
+                ],
+                "lines": [3, 1, 2]
+            })
+        );
+    }
+}
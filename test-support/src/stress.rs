@@ -0,0 +1,292 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+use tokio::task::JoinSet;
+
+use crate::{timeouts, IpcClient};
+
+/// One tool call in a stress run.
+#[derive(Debug, Clone)]
+pub struct StressCall {
+    pub tool: String,
+    pub args: Value,
+    /// Text the result must contain. Pick it so calls with different
+    /// arguments expect different text; a result handed to the wrong caller
+    /// then fails the check.
+    pub expect: String,
+}
+
+impl StressCall {
+    pub fn new(tool: &str, args: Value, expect: &str) -> Self {
+        Self {
+            tool: tool.to_string(),
+            args,
+            expect: expect.to_string(),
+        }
+    }
+
+    /// A call on the position `line`:`character` of `path`.
+    pub fn at(tool: &str, path: &Path, line: u32, character: u32, expect: &str) -> Self {
+        Self::new(
+            tool,
+            json!({
+                "file_path": path.to_string_lossy(),
+                "line": line,
+                "character": character
+            }),
+            expect,
+        )
+    }
+
+    /// A call on the whole of `path`.
+    pub fn file(tool: &str, path: &Path, expect: &str) -> Self {
+        Self::new(tool, json!({ "file_path": path.to_string_lossy() }), expect)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StressOptions {
+    /// Calls to fire at once, cycling through the given `StressCall`s.
+    pub calls: usize,
+    /// Calls still running after this are taken as a deadlock.
+    pub deadline: Duration,
+    /// Fraction of calls allowed to fail.
+    pub max_error_rate: f64,
+}
+
+impl Default for StressOptions {
+    fn default() -> Self {
+        Self {
+            calls: 50,
+            deadline: timeouts::stress_timeout(timeouts::STRESS_MIXED_BASE_SECS),
+            max_error_rate: 0.1,
+        }
+    }
+}
+
+/// What happened to one call.
+#[derive(Debug, Clone)]
+pub struct CallOutcome {
+    pub tool: String,
+    pub latency: Duration,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Ok,
+    /// The call failed; failures count against `max_error_rate`.
+    Error(String),
+    /// The result lacked the call's `expect` text: it may belong to another
+    /// call. Never allowed.
+    Mismatch {
+        expected: String,
+        text: String,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct StressReport {
+    pub outcomes: Vec<CallOutcome>,
+    pub elapsed: Duration,
+}
+
+impl StressReport {
+    pub fn errors(&self) -> usize {
+        self.count(|outcome| matches!(outcome, Outcome::Error(_)))
+    }
+
+    pub fn mismatches(&self) -> usize {
+        self.count(|outcome| matches!(outcome, Outcome::Mismatch { .. }))
+    }
+
+    pub fn error_rate(&self) -> f64 {
+        if self.outcomes.is_empty() {
+            return 0.0;
+        }
+        self.errors() as f64 / self.outcomes.len() as f64
+    }
+
+    /// Latency below which `fraction` (0.0 to 1.0) of the calls finished.
+    pub fn latency_percentile(&self, fraction: f64) -> Duration {
+        let mut latencies: Vec<Duration> = self.outcomes.iter().map(|o| o.latency).collect();
+        latencies.sort();
+        let index = ((latencies.len() as f64 * fraction).ceil() as usize).saturating_sub(1);
+        latencies.get(index).copied().unwrap_or_default()
+    }
+
+    /// Fail on any mismatched result, or on more errors than `options`
+    /// allow.
+    pub fn check(&self, options: &StressOptions) -> Result<()> {
+        if let Some(mismatch) = self
+            .outcomes
+            .iter()
+            .find(|o| matches!(o.outcome, Outcome::Mismatch { .. }))
+        {
+            return Err(anyhow!(
+                "{} of {} results did not match their call, e.g. {}: {:?}",
+                self.mismatches(),
+                self.outcomes.len(),
+                mismatch.tool,
+                mismatch.outcome
+            ));
+        }
+        if self.error_rate() > options.max_error_rate {
+            let errors: Vec<&str> = self
+                .outcomes
+                .iter()
+                .filter_map(|o| match &o.outcome {
+                    Outcome::Error(e) => Some(e.as_str()),
+                    _ => None,
+                })
+                .take(5)
+                .collect();
+            return Err(anyhow!(
+                "{} of {} calls failed (allowed {:.0}%): {:?}",
+                self.errors(),
+                self.outcomes.len(),
+                options.max_error_rate * 100.0,
+                errors
+            ));
+        }
+        Ok(())
+    }
+
+    fn count(&self, matches: impl Fn(&Outcome) -> bool) -> usize {
+        self.outcomes.iter().filter(|o| matches(&o.outcome)).count()
+    }
+}
+
+impl std::fmt::Display for StressReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} calls in {:?}: {} errors, {} mismatches; latency p50 {:?}, p95 {:?}, max {:?}",
+            self.outcomes.len(),
+            self.elapsed,
+            self.errors(),
+            self.mismatches(),
+            self.latency_percentile(0.5),
+            self.latency_percentile(0.95),
+            self.latency_percentile(1.0)
+        )
+    }
+}
+
+/// Fire `options.calls` tool calls at `client`'s server at once, cycling
+/// through `calls`, and record each one's latency and outcome. Errs if
+/// calls are still running at `options.deadline`; check the invariants with
+/// `StressReport::check`.
+pub async fn stress(
+    client: &IpcClient,
+    calls: &[StressCall],
+    options: &StressOptions,
+) -> Result<StressReport> {
+    if calls.is_empty() {
+        return Err(anyhow!("No calls to stress with"));
+    }
+
+    let start = Instant::now();
+    let mut tasks = JoinSet::new();
+    for call in calls.iter().cycle().take(options.calls).cloned() {
+        let mut client = client.connection();
+        tasks.spawn(async move {
+            let call_start = Instant::now();
+            let outcome = match client.call_tool(&call.tool, call.args).await {
+                Ok(result) => {
+                    let text = result["content"][0]["text"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string();
+                    if text.contains(&call.expect) {
+                        Outcome::Ok
+                    } else {
+                        Outcome::Mismatch {
+                            expected: call.expect,
+                            text,
+                        }
+                    }
+                }
+                Err(e) => Outcome::Error(e.to_string()),
+            };
+            CallOutcome {
+                tool: call.tool,
+                latency: call_start.elapsed(),
+                outcome,
+            }
+        });
+    }
+
+    let mut outcomes = Vec::with_capacity(options.calls);
+    let collect = async {
+        while let Some(outcome) = tasks.join_next().await {
+            outcomes.push(outcome?);
+        }
+        Ok::<_, anyhow::Error>(())
+    };
+    if let Ok(collected) = tokio::time::timeout(options.deadline, collect).await {
+        collected?;
+    } else {
+        return Err(anyhow!(
+            "Possible deadlock: {} of {} calls still running after {:?}",
+            options.calls - outcomes.len(),
+            options.calls,
+            options.deadline
+        ));
+    }
+
+    Ok(StressReport {
+        outcomes,
+        elapsed: start.elapsed(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(millis: u64, outcome: Outcome) -> CallOutcome {
+        CallOutcome {
+            tool: "rust_analyzer_hover".to_string(),
+            latency: Duration::from_millis(millis),
+            outcome,
+        }
+    }
+
+    #[test]
+    fn test_report_invariants() {
+        let options = StressOptions {
+            max_error_rate: 0.25,
+            ..StressOptions::default()
+        };
+        let mut report = StressReport {
+            outcomes: vec![
+                outcome(40, Outcome::Ok),
+                outcome(10, Outcome::Ok),
+                outcome(30, Outcome::Error("timed out".to_string())),
+                outcome(20, Outcome::Ok),
+            ],
+            elapsed: Duration::from_millis(40),
+        };
+        assert_eq!(report.latency_percentile(0.5), Duration::from_millis(20));
+        assert_eq!(report.latency_percentile(1.0), Duration::from_millis(40));
+        assert!(report.check(&options).is_ok());
+
+        report.outcomes[0].outcome = Outcome::Error("timed out".to_string());
+        assert!(report.check(&options).is_err());
+
+        report.outcomes[0].outcome = Outcome::Mismatch {
+            expected: "fn greet".to_string(),
+            text: "struct Calculator".to_string(),
+        };
+        let error = report.check(&options).unwrap_err().to_string();
+        assert!(
+            error.starts_with("1 of 4 results did not match"),
+            "{}",
+            error
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+use crate::IpcClient;
+
+/// A cargo project scaffolded in a temp dir for one test, with its own
+/// server. Files can be changed mid-test without touching the shared
+/// fixtures; the server and the directory go away on drop.
+pub struct TempWorkspace {
+    /// Dropped first, so the server is gone before its workspace is.
+    client: Option<IpcClient>,
+    dir: TempDir,
+}
+
+/// Files for a `TempWorkspace`, by path relative to its root.
+#[derive(Debug, Clone, Default)]
+pub struct TempWorkspaceBuilder {
+    files: Vec<(PathBuf, String)>,
+    server_args: Vec<String>,
+}
+
+impl TempWorkspace {
+    /// An empty project; add a `Cargo.toml` and sources with `.file`.
+    pub fn builder() -> TempWorkspaceBuilder {
+        TempWorkspaceBuilder::default()
+    }
+
+    /// A library crate without errors or warnings.
+    pub fn clean_crate() -> TempWorkspaceBuilder {
+        Self::builder()
+            .file("Cargo.toml", &package_manifest("temp-crate", ""))
+            .file(
+                "src/lib.rs",
+                "/// Adds two numbers.\npub fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n",
+            )
+    }
+
+    /// A library crate whose `src/lib.rs` has a type error (E0308).
+    pub fn crate_with_errors() -> TempWorkspaceBuilder {
+        Self::builder()
+            .file("Cargo.toml", &package_manifest("temp-crate", ""))
+            .file(
+                "src/lib.rs",
+                "pub fn broken() -> u32 {\n    \"not a number\"\n}\n",
+            )
+    }
+
+    /// A workspace of a `core` library and an `app` binary depending on it.
+    pub fn two_crate_workspace() -> TempWorkspaceBuilder {
+        Self::builder()
+            .file(
+                "Cargo.toml",
+                "[workspace]\nmembers = [\"core\", \"app\"]\nresolver = \"2\"\n",
+            )
+            .file("core/Cargo.toml", &package_manifest("temp-core", ""))
+            .file(
+                "core/src/lib.rs",
+                "pub fn greeting(name: &str) -> String {\n    format!(\"Hello, {}!\", name)\n}\n",
+            )
+            .file(
+                "app/Cargo.toml",
+                &package_manifest("temp-app", "temp-core = { path = \"../core\" }\n"),
+            )
+            .file(
+                "app/src/main.rs",
+                "fn main() {\n    println!(\"{}\", temp_core::greeting(\"world\"));\n}\n",
+            )
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn file_path(&self, relative_path: &str) -> PathBuf {
+        self.dir.path().join(relative_path)
+    }
+
+    pub fn read(&self, relative_path: &str) -> Result<String> {
+        Ok(std::fs::read_to_string(self.file_path(relative_path))?)
+    }
+
+    /// Create or overwrite a file, creating its directories.
+    pub fn write(&self, relative_path: &str, content: &str) -> Result<()> {
+        write_file(self.dir.path(), Path::new(relative_path), content)
+    }
+
+    pub fn remove(&self, relative_path: &str) -> Result<()> {
+        Ok(std::fs::remove_file(self.file_path(relative_path))?)
+    }
+
+    /// The client of the workspace's server. Panics for a workspace made
+    /// with `create` rather than `start`.
+    pub fn client(&mut self) -> &mut IpcClient {
+        self.client
+            .as_mut()
+            .expect("TempWorkspace was created without a server; use start()")
+    }
+}
+
+impl TempWorkspaceBuilder {
+    /// Add a file, replacing an earlier one at the same path.
+    pub fn file(mut self, path: impl AsRef<Path>, content: &str) -> Self {
+        let path = path.as_ref().to_path_buf();
+        self.files.retain(|(existing, _)| *existing != path);
+        self.files.push((path, content.to_string()));
+        self
+    }
+
+    /// Pass extra arguments (such as `--watch`) to the server `start` runs.
+    pub fn server_arg(mut self, arg: &str) -> Self {
+        self.server_args.push(arg.to_string());
+        self
+    }
+
+    /// Write the files, without a server.
+    pub fn create(self) -> Result<TempWorkspace> {
+        let dir = TempDir::new()?;
+        for (path, content) in &self.files {
+            write_file(dir.path(), path, content)?;
+        }
+        Ok(TempWorkspace { client: None, dir })
+    }
+
+    /// Write the files and start a server on them.
+    pub async fn start(self) -> Result<TempWorkspace> {
+        let server_args = self.server_args.clone();
+        let mut workspace = self.create()?;
+        workspace.client = Some(IpcClient::start(workspace.path(), &server_args).await?);
+        Ok(workspace)
+    }
+}
+
+fn write_file(root: &Path, path: &Path, content: &str) -> Result<()> {
+    let path = root.join(path);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+fn package_manifest(name: &str, dependencies: &str) -> String {
+    format!(
+        "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\n{}",
+        name, dependencies
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_templates_scaffold_files() -> Result<()> {
+        let workspace = TempWorkspace::two_crate_workspace().create()?;
+        assert!(workspace.file_path("core/src/lib.rs").exists());
+        assert!(workspace
+            .read("app/Cargo.toml")?
+            .contains("path = \"../core\""));
+
+        let workspace = TempWorkspace::crate_with_errors()
+            .file("src/lib.rs", "pub fn fixed() {}\n")
+            .create()?;
+        assert_eq!(workspace.read("src/lib.rs")?, "pub fn fixed() {}\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_files_change_and_directory_is_removed() -> Result<()> {
+        let workspace = TempWorkspace::clean_crate().create()?;
+        workspace.write("src/extra/mod.rs", "pub struct Extra;\n")?;
+        assert!(workspace.file_path("src/extra/mod.rs").exists());
+        workspace.remove("src/lib.rs")?;
+        assert!(!workspace.file_path("src/lib.rs").exists());
+
+        let root = workspace.path().to_path_buf();
+        drop(workspace);
+        assert!(!root.exists());
+        Ok(())
+    }
+}
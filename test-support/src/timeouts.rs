@@ -35,6 +35,7 @@ pub const DOCUMENT_OPEN_DELAY_MILLIS: u64 = 200;
 pub const STRESS_CONCURRENT_BASE_SECS: u64 = 10;
 pub const STRESS_SEQUENTIAL_BASE_SECS: u64 = 20;
 pub const STRESS_FILES_BASE_SECS: u64 = 10;
+pub const STRESS_MIXED_BASE_SECS: u64 = 60;
 
 /// Stress test delays.
 pub const STRESS_BATCH_DELAY_MILLIS: u64 = 500;
@@ -0,0 +1,2 @@
+/* 🦀 é */ pub fn target() -> u32 { 1 }
+pub fn caller() -> u32 { let _s = "日本🦀"; target() }
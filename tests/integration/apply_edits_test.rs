@@ -0,0 +1,83 @@
+use anyhow::{anyhow, Result};
+use serde_json::json;
+use std::{path::Path, time::Duration};
+use test_support::{IpcClient, IsolatedProject};
+
+/// Whether the diagnostics for `file_path` include an unused-variable warning
+/// on `line`.
+async fn has_unused_warning(client: &mut IpcClient, file_path: &Path, line: u64) -> Result<bool> {
+    let result = client.diagnostics(file_path).await?;
+    Ok(result.diagnostics.iter().any(|d| {
+        d["range"]["start"]["line"] == line
+            && d["message"]
+                .as_str()
+                .is_some_and(|m| m.contains("unused variable"))
+    }))
+}
+
+#[tokio::test]
+async fn test_applied_quickfix_clears_diagnostic() -> Result<()> {
+    let project = IsolatedProject::new_diagnostics()?;
+    let mut client = IpcClient::owned(project.path()).await?;
+    let file_path = project.file_path("src/errors.rs");
+    // `let unused = 42;` in test_unused_variable (0-based line 11).
+    let line = 11;
+
+    let mut found = false;
+    for _ in 0..60 {
+        if has_unused_warning(&mut client, &file_path, line).await? {
+            found = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    assert!(
+        found,
+        "Expected an unused variable warning on line {}",
+        line
+    );
+
+    let actions = client
+        .code_actions(&file_path, (line as u32, 8), (line as u32, 14))
+        .await?;
+    let title = actions
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|a| a["title"].as_str())
+        .find(|t| t.contains("_unused") || t.contains("underscore"))
+        .ok_or_else(|| anyhow!("No underscore quickfix offered: {}", actions))?
+        .to_string();
+
+    let applied = client
+        .call_tool_json(
+            "rust_analyzer_code_actions",
+            json!({
+                "file_path": file_path.to_str().unwrap(),
+                "line": line,
+                "character": 8,
+                "end_line": line,
+                "end_character": 14,
+                "apply": title
+            }),
+        )
+        .await?;
+    assert_eq!(applied["applied"], title);
+    assert!(std::fs::read_to_string(&file_path)?.contains("let _unused = 42;"));
+
+    // didChange + didSave make rust-analyzer re-check without any prodding.
+    let mut cleared = false;
+    for _ in 0..60 {
+        if !has_unused_warning(&mut client, &file_path, line).await? {
+            cleared = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    assert!(
+        cleared,
+        "Unused variable warning should be gone after the fix"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use anyhow::Result;
+use serde_json::json;
+use std::time::{Duration, Instant};
+use test_support::TempWorkspace;
+
+/// rust-analyzer is sent the manifest as TOML. Hover there answers with what
+/// rust-analyzer has, which for dependency lines is currently nothing, and
+/// doesn't retry as if indexing.
+#[tokio::test]
+async fn test_cargo_toml_hover_on_a_dependency_line() -> Result<()> {
+    let mut workspace = TempWorkspace::two_crate_workspace().start().await?;
+    let main = workspace.file_path("app/src/main.rs");
+    let manifest = workspace.file_path("app/Cargo.toml");
+    let client = workspace.client();
+    // Waits out indexing.
+    let hover = client.hover(&main, 1, 25).await?;
+    assert!(hover.to_string().contains("temp_core"), "{}", hover);
+
+    // Line 6 is `temp-core = { path = "../core" }`.
+    let start = Instant::now();
+    let hover = client
+        .call_tool_json(
+            "rust_analyzer_hover",
+            json!({ "file_path": manifest.to_string_lossy(), "line": 6, "character": 3 }),
+        )
+        .await?;
+    assert!(
+        start.elapsed() < Duration::from_secs(5),
+        "{:?}",
+        start.elapsed()
+    );
+    assert!(
+        hover.is_null() || hover.get("contents").is_some(),
+        "{}",
+        hover
+    );
+
+    // Opening the manifest leaves Rust files working.
+    let hover = client.hover(&main, 1, 25).await?;
+    assert!(hover.to_string().contains("temp_core"), "{}", hover);
+    Ok(())
+}
@@ -0,0 +1,161 @@
+use anyhow::Result;
+use assert_cmd::Command;
+use serde_json::json;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use test_support::IpcClient;
+
+fn unused_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+#[test]
+fn test_status_unreachable() {
+    Command::cargo_bin("rust-analyzer-server")
+        .unwrap()
+        .args(["status", "--port", &unused_port().to_string()])
+        .assert()
+        .code(2);
+}
+
+#[tokio::test]
+async fn test_status_json() -> Result<()> {
+    let client = IpcClient::get_or_create("test-project").await?;
+
+    let output = Command::cargo_bin("rust-analyzer-server")?
+        .args(["status", "--port", &client.port().to_string(), "--json"])
+        .output()?;
+
+    // 0 when ready, 1 while still indexing.
+    let code = output.status.code();
+    assert!(
+        matches!(code, Some(0) | Some(1)),
+        "unexpected exit code {:?}",
+        code
+    );
+
+    let status: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert!(status["workspace"].is_string());
+    assert!(status["state"].is_string());
+    assert!(status["uptime_secs"].is_number());
+    assert_eq!(code == Some(0), status["state"] == "ready");
+
+    Ok(())
+}
+
+#[test]
+fn test_tools_lists_builtin_tools() -> Result<()> {
+    let output = Command::cargo_bin("rust-analyzer-server")?
+        .args(["tools", "--schema"])
+        .output()?;
+    assert!(output.status.success());
+
+    let tools: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout)?;
+    let hover = tools
+        .iter()
+        .find(|t| t["name"] == "rust_analyzer_hover")
+        .expect("hover tool listed");
+    assert!(hover["inputSchema"]["properties"]["file_path"].is_object());
+
+    Ok(())
+}
+
+/// Start a daemon on a free port with its pid and log files in `dir`, and
+/// wait until it has written its pid file and accepts connections.
+#[cfg(unix)]
+fn start_daemon(dir: &Path) -> Result<(String, PathBuf)> {
+    let port = unused_port().to_string();
+    let pid_file = dir.join("server.pid");
+
+    Command::cargo_bin("rust-analyzer-server")?
+        .args(["--daemon", "--port", &port, "--workspace"])
+        .arg(dir)
+        .arg("--pid-file")
+        .arg(&pid_file)
+        .arg("--log-file")
+        .arg(dir.join("server.log"))
+        .assert()
+        .success();
+
+    let addr = format!("127.0.0.1:{}", port);
+    for _ in 0..50 {
+        if pid_file.exists() && std::net::TcpStream::connect(&addr).is_ok() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    Ok((port, pid_file))
+}
+
+#[cfg(unix)]
+fn stop_daemon(port: &str, pid_file: &Path) -> Result<()> {
+    Command::cargo_bin("rust-analyzer-server")?
+        .args(["stop", "--port", port, "--pid-file"])
+        .arg(pid_file)
+        .assert()
+        .success();
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_daemon_leads_its_own_session() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (port, pid_file) = start_daemon(dir.path())?;
+
+    let content = std::fs::read_to_string(&pid_file)?;
+    let pid = content.lines().next().unwrap_or_default();
+    // Fields after the parenthesized command name: state, ppid, pgrp, session, tty_nr.
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid))?;
+    let fields: Vec<&str> = stat
+        .rsplit_once(')')
+        .unwrap()
+        .1
+        .split_whitespace()
+        .collect();
+
+    stop_daemon(&port, &pid_file)?;
+
+    assert_eq!(fields[3], pid, "daemon should be a session leader");
+    assert_eq!(fields[4], "0", "daemon should have no controlling terminal");
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_shutdown_needs_the_workspace_confirmation() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (port, pid_file) = start_daemon(dir.path())?;
+
+    let url = format!("http://127.0.0.1:{}/api/v1/shutdown", port);
+    let client = reqwest::Client::new();
+    let unconfirmed = client.post(&url).json(&json!({})).send().await?.status();
+    let other_workspace = client
+        .post(&url)
+        .json(&json!({ "confirm": std::env::temp_dir() }))
+        .send()
+        .await?
+        .status();
+    let confirmed = client
+        .post(&url)
+        .json(&json!({ "confirm": dir.path().canonicalize()? }))
+        .send()
+        .await?
+        .status();
+    if !confirmed.is_success() {
+        stop_daemon(&port, &pid_file)?;
+    }
+
+    assert_eq!(unconfirmed, reqwest::StatusCode::FORBIDDEN);
+    assert_eq!(other_workspace, reqwest::StatusCode::FORBIDDEN);
+    assert!(confirmed.is_success(), "shutdown failed: {}", confirmed);
+
+    Ok(())
+}
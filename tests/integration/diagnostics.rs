@@ -1,6 +1,6 @@
 use anyhow::Result;
-use serde_json::json;
-use test_support::IpcClient;
+use serde_json::{json, Value};
+use test_support::{IpcClient, TempWorkspace};
 
 fn assert_tool_response(response: &serde_json::Value) {
     assert!(
@@ -100,63 +100,40 @@ async fn test_file_diagnostics() -> Result<()> {
 
 #[tokio::test]
 async fn test_file_diagnostics_clean_file() -> Result<()> {
-    // Use test-project-diagnostics which has a clean file
-    let mut client = IpcClient::get_or_create("test-project-diagnostics").await?;
-    let workspace_path = client.workspace_path();
-    let clean_path = workspace_path.join("src/clean.rs");
-    eprintln!("Using clean.rs file for clean file test");
-    eprintln!("Workspace fully initialized with all modules resolved");
+    // A crate of our own, so nothing another test writes can dirty it
+    let mut workspace = TempWorkspace::clean_crate().start().await?;
+    let clean_path = workspace.file_path("src/lib.rs");
+    let client = workspace.client();
 
     // Retry a few times to handle transient rust-analyzer initialization issues
     let mut last_error = None;
     for attempt in 1..=3 {
         // Get diagnostics - use absolute path
-        let response = client
-            .call_tool(
-                "rust_analyzer_diagnostics",
-                json!({
-                    "file_path": clean_path.to_str().unwrap()
-                }),
-            )
-            .await?;
-
-        assert_tool_response(&response);
-        let content = response["content"][0]["text"].as_str().unwrap();
-        let parsed: serde_json::Value = serde_json::from_str(content).unwrap();
-
-        // Check summary for clean file (lib.rs should have no errors)
-        let summary = &parsed["summary"];
-        let error_count = summary["errors"].as_u64().unwrap_or(0);
-
-        // If no errors, we're good
-        if error_count == 0 {
-            // Additional check: no error-level diagnostics
-            if let Some(diagnostics) = parsed["diagnostics"].as_array() {
-                let has_errors = diagnostics
-                    .iter()
-                    .any(|d| d["severity"].as_str() == Some("error"));
-
-                if !has_errors {
-                    // Success!
-                    // No need to shutdown with shared client
-                    return Ok(());
-                }
-            } else {
-                // If diagnostics is not an array, that's okay if error_count is 0
-                return Ok(());
-            }
+        let diagnostics = client.diagnostics(&clean_path).await?;
+
+        // Check summary for the clean file (it should have no errors), and
+        // that no diagnostic is error-level either
+        let has_errors = diagnostics
+            .diagnostics
+            .iter()
+            .any(|d| d["severity"].as_str() == Some("error"));
+        if diagnostics.summary.errors == 0 && !has_errors {
+            return Ok(());
         }
 
         // Log the issue for debugging
-        eprintln!("Attempt {}: Found {} errors", attempt, error_count);
+        eprintln!(
+            "Attempt {}: Found {} errors",
+            attempt, diagnostics.summary.errors
+        );
         if attempt == 1 {
-            eprintln!("Full diagnostic response for src/clean.rs:");
-            eprintln!("{}", serde_json::to_string_pretty(&parsed).unwrap());
+            eprintln!("Full diagnostics for src/lib.rs:");
+            eprintln!("{:#?}", diagnostics.diagnostics);
         }
 
         last_error = Some(format!(
-            "Clean file (src/clean.rs) should have no errors. Summary: {:?}",
-            summary
+            "Clean file (src/lib.rs) should have no errors. Summary: {:?}",
+            diagnostics.summary
         ));
 
         if attempt < 3 {
@@ -165,11 +142,47 @@ async fn test_file_diagnostics_clean_file() -> Result<()> {
         }
     }
 
-    // All attempts failed
-    // No need to shutdown with shared client
+    // All attempts failed; the server stops when `workspace` drops
     Err(anyhow::anyhow!(last_error.unwrap()))
 }
 
+#[tokio::test]
+async fn test_errors_clear_when_fixed_mid_session() -> Result<()> {
+    let mut workspace = TempWorkspace::crate_with_errors().start().await?;
+    let lib_path = workspace.file_path("src/lib.rs");
+
+    let mut errors = 0;
+    for _ in 0..20 {
+        errors = workspace
+            .client()
+            .diagnostics(&lib_path)
+            .await?
+            .summary
+            .errors;
+        if errors > 0 {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    }
+    assert!(errors > 0, "Expected the type error in src/lib.rs");
+
+    workspace.write("src/lib.rs", "pub fn fixed() -> u32 {\n    42\n}\n")?;
+    for _ in 0..20 {
+        errors = workspace
+            .client()
+            .diagnostics(&lib_path)
+            .await?
+            .summary
+            .errors;
+        if errors == 0 {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    }
+    assert_eq!(errors, 0, "Errors should clear once the file is fixed");
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_workspace_diagnostics() -> Result<()> {
     let mut client = IpcClient::get_or_create("test-project-diagnostics").await?;
@@ -365,3 +378,142 @@ async fn test_diagnostics_severity_levels() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_workspace_diagnostics_response_size() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project-diagnostics").await?;
+
+    // Pretty vs compact tool text.
+    let pretty = client
+        .call_tool("rust_analyzer_workspace_diagnostics", json!({}))
+        .await?;
+    let compact = client
+        .call_tool(
+            "rust_analyzer_workspace_diagnostics",
+            json!({ "compact": true }),
+        )
+        .await?;
+    let pretty_text = pretty["content"][0]["text"].as_str().unwrap();
+    let compact_text = compact["content"][0]["text"].as_str().unwrap();
+    assert!(compact_text.len() <= pretty_text.len());
+    eprintln!(
+        "workspace diagnostics text: pretty {} bytes, compact {} bytes",
+        pretty_text.len(),
+        compact_text.len()
+    );
+
+    let url = format!(
+        "http://127.0.0.1:{}/api/v1/rust_analyzer_workspace_diagnostics",
+        client.port()
+    );
+    let http = reqwest::Client::new();
+
+    // Compact vs pretty-printed body, read as the bytes sent rather than
+    // through IpcClient, which pretty-prints results again.
+    let compact = http.post(&url).json(&json!({})).send().await?;
+    assert!(compact.status().is_success());
+    let compact = compact.bytes().await?;
+    let pretty = http
+        .post(format!("{}?pretty=true", url))
+        .json(&json!({}))
+        .send()
+        .await?
+        .bytes()
+        .await?;
+    assert_eq!(
+        serde_json::from_slice::<Value>(&compact)?,
+        serde_json::from_slice::<Value>(&pretty)?
+    );
+    assert!(!compact.contains(&b'\n'), "bodies are compact by default");
+    assert!(
+        pretty.contains(&b'\n'),
+        "?pretty=true pretty-prints the body"
+    );
+    eprintln!(
+        "workspace diagnostics body: compact {} bytes, pretty {} bytes",
+        compact.len(),
+        pretty.len()
+    );
+
+    // Identity vs gzip over the wire. reqwest is built without its gzip
+    // feature, so the body is not decoded and its length is the wire size.
+    let plain = http.post(&url).json(&json!({})).send().await?;
+    let plain_len = plain.bytes().await?.len();
+    let gzipped = http
+        .post(&url)
+        .header("Accept-Encoding", "gzip")
+        .json(&json!({}))
+        .send()
+        .await?;
+    let encoding = gzipped
+        .headers()
+        .get("content-encoding")
+        .map(|v| v.to_str().unwrap_or_default().to_string());
+    let gzip_len = gzipped.bytes().await?.len();
+    eprintln!(
+        "workspace diagnostics body: identity {} bytes, gzip {} bytes ({:?})",
+        plain_len, gzip_len, encoding
+    );
+
+    // Tiny bodies are sent uncompressed (and gzip only pays off above a few hundred bytes).
+    if plain_len > 1024 {
+        assert_eq!(encoding.as_deref(), Some("gzip"));
+        assert!(gzip_len < plain_len);
+    }
+
+    Ok(())
+}
+
+/// Error count reported for `file_path`, analyzing `content` instead of the
+/// file on disk when given.
+async fn error_count(
+    client: &mut IpcClient,
+    file_path: &str,
+    content: Option<&str>,
+) -> Result<u64> {
+    let file_path = std::path::Path::new(file_path);
+    let diagnostics = match content {
+        Some(content) => client.diagnostics_for_content(file_path, content).await?,
+        None => client.diagnostics(file_path).await?,
+    };
+    Ok(diagnostics.summary.errors)
+}
+
+#[tokio::test]
+async fn test_diagnostics_for_unsaved_content() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project-diagnostics").await?;
+    let lib_path = client.workspace_path().join("src/lib.rs");
+    let lib_path = lib_path.to_str().unwrap().to_string();
+    let on_disk = std::fs::read_to_string(&lib_path)?;
+    let unsaved = format!(
+        "{}\npub fn unsaved() -> u32 {{ \"not a number\" }}\n",
+        on_disk
+    );
+
+    // The unsaved text has a type error that the file on disk doesn't.
+    let mut unsaved_errors = 0;
+    for _ in 0..20 {
+        unsaved_errors = error_count(&mut client, &lib_path, Some(&unsaved)).await?;
+        if unsaved_errors > 0 {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    }
+    assert!(
+        unsaved_errors > 0,
+        "Expected an error in the unsaved content"
+    );
+
+    // A call without content resyncs the document from disk.
+    let mut disk_errors = u64::MAX;
+    for _ in 0..20 {
+        disk_errors = error_count(&mut client, &lib_path, None).await?;
+        if disk_errors == 0 {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    }
+    assert_eq!(disk_errors, 0, "Document should be resynced from disk");
+
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use anyhow::Result;
+use std::time::{Duration, Instant};
+use test_support::{TempWorkspace, TempWorkspaceBuilder};
+
+/// A clean crate with a second small file, so the measured hover is the
+/// first on that file and pays for opening it.
+fn crate_with_extra_file() -> TempWorkspaceBuilder {
+    TempWorkspace::clean_crate()
+        .file(
+            "src/lib.rs",
+            "pub mod extra;\n\n/// Adds two numbers.\npub fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n",
+        )
+        .file(
+            "src/extra.rs",
+            "/// Doubles a number.\npub fn double(x: i32) -> i32 {\n    x * 2\n}\n",
+        )
+}
+
+/// Latency of a hover on a file not opened before, once indexing is done.
+async fn first_hover_latency(mut workspace: TempWorkspace) -> Result<Duration> {
+    let lib = workspace.file_path("src/lib.rs");
+    let extra = workspace.file_path("src/extra.rs");
+    let client = workspace.client();
+    // Waits out indexing.
+    client.hover(&lib, 3, 7).await?;
+
+    let start = Instant::now();
+    let hover = client.hover(&extra, 1, 7).await?;
+    let latency = start.elapsed();
+    assert!(hover.to_string().contains("double"), "{}", hover);
+    Ok(latency)
+}
+
+#[tokio::test]
+async fn test_document_open_waits_for_readiness_not_a_fixed_delay() -> Result<()> {
+    let fixed = crate_with_extra_file()
+        .file(
+            "rust-analyzer-server.toml",
+            "wait_for_document_ready = false\ndocument_open_delay_millis = 1000\n",
+        )
+        .start()
+        .await?;
+    let fixed = first_hover_latency(fixed).await?;
+
+    let ready = crate_with_extra_file().start().await?;
+    let ready = first_hover_latency(ready).await?;
+
+    eprintln!(
+        "First hover on a small file: {:?} with a fixed 1s delay, {:?} waiting for readiness",
+        fixed, ready
+    );
+    assert!(fixed >= Duration::from_secs(1));
+    assert!(ready < fixed, "{:?} >= {:?}", ready, fixed);
+    Ok(())
+}
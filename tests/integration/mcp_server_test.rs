@@ -3,7 +3,7 @@ use serde_json::{json, Value};
 use std::path::Path;
 
 // Import test support library
-use test_support::{is_ci, timeouts, IpcClient};
+use test_support::{is_ci, timeouts, IpcClient, IsolatedProject, TempWorkspace};
 
 #[tokio::test]
 async fn test_server_initialization() -> Result<()> {
@@ -26,6 +26,20 @@ async fn test_server_initialization() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_owned_server_shuts_down_on_drop() -> Result<()> {
+    let workspace = TempWorkspace::clean_crate().create()?;
+    let mut client = IpcClient::owned(workspace.path()).await?;
+    let port = client.port();
+    let response = client.send_request("tools/list", None).await?;
+    assert!(response.get("tools").is_some());
+
+    // Drop waits for the server to exit.
+    drop(client);
+    assert!(std::net::TcpStream::connect(("127.0.0.1", port)).is_err());
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_all_lsp_tools() -> Result<()> {
     let mut client = IpcClient::get_or_create("test-project-singleton").await?;
@@ -103,7 +117,7 @@ async fn test_workspace_change() -> Result<()> {
     let mut client = IpcClient::get_or_create("test-project").await?;
 
     // Create a second isolated project to switch to
-    let second_project = test_support::IsolatedProject::new()?;
+    let second_project = IsolatedProject::new()?;
     let response = client
         .call_tool(
             "rust_analyzer_set_workspace",
@@ -214,6 +228,117 @@ async fn test_error_handling_invalid_positions() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_error_handling_file_outside_workspace() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+
+    // A copy of the test project lives in a temp dir, outside the server's workspace
+    let other_project = IsolatedProject::new()?;
+    let other_main = other_project.path().join("src/main.rs");
+
+    let result = client
+        .call_tool(
+            "rust_analyzer_hover",
+            json!({
+                "file_path": other_main.to_str().unwrap(),
+                "line": 0,
+                "character": 0
+            }),
+        )
+        .await;
+
+    let Err(e) = result else {
+        return Err(anyhow::anyhow!(
+            "Expected an error for a file outside the workspace"
+        ));
+    };
+    assert!(
+        e.to_string().contains("not inside workspace"),
+        "Unexpected error: {}",
+        e
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_error_handling_other_workspace_path() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+
+    // Naming another workspace inline must not switch the shared server
+    let other_project = IsolatedProject::new()?;
+    let result = client
+        .call_tool(
+            "rust_analyzer_symbols",
+            json!({
+                "file_path": other_project.path().join("src/main.rs").to_str().unwrap(),
+                "workspace_path": other_project.path().to_str().unwrap()
+            }),
+        )
+        .await;
+
+    let Err(e) = result else {
+        return Err(anyhow::anyhow!(
+            "Expected an error for a workspace_path naming another workspace"
+        ));
+    };
+    assert!(
+        e.to_string().contains("is not the server's workspace"),
+        "Unexpected error: {}",
+        e
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_close_document() -> Result<()> {
+    // A server of its own, so no other test reopens the file in between.
+    let mut workspace = TempWorkspace::clean_crate().start().await?;
+    let lib_path = workspace.file_path("src/lib.rs");
+    let file_path = lib_path.to_str().unwrap();
+    let client = workspace.client();
+
+    client
+        .call_tool("rust_analyzer_symbols", json!({ "file_path": file_path }))
+        .await?;
+
+    let mut closed = Vec::new();
+    for _ in 0..2 {
+        let response = client
+            .call_tool(
+                "rust_analyzer_close_document",
+                json!({ "file_path": file_path }),
+            )
+            .await?;
+        let text = response["content"][0]["text"].as_str().unwrap();
+        let parsed: Value = serde_json::from_str(text)?;
+        closed.push(parsed["closed"].as_bool().unwrap());
+    }
+
+    // Open after the symbols call, already closed the second time.
+    assert_eq!(closed, vec![true, false]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_unchanged_document_opened_once() -> Result<()> {
+    let project = IsolatedProject::new_diagnostics()?;
+    let mut client = IpcClient::owned(project.path()).await?;
+    let warnings_path = project.file_path("src/warnings.rs");
+
+    for _ in 0..3 {
+        client.symbols(&warnings_path).await?;
+    }
+
+    let status = client.status().await?;
+    assert_eq!(status["open_documents"]["opened"], 1);
+    assert_eq!(status["open_documents"]["cache_hits"], 2);
+
+    Ok(())
+}
+
 // Helper functions for test_all_lsp_tools
 
 async fn test_symbols(client: &mut IpcClient, workspace_path: &Path) -> Result<()> {
@@ -281,111 +406,22 @@ async fn test_symbols(client: &mut IpcClient, workspace_path: &Path) -> Result<(
 
 async fn test_definition(client: &mut IpcClient, workspace_path: &Path) -> Result<bool> {
     let main_path = workspace_path.join("src/main.rs");
-    let response = client
-        .call_tool(
-            "rust_analyzer_definition",
-            json!({
-                "file_path": main_path.to_str().unwrap(),
-                "line": 1,
-                "character": 18
-            }),
-        )
-        .await?;
-
-    let Some(content) = response.get("content") else {
-        return Ok(false);
-    };
-
-    if !content.is_array() || content[0].is_null() {
-        return Ok(false);
-    }
-
-    let Some(text) = content[0].get("text") else {
-        return Ok(false);
-    };
-
-    let Some(text_str) = text.as_str() else {
-        return Ok(false);
-    };
+    let definitions = client.definition(&main_path, 1, 18).await?;
 
     // null or empty array during initialization is normal for LSP.
     // We just check that we got a valid response.
-    if text_str == "null" || text_str == "[]" {
-        // This is a valid response during initialization.
-        return Ok(true);
-    }
-
-    // Try to parse as array
-    let Ok(definitions) = serde_json::from_str::<Vec<Value>>(text_str) else {
-        return Ok(false);
-    };
-
-    Ok(!definitions.is_empty())
+    Ok(matches!(definitions, Value::Null | Value::Array(_)))
 }
 
 async fn test_references(client: &mut IpcClient, workspace_path: &Path) -> Result<bool> {
     let main_path = workspace_path.join("src/main.rs");
-    let response = client
-        .call_tool(
-            "rust_analyzer_references",
-            json!({
-                "file_path": main_path.to_str().unwrap(),
-                "line": 9,
-                "character": 4
-            }),
-        )
-        .await?;
-
-    let Some(content) = response.get("content") else {
-        return Ok(false);
-    };
-
-    let Some(text) = content[0].get("text") else {
-        return Ok(false);
-    };
-
-    if text.as_str() == Some("null") {
-        return Ok(false);
-    }
-
-    let Some(text_str) = text.as_str() else {
-        return Ok(false);
-    };
-
-    let references: Vec<Value> = serde_json::from_str(text_str)?;
-    Ok(!references.is_empty())
+    let references = client.references(&main_path, 9, 4).await?;
+    Ok(references.as_array().is_some_and(|refs| !refs.is_empty()))
 }
 
 async fn test_hover(client: &mut IpcClient, workspace_path: &Path) -> Result<bool> {
     let main_path = workspace_path.join("src/main.rs");
-    let response = client
-        .call_tool(
-            "rust_analyzer_hover",
-            json!({
-                "file_path": main_path.to_str().unwrap(),
-                "line": 4,
-                "character": 15
-            }),
-        )
-        .await?;
-
-    let Some(content) = response.get("content") else {
-        return Ok(false);
-    };
-
-    let Some(text) = content[0].get("text") else {
-        return Ok(false);
-    };
-
-    if text.as_str() == Some("null") {
-        return Ok(false);
-    }
-
-    let Some(text_str) = text.as_str() else {
-        return Ok(false);
-    };
-
-    let hover: Value = serde_json::from_str(text_str)?;
+    let hover = client.hover(&main_path, 4, 15).await?;
     Ok(hover.get("contents").is_some())
 }
 
@@ -0,0 +1,37 @@
+use anyhow::Result;
+use test_support::{IpcClient, IsolatedProject};
+
+const FIXTURE: &str = include_str!("../fixtures/unicode_positions.rs");
+
+/// Character column of the first `needle` on `line` of the fixture.
+fn char_column(line: usize, needle: &str) -> usize {
+    let text = FIXTURE.lines().nth(line).unwrap();
+    text[..text.find(needle).unwrap()].chars().count()
+}
+
+#[tokio::test]
+async fn test_definition_after_multibyte_characters() -> Result<()> {
+    let project = IsolatedProject::new_diagnostics()?;
+    let lib = project.file_path("src/lib.rs");
+    let mut lib_source = std::fs::read_to_string(&lib)?;
+    lib_source.push_str("\npub mod unicode_positions;\n");
+    std::fs::write(&lib, lib_source)?;
+    let fixture = project.file_path("src/unicode_positions.rs");
+    std::fs::write(&fixture, FIXTURE)?;
+    let mut client = IpcClient::owned(project.path()).await?;
+
+    // The call to `target()` comes after `日本🦀` on line 1.
+    let result = client
+        .definition(&fixture, 1, char_column(1, "target()") as u32)
+        .await?;
+
+    let expected = format!("unicode_positions.rs:0:{}", char_column(0, "target"));
+    let location = result[0]["location"].as_str().unwrap_or_default();
+    assert!(
+        location.ends_with(&expected),
+        "expected a location ending in {}, got {}",
+        expected,
+        result
+    );
+    Ok(())
+}
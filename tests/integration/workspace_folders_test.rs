@@ -0,0 +1,44 @@
+use anyhow::Result;
+use serde_json::json;
+use std::path::Path;
+use test_support::{IpcClient, IsolatedProject};
+
+#[tokio::test]
+async fn test_extra_workspace_folder() -> Result<()> {
+    let project = IsolatedProject::new_diagnostics()?;
+    let mut client = IpcClient::owned(project.path()).await?;
+    // A copy of test-project next to the diagnostics project, outside it.
+    let tool = IsolatedProject::new()?;
+    let tool_path = tool.path().canonicalize()?;
+
+    let result = client
+        .call_tool_json(
+            "rust_analyzer_workspace_folders",
+            json!({ "add": [tool_path.to_str().unwrap()] }),
+        )
+        .await?;
+    assert_eq!(result["added"], json!([tool_path]));
+
+    let status = client.status().await?;
+    let folders = status["workspace_folders"].as_array().unwrap();
+    assert_eq!(folders.len(), 2, "{}", status["workspace_folders"]);
+    assert_eq!(folders[1], json!(tool_path));
+
+    // `src/utils.rs` only exists in the added folder, so the relative path
+    // resolves there instead of being rejected.
+    let symbols = client.symbols(Path::new("src/utils.rs")).await?;
+    assert!(
+        symbols.to_string().contains("process"),
+        "unexpected symbols: {}",
+        symbols
+    );
+
+    let result = client
+        .call_tool_json(
+            "rust_analyzer_workspace_folders",
+            json!({ "remove": [tool_path.to_str().unwrap()] }),
+        )
+        .await?;
+    assert_eq!(result["workspace_folders"].as_array().unwrap().len(), 1);
+    Ok(())
+}
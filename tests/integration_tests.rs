@@ -1,5 +1,11 @@
 mod integration {
+    mod apply_edits_test;
+    mod cargo_toml_test;
+    mod cli_test;
     mod diagnostics;
+    mod document_open_test;
     mod mcp_server_test;
+    mod unicode_positions_test;
+    mod workspace_folders_test;
     // mod shared_test;  // This test file doesn't exist yet
 }
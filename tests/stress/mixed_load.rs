@@ -0,0 +1,41 @@
+use anyhow::Result;
+
+use test_support::{stress, IpcClient, StressCall, StressOptions};
+
+/// Many mixed tool calls at once on one server. Each call's result must be
+/// its own, none may hang, and few may fail. Run on demand with
+/// `cargo test --test stress_tests -- --ignored`.
+#[tokio::test]
+#[ignore = "slow; run on demand and in the nightly workflow"]
+async fn test_mixed_concurrent_calls() -> Result<()> {
+    let client = IpcClient::get_or_create("test-project-concurrent").await?;
+    let main = client.workspace_path().join("src/main.rs");
+    let lib = client.workspace_path().join("src/lib.rs");
+
+    // Positions chosen so no two calls of a tool expect the same text.
+    let calls = [
+        StressCall::at("rust_analyzer_hover", &main, 1, 18, "fn greet"),
+        StressCall::at("rust_analyzer_hover", &main, 4, 15, "struct Calculator"),
+        StressCall::at("rust_analyzer_definition", &main, 1, 18, "main.rs:13:3"),
+        StressCall::at("rust_analyzer_definition", &main, 5, 22, "main.rs:26:7"),
+        StressCall::at("rust_analyzer_references", &main, 13, 3, "main.rs:1:18"),
+        StressCall::file("rust_analyzer_symbols", &main, "Calculator"),
+        StressCall::file("rust_analyzer_symbols", &lib, "Person"),
+    ];
+
+    // Warm up first so indexing doesn't count as contention.
+    for call in &calls {
+        client
+            .connection()
+            .call_tool(&call.tool, call.args.clone())
+            .await?;
+    }
+
+    let options = StressOptions {
+        calls: 100,
+        ..StressOptions::default()
+    };
+    let report = stress(&client, &calls, &options).await?;
+    eprintln!("{}", report);
+    report.check(&options)
+}
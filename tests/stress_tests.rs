@@ -1,3 +1,4 @@
 mod stress {
     mod concurrent_requests;
+    mod mixed_load;
 }
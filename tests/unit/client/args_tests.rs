@@ -0,0 +1,69 @@
+use serde_json::json;
+
+use rust_analyzer_server::client::parse_tool_args;
+
+fn args(pairs: &[&str]) -> Vec<String> {
+    pairs.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn test_numeric_values_are_coerced() {
+    let parsed = parse_tool_args(
+        "hover",
+        &args(&["file_path=src/main.rs", "line=5", "character=10"]),
+    )
+    .unwrap();
+    assert_eq!(
+        parsed,
+        json!({ "file_path": "src/main.rs", "line": 5, "character": 10 })
+    );
+    assert!(parsed["line"].as_u64().is_some());
+}
+
+#[test]
+fn test_booleans_and_strings() {
+    let parsed = parse_tool_args(
+        "rust_analyzer_workspace_symbol",
+        &args(&["compact=true", "query=MyStruct", "empty="]),
+    )
+    .unwrap();
+    assert_eq!(
+        parsed,
+        json!({ "compact": true, "query": "MyStruct", "empty": "" })
+    );
+}
+
+#[test]
+fn test_only_typed_parameters_are_coerced() {
+    // A numeric-looking query is still a string; so are arguments the
+    // schema doesn't know, and values that don't parse as the schema type.
+    let parsed = parse_tool_args(
+        "search",
+        &args(&[
+            "query=123",
+            "other=true",
+            "timeout_secs=30",
+            "wait_for_index=no",
+        ]),
+    )
+    .unwrap();
+    assert_eq!(
+        parsed,
+        json!({ "query": "123", "other": "true", "timeout_secs": 30, "wait_for_index": "no" })
+    );
+
+    let parsed = parse_tool_args("no_such_tool", &args(&["line=5", "compact=false"])).unwrap();
+    assert_eq!(parsed, json!({ "line": "5", "compact": false }));
+}
+
+#[test]
+fn test_value_may_contain_equals() {
+    let parsed = parse_tool_args("workspace_symbol", &args(&["query=a=b"])).unwrap();
+    assert_eq!(parsed["query"], "a=b");
+}
+
+#[test]
+fn test_missing_equals_is_an_error() {
+    let err = parse_tool_args("hover", &args(&["line"])).unwrap_err();
+    assert!(err.to_string().contains("key=value"));
+}
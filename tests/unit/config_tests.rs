@@ -0,0 +1,333 @@
+use serde_json::json;
+
+use std::time::Duration;
+
+use rust_analyzer_server::config::{
+    document_ready_deadline, merge_json, timeout_key, Config, CONFIG_FILE_NAME, DEFAULT_PORT,
+};
+
+#[test]
+fn test_empty_file_uses_defaults() {
+    assert_eq!(Config::from_toml("").unwrap(), Config::default());
+    assert_eq!(Config::default().port, DEFAULT_PORT);
+}
+
+#[test]
+fn test_all_keys() {
+    let config = Config::from_toml(
+        r#"
+        port = 4000
+        bind = "0.0.0.0"
+        extra_workspaces = ["../tool"]
+        indexing_timeout_secs = 120
+        retry_interval_millis = 250
+        document_open_delay_millis = 500
+        wait_for_document_ready = false
+        request_timeout_secs = 45
+        health_ping_timeout_millis = 3000
+        max_open_documents = 20
+        max_diagnostics_files = 300
+        max_diagnostics_mb = 8
+        rust_analyzer_path = "/opt/rust-analyzer"
+        auth_token = "secret"
+        preload = true
+        watch = true
+        prewarm = true
+        prewarm_files = ["crates/core/src/lib.rs"]
+        relative_paths = true
+
+        [initialization_options.cargo]
+        features = ["serde"]
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(config.port, 4000);
+    assert_eq!(config.bind, "0.0.0.0");
+    assert_eq!(
+        config.extra_workspaces,
+        [std::path::PathBuf::from("../tool")]
+    );
+    assert_eq!(config.indexing_timeout().as_secs(), 120);
+    assert_eq!(config.retry_interval().as_millis(), 250);
+    assert_eq!(config.document_open_delay().as_millis(), 500);
+    assert!(!config.wait_for_document_ready);
+    assert_eq!(config.request_timeout().as_secs(), 45);
+    assert_eq!(config.health_ping_timeout().as_millis(), 3000);
+    assert_eq!(config.max_open_documents, 20);
+    assert_eq!(config.max_diagnostics_files, 300);
+    assert_eq!(config.max_diagnostics_mb, 8);
+    assert_eq!(
+        config.rust_analyzer_path.as_deref(),
+        Some(std::path::Path::new("/opt/rust-analyzer"))
+    );
+    assert_eq!(config.auth_token.as_deref(), Some("secret"));
+    assert!(config.preload);
+    assert!(config.watch);
+    assert!(config.prewarm);
+    assert_eq!(
+        config.prewarm_files,
+        [std::path::PathBuf::from("crates/core/src/lib.rs")]
+    );
+    assert!(config.relative_paths);
+    assert_eq!(
+        config.initialization_options,
+        Some(json!({ "cargo": { "features": ["serde"] } }))
+    );
+}
+
+#[test]
+fn test_timeout_keys() {
+    assert_eq!(timeout_key("workspace/symbol"), "workspace_symbol");
+    assert_eq!(timeout_key("textDocument/hover"), "hover");
+    assert_eq!(
+        timeout_key("textDocument/documentSymbol"),
+        "document_symbol"
+    );
+    assert_eq!(timeout_key("codeAction/resolve"), "code_action_resolve");
+}
+
+#[test]
+fn test_per_method_timeouts() {
+    let config = Config::from_toml(
+        r#"
+        request_timeout_secs = 20
+
+        [timeouts]
+        workspace_symbol = 180
+        hover = 5
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        config.request_timeout_for("workspace/symbol"),
+        Duration::from_secs(180)
+    );
+    assert_eq!(
+        config.request_timeout_for("textDocument/hover"),
+        Duration::from_secs(5)
+    );
+    assert_eq!(
+        config.request_timeout_for("textDocument/definition"),
+        Duration::from_secs(20)
+    );
+
+    let err = Config::from_toml("[timeouts]\nhover = 0").unwrap_err();
+    assert!(err.to_string().contains("timeouts.hover"), "{}", err);
+}
+
+#[test]
+fn test_cargo_settings_become_initialization_options() {
+    let config = Config::from_toml(
+        r#"
+        [cargo]
+        features = ["serde", "tokio"]
+        no_default_features = true
+        target = "wasm32-unknown-unknown"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        config.cargo.initialization_options(),
+        json!({
+            "cargo": {
+                "features": ["serde", "tokio"],
+                "noDefaultFeatures": true,
+                "target": "wasm32-unknown-unknown",
+                "buildScripts": { "enable": true },
+                "runBuildScripts": true
+            },
+            "procMacro": { "enable": true }
+        })
+    );
+
+    let config = Config::from_toml("[cargo]\nall_features = true").unwrap();
+    assert_eq!(
+        config.cargo.initialization_options()["cargo"]["features"],
+        json!("all")
+    );
+
+    let err = Config::from_toml("[cargo]\ntarget = \"\"").unwrap_err();
+    assert!(err.to_string().contains("cargo.target"), "{}", err);
+}
+
+#[test]
+fn test_proc_macros_need_build_scripts() {
+    let config = Config::from_toml("[cargo]\nproc_macros = false").unwrap();
+    assert!(!config.cargo.proc_macro_expansion());
+    assert_eq!(
+        config.cargo.initialization_options()["procMacro"]["enable"],
+        json!(false)
+    );
+
+    let config = Config::from_toml("[cargo]\nbuild_scripts = false").unwrap();
+    assert!(!config.cargo.proc_macro_expansion());
+    let options = config.cargo.initialization_options();
+    assert_eq!(options["cargo"]["buildScripts"]["enable"], json!(false));
+    assert_eq!(options["procMacro"]["enable"], json!(false));
+
+    assert!(Config::default().cargo.proc_macro_expansion());
+}
+
+#[test]
+fn test_clippy_check_settings() {
+    let config = Config::from_toml(
+        r#"
+        [check]
+        clippy = true
+        extra_args = ["-W", "clippy::pedantic"]
+        "#,
+    )
+    .unwrap();
+    let options = config.check.initialization_options();
+    for key in ["check", "checkOnSave"] {
+        assert_eq!(options[key]["command"], "clippy");
+        assert_eq!(options[key]["extraArgs"], json!(["-W", "clippy::pedantic"]));
+    }
+    assert_eq!(Config::default().check.command(), "check");
+}
+
+#[test]
+fn test_unknown_key_is_named() {
+    let err = Config::from_toml("prot = 4000").unwrap_err();
+    assert!(err.to_string().contains("prot"), "{}", err);
+}
+
+#[test]
+fn test_wrong_type_is_rejected() {
+    let err = Config::from_toml("port = \"4000\"").unwrap_err();
+    assert!(err.to_string().contains("port"), "{}", err);
+}
+
+#[test]
+fn test_validation_names_key() {
+    let err = Config::from_toml("retry_interval_millis = 0").unwrap_err();
+    assert!(err.to_string().contains("retry_interval_millis"), "{}", err);
+}
+
+#[test]
+fn test_validation_enforces_bounds() {
+    let err = Config::from_toml("indexing_timeout_secs = 100000").unwrap_err();
+    assert!(err.to_string().contains("indexing_timeout_secs"), "{}", err);
+    assert!(Config::from_toml("document_open_delay_millis = 0").is_ok());
+}
+
+#[test]
+fn test_document_open_waits_for_readiness_by_default() {
+    assert!(Config::default().wait_for_document_ready);
+    // The wait allowed grows with the document, up to a cap.
+    assert_eq!(document_ready_deadline(500), Duration::from_millis(100));
+    assert_eq!(
+        document_ready_deadline(512 * 1024),
+        Duration::from_millis(1124)
+    );
+    assert_eq!(document_ready_deadline(100 << 20), Duration::from_secs(10));
+}
+
+#[test]
+fn test_env_overrides_file_values() {
+    let mut config = Config::from_toml("request_timeout_secs = 45").unwrap();
+    config
+        .apply_env_from(|name| match name {
+            "RUST_ANALYZER_REQUEST_TIMEOUT_SECS" => Some("120".to_string()),
+            "RUST_ANALYZER_DOCUMENT_OPEN_DELAY_MILLIS" => Some(" 250 ".to_string()),
+            _ => None,
+        })
+        .unwrap();
+
+    assert_eq!(config.request_timeout_secs, 120);
+    assert_eq!(config.document_open_delay_millis, 250);
+    assert_eq!(
+        config.indexing_timeout_secs,
+        Config::default().indexing_timeout_secs
+    );
+    assert_eq!(config.tunables()["request_timeout_secs"], json!(120));
+}
+
+#[test]
+fn test_env_selects_rust_analyzer_binary() {
+    let mut config = Config::from_toml(r#"rust_analyzer_path = "/opt/old""#).unwrap();
+    config
+        .apply_env_from(|name| (name == "RUST_ANALYZER_BIN").then(|| "/opt/new".to_string()))
+        .unwrap();
+    assert_eq!(
+        config.rust_analyzer_path.as_deref(),
+        Some(std::path::Path::new("/opt/new"))
+    );
+
+    // An empty variable leaves the file's value alone.
+    let mut config = Config::from_toml(r#"rust_analyzer_path = "/opt/old""#).unwrap();
+    config
+        .apply_env_from(|name| (name == "RUST_ANALYZER_BIN").then(String::new))
+        .unwrap();
+    assert_eq!(
+        config.rust_analyzer_path.as_deref(),
+        Some(std::path::Path::new("/opt/old"))
+    );
+}
+
+#[test]
+fn test_env_errors_name_variable() {
+    let not_a_number = Config::default()
+        .apply_env_from(|name| {
+            (name == "RUST_ANALYZER_RETRY_INTERVAL_MILLIS").then(|| "fast".to_string())
+        })
+        .unwrap_err();
+    assert!(
+        not_a_number
+            .to_string()
+            .contains("RUST_ANALYZER_RETRY_INTERVAL_MILLIS"),
+        "{}",
+        not_a_number
+    );
+
+    let out_of_range = Config::default()
+        .apply_env_from(|name| {
+            (name == "RUST_ANALYZER_INDEXING_TIMEOUT_SECS").then(|| "0".to_string())
+        })
+        .unwrap_err();
+    assert!(
+        out_of_range.to_string().contains("out of range"),
+        "{}",
+        out_of_range
+    );
+}
+
+#[test]
+fn test_load_missing_workspace_file_uses_defaults() {
+    let dir = tempfile::tempdir().unwrap();
+    assert_eq!(Config::load(None, dir.path()).unwrap(), Config::default());
+}
+
+#[test]
+fn test_load_workspace_file_and_explicit_path() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join(CONFIG_FILE_NAME), "port = 4001").unwrap();
+    assert_eq!(Config::load(None, dir.path()).unwrap().port, 4001);
+
+    let other = dir.path().join("other.toml");
+    std::fs::write(&other, "port = 4002").unwrap();
+    assert_eq!(Config::load(Some(&other), dir.path()).unwrap().port, 4002);
+
+    // An explicit path must exist.
+    assert!(Config::load(Some(&dir.path().join("missing.toml")), dir.path()).is_err());
+}
+
+#[test]
+fn test_merge_json_is_recursive() {
+    let mut base = json!({
+        "cargo": { "buildScripts": { "enable": true } },
+        "procMacro": { "enable": true }
+    });
+    merge_json(
+        &mut base,
+        &json!({ "cargo": { "features": ["x"] }, "procMacro": { "enable": false } }),
+    );
+    assert_eq!(
+        base,
+        json!({
+            "cargo": { "buildScripts": { "enable": true }, "features": ["x"] },
+            "procMacro": { "enable": false }
+        })
+    );
+}
@@ -0,0 +1,61 @@
+use serde_json::{json, Value};
+
+use rust_analyzer_server::diagnostics::{format_diagnostics, DiagnosticFilter};
+
+fn sample() -> Value {
+    json!([
+        { "severity": 1, "message": "mismatched types", "code": "E0308", "source": "rustc" },
+        {
+            "severity": 2,
+            "message": "unneeded `return` statement",
+            "code": "clippy::needless_return",
+            "source": "clippy"
+        },
+        { "severity": 2, "message": "unused variable: `x`", "code": "unused_variables", "source": "rustc" },
+        { "severity": 4, "message": "consider removing", "source": "rust-analyzer" }
+    ])
+}
+
+fn codes(diagnostics: &Value) -> Vec<Value> {
+    diagnostics
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|d| d["code"].clone())
+        .collect()
+}
+
+#[test]
+fn test_code_prefix_selects_clippy_lints() {
+    let filter = DiagnosticFilter::from_args(&json!({ "code": "clippy::" })).unwrap();
+    assert_eq!(
+        codes(&filter.apply(&sample())),
+        vec![json!("clippy::needless_return")]
+    );
+}
+
+#[test]
+fn test_severity_keeps_more_severe() {
+    let filter = DiagnosticFilter::from_args(&json!({ "severity": "warning" })).unwrap();
+    assert_eq!(filter.apply(&sample()).as_array().unwrap().len(), 3);
+
+    let filter = DiagnosticFilter::from_args(&json!({ "severity": "error" })).unwrap();
+    assert_eq!(codes(&filter.apply(&sample())), vec![json!("E0308")]);
+}
+
+#[test]
+fn test_unknown_severity_is_rejected() {
+    let err = DiagnosticFilter::from_args(&json!({ "severity": "fatal" })).unwrap_err();
+    assert!(err.to_string().contains("fatal"), "{}", err);
+}
+
+#[test]
+fn test_formatting_keeps_lint_name_and_source() {
+    let filter = DiagnosticFilter::from_args(&json!({ "code": "clippy::" })).unwrap();
+    let formatted = format_diagnostics("src/lib.rs", &filter.apply(&sample()));
+    let diagnostic = &formatted["diagnostics"][0];
+    assert_eq!(diagnostic["code"], "clippy::needless_return");
+    assert_eq!(diagnostic["source"], "clippy");
+    assert_eq!(diagnostic["severity"], "warning");
+    assert_eq!(formatted["summary"]["warnings"], 1);
+}
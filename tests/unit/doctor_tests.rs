@@ -0,0 +1,62 @@
+use rust_analyzer_server::doctor::{
+    check_port, check_workspace_manifest, check_workspace_writable, has_failures, CheckStatus,
+};
+
+#[test]
+fn test_workspace_manifest_found() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("Cargo.toml"), "[package]\nname = \"x\"\n").unwrap();
+
+    let check = check_workspace_manifest(dir.path());
+    assert_eq!(check.status, CheckStatus::Pass);
+    assert!(check.hint.is_none());
+}
+
+#[test]
+fn test_workspace_manifest_missing() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let check = check_workspace_manifest(dir.path());
+    assert_eq!(check.status, CheckStatus::Fail);
+    assert!(check.hint.is_some());
+    assert!(has_failures(&[check]));
+}
+
+#[test]
+fn test_workspace_not_a_directory() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let check = check_workspace_manifest(&dir.path().join("missing"));
+    assert_eq!(check.status, CheckStatus::Fail);
+}
+
+#[test]
+fn test_workspace_writable_leaves_no_file_behind() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let check = check_workspace_writable(dir.path());
+    assert_eq!(check.status, CheckStatus::Pass);
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[tokio::test]
+async fn test_port_free() {
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    assert_eq!(check_port(port).await.status, CheckStatus::Pass);
+}
+
+#[tokio::test]
+async fn test_port_taken_by_another_program() {
+    // Bound but never answers HTTP.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let check = check_port(port).await;
+    assert_eq!(check.status, CheckStatus::Fail);
+    assert!(check.hint.unwrap().contains("--port"));
+}
@@ -0,0 +1,108 @@
+use serde_json::json;
+use std::time::Duration;
+
+use rust_analyzer_server::{
+    http::{sanitize_args, CallHistory, CallRecord},
+    protocol::{ContentItem, ErrorCode, ToolError, ToolResult},
+};
+
+fn answer(text: &str) -> anyhow::Result<ToolResult> {
+    Ok(ToolResult {
+        content: vec![ContentItem {
+            content_type: "text".to_string(),
+            text: text.to_string(),
+        }],
+    })
+}
+
+fn failure() -> anyhow::Result<ToolResult> {
+    Err(ToolError::new(ErrorCode::InvalidParams, "Missing line").into())
+}
+
+#[test]
+fn test_records_outcome_and_size() {
+    let args = json!({ "file_path": "src/lib.rs" });
+    let ok = CallRecord::new(
+        "rust_analyzer_hover",
+        args.clone(),
+        Duration::from_millis(12),
+        &answer("{}"),
+    );
+    assert_eq!(ok.outcome, "ok");
+    assert_eq!(ok.response_bytes, 2);
+    assert_eq!(ok.duration_ms, 12);
+    assert!(ok.error.is_none());
+
+    let failed = CallRecord::new("rust_analyzer_hover", args, Duration::ZERO, &failure());
+    assert_eq!(failed.outcome, "invalid_params");
+    assert_eq!(failed.error.as_deref(), Some("Missing line"));
+    assert_eq!(failed.response_bytes, 0);
+}
+
+#[test]
+fn test_long_argument_values_are_cut() {
+    let content = "x".repeat(1000);
+    let args = sanitize_args(&json!({
+        "file_path": "src/lib.rs",
+        "content": content,
+        "nested": [{ "text": content }],
+        "line": 3
+    }));
+
+    assert_eq!(args["file_path"], "src/lib.rs");
+    assert_eq!(args["line"], 3);
+    let cut = args["content"].as_str().unwrap();
+    assert!(cut.len() < 300, "{}", cut.len());
+    assert!(cut.ends_with("… (1000 chars)"), "{}", cut);
+    assert_eq!(args["nested"][0]["text"], args["content"]);
+}
+
+#[test]
+fn test_keeps_the_most_recent_calls() {
+    let mut history = CallHistory::new(2);
+    for tool in ["a", "b", "c"] {
+        history.push(CallRecord::new(
+            tool,
+            json!({}),
+            Duration::ZERO,
+            &answer(""),
+        ));
+    }
+
+    let tools: Vec<&str> = history
+        .query(None, false)
+        .iter()
+        .map(|call| call.tool.as_str())
+        .collect();
+    assert_eq!(tools, ["b", "c"]);
+}
+
+#[test]
+fn test_filters_by_tool_and_errors() {
+    let mut history = CallHistory::new(10);
+    let hover = "rust_analyzer_hover";
+    history.push(CallRecord::new(
+        hover,
+        json!({}),
+        Duration::ZERO,
+        &answer(""),
+    ));
+    history.push(CallRecord::new(
+        hover,
+        json!({}),
+        Duration::ZERO,
+        &failure(),
+    ));
+    history.push(CallRecord::new(
+        "rust_analyzer_definition",
+        json!({}),
+        Duration::ZERO,
+        &failure(),
+    ));
+
+    assert_eq!(history.query(Some(hover), false).len(), 2);
+    assert_eq!(history.query(None, true).len(), 2);
+    let failed_hovers = history.query(Some(hover), true);
+    assert_eq!(failed_hovers.len(), 1);
+    assert_eq!(failed_hovers[0].outcome, "invalid_params");
+}
@@ -0,0 +1,142 @@
+use serde_json::Value;
+
+use rust_analyzer_server::http::openapi_spec;
+
+const HTTP_METHODS: &[&str] = &[
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+/// Resolve a local `#/components/...` reference against the document.
+fn resolve_ref<'a>(spec: &'a Value, reference: &str) -> Option<&'a Value> {
+    let pointer = reference.strip_prefix('#')?;
+    spec.pointer(pointer)
+}
+
+/// Collect every `$ref` string in a JSON value.
+fn collect_refs(value: &Value, refs: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, v) in map {
+                if key == "$ref" {
+                    if let Some(s) = v.as_str() {
+                        refs.push(s.to_string());
+                    }
+                } else {
+                    collect_refs(v, refs);
+                }
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|v| collect_refs(v, refs)),
+        _ => {}
+    }
+}
+
+#[test]
+fn test_openapi_document_structure() {
+    let spec = openapi_spec();
+
+    // Round-trip through text to make sure it's a well-formed JSON document
+    let text = serde_json::to_string(&spec).unwrap();
+    let spec: Value = serde_json::from_str(&text).unwrap();
+
+    let version = spec["openapi"].as_str().expect("openapi version");
+    assert!(
+        version.starts_with("3.0."),
+        "Unexpected version: {}",
+        version
+    );
+    assert!(spec["info"]["title"].is_string());
+    assert!(spec["info"]["version"].is_string());
+
+    let paths = spec["paths"].as_object().expect("paths object");
+    assert!(!paths.is_empty());
+
+    for (path, item) in paths {
+        assert!(path.starts_with('/'), "Path must start with '/': {}", path);
+        let item = item.as_object().expect("path item object");
+        assert!(!item.is_empty(), "Path {} has no operations", path);
+
+        for (method, op) in item {
+            assert!(
+                HTTP_METHODS.contains(&method.as_str()),
+                "Invalid method {} on {}",
+                method,
+                path
+            );
+            let responses = op["responses"].as_object().expect("responses object");
+            assert!(
+                !responses.is_empty(),
+                "{} {} has no responses",
+                method,
+                path
+            );
+            for response in responses.values() {
+                assert!(response["description"].is_string());
+            }
+            if let Some(body) = op.get("requestBody") {
+                assert!(body["content"]["application/json"]["schema"].is_object());
+            }
+        }
+    }
+
+    // Every reference must resolve within the document
+    let mut refs = Vec::new();
+    collect_refs(&spec, &mut refs);
+    assert!(!refs.is_empty());
+    for reference in refs {
+        assert!(
+            resolve_ref(&spec, &reference).is_some(),
+            "Unresolved $ref: {}",
+            reference
+        );
+    }
+}
+
+#[test]
+fn test_openapi_includes_tools() {
+    let spec = openapi_spec();
+
+    for tool in [
+        "rust_analyzer_hover",
+        "rust_analyzer_definition",
+        "rust_analyzer_workspace_symbol",
+    ] {
+        let op = &spec["paths"][format!("/api/v1/{}", tool)]["post"];
+        assert_eq!(op["operationId"], tool);
+        let schema = &op["requestBody"]["content"]["application/json"]["schema"];
+        assert_eq!(
+            schema["type"], "object",
+            "{} should use its input schema",
+            tool
+        );
+    }
+
+    let query = &spec["paths"]["/api/v1/rust_analyzer_workspace_symbol"]["post"]["requestBody"]
+        ["content"]["application/json"]["schema"]["properties"]["query"];
+    assert_eq!(query["type"], "string");
+}
+
+#[test]
+fn test_openapi_get_for_file_scoped_tools() {
+    let spec = openapi_spec();
+
+    let get = &spec["paths"]["/api/v1/rust_analyzer_diagnostics"]["get"];
+    assert_eq!(get["operationId"], "get_rust_analyzer_diagnostics");
+    assert!(get.get("requestBody").is_none());
+    assert!(get["responses"]["304"].is_object());
+    assert!(get["responses"]["200"]["headers"]["ETag"].is_object());
+    let parameters = get["parameters"].as_array().unwrap();
+    let file_path = parameters
+        .iter()
+        .find(|parameter| parameter["name"] == "file_path")
+        .unwrap();
+    assert_eq!(file_path["in"], "query");
+    assert_eq!(file_path["required"], true);
+    assert!(parameters
+        .iter()
+        .all(|parameter| parameter["name"] != "content"));
+
+    assert!(spec["paths"]["/api/v1/rust_analyzer_hover"]
+        .get("get")
+        .is_none());
+}
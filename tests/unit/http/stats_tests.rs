@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+use rust_analyzer_server::http::CallStats;
+
+#[test]
+fn test_counts_calls_errors_and_slow_calls() {
+    let mut stats = CallStats::new(Duration::from_millis(100));
+    assert!(!stats.record("rust_analyzer_hover", Duration::from_millis(10), true));
+    assert!(stats.record("rust_analyzer_hover", Duration::from_millis(100), false));
+    assert!(!stats.record("rust_analyzer_definition", Duration::from_millis(5), true));
+
+    let json = stats.to_json();
+    assert_eq!(json["slow_call_millis"], 100);
+    let hover = &json["tools"]["rust_analyzer_hover"];
+    assert_eq!(hover["calls"], 2);
+    assert_eq!(hover["errors"], 1);
+    assert_eq!(hover["slow"], 1);
+    assert_eq!(json["tools"]["rust_analyzer_definition"]["errors"], 0);
+}
+
+#[test]
+fn test_latency_percentiles() {
+    let mut stats = CallStats::new(Duration::from_secs(60));
+    for millis in (1..=100).rev() {
+        stats.record("rust_analyzer_hover", Duration::from_millis(millis), true);
+    }
+
+    let hover = &stats.to_json()["tools"]["rust_analyzer_hover"];
+    assert_eq!(hover["p50_ms"], 50.0);
+    assert_eq!(hover["p95_ms"], 95.0);
+    assert_eq!(hover["max_ms"], 100.0);
+}
+
+#[test]
+fn test_percentiles_cover_only_recent_calls() {
+    let mut stats = CallStats::new(Duration::from_secs(60));
+    let window = stats.to_json()["window"].as_u64().unwrap();
+    stats.record("rust_analyzer_hover", Duration::from_secs(10), true);
+    for _ in 0..window {
+        stats.record("rust_analyzer_hover", Duration::from_millis(1), true);
+    }
+
+    let hover = &stats.to_json()["tools"]["rust_analyzer_hover"];
+    assert_eq!(hover["calls"], window + 1);
+    assert_eq!(hover["max_ms"], 1.0);
+}
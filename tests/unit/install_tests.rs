@@ -0,0 +1,566 @@
+use serde_json::Value;
+use std::fs;
+
+use rust_analyzer_server::{
+    config::DEFAULT_PORT,
+    install::{
+        apply_install, available_skills, describe_plan, describe_verification,
+        diagnostics_hook_entry, generate_skill, install_skills, mcp_server_entry, plan_install,
+        short_diff, strip_claude_md_section, strip_jsonc, uninstall_skills, verify_plan,
+        vscode_tasks, FileAction, FileState, InstallOptions, MANIFEST_FILE, MCP_SERVER_NAME,
+    },
+    mcp::tools::get_tools,
+};
+
+/// `ra-hover.md` as 0.3.0 installed it.
+const SHIPPED_HOVER: &str = include_str!("../fixtures/skills-0.3.0/ra-hover.md");
+
+#[test]
+fn test_dry_run_plans_without_writing() {
+    let dir = tempfile::tempdir().unwrap();
+    let plan = plan_install(dir.path(), &InstallOptions::default()).unwrap();
+    assert!(plan.skills.iter().all(|s| s.action() == FileAction::Create));
+    assert_eq!(
+        plan.claude_md.as_ref().unwrap().action(),
+        FileAction::Create
+    );
+    let report = describe_plan(&plan);
+    assert!(report.contains("Would create"), "{}", report);
+    assert!(!dir.path().join(".claude").exists());
+    assert!(!dir.path().join("CLAUDE.md").exists());
+
+    apply_install(&plan).unwrap();
+    let plan = plan_install(dir.path(), &InstallOptions::default()).unwrap();
+    assert!(plan
+        .skills
+        .iter()
+        .all(|s| s.action() == FileAction::Unchanged));
+    assert_eq!(
+        plan.claude_md.as_ref().unwrap().action(),
+        FileAction::Unchanged
+    );
+}
+
+#[test]
+fn test_dry_run_shows_local_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    install_skills(dir.path()).unwrap();
+    let hover = dir.path().join(".claude/commands/ra-hover.md");
+    let mut content = fs::read_to_string(&hover).unwrap();
+    content.push_str("Always mention the crate version.\n");
+    fs::write(&hover, &content).unwrap();
+
+    let plan = plan_install(dir.path(), &InstallOptions::default()).unwrap();
+    let planned = plan.skills.iter().find(|s| s.path == hover).unwrap();
+    assert_eq!(planned.action(), FileAction::Overwrite);
+    let report = describe_plan(&plan);
+    assert!(
+        report.contains("- Always mention the crate version."),
+        "{}",
+        report
+    );
+    // Nothing was written.
+    assert_eq!(fs::read_to_string(&hover).unwrap(), content);
+}
+
+#[test]
+fn test_short_diff() {
+    assert_eq!(short_diff("a\nb\nc\n", "a\nB\nc\n", 8), ["- b", "+ B"]);
+    assert!(short_diff("a\n", "a\n", 8).is_empty());
+    let diff = short_diff("", "1\n2\n3\n4\n", 3);
+    assert_eq!(diff, ["+ 1", "+ 2", "... 2 more changed lines"]);
+}
+
+#[test]
+fn test_only_installs_the_named_skills() {
+    let dir = tempfile::tempdir().unwrap();
+    let options = InstallOptions {
+        only: vec!["ra-diagnostics".to_string(), "ra-hover.md".to_string()],
+        ..InstallOptions::default()
+    };
+    apply_install(&plan_install(dir.path(), &options).unwrap()).unwrap();
+
+    let mut installed: Vec<_> = fs::read_dir(dir.path().join(".claude/commands"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    installed.sort();
+    assert_eq!(installed, ["ra-diagnostics.md", "ra-hover.md"]);
+
+    let claude_md = fs::read_to_string(dir.path().join("CLAUDE.md")).unwrap();
+    assert!(claude_md.contains("| `/ra-hover` |"));
+    assert!(!claude_md.contains("/ra-references"));
+    // Workflow steps for missing skills are dropped and the rest renumbered.
+    assert!(claude_md.contains("3. /ra-diagnostics"), "{}", claude_md);
+}
+
+#[test]
+fn test_unknown_skill_names_are_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let options = InstallOptions {
+        except: vec!["ra-hovr".to_string()],
+        ..InstallOptions::default()
+    };
+    let error = plan_install(dir.path(), &options).unwrap_err().to_string();
+    assert!(error.contains("Unknown skill `ra-hovr`"), "{}", error);
+    assert!(error.contains("ra-hover, "), "{}", error);
+
+    let options = InstallOptions {
+        only: vec!["ra-hover".to_string()],
+        except: vec!["ra-hover".to_string()],
+        ..InstallOptions::default()
+    };
+    assert!(plan_install(dir.path(), &options).is_err());
+}
+
+#[test]
+fn test_custom_destination_without_claude_md() {
+    let dir = tempfile::tempdir().unwrap();
+    let options = InstallOptions {
+        dest: Some("docs/agent-skills".into()),
+        no_claude_md: true,
+        ..InstallOptions::default()
+    };
+    let plan = plan_install(dir.path(), &options).unwrap();
+    assert_eq!(plan.commands_dir, dir.path().join("docs/agent-skills"));
+    assert!(plan.claude_md.is_none());
+
+    apply_install(&plan).unwrap();
+    assert!(dir.path().join("docs/agent-skills/ra-hover.md").exists());
+    assert!(!dir.path().join(".claude/commands").exists());
+    assert!(!dir.path().join("CLAUDE.md").exists());
+}
+
+#[test]
+fn test_port_and_workspace_are_templated() {
+    let dir = tempfile::tempdir().unwrap();
+    install_skills(dir.path()).unwrap();
+    let hover = dir.path().join(".claude/commands/ra-hover.md");
+    assert!(fs::read_to_string(&hover)
+        .unwrap()
+        .contains(r#"PORT="${PORT:-15423}""#));
+
+    // Installing again on another port updates the skills and the section.
+    let options = InstallOptions {
+        port: Some(4000),
+        workspace: Some("/work/my project".into()),
+        ..InstallOptions::default()
+    };
+    apply_install(&plan_install(dir.path(), &options).unwrap()).unwrap();
+    let hover = fs::read_to_string(&hover).unwrap();
+    assert!(hover.contains(r#"PORT="${PORT:-4000}""#), "{}", hover);
+    assert!(hover.contains("--workspace '/work/my project' --port 4000"));
+    assert!(!hover.contains("{{"));
+
+    let claude_md = fs::read_to_string(dir.path().join("CLAUDE.md")).unwrap();
+    assert!(claude_md.contains("${RUST_ANALYZER_PORT:-4000}"));
+    assert!(claude_md.contains("--workspace '/work/my project' --port 4000"));
+    assert!(!claude_md.contains("15423"), "{}", claude_md);
+    assert_eq!(
+        claude_md.matches("<!-- rust-analyzer-server -->").count(),
+        1
+    );
+}
+
+#[test]
+fn test_verify_reports_missing_and_modified_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let options = InstallOptions {
+        only: vec!["ra-hover".to_string(), "ra-search".to_string()],
+        ..InstallOptions::default()
+    };
+    apply_install(&plan_install(dir.path(), &options).unwrap()).unwrap();
+    let entries = verify_plan(&plan_install(dir.path(), &options).unwrap());
+    assert!(entries.iter().all(|e| e.state == FileState::UpToDate));
+    assert!(describe_verification(&entries).contains("All 3 checked files are up to date"));
+
+    let commands = dir.path().join(".claude/commands");
+    fs::write(commands.join("ra-hover.md"), "old\n").unwrap();
+    fs::remove_file(commands.join("ra-search.md")).unwrap();
+    let claude_md = dir.path().join("CLAUDE.md");
+    fs::write(&claude_md, "# CLAUDE.md\n").unwrap();
+
+    let plan = plan_install(dir.path(), &options).unwrap();
+    let states: Vec<FileState> = verify_plan(&plan).iter().map(|e| e.state).collect();
+    assert_eq!(
+        states,
+        [FileState::Modified, FileState::Missing, FileState::Missing]
+    );
+    let report = describe_verification(&verify_plan(&plan));
+    assert!(report.contains("modified    "), "{}", report);
+    assert!(report.contains("(rust-analyzer section)"), "{}", report);
+    assert!(
+        report.contains("3 of 3 checked files are out of date"),
+        "{}",
+        report
+    );
+
+    // Fixing is installing the same plan; the local edit needs --force.
+    apply_install(&plan).unwrap();
+    let entries = verify_plan(&plan_install(dir.path(), &options).unwrap());
+    assert_eq!(entries[0].state, FileState::Modified);
+    assert!(entries[1..].iter().all(|e| e.state == FileState::UpToDate));
+    let options = InstallOptions {
+        force: true,
+        ..options
+    };
+    apply_install(&plan_install(dir.path(), &options).unwrap()).unwrap();
+    let entries = verify_plan(&plan_install(dir.path(), &options).unwrap());
+    assert!(entries.iter().all(|e| e.state == FileState::UpToDate));
+}
+
+#[test]
+fn test_locally_modified_skills_are_kept() {
+    let dir = tempfile::tempdir().unwrap();
+    install_skills(dir.path()).unwrap();
+    let commands = dir.path().join(".claude/commands");
+    let diagnostics = commands.join("ra-diagnostics.md");
+    let edited = format!(
+        "{}\nProject example\n",
+        fs::read_to_string(&diagnostics).unwrap()
+    );
+    fs::write(&diagnostics, &edited).unwrap();
+
+    let options = InstallOptions {
+        port: Some(4000),
+        ..InstallOptions::default()
+    };
+    let plan = plan_install(dir.path(), &options).unwrap();
+    let states: Vec<FileState> = verify_plan(&plan).iter().map(|e| e.state).collect();
+    assert_eq!(states[1], FileState::Outdated);
+    assert_eq!(states[5], FileState::Modified);
+    apply_install(&plan).unwrap();
+
+    // The edit survives and the new version is written next to it.
+    assert_eq!(fs::read_to_string(&diagnostics).unwrap(), edited);
+    let new = fs::read_to_string(commands.join("ra-diagnostics.md.new")).unwrap();
+    assert!(new.contains("4000"));
+    assert!(fs::read_to_string(commands.join("ra-hover.md"))
+        .unwrap()
+        .contains("4000"));
+
+    // Uninstalling leaves the edited file but removes the rest.
+    let report = uninstall_skills(dir.path()).unwrap();
+    assert_eq!(report.kept, std::slice::from_ref(&diagnostics));
+    assert!(report
+        .removed
+        .contains(&commands.join("ra-diagnostics.md.new")));
+    assert!(!dir.path().join(MANIFEST_FILE).exists());
+
+    let options = InstallOptions {
+        force: true,
+        ..InstallOptions::default()
+    };
+    apply_install(&plan_install(dir.path(), &options).unwrap()).unwrap();
+    assert_ne!(fs::read_to_string(&diagnostics).unwrap(), edited);
+}
+
+#[test]
+fn test_mcp_json_keeps_other_servers() {
+    let dir = tempfile::tempdir().unwrap();
+    let mcp_json = dir.path().join(".mcp.json");
+    fs::write(
+        &mcp_json,
+        r#"{"mcpServers": {"other": {"command": "other-server"}}, "extra": true}"#,
+    )
+    .unwrap();
+    let options = InstallOptions {
+        mcp: true,
+        ..InstallOptions::default()
+    };
+    let plan = plan_install(dir.path(), &options).unwrap();
+    assert!(describe_plan(&plan).contains("Would add the MCP server entry"));
+    apply_install(&plan).unwrap();
+
+    let config: Value = serde_json::from_str(&fs::read_to_string(&mcp_json).unwrap()).unwrap();
+    assert_eq!(config["mcpServers"]["other"]["command"], "other-server");
+    assert_eq!(config["extra"], true);
+    let entry = &config["mcpServers"][MCP_SERVER_NAME];
+    assert_eq!(entry, &mcp_server_entry(dir.path(), DEFAULT_PORT));
+    assert_eq!(entry["args"][0], "--stdio");
+    // The port matches the one CLAUDE.md gives the skills.
+    let claude_md = fs::read_to_string(dir.path().join("CLAUDE.md")).unwrap();
+    let port = entry["args"][4].as_str().unwrap();
+    assert!(
+        claude_md.contains(&format!(":-{}}}", port)),
+        "{}",
+        claude_md
+    );
+
+    // Installing again is a no-op; a file that isn't an object is left alone.
+    let plan = plan_install(dir.path(), &options).unwrap();
+    assert_eq!(plan.mcp_json.unwrap().action(), FileAction::Unchanged);
+    fs::write(&mcp_json, "[]").unwrap();
+    assert!(plan_install(dir.path(), &options).is_err());
+}
+
+#[test]
+fn test_vscode_tasks_merge_into_commented_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let vscode = dir.path().join(".vscode");
+    fs::create_dir(&vscode).unwrap();
+    fs::write(
+        vscode.join("tasks.json"),
+        r#"// Build tasks
+{
+    "version": "2.0.0",
+    "tasks": [
+        { "label": "build", "command": "cargo build" }, /* ours */
+        { "label": "Stop rust-analyzer-server", "command": "old" },
+    ],
+}"#,
+    )
+    .unwrap();
+    let options = InstallOptions {
+        vscode: true,
+        ..InstallOptions::default()
+    };
+    let plan = plan_install(dir.path(), &options).unwrap();
+    let report = describe_plan(&plan);
+    assert!(
+        report.contains("Would add the rust-analyzer-server tasks"),
+        "{}",
+        report
+    );
+    assert!(
+        report.contains("-       \"command\": \"old\","),
+        "{}",
+        report
+    );
+    apply_install(&plan).unwrap();
+
+    let tasks: Value =
+        serde_json::from_str(&fs::read_to_string(vscode.join("tasks.json")).unwrap()).unwrap();
+    let labels: Vec<&str> = tasks["tasks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|task| task["label"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        labels,
+        [
+            "build",
+            "Stop rust-analyzer-server",
+            "Start rust-analyzer-server"
+        ]
+    );
+    assert_eq!(tasks["tasks"][1], vscode_tasks(None, DEFAULT_PORT)[1]);
+
+    let settings: Value =
+        serde_json::from_str(&fs::read_to_string(vscode.join("settings.json")).unwrap()).unwrap();
+    assert_eq!(
+        settings["terminal.integrated.env.linux"]["RUST_ANALYZER_PORT"],
+        "15423"
+    );
+}
+
+#[test]
+fn test_hooks_merge_into_claude_settings() {
+    let dir = tempfile::tempdir().unwrap();
+    let claude = dir.path().join(".claude");
+    fs::create_dir(&claude).unwrap();
+    fs::write(
+        claude.join("settings.json"),
+        r#"{
+    "permissions": { "allow": ["Bash(cargo test)"] },
+    "hooks": {
+        "PostToolUse": [{ "matcher": "Bash", "hooks": [{ "type": "command", "command": "true" }] }]
+    }
+}"#,
+    )
+    .unwrap();
+    let options = InstallOptions {
+        hooks: true,
+        port: Some(4000),
+        ..InstallOptions::default()
+    };
+    let plan = plan_install(dir.path(), &options).unwrap();
+    apply_install(&plan).unwrap();
+
+    let settings: Value =
+        serde_json::from_str(&fs::read_to_string(claude.join("settings.json")).unwrap()).unwrap();
+    assert_eq!(settings["permissions"]["allow"][0], "Bash(cargo test)");
+    let post_tool_use = settings["hooks"]["PostToolUse"].as_array().unwrap();
+    assert_eq!(post_tool_use.len(), 2);
+    assert_eq!(post_tool_use[0]["matcher"], "Bash");
+    assert_eq!(post_tool_use[1], diagnostics_hook_entry());
+
+    let script_path = claude.join("hooks/ra-diagnostics.sh");
+    let script = fs::read_to_string(&script_path).unwrap();
+    assert!(script.contains("PORT=\"${PORT:-4000}\""), "{}", script);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&script_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o111, 0o111);
+    }
+
+    // Installing again changes nothing.
+    let plan = plan_install(dir.path(), &options).unwrap();
+    assert_eq!(
+        plan.claude_settings.unwrap().action(),
+        FileAction::Unchanged
+    );
+    assert_eq!(plan.hook_script.unwrap().action(), FileAction::Unchanged);
+}
+
+#[test]
+fn test_every_tool_has_a_skill() {
+    let skills = available_skills();
+    let tools = get_tools();
+    for tool in &tools {
+        assert!(
+            skills.iter().any(|skill| skill.tools.contains(&tool.name)),
+            "no skill calls {}",
+            tool.name
+        );
+    }
+    // Hand-written skills only name tools that exist, so a renamed tool
+    // doesn't leave one behind.
+    for skill in skills.iter().filter(|skill| !skill.generated) {
+        for name in &skill.tools {
+            assert!(
+                tools.iter().any(|tool| &tool.name == name),
+                "{} names unknown tool {}",
+                skill.name,
+                name
+            );
+        }
+    }
+    let mut names: Vec<&str> = skills.iter().map(|skill| skill.name.as_str()).collect();
+    names.sort();
+    names.dedup();
+    assert_eq!(names.len(), skills.len());
+}
+
+#[test]
+fn test_generated_skill() {
+    let tool = get_tools()
+        .into_iter()
+        .find(|tool| tool.name == "rust_analyzer_parent_module")
+        .unwrap();
+    let skill = generate_skill(&tool);
+    assert_eq!(skill.name, "ra-parent-module");
+    assert!(skill.generated);
+    assert!(
+        skill
+            .content
+            .contains("Usage: /ra-parent-module <file_path> <line> <character>"),
+        "{}",
+        skill.content
+    );
+    assert!(skill
+        .content
+        .contains("| `file_path` | string | yes | Path to the Rust file |"));
+    assert!(skill.content.contains("| `content` | string | no |"));
+    assert!(skill
+        .content
+        .contains("/api/v1/rust_analyzer_parent_module"));
+    assert!(skill.content.contains(
+        r#"-d "{\"file_path\":\"$FILE_PATH\",\"line\":$LINE,\"character\":$CHARACTER}""#
+    ));
+    assert!(skill.content.contains("{{PORT}}"));
+
+    // Generated skills are installed and listed in CLAUDE.md.
+    let dir = tempfile::tempdir().unwrap();
+    install_skills(dir.path()).unwrap();
+    assert!(dir
+        .path()
+        .join(".claude/commands/ra-parent-module.md")
+        .exists());
+    let claude_md = fs::read_to_string(dir.path().join("CLAUDE.md")).unwrap();
+    assert!(claude_md.contains("| `/ra-parent-module` | Navigate to parent module declaration |"));
+}
+
+#[test]
+fn test_strip_jsonc() {
+    assert_eq!(
+        strip_jsonc("{\"a\": \"//not a comment\", // comment\n\"b\": [1, 2,],}"),
+        "{\"a\": \"//not a comment\", \n\"b\": [1, 2]}"
+    );
+    assert_eq!(strip_jsonc(r#"{"a\"/*": 1 /* x */}"#), r#"{"a\"/*": 1 }"#);
+}
+
+#[test]
+fn test_uninstall_removes_only_our_files() {
+    let dir = tempfile::tempdir().unwrap();
+    install_skills(dir.path()).unwrap();
+    let commands = dir.path().join(".claude/commands");
+    fs::write(commands.join("my-command.md"), "mine").unwrap();
+    fs::remove_file(commands.join("ra-impact.md")).unwrap();
+
+    let report = uninstall_skills(dir.path()).unwrap();
+    assert!(report.section_removed);
+    assert_eq!(report.skipped, [commands.join("ra-impact.md")]);
+    // The other skills, and CLAUDE.md, which only had our section.
+    assert_eq!(report.removed.len(), available_skills().len());
+    assert!(!dir.path().join("CLAUDE.md").exists());
+    let left: Vec<_> = fs::read_dir(&commands).unwrap().collect();
+    assert_eq!(left.len(), 1);
+}
+
+#[test]
+fn test_uninstall_keeps_the_rest_of_claude_md() {
+    let dir = tempfile::tempdir().unwrap();
+    let claude_md = dir.path().join("CLAUDE.md");
+    fs::write(&claude_md, "# Project\n\nBuild with make.\n").unwrap();
+    install_skills(dir.path()).unwrap();
+
+    uninstall_skills(dir.path()).unwrap();
+    assert_eq!(
+        fs::read_to_string(&claude_md).unwrap(),
+        "# Project\n\nBuild with make.\n"
+    );
+
+    // A second run has nothing left to do.
+    let report = uninstall_skills(dir.path()).unwrap();
+    assert!(report.removed.is_empty());
+    assert!(!report.section_removed);
+}
+
+#[test]
+fn test_strip_section_between_other_content() {
+    let content =
+        "# A\n\n<!-- rust-analyzer-server -->\nours\n<!-- /rust-analyzer-server -->\n\n## B\n";
+    assert_eq!(
+        strip_claude_md_section(content).as_deref(),
+        Some("# A\n\n## B\n")
+    );
+    assert_eq!(strip_claude_md_section("# A\n"), None);
+}
+
+#[test]
+fn test_skills_from_before_the_manifest_are_upgraded() {
+    let dir = tempfile::tempdir().unwrap();
+    let commands = dir.path().join(".claude/commands");
+    fs::create_dir_all(&commands).unwrap();
+    // As 0.3.0 installed it, with no manifest.
+    let hover = commands.join("ra-hover.md");
+    fs::write(&hover, SHIPPED_HOVER).unwrap();
+    let edited = commands.join("ra-search.md");
+    fs::write(&edited, "Our own search notes\n").unwrap();
+
+    let plan = plan_install(dir.path(), &InstallOptions::default()).unwrap();
+    let report = apply_install(&plan).unwrap();
+
+    assert_ne!(fs::read_to_string(&hover).unwrap(), SHIPPED_HOVER);
+    assert!(!commands.join("ra-hover.md.new").exists());
+    assert_eq!(report.kept, [edited]);
+    assert!(report.installed.contains(&hover));
+}
+
+#[test]
+fn test_reinstall_counts_only_written_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let install = || {
+        let plan = plan_install(dir.path(), &InstallOptions::default()).unwrap();
+        apply_install(&plan).unwrap()
+    };
+    let skills = available_skills().len();
+    assert_eq!(install().installed.len(), skills);
+
+    let again = install();
+    assert!(again.installed.is_empty());
+    assert_eq!(again.unchanged.len(), skills);
+}
@@ -0,0 +1,37 @@
+use std::io::Write;
+
+use rust_analyzer_server::logging::RotatingFile;
+
+#[test]
+fn test_rotates_and_keeps_max_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("server.log");
+    let mut log = RotatingFile::open(path.clone(), 10, 2).unwrap();
+
+    for line in [
+        "first-line\n",
+        "second-line\n",
+        "third-line\n",
+        "fourth-line\n",
+    ] {
+        log.write_all(line.as_bytes()).unwrap();
+    }
+
+    let read = |suffix: &str| std::fs::read_to_string(format!("{}{}", path.display(), suffix));
+    assert_eq!(read("").unwrap(), "fourth-line\n");
+    assert_eq!(read(".1").unwrap(), "third-line\n");
+    assert_eq!(read(".2").unwrap(), "second-line\n");
+    assert!(read(".3").is_err(), "only max_files rotated files are kept");
+}
+
+#[test]
+fn test_appends_to_existing_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("server.log");
+    std::fs::write(&path, "old\n").unwrap();
+
+    let mut log = RotatingFile::open(path.clone(), 0, 2).unwrap();
+    log.write_all(b"new\n").unwrap();
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "old\nnew\n");
+}
@@ -0,0 +1,27 @@
+use rust_analyzer_server::lsp::binary::{find_rust_analyzer, BinarySource};
+use test_support::FakeRustAnalyzer;
+
+#[test]
+fn test_configured_binary_reports_version() {
+    let dir = tempfile::tempdir().unwrap();
+    let fake = FakeRustAnalyzer::new(dir.path(), &[]);
+    let path = fake.path().to_path_buf();
+
+    let binary = find_rust_analyzer(Some(&path)).unwrap();
+    assert_eq!(binary.path, path);
+    assert_eq!(binary.source, BinarySource::Configured);
+    assert_eq!(
+        binary.version.as_deref(),
+        Some("rust-analyzer 0.0.0 (fake)")
+    );
+    assert!(binary.to_string().contains("from configured"));
+}
+
+#[test]
+fn test_missing_configured_binary_names_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("no-such-rust-analyzer");
+
+    let err = find_rust_analyzer(Some(&path)).unwrap_err();
+    assert!(err.to_string().contains(path.to_str().unwrap()), "{}", err);
+}
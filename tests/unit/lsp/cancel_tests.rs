@@ -0,0 +1,80 @@
+use std::{path::Path, sync::Arc, time::Duration};
+
+use rust_analyzer_server::{
+    config::Config,
+    lsp::{RequestTimeout, RustAnalyzerClient, SymbolScope},
+};
+use test_support::FakeRustAnalyzer;
+
+/// Ids named by the `$/cancelRequest` notifications sent to `server`.
+fn cancelled_ids(server: &FakeRustAnalyzer) -> Vec<u64> {
+    server
+        .received()
+        .into_iter()
+        .filter(|message| message["method"] == "$/cancelRequest")
+        .filter_map(|message| message["params"]["id"].as_u64())
+        .collect()
+}
+
+async fn wait_for_cancel(server: &FakeRustAnalyzer, id: u64) -> Vec<u64> {
+    for _ in 0..50 {
+        let ids = cancelled_ids(server);
+        if ids.contains(&id) {
+            return ids;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    cancelled_ids(server)
+}
+
+/// A client of a server that answers nothing after `initialize`.
+async fn start_client(dir: &Path) -> (FakeRustAnalyzer, RustAnalyzerClient) {
+    let server = FakeRustAnalyzer::new(dir, &[]);
+    let config = Config {
+        request_timeout_secs: 1,
+        rust_analyzer_path: Some(server.path().to_path_buf()),
+        ..Config::default()
+    };
+    let mut client = RustAnalyzerClient::new(dir.to_path_buf(), Arc::new(config));
+    client.start().await.unwrap();
+    (server, client)
+}
+
+#[tokio::test]
+async fn test_timed_out_request_is_cancelled() {
+    let dir = tempfile::tempdir().unwrap();
+    let (server, mut client) = start_client(dir.path()).await;
+
+    // Startup sent initialize (1) and reloadWorkspace (2); this is request 3.
+    let err = client
+        .workspace_symbol("Calculator", SymbolScope::Workspace)
+        .await
+        .unwrap_err();
+    let timeout = err.downcast_ref::<RequestTimeout>().unwrap();
+    assert_eq!(timeout.method, "workspace/symbol");
+    assert_eq!(timeout.timeout, Duration::from_secs(1));
+
+    // Sent straight away, not held back until the next request.
+    let ids = wait_for_cancel(&server, 3).await;
+    assert!(ids.contains(&3), "no $/cancelRequest for 3 in {:?}", ids);
+
+    client.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_dropped_request_is_cancelled() {
+    let dir = tempfile::tempdir().unwrap();
+    let (server, mut client) = start_client(dir.path()).await;
+
+    // The caller gives up on request 3 before its own timeout.
+    let hover = client.hover("file:///src/main.rs", 0, 0);
+    assert!(tokio::time::timeout(Duration::from_millis(100), hover)
+        .await
+        .is_err());
+
+    // Sent straight away, without waiting for another message to carry it.
+    let ids = wait_for_cancel(&server, 3).await;
+    assert!(ids.contains(&3), "no $/cancelRequest for 3 in {:?}", ids);
+
+    client.shutdown().await.unwrap();
+}
@@ -0,0 +1,86 @@
+use serde_json::json;
+
+use rust_analyzer_server::lsp::completion::{
+    auto_import, simplify_completion_item, snippet_to_text,
+};
+
+#[test]
+fn test_tabstops_and_placeholders() {
+    assert_eq!(snippet_to_text("println!($0)"), "println!()");
+    assert_eq!(snippet_to_text("new(${1:a}, ${2:b})$0"), "new(a, b)");
+    assert_eq!(snippet_to_text("${1:Vec<${2:T}>}"), "Vec<T>");
+    assert_eq!(snippet_to_text("foo(${1})"), "foo()");
+}
+
+#[test]
+fn test_choices_variables_and_escapes() {
+    assert_eq!(snippet_to_text("${1|pub,pub(crate)|} fn"), "pub fn");
+    assert_eq!(snippet_to_text("$TM_SELECTED_TEXT;"), ";");
+    assert_eq!(
+        snippet_to_text(r#"format!("\$x {}")"#),
+        r#"format!("$x {}")"#
+    );
+    assert_eq!(snippet_to_text(r"${1:a\}b}"), "a}b");
+    assert_eq!(snippet_to_text("cost: 5$"), "cost: 5$");
+}
+
+#[test]
+fn test_snippet_items_become_plain_text() {
+    let mut item = json!({
+        "label": "vec!",
+        "insertTextFormat": 2,
+        "textEdit": { "newText": "vec![$0]" },
+        "documentation": { "kind": "markdown", "value": "Creates a `Vec`." }
+    });
+    simplify_completion_item(&mut item);
+    assert_eq!(item["textEdit"]["newText"], "vec![]");
+    assert_eq!(item["insertTextFormat"], 1);
+    assert_eq!(item["documentation"], "Creates a `Vec`.");
+
+    // Plain text items keep their dollar signs.
+    let mut item = json!({ "label": "$crate", "insertText": "$crate" });
+    simplify_completion_item(&mut item);
+    assert_eq!(item["insertText"], "$crate");
+}
+
+#[test]
+fn test_auto_import_from_resolve_data() {
+    let mut item = json!({
+        "label": "HashMap",
+        "data": {
+            "position": { "textDocument": { "uri": "file:///ws/src/lib.rs" } },
+            "imports": [{
+                "full_import_path": "std::collections::HashMap",
+                "imported_name": "HashMap"
+            }]
+        }
+    });
+    simplify_completion_item(&mut item);
+    assert_eq!(item["auto_import"], "use std::collections::HashMap;");
+}
+
+#[test]
+fn test_auto_import_from_additional_edits() {
+    let item = json!({
+        "label": "Arc",
+        "additionalTextEdits": [{
+            "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 0 } },
+            "newText": "use std::sync::Arc;\n\n"
+        }]
+    });
+    assert_eq!(auto_import(&item).as_deref(), Some("use std::sync::Arc;"));
+
+    // Merged into an existing `use` tree: no statement to report.
+    let merged = json!({
+        "label": "Mutex",
+        "additionalTextEdits": [{ "newText": ", Mutex" }]
+    });
+    assert_eq!(auto_import(&merged), None);
+}
+
+#[test]
+fn test_items_in_scope_have_no_auto_import() {
+    let mut item = json!({ "label": "len", "insertText": "len()" });
+    simplify_completion_item(&mut item);
+    assert!(item.get("auto_import").is_none());
+}
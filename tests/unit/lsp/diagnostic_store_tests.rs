@@ -0,0 +1,61 @@
+use serde_json::json;
+
+use rust_analyzer_server::lsp::DiagnosticStore;
+
+fn warning(message: &str) -> serde_json::Value {
+    json!({ "severity": 2, "message": message })
+}
+
+#[test]
+fn test_least_recently_published_is_evicted() {
+    let mut store = DiagnosticStore::new(2, usize::MAX);
+    store.publish("file:///a.rs", vec![warning("a")]);
+    store.publish("file:///b.rs", vec![warning("b")]);
+    // Republishing makes a.rs the most recent.
+    store.publish("file:///a.rs", vec![warning("a2")]);
+    store.publish("file:///c.rs", vec![warning("c")]);
+
+    assert!(store.get("file:///b.rs").is_none());
+    assert_eq!(store.get("file:///a.rs").unwrap()[0]["message"], "a2");
+    assert!(store.get("file:///c.rs").is_some());
+    let stats = store.stats();
+    assert_eq!(stats.files, 2);
+    assert_eq!(stats.evicted, 1);
+}
+
+#[test]
+fn test_open_documents_are_never_evicted() {
+    let mut store = DiagnosticStore::new(1, usize::MAX);
+    store.pin("file:///open.rs");
+    store.publish("file:///open.rs", vec![warning("open")]);
+    store.publish("file:///a.rs", vec![warning("a")]);
+    store.publish("file:///b.rs", vec![warning("b")]);
+
+    assert!(store.get("file:///open.rs").is_some());
+    assert!(store.get("file:///a.rs").is_none());
+    assert!(store.get("file:///b.rs").is_none());
+    assert_eq!(store.stats().files, 1);
+
+    store.unpin("file:///open.rs");
+    store.publish("file:///c.rs", vec![warning("c")]);
+    assert!(store.get("file:///open.rs").is_none());
+}
+
+#[test]
+fn test_byte_limit_and_accounting() {
+    let diagnostics = vec![warning("x".repeat(100).as_str())];
+    let size = serde_json::to_string(&diagnostics).unwrap().len();
+    let mut store = DiagnosticStore::new(100, size * 2);
+
+    store.publish("file:///a.rs", diagnostics.clone());
+    store.publish("file:///b.rs", diagnostics.clone());
+    assert_eq!(store.stats().bytes, size * 2);
+    store.publish("file:///c.rs", diagnostics.clone());
+    assert!(store.get("file:///a.rs").is_none());
+    assert_eq!(store.stats().bytes, size * 2);
+
+    store.remove("file:///b.rs");
+    store.publish("file:///c.rs", vec![]);
+    assert_eq!(store.stats().bytes, 2);
+    assert_eq!(store.stats().files, 1);
+}
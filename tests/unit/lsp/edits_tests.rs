@@ -0,0 +1,84 @@
+use serde_json::json;
+
+use rust_analyzer_server::lsp::{
+    edits::{apply_text_edits, position_to_offset, workspace_edit_changes},
+    position::PositionEncoding::{Utf16, Utf8},
+};
+
+fn edit(start: (u32, u32), end: (u32, u32), new_text: &str) -> serde_json::Value {
+    json!({
+        "range": {
+            "start": { "line": start.0, "character": start.1 },
+            "end": { "line": end.0, "character": end.1 }
+        },
+        "newText": new_text
+    })
+}
+
+#[test]
+fn test_position_counts_utf16_units() {
+    let content = "let s = \"é😀\";\nx";
+    // 'é' is one UTF-16 unit, '😀' two.
+    assert_eq!(
+        position_to_offset(content, 0, 10, Utf16),
+        "let s = \"é".len()
+    );
+    assert_eq!(
+        position_to_offset(content, 0, 12, Utf16),
+        "let s = \"é😀".len()
+    );
+    assert_eq!(position_to_offset(content, 1, 0, Utf16), content.len() - 1);
+    // Past the end of a line or the file is clamped.
+    assert_eq!(
+        position_to_offset(content, 0, 100, Utf16),
+        content.find('\n').unwrap()
+    );
+    assert_eq!(position_to_offset(content, 9, 0, Utf16), content.len());
+}
+
+#[test]
+fn test_position_counts_bytes_in_utf8() {
+    let content = "let s = \"é😀\";\nx";
+    let offset = "let s = \"é😀".len();
+    assert_eq!(position_to_offset(content, 0, offset as u32, Utf8), offset);
+}
+
+#[test]
+fn test_apply_edits_against_original_positions() {
+    let content = "fn main() {\n    let unused = 42;\n}\n";
+    let edits = vec![edit((1, 8), (1, 8), "_"), edit((0, 3), (0, 7), "start")];
+    assert_eq!(
+        apply_text_edits(content, &edits, Utf16).unwrap(),
+        "fn start() {\n    let _unused = 42;\n}\n"
+    );
+}
+
+#[test]
+fn test_overlapping_edits_are_rejected() {
+    let edits = vec![edit((0, 0), (0, 5), "a"), edit((0, 3), (0, 8), "b")];
+    assert!(apply_text_edits("0123456789", &edits, Utf16).is_err());
+}
+
+#[test]
+fn test_workspace_edit_forms() {
+    let changes = workspace_edit_changes(&json!({
+        "changes": { "file:///a.rs": [edit((0, 0), (0, 0), "x")] }
+    }))
+    .unwrap();
+    assert_eq!(changes["file:///a.rs"].len(), 1);
+
+    let document_changes = workspace_edit_changes(&json!({
+        "documentChanges": [
+            { "textDocument": { "uri": "file:///a.rs", "version": 3 }, "edits": [edit((0, 0), (0, 0), "x")] },
+            { "textDocument": { "uri": "file:///a.rs", "version": 3 }, "edits": [edit((1, 0), (1, 0), "y")] }
+        ]
+    }))
+    .unwrap();
+    assert_eq!(document_changes["file:///a.rs"].len(), 2);
+
+    let err = workspace_edit_changes(&json!({
+        "documentChanges": [{ "kind": "create", "uri": "file:///b.rs" }]
+    }))
+    .unwrap_err();
+    assert!(err.to_string().contains("create"), "{}", err);
+}
@@ -0,0 +1,37 @@
+use rust_analyzer_server::{
+    config::SERVER_MESSAGES_LEN,
+    lsp::messages::{MessageType, ServerMessages},
+};
+
+#[test]
+fn test_message_types() {
+    assert_eq!(MessageType::from_lsp(1), MessageType::Error);
+    assert_eq!(MessageType::from_lsp(2), MessageType::Warning);
+    assert_eq!(MessageType::from_lsp(3), MessageType::Info);
+    assert_eq!(MessageType::from_lsp(4), MessageType::Log);
+    assert_eq!(MessageType::from_lsp(5), MessageType::Debug);
+}
+
+#[test]
+fn test_last_error_is_only_the_latest_message() {
+    let mut messages = ServerMessages::new();
+    assert!(messages.last_error().is_none());
+
+    let failure = "Failed to load workspace: Cargo.toml parse error";
+    messages.push(MessageType::Error, failure.to_string());
+    assert_eq!(messages.last_error().unwrap().message, failure);
+
+    messages.push(MessageType::Info, "Reloaded workspace".to_string());
+    assert!(messages.last_error().is_none());
+}
+
+#[test]
+fn test_messages_are_bounded() {
+    let mut messages = ServerMessages::new();
+    for i in 0..SERVER_MESSAGES_LEN + 5 {
+        messages.push(MessageType::Log, i.to_string());
+    }
+    let all = messages.all();
+    assert_eq!(all.len(), SERVER_MESSAGES_LEN);
+    assert_eq!(all[0].message, "5");
+}
@@ -0,0 +1,205 @@
+use std::path::{Path, PathBuf};
+
+use rust_analyzer_server::lsp::{
+    paths::{file_uri, uri_path, ExternalFile, ModulePaths},
+    PathStyle,
+};
+
+fn relative_style(cargo_home: &str, sysroot: &str) -> PathStyle {
+    PathStyle::new(
+        true,
+        PathBuf::from("/home/me/work/app"),
+        Some(PathBuf::from(cargo_home)),
+        Some(PathBuf::from(sysroot)),
+    )
+}
+
+#[test]
+fn test_absolute_paths_are_left_alone() {
+    let style = PathStyle::absolute();
+    assert!(!style.is_relative());
+    assert_eq!(
+        style.display("file:///home/me/work/app/src/lib.rs"),
+        "/home/me/work/app/src/lib.rs"
+    );
+}
+
+#[test]
+fn test_file_uris_are_percent_encoded() {
+    let path = Path::new("/home/me/my work/100%/a#b?é.rs");
+    let uri = file_uri(path);
+    assert_eq!(uri, "file:///home/me/my%20work/100%25/a%23b%3F%C3%A9.rs");
+    assert_eq!(uri_path(&uri), path);
+    assert_eq!(
+        uri_path("file:///home/me/src/lib.rs"),
+        Path::new("/home/me/src/lib.rs")
+    );
+    assert_eq!(
+        uri_path("file://localhost/home/me/lib.rs"),
+        Path::new("/home/me/lib.rs")
+    );
+    // Plain paths are taken as they are, `%` and all.
+    assert_eq!(uri_path("/tmp/100%20.rs"), Path::new("/tmp/100%20.rs"));
+    assert_eq!(
+        PathStyle::absolute().display("file:///home/me/my%20work/lib.rs"),
+        "/home/me/my work/lib.rs"
+    );
+}
+
+#[test]
+fn test_workspace_paths_are_relative() {
+    let style = relative_style("/home/me/.cargo", "/opt/rust");
+    assert_eq!(
+        style.display("file:///home/me/work/app/src/lib.rs"),
+        "src/lib.rs"
+    );
+    assert_eq!(style.display("/home/me/work/app"), ".");
+    // A sibling sharing the root's name as a prefix is outside it.
+    assert_eq!(
+        style.display("file:///home/me/work/app2/src/lib.rs"),
+        "/home/me/work/app2/src/lib.rs"
+    );
+}
+
+#[test]
+fn test_external_paths_are_marked() {
+    let style = relative_style("/home/me/.cargo", "/opt/rust");
+    assert_eq!(
+        style.display(
+            "file:///home/me/.cargo/registry/src/index.crates.io-6f17d22bba15001f/tokio-1.38.0/src/lib.rs"
+        ),
+        "~cargo/tokio-1.38.0/src/lib.rs"
+    );
+    assert_eq!(
+        style.display("file:///home/me/.cargo/git/checkouts/hyper-1a2b/3c4d/src/lib.rs"),
+        "~cargo/git/checkouts/hyper-1a2b/3c4d/src/lib.rs"
+    );
+    assert_eq!(
+        style.display("file:///opt/rust/lib/rustlib/src/rust/library/core/src/option.rs"),
+        "~rust/core/src/option.rs"
+    );
+}
+
+#[test]
+fn test_marked_paths_expand_to_existing_files() {
+    let cargo_home = tempfile::tempdir().unwrap();
+    let sysroot = tempfile::tempdir().unwrap();
+    let crate_dir = cargo_home
+        .path()
+        .join("registry/src/index.crates.io-6f17d22bba15001f/tokio-1.38.0/src");
+    std::fs::create_dir_all(&crate_dir).unwrap();
+    std::fs::write(crate_dir.join("lib.rs"), "").unwrap();
+    let library = sysroot.path().join("lib/rustlib/src/rust/library/core/src");
+    std::fs::create_dir_all(&library).unwrap();
+    std::fs::write(library.join("option.rs"), "").unwrap();
+
+    let style = relative_style(
+        cargo_home.path().to_str().unwrap(),
+        sysroot.path().to_str().unwrap(),
+    );
+    let tokio = style.expand("~cargo/tokio-1.38.0/src/lib.rs").unwrap();
+    assert_eq!(tokio, crate_dir.join("lib.rs"));
+    assert_eq!(
+        style.display(tokio.to_str().unwrap()),
+        "~cargo/tokio-1.38.0/src/lib.rs"
+    );
+    assert_eq!(
+        style.expand("~rust/core/src/option.rs"),
+        Some(library.join("option.rs"))
+    );
+    assert_eq!(style.expand("~cargo/tokio-1.38.0/src/missing.rs"), None);
+    assert_eq!(style.expand("src/lib.rs"), None);
+}
+
+#[test]
+fn test_module_path_from_file_location() {
+    let dir = tempfile::tempdir().unwrap();
+    let package = dir.path().join("net-utils");
+    std::fs::create_dir_all(package.join("src/http")).unwrap();
+    std::fs::write(
+        package.join("Cargo.toml"),
+        "[package]\nname = \"net-utils\"\nversion = \"0.1.0\"\n",
+    )
+    .unwrap();
+
+    let mut paths = ModulePaths::default();
+    assert_eq!(
+        paths.module_path(&package.join("src/lib.rs")).as_deref(),
+        Some("net_utils")
+    );
+    assert_eq!(
+        paths
+            .module_path(&package.join("src/http/client.rs"))
+            .as_deref(),
+        Some("net_utils::http::client")
+    );
+    assert_eq!(
+        paths
+            .module_path(&package.join("src/http/mod.rs"))
+            .as_deref(),
+        Some("net_utils::http")
+    );
+    assert_eq!(paths.module_path(&package.join("build.rs")), None);
+    assert_eq!(paths.module_path(&dir.path().join("loose.rs")), None);
+}
+
+#[test]
+fn test_external_files_name_their_crate() {
+    let registry = Path::new(
+        "/home/me/.cargo/registry/src/index.crates.io-6f17d22bba15001f/tokio-1.38.0/src/sync/mutex.rs",
+    );
+    assert_eq!(
+        ExternalFile::of(registry),
+        Some(ExternalFile {
+            krate: "tokio".to_string(),
+            version: Some("1.38.0".to_string()),
+            path: PathBuf::from("src/sync/mutex.rs"),
+        })
+    );
+    // Crate names with dashes and digits, and pre-release versions.
+    let dashed = Path::new("/c/registry/src/index/sha-1-0.10.1-rc.2/src/lib.rs");
+    let dashed = ExternalFile::of(dashed).unwrap();
+    assert_eq!(dashed.krate, "sha-1");
+    assert_eq!(dashed.version.as_deref(), Some("0.10.1-rc.2"));
+
+    let sysroot = Path::new(
+        "/home/me/.rustup/toolchains/stable-x86_64-unknown-linux-gnu/lib/rustlib/src/rust/library/core/src/option.rs",
+    );
+    assert_eq!(
+        ExternalFile::of(sysroot),
+        Some(ExternalFile {
+            krate: "core".to_string(),
+            version: None,
+            path: PathBuf::from("src/option.rs"),
+        })
+    );
+
+    assert_eq!(
+        ExternalFile::of(Path::new("/home/me/work/app/src/lib.rs")),
+        None
+    );
+}
+
+#[test]
+fn test_external_location_says_whether_the_file_is_readable() {
+    let cargo_home = tempfile::tempdir().unwrap();
+    let crate_dir = cargo_home
+        .path()
+        .join("registry/src/index/tokio-1.38.0/src");
+    std::fs::create_dir_all(&crate_dir).unwrap();
+    let file = crate_dir.join("lib.rs");
+    std::fs::write(&file, "").unwrap();
+
+    let external = ExternalFile::of(&file).unwrap();
+    let location = external.location(&file, 4, 7);
+    assert_eq!(location["crate"], "tokio");
+    assert_eq!(location["version"], "1.38.0");
+    assert_eq!(location["path"], "src/lib.rs");
+    assert_eq!(location["line"], 4);
+    assert_eq!(location["col"], 7);
+    assert_eq!(location["readable"], true);
+    assert_eq!(location["abs_path"], file.display().to_string());
+
+    let missing = crate_dir.join("gone.rs");
+    assert_eq!(external.location(&missing, 0, 0)["readable"], false);
+}
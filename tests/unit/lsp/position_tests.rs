@@ -0,0 +1,93 @@
+use serde_json::json;
+
+use rust_analyzer_server::lsp::position::{
+    line_text, uris_in, DocumentColumns, PositionEncoding,
+    PositionEncoding::{Utf16, Utf32, Utf8},
+};
+
+const FIXTURE: &str = include_str!("../../fixtures/unicode_positions.rs");
+const URI: &str = "file:///src/unicode_positions.rs";
+
+/// Character column of the first `needle` on `line` of the fixture.
+fn char_column(line: u32, needle: &str) -> u32 {
+    let text = line_text(FIXTURE, line).unwrap();
+    text[..text.find(needle).unwrap()].chars().count() as u32
+}
+
+#[test]
+fn test_columns_round_trip_past_multibyte_characters() {
+    let line = line_text(FIXTURE, 1).unwrap();
+    // `日本` are one UTF-16 unit each, `🦀` is a surrogate pair.
+    let character = char_column(1, "target()");
+    assert_eq!(Utf16.char_to_units(line, character), character + 1);
+    assert_eq!(Utf16.units_to_char(line, character + 1), character);
+
+    // ASCII-only prefixes are unchanged.
+    assert_eq!(Utf16.char_to_units(line, 4), 4);
+}
+
+#[test]
+fn test_surrogate_halves_and_line_end() {
+    let line = "🦀x";
+    assert_eq!(Utf16.units_to_char(line, 1), 1);
+    assert_eq!(Utf16.units_to_char(line, 2), 1);
+    assert_eq!(Utf16.units_to_char(line, 3), 2);
+    // Past the end of the line both count one per column.
+    assert_eq!(Utf16.char_to_units(line, 4), 5);
+    assert_eq!(Utf16.units_to_char(line, 5), 4);
+}
+
+#[test]
+fn test_document_columns() {
+    let mut columns = DocumentColumns::new(Utf16);
+    columns.insert(URI, FIXTURE.to_string());
+
+    let definition = char_column(0, "target");
+    // `🦀` before `target` on line 0 takes two units.
+    assert_eq!(columns.to_lsp(URI, 0, definition), definition + 1);
+    assert_eq!(
+        columns.to_user(URI, 0, (definition + 1) as u64),
+        definition as u64
+    );
+
+    // Documents without text pass columns through.
+    assert_eq!(columns.to_lsp("file:///other.rs", 0, 9), 9);
+    assert_eq!(columns.to_user("file:///other.rs", 0, 9), 9);
+}
+
+#[test]
+fn test_utf8_and_utf32_columns() {
+    let line = line_text(FIXTURE, 1).unwrap();
+    let character = char_column(1, "target()");
+    let bytes = line.find("target()").unwrap() as u32;
+    assert_eq!(Utf8.char_to_units(line, character), bytes);
+    assert_eq!(Utf8.units_to_char(line, bytes), character);
+    assert_eq!(Utf32.char_to_units(line, character), character);
+
+    // An offset inside `🦀` maps to the crab.
+    assert_eq!(Utf8.units_to_char("🦀x", 2), 1);
+
+    let mut columns = DocumentColumns::new(Utf8);
+    columns.insert(URI, FIXTURE.to_string());
+    assert_eq!(columns.to_lsp(URI, 1, character), bytes);
+    assert_eq!(columns.to_user(URI, 1, bytes as u64), character as u64);
+}
+
+#[test]
+fn test_encoding_negotiation() {
+    assert_eq!(PositionEncoding::from_lsp(Some("utf-8")), Utf8);
+    assert_eq!(PositionEncoding::from_lsp(Some("utf-32")), Utf32);
+    // Servers that don't pick one use the protocol default.
+    assert_eq!(PositionEncoding::from_lsp(None), Utf16);
+    assert_eq!(serde_json::to_value(Utf8).unwrap(), json!("utf-8"));
+}
+
+#[test]
+fn test_uris_in_locations() {
+    let result = json!([
+        { "targetUri": "file:///a.rs", "targetRange": {} },
+        { "from": { "uri": "file:///b.rs" }, "fromRanges": [] },
+    ]);
+    let uris: Vec<String> = uris_in(&result).into_iter().collect();
+    assert_eq!(uris, ["file:///a.rs", "file:///b.rs"]);
+}
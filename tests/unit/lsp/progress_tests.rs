@@ -0,0 +1,141 @@
+use rust_analyzer_server::lsp::progress::{IndexingEstimate, ProgressState};
+
+fn begin(state: &mut ProgressState, token: &str, title: &str, percentage: Option<u32>) {
+    state.begin(token.to_string(), title.to_string(), None, percentage);
+}
+
+#[test]
+fn test_idle_is_complete() {
+    let state = ProgressState::new();
+    assert_eq!(
+        state.indexing_estimate(),
+        IndexingEstimate {
+            percent: 100,
+            title: None
+        }
+    );
+}
+
+#[test]
+fn test_main_tasks_outweigh_others() {
+    let mut state = ProgressState::new();
+    begin(&mut state, "rustAnalyzer/Fetching", "Fetching", None);
+    begin(&mut state, "rustAnalyzer/Indexing", "Indexing", Some(50));
+
+    // (6 * 50 + 1 * 0) / 7
+    let estimate = state.indexing_estimate();
+    assert_eq!(estimate.percent, 42);
+    assert_eq!(estimate.title.as_deref(), Some("Indexing"));
+}
+
+#[test]
+fn test_percent_never_goes_backwards_within_a_session() {
+    let mut state = ProgressState::new();
+    begin(&mut state, "rustAnalyzer/Indexing", "Indexing", Some(0));
+    state.report("rustAnalyzer/Indexing", None, Some(80));
+    assert_eq!(state.indexing_estimate().percent, 80);
+
+    // rust-analyzer restarts the phase.
+    state.report("rustAnalyzer/Indexing", None, Some(10));
+    assert_eq!(state.indexing_estimate().percent, 80);
+    begin(&mut state, "rustAnalyzer/cargo check", "cargo check", None);
+    assert_eq!(state.indexing_estimate().percent, 80);
+
+    // Once everything ends, the next session starts from scratch.
+    state.end("rustAnalyzer/Indexing");
+    state.end("rustAnalyzer/cargo check");
+    assert_eq!(state.indexing_estimate().percent, 100);
+    begin(&mut state, "rustAnalyzer/Indexing", "Indexing", Some(5));
+    assert_eq!(state.indexing_estimate().percent, 5);
+}
+
+#[test]
+fn test_flychecks_are_counted_under_either_token() {
+    let mut state = ProgressState::new();
+    begin(&mut state, "rustAnalyzer/Indexing", "Indexing", None);
+    assert!(!state.is_flychecking());
+    assert_eq!(state.flychecks_begun(), 0);
+
+    begin(&mut state, "rust-analyzer/flycheck/0", "cargo check", None);
+    assert!(state.is_flychecking());
+    state.end("rust-analyzer/flycheck/0");
+    assert!(!state.is_flychecking());
+
+    begin(
+        &mut state,
+        "rustAnalyzer/cargo clippy",
+        "cargo clippy",
+        None,
+    );
+    assert!(state.is_flychecking());
+    assert_eq!(state.flychecks_begun(), 2);
+}
+
+#[test]
+fn test_finished_match_ignores_tasks_ended_before_the_wait() {
+    let mut state = ProgressState::new();
+    begin(&mut state, "rustAnalyzer/Indexing", "Indexing", None);
+    state.end("rustAnalyzer/Indexing");
+
+    let since = state.ended_count();
+    assert!(state.finished_match("Indexing", since).is_none());
+
+    begin(&mut state, "rustAnalyzer/Indexing", "Indexing", None);
+    assert!(state.finished_match("Indexing", since).is_none());
+    begin(&mut state, "rustAnalyzer/Fetching", "Fetching", None);
+    state.end("rustAnalyzer/Indexing");
+    state.end("rustAnalyzer/Fetching");
+
+    let matched = state.finished_match("Indexing", since).unwrap();
+    assert_eq!(matched.token, "rustAnalyzer/Indexing");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_client_asks_for_progress_and_tracks_it() {
+    use rust_analyzer_server::{config::Config, lsp::RustAnalyzerClient};
+    use serde_json::json;
+    use std::{sync::Arc, time::Duration};
+    use test_support::FakeRustAnalyzer;
+
+    let dir = tempfile::tempdir().unwrap();
+    let begin = json!({
+        "jsonrpc": "2.0",
+        "method": "$/progress",
+        "params": {
+            "token": "rustAnalyzer/Indexing",
+            "value": { "kind": "begin", "title": "Indexing", "percentage": 0 }
+        }
+    });
+    let server = FakeRustAnalyzer::new(dir.path(), &[begin]);
+    let config = Config {
+        request_timeout_secs: 1,
+        rust_analyzer_path: Some(server.path().to_path_buf()),
+        ..Config::default()
+    };
+    let mut client = RustAnalyzerClient::new(dir.path().to_path_buf(), Arc::new(config));
+    client.start().await.unwrap();
+
+    // rust-analyzer only reports progress to clients that say they take it.
+    let initialize = server
+        .received()
+        .into_iter()
+        .find(|message| message["method"] == "initialize")
+        .unwrap();
+    assert_eq!(
+        initialize["params"]["capabilities"]["window"]["workDoneProgress"],
+        true
+    );
+
+    let mut indexing = false;
+    for _ in 0..50 {
+        indexing = client.progress.lock().await.is_indexing_workspace();
+        if indexing {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(indexing);
+
+    client.shutdown().await.unwrap();
+}
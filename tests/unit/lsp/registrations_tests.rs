@@ -0,0 +1,131 @@
+use serde_json::json;
+use std::path::Path;
+
+use rust_analyzer_server::{
+    lsp::registrations::{glob_matches, FileWatcher, Registrations},
+    watcher::should_forward,
+};
+
+#[test]
+fn test_glob_syntax() {
+    assert!(glob_matches("**/*.rs", "/ws/src/lib.rs"));
+    assert!(glob_matches("**/*.rs", "/lib.rs"));
+    assert!(!glob_matches("**/*.rs", "/ws/src/lib.rsx"));
+    assert!(glob_matches("/ws/**/Cargo.{toml,lock}", "/ws/Cargo.lock"));
+    assert!(glob_matches(
+        "/ws/**/Cargo.{toml,lock}",
+        "/ws/a/b/Cargo.toml"
+    ));
+    assert!(!glob_matches(
+        "/ws/**/Cargo.{toml,lock}",
+        "/other/Cargo.toml"
+    ));
+    // `*` and `?` stay within a segment.
+    assert!(!glob_matches("/ws/*.rs", "/ws/src/lib.rs"));
+    assert!(glob_matches("/ws/src/?ib.rs", "/ws/src/lib.rs"));
+    assert!(glob_matches("/ws/[a-c]*.rs", "/ws/build.rs"));
+    assert!(!glob_matches("/ws/[!a-c]*.rs", "/ws/build.rs"));
+}
+
+#[test]
+fn test_register_and_unregister() {
+    let mut registrations = Registrations::default();
+    registrations.register(&json!({
+        "registrations": [{
+            "id": "workspace/didChangeWatchedFiles",
+            "method": "workspace/didChangeWatchedFiles",
+            "registerOptions": { "watchers": [
+                { "globPattern": { "baseUri": "file:///ws/", "pattern": "**/*.rs" } },
+                { "globPattern": "**/rust-analyzer.toml", "kind": 2 }
+            ]}
+        }]
+    }));
+
+    let watchers = registrations.file_watchers();
+    assert_eq!(watchers[0].glob, "/ws/**/*.rs");
+    assert!(watchers[0].matches(Path::new("/ws/src/lib.rs"), 3));
+    // A change-only watcher ignores deletions.
+    assert!(watchers[1].matches(Path::new("/ws/rust-analyzer.toml"), 2));
+    assert!(!watchers[1].matches(Path::new("/ws/rust-analyzer.toml"), 3));
+
+    registrations.unregister(&json!({
+        "unregisterations": [{ "id": "workspace/didChangeWatchedFiles", "method": "" }]
+    }));
+    assert!(registrations.file_watchers().is_empty());
+}
+
+#[test]
+fn test_registered_watchers_replace_the_default_filter() {
+    let root = Path::new("/ws");
+    let lock = Path::new("/ws/Cargo.lock");
+    assert!(!should_forward(root, lock, 2, &[]));
+
+    let watchers = [FileWatcher {
+        glob: "/ws/**/Cargo.{toml,lock}".to_string(),
+        kind: 7,
+    }];
+    assert!(should_forward(root, lock, 2, &watchers));
+    assert!(!should_forward(
+        root,
+        Path::new("/ws/src/lib.rs"),
+        2,
+        &watchers
+    ));
+    assert!(!should_forward(
+        root,
+        Path::new("/ws/target/Cargo.lock"),
+        2,
+        &watchers
+    ));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_register_capability_is_acknowledged() {
+    use rust_analyzer_server::{config::Config, lsp::RustAnalyzerClient};
+    use std::{sync::Arc, time::Duration};
+    use test_support::FakeRustAnalyzer;
+
+    let dir = tempfile::tempdir().unwrap();
+    // Asks to register a file watcher as request 7.
+    let register = json!({
+        "jsonrpc": "2.0",
+        "id": 7,
+        "method": "client/registerCapability",
+        "params": { "registrations": [{
+            "id": "watchers",
+            "method": "workspace/didChangeWatchedFiles",
+            "registerOptions": { "watchers": [{ "globPattern": "**/*.rs" }] }
+        }]}
+    });
+    let server = FakeRustAnalyzer::new(dir.path(), &[register]);
+    let config = Config {
+        request_timeout_secs: 1,
+        rust_analyzer_path: Some(server.path().to_path_buf()),
+        ..Config::default()
+    };
+    let mut client = RustAnalyzerClient::new(dir.path().to_path_buf(), Arc::new(config));
+    client.start().await.unwrap();
+
+    let mut reply = None;
+    for _ in 0..50 {
+        reply = server
+            .received()
+            .into_iter()
+            .find(|message| message["id"] == 7 && message.get("method").is_none());
+        if reply.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(
+        reply,
+        Some(json!({ "jsonrpc": "2.0", "id": 7, "result": null }))
+    );
+
+    let watchers = client.registrations.lock().await.file_watchers();
+    assert_eq!(watchers.len(), 1);
+    assert_eq!(watchers[0].glob, "**/*.rs");
+
+    client.shutdown().await.unwrap();
+}
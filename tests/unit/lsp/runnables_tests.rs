@@ -0,0 +1,83 @@
+use serde_json::{json, Value};
+
+use rust_analyzer_server::lsp::runnables::simplify_test_runnable;
+
+fn runnable(label: &str, cargo_args: &[&str], executable_args: &[&str]) -> Value {
+    json!({
+        "label": label,
+        "kind": "cargo",
+        "location": { "targetUri": "file:///ws/src/lib.rs" },
+        "args": {
+            "workspaceRoot": "/ws",
+            "cwd": "/ws",
+            "cargoArgs": cargo_args,
+            "executableArgs": executable_args
+        }
+    })
+}
+
+#[test]
+fn test_test_runnable_keeps_exact_invocation() {
+    let test = simplify_test_runnable(&runnable(
+        "test tests::adds",
+        &["test", "--package", "calc", "--lib"],
+        &["tests::adds", "--exact", "--show-output"],
+    ))
+    .unwrap();
+    assert_eq!(test["name"], "tests::adds");
+    assert_eq!(test["kind"], "test");
+    assert_eq!(test["ignored"], false);
+    assert_eq!(test["cwd"], "/ws");
+    assert_eq!(
+        test["command"],
+        "cargo test --package calc --lib -- tests::adds --exact --show-output"
+    );
+    assert_eq!(
+        test["executable_args"],
+        json!(["tests::adds", "--exact", "--show-output"])
+    );
+}
+
+#[test]
+fn test_ignored_tests_and_doctests_are_flagged() {
+    let ignored = simplify_test_runnable(&runnable(
+        "test tests::slow",
+        &["test", "--package", "calc", "--lib"],
+        &["tests::slow", "--exact", "--show-output", "--ignored"],
+    ))
+    .unwrap();
+    assert_eq!(ignored["ignored"], true);
+
+    let doctest = simplify_test_runnable(&runnable(
+        "doctest Calculator::add",
+        &["test", "--package", "calc", "--doc"],
+        &["Calculator::add", "--exact", "--show-output"],
+    ))
+    .unwrap();
+    assert_eq!(doctest["kind"], "doctest");
+    assert_eq!(doctest["name"], "Calculator::add");
+
+    let module = simplify_test_runnable(&runnable(
+        "test-mod tests",
+        &["test", "--package", "calc", "--lib"],
+        &["tests", "--show-output"],
+    ))
+    .unwrap();
+    assert_eq!(module["kind"], "test_module");
+}
+
+#[test]
+fn test_non_test_runnables_are_skipped() {
+    assert!(simplify_test_runnable(&runnable(
+        "run calc",
+        &["run", "--package", "calc", "--bin", "calc"],
+        &[]
+    ))
+    .is_none());
+    assert!(simplify_test_runnable(&runnable(
+        "cargo check -p calc",
+        &["check", "--package", "calc"],
+        &[]
+    ))
+    .is_none());
+}
@@ -0,0 +1,214 @@
+use serde_json::{json, Value};
+use std::path::Path;
+
+use rust_analyzer_server::mcp::handle_tool_call;
+use test_support::{server_with_backend, write_file, MockBackend};
+
+/// A call hierarchy item for the function `name` declared on `line`.
+fn item(name: &str, uri: &str, line: u32) -> Value {
+    let range = |start: u32, end: u32| {
+        json!({
+            "start": { "line": line, "character": start },
+            "end": { "line": line, "character": end }
+        })
+    };
+    json!({
+        "name": name,
+        "kind": 12,
+        "uri": uri,
+        "range": range(0, 1),
+        "selectionRange": range(7, 7 + name.len() as u32)
+    })
+}
+
+/// A workspace of two members, `geometry` and `app`, where `app` calls
+/// `geometry::area`. Returns the URIs of their sources.
+fn workspace(root: &Path) -> (String, String) {
+    write_file(
+        root,
+        "Cargo.toml",
+        "[workspace]\nmembers = [\"geometry\", \"app\"]\nresolver = \"2\"\n",
+    );
+    write_file(
+        root,
+        "geometry/Cargo.toml",
+        "[package]\nname = \"geometry\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+    );
+    let lib = write_file(
+        root,
+        "geometry/src/lib.rs",
+        "pub fn area(w: u32, h: u32) -> u32 {\n    w * h\n}\npub fn square(s: u32) -> u32 {\n    area(s, s)\n}\n",
+    );
+    write_file(
+        root,
+        "app/Cargo.toml",
+        "[package]\nname = \"app\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\ngeometry = { path = \"../geometry\" }\n",
+    );
+    let main = write_file(
+        root,
+        "app/src/main.rs",
+        "fn main() {\n    println!(\"{}\", geometry::area(2, 3));\n}\n",
+    );
+    (lib, main)
+}
+
+fn args(root: &Path, extra: Value) -> Value {
+    let mut args = json!({
+        "file_path": root.join("geometry/src/lib.rs").to_string_lossy(),
+        "line": 0,
+        "character": 7
+    });
+    for (key, value) in extra.as_object().unwrap() {
+        args[key] = value.clone();
+    }
+    args
+}
+
+#[tokio::test]
+async fn test_call_graph_as_dot_with_crate_clusters() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let (lib, main) = workspace(&root);
+
+    let backend = MockBackend::new()
+        .respond(
+            "textDocument/prepareCallHierarchy",
+            json!([item("area", &lib, 0)]),
+        )
+        .respond(
+            "callHierarchy/incomingCalls",
+            json!([
+                { "from": item("square", &lib, 3), "fromRanges": [] },
+                { "from": item("main", &main, 0), "fromRanges": [] }
+            ]),
+        );
+    let mut server = server_with_backend(&root, &backend).await;
+
+    let args = args(&root, json!({ "depth": 1 }));
+    let result = handle_tool_call(&mut server, "rust_analyzer_call_graph", args)
+        .await
+        .unwrap();
+    assert_eq!(
+        result.content[0].text,
+        [
+            "digraph calls {",
+            "  rankdir=LR;",
+            "  node [shape=box, fontname=\"monospace\"];",
+            "  subgraph cluster_0 {",
+            "    label=\"app\";",
+            "    n2 [label=\"fn main\\napp/src/main.rs:0\"];",
+            "  }",
+            "  subgraph cluster_1 {",
+            "    label=\"geometry\";",
+            "    n0 [label=\"fn area\\ngeometry/src/lib.rs:0\", style=bold];",
+            "    n1 [label=\"fn square\\ngeometry/src/lib.rs:3\"];",
+            "  }",
+            "  n1 -> n0;",
+            "  n2 -> n0;",
+            "}",
+        ]
+        .join("\n")
+    );
+    // Callees weren't found, so only the root was asked for them.
+    assert_eq!(backend.requests("callHierarchy/outgoingCalls").len(), 1);
+}
+
+#[tokio::test]
+async fn test_call_graph_expands_each_function_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let (lib, main) = workspace(&root);
+
+    // Every function is called by `square` and `main`, themselves included.
+    let backend = MockBackend::new()
+        .respond(
+            "textDocument/prepareCallHierarchy",
+            json!([item("area", &lib, 0)]),
+        )
+        .respond(
+            "callHierarchy/incomingCalls",
+            json!([
+                { "from": item("square", &lib, 3), "fromRanges": [] },
+                { "from": item("main", &main, 0), "fromRanges": [] }
+            ]),
+        );
+    let mut server = server_with_backend(&root, &backend).await;
+
+    let args = args(
+        &root,
+        json!({ "direction": "callers", "depth": 10, "format": "json" }),
+    );
+    let result = handle_tool_call(&mut server, "rust_analyzer_call_graph", args)
+        .await
+        .unwrap();
+    let graph: Value = serde_json::from_str(&result.content[0].text).unwrap();
+    assert_eq!(
+        graph,
+        json!({
+            "root": "n0",
+            "nodes": [
+                { "id": "n0", "name": "area", "location": "geometry/src/lib.rs:0", "crate": "geometry" },
+                { "id": "n1", "name": "square", "location": "geometry/src/lib.rs:3", "crate": "geometry" },
+                { "id": "n2", "name": "main", "location": "app/src/main.rs:0", "crate": "app" }
+            ],
+            "calls": {
+                "n1": ["n0", "n1", "n2"],
+                "n2": ["n0", "n1", "n2"]
+            }
+        })
+    );
+    assert_eq!(backend.requests("callHierarchy/incomingCalls").len(), 3);
+    assert!(backend.requests("callHierarchy/outgoingCalls").is_empty());
+}
+
+#[tokio::test]
+async fn test_call_graph_says_when_truncated() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let (lib, _) = workspace(&root);
+
+    let callers: Vec<Value> = (0..150)
+        .map(|n| json!({ "from": item(&format!("caller_{}", n), &lib, n + 10), "fromRanges": [] }))
+        .collect();
+    let backend = MockBackend::new()
+        .respond(
+            "textDocument/prepareCallHierarchy",
+            json!([item("area", &lib, 0)]),
+        )
+        .respond("callHierarchy/incomingCalls", json!(callers));
+    let mut server = server_with_backend(&root, &backend).await;
+
+    let args = args(&root, json!({ "direction": "callers", "format": "json" }));
+    let result = handle_tool_call(&mut server, "rust_analyzer_call_graph", args)
+        .await
+        .unwrap();
+    let graph: Value = serde_json::from_str(&result.content[0].text).unwrap();
+    assert_eq!(graph["nodes"].as_array().unwrap().len(), 100);
+    assert!(graph["truncated"]
+        .as_str()
+        .unwrap()
+        .starts_with("Truncated at 100 functions"));
+
+    let args = self::args(&root, json!({ "direction": "callers" }));
+    let result = handle_tool_call(&mut server, "rust_analyzer_call_graph", args)
+        .await
+        .unwrap();
+    assert!(result.content[0]
+        .text
+        .contains("  label=\"Truncated at 100 functions"));
+}
+
+#[tokio::test]
+async fn test_call_graph_rejects_unknown_direction() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    workspace(&root);
+    let backend = MockBackend::new();
+    let mut server = server_with_backend(&root, &backend).await;
+
+    let args = args(&root, json!({ "direction": "sideways" }));
+    let error = handle_tool_call(&mut server, "rust_analyzer_call_graph", args)
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("Unknown direction 'sideways'"));
+}
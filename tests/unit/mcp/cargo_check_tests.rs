@@ -0,0 +1,86 @@
+use serde_json::{json, Value};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::Mutex;
+
+use rust_analyzer_server::{
+    config::Config,
+    mcp::{handle_shared_tool_call, RustAnalyzerMCPServer},
+};
+use test_support::MockBackend;
+
+async fn shared_server(
+    backend: &MockBackend,
+) -> (tempfile::TempDir, Arc<Mutex<RustAnalyzerMCPServer>>) {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("lib.rs"), "fn main() {}\n").unwrap();
+    let config = Config {
+        retry_interval_millis: 10,
+        ..Config::default()
+    };
+    let mut server = RustAnalyzerMCPServer::with_config(dir.path().to_path_buf(), config);
+    server
+        .start_with_backend(Box::new(backend.clone()))
+        .await
+        .unwrap();
+    (dir, Arc::new(Mutex::new(server)))
+}
+
+async fn cargo_check(server: &Mutex<RustAnalyzerMCPServer>, args: Value) -> Value {
+    let result = handle_shared_tool_call(server, "rust_analyzer_cargo_check", args)
+        .await
+        .unwrap();
+    serde_json::from_str(&result.content[0].text).unwrap()
+}
+
+#[tokio::test]
+async fn test_concurrent_checks_share_one_flycheck() {
+    let backend = MockBackend::new();
+    let (_dir, server) = shared_server(&backend).await;
+    let progress = server.lock().await.progress_handle().unwrap();
+
+    // rust-analyzer: once asked, check for a while.
+    let requested = backend.clone();
+    let checker = tokio::spawn(async move {
+        while requested
+            .notifications("rust-analyzer/runFlycheck")
+            .is_empty()
+        {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let token = "rust-analyzer/flycheck/0".to_string();
+        progress
+            .lock()
+            .await
+            .begin(token.clone(), "cargo check".to_string(), None, None);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        progress.lock().await.end(&token);
+    });
+
+    let (first, second) = tokio::join!(
+        cargo_check(&server, json!({})),
+        cargo_check(&server, json!({ "severity": "error" }))
+    );
+    checker.await.unwrap();
+
+    assert_eq!(backend.notifications("rust-analyzer/runFlycheck").len(), 1);
+    for result in [&first, &second] {
+        assert_eq!(result["flycheck"]["completed"], true, "{}", result);
+        assert!(result["flycheck"]["duration_ms"].as_u64().unwrap() >= 100);
+        assert_eq!(result["summary"]["total_errors"], 0);
+    }
+    assert_ne!(
+        first["flycheck"]["coalesced"], second["flycheck"]["coalesced"],
+        "{} {}",
+        first, second
+    );
+
+    // The check finished, so the next call starts another.
+    let again = cargo_check(&server, json!({ "timeout_secs": 1 })).await;
+    assert_eq!(backend.notifications("rust-analyzer/runFlycheck").len(), 2);
+    assert_eq!(again["flycheck"]["coalesced"], false);
+    assert_eq!(again["flycheck"]["completed"], false);
+    assert!(again["flycheck"]["note"]
+        .as_str()
+        .unwrap()
+        .contains("did not finish within 1 seconds"));
+}
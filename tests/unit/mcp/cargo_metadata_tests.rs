@@ -0,0 +1,245 @@
+use serde_json::{json, Value};
+use std::time::{Duration, SystemTime};
+use tokio::sync::Mutex;
+
+use rust_analyzer_server::{
+    config::CargoSettings,
+    mcp::{
+        cargo_metadata::{feature_report, member_detail, summarize_metadata},
+        handle_shared_tool_call, RustAnalyzerMCPServer,
+    },
+};
+use test_support::MockBackend;
+
+fn metadata() -> Value {
+    json!({
+        "workspace_root": "/ws",
+        "target_directory": "/ws/target",
+        "workspace_members": ["path+file:///ws/app#0.2.0"],
+        "packages": [
+            {
+                "id": "path+file:///ws/app#0.2.0",
+                "name": "app",
+                "version": "0.2.0",
+                "manifest_path": "/ws/app/Cargo.toml",
+                "targets": [
+                    { "name": "app", "kind": ["lib"], "src_path": "/ws/app/src/lib.rs" },
+                    { "name": "app", "kind": ["bin"], "src_path": "/ws/app/src/main.rs" },
+                    { "name": "smoke", "kind": ["test"], "src_path": "/ws/app/tests/smoke.rs" },
+                    { "name": "build-script-build", "kind": ["custom-build"], "src_path": "/ws/app/build.rs" }
+                ],
+                "dependencies": [
+                    { "name": "serde", "req": "^1", "kind": null, "optional": false, "rename": null },
+                    { "name": "tokio", "req": "^1.40", "kind": "dev", "optional": false, "rename": null },
+                    { "name": "serde_json", "req": "^1", "kind": null, "optional": true, "rename": "json" }
+                ]
+            },
+            {
+                "id": "registry+https://github.com/rust-lang/crates.io-index#serde@1.0.210",
+                "name": "serde",
+                "version": "1.0.210",
+                "manifest_path": "/registry/serde-1.0.210/Cargo.toml",
+                "targets": [],
+                "dependencies": []
+            }
+        ]
+    })
+}
+
+#[test]
+fn test_summary_lists_members_with_lib_bin_targets_and_direct_deps() {
+    assert_eq!(
+        summarize_metadata(&metadata()),
+        json!({
+            "workspace_root": "/ws",
+            "target_directory": "/ws/target",
+            "members": [{
+                "name": "app",
+                "version": "0.2.0",
+                "manifest_path": "/ws/app/Cargo.toml",
+                "targets": [
+                    { "name": "app", "kind": "lib", "src_path": "/ws/app/src/lib.rs" },
+                    { "name": "app", "kind": "bin", "src_path": "/ws/app/src/main.rs" }
+                ],
+                "dependencies": [
+                    { "name": "serde", "req": "^1" },
+                    { "name": "tokio", "req": "^1.40", "kind": "dev" },
+                    { "name": "serde_json", "req": "^1", "optional": true, "rename": "json" }
+                ]
+            }]
+        })
+    );
+}
+
+#[test]
+fn test_member_detail_is_the_full_package() {
+    let metadata = metadata();
+    let app = member_detail(&metadata, "app").unwrap();
+    assert_eq!(app["targets"].as_array().unwrap().len(), 4);
+
+    // Dependencies aren't members.
+    let error = member_detail(&metadata, "serde").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "No workspace member named serde; members: app"
+    );
+}
+
+fn featured_metadata() -> Value {
+    json!({
+        "workspace_members": ["app"],
+        "packages": [{
+            "id": "app",
+            "name": "app",
+            "features": {
+                "default": ["std"],
+                "std": ["serde?/std"],
+                "serde": ["dep:serde", "derive"],
+                "derive": [],
+                "full": ["web", "tokio/full"],
+                "web": ["serde"],
+                "tokio": ["dep:tokio"]
+            },
+            "dependencies": [
+                { "name": "serde", "optional": true, "rename": null },
+                { "name": "tokio", "optional": true, "rename": null },
+                { "name": "log", "optional": false, "rename": null }
+            ]
+        }]
+    })
+}
+
+#[test]
+fn test_active_features_follow_defaults_requests_and_implications() {
+    let metadata = featured_metadata();
+
+    let report = feature_report(&metadata, &CargoSettings::default(), None).unwrap();
+    let app = &report["members"][0];
+    assert_eq!(
+        app["declared"],
+        json!(["default", "derive", "full", "serde", "std", "tokio", "web"])
+    );
+    assert_eq!(app["default"], json!(["std"]));
+    assert_eq!(app["active"], json!(["default", "std"]));
+    assert!(app.get("enables_optional_dependencies").is_none());
+    assert!(report.get("unknown_features").is_none());
+
+    let cargo = CargoSettings {
+        features: vec!["app/serde".to_string(), "sered".to_string()],
+        no_default_features: true,
+        ..CargoSettings::default()
+    };
+    let report = feature_report(&metadata, &cargo, None).unwrap();
+    assert_eq!(report["members"][0]["active"], json!(["derive", "serde"]));
+    assert_eq!(report["unknown_features"], json!(["sered"]));
+    assert_eq!(report["analysis"]["no_default_features"], true);
+
+    let cargo = CargoSettings {
+        all_features: true,
+        ..CargoSettings::default()
+    };
+    let report = feature_report(&metadata, &cargo, None).unwrap();
+    assert_eq!(report["members"][0]["inactive"], json!([]));
+}
+
+#[test]
+fn test_package_features_list_the_optional_dependencies_they_enable() {
+    let report =
+        feature_report(&featured_metadata(), &CargoSettings::default(), Some("app")).unwrap();
+    assert_eq!(
+        report["members"][0]["enables_optional_dependencies"],
+        json!({
+            "default": [],
+            "derive": [],
+            "full": ["serde", "tokio"],
+            "serde": ["serde"],
+            // `serde?/std` only applies once something else enables serde.
+            "std": [],
+            "tokio": ["tokio"],
+            "web": ["serde"]
+        })
+    );
+
+    let error =
+        feature_report(&featured_metadata(), &CargoSettings::default(), Some("lib")).unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("No workspace member named lib"));
+}
+
+#[tokio::test]
+async fn test_metadata_is_cached_until_the_manifest_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let manifest = root.join("Cargo.toml");
+    std::fs::write(
+        &manifest,
+        "[package]\nname = \"calc\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+    )
+    .unwrap();
+    std::fs::create_dir(root.join("src")).unwrap();
+    std::fs::write(root.join("src/lib.rs"), "").unwrap();
+
+    let mut server = RustAnalyzerMCPServer::with_workspace(root.clone());
+    server
+        .start_with_backend(Box::new(MockBackend::new()))
+        .await
+        .unwrap();
+    let server = Mutex::new(server);
+    let metadata = |args: Value| {
+        let server = &server;
+        async move {
+            let result = handle_shared_tool_call(server, "rust_analyzer_cargo_metadata", args)
+                .await
+                .unwrap();
+            serde_json::from_str::<Value>(&result.content[0].text).unwrap()
+        }
+    };
+
+    let first = metadata(json!({})).await;
+    assert_eq!(first["cached"], false);
+    assert_eq!(first["members"][0]["name"], "calc");
+    assert_eq!(first["members"][0]["targets"][0]["kind"], "lib");
+
+    let detail = metadata(json!({ "package": "calc" })).await;
+    assert_eq!(detail["cached"], true);
+    assert_eq!(detail["package"]["edition"], "2021");
+
+    std::fs::write(
+        &manifest,
+        "[package]\nname = \"calc\"\nversion = \"0.2.0\"\nedition = \"2021\"\n",
+    )
+    .unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(&manifest)
+        .unwrap()
+        .set_modified(SystemTime::now() + Duration::from_secs(60))
+        .unwrap();
+    let changed = metadata(json!({})).await;
+    assert_eq!(changed["cached"], false);
+    assert_eq!(changed["members"][0]["version"], "0.2.0");
+}
+
+#[tokio::test]
+async fn test_cargo_errors_are_returned_verbatim() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut server = RustAnalyzerMCPServer::with_workspace(dir.path().to_path_buf());
+    server
+        .start_with_backend(Box::new(MockBackend::new()))
+        .await
+        .unwrap();
+
+    let error = handle_shared_tool_call(
+        &Mutex::new(server),
+        "rust_analyzer_cargo_metadata",
+        json!({}),
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        error.to_string().contains("could not find `Cargo.toml`"),
+        "{}",
+        error
+    );
+}
@@ -0,0 +1,149 @@
+use serde_json::{json, Value};
+use std::path::Path;
+
+use rust_analyzer_server::mcp::{context::parse_location, handle_tool_call, RustAnalyzerMCPServer};
+use test_support::MockBackend;
+
+const SOURCE: &str = "\
+pub struct Counter {
+    count: u32,
+}
+
+impl Counter {
+    pub fn bump(&mut self, by: u32) -> u32 {
+        self.count += by;
+        self.count
+    }
+}
+";
+
+fn range(start: (u32, u32), end: (u32, u32)) -> Value {
+    json!({
+        "start": { "line": start.0, "character": start.1 },
+        "end": { "line": end.0, "character": end.1 }
+    })
+}
+
+fn counter_symbols() -> Value {
+    json!([
+        { "name": "Counter", "kind": 23, "range": range((0, 0), (2, 1)), "selectionRange": range((0, 11), (0, 18)) },
+        {
+            "name": "impl Counter", "kind": 19, "range": range((4, 0), (9, 1)), "selectionRange": range((4, 5), (4, 12)),
+            "children": [{
+                "name": "bump", "detail": "fn(&mut self, u32) -> u32", "kind": 6,
+                "range": range((5, 4), (8, 5)), "selectionRange": range((5, 11), (5, 15))
+            }]
+        }
+    ])
+}
+
+async fn server_for(root: &Path) -> RustAnalyzerMCPServer {
+    let backend = MockBackend::new().respond("textDocument/documentSymbol", counter_symbols());
+    let mut server = RustAnalyzerMCPServer::with_workspace(root.to_path_buf());
+    server.start_with_backend(Box::new(backend)).await.unwrap();
+    server
+}
+
+async fn context(server: &mut RustAnalyzerMCPServer, args: Value) -> anyhow::Result<Value> {
+    let result = handle_tool_call(server, "rust_analyzer_context", args).await?;
+    Ok(serde_json::from_str(&result.content[0].text).unwrap())
+}
+
+#[test]
+fn test_parse_location() {
+    assert_eq!(
+        parse_location("/ws/src/lib.rs:5:11"),
+        Some(("/ws/src/lib.rs", 5, 11))
+    );
+    assert_eq!(parse_location("src/lib.rs:5"), Some(("src/lib.rs", 5, 0)));
+    assert_eq!(parse_location("src/lib.rs"), None);
+    assert_eq!(parse_location(":5:1"), None);
+}
+
+#[tokio::test]
+async fn test_context_shows_numbered_lines_and_enclosing_item() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let file = root.join("lib.rs");
+    std::fs::write(&file, SOURCE).unwrap();
+    let mut server = server_for(&root).await;
+
+    let location = format!("{}:6:13", file.display());
+    let args = json!({ "location": location, "before": 2, "after": 1 });
+    let result = context(&mut server, args).await.unwrap();
+    assert_eq!(
+        result,
+        json!({
+            "file": file.display().to_string(),
+            "line": 6,
+            "total_lines": 10,
+            "enclosing": {
+                "name": "bump",
+                "kind": "method",
+                "signature": "fn bump(&mut self, u32) -> u32",
+                "location": format!("{}:5:11", file.display()),
+                "within": ["impl Counter"]
+            },
+            "source": "4 | impl Counter {\n\
+                       5 |     pub fn bump(&mut self, by: u32) -> u32 {\n\
+                       6 >         self.count += by;\n\
+                       7 |         self.count"
+        })
+    );
+
+    // Between items there's nothing enclosing; the window stops at the file's ends.
+    let args = json!({ "file_path": "lib.rs", "line": 3 });
+    let result = context(&mut server, args).await.unwrap();
+    assert_eq!(result["enclosing"], Value::Null);
+    assert!(result["source"]
+        .as_str()
+        .unwrap()
+        .starts_with("0 | pub struct Counter {"));
+    assert!(result["source"].as_str().unwrap().ends_with("9 | }"));
+
+    let error = context(&mut server, json!({ "file_path": "lib.rs", "line": 10 }))
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("past the end"), "{}", error);
+}
+
+#[tokio::test]
+async fn test_context_reads_dependency_sources_outside_the_workspace() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let workspace = root.join("app");
+    let dependency = root.join("counter");
+    for (dir, manifest) in [
+        (&workspace, "[package]\nname = \"app\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\ncounter = { path = \"../counter\" }\n"),
+        (&dependency, "[package]\nname = \"counter\"\nversion = \"0.1.0\"\nedition = \"2021\"\n"),
+    ] {
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+    }
+    std::fs::write(workspace.join("src/lib.rs"), "").unwrap();
+    let file = dependency.join("src/lib.rs");
+    std::fs::write(&file, SOURCE).unwrap();
+    let stray = root.join("stray.rs");
+    std::fs::write(&stray, SOURCE).unwrap();
+    let mut server = server_for(&workspace).await;
+
+    let location = format!("{}:1:4", file.display());
+    let result = context(&mut server, json!({ "location": location, "after": 0 }))
+        .await
+        .unwrap();
+    assert_eq!(result["enclosing"]["name"], "Counter");
+    assert_eq!(
+        result["source"],
+        "0 | pub struct Counter {\n1 >     count: u32,"
+    );
+
+    let location = format!("{}:1:4", stray.display());
+    let error = context(&mut server, json!({ "location": location }))
+        .await
+        .unwrap_err();
+    assert!(
+        error.to_string().contains("is not inside workspace"),
+        "{}",
+        error
+    );
+}
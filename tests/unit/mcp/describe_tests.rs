@@ -0,0 +1,150 @@
+use serde_json::{json, Value};
+
+use rust_analyzer_server::mcp::{
+    describe::{parse_hover, HoverInfo},
+    handle_tool_call, RustAnalyzerMCPServer,
+};
+use test_support::MockBackend;
+
+const HOVER: &str = "\n```rust\napp::counter\n```\n\n```rust\npub struct Counter {\n    count: u32,\n}\n```\n\n---\n\nsize = 4, align = 0x4\n\n---\n\nCounts things.\n\nStarts at zero.";
+
+const SOURCE: &str = "\
+/// Counts things.
+pub struct Counter {
+    count: u32,
+}
+
+fn main() {
+    let counter = Counter { count: 0 };
+}
+";
+
+fn range(start: (u32, u32), end: (u32, u32)) -> Value {
+    json!({
+        "start": { "line": start.0, "character": start.1 },
+        "end": { "line": end.0, "character": end.1 }
+    })
+}
+
+fn markdown(value: &str) -> Value {
+    json!({ "contents": { "kind": "markdown", "value": value } })
+}
+
+/// Call `rust_analyzer_describe` on `SOURCE` with `args`, answered by the
+/// backend `backend` builds from the file's URI, with the file's path
+/// shortened to `main.rs` in the report.
+async fn describe(backend: impl FnOnce(&str) -> MockBackend, mut args: Value) -> Value {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let file = root.join("main.rs");
+    std::fs::write(&file, SOURCE).unwrap();
+    let backend = backend(&format!("file://{}", file.display()));
+    let mut server = RustAnalyzerMCPServer::with_workspace(root);
+    server.start_with_backend(Box::new(backend)).await.unwrap();
+
+    args["file_path"] = json!(file.to_string_lossy());
+    let result = handle_tool_call(&mut server, "rust_analyzer_describe", args)
+        .await
+        .unwrap();
+    let text = result.content[0]
+        .text
+        .replace(&file.display().to_string(), "main.rs");
+    serde_json::from_str(&text).unwrap()
+}
+
+#[test]
+fn test_parse_hover_splits_signature_layout_and_docs() {
+    assert_eq!(
+        parse_hover(&markdown(HOVER)),
+        HoverInfo {
+            path: Some("app::counter".to_string()),
+            signature: Some("pub struct Counter {\n    count: u32,\n}".to_string()),
+            layout: Some("size = 4, align = 0x4".to_string()),
+            docs: Some("Counts things.\n\nStarts at zero.".to_string()),
+        }
+    );
+    assert_eq!(
+        parse_hover(&json!({ "contents": "```rust\ni32\n```" })),
+        HoverInfo {
+            signature: Some("i32".to_string()),
+            ..HoverInfo::default()
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_describe_combines_hover_definition_and_snippet() {
+    let backend = |uri: &str| {
+        MockBackend::new()
+            .respond("textDocument/hover", markdown(HOVER))
+            .respond(
+                "textDocument/definition",
+                json!([{
+                    "targetUri": uri,
+                    "targetRange": range((0, 0), (3, 1)),
+                    "targetSelectionRange": range((1, 11), (1, 18))
+                }]),
+            )
+    };
+    let args = json!({ "line": 6, "character": 18, "max_doc_chars": 10 });
+    let report = describe(backend, args).await;
+    assert_eq!(
+        report,
+        json!({
+            "hover": {
+                "path": "app::counter",
+                "signature": "pub struct Counter {\n    count: u32,\n}",
+                "layout": "size = 4, align = 0x4",
+                "docs": "Counts thi…",
+                "docs_truncated": true,
+                "defined_in": "main.rs:1:11"
+            },
+            "definition": "main.rs:1:11",
+            "snippet": "1 > pub struct Counter {\n2 |     count: u32,\n3 | }"
+        })
+    );
+
+    // Without a full range, the snippet runs for snippet_lines.
+    let backend = |uri: &str| {
+        MockBackend::new().respond(
+            "textDocument/definition",
+            json!({ "uri": uri, "range": range((5, 3), (5, 7)) }),
+        )
+    };
+    let args = json!({ "line": 6, "character": 18, "snippet_lines": 2 });
+    let report = describe(backend, args).await;
+    assert_eq!(
+        report["snippet"],
+        "5 > fn main() {\n6 |     let counter = Counter { count: 0 };"
+    );
+    assert_eq!(
+        report["unavailable"],
+        json!({ "hover": "No hover information at this position" })
+    );
+}
+
+#[tokio::test]
+async fn test_describe_keeps_hover_without_a_definition() {
+    // A primitive type has a hover but nothing to go to.
+    let backend = |_: &str| {
+        MockBackend::new()
+            .respond("textDocument/hover", markdown("```rust\nu32\n```"))
+            .respond("textDocument/definition", json!([]))
+    };
+    let report = describe(backend, json!({ "line": 2, "character": 11 })).await;
+    assert_eq!(
+        report,
+        json!({
+            "hover": { "signature": "u32" },
+            "unavailable": { "definition": "No definition found" }
+        })
+    );
+
+    let backend = |_: &str| {
+        MockBackend::new()
+            .respond("textDocument/hover", markdown("```rust\nu32\n```"))
+            .fail("textDocument/definition", "content modified")
+    };
+    let report = describe(backend, json!({ "line": 2, "character": 11 })).await;
+    assert_eq!(report["unavailable"]["definition"], "content modified");
+}
@@ -0,0 +1,254 @@
+use serde_json::{json, Value};
+use std::{path::Path, sync::Arc, time::Duration};
+use tokio::sync::Mutex;
+
+use rust_analyzer_server::{
+    config::Config,
+    mcp::{handle_shared_tool_call, handle_tool_call, RustAnalyzerMCPServer},
+    protocol::{ErrorCode, ToolError},
+};
+use test_support::MockBackend;
+
+fn write(root: &Path, path: &str) -> String {
+    let file = root.join(path);
+    std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+    std::fs::write(&file, "fn f() {}\n").unwrap();
+    format!("file://{}", file.display())
+}
+
+fn diagnostic(severity: u32, message: &str) -> Value {
+    json!({
+        "range": {
+            "start": { "line": 0, "character": 3 },
+            "end": { "line": 0, "character": 4 }
+        },
+        "severity": severity,
+        "message": message
+    })
+}
+
+/// A crate with two files under `src/net`, one with an error and one with
+/// a warning, plus files elsewhere and a build output that's skipped.
+fn workspace(root: &Path) -> MockBackend {
+    for path in ["src/lib.rs", "tests/it.rs", "target/debug/build/out.rs"] {
+        write(root, path);
+    }
+    let http = write(root, "src/net/http.rs");
+    let tcp = write(root, "src/net/tcp.rs");
+    MockBackend::new()
+        .respond_for(
+            "textDocument/diagnostic",
+            &http,
+            json!({ "items": [diagnostic(1, "mismatched types")] }),
+        )
+        .respond_for(
+            "textDocument/diagnostic",
+            &tcp,
+            json!({ "items": [diagnostic(2, "unused variable")] }),
+        )
+}
+
+async fn server(
+    root: &Path,
+    backend: &MockBackend,
+    max_open_documents: u64,
+) -> RustAnalyzerMCPServer {
+    let config = Config {
+        retry_interval_millis: 10,
+        max_open_documents,
+        ..Config::default()
+    };
+    let mut server = RustAnalyzerMCPServer::with_config(root.to_path_buf(), config);
+    server
+        .start_with_backend(Box::new(backend.clone()))
+        .await
+        .unwrap();
+    server
+}
+
+async fn diagnostics_for(server: &mut RustAnalyzerMCPServer, args: Value) -> anyhow::Result<Value> {
+    let result = handle_tool_call(server, "rust_analyzer_diagnostics_for", args).await?;
+    Ok(serde_json::from_str(&result.content[0].text)?)
+}
+
+#[tokio::test]
+async fn test_diagnostics_for_a_directory_after_a_check() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let backend = workspace(&root);
+    let server = Arc::new(Mutex::new(server(&root, &backend, 100).await));
+    let progress = server.lock().await.progress_handle().unwrap();
+
+    // rust-analyzer: once asked, check for a while.
+    let requested = backend.clone();
+    let checker = tokio::spawn(async move {
+        while requested
+            .notifications("rust-analyzer/runFlycheck")
+            .is_empty()
+        {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let token = "rust-analyzer/flycheck/0".to_string();
+        progress
+            .lock()
+            .await
+            .begin(token.clone(), "cargo check".to_string(), None, None);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        progress.lock().await.end(&token);
+    });
+
+    let args = json!({ "path": "src/net", "relative_paths": true });
+    let result = handle_shared_tool_call(&server, "rust_analyzer_diagnostics_for", args)
+        .await
+        .unwrap();
+    checker.await.unwrap();
+    let result: Value = serde_json::from_str(&result.content[0].text).unwrap();
+
+    assert_eq!(result["path"], "src/net");
+    assert_eq!(result["flycheck"]["completed"], true, "{}", result);
+    assert_eq!(result["summary"]["files_checked"], 2);
+    assert_eq!(result["summary"]["total_files"], 2);
+    assert_eq!(result["summary"]["total_errors"], 1);
+    assert_eq!(result["summary"]["total_warnings"], 1);
+    assert_eq!(
+        result["files"]["src/net/http.rs"]["diagnostics"][0]["message"],
+        "mismatched types"
+    );
+    assert_eq!(result["files"]["src/net/tcp.rs"]["summary"]["warnings"], 1);
+    assert!(result.get("truncated").is_none());
+    assert_eq!(backend.notifications("textDocument/didOpen").len(), 2);
+}
+
+#[tokio::test]
+async fn test_diagnostics_for_a_glob_is_capped() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let backend = workspace(&root);
+    let mut server = server(&root, &backend, 100).await;
+
+    let args = json!({
+        "path": "**/*.rs",
+        "max_files": "2",
+        "severity": "error",
+        "timeout_secs": 1
+    });
+    let result = diagnostics_for(&mut server, args).await.unwrap();
+    // lib.rs, net/http.rs, net/tcp.rs and it.rs; nothing under target.
+    assert_eq!(result["matched_files"], 4);
+    assert_eq!(result["truncated"], true);
+    assert!(result["note"]
+        .as_str()
+        .unwrap()
+        .starts_with("4 files match **/*.rs; only the first 2 were checked"));
+    assert_eq!(result["summary"]["files_checked"], 2);
+    assert_eq!(result["summary"]["total_errors"], 1);
+    assert_eq!(result["summary"]["total_warnings"], 0);
+    assert_eq!(result["flycheck"]["completed"], false);
+
+    // Never more than stay open at once.
+    let mut server = self::server(&root, &backend, 1).await;
+    let args = json!({ "path": "src/**/*.rs", "timeout_secs": 1 });
+    let result = diagnostics_for(&mut server, args).await.unwrap();
+    assert_eq!(result["summary"]["files_checked"], 1);
+    assert_eq!(result["matched_files"], 3);
+}
+
+#[tokio::test]
+async fn test_diagnostics_for_bad_paths() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let backend = workspace(&root);
+    let mut server = server(&root, &backend, 100).await;
+
+    for (args, code, message) in [
+        (json!({}), ErrorCode::InvalidParams, "Missing path"),
+        (
+            json!({ "path": "src", "max_files": 0 }),
+            ErrorCode::InvalidParams,
+            "max_files must be at least 1",
+        ),
+        (
+            json!({ "path": "benches/*.rs" }),
+            ErrorCode::FileNotFound,
+            "No .rs files match benches/*.rs",
+        ),
+    ] {
+        let error = diagnostics_for(&mut server, args).await.unwrap_err();
+        assert_eq!(error.to_string(), message);
+        assert_eq!(ToolError::from_anyhow(&error).code, code);
+    }
+
+    let error = diagnostics_for(&mut server, json!({ "path": "docs" }))
+        .await
+        .unwrap_err();
+    assert!(
+        error.to_string().starts_with("No such directory:"),
+        "{}",
+        error
+    );
+    assert!(backend
+        .notifications("rust-analyzer/runFlycheck")
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_diagnostics_for_keeps_its_files_open_during_the_check() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let backend = workspace(&root).respond(
+        "textDocument/hover",
+        json!({ "contents": { "kind": "markdown", "value": "fn f()" } }),
+    );
+    let server = Arc::new(Mutex::new(server(&root, &backend, 2).await));
+    let progress = server.lock().await.progress_handle().unwrap();
+
+    // While the check runs, other calls open more files than stay open.
+    let requested = backend.clone();
+    let other_calls = Arc::clone(&server);
+    let root_dir = root.clone();
+    let checker = tokio::spawn(async move {
+        while requested
+            .notifications("rust-analyzer/runFlycheck")
+            .is_empty()
+        {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        for path in ["src/lib.rs", "tests/it.rs"] {
+            let args = json!({
+                "file_path": root_dir.join(path).to_string_lossy(),
+                "line": 0,
+                "character": 3
+            });
+            handle_shared_tool_call(&other_calls, "rust_analyzer_hover", args)
+                .await
+                .unwrap();
+        }
+        let token = "rust-analyzer/flycheck/0".to_string();
+        progress
+            .lock()
+            .await
+            .begin(token.clone(), "cargo check".to_string(), None, None);
+        progress.lock().await.end(&token);
+    });
+
+    let args = json!({ "path": "src/net", "relative_paths": true });
+    let result = handle_shared_tool_call(&server, "rust_analyzer_diagnostics_for", args)
+        .await
+        .unwrap();
+    checker.await.unwrap();
+    let result: Value = serde_json::from_str(&result.content[0].text).unwrap();
+
+    assert_eq!(result["summary"]["total_errors"], 1, "{}", result);
+    assert_eq!(result["summary"]["total_warnings"], 1);
+    let closed = backend.notifications("textDocument/didClose");
+    assert!(
+        closed.iter().all(|params| !params["textDocument"]["uri"]
+            .as_str()
+            .unwrap()
+            .contains("/src/net/")),
+        "{:?}",
+        closed
+    );
+    // The other calls' files were closed instead.
+    assert!(!closed.is_empty());
+}
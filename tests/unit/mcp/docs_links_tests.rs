@@ -0,0 +1,189 @@
+use serde_json::{json, Value};
+
+use rust_analyzer_server::mcp::{
+    describe::HoverInfo, docs_links::docs_rs_url, handle_tool_call, RustAnalyzerMCPServer,
+};
+use test_support::MockBackend;
+
+/// `tokio-util` 0.7.11 from crates.io, as `cargo metadata` lists it.
+fn registry_package() -> Value {
+    json!({
+        "name": "tokio-util",
+        "version": "0.7.11",
+        "source": "registry+https://github.com/rust-lang/crates.io-index",
+        "targets": [{ "name": "tokio-util", "kind": ["lib"] }]
+    })
+}
+
+fn hover(path: &str, signature: &str) -> HoverInfo {
+    HoverInfo {
+        path: Some(path.to_string()),
+        signature: Some(signature.to_string()),
+        ..HoverInfo::default()
+    }
+}
+
+#[test]
+fn test_docs_rs_url_from_path_and_signature() {
+    let package = registry_package();
+    assert_eq!(
+        docs_rs_url(
+            &package,
+            &hover("tokio_util::sync", "pub struct CancellationToken {")
+        )
+        .as_deref(),
+        Some("https://docs.rs/tokio-util/0.7.11/tokio_util/sync/struct.CancellationToken.html")
+    );
+    assert_eq!(
+        docs_rs_url(
+            &package,
+            &hover("tokio_util::io", "pub async fn read_buf<R>(read: &mut R)")
+        )
+        .as_deref(),
+        Some("https://docs.rs/tokio-util/0.7.11/tokio_util/io/fn.read_buf.html")
+    );
+    assert_eq!(
+        docs_rs_url(&package, &hover("tokio_util", "pub mod codec")).as_deref(),
+        Some("https://docs.rs/tokio-util/0.7.11/tokio_util/codec/index.html")
+    );
+    assert_eq!(
+        docs_rs_url(
+            &package,
+            &hover("tokio_util::codec", "pub const MAX: usize = 8")
+        )
+        .as_deref(),
+        Some("https://docs.rs/tokio-util/0.7.11/tokio_util/codec/constant.MAX.html")
+    );
+}
+
+#[test]
+fn test_docs_rs_url_gives_up_on_what_it_cannot_place() {
+    let package = registry_package();
+    // Methods: under an `impl` line, or taking `self`.
+    let method = "impl CancellationToken\npub fn cancel(&self)";
+    assert_eq!(
+        docs_rs_url(&package, &hover("tokio_util::sync", method)),
+        None
+    );
+    let method = "pub fn cancel(&self)";
+    assert_eq!(
+        docs_rs_url(
+            &package,
+            &hover("tokio_util::sync::CancellationToken", method)
+        ),
+        None
+    );
+    // Fields declare no item.
+    assert_eq!(
+        docs_rs_url(
+            &package,
+            &hover("tokio_util::sync::Token", "pub inner: Arc<Inner>")
+        ),
+        None
+    );
+    // A path from another crate, and a crate not from a registry.
+    assert_eq!(
+        docs_rs_url(&package, &hover("tokio::sync", "pub struct Mutex<T>")),
+        None
+    );
+    let mut git = registry_package();
+    git["source"] = json!("git+https://github.com/tokio-rs/tokio");
+    assert_eq!(
+        docs_rs_url(
+            &git,
+            &hover("tokio_util::sync", "pub struct CancellationToken")
+        ),
+        None
+    );
+}
+
+const SOURCE: &str = "pub struct Counter;\n\nfn main() {\n    let counter = Counter;\n}\n";
+
+/// Call `rust_analyzer_hover` on `SOURCE` at line 3 asking for links, with
+/// the definition `definition` builds from the file's URI.
+async fn hover_with(backend: MockBackend, definition: impl FnOnce(&str) -> Value) -> Value {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let file = root.join("main.rs");
+    std::fs::write(&file, SOURCE).unwrap();
+    let backend = backend
+        .respond(
+            "textDocument/hover",
+            json!({ "contents": { "kind": "markdown", "value": "```rust\napp\n```\n\n```rust\npub struct Counter\n```" } }),
+        )
+        .respond("textDocument/definition", definition(&format!("file://{}", file.display())));
+    let mut server = RustAnalyzerMCPServer::with_workspace(root);
+    server.start_with_backend(Box::new(backend)).await.unwrap();
+
+    let args = json!({
+        "file_path": file.to_string_lossy(),
+        "line": 3,
+        "character": 18,
+        "links": true
+    });
+    let result = handle_tool_call(&mut server, "rust_analyzer_hover", args)
+        .await
+        .unwrap();
+    serde_json::from_str(&result.content[0].text).unwrap()
+}
+
+#[tokio::test]
+async fn test_hover_of_workspace_item_says_where_it_is_defined() {
+    let definition = |uri: &str| {
+        json!([{
+            "targetUri": uri,
+            "targetRange": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 19 } },
+            "targetSelectionRange": { "start": { "line": 0, "character": 11 }, "end": { "line": 0, "character": 18 } }
+        }])
+    };
+    let hover = hover_with(MockBackend::new(), definition).await;
+    assert_eq!(hover["defined_in"], "main.rs:0:11");
+    assert!(hover.get("docs_url").is_none());
+    assert!(hover["contents"]["value"].is_string());
+}
+
+#[tokio::test]
+async fn test_hover_of_external_item_falls_back_to_external_docs() {
+    let backend = MockBackend::new().respond(
+        "experimental/externalDocs",
+        json!({ "web": "https://docs.rs/app/latest/app/struct.Counter.html", "local": null }),
+    );
+    let definition = |_: &str| {
+        json!({
+            "uri": "file:///nonexistent/registry/app-1.0.0/src/lib.rs",
+            "range": { "start": { "line": 0, "character": 11 }, "end": { "line": 0, "character": 18 } }
+        })
+    };
+    let hover = hover_with(backend.clone(), definition).await;
+    assert_eq!(
+        hover["docs_url"],
+        "https://docs.rs/app/latest/app/struct.Counter.html"
+    );
+    assert!(hover.get("defined_in").is_none());
+    assert_eq!(backend.requests("experimental/externalDocs").len(), 1);
+}
+
+#[tokio::test]
+async fn test_plain_hover_asks_for_no_links() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("main.rs");
+    std::fs::write(&file, SOURCE).unwrap();
+    let backend = MockBackend::new().respond(
+        "textDocument/hover",
+        json!({ "contents": { "kind": "markdown", "value": "```rust\npub struct Counter\n```" } }),
+    );
+    let mut server = RustAnalyzerMCPServer::with_workspace(dir.path().to_path_buf());
+    server
+        .start_with_backend(Box::new(backend.clone()))
+        .await
+        .unwrap();
+
+    let args = json!({ "file_path": file.to_string_lossy(), "line": 3, "character": 18 });
+    let result = handle_tool_call(&mut server, "rust_analyzer_hover", args)
+        .await
+        .unwrap();
+    let hover: Value = serde_json::from_str(&result.content[0].text).unwrap();
+    assert!(hover.get("defined_in").is_none());
+    assert!(backend.requests("textDocument/definition").is_empty());
+    assert!(backend.requests("experimental/externalDocs").is_empty());
+}
@@ -0,0 +1,160 @@
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use rust_analyzer_server::{
+    mcp::{handle_tool_call, RustAnalyzerMCPServer},
+    protocol::{ErrorCode, ToolError},
+};
+use test_support::MockBackend;
+
+fn range(line: u32, character: u32, end_character: u32) -> Value {
+    json!({
+        "start": { "line": line, "character": character },
+        "end": { "line": line, "character": end_character }
+    })
+}
+
+fn symbol(name: &str, kind: u32, container: &str, uri: &str, line: u32) -> Value {
+    json!({
+        "name": name,
+        "kind": kind,
+        "containerName": container,
+        "location": { "uri": uri, "range": range(line, 7, 7 + name.len() as u32) }
+    })
+}
+
+fn reference(uri: &str, line: u32, character: u32) -> Value {
+    json!({ "uri": uri, "range": range(line, character, character + 5) })
+}
+
+/// `parse` is a function in `config` and a method of `Args` in `cli`.
+fn backend() -> MockBackend {
+    MockBackend::new()
+        .respond(
+            "workspace/symbol",
+            json!([
+                symbol("parse", 12, "config", "file:///ws/src/config.rs", 3),
+                symbol("parse", 6, "Args", "file:///ws/src/cli.rs", 10),
+                symbol("parser", 2, "crate", "file:///ws/src/parser.rs", 0)
+            ]),
+        )
+        .respond(
+            "textDocument/references",
+            json!([
+                reference("file:///ws/src/main.rs", 8, 4),
+                reference("file:///ws/src/config.rs", 3, 7),
+                reference("file:///ws/src/main.rs", 2, 12)
+            ]),
+        )
+}
+
+async fn find_references(backend: &MockBackend, args: Value) -> Value {
+    let mut server = RustAnalyzerMCPServer::with_workspace("/ws".into());
+    server
+        .start_with_backend(Box::new(backend.clone()))
+        .await
+        .unwrap();
+    let result = handle_tool_call(&mut server, "rust_analyzer_find_references", args)
+        .await
+        .unwrap();
+    serde_json::from_str(&result.content[0].text).unwrap()
+}
+
+#[tokio::test]
+async fn test_find_references_by_name_and_kind() {
+    let backend = backend();
+    let result = find_references(&backend, json!({ "symbol": "parse", "kind": "function" })).await;
+    assert_eq!(
+        result,
+        json!({
+            "symbol": {
+                "name": "parse",
+                "kind": "function",
+                "container": "config",
+                "location": "/ws/src/config.rs:3:7"
+            },
+            "total": 3,
+            "references": [
+                { "file": "/ws/src/config.rs", "count": 1, "locations": ["/ws/src/config.rs:3:7"] },
+                {
+                    "file": "/ws/src/main.rs",
+                    "count": 2,
+                    "locations": ["/ws/src/main.rs:8:4", "/ws/src/main.rs:2:12"]
+                }
+            ]
+        })
+    );
+
+    // Functions are searched for too, and references asked for at the
+    // definition.
+    assert_eq!(backend.requests("workspace/symbol")[0]["query"], "parse#");
+    assert_eq!(
+        backend.requests("textDocument/references"),
+        [json!({
+            "textDocument": { "uri": "file:///ws/src/config.rs" },
+            "position": { "line": 3, "character": 7 },
+            "context": { "includeDeclaration": true }
+        })]
+    );
+}
+
+#[tokio::test]
+async fn test_find_references_by_container_path() {
+    let backend = backend();
+    let result = find_references(
+        &backend,
+        json!({ "symbol": "parse", "path": "Args::parse" }),
+    )
+    .await;
+    assert_eq!(result["symbol"]["kind"], "method");
+    assert_eq!(result["symbol"]["location"], "/ws/src/cli.rs:10:7");
+}
+
+#[tokio::test]
+async fn test_find_references_lists_candidates_when_ambiguous() {
+    let backend = backend();
+    let result = find_references(&backend, json!({ "symbol": "parse" })).await;
+    assert!(result["ambiguous"]
+        .as_str()
+        .unwrap()
+        .starts_with("2 symbols are named parse"));
+    assert_eq!(
+        result["candidates"],
+        json!([
+            { "name": "parse", "kind": "function", "container": "config", "location": "/ws/src/config.rs:3:7" },
+            { "name": "parse", "kind": "method", "container": "Args", "location": "/ws/src/cli.rs:10:7" }
+        ])
+    );
+    assert!(backend.requests("textDocument/references").is_empty());
+}
+
+#[tokio::test]
+async fn test_find_references_without_a_match() {
+    let mut server = RustAnalyzerMCPServer::with_workspace("/ws".into());
+    server
+        .start_with_backend(Box::new(backend()))
+        .await
+        .unwrap();
+    let args = json!({ "symbol": "parse", "kind": "trait" });
+    let error = handle_tool_call(&mut server, "rust_analyzer_find_references", args)
+        .await
+        .unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("No symbol named parse of kind trait"));
+}
+
+#[tokio::test]
+async fn test_find_references_honours_timeout_secs() {
+    // Slower than the call's timeout_secs, well within the configured one.
+    let backend = backend().delay("textDocument/references", Duration::from_secs(2));
+    let mut server = RustAnalyzerMCPServer::with_workspace("/ws".into());
+    server.start_with_backend(Box::new(backend)).await.unwrap();
+    let args = json!({ "symbol": "parse", "kind": "function", "timeout_secs": 1 });
+    let error = handle_tool_call(&mut server, "rust_analyzer_find_references", args)
+        .await
+        .unwrap_err();
+    let error = ToolError::from_anyhow(&error);
+    assert_eq!(error.code, ErrorCode::Timeout);
+    assert_eq!(error.details.unwrap()["timeout_secs"], 1.0);
+}
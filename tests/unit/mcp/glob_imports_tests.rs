@@ -0,0 +1,169 @@
+use serde_json::{json, Value};
+
+use rust_analyzer_server::mcp::{
+    glob_imports::find_glob_imports, handle_tool_call, RustAnalyzerMCPServer,
+};
+use test_support::MockBackend;
+
+const SOURCE: &str = "\
+use std::collections::*;
+use std::io::*;
+use std::fmt::{self, *};
+
+fn main() {
+    let map: HashMap<u8, u8> = HashMap::new();
+}
+";
+
+#[test]
+fn test_find_glob_imports_in_use_items() {
+    assert_eq!(find_glob_imports(SOURCE), vec![(0, 22), (1, 13), (2, 21)]);
+
+    let text = "\
+pub(crate) use crate::{
+    a::*, // b::*
+    c,
+};
+// use d::*;
+fn area(w: u32, h: u32) -> u32 { w * h }
+";
+    assert_eq!(find_glob_imports(text), vec![(1, 7)]);
+}
+
+/// A resolved "Expand glob import" action replacing `old` on `line` of
+/// `uri` with `new`.
+fn expand_action(uri: &str, line: u32, old: &str, new: &str) -> Value {
+    json!([
+        { "title": "Remove unused import" },
+        {
+            "title": "Expand glob import",
+            "edit": { "changes": { uri: [{
+                "range": {
+                    "start": { "line": line, "character": 0 },
+                    "end": { "line": line, "character": old.len() }
+                },
+                "newText": new
+            }] } }
+        }
+    ])
+}
+
+/// A server on a workspace holding `SOURCE` in `main.rs`, answering
+/// through the backend `backend` builds from the file's URI.
+async fn server_with(
+    backend: impl FnOnce(&str) -> MockBackend,
+) -> (tempfile::TempDir, std::path::PathBuf, RustAnalyzerMCPServer) {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let file = root.join("main.rs");
+    std::fs::write(&file, SOURCE).unwrap();
+    let backend = backend(&format!("file://{}", file.display()));
+    let mut server = RustAnalyzerMCPServer::with_workspace(root);
+    server.start_with_backend(Box::new(backend)).await.unwrap();
+    (dir, file, server)
+}
+
+async fn call(server: &mut RustAnalyzerMCPServer, args: Value) -> anyhow::Result<Value> {
+    let result = handle_tool_call(server, "rust_analyzer_expand_glob_import", args).await?;
+    Ok(serde_json::from_str(&result.content[0].text)?)
+}
+
+#[tokio::test]
+async fn test_expand_glob_import_at_position() {
+    let (_dir, file, mut server) = server_with(|uri| {
+        MockBackend::new().respond(
+            "textDocument/codeAction",
+            expand_action(
+                uri,
+                0,
+                "use std::collections::*;",
+                "use std::collections::HashMap;",
+            ),
+        )
+    })
+    .await;
+    let args = json!({ "file_path": file.to_string_lossy(), "line": 0, "character": 22 });
+
+    let result = call(&mut server, args.clone()).await.unwrap();
+    assert_eq!(
+        result,
+        json!({
+            "expanded": [{ "line": 0, "import": "use std::collections::*;" }],
+            "diff": ["- use std::collections::*;", "+ use std::collections::HashMap;"]
+        })
+    );
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), SOURCE);
+
+    let mut apply = args;
+    apply["apply"] = json!(true);
+    let result = call(&mut server, apply).await.unwrap();
+    assert_eq!(result["applied"], true);
+    assert!(std::fs::read_to_string(&file)
+        .unwrap()
+        .starts_with("use std::collections::HashMap;\nuse std::io::*;"));
+}
+
+#[tokio::test]
+async fn test_expand_glob_import_without_the_assist_is_an_error() {
+    let (_dir, file, mut server) = server_with(|_| {
+        MockBackend::new().respond(
+            "textDocument/codeAction",
+            json!([{ "title": "Remove unused import" }]),
+        )
+    })
+    .await;
+    let args = json!({ "file_path": file.to_string_lossy(), "line": 5, "character": 4 });
+    let error = call(&mut server, args).await.unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("no glob import here; available: Remove unused import"));
+}
+
+#[tokio::test]
+async fn test_expand_all_glob_imports_one_after_another() {
+    let (_dir, file, mut server) = server_with(|uri| {
+        MockBackend::new()
+            .respond_at(
+                "textDocument/codeAction",
+                uri,
+                0,
+                expand_action(
+                    uri,
+                    0,
+                    "use std::collections::*;",
+                    "use std::collections::HashMap;",
+                ),
+            )
+            .respond_at(
+                "textDocument/codeAction",
+                uri,
+                2,
+                expand_action(uri, 2, "use std::fmt::{self, *};", "use std::fmt::{self};"),
+            )
+    })
+    .await;
+    let args = json!({ "file_path": file.to_string_lossy(), "all": true, "apply": true });
+
+    let result = call(&mut server, args).await.unwrap();
+    assert_eq!(
+        result["expanded"],
+        json!([
+            { "line": 0, "import": "use std::collections::*;" },
+            { "line": 2, "import": "use std::fmt::{self, *};" }
+        ])
+    );
+    assert_eq!(
+        result["skipped"],
+        json!([{
+            "line": 1,
+            "import": "use std::io::*;",
+            "reason": "rust-analyzer offers no code actions here"
+        }])
+    );
+    assert_eq!(
+        std::fs::read_to_string(&file).unwrap(),
+        SOURCE
+            .replace("collections::*", "collections::HashMap")
+            .replace("{self, *}", "{self}")
+    );
+}
@@ -0,0 +1,1252 @@
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use tokio::sync::Mutex;
+
+use rust_analyzer_server::{
+    config::Config,
+    mcp::{
+        handle_shared_tool_call, handle_tool_call, refuse_while_indexing, RustAnalyzerMCPServer,
+    },
+    prewarm::{prewarm, SharedPrewarmStatus},
+    protocol::{ErrorCode, ToolError},
+};
+use test_support::MockBackend;
+
+/// A server on a one-file workspace, answering through `backend`, with
+/// timings short enough that retries take milliseconds.
+async fn mock_server(
+    backend: &MockBackend,
+    indexing_timeout_secs: u64,
+) -> (TempDir, RustAnalyzerMCPServer) {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("lib.rs"),
+        "pub fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n",
+    )
+    .unwrap();
+    let config = Config {
+        indexing_timeout_secs,
+        retry_interval_millis: 10,
+        document_open_delay_millis: 0,
+        request_timeout_secs: 1,
+        ..Config::default()
+    };
+    let mut server = RustAnalyzerMCPServer::with_config(dir.path().to_path_buf(), config);
+    server
+        .start_with_backend(Box::new(backend.clone()))
+        .await
+        .unwrap();
+    (dir, server)
+}
+
+fn at(dir: &TempDir, line: u32, character: u32) -> Value {
+    json!({
+        "file_path": dir.path().join("lib.rs").to_string_lossy(),
+        "line": line,
+        "character": character
+    })
+}
+
+async fn call(
+    server: &mut RustAnalyzerMCPServer,
+    tool: &str,
+    args: Value,
+) -> anyhow::Result<Value> {
+    let result = handle_tool_call(server, tool, args).await?;
+    Ok(serde_json::from_str(&result.content[0].text)?)
+}
+
+#[tokio::test]
+async fn test_hover_retries_while_indexing() {
+    let hover =
+        json!({ "contents": { "kind": "markdown", "value": "fn add(a: i32, b: i32) -> i32" } });
+    let backend = MockBackend::new()
+        .respond("textDocument/hover", hover.clone())
+        .indexing("textDocument/hover", 3);
+    let (dir, mut server) = mock_server(&backend, 5).await;
+
+    let result = call(&mut server, "rust_analyzer_hover", at(&dir, 0, 7))
+        .await
+        .unwrap();
+    assert_eq!(result, hover);
+    assert_eq!(backend.requests("textDocument/hover").len(), 4);
+    assert_eq!(backend.notifications("textDocument/didOpen").len(), 1);
+}
+
+#[tokio::test]
+async fn test_still_indexing_after_timeout() {
+    let backend = MockBackend::new().indexing("textDocument/definition", usize::MAX);
+    let (dir, mut server) = mock_server(&backend, 0).await;
+    server.progress_handle().unwrap().lock().await.begin(
+        "rustAnalyzer/Indexing".to_string(),
+        "Indexing".to_string(),
+        None,
+        Some(40),
+    );
+
+    let error = call(&mut server, "rust_analyzer_definition", at(&dir, 0, 7))
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("still indexing"), "{}", error);
+}
+
+#[tokio::test]
+async fn test_still_indexing_error_is_retryable() {
+    let backend = MockBackend::new().indexing("textDocument/hover", usize::MAX);
+    let (dir, mut server) = mock_server(&backend, 0).await;
+    server.progress_handle().unwrap().lock().await.begin(
+        "rustAnalyzer/Indexing".to_string(),
+        "Indexing".to_string(),
+        None,
+        Some(40),
+    );
+
+    let error = call(&mut server, "rust_analyzer_hover", at(&dir, 0, 7))
+        .await
+        .unwrap_err();
+    let error = ToolError::from_anyhow(&error).to_json();
+    assert_eq!(error["code"], "indexing");
+    assert_eq!(error["retryable"], true);
+    assert_eq!(error["details"]["waited_secs"], 0);
+    assert!(error["details"]["percent"].is_u64());
+    assert!(error["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("Rust-analyzer is still indexing"));
+}
+
+#[tokio::test]
+async fn test_fail_fast_while_indexing() {
+    let backend = MockBackend::new().respond("textDocument/hover", json!(null));
+    let (dir, mut server) = mock_server(&backend, 30).await;
+    server.progress_handle().unwrap().lock().await.begin(
+        "rustAnalyzer/Indexing".to_string(),
+        "Indexing".to_string(),
+        None,
+        Some(40),
+    );
+
+    let mut args = at(&dir, 0, 7);
+    args["wait_for_index"] = json!(false);
+    let start = Instant::now();
+    let error = call(&mut server, "rust_analyzer_hover", args)
+        .await
+        .unwrap_err();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    let error = ToolError::from_anyhow(&error).to_json();
+    assert_eq!(error["code"], "indexing");
+    assert!(error["details"]["percent"].is_u64());
+    assert!(error["details"]["estimated_wait_secs"].as_u64().unwrap() >= 1);
+    assert!(error["details"]["waited_secs"].is_null());
+    assert!(backend.requests("textDocument/hover").is_empty());
+
+    // Tools that don't ask rust-analyzer anything still answer.
+    let workspace = call(
+        &mut server,
+        "rust_analyzer_get_workspace",
+        json!({ "wait_for_index": false }),
+    )
+    .await;
+    assert!(workspace.is_ok(), "{:?}", workspace);
+}
+
+#[tokio::test]
+async fn test_cargo_check_is_not_indexing() {
+    let hover = json!({ "contents": { "kind": "markdown", "value": "fn add" } });
+    let backend = MockBackend::new().respond("textDocument/hover", hover.clone());
+    let (dir, mut server) = mock_server(&backend, 30).await;
+    server.shared_progress().lock().await.begin(
+        "rust-analyzer/flycheck/0".to_string(),
+        "cargo check".to_string(),
+        None,
+        None,
+    );
+
+    let mut args = at(&dir, 0, 7);
+    args["wait_for_index"] = json!(false);
+    let result = call(&mut server, "rust_analyzer_hover", args.clone()).await;
+    assert_eq!(result.unwrap(), hover);
+    let progress = server.shared_progress();
+    let server = Mutex::new(server);
+    let _held = server.lock().await;
+    refuse_while_indexing(&progress, "rust_analyzer_hover", &args)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_refuse_while_indexing_without_the_server() {
+    let backend = MockBackend::new();
+    let (dir, server) = mock_server(&backend, 30).await;
+    let progress = server.shared_progress();
+    progress.lock().await.begin(
+        "rustAnalyzer/Indexing".to_string(),
+        "Indexing".to_string(),
+        None,
+        Some(40),
+    );
+    // Another call holding the server doesn't hold this one up.
+    let server = Mutex::new(server);
+    let _held = server.lock().await;
+
+    let mut args = at(&dir, 0, 7);
+    let waits = refuse_while_indexing(&progress, "rust_analyzer_hover", &args).await;
+    assert!(waits.is_ok());
+    args["wait_for_index"] = json!(false);
+    let error = refuse_while_indexing(&progress, "hover", &args)
+        .await
+        .unwrap_err();
+    assert_eq!(ToolError::from_anyhow(&error).code, ErrorCode::Indexing);
+    let workspace = refuse_while_indexing(&progress, "rust_analyzer_get_workspace", &args).await;
+    assert!(workspace.is_ok());
+
+    args["wait_for_index"] = json!("no");
+    let error = refuse_while_indexing(&progress, "hover", &args)
+        .await
+        .unwrap_err();
+    assert_eq!(error.to_string(), "wait_for_index must be true or false");
+    assert_eq!(
+        ToolError::from_anyhow(&error).code,
+        ErrorCode::InvalidParams
+    );
+}
+
+#[tokio::test]
+async fn test_no_retries_once_indexing_starts_without_wait_for_index() {
+    let backend = MockBackend::new()
+        .respond("textDocument/hover", json!(null))
+        .delay("textDocument/hover", Duration::from_millis(200));
+    let (dir, mut server) = mock_server(&backend, 30).await;
+    let progress = server.shared_progress();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        progress.lock().await.begin(
+            "rustAnalyzer/Indexing".to_string(),
+            "Indexing".to_string(),
+            None,
+            Some(10),
+        );
+    });
+
+    let mut args = at(&dir, 0, 7);
+    args["wait_for_index"] = json!(false);
+    let start = Instant::now();
+    let error = call(&mut server, "rust_analyzer_hover", args)
+        .await
+        .unwrap_err();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(ToolError::from_anyhow(&error).code, ErrorCode::Indexing);
+    assert_eq!(backend.requests("textDocument/hover").len(), 1);
+}
+
+#[tokio::test]
+async fn test_wait_for_index_must_be_a_bool() {
+    let backend = MockBackend::new();
+    let (dir, mut server) = mock_server(&backend, 0).await;
+
+    let mut args = at(&dir, 0, 7);
+    args["wait_for_index"] = json!("no");
+    let error = call(&mut server, "rust_analyzer_hover", args)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        ToolError::from_anyhow(&error).code,
+        ErrorCode::InvalidParams
+    );
+}
+
+#[tokio::test]
+async fn test_missing_argument_is_invalid_params() {
+    let backend = MockBackend::new();
+    let (dir, mut server) = mock_server(&backend, 0).await;
+
+    let args = json!({ "file_path": dir.path().join("lib.rs").to_string_lossy() });
+    let error = call(&mut server, "rust_analyzer_hover", args)
+        .await
+        .unwrap_err();
+    let error = ToolError::from_anyhow(&error);
+    assert_eq!(error.code, ErrorCode::InvalidParams);
+    assert!(!error.code.retryable());
+}
+
+#[tokio::test]
+async fn test_numeric_strings_are_taken_as_positions() {
+    let hover = json!({ "contents": { "kind": "markdown", "value": "fn add" } });
+    let backend = MockBackend::new()
+        .respond("textDocument/hover", hover)
+        .respond("textDocument/inlayHint", json!([]));
+    let (dir, mut server) = mock_server(&backend, 0).await;
+
+    let mut args = at(&dir, 0, 0);
+    args["line"] = json!(" 1 ");
+    args["character"] = json!(4.0);
+    call(&mut server, "rust_analyzer_hover", args)
+        .await
+        .unwrap();
+    assert_eq!(
+        backend.requests("textDocument/hover")[0]["position"],
+        json!({ "line": 1, "character": 4 })
+    );
+
+    let mut args = at(&dir, 0, 0);
+    args["end_line"] = json!("2");
+    args["end_character"] = json!("1.0");
+    call(&mut server, "rust_analyzer_inlay_hint", args)
+        .await
+        .unwrap();
+    assert_eq!(
+        backend.requests("textDocument/inlayHint")[0]["range"]["end"],
+        json!({ "line": 2, "character": 1 })
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_positions_say_what_was_sent() {
+    let backend = MockBackend::new();
+    let (dir, mut server) = mock_server(&backend, 0).await;
+
+    for (line, message) in [
+        (json!("abc"), "line must be a number, got string \"abc\""),
+        (json!(-3), "line must be a non-negative number, got -3"),
+        (
+            json!("-3"),
+            "line must be a non-negative number, got string \"-3\"",
+        ),
+        (json!(5.7), "line must be a whole number, got 5.7"),
+        (
+            json!("5.7"),
+            "line must be a whole number, got string \"5.7\"",
+        ),
+        (json!(true), "line must be a number, got boolean true"),
+        (
+            json!(1e10),
+            "line must be at most 4294967295, got 10000000000.0",
+        ),
+    ] {
+        let mut args = at(&dir, 0, 0);
+        args["line"] = line;
+        let error = call(&mut server, "rust_analyzer_hover", args)
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), message);
+        assert_eq!(
+            ToolError::from_anyhow(&error).code,
+            ErrorCode::InvalidParams
+        );
+    }
+
+    let mut args = at(&dir, 0, 0);
+    args["end_line"] = json!(1);
+    args["end_character"] = json!("x");
+    let error = call(&mut server, "rust_analyzer_code_actions", args)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "end_character must be a number, got string \"x\""
+    );
+    assert!(backend.requests("textDocument/hover").is_empty());
+}
+
+#[tokio::test]
+async fn test_missing_file_is_file_not_found() {
+    let backend = MockBackend::new();
+    let (dir, mut server) = mock_server(&backend, 0).await;
+
+    let args = json!({
+        "file_path": dir.path().join("missing.rs").to_string_lossy(),
+        "line": 0,
+        "character": 0
+    });
+    let error = call(&mut server, "rust_analyzer_hover", args)
+        .await
+        .unwrap_err();
+    assert_eq!(ToolError::from_anyhow(&error).code, ErrorCode::FileNotFound);
+}
+
+#[tokio::test]
+async fn test_file_uris_and_absolute_paths_are_accepted() {
+    let backend = MockBackend::new();
+    let (dir, mut server) = mock_server(&backend, 5).await;
+    let root = dir.path().canonicalize().unwrap();
+    std::fs::write(root.join("has space#1.rs"), "pub fn one() {}\n").unwrap();
+    let uri = format!("file://{}/has%20space%231.rs", root.display());
+    let _ = backend.clone().respond(
+        "textDocument/references",
+        json!([{
+            "uri": uri,
+            "range": { "start": { "line": 0, "character": 7 }, "end": { "line": 0, "character": 10 } }
+        }]),
+    );
+
+    // A URI from an earlier result, then the plain absolute path.
+    let mut args = at(&dir, 0, 7);
+    for file_path in [
+        uri.clone(),
+        root.join("has space#1.rs").display().to_string(),
+    ] {
+        args["file_path"] = json!(file_path);
+        let result = call(&mut server, "rust_analyzer_references", args.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            result,
+            json!([{ "location": format!("{}/has space#1.rs:0:7", root.display()) }])
+        );
+    }
+    let requests = backend.requests("textDocument/references");
+    assert_eq!(requests.len(), 2);
+    assert!(requests
+        .iter()
+        .all(|request| request["textDocument"]["uri"] == json!(uri)));
+
+    let outside = tempfile::tempdir().unwrap();
+    std::fs::write(outside.path().join("lib.rs"), "").unwrap();
+    args["file_path"] = json!(format!("file://{}/lib.rs", outside.path().display()));
+    let error = call(&mut server, "rust_analyzer_references", args)
+        .await
+        .unwrap_err();
+    assert!(
+        error.to_string().contains("is not inside workspace"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn test_manifests_are_opened_as_toml() {
+    let hover = json!({ "contents": { "kind": "markdown", "value": "serde 1.0.219" } });
+    let backend = MockBackend::new();
+    let (dir, mut server) = mock_server(&backend, 5).await;
+    let manifest = dir.path().canonicalize().unwrap().join("Cargo.toml");
+    // Shared state: the answer can name the manifest once it exists.
+    let uri = format!("file://{}", manifest.display());
+    let _ = backend
+        .clone()
+        .respond_at("textDocument/hover", &uri, 7, hover.clone());
+    std::fs::write(
+        &manifest,
+        "[package]\nname = \"demo\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\nserde = \"1.0\"\n",
+    )
+    .unwrap();
+
+    let args = json!({ "file_path": manifest.to_string_lossy(), "line": 7, "character": 2 });
+    let result = call(&mut server, "rust_analyzer_hover", args)
+        .await
+        .unwrap();
+    assert_eq!(result, hover);
+    let opened = backend.notifications("textDocument/didOpen");
+    assert_eq!(opened[0]["textDocument"]["languageId"], "toml");
+    // No definition lookup for docs links.
+    assert!(backend.requests("textDocument/definition").is_empty());
+
+    // Nothing to say about a line isn't taken for indexing.
+    let start = Instant::now();
+    let args = json!({ "file_path": manifest.to_string_lossy(), "line": 0, "character": 0 });
+    let result = call(&mut server, "rust_analyzer_hover", args)
+        .await
+        .unwrap();
+    assert_eq!(result, Value::Null);
+    assert!(start.elapsed() < Duration::from_secs(2));
+
+    let args = json!({ "file_path": manifest.to_string_lossy(), "line": 7, "character": 2 });
+    let error = call(&mut server, "rust_analyzer_definition", args)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "rust_analyzer_definition doesn't take Cargo.toml; hover, completion and diagnostics do"
+    );
+
+    let notes = dir.path().join("notes.txt");
+    std::fs::write(&notes, "").unwrap();
+    let args = json!({ "file_path": notes.to_string_lossy(), "line": 0, "character": 0 });
+    let error = call(&mut server, "rust_analyzer_hover", args)
+        .await
+        .unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .ends_with("notes.txt is neither a Rust source file nor a Cargo.toml manifest"),
+        "{}",
+        error
+    );
+    assert_eq!(
+        ToolError::from_anyhow(&error).code,
+        ErrorCode::InvalidParams
+    );
+    assert_eq!(backend.notifications("textDocument/didOpen").len(), 1);
+}
+
+#[tokio::test]
+async fn test_request_timeout_is_not_retried() {
+    let backend = MockBackend::new().delay("textDocument/references", Duration::from_secs(60));
+    let (dir, mut server) = mock_server(&backend, 30).await;
+
+    let start = Instant::now();
+    let error = call(&mut server, "rust_analyzer_references", at(&dir, 0, 7))
+        .await
+        .unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("textDocument/references timed out"),
+        "{}",
+        error
+    );
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(backend.requests("textDocument/references").len(), 1);
+}
+
+#[tokio::test]
+async fn test_relative_paths_strip_workspace_root() {
+    let backend = MockBackend::new();
+    let (dir, mut server) = mock_server(&backend, 5).await;
+    let root = dir.path().canonicalize().unwrap();
+    // Shared state: the answer can name the workspace once it exists.
+    let _ = backend.clone().respond(
+        "textDocument/references",
+        json!([
+            {
+                "uri": format!("file://{}/lib.rs", root.display()),
+                "range": { "start": { "line": 0, "character": 7 }, "end": { "line": 0, "character": 10 } }
+            },
+            {
+                "uri": "file:///elsewhere/src/lib.rs",
+                "range": { "start": { "line": 2, "character": 4 }, "end": { "line": 2, "character": 7 } }
+            }
+        ]),
+    );
+
+    let mut args = at(&dir, 0, 7);
+    args["relative_paths"] = json!(true);
+    let result = call(&mut server, "rust_analyzer_references", args.clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        result,
+        json!([
+            { "location": "lib.rs:0:7" },
+            { "location": "/elsewhere/src/lib.rs:2:4" }
+        ])
+    );
+
+    // Inputs may be relative too.
+    args["file_path"] = json!("lib.rs");
+    call(&mut server, "rust_analyzer_references", args.clone())
+        .await
+        .unwrap();
+
+    args["relative_paths"] = json!("yes");
+    let error = call(&mut server, "rust_analyzer_references", args)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        ToolError::from_anyhow(&error).code,
+        ErrorCode::InvalidParams
+    );
+}
+
+#[tokio::test]
+async fn test_definition_is_simplified_to_locations() {
+    let backend = MockBackend::new().respond(
+        "textDocument/definition",
+        json!([{
+            "targetUri": "file:///ws/src/math.rs",
+            "targetRange": { "start": { "line": 3, "character": 0 }, "end": { "line": 5, "character": 1 } },
+            "targetSelectionRange": { "start": { "line": 3, "character": 7 }, "end": { "line": 3, "character": 10 } }
+        }]),
+    );
+    let (dir, mut server) = mock_server(&backend, 5).await;
+
+    let result = call(&mut server, "rust_analyzer_definition", at(&dir, 1, 4))
+        .await
+        .unwrap();
+    assert_eq!(result, json!([{ "location": "/ws/src/math.rs:3:7" }]));
+}
+
+#[tokio::test]
+async fn test_definition_in_a_dependency_names_its_crate() {
+    let registry =
+        "file:///home/me/.cargo/registry/src/index.crates.io-6f17d22bba15001f/tokio-1.38.0";
+    let backend = MockBackend::new()
+        .respond(
+            "textDocument/definition",
+            json!([{
+                "targetUri": format!("{}/src/sync/mutex.rs", registry),
+                "targetRange": { "start": { "line": 120, "character": 0 }, "end": { "line": 140, "character": 1 } },
+                "targetSelectionRange": { "start": { "line": 126, "character": 11 }, "end": { "line": 126, "character": 16 } }
+            }]),
+        )
+        .respond(
+            "textDocument/implementation",
+            json!([
+                {
+                    "targetUri": format!("{}/src/sync/mutex.rs", registry),
+                    "targetRange": { "start": { "line": 300, "character": 0 }, "end": { "line": 310, "character": 1 } }
+                },
+                {
+                    "targetUri": "file:///ws/src/math.rs",
+                    "targetRange": { "start": { "line": 3, "character": 0 }, "end": { "line": 5, "character": 1 } }
+                }
+            ]),
+        );
+    let (dir, mut server) = mock_server(&backend, 5).await;
+    let abs_path = "/home/me/.cargo/registry/src/index.crates.io-6f17d22bba15001f/tokio-1.38.0/src/sync/mutex.rs";
+
+    let result = call(&mut server, "rust_analyzer_definition", at(&dir, 1, 4))
+        .await
+        .unwrap();
+    assert_eq!(
+        result,
+        json!([{
+            "location": {
+                "crate": "tokio",
+                "version": "1.38.0",
+                "path": "src/sync/mutex.rs",
+                "line": 126,
+                "col": 11,
+                "readable": false,
+                "abs_path": abs_path
+            }
+        }])
+    );
+
+    let result = call(&mut server, "rust_analyzer_implementation", at(&dir, 1, 4))
+        .await
+        .unwrap();
+    assert_eq!(result[0]["location"]["line"], 300);
+    assert_eq!(result[0]["location"]["crate"], "tokio");
+    assert_eq!(result[1], json!({ "location": "/ws/src/math.rs:3:0" }));
+}
+
+#[tokio::test]
+async fn test_incoming_calls_details_and_pages() {
+    let backend = MockBackend::new().respond(
+        "textDocument/prepareCallHierarchy",
+        json!([{ "name": "add", "kind": 12 }]),
+    );
+    let (dir, mut server) = mock_server(&backend, 5).await;
+    let root = dir.path().canonicalize().unwrap();
+    std::fs::write(
+        root.join("main.rs"),
+        "fn main() {\n    run();\n}\n\nfn run() {\n    add(1, add(2, 3));\n}\n\nfn twice() {\n    add(4, 4);\n}\n",
+    )
+    .unwrap();
+    let uri = format!("file://{}", root.join("main.rs").display());
+    let position = |line: u32, character: u32| json!({ "line": line, "character": character });
+    let range = |line: u32, start: u32, end: u32| json!({ "start": position(line, start), "end": position(line, end) });
+    let incoming = |name: &str, line: u32, calls: Value| {
+        json!({
+            "from": {
+                "name": name,
+                "kind": 12,
+                "uri": uri,
+                "range": range(line, 0, 1),
+                "selectionRange": range(line, 3, 3 + name.len() as u32)
+            },
+            "fromRanges": calls
+        })
+    };
+    let mut main = incoming("main", 0, json!([range(1, 4, 7)]));
+    main["from"]["detail"] = json!("fn main()");
+    let _ = backend
+        .clone()
+        .respond(
+            "callHierarchy/incomingCalls",
+            json!([
+                main,
+                incoming("run", 4, json!([range(5, 4, 7), range(5, 11, 14)])),
+                incoming("twice", 8, json!([range(9, 4, 7)]))
+            ]),
+        )
+        .respond(
+            "textDocument/documentSymbol",
+            json!([{
+                "name": "run",
+                "kind": 12,
+                "detail": "fn run()",
+                "range": range(4, 0, 1),
+                "selectionRange": range(4, 3, 6),
+                "children": []
+            }]),
+        )
+        .respond(
+            "textDocument/hover",
+            json!({ "contents": { "kind": "markdown", "value": "```rust\nfn twice()\n```" } }),
+        );
+
+    // Without options, the compact list of callers.
+    let result = call(&mut server, "rust_analyzer_incoming_calls", at(&dir, 0, 7))
+        .await
+        .unwrap();
+    let main_rs = root.join("main.rs").display().to_string();
+    assert_eq!(
+        result[1],
+        json!({ "caller": "run", "location": format!("{}:4:0", main_rs) })
+    );
+    assert_eq!(result.as_array().unwrap().len(), 3);
+
+    let mut args = at(&dir, 0, 7);
+    args["with_signature"] = json!(true);
+    args["with_snippet"] = json!(true);
+    args["limit"] = json!(2);
+    let result = call(&mut server, "rust_analyzer_incoming_calls", args.clone())
+        .await
+        .unwrap();
+    assert_eq!(result["total"], 3);
+    assert_eq!(result["next_offset"], 2);
+    let callers = result["callers"].as_array().unwrap();
+    assert_eq!(callers.len(), 2);
+    assert_eq!(callers[0]["signature"], "fn main()");
+    assert_eq!(callers[0]["call_sites"], json!(["1 >     run();"]));
+    assert_eq!(callers[1]["signature"], "fn run()");
+    assert_eq!(
+        callers[1]["call_sites"],
+        json!(["5 >     add(1, add(2, 3));"])
+    );
+
+    args["offset"] = json!(2);
+    let result = call(&mut server, "rust_analyzer_incoming_calls", args)
+        .await
+        .unwrap();
+    assert_eq!(result["callers"][0]["signature"], "fn twice()");
+    assert!(result.get("next_offset").is_none());
+    // The file's symbols were asked for once per call, and only for
+    // callers without a detail.
+    assert_eq!(backend.requests("textDocument/documentSymbol").len(), 2);
+}
+
+#[tokio::test]
+async fn test_completion_snippets_are_simplified() {
+    let backend = MockBackend::new().respond(
+        "textDocument/completion",
+        json!({
+            "isIncomplete": false,
+            "items": [{
+                "label": "add(…)",
+                "insertText": "add(${1:a}, ${2:b})$0",
+                "insertTextFormat": 2,
+                "documentation": { "kind": "markdown", "value": "Adds two numbers." }
+            }]
+        }),
+    );
+    let (dir, mut server) = mock_server(&backend, 5).await;
+
+    let result = call(&mut server, "rust_analyzer_completion", at(&dir, 1, 4))
+        .await
+        .unwrap();
+    let item = &result["items"][0];
+    assert_eq!(item["insertText"], "add(a, b)");
+    assert_eq!(item["insertTextFormat"], 1);
+    assert_eq!(item["documentation"], "Adds two numbers.");
+}
+
+#[tokio::test]
+async fn test_completion_resolves_auto_imports() {
+    let edit = json!([{
+        "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 0 } },
+        "newText": "use std::collections::HashMap;\n\n"
+    }]);
+    let backend = MockBackend::new()
+        .respond(
+            "textDocument/completion",
+            json!({
+                "isIncomplete": true,
+                "items": [
+                    { "label": "a", "insertText": "a" },
+                    {
+                        "label": "HashMap (use std::collections::HashMap)",
+                        "insertText": "HashMap",
+                        "data": {
+                            "imports": [{
+                                "full_import_path": "std::collections::HashMap",
+                                "imported_name": "HashMap"
+                            }]
+                        }
+                    }
+                ]
+            }),
+        )
+        .respond(
+            "completionItem/resolve",
+            json!({ "label": "HashMap", "additionalTextEdits": edit }),
+        );
+    let (dir, mut server) = mock_server(&backend, 5).await;
+
+    let result = call(&mut server, "rust_analyzer_completion", at(&dir, 1, 4))
+        .await
+        .unwrap();
+    assert!(result["items"][0].get("auto_import").is_none());
+    let item = &result["items"][1];
+    assert_eq!(item["auto_import"], "use std::collections::HashMap;");
+    assert_eq!(item["additionalTextEdits"], edit);
+    // Only the item needing an import was resolved.
+    assert_eq!(backend.requests("completionItem/resolve").len(), 1);
+}
+
+#[tokio::test]
+async fn test_lsp_errors_and_missing_arguments() {
+    let backend = MockBackend::new().fail("textDocument/documentSymbol", "content modified");
+    let (dir, mut server) = mock_server(&backend, 5).await;
+
+    let error = call(&mut server, "rust_analyzer_symbols", at(&dir, 0, 0))
+        .await
+        .unwrap_err();
+    assert_eq!(error.to_string(), "content modified");
+
+    let error = call(
+        &mut server,
+        "rust_analyzer_hover",
+        json!({ "line": 0, "character": 0 }),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(error.to_string(), "Missing file_path");
+    assert!(backend.requests("textDocument/hover").is_empty());
+}
+
+#[tokio::test]
+async fn test_large_results_resume_from_cursor() {
+    let items: Vec<Value> = (0..200)
+        .map(|i| json!({ "label": format!("field_{}", i), "kind": 5 }))
+        .collect();
+    let backend = MockBackend::new().respond(
+        "textDocument/completion",
+        json!({ "isIncomplete": false, "items": items }),
+    );
+    let (dir, mut server) = mock_server(&backend, 5).await;
+
+    let mut args = at(&dir, 1, 4);
+    args["max_output_bytes"] = json!(2000);
+    let first = call(&mut server, "rust_analyzer_completion", args.clone())
+        .await
+        .unwrap();
+    assert_eq!(first["truncated"], true);
+    let kept = first["items"].as_array().unwrap().len();
+    assert_eq!(first["omitted_items"], json!(200 - kept));
+
+    args["cursor"] = first["cursor"].clone();
+    let second = call(&mut server, "rust_analyzer_completion", args)
+        .await
+        .unwrap();
+    assert_eq!(second["items"][0]["label"], format!("field_{}", kept));
+
+    let error = call(
+        &mut server,
+        "rust_analyzer_completion",
+        json!({ "max_output_bytes": 0 }),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "max_output_bytes must be a positive whole number"
+    );
+}
+
+#[tokio::test]
+async fn test_workspace_symbol_cache_until_files_change() {
+    let backend = MockBackend::new()
+        .respond(
+            "workspace/symbol",
+            json!([{
+                "name": "add",
+                "kind": 12,
+                "location": {
+                    "uri": "file:///ws/lib.rs",
+                    "range": { "start": { "line": 0, "character": 7 }, "end": { "line": 0, "character": 10 } }
+                }
+            }]),
+        )
+        .respond("textDocument/hover", json!({ "contents": "fn add" }));
+    let (dir, mut server) = mock_server(&backend, 5).await;
+    let query = json!({ "query": "add" });
+
+    let first = call(&mut server, "rust_analyzer_workspace_symbol", query.clone())
+        .await
+        .unwrap();
+    let second = call(&mut server, "rust_analyzer_workspace_symbol", query.clone())
+        .await
+        .unwrap();
+    assert_eq!(first, second);
+    assert_eq!(backend.requests("workspace/symbol").len(), 1);
+
+    // Opening a file unchanged on disk keeps the cache.
+    call(&mut server, "rust_analyzer_hover", at(&dir, 0, 7))
+        .await
+        .unwrap();
+    call(&mut server, "rust_analyzer_workspace_symbol", query.clone())
+        .await
+        .unwrap();
+    assert_eq!(backend.requests("workspace/symbol").len(), 1);
+
+    // Editing it doesn't.
+    let mut args = at(&dir, 0, 7);
+    args["content"] = json!("pub fn add(a: i64, b: i64) -> i64 {\n    a + b\n}\n");
+    call(&mut server, "rust_analyzer_hover", args)
+        .await
+        .unwrap();
+    assert_eq!(backend.notifications("textDocument/didChange").len(), 1);
+    call(&mut server, "rust_analyzer_workspace_symbol", query.clone())
+        .await
+        .unwrap();
+    assert_eq!(backend.requests("workspace/symbol").len(), 2);
+
+    call(
+        &mut server,
+        "rust_analyzer_workspace_symbol",
+        json!({ "query": "add", "no_cache": true }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(backend.requests("workspace/symbol").len(), 3);
+    assert_eq!(server.symbol_cache_stats(), (1, 2, 2));
+}
+
+#[tokio::test]
+async fn test_document_symbols_cached_until_file_changes() {
+    let backend = MockBackend::new().respond(
+        "textDocument/documentSymbol",
+        json!([{
+            "name": "add",
+            "kind": 12,
+            "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 2, "character": 1 } },
+            "selectionRange": { "start": { "line": 0, "character": 7 }, "end": { "line": 0, "character": 10 } }
+        }]),
+    );
+    let (dir, mut server) = mock_server(&backend, 5).await;
+    let args = json!({ "file_path": dir.path().join("lib.rs").to_str().unwrap() });
+
+    let first = call(&mut server, "rust_analyzer_symbols", args.clone())
+        .await
+        .unwrap();
+    let second = call(&mut server, "rust_analyzer_symbols", args.clone())
+        .await
+        .unwrap();
+    assert_eq!(first, second);
+    assert_eq!(backend.requests("textDocument/documentSymbol").len(), 1);
+
+    // New text for the file is a new result.
+    let mut edited = args.clone();
+    edited["content"] = json!("pub fn add(a: i64, b: i64) -> i64 {\n    a + b\n}\n");
+    call(&mut server, "rust_analyzer_symbols", edited.clone())
+        .await
+        .unwrap();
+    assert_eq!(backend.requests("textDocument/documentSymbol").len(), 2);
+    call(&mut server, "rust_analyzer_symbols", edited)
+        .await
+        .unwrap();
+    assert_eq!(backend.requests("textDocument/documentSymbol").len(), 2);
+
+    let mut uncached = args;
+    uncached["no_cache"] = json!(true);
+    call(&mut server, "rust_analyzer_symbols", uncached)
+        .await
+        .unwrap();
+    assert_eq!(backend.requests("textDocument/documentSymbol").len(), 3);
+    assert_eq!(server.result_cache_stats().1, 2);
+}
+
+#[tokio::test]
+async fn test_resync_from_a_changed_file_starts_cargo_check() {
+    let backend = MockBackend::new().respond("textDocument/hover", json!({ "contents": "fn add" }));
+    let (dir, mut server) = mock_server(&backend, 5).await;
+
+    call(&mut server, "rust_analyzer_hover", at(&dir, 0, 7))
+        .await
+        .unwrap();
+
+    // Unsaved content is not checked.
+    let mut args = at(&dir, 0, 7);
+    args["content"] = json!("pub fn add(a: i64, b: i64) -> i64 {\n    a + b\n}\n");
+    call(&mut server, "rust_analyzer_hover", args)
+        .await
+        .unwrap();
+    assert!(backend
+        .notifications("rust-analyzer/runFlycheck")
+        .is_empty());
+
+    std::fs::write(
+        dir.path().join("lib.rs"),
+        "pub fn add(a: u8, b: u8) -> u8 {\n    a + b\n}\n",
+    )
+    .unwrap();
+    call(&mut server, "rust_analyzer_hover", at(&dir, 0, 7))
+        .await
+        .unwrap();
+    assert_eq!(backend.notifications("textDocument/didChange").len(), 2);
+    assert_eq!(backend.notifications("rust-analyzer/runFlycheck").len(), 1);
+}
+
+#[tokio::test]
+async fn test_workspace_symbol_scope_hides_external_matches() {
+    let backend = MockBackend::new().respond(
+        "initialize",
+        json!({ "capabilities": { "experimental": { "workspaceSymbolScopeKindFiltering": true } } }),
+    );
+    let (dir, mut server) = mock_server(&backend, 5).await;
+    let root = dir.path().canonicalize().unwrap();
+    let symbol = |uri: String| {
+        json!({
+            "name": "Client",
+            "kind": 23,
+            "location": {
+                "uri": uri,
+                "range": { "start": { "line": 0, "character": 11 }, "end": { "line": 0, "character": 17 } }
+            }
+        })
+    };
+    let _ = backend.clone().respond(
+        "workspace/symbol",
+        json!([
+            symbol(
+                "file:///home/me/.cargo/registry/src/index/hyper-1.0.0/src/client.rs".to_string()
+            ),
+            symbol(format!("file://{}/lib.rs", root.display())),
+            symbol(
+                "file:///home/me/.cargo/registry/src/index/reqwest-0.12.0/src/lib.rs".to_string()
+            )
+        ]),
+    );
+
+    let result = call(
+        &mut server,
+        "rust_analyzer_workspace_symbol",
+        json!({ "query": "Client" }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(result["hidden_external"], 2);
+    assert_eq!(result["symbols"].as_array().unwrap().len(), 1);
+    assert!(result["note"]
+        .as_str()
+        .unwrap()
+        .contains("\"scope\": \"all\""));
+    assert_eq!(
+        backend.requests("workspace/symbol")[0]["searchScope"],
+        "workspace"
+    );
+
+    let all = call(
+        &mut server,
+        "rust_analyzer_workspace_symbol",
+        json!({ "query": "Client", "scope": "all" }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(all.as_array().unwrap().len(), 3);
+    assert_eq!(
+        backend.requests("workspace/symbol")[1]["searchScope"],
+        "workspaceAndDependencies"
+    );
+
+    let error = call(
+        &mut server,
+        "rust_analyzer_workspace_symbol",
+        json!({ "query": "Client", "scope": "deps" }),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(
+        ToolError::from_anyhow(&error).code,
+        ErrorCode::InvalidParams
+    );
+}
+
+#[tokio::test]
+async fn test_workspace_symbol_containers_and_ranking() {
+    let backend = MockBackend::new();
+    let (dir, mut server) = mock_server(&backend, 5).await;
+    std::fs::create_dir_all(dir.path().join("src/net")).unwrap();
+    std::fs::write(
+        dir.path().join("Cargo.toml"),
+        "[package]\nname = \"app\"\nversion = \"0.1.0\"\n",
+    )
+    .unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let symbol = |name: &str, file: &str, container: Option<&str>| {
+        let mut symbol = json!({
+            "name": name,
+            "kind": 12,
+            "location": {
+                "uri": format!("file://{}/{}", root.display(), file),
+                "range": { "start": { "line": 1, "character": 4 }, "end": { "line": 1, "character": 7 } }
+            }
+        });
+        if let Some(container) = container {
+            symbol["containerName"] = json!(container);
+        }
+        symbol
+    };
+    let _ = backend.clone().respond(
+        "workspace/symbol",
+        json!([
+            symbol("new_client", "src/net/client.rs", None),
+            symbol("NEW", "src/lib.rs", None),
+            symbol("new", "src/net/client.rs", Some("Client")),
+            symbol("new", "src/lib.rs", None)
+        ]),
+    );
+
+    let result = call(
+        &mut server,
+        "rust_analyzer_workspace_symbol",
+        json!({ "query": "new" }),
+    )
+    .await
+    .unwrap();
+    let found: Vec<(&str, &str)> = result
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| {
+            (
+                s["name"].as_str().unwrap(),
+                s["container"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("new", "Client"),
+            ("new", "app"),
+            ("NEW", "app"),
+            ("new_client", "app::net::client")
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_definition_falls_back_once_index_is_idle() {
+    let backend = MockBackend::new().respond(
+        "textDocument/typeDefinition",
+        json!([{
+            "targetUri": "file:///ws/src/math.rs",
+            "targetRange": { "start": { "line": 8, "character": 0 }, "end": { "line": 10, "character": 1 } },
+            "targetSelectionRange": { "start": { "line": 8, "character": 11 }, "end": { "line": 8, "character": 17 } }
+        }]),
+    );
+    let (dir, mut server) = mock_server(&backend, 30).await;
+    indexed(&server).await;
+
+    let start = Instant::now();
+    let result = call(&mut server, "rust_analyzer_definition", at(&dir, 1, 4))
+        .await
+        .unwrap();
+    assert_eq!(
+        result,
+        json!([{ "location": "/ws/src/math.rs:8:11", "via": "type_definition" }])
+    );
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(backend.requests("textDocument/definition").len(), 1);
+    assert_eq!(backend.requests("textDocument/declaration").len(), 1);
+
+    let backend = MockBackend::new();
+    let (dir, mut server) = mock_server(&backend, 1).await;
+    // Before rust-analyzer has indexed, nothing yet isn't an answer.
+    let error = call(&mut server, "rust_analyzer_definition", at(&dir, 0, 0))
+        .await
+        .unwrap_err();
+    assert_eq!(ToolError::from_anyhow(&error).code, ErrorCode::Indexing);
+    assert!(backend.requests("textDocument/declaration").is_empty());
+
+    indexed(&server).await;
+    let result = call(&mut server, "rust_analyzer_definition", at(&dir, 0, 0))
+        .await
+        .unwrap();
+    assert_eq!(
+        result,
+        json!({
+            "message": "no definition found",
+            "tried": ["definition", "declaration", "type_definition"]
+        })
+    );
+}
+
+/// Have rust-analyzer's first indexing run begin and end.
+async fn indexed(server: &RustAnalyzerMCPServer) {
+    let progress = server.shared_progress();
+    let mut progress = progress.lock().await;
+    let token = "rustAnalyzer/Indexing".to_string();
+    progress.begin(token.clone(), "Indexing".to_string(), None, None);
+    progress.end(&token);
+}
+
+#[tokio::test]
+async fn test_list_tests_from_runnables() {
+    let backend = MockBackend::new().respond(
+        "experimental/runnables",
+        json!([
+            {
+                "label": "cargo check -p calc",
+                "kind": "cargo",
+                "args": { "workspaceRoot": "/ws", "cargoArgs": ["check", "--package", "calc"], "executableArgs": [] }
+            },
+            {
+                "label": "test tests::adds",
+                "kind": "cargo",
+                "location": {
+                    "targetUri": "file:///ws/lib.rs",
+                    "targetRange": { "start": { "line": 6, "character": 4 }, "end": { "line": 9, "character": 5 } },
+                    "targetSelectionRange": { "start": { "line": 7, "character": 7 }, "end": { "line": 7, "character": 11 } }
+                },
+                "args": {
+                    "workspaceRoot": "/ws",
+                    "cwd": "/ws",
+                    "cargoArgs": ["test", "--package", "calc", "--lib"],
+                    "executableArgs": ["tests::adds", "--exact", "--show-output"]
+                }
+            }
+        ]),
+    );
+    let (dir, mut server) = mock_server(&backend, 5).await;
+
+    let result = call(
+        &mut server,
+        "rust_analyzer_list_tests",
+        json!({ "file_path": dir.path().join("lib.rs").to_string_lossy() }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(result.as_array().unwrap().len(), 1);
+    assert_eq!(result[0]["name"], "tests::adds");
+    assert_eq!(result[0]["location"], "/ws/lib.rs:7:7");
+    assert_eq!(
+        result[0]["command"],
+        "cargo test --package calc --lib -- tests::adds --exact --show-output"
+    );
+    assert_eq!(
+        backend.requests("experimental/runnables")[0]["position"],
+        Value::Null
+    );
+}
+
+#[tokio::test]
+async fn test_cancelled_call_leaves_no_timeout_override() {
+    let backend = MockBackend::new()
+        .delay("textDocument/hover", Duration::from_secs(60))
+        .delay("workspace/symbol", Duration::from_secs(60));
+    let (dir, server) = mock_server(&backend, 30).await;
+    let server = Mutex::new(server);
+
+    let mut args = at(&dir, 0, 7);
+    args["timeout_secs"] = json!(30);
+    let cancelled = tokio::time::timeout(
+        Duration::from_millis(200),
+        handle_shared_tool_call(&server, "rust_analyzer_hover", args),
+    )
+    .await;
+    assert!(cancelled.is_err(), "hover should still be waiting");
+
+    // Prewarm's requests get the configured one-second timeout, not the
+    // cancelled call's.
+    let start = Instant::now();
+    let status = SharedPrewarmStatus::default();
+    prewarm(&server, &status).await;
+    let error = status.lock().unwrap().last_error.clone().unwrap();
+    assert!(error.contains("timed out"), "{}", error);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
@@ -0,0 +1,139 @@
+use serde_json::{json, Value};
+use std::path::Path;
+
+use rust_analyzer_server::mcp::{handle_tool_call, RustAnalyzerMCPServer};
+use test_support::{server_with_backend, write_file, MockBackend};
+
+fn location(uri: &str, line: u32, character: u32) -> Value {
+    let position = json!({ "line": line, "character": character });
+    json!({ "uri": uri, "range": { "start": position, "end": position } })
+}
+
+/// A workspace of two members, `geometry` and `app`, where `app` calls
+/// `geometry::area`. Returns the root and the URIs of their sources.
+fn workspace(root: &Path) -> (String, String) {
+    write_file(
+        root,
+        "Cargo.toml",
+        "[workspace]\nmembers = [\"geometry\", \"app\"]\nresolver = \"2\"\n",
+    );
+    write_file(
+        root,
+        "geometry/Cargo.toml",
+        "[package]\nname = \"geometry\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+    );
+    let lib = write_file(
+        root,
+        "geometry/src/lib.rs",
+        "pub fn area(w: u32, h: u32) -> u32 {\n    w * h\n}\npub fn square(s: u32) -> u32 {\n    area(s, s)\n}\n",
+    );
+    write_file(
+        root,
+        "app/Cargo.toml",
+        "[package]\nname = \"app\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\ngeometry = { path = \"../geometry\" }\n",
+    );
+    let main = write_file(
+        root,
+        "app/src/main.rs",
+        "fn main() {\n    println!(\"{}\", geometry::area(2, 3));\n}\n",
+    );
+    (lib, main)
+}
+
+async fn impact(server: &mut RustAnalyzerMCPServer, root: &Path) -> Value {
+    let args = json!({
+        "file_path": root.join("geometry/src/lib.rs").to_string_lossy(),
+        "line": 0,
+        "character": 7
+    });
+    let result = handle_tool_call(server, "rust_analyzer_impact", args)
+        .await
+        .unwrap();
+    serde_json::from_str(&result.content[0].text).unwrap()
+}
+
+#[tokio::test]
+async fn test_impact_merges_references_callers_and_crates() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let (lib, main) = workspace(&root);
+
+    let item = json!({
+        "name": "area",
+        "kind": 12,
+        "uri": lib,
+        "range": location(&lib, 0, 0)["range"],
+        "selectionRange": location(&lib, 0, 7)["range"]
+    });
+    let backend = MockBackend::new()
+        .respond(
+            "textDocument/references",
+            json!([location(&lib, 0, 7), location(&lib, 4, 4), location(&main, 1, 28)]),
+        )
+        .respond("textDocument/definition", json!([location(&lib, 0, 7)]))
+        .respond("textDocument/prepareCallHierarchy", json!([item]))
+        .respond(
+            "callHierarchy/incomingCalls",
+            json!([
+                { "from": { "name": "square", "uri": lib, "range": location(&lib, 3, 0)["range"] } },
+                { "from": { "name": "main", "uri": main, "range": location(&main, 0, 0)["range"] } }
+            ]),
+        )
+        .respond("textDocument/implementation", json!([]));
+    let mut server = server_with_backend(&root, &backend).await;
+
+    let result = impact(&mut server, &root).await;
+    let lib_path = root.join("geometry/src/lib.rs").display().to_string();
+    let main_path = root.join("app/src/main.rs").display().to_string();
+    assert_eq!(
+        result,
+        json!({
+            "crate": "geometry",
+            "definition": format!("{}:0:7", lib_path),
+            "references": {
+                "total": 3,
+                "by_file": [
+                    { "file": main_path, "crate": "app", "count": 1 },
+                    { "file": lib_path, "crate": "geometry", "count": 2 }
+                ]
+            },
+            "used_outside_crate": true,
+            "outside_crates": ["app"],
+            "callers": [
+                { "caller": "square", "location": format!("{}:3:0", lib_path) },
+                { "caller": "main", "location": format!("{}:0:0", main_path) }
+            ],
+            "implementations": []
+        })
+    );
+    let calls = backend.requests("callHierarchy/incomingCalls");
+    assert_eq!(calls, [json!({ "item": item })]);
+}
+
+#[tokio::test]
+async fn test_impact_omits_sections_that_fail() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let (lib, _) = workspace(&root);
+
+    // A struct: no call hierarchy, and implementations fail.
+    let backend = MockBackend::new()
+        .respond("textDocument/references", json!([location(&lib, 0, 7)]))
+        .fail("textDocument/implementation", "not applicable");
+    let mut server = server_with_backend(&root, &backend).await;
+
+    let result = impact(&mut server, &root).await;
+    assert_eq!(result["references"]["total"], 1);
+    assert_eq!(result["used_outside_crate"], false);
+    assert!(result.get("callers").is_none());
+    assert!(result.get("implementations").is_none());
+    assert_eq!(
+        result["unavailable"]["callers"],
+        "No call hierarchy at this position"
+    );
+    assert!(result["unavailable"]["implementations"]
+        .as_str()
+        .unwrap()
+        .contains("not applicable"));
+    assert!(backend.requests("callHierarchy/incomingCalls").is_empty());
+}
@@ -0,0 +1,121 @@
+use serde_json::{json, Value};
+
+use rust_analyzer_server::mcp::{
+    handle_tool_call, markdown::symbol_outline, RustAnalyzerMCPServer,
+};
+use test_support::MockBackend;
+
+fn range(line: u32, start: u32, end: u32) -> Value {
+    json!({
+        "start": { "line": line, "character": start },
+        "end": { "line": line, "character": end }
+    })
+}
+
+fn symbol(name: &str, detail: Option<&str>, kind: u32, line: u32, children: Value) -> Value {
+    let mut symbol = json!({
+        "name": name,
+        "kind": kind,
+        "range": range(line.saturating_sub(1), 0, 1),
+        "selectionRange": range(line, 4, 8),
+        "children": children
+    });
+    if let Some(detail) = detail {
+        symbol["detail"] = json!(detail);
+    }
+    symbol
+}
+
+#[test]
+fn test_outline_nests_children_with_signatures() {
+    let symbols = json!([
+        symbol(
+            "Foo",
+            None,
+            23,
+            1,
+            json!([symbol("value", Some("i32"), 8, 2, json!([]))])
+        ),
+        symbol(
+            "impl Foo",
+            None,
+            19,
+            5,
+            json!([
+                symbol("new", Some("fn() -> Foo"), 6, 6, json!([])),
+                symbol(
+                    "get",
+                    Some("const fn<T>(&self, key: T) -> i32"),
+                    6,
+                    9,
+                    json!([])
+                )
+            ])
+        ),
+        symbol("MAX", Some("usize"), 14, 14, json!([])),
+        symbol("Alias", Some("Vec<Foo>"), 26, 15, json!([])),
+        symbol("tests", None, 2, 17, json!([]))
+    ]);
+    assert_eq!(
+        symbol_outline(&symbols),
+        "- struct Foo  (line 1)\n\
+         \x20 - value: i32  (line 2)\n\
+         - impl Foo  (line 5)\n\
+         \x20 - fn new() -> Foo  (line 6)\n\
+         \x20 - const fn get<T>(&self, key: T) -> i32  (line 9)\n\
+         - const MAX: usize  (line 14)\n\
+         - type Alias = Vec<Foo>  (line 15)\n\
+         - mod tests  (line 17)\n"
+    );
+}
+
+#[test]
+fn test_outline_of_flat_symbols_uses_location() {
+    let symbols = json!([
+        { "name": "main", "kind": 12, "location": { "uri": "file:///ws/src/main.rs", "range": range(3, 3, 7) } },
+        { "name": "Mode", "kind": 10, "location": { "uri": "file:///ws/src/main.rs", "range": range(8, 5, 9) } }
+    ]);
+    assert_eq!(
+        symbol_outline(&symbols),
+        "- fn main  (line 3)\n- enum Mode  (line 8)\n"
+    );
+    assert_eq!(symbol_outline(&json!(null)), "");
+}
+
+#[tokio::test]
+async fn test_symbols_markdown_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().canonicalize().unwrap().join("lib.rs");
+    std::fs::write(&file, "pub fn greet(name: &str) -> String {}\n").unwrap();
+    let backend = MockBackend::new().respond(
+        "textDocument/documentSymbol",
+        json!([symbol(
+            "greet",
+            Some("fn(&str) -> String"),
+            12,
+            0,
+            json!([])
+        )]),
+    );
+    let mut server = RustAnalyzerMCPServer::with_workspace(dir.path().canonicalize().unwrap());
+    server.start_with_backend(Box::new(backend)).await.unwrap();
+
+    let args = json!({ "file_path": file.to_string_lossy(), "output": "markdown" });
+    let result = handle_tool_call(&mut server, "rust_analyzer_symbols", args)
+        .await
+        .unwrap();
+    assert_eq!(
+        result.content[0].text,
+        "- fn greet(&str) -> String  (line 0)\n"
+    );
+
+    let args = json!({ "file_path": file.to_string_lossy(), "output": "yaml" });
+    let error = handle_tool_call(&mut server, "rust_analyzer_symbols", args)
+        .await
+        .unwrap_err();
+    assert!(
+        error.to_string().contains("expected json or markdown"),
+        "{}",
+        error
+    );
+}
@@ -0,0 +1,416 @@
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+
+use rust_analyzer_server::{
+    lsp::position::PositionEncoding,
+    mcp::{
+        handle_shared_tool_call,
+        module_tree::{outline_file, Export, ModuleDecl, Outline},
+    },
+};
+use test_support::{server_with_backend, write_file, MockBackend};
+
+/// LSP range of the first occurrence of `snippet` in ASCII `content`.
+fn range_of(content: &str, snippet: &str) -> Value {
+    let start = content.find(snippet).unwrap();
+    let position = |offset: usize| {
+        let before = &content[..offset];
+        let line = before.matches('\n').count();
+        let character = offset - before.rfind('\n').map_or(0, |newline| newline + 1);
+        json!({ "line": line, "character": character })
+    };
+    json!({ "start": position(start), "end": position(start + snippet.len()) })
+}
+
+/// Flat `SymbolInformation`s for `(name, kind, source)` in `content`.
+fn flat_symbols(uri: &str, content: &str, symbols: &[(&str, u64, &str)]) -> Value {
+    json!(symbols
+        .iter()
+        .map(|(name, kind, source)| json!({
+            "name": name,
+            "kind": kind,
+            "location": { "uri": uri, "range": range_of(content, source) }
+        }))
+        .collect::<Vec<_>>())
+}
+
+const LIB: &str = "\
+//! Crate docs.
+pub mod api;
+mod util {
+    pub fn helper() {}
+    fn private() {}
+}
+#[cfg(test)]
+mod tests;
+
+pub struct Point { x: i32 }
+pub(crate) fn internal() {}
+impl Point { pub fn new() -> Self { Point { x: 0 } } }
+";
+
+#[test]
+fn test_outline_counts_direct_items_and_nests_inline_modules() {
+    let util = "mod util {\n    pub fn helper() {}\n    fn private() {}\n}";
+    let symbols = flat_symbols(
+        "file:///lib.rs",
+        LIB,
+        &[
+            ("api", 2, "pub mod api;"),
+            ("util", 2, util),
+            ("helper", 12, "pub fn helper() {}"),
+            ("private", 12, "fn private() {}"),
+            ("tests", 2, "#[cfg(test)]\nmod tests;"),
+            ("Point", 23, "pub struct Point { x: i32 }"),
+            ("x", 8, "x: i32"),
+            ("internal", 12, "pub(crate) fn internal() {}"),
+            (
+                "impl Point",
+                19,
+                "impl Point { pub fn new() -> Self { Point { x: 0 } } }",
+            ),
+            ("new", 6, "pub fn new() -> Self { Point { x: 0 } }"),
+        ],
+    );
+
+    let outline = outline_file(LIB, &symbols, PositionEncoding::Utf16);
+    assert_eq!(
+        outline,
+        Outline {
+            items: 6,
+            public_items: 2,
+            exports: vec![
+                Export {
+                    name: "api".to_string(),
+                    kind: 2,
+                    line: 1,
+                    character: 8,
+                    hidden: false
+                },
+                Export {
+                    name: "Point".to_string(),
+                    kind: 23,
+                    line: 9,
+                    character: 11,
+                    hidden: false
+                },
+            ],
+            reexports: vec![],
+            modules: vec![
+                ModuleDecl {
+                    name: "api".to_string(),
+                    line: 1,
+                    character: 8,
+                    public: true,
+                    body: None
+                },
+                ModuleDecl {
+                    name: "util".to_string(),
+                    line: 2,
+                    character: 4,
+                    public: false,
+                    body: Some(Outline {
+                        items: 2,
+                        public_items: 1,
+                        exports: vec![Export {
+                            name: "helper".to_string(),
+                            kind: 12,
+                            line: 3,
+                            character: 11,
+                            hidden: false
+                        }],
+                        reexports: vec![],
+                        modules: vec![]
+                    })
+                },
+                ModuleDecl {
+                    name: "tests".to_string(),
+                    line: 7,
+                    character: 4,
+                    public: false,
+                    body: None
+                },
+            ]
+        }
+    );
+
+    // Nested `DocumentSymbol`s outline the same way.
+    let nested = json!([
+        { "name": "api", "kind": 2, "range": range_of(LIB, "pub mod api;") },
+        {
+            "name": "util",
+            "kind": 2,
+            "range": range_of(LIB, util),
+            "children": [
+                { "name": "helper", "kind": 12, "range": range_of(LIB, "pub fn helper() {}") },
+                { "name": "private", "kind": 12, "range": range_of(LIB, "fn private() {}") }
+            ]
+        }
+    ]);
+    let outline = outline_file(LIB, &nested, PositionEncoding::Utf16);
+    assert_eq!((outline.items, outline.public_items), (2, 1));
+    assert_eq!(outline.modules[1].body.as_ref().unwrap().items, 2);
+}
+
+#[tokio::test]
+async fn test_module_tree_follows_file_and_inline_modules() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    write_file(
+        &root,
+        "Cargo.toml",
+        "[package]\nname = \"shapes\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+    );
+    let lib = "pub mod api;\nmod util {\n    mod deep;\n}\n";
+    let lib_uri = write_file(&root, "src/lib.rs", lib);
+    let api = "pub fn get() {}\nfn helper() {}\n";
+    let api_uri = write_file(&root, "src/api.rs", api);
+    let deep = "fn x() {}\n";
+    let deep_uri = write_file(&root, "src/util/deep.rs", deep);
+
+    // No definitions, so module files are found by the usual layout.
+    let backend = MockBackend::new()
+        .respond_for(
+            "textDocument/documentSymbol",
+            &lib_uri,
+            flat_symbols(
+                &lib_uri,
+                lib,
+                &[
+                    ("api", 2, "pub mod api;"),
+                    ("util", 2, "mod util {\n    mod deep;\n}"),
+                    ("deep", 2, "mod deep;"),
+                ],
+            ),
+        )
+        .respond_for(
+            "textDocument/documentSymbol",
+            &api_uri,
+            flat_symbols(
+                &api_uri,
+                api,
+                &[
+                    ("get", 12, "pub fn get() {}"),
+                    ("helper", 12, "fn helper() {}"),
+                ],
+            ),
+        )
+        .respond_for(
+            "textDocument/documentSymbol",
+            &deep_uri,
+            flat_symbols(&deep_uri, deep, &[("x", 12, "fn x() {}")]),
+        );
+    let server = Mutex::new(server_with_backend(&root, &backend).await);
+    let module_tree = |args: Value| {
+        let server = &server;
+        async move {
+            let result = handle_shared_tool_call(server, "rust_analyzer_module_tree", args)
+                .await
+                .unwrap();
+            serde_json::from_str::<Value>(&result.content[0].text).unwrap()
+        }
+    };
+
+    let result = module_tree(json!({})).await;
+    assert_eq!(result["package"], "shapes");
+    assert_eq!(result["files"], 3);
+    let tree = &result["tree"];
+    assert_eq!(tree["name"], "crate");
+    assert_eq!(
+        (&tree["items"], &tree["public_items"]),
+        (&json!(2), &json!(1))
+    );
+    let api_node = &tree["modules"][0];
+    assert_eq!(
+        api_node["file"],
+        root.join("src/api.rs").to_string_lossy().as_ref()
+    );
+    assert_eq!(
+        (&api_node["items"], &api_node["public_items"]),
+        (&json!(2), &json!(1))
+    );
+    let util_node = &tree["modules"][1];
+    assert_eq!(util_node["inline"], true);
+    assert_eq!(
+        util_node["modules"][0]["file"],
+        root.join("src/util/deep.rs").to_string_lossy().as_ref()
+    );
+    let definition = &backend.requests("textDocument/definition")[0];
+    assert_eq!(definition["position"], json!({ "line": 0, "character": 8 }));
+
+    let shallow = module_tree(json!({ "max_depth": 1 })).await;
+    assert_eq!(
+        shallow["tree"]["modules"][1]["modules"][0],
+        json!({ "name": "deep", "truncated": true })
+    );
+}
+
+#[test]
+fn test_outline_lists_reexports_and_doc_hidden_items() {
+    let content = "\
+pub use crate::shapes::{Circle, Square as Box, nested::{self, Leaf}};
+#[doc(hidden)]
+pub use crate::raw::*;
+use crate::private::Thing;
+pub use std::fmt::Write as _;
+#[doc ( hidden )]
+pub fn internal() {}
+fn body() {
+    pub use crate::inner::Local;
+}
+";
+    let symbols = flat_symbols(
+        "file:///lib.rs",
+        content,
+        &[
+            ("internal", 12, "#[doc ( hidden )]\npub fn internal() {}"),
+            (
+                "body",
+                12,
+                "fn body() {\n    pub use crate::inner::Local;\n}",
+            ),
+        ],
+    );
+
+    let outline = outline_file(content, &symbols, PositionEncoding::Utf16);
+    let reexports: Vec<_> = outline
+        .reexports
+        .iter()
+        .map(|reexport| {
+            (
+                reexport.path.as_str(),
+                reexport.name.as_str(),
+                reexport.line,
+                reexport.character,
+                reexport.hidden,
+            )
+        })
+        .collect();
+    assert_eq!(
+        reexports,
+        vec![
+            ("crate::shapes::Circle", "Circle", 0, 24),
+            ("crate::shapes::Square", "Box", 0, 32),
+            ("crate::shapes::nested", "nested", 0, 56),
+            ("crate::shapes::nested::Leaf", "Leaf", 0, 62),
+            ("crate::raw::*", "*", 2, 20),
+        ]
+        .into_iter()
+        .map(|(path, name, line, character)| (path, name, line, character, line == 2))
+        .collect::<Vec<_>>()
+    );
+    assert_eq!(outline.exports.len(), 1);
+    assert!(outline.exports[0].hidden);
+    assert_eq!(
+        (outline.exports[0].line, outline.exports[0].character),
+        (6, 7)
+    );
+}
+
+#[tokio::test]
+async fn test_public_api_lists_paths_in_public_modules() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    write_file(
+        &root,
+        "Cargo.toml",
+        "[package]\nname = \"my-shapes\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+    );
+    let lib = "\
+pub mod api;
+mod util;
+#[doc(hidden)]
+pub mod internals {
+    pub fn raw() {}
+}
+pub use api::get as fetch;
+";
+    let lib_uri = write_file(&root, "src/lib.rs", lib);
+    let api = "pub fn get() {}\nfn helper() {}\n";
+    let api_uri = write_file(&root, "src/api.rs", api);
+    write_file(&root, "src/util.rs", "pub fn unreachable() {}\n");
+
+    let backend = MockBackend::new()
+        .respond_for(
+            "textDocument/documentSymbol",
+            &lib_uri,
+            flat_symbols(
+                &lib_uri,
+                lib,
+                &[
+                    ("api", 2, "pub mod api;"),
+                    ("util", 2, "mod util;"),
+                    (
+                        "internals",
+                        2,
+                        "#[doc(hidden)]\npub mod internals {\n    pub fn raw() {}\n}",
+                    ),
+                    ("raw", 12, "pub fn raw() {}"),
+                ],
+            ),
+        )
+        .respond_for(
+            "textDocument/documentSymbol",
+            &api_uri,
+            flat_symbols(
+                &api_uri,
+                api,
+                &[
+                    ("get", 12, "pub fn get() {}"),
+                    ("helper", 12, "fn helper() {}"),
+                ],
+            ),
+        )
+        .respond(
+            "textDocument/definition",
+            json!({ "uri": api_uri, "range": range_of(api, "get") }),
+        );
+    let server = Mutex::new(server_with_backend(&root, &backend).await);
+
+    let result = handle_shared_tool_call(&server, "rust_analyzer_public_api", json!({}))
+        .await
+        .unwrap();
+    let result: Value = serde_json::from_str(&result.content[0].text).unwrap();
+    let lib_file = root.join("src/lib.rs").display().to_string();
+    let api_file = root.join("src/api.rs").display().to_string();
+    assert_eq!(result["count"], 5);
+    assert_eq!(
+        result["items"],
+        json!([
+            {
+                "path": "my_shapes::api",
+                "kind": "module",
+                "location": format!("{}:0:8", lib_file)
+            },
+            {
+                "path": "my_shapes::api::get",
+                "kind": "function",
+                "location": format!("{}:0:7", api_file)
+            },
+            {
+                "path": "my_shapes::fetch",
+                "kind": "reexport",
+                "source": "api::get",
+                "target": format!("{}:0:7", api_file)
+            },
+            {
+                "path": "my_shapes::internals",
+                "kind": "module",
+                "location": format!("{}:3:8", lib_file),
+                "hidden": true
+            },
+            {
+                "path": "my_shapes::internals::raw",
+                "kind": "function",
+                "location": format!("{}:4:11", lib_file),
+                "hidden": true
+            }
+        ])
+    );
+    // The private module's file is never opened.
+    let opened = backend.notifications("textDocument/didOpen");
+    assert!(opened.iter().all(|open| !open["textDocument"]["uri"]
+        .as_str()
+        .unwrap()
+        .ends_with("util.rs")));
+}
@@ -0,0 +1,183 @@
+use serde_json::{json, Value};
+use std::path::Path;
+
+use rust_analyzer_server::mcp::{handle_tool_call, rename::unified_diff};
+use test_support::{server_with_backend, write_file, MockBackend};
+
+#[test]
+fn test_unified_diff_hunks() {
+    let old: String = (1..=20).map(|n| format!("line {}\n", n)).collect();
+    let new = old
+        .replace("line 2\n", "line two\n")
+        .replace("line 15\n", "line 15\nline 15b\n");
+    assert_eq!(
+        unified_diff(&old, &new, "src/lib.rs", 1),
+        [
+            "--- src/lib.rs",
+            "+++ src/lib.rs",
+            "@@ -1,3 +1,3 @@",
+            " line 1",
+            "-line 2",
+            "+line two",
+            " line 3",
+            "@@ -15,2 +15,3 @@",
+            " line 15",
+            "+line 15b",
+            " line 16",
+            "",
+        ]
+        .join("\n")
+    );
+    assert_eq!(unified_diff(&old, &old, "src/lib.rs", 3), "");
+}
+
+#[test]
+fn test_unified_diff_joins_nearby_changes() {
+    let old = "a\nb\nc\nd\ne\n";
+    let new = "A\nb\nc\nD\ne\n";
+    assert_eq!(
+        unified_diff(old, new, "f.rs", 1),
+        "--- f.rs\n+++ f.rs\n@@ -1,5 +1,5 @@\n-a\n+A\n b\n c\n-d\n+D\n e\n"
+    );
+}
+
+fn edit(line: u32, start: u32, end: u32, new_text: &str) -> Value {
+    json!({
+        "range": {
+            "start": { "line": line, "character": start },
+            "end": { "line": line, "character": end }
+        },
+        "newText": new_text
+    })
+}
+
+/// A workspace where `area` is defined in lib.rs and used in main.rs, and
+/// a backend renaming it to `surface`.
+fn workspace(root: &Path) -> (MockBackend, String, String) {
+    let lib = write_file(root, "src/lib.rs", "pub fn area() -> u32 {\n    4\n}\n");
+    let main = write_file(root, "src/main.rs", "fn main() {\n    demo::area();\n}\n");
+    let mut changes = serde_json::Map::new();
+    changes.insert(lib.clone(), json!([edit(0, 7, 11, "surface")]));
+    changes.insert(main.clone(), json!([edit(1, 10, 14, "surface")]));
+    let backend = MockBackend::new().respond("textDocument/rename", json!({ "changes": changes }));
+    (backend, lib, main)
+}
+
+fn args(root: &Path, preview: bool) -> Value {
+    json!({
+        "file_path": root.join("src/lib.rs").to_string_lossy(),
+        "line": 0,
+        "character": 7,
+        "new_name": "surface",
+        "preview": preview
+    })
+}
+
+#[tokio::test]
+async fn test_rename_preview_writes_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let (backend, lib, _) = workspace(&root);
+    let mut server = server_with_backend(&root, &backend).await;
+
+    let result = handle_tool_call(&mut server, "rust_analyzer_rename", args(&root, true))
+        .await
+        .unwrap();
+    let result: Value = serde_json::from_str(&result.content[0].text).unwrap();
+    assert_eq!(result["files_touched"], 2);
+    assert_eq!(result["occurrences"], 2);
+    assert!(result.get("warning").is_none());
+    let lib_path = root.join("src/lib.rs").display().to_string();
+    assert_eq!(result["files"][0]["file"], lib_path);
+    assert_eq!(
+        result["files"][0]["diff"],
+        format!(
+            "--- {0}\n+++ {0}\n@@ -1,3 +1,3 @@\n-pub fn area() -> u32 {{\n+pub fn surface() -> u32 {{\n     4\n }}\n",
+            lib_path
+        )
+    );
+
+    assert_eq!(
+        std::fs::read_to_string(root.join("src/lib.rs")).unwrap(),
+        "pub fn area() -> u32 {\n    4\n}\n"
+    );
+    assert_eq!(
+        backend.requests("textDocument/rename"),
+        [json!({
+            "textDocument": { "uri": lib },
+            "position": { "line": 0, "character": 7 },
+            "newName": "surface"
+        })]
+    );
+}
+
+#[tokio::test]
+async fn test_rename_preview_flags_files_changed_on_disk() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let (backend, _, _) = workspace(&root);
+    let backend = backend.respond("textDocument/hover", json!({ "contents": "fn main()" }));
+    let mut server = server_with_backend(&root, &backend).await;
+
+    // rust-analyzer has main.rs open, then it changes under it.
+    let hover = json!({
+        "file_path": root.join("src/main.rs").to_string_lossy(),
+        "line": 1,
+        "character": 10
+    });
+    handle_tool_call(&mut server, "rust_analyzer_hover", hover)
+        .await
+        .unwrap();
+    write_file(
+        &root,
+        "src/main.rs",
+        "fn main() {\n    demo::area(); // edited\n}\n",
+    );
+
+    let result = handle_tool_call(&mut server, "rust_analyzer_rename", args(&root, true))
+        .await
+        .unwrap();
+    let result: Value = serde_json::from_str(&result.content[0].text).unwrap();
+    assert!(result["files"][0].get("modified").is_none());
+    assert_eq!(result["files"][1]["modified"], true);
+    assert!(result["warning"].as_str().unwrap().contains("may conflict"));
+    // The diff is of the text rust-analyzer renamed in.
+    assert!(result["files"][1]["diff"]
+        .as_str()
+        .unwrap()
+        .contains("\n+    demo::surface();\n"));
+}
+
+#[tokio::test]
+async fn test_rename_writes_every_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let (backend, _, _) = workspace(&root);
+    let mut server = server_with_backend(&root, &backend).await;
+
+    let result = handle_tool_call(&mut server, "rust_analyzer_rename", args(&root, false))
+        .await
+        .unwrap();
+    let result: Value = serde_json::from_str(&result.content[0].text).unwrap();
+    assert_eq!(result["renamed"], "surface");
+    assert_eq!(result["occurrences"], 2);
+    assert_eq!(
+        std::fs::read_to_string(root.join("src/main.rs")).unwrap(),
+        "fn main() {\n    demo::surface();\n}\n"
+    );
+}
+
+#[tokio::test]
+async fn test_rename_needs_a_new_name() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let (backend, _, _) = workspace(&root);
+    let mut server = server_with_backend(&root, &backend).await;
+
+    let mut args = args(&root, true);
+    args["new_name"] = json!("");
+    let error = handle_tool_call(&mut server, "rust_analyzer_rename", args)
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("Missing new_name"));
+}
@@ -0,0 +1,157 @@
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+
+use rust_analyzer_server::{
+    config::Config,
+    mcp::{
+        handle_shared_tool_call,
+        run_test::{failure_excerpt, tests_run},
+        RustAnalyzerMCPServer,
+    },
+};
+use test_support::MockBackend;
+
+const FAILED_RUN: &str = "\
+running 1 test
+test tests::subtracts ... FAILED
+
+failures:
+
+---- tests::subtracts stdout ----
+
+thread 'tests::subtracts' panicked at src/lib.rs:12:9:
+assertion `left == right` failed
+  left: 1
+ right: 2
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+
+
+failures:
+    tests::subtracts
+
+test result: FAILED. 0 passed; 1 failed; 0 ignored; 0 measured; 1 filtered out; finished in 0.00s
+";
+
+#[test]
+fn test_failure_excerpt_is_the_panic() {
+    assert_eq!(
+        failure_excerpt(FAILED_RUN, ""),
+        "thread 'tests::subtracts' panicked at src/lib.rs:12:9:\n\
+         assertion `left == right` failed\n  left: 1\n right: 2"
+    );
+
+    let stderr = "   Compiling calc v0.1.0 (/ws)\n\
+                  error[E0425]: cannot find value `y` in this scope\n \
+                  --> src/lib.rs:3:9\n";
+    assert!(failure_excerpt("", stderr).starts_with("error[E0425]"));
+}
+
+#[test]
+fn test_tests_run_counts_passed_and_failed() {
+    assert_eq!(tests_run(FAILED_RUN), 1);
+    assert_eq!(
+        tests_run(
+            "test result: ok. 0 passed; 0 failed; 0 ignored; 0 measured; 3 filtered out; finished in 0.00s\n\
+             test result: ok. 2 passed; 0 failed; 1 ignored; 0 measured; 0 filtered out; finished in 0.01s"
+        ),
+        2
+    );
+}
+
+fn runnable(dir: &std::path::Path, name: &str) -> Value {
+    json!({
+        "label": format!("test {}", name),
+        "kind": "cargo",
+        "args": {
+            "workspaceRoot": dir.to_string_lossy(),
+            "cwd": dir.to_string_lossy(),
+            "cargoArgs": ["test", "--package", "calc", "--lib"],
+            "executableArgs": [name, "--exact", "--show-output"]
+        }
+    })
+}
+
+#[tokio::test]
+async fn test_run_test_reports_pass_and_failure() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    std::fs::write(
+        root.join("Cargo.toml"),
+        "[package]\nname = \"calc\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+    )
+    .unwrap();
+    std::fs::create_dir(root.join("src")).unwrap();
+    let lib = root.join("src/lib.rs");
+    std::fs::write(
+        &lib,
+        "#[cfg(test)]\nmod tests {\n    #[test]\n    fn adds() {\n        assert_eq!(1 + 1, 2);\n    }\n\n    #[test]\n    fn subtracts() {\n        assert_eq!(2 - 1, 2);\n    }\n}\n",
+    )
+    .unwrap();
+
+    let backend = MockBackend::new().respond(
+        "experimental/runnables",
+        json!([runnable(&root, "tests::subtracts")]),
+    );
+    let config = Config {
+        document_open_delay_millis: 0,
+        ..Config::default()
+    };
+    let mut server = RustAnalyzerMCPServer::with_config(root.clone(), config);
+    server
+        .start_with_backend(Box::new(backend.clone()))
+        .await
+        .unwrap();
+    let server = Mutex::new(server);
+
+    let run = |args: Value| {
+        let server = &server;
+        async move {
+            let result = handle_shared_tool_call(server, "rust_analyzer_run_test", args)
+                .await
+                .unwrap();
+            serde_json::from_str::<Value>(&result.content[0].text).unwrap()
+        }
+    };
+
+    let listed = json!({
+        "name": "tests::adds",
+        "cwd": root.to_string_lossy(),
+        "cargo_args": ["test", "--package", "calc", "--lib"],
+        "executable_args": ["tests::adds", "--exact"]
+    });
+    let passed = run(json!({ "runnable": listed })).await;
+    assert_eq!(passed["status"], "passed", "{}", passed);
+    assert_eq!(passed["passed"], true);
+
+    let failed = run(json!({
+        "file_path": lib.to_string_lossy(),
+        "line": 10,
+        "character": 8
+    }))
+    .await;
+    assert_eq!(failed["name"], "tests::subtracts");
+    assert_eq!(failed["status"], "failed");
+    assert!(
+        failed["failure"]
+            .as_str()
+            .unwrap()
+            .contains("assertion `left == right` failed"),
+        "{}",
+        failed
+    );
+    let position = &backend.requests("experimental/runnables")[0]["position"];
+    assert_eq!(position, &json!({ "line": 10, "character": 8 }));
+
+    let error = handle_shared_tool_call(
+        &server,
+        "rust_analyzer_run_test",
+        json!({ "runnable": { "cwd": "/", "cargo_args": ["test"], "executable_args": [] } }),
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        error.to_string().contains("outside the workspace"),
+        "{}",
+        error
+    );
+}
@@ -0,0 +1,396 @@
+use serde_json::{json, Value};
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use protobuf::Message;
+use rust_analyzer_server::{
+    mcp::{
+        handle_tool_call,
+        scip::{module_descriptors, scip_range, ProtoMessage, SymbolTable},
+    },
+    protocol::{ErrorCode, ToolError},
+};
+use scip::types::{Document, Index, Occurrence, PositionEncoding, SymbolRole, TextEncoding};
+use test_support::{server_with_backend, write_file, MockBackend};
+
+fn has_unknown_fields(message: &impl Message) -> bool {
+    message
+        .special_fields()
+        .unknown_fields()
+        .iter()
+        .next()
+        .is_some()
+}
+
+/// Parse `bytes` as a `scip.proto` document, failing on any field
+/// `scip.proto` doesn't define.
+fn parse_document(bytes: &[u8]) -> Document {
+    let document = Document::parse_from_bytes(bytes).unwrap();
+    assert_known_fields(&document);
+    document
+}
+
+fn assert_known_fields(document: &Document) {
+    assert!(!has_unknown_fields(document));
+    assert!(!document.occurrences.iter().any(has_unknown_fields));
+    assert!(!document.symbols.iter().any(has_unknown_fields));
+}
+
+/// Parse `bytes` as a `scip.proto` index, failing on any field `scip.proto`
+/// doesn't define.
+fn parse_index(bytes: &[u8]) -> Index {
+    let index = Index::parse_from_bytes(bytes).unwrap();
+    assert!(!has_unknown_fields(&index));
+    assert!(!has_unknown_fields(&*index.metadata));
+    assert!(!has_unknown_fields(&*index.metadata.tool_info));
+    index.documents.iter().for_each(assert_known_fields);
+    index
+}
+
+#[test]
+fn test_proto_message_encoding() {
+    let mut message = ProtoMessage::new();
+    message
+        .uint(1, 150)
+        .string(2, "hi")
+        .packed(3, &[3, 270])
+        .uint(4, 0)
+        .string(5, "")
+        .packed(6, &[]);
+    assert_eq!(
+        message.as_bytes(),
+        [0x08, 0x96, 0x01, 0x12, 2, b'h', b'i', 0x1a, 3, 3, 0x8e, 0x02]
+    );
+
+    let mut outer = ProtoMessage::new();
+    outer.message(1, &ProtoMessage::new()).packed(2, &[-1]);
+    assert_eq!(outer.as_bytes()[..2], [0x0a, 0]);
+    // A negative int32 is sign-extended to ten bytes.
+    assert_eq!(outer.as_bytes()[2..4], [0x12, 10]);
+}
+
+#[test]
+fn test_proto_message_round_trip() {
+    let long = "é".repeat(100);
+    let mut occurrence = ProtoMessage::new();
+    occurrence
+        .packed(1, &[0, 127, 128, i32::MAX])
+        .string(2, &long)
+        .uint(3, u64::from(u32::MAX >> 1));
+    let mut negative = ProtoMessage::new();
+    negative.packed(1, &[-1, i32::MIN, 3]);
+    let mut information = ProtoMessage::new();
+    information.string(1, "a b c.").string(3, "").string(6, "c");
+    let mut document = ProtoMessage::new();
+    document
+        .string(1, "src/lib.rs")
+        .message(2, &occurrence)
+        .message(2, &negative)
+        .message(2, &ProtoMessage::new())
+        .message(3, &information)
+        .string(4, "rust")
+        .uint(6, 3);
+
+    let document = parse_document(document.as_bytes());
+    assert_eq!(document.relative_path, "src/lib.rs");
+    assert_eq!(document.language, "rust");
+    assert_eq!(
+        document.position_encoding.enum_value(),
+        Ok(PositionEncoding::UTF32CodeUnitOffsetFromLineStart)
+    );
+    assert_eq!(document.occurrences.len(), 3);
+    assert_eq!(document.occurrences[0].range, [0, 127, 128, i32::MAX]);
+    assert_eq!(document.occurrences[0].symbol, long);
+    assert_eq!(document.occurrences[0].symbol_roles, i32::MAX);
+    assert_eq!(document.occurrences[1].range, [-1, i32::MIN, 3]);
+    assert_eq!(document.occurrences[2], Occurrence::new());
+    assert_eq!(document.symbols[0].symbol, "a b c.");
+    // Empty strings are left out, as protobuf does.
+    assert!(document.symbols[0].documentation.is_empty());
+    assert_eq!(document.symbols[0].display_name, "c");
+
+    let mut stray = ProtoMessage::new();
+    stray.string(99, "not in scip.proto");
+    assert!(has_unknown_fields(
+        &Document::parse_from_bytes(stray.as_bytes()).unwrap()
+    ));
+}
+
+#[test]
+fn test_scip_range() {
+    let range = |start: (u32, u32), end: (u32, u32)| {
+        json!({
+            "start": { "line": start.0, "character": start.1 },
+            "end": { "line": end.0, "character": end.1 }
+        })
+    };
+    assert_eq!(scip_range(&range((3, 4), (3, 9))), Some(vec![3, 4, 9]));
+    assert_eq!(scip_range(&range((3, 0), (5, 1))), Some(vec![3, 0, 5, 1]));
+    assert_eq!(scip_range(&json!(null)), None);
+}
+
+#[test]
+fn test_module_descriptors() {
+    let descriptors = |path: &str| module_descriptors(Path::new(path));
+    assert_eq!(descriptors("src/lib.rs"), "");
+    assert_eq!(descriptors("src/main.rs"), "");
+    assert_eq!(descriptors("src/shapes/mod.rs"), "shapes/");
+    assert_eq!(descriptors("src/shapes/round.rs"), "shapes/round/");
+    assert_eq!(descriptors("tests/it.rs"), "tests/it/");
+    assert_eq!(descriptors("src/r#type.rs"), "`r#type`/");
+}
+
+fn symbol(name: &str, kind: u32, line: u32, children: Value) -> Value {
+    let range = json!({
+        "start": { "line": line, "character": 4 },
+        "end": { "line": line, "character": 4 + name.len() }
+    });
+    json!({
+        "name": name,
+        "kind": kind,
+        "detail": if kind == 12 || kind == 6 { json!(format!("fn {}()", name)) } else { json!(null) },
+        "range": range,
+        "selectionRange": range,
+        "children": children
+    })
+}
+
+#[test]
+fn test_symbol_table_definitions() {
+    let symbols = json!([
+        symbol("Point", 23, 0, json!([symbol("x", 8, 1, json!([]))])),
+        symbol(
+            "impl Display for Point",
+            19,
+            3,
+            json!([symbol("fmt", 6, 4, json!([]))])
+        ),
+        symbol(
+            "impl<T> Debug for Point<T>",
+            19,
+            6,
+            json!([symbol("fmt", 6, 7, json!([]))])
+        ),
+        symbol("round", 2, 9, json!([symbol("PI", 14, 10, json!([]))])),
+        symbol("main", 12, 12, json!([]))
+    ]);
+    let mut table = SymbolTable::default();
+    let prefix = "rust-analyzer cargo geometry 0.1.0 shapes/";
+    let definitions = table.definitions(&symbols, prefix);
+    let names: Vec<&str> = definitions
+        .iter()
+        .map(|definition| {
+            table.symbols[definition.symbol]
+                .strip_prefix(prefix)
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "Point#",
+            "Point#x.",
+            "Point#fmt().",
+            "Point#fmt(+1).",
+            "round/",
+            "round/PI.",
+            "main()."
+        ]
+    );
+    assert_eq!(definitions[6].range, [12, 4, 8]);
+    assert_eq!(definitions[6].position, (12, 4));
+    assert_eq!(definitions[6].detail.as_deref(), Some("fn main()"));
+    assert!(definitions.iter().all(|definition| definition.first));
+
+    // The same item seen again is the same symbol.
+    let again = table.definitions(&json!([symbol("Point", 23, 0, json!([]))]), prefix);
+    assert_eq!(again[0].symbol, definitions[0].symbol);
+    assert!(!again[0].first);
+}
+
+#[tokio::test]
+async fn test_export_index_writes_definitions_and_references() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    write_file(
+        &root,
+        "Cargo.toml",
+        "[package]\nname = \"geometry\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+    );
+    let lib = write_file(&root, "src/lib.rs", "mod shapes;\npub fn area() {}\n");
+    let shapes = write_file(&root, "src/shapes.rs", "fn square() { crate::area() }\n");
+    let location = |uri: &str, line: u32, start: u32, end: u32| {
+        json!({
+            "uri": uri,
+            "range": {
+                "start": { "line": line, "character": start },
+                "end": { "line": line, "character": end }
+            }
+        })
+    };
+
+    let backend = MockBackend::new()
+        .respond_for(
+            "textDocument/documentSymbol",
+            &lib,
+            json!([symbol("area", 12, 1, json!([]))]),
+        )
+        .respond_for(
+            "textDocument/documentSymbol",
+            &shapes,
+            json!([symbol("square", 12, 0, json!([]))]),
+        )
+        // References to `area`, one of them in a dependency.
+        .respond_at(
+            "textDocument/references",
+            &lib,
+            1,
+            json!([
+                location(&shapes, 0, 21, 25),
+                location("file:///registry/other/src/lib.rs", 3, 0, 4)
+            ]),
+        );
+    let mut server = server_with_backend(&root, &backend).await;
+
+    let result = handle_tool_call(&mut server, "rust_analyzer_export_index", json!({}))
+        .await
+        .unwrap();
+    let result: Value = serde_json::from_str(&result.content[0].text).unwrap();
+    assert_eq!(result["documents"], 2);
+    assert_eq!(result["symbols"], 2);
+    assert_eq!(result["occurrences"], 3);
+    assert_eq!(
+        backend.requests("textDocument/references")[0]["context"],
+        json!({ "includeDeclaration": false })
+    );
+
+    let index = parse_index(&std::fs::read(root.join("index.scip")).unwrap());
+    let metadata = &index.metadata;
+    assert_eq!(metadata.project_root, format!("file://{}", root.display()));
+    assert_eq!(metadata.tool_info.name, "rust-analyzer-server");
+    assert_eq!(
+        metadata.text_document_encoding.enum_value(),
+        Ok(TextEncoding::UTF8)
+    );
+
+    let paths: Vec<&str> = index
+        .documents
+        .iter()
+        .map(|document| document.relative_path.as_str())
+        .collect();
+    assert_eq!(paths, ["src/lib.rs", "src/shapes.rs"]);
+
+    let shapes_document = &index.documents[1];
+    assert_eq!(shapes_document.language, "rust");
+    // The mock agrees on no encoding, so columns are UTF-16.
+    assert_eq!(
+        shapes_document.position_encoding.enum_value(),
+        Ok(PositionEncoding::UTF16CodeUnitOffsetFromLineStart)
+    );
+    let occurrences: Vec<(&[i32], &str, i32)> = shapes_document
+        .occurrences
+        .iter()
+        .map(|occurrence| {
+            (
+                occurrence.range.as_slice(),
+                occurrence.symbol.as_str(),
+                occurrence.symbol_roles,
+            )
+        })
+        .collect();
+    assert_eq!(
+        occurrences,
+        [
+            (
+                &[0, 4, 10][..],
+                "rust-analyzer cargo geometry 0.1.0 shapes/square().",
+                SymbolRole::Definition as i32
+            ),
+            (
+                &[0, 21, 25][..],
+                "rust-analyzer cargo geometry 0.1.0 area().",
+                0
+            ),
+        ]
+    );
+
+    let information = &index.documents[0].symbols[0];
+    assert_eq!(
+        information.symbol,
+        "rust-analyzer cargo geometry 0.1.0 area()."
+    );
+    assert_eq!(information.documentation, ["```rust\nfn area()\n```"]);
+    assert_eq!(information.display_name, "area");
+
+    // Only the index is left.
+    assert!(!root.join("index.scip.tmp").exists());
+}
+
+#[tokio::test]
+async fn test_export_index_rejects_lsif() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let backend = MockBackend::new();
+    let mut server = server_with_backend(&root, &backend).await;
+
+    let args = json!({ "format": "lsif" });
+    let error = handle_tool_call(&mut server, "rust_analyzer_export_index", args)
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("LSIF output isn't supported"));
+}
+
+#[tokio::test]
+async fn test_export_index_reference_requests_honour_timeout_secs() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let lib = write_file(&root, "src/lib.rs", "pub fn area() {}\nfn f() { area() }\n");
+    let backend = MockBackend::new()
+        .respond_for(
+            "textDocument/documentSymbol",
+            &lib,
+            json!([symbol("area", 12, 0, json!([]))]),
+        )
+        .respond(
+            "textDocument/references",
+            json!([{
+                "uri": lib,
+                "range": {
+                    "start": { "line": 1, "character": 9 },
+                    "end": { "line": 1, "character": 13 }
+                }
+            }]),
+        )
+        .delay("textDocument/references", Duration::from_secs(3));
+    let mut server = server_with_backend(&root, &backend).await;
+
+    let start = Instant::now();
+    let args = json!({ "timeout_secs": 1 });
+    let result = handle_tool_call(&mut server, "rust_analyzer_export_index", args)
+        .await
+        .unwrap();
+    assert!(start.elapsed() < Duration::from_secs(3));
+    let result: Value = serde_json::from_str(&result.content[0].text).unwrap();
+    // The reference came too late to be indexed.
+    assert_eq!(result["occurrences"], 1);
+}
+
+#[tokio::test]
+async fn test_export_index_stays_inside_the_workspace() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap().join("project");
+    write_file(&root, "src/lib.rs", "pub fn area() {}\n");
+    let mut server = server_with_backend(&root, &MockBackend::new()).await;
+
+    let args = json!({ "output": "../index.scip" });
+    let error = handle_tool_call(&mut server, "rust_analyzer_export_index", args)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        ToolError::from_anyhow(&error).code,
+        ErrorCode::InvalidParams
+    );
+    assert!(!dir.path().join("index.scip").exists());
+}
@@ -0,0 +1,198 @@
+//! Pins the simplified output of each tool for `test-project`, from
+//! responses shaped like rust-analyzer's. Review changes with
+//! `cargo insta review`, or rerun with `INSTA_UPDATE=always`.
+
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+use rust_analyzer_server::{
+    config::Config,
+    mcp::{handle_tool_call, RustAnalyzerMCPServer},
+};
+use test_support::{normalize_for_snapshot, MockBackend};
+
+fn fixture() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("test-project")
+        .canonicalize()
+        .unwrap()
+}
+
+fn uri(relative_path: &str) -> String {
+    format!("file://{}", fixture().join(relative_path).display())
+}
+
+fn range(line: u32, start: u32, end: u32) -> Value {
+    json!({
+        "start": { "line": line, "character": start },
+        "end": { "line": line, "character": end }
+    })
+}
+
+/// Make `calls` on a server answering through `backend`, and normalize the
+/// last one's result for snapshotting.
+async fn tool_output(backend: MockBackend, calls: &[(&str, Value)]) -> Value {
+    let config = Config {
+        retry_interval_millis: 10,
+        document_open_delay_millis: 0,
+        ..Config::default()
+    };
+    let mut server = RustAnalyzerMCPServer::with_config(fixture(), config);
+    server.start_with_backend(Box::new(backend)).await.unwrap();
+
+    let mut output = Value::Null;
+    for (tool, args) in calls {
+        let result = handle_tool_call(&mut server, tool, args.clone())
+            .await
+            .unwrap();
+        output = serde_json::from_str(&result.content[0].text).unwrap();
+    }
+    normalize_for_snapshot(&output, &fixture())
+}
+
+fn at(line: u32, character: u32) -> Value {
+    json!({
+        "file_path": fixture().join("src/main.rs").to_string_lossy(),
+        "line": line,
+        "character": character
+    })
+}
+
+#[tokio::test]
+async fn test_hover_snapshot() {
+    let backend = MockBackend::new().respond(
+        "textDocument/hover",
+        json!({
+            "contents": {
+                "kind": "markdown",
+                "value": "\n```rust\ntest_project\n```\n\n```rust\nfn greet(name: &str) -> String\n```"
+            },
+            "range": range(1, 18, 23)
+        }),
+    );
+    let output = tool_output(backend, &[("rust_analyzer_hover", at(1, 18))]).await;
+    insta::assert_json_snapshot!(output);
+}
+
+#[tokio::test]
+async fn test_definition_snapshot() {
+    let backend = MockBackend::new().respond(
+        "textDocument/definition",
+        json!([{
+            "originSelectionRange": range(1, 18, 23),
+            "targetUri": uri("src/main.rs"),
+            "targetRange": { "start": { "line": 14, "character": 0 }, "end": { "line": 16, "character": 1 } },
+            "targetSelectionRange": range(14, 3, 8)
+        }]),
+    );
+    let output = tool_output(backend, &[("rust_analyzer_definition", at(1, 18))]).await;
+    insta::assert_json_snapshot!(output);
+}
+
+#[tokio::test]
+async fn test_references_snapshot() {
+    let backend = MockBackend::new().respond(
+        "textDocument/references",
+        json!([
+            { "uri": uri("src/main.rs"), "range": range(46, 19, 24) },
+            { "uri": uri("src/main.rs"), "range": range(14, 3, 8) },
+            { "uri": uri("src/main.rs"), "range": range(1, 18, 23) }
+        ]),
+    );
+    let output = tool_output(backend, &[("rust_analyzer_references", at(14, 3))]).await;
+    insta::assert_json_snapshot!(output);
+}
+
+#[tokio::test]
+async fn test_inlay_hint_snapshot() {
+    let backend = MockBackend::new().respond(
+        "textDocument/inlayHint",
+        json!([
+            { "position": { "line": 1, "character": 15 }, "label": [{ "value": ": " }, { "value": "String" }], "kind": 1 },
+            { "position": { "line": 1, "character": 24 }, "label": "name:", "kind": 2, "paddingRight": true },
+            { "position": { "line": 4, "character": 12 }, "label": ": Calculator", "kind": 1 }
+        ]),
+    );
+    let mut args = at(0, 0);
+    args["end_line"] = json!(12);
+    args["end_character"] = json!(0);
+    let output = tool_output(backend, &[("rust_analyzer_inlay_hint", args)]).await;
+    insta::assert_json_snapshot!(output);
+}
+
+#[tokio::test]
+async fn test_symbols_snapshot() {
+    let backend = MockBackend::new().respond(
+        "textDocument/documentSymbol",
+        json!([
+            { "name": "main", "kind": 12, "range": range(0, 0, 1), "selectionRange": range(0, 3, 7) },
+            { "name": "greet", "detail": "fn(&str) -> String", "kind": 12, "range": range(14, 0, 1), "selectionRange": range(14, 3, 8) },
+            {
+                "name": "Calculator", "kind": 23, "range": range(18, 0, 1), "selectionRange": range(18, 7, 17),
+                "children": [{ "name": "value", "detail": "i32", "kind": 8, "range": range(19, 4, 14), "selectionRange": range(19, 4, 9) }]
+            }
+        ]),
+    );
+    let output = tool_output(
+        backend,
+        &[(
+            "rust_analyzer_symbols",
+            json!({ "file_path": fixture().join("src/main.rs").to_string_lossy() }),
+        )],
+    )
+    .await;
+    insta::assert_json_snapshot!(output);
+}
+
+#[tokio::test]
+async fn test_workspace_symbol_snapshot() {
+    let backend = MockBackend::new().respond(
+        "workspace/symbol",
+        json!([{
+            "name": "Calculator",
+            "kind": 23,
+            "location": { "uri": uri("src/main.rs"), "range": range(18, 7, 17) }
+        }]),
+    );
+    let output = tool_output(
+        backend,
+        &[(
+            "rust_analyzer_workspace_symbol",
+            json!({ "query": "Calculator" }),
+        )],
+    )
+    .await;
+    insta::assert_json_snapshot!(output);
+}
+
+#[tokio::test]
+async fn test_workspace_diagnostics_snapshot() {
+    // No pull support, so the open document's diagnostics are collected.
+    let backend = MockBackend::new().respond(
+        "textDocument/diagnostic",
+        json!({
+            "kind": "full",
+            "resultId": "rust-analyzer:1",
+            "items": [{
+                "range": range(26, 4, 30),
+                "severity": 2,
+                "code": "dead_code",
+                "source": "rustc",
+                "message": "method `multiply` is never used",
+                "data": { "rendered": "warning: method `multiply` is never used" }
+            }]
+        }),
+    );
+    let output = tool_output(
+        backend,
+        &[
+            (
+                "rust_analyzer_symbols",
+                json!({ "file_path": fixture().join("src/main.rs").to_string_lossy() }),
+            ),
+            ("rust_analyzer_workspace_diagnostics", json!({})),
+        ],
+    )
+    .await;
+    insta::assert_json_snapshot!(output);
+}
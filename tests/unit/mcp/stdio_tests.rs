@@ -0,0 +1,59 @@
+use serde_json::json;
+use tokio::sync::Mutex;
+
+use rust_analyzer_server::{
+    mcp::stdio::{handle_message, PROTOCOL_VERSION},
+    RustAnalyzerMCPServer,
+};
+
+fn server() -> Mutex<RustAnalyzerMCPServer> {
+    Mutex::new(RustAnalyzerMCPServer::with_workspace(std::env::temp_dir()))
+}
+
+#[tokio::test]
+async fn test_initialize_and_list_tools() {
+    let server = server();
+    let reply = handle_message(
+        &server,
+        &json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(reply["id"], 1);
+    assert_eq!(reply["result"]["protocolVersion"], PROTOCOL_VERSION);
+    assert!(reply["result"]["capabilities"]["tools"].is_object());
+
+    let reply = handle_message(
+        &server,
+        &json!({ "jsonrpc": "2.0", "id": "list", "method": "tools/list" }),
+    )
+    .await
+    .unwrap();
+    let tools = reply["result"]["tools"].as_array().unwrap();
+    assert!(tools
+        .iter()
+        .any(|tool| tool["name"] == "rust_analyzer_hover" && tool["inputSchema"].is_object()));
+}
+
+#[tokio::test]
+async fn test_notifications_and_unknown_methods() {
+    let server = server();
+    let notification = json!({ "jsonrpc": "2.0", "method": "notifications/initialized" });
+    assert_eq!(handle_message(&server, &notification).await, None);
+
+    let reply = handle_message(
+        &server,
+        &json!({ "jsonrpc": "2.0", "id": 2, "method": "resources/list" }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(reply["error"]["code"], -32601);
+
+    let reply = handle_message(
+        &server,
+        &json!({ "jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": {} }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(reply["error"]["code"], -32602);
+}
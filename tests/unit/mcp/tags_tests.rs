@@ -0,0 +1,234 @@
+use serde_json::{json, Value};
+use std::path::Path;
+
+use rust_analyzer_server::{
+    mcp::{
+        handle_tool_call,
+        tags::{rust_files, tag_lines, TagSorter},
+        RustAnalyzerMCPServer,
+    },
+    protocol::{ErrorCode, ToolError},
+};
+use test_support::MockBackend;
+
+fn symbol(name: &str, kind: u32, line: u32, children: Value) -> Value {
+    let range = json!({
+        "start": { "line": line, "character": 0 },
+        "end": { "line": line, "character": 1 }
+    });
+    json!({
+        "name": name,
+        "kind": kind,
+        "range": range,
+        "selectionRange": range,
+        "children": children
+    })
+}
+
+fn symbols() -> Value {
+    json!([
+        symbol(
+            "shapes",
+            2,
+            0,
+            json!([
+                symbol("Point", 23, 1, json!([symbol("x", 8, 2, json!([]))])),
+                symbol(
+                    "impl Display for Point",
+                    19,
+                    4,
+                    json!([symbol("fmt", 6, 5, json!([]))])
+                ),
+                symbol("round", 2, 7, json!([symbol("PI", 14, 8, json!([]))]))
+            ])
+        ),
+        symbol("main", 12, 10, json!([]))
+    ])
+}
+
+#[test]
+fn test_tag_lines() {
+    assert_eq!(
+        tag_lines(&symbols(), "src/lib.rs"),
+        [
+            "shapes\tsrc/lib.rs\t1;\"\tkind:module",
+            "Point\tsrc/lib.rs\t2;\"\tkind:struct\tmodule:shapes",
+            "x\tsrc/lib.rs\t3;\"\tkind:field\tstruct:Point",
+            "fmt\tsrc/lib.rs\t6;\"\tkind:method\timplementation:Point",
+            "round\tsrc/lib.rs\t8;\"\tkind:module\tmodule:shapes",
+            "PI\tsrc/lib.rs\t9;\"\tkind:constant\tmodule:shapes::round",
+            "main\tsrc/lib.rs\t11;\"\tkind:function",
+        ]
+    );
+}
+
+#[test]
+fn test_tag_sorter_merges_runs() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("tags");
+    let mut sorter = TagSorter::new(&output, 2);
+    for name in ["delta", "alpha", "echo", "charlie", "bravo"] {
+        sorter
+            .push(format!("{}\tlib.rs\t1;\"\tkind:function", name))
+            .unwrap();
+    }
+    assert_eq!(sorter.finish().unwrap(), 5);
+
+    let tags = std::fs::read_to_string(&output).unwrap();
+    let lines: Vec<&str> = tags.lines().collect();
+    assert!(lines[..4].iter().all(|line| line.starts_with("!_TAG_")));
+    assert_eq!(
+        lines[1],
+        "!_TAG_FILE_SORTED\t1\t/0=unsorted, 1=sorted, 2=foldcase/"
+    );
+    let names: Vec<&str> = lines[4..]
+        .iter()
+        .map(|line| line.split('\t').next().unwrap())
+        .collect();
+    assert_eq!(names, ["alpha", "bravo", "charlie", "delta", "echo"]);
+
+    // Only the tags file is left.
+    let files: Vec<_> = std::fs::read_dir(dir.path()).unwrap().collect();
+    assert_eq!(files.len(), 1);
+}
+
+fn write(root: &Path, path: &str) {
+    let file = root.join(path);
+    std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+    std::fs::write(file, "fn main() {}\n").unwrap();
+}
+
+#[test]
+fn test_rust_files_skips_build_output_hidden_and_excluded() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    for path in [
+        "src/lib.rs",
+        "src/shapes/mod.rs",
+        "src/generated/bindings.rs",
+        "benches/speed.rs",
+        "target/debug/build/out.rs",
+        ".git/hooks/x.rs",
+        "README.md",
+    ] {
+        write(root, path);
+    }
+
+    let exclude = ["benches/**".to_string(), "**/generated".to_string()];
+    let files: Vec<String> = rust_files(root, &exclude)
+        .iter()
+        .map(|file| file.strip_prefix(root).unwrap().display().to_string())
+        .collect();
+    assert_eq!(files, ["src/lib.rs", "src/shapes/mod.rs"]);
+}
+
+#[tokio::test]
+async fn test_export_tags_writes_the_workspace_tags() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    write(&root, "src/lib.rs");
+    write(&root, "src/main.rs");
+    write(&root, "examples/demo.rs");
+    let lib = format!("file://{}", root.join("src/lib.rs").display());
+    let main = format!("file://{}", root.join("src/main.rs").display());
+
+    let backend = MockBackend::new()
+        .respond_for("textDocument/documentSymbol", &lib, symbols())
+        .respond_for(
+            "textDocument/documentSymbol",
+            &main,
+            json!([symbol("run", 12, 0, json!([]))]),
+        );
+    let mut server = RustAnalyzerMCPServer::with_workspace(root.clone());
+    server
+        .start_with_backend(Box::new(backend.clone()))
+        .await
+        .unwrap();
+
+    let args = json!({ "output": "out/tags", "exclude": ["examples/**"] });
+    std::fs::create_dir(root.join("out")).unwrap();
+    let result = handle_tool_call(&mut server, "rust_analyzer_export_tags", args)
+        .await
+        .unwrap();
+    let result: Value = serde_json::from_str(&result.content[0].text).unwrap();
+    assert_eq!(result["tags"], 8);
+    assert_eq!(result["files"], 2);
+    assert_eq!(backend.requests("textDocument/documentSymbol").len(), 2);
+
+    // The sources aren't under out/, so their paths are absolute.
+    let tags = std::fs::read_to_string(root.join("out/tags")).unwrap();
+    let main_path = root.join("src/main.rs").display().to_string();
+    assert!(tags.contains(&format!("run\t{}\t1;\"\tkind:function\n", main_path)));
+    let names: Vec<&str> = tags
+        .lines()
+        .filter(|line| !line.starts_with('!'))
+        .map(|line| line.split('\t').next().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        ["PI", "Point", "fmt", "main", "round", "run", "shapes", "x"]
+    );
+}
+
+#[tokio::test]
+async fn test_export_tags_paths_relative_to_the_tags_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    write(&root, "src/lib.rs");
+    let lib = format!("file://{}", root.join("src/lib.rs").display());
+
+    let backend = MockBackend::new().respond_for(
+        "textDocument/documentSymbol",
+        &lib,
+        json!([symbol("main", 12, 0, json!([]))]),
+    );
+    let mut server = RustAnalyzerMCPServer::with_workspace(root.clone());
+    server
+        .start_with_backend(Box::new(backend.clone()))
+        .await
+        .unwrap();
+
+    handle_tool_call(&mut server, "rust_analyzer_export_tags", json!({}))
+        .await
+        .unwrap();
+    let tags = std::fs::read_to_string(root.join("tags")).unwrap();
+    assert!(tags.ends_with("main\tsrc/lib.rs\t1;\"\tkind:function\n"));
+}
+
+#[tokio::test]
+async fn test_export_tags_stays_inside_the_workspace() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap().join("project");
+    write(&root, "src/lib.rs");
+    let mut server = RustAnalyzerMCPServer::with_workspace(root.clone());
+    server
+        .start_with_backend(Box::new(MockBackend::new()))
+        .await
+        .unwrap();
+
+    let outside = dir.path().join("outside");
+    std::fs::write(&outside, "keep").unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+    for output in [
+        "../escaped",
+        "/tmp/../etc/tags",
+        outside.to_str().unwrap(),
+        "link",
+        "..",
+    ] {
+        let args = json!({ "output": output });
+        let error = handle_tool_call(&mut server, "rust_analyzer_export_tags", args)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            ToolError::from_anyhow(&error).code,
+            ErrorCode::InvalidParams,
+            "{}: {}",
+            output,
+            error
+        );
+    }
+    assert!(!dir.path().join("escaped").exists());
+    assert_eq!(std::fs::read_to_string(&outside).unwrap(), "keep");
+}
@@ -0,0 +1,105 @@
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+
+use rust_analyzer_server::mcp::{
+    handle_shared_tool_call,
+    toolchain::{parse_rustc_verbose, pin_mismatch, pinned_toolchain},
+    RustAnalyzerMCPServer,
+};
+use test_support::MockBackend;
+
+const RUSTC_VERBOSE: &str = "\
+rustc 1.83.0-nightly (9e394f551 2024-09-25)
+binary: rustc
+commit-hash: 9e394f551c050ff03c6fc57f190e0761cf0be6e8
+commit-date: 2024-09-25
+host: aarch64-apple-darwin
+release: 1.83.0-nightly
+LLVM version: 19.1.0
+";
+
+#[test]
+fn test_parse_rustc_verbose() {
+    assert_eq!(
+        parse_rustc_verbose(RUSTC_VERBOSE),
+        json!({
+            "version": "rustc 1.83.0-nightly (9e394f551 2024-09-25)",
+            "binary": "rustc",
+            "commit_hash": "9e394f551c050ff03c6fc57f190e0761cf0be6e8",
+            "commit_date": "2024-09-25",
+            "host": "aarch64-apple-darwin",
+            "release": "1.83.0-nightly",
+            "llvm_version": "19.1.0",
+            "channel": "nightly"
+        })
+    );
+}
+
+#[test]
+fn test_pinned_toolchain_is_found_in_parent_directories() {
+    let dir = tempfile::tempdir().unwrap();
+    let member = dir.path().join("crates/app");
+    std::fs::create_dir_all(&member).unwrap();
+    assert_eq!(pinned_toolchain(&member).unwrap(), None);
+
+    std::fs::write(dir.path().join("rust-toolchain"), "nightly-2024-09-01\n").unwrap();
+    let pinned = pinned_toolchain(&member).unwrap().unwrap();
+    assert_eq!(pinned["channel"], "nightly-2024-09-01");
+
+    std::fs::write(
+        dir.path().join("rust-toolchain.toml"),
+        "[toolchain]\nchannel = \"1.80\"\ncomponents = [\"rust-src\"]\n",
+    )
+    .unwrap();
+    let pinned = pinned_toolchain(&member).unwrap().unwrap();
+    assert_eq!(pinned["channel"], "1.80");
+    assert_eq!(pinned["components"], json!(["rust-src"]));
+    assert!(pinned["file"]
+        .as_str()
+        .unwrap()
+        .ends_with("rust-toolchain.toml"));
+}
+
+#[test]
+fn test_pin_mismatch_compares_channel_and_release() {
+    let rustc = parse_rustc_verbose(RUSTC_VERBOSE);
+    let pin = |channel: &str| json!({ "channel": channel });
+
+    assert_eq!(pin_mismatch(&pin("nightly-2024-09-25"), &rustc), None);
+    assert!(pin_mismatch(&pin("stable"), &rustc)
+        .unwrap()
+        .contains("pins stable but rustc there is 1.83.0-nightly (nightly)"));
+
+    let stable = parse_rustc_verbose("rustc 1.80.1 (3f5fd8dd4 2024-08-06)\nrelease: 1.80.1\n");
+    assert_eq!(pin_mismatch(&pin("1.80"), &stable), None);
+    assert_eq!(pin_mismatch(&pin("1.80.1"), &stable), None);
+    assert!(pin_mismatch(&pin("1.79"), &stable).is_some());
+    // Custom toolchain names can't be compared.
+    assert_eq!(pin_mismatch(&pin("my-toolchain"), &stable), None);
+}
+
+#[tokio::test]
+async fn test_toolchain_reports_rustc_and_rust_analyzer() {
+    let dir = tempfile::tempdir().unwrap();
+    let backend = MockBackend::new().respond(
+        "initialize",
+        json!({
+            "capabilities": {},
+            "serverInfo": { "name": "rust-analyzer", "version": "0.3.2146-standalone" }
+        }),
+    );
+    let mut server = RustAnalyzerMCPServer::with_workspace(dir.path().to_path_buf());
+    server.start_with_backend(Box::new(backend)).await.unwrap();
+    let server = Mutex::new(server);
+
+    let result = handle_shared_tool_call(&server, "rust_analyzer_toolchain", json!({}))
+        .await
+        .unwrap();
+    let report: Value = serde_json::from_str(&result.content[0].text).unwrap();
+    assert_eq!(report["rust_analyzer"]["version"], "0.3.2146-standalone");
+    assert!(report["rustc"]["release"].is_string(), "{}", report);
+    assert_eq!(report["host"], report["rustc"]["host"]);
+    assert!(report["rustc"]["sysroot"].is_string());
+    assert!(report["pinned_toolchain"].is_null());
+    assert!(report["env"].as_object().unwrap().contains_key("RUSTFLAGS"));
+}
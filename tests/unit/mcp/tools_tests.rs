@@ -0,0 +1,91 @@
+use serde_json::json;
+
+use rust_analyzer_server::mcp::{
+    handle_tool_call,
+    tools::{get_tools, resolve_tool_name, suggest_tool_name, TOOL_ALIASES},
+    RustAnalyzerMCPServer,
+};
+use test_support::MockBackend;
+
+#[test]
+fn test_aliases_resolve_to_their_tools() {
+    assert_eq!(resolve_tool_name("refs"), "rust_analyzer_references");
+    assert_eq!(
+        resolve_tool_name("wdiag"),
+        "rust_analyzer_workspace_diagnostics"
+    );
+    assert_eq!(resolve_tool_name("hover"), "rust_analyzer_hover");
+    assert_eq!(
+        resolve_tool_name("rust_analyzer_hover"),
+        "rust_analyzer_hover"
+    );
+}
+
+#[test]
+fn test_aliases_are_unique_and_listed_with_their_tools() {
+    let tools = get_tools();
+    for (alias, tool) in TOOL_ALIASES {
+        assert_eq!(
+            TOOL_ALIASES
+                .iter()
+                .filter(|(other, _)| other == alias)
+                .count(),
+            1,
+            "{} is an alias twice",
+            alias
+        );
+        assert!(
+            tools
+                .iter()
+                .all(|listed| listed.name != format!("rust_analyzer_{}", alias)),
+            "{} is also a tool's own name",
+            alias
+        );
+        if let Some(listed) = tools.iter().find(|listed| listed.name == *tool) {
+            assert!(listed.aliases.iter().any(|listed| listed == alias));
+        }
+    }
+    let references = tools
+        .iter()
+        .find(|tool| tool.name == "rust_analyzer_references")
+        .unwrap();
+    assert_eq!(references.aliases, ["references", "refs"]);
+    let listed = serde_json::to_value(references).unwrap();
+    assert_eq!(listed["aliases"], json!(["references", "refs"]));
+}
+
+#[test]
+fn test_unknown_names_suggest_the_closest() {
+    assert_eq!(suggest_tool_name("hovr").as_deref(), Some("hover"));
+    assert_eq!(
+        suggest_tool_name("rust_analyzer_refrences").as_deref(),
+        Some("references")
+    );
+    assert_eq!(suggest_tool_name("wdaig").as_deref(), Some("wdiag"));
+    assert_eq!(suggest_tool_name("frobnicate"), None);
+}
+
+#[tokio::test]
+async fn test_tool_call_by_alias() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let backend = MockBackend::new();
+    let mut server = RustAnalyzerMCPServer::with_workspace(root.clone());
+    server
+        .start_with_backend(Box::new(backend.clone()))
+        .await
+        .unwrap();
+
+    let result = handle_tool_call(&mut server, "get_workspace", json!({}))
+        .await
+        .unwrap();
+    assert!(result.content[0].text.contains(&*root.to_string_lossy()));
+
+    let error = handle_tool_call(&mut server, "get_worksapce", json!({}))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Unknown tool: get_worksapce; did you mean get_workspace?"
+    );
+}
@@ -0,0 +1,125 @@
+use serde_json::{json, Value};
+use std::path::Path;
+
+use rust_analyzer_server::mcp::handle_tool_call;
+use test_support::{server_with_backend, MockBackend};
+
+fn range(line: u32, character: u32, end_line: u32, end_character: u32) -> Value {
+    json!({
+        "start": { "line": line, "character": character },
+        "end": { "line": end_line, "character": end_character }
+    })
+}
+
+fn trait_symbol(container: &str, uri: &str, line: u32) -> Value {
+    json!({
+        "name": "Handler",
+        "kind": 11,
+        "containerName": container,
+        "location": { "uri": uri, "range": range(line, 10, line, 17) }
+    })
+}
+
+#[tokio::test]
+async fn test_find_implementations_by_trait_name() {
+    let backend = MockBackend::new()
+        .respond(
+            "workspace/symbol",
+            json!([
+                trait_symbol("server", "file:///ws/src/server.rs", 3),
+                // Fuzzy matches that aren't the trait.
+                { "name": "HandlerError", "kind": 10, "location": { "uri": "file:///ws/src/server.rs", "range": range(9, 9, 9, 21) } },
+                { "name": "Handler", "kind": 23, "location": { "uri": "file:///ws/src/mock.rs", "range": range(0, 11, 0, 18) } }
+            ]),
+        )
+        .respond(
+            "textDocument/implementation",
+            json!([
+                { "targetUri": "file:///ws/src/routes.rs", "targetRange": range(4, 0, 8, 1), "targetSelectionRange": range(4, 25, 4, 30) },
+                { "targetUri": "file:///ws/src/routes.rs", "targetRange": range(10, 0, 14, 1), "targetSelectionRange": range(10, 28, 10, 36) }
+            ]),
+        )
+        .respond_for(
+            "textDocument/documentSymbol",
+            "file:///ws/src/routes.rs",
+            json!([
+                { "name": "Index", "kind": 23, "location": { "uri": "file:///ws/src/routes.rs", "range": range(0, 0, 2, 1) } },
+                { "name": "impl Handler for Index", "kind": 19, "location": { "uri": "file:///ws/src/routes.rs", "range": range(4, 0, 8, 1) } },
+                { "name": "handle", "kind": 12, "location": { "uri": "file:///ws/src/routes.rs", "range": range(5, 4, 7, 5) } },
+                { "name": "impl<T> Handler for Wrapper<T>", "kind": 19, "location": { "uri": "file:///ws/src/routes.rs", "range": range(10, 0, 14, 1) } }
+            ]),
+        );
+    let mut server = server_with_backend(Path::new("/ws"), &backend).await;
+
+    let result = handle_tool_call(
+        &mut server,
+        "rust_analyzer_find_implementations",
+        json!({ "trait_name": "Handler" }),
+    )
+    .await
+    .unwrap();
+    let result: Value = serde_json::from_str(&result.content[0].text).unwrap();
+    assert_eq!(
+        result,
+        json!({
+            "trait": { "name": "Handler", "container": "server", "location": "/ws/src/server.rs:3:10" },
+            "count": 2,
+            "implementations": [
+                { "type": "Index", "location": "/ws/src/routes.rs:4:25" },
+                { "type": "Wrapper<T>", "location": "/ws/src/routes.rs:10:28" }
+            ]
+        })
+    );
+    let requested = &backend.requests("textDocument/implementation")[0];
+    assert_eq!(requested["position"], json!({ "line": 3, "character": 10 }));
+    // Both implementations are in one file, asked for once.
+    assert_eq!(backend.requests("textDocument/documentSymbol").len(), 1);
+}
+
+#[tokio::test]
+async fn test_find_implementations_asks_to_disambiguate() {
+    let backend = MockBackend::new().respond(
+        "workspace/symbol",
+        json!([
+            trait_symbol("server", "file:///ws/src/server.rs", 3),
+            trait_symbol("client::hooks", "file:///ws/src/client/hooks.rs", 7)
+        ]),
+    );
+    let mut server = server_with_backend(Path::new("/ws"), &backend).await;
+
+    let error = handle_tool_call(
+        &mut server,
+        "rust_analyzer_find_implementations",
+        json!({ "trait_name": "Handler" }),
+    )
+    .await
+    .unwrap_err()
+    .to_string();
+    assert!(error.contains("2 traits are named Handler"), "{}", error);
+    assert!(
+        error.contains("server::Handler (/ws/src/server.rs:3:10)"),
+        "{}",
+        error
+    );
+    assert!(error.contains("client::hooks::Handler"), "{}", error);
+
+    let result = handle_tool_call(
+        &mut server,
+        "rust_analyzer_find_implementations",
+        json!({ "trait_name": "Handler", "path": "hooks::Handler" }),
+    )
+    .await
+    .unwrap();
+    let result: Value = serde_json::from_str(&result.content[0].text).unwrap();
+    assert_eq!(result["trait"]["container"], "client::hooks");
+    assert_eq!(result["count"], 0);
+
+    let error = handle_tool_call(
+        &mut server,
+        "rust_analyzer_find_implementations",
+        json!({ "trait_name": "Handler", "path": "nowhere" }),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(error.to_string(), "No trait named Handler matching nowhere");
+}
@@ -0,0 +1,106 @@
+use serde_json::{json, Value};
+
+use rust_analyzer_server::mcp::truncate::{limit_output, Cursor};
+
+fn parse(text: &str) -> Value {
+    serde_json::from_str(text).unwrap()
+}
+
+#[test]
+fn test_output_within_budget_is_unchanged() {
+    let text =
+        serde_json::to_string_pretty(&json!([{ "location": "/ws/src/lib.rs:0:7" }])).unwrap();
+    assert_eq!(limit_output(&text, 1000, None, true).unwrap(), text);
+}
+
+#[test]
+fn test_array_is_paged_with_a_cursor() {
+    let items: Vec<Value> = (0..100)
+        .map(|i| json!({ "label": format!("item_{:03}", i) }))
+        .collect();
+    let text = Value::Array(items).to_string();
+
+    let mut seen = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = parse(&limit_output(&text, 300, cursor.as_ref(), false).unwrap());
+        assert!(page.to_string().len() <= 300, "{}", page);
+        seen.extend(page["items"].as_array().unwrap().iter().cloned());
+        assert_eq!(page["omitted_items"], json!(100 - seen.len()));
+        if page["truncated"] == json!(false) {
+            assert!(page.get("cursor").is_none());
+            break;
+        }
+        cursor = Some(Cursor::parse(page["cursor"].as_str().unwrap()).unwrap());
+    }
+    assert_eq!(Value::Array(seen), parse(&text));
+}
+
+#[test]
+fn test_object_largest_field_is_paged() {
+    let text = json!({
+        "isIncomplete": false,
+        "items": (0..50).map(|i| json!({ "label": format!("item_{}", i) })).collect::<Vec<_>>()
+    })
+    .to_string();
+
+    let page = parse(&limit_output(&text, 200, None, false).unwrap());
+    assert_eq!(page["isIncomplete"], false);
+    assert_eq!(page["truncated"], true);
+    let kept = page["items"].as_array().unwrap().len();
+    assert_eq!(page["omitted_items"], json!(50 - kept));
+    assert_eq!(page["cursor"], json!(format!("items:{}", kept)));
+}
+
+#[test]
+fn test_per_file_object_is_paged_by_entry() {
+    let text = json!({
+        "file:///ws/a.rs": ["unused import"],
+        "file:///ws/b.rs": ["unused variable"],
+        "file:///ws/c.rs": ["dead code"]
+    })
+    .to_string();
+    let wrapped = json!({ "diagnostics": parse(&text) }).to_string();
+
+    let cursor = Cursor::parse("diagnostics:1").unwrap();
+    let page = parse(&limit_output(&wrapped, 90, Some(&cursor), false).unwrap());
+    assert_eq!(
+        page["diagnostics"],
+        json!({ "file:///ws/b.rs": ["unused variable"] })
+    );
+    assert_eq!(page["cursor"], "diagnostics:2");
+}
+
+#[test]
+fn test_oversized_item_is_still_returned() {
+    let text = json!([{ "doc": "x".repeat(500) }, { "doc": "y" }]).to_string();
+    let page = parse(&limit_output(&text, 100, None, false).unwrap());
+    assert_eq!(page["items"].as_array().unwrap().len(), 1);
+    assert_eq!(page["cursor"], "1");
+}
+
+#[test]
+fn test_plain_text_is_cut_at_a_char_boundary() {
+    let text = "é".repeat(100);
+    let page = parse(&limit_output(&text, 51, None, false).unwrap());
+    assert_eq!(page["text"], "é".repeat(25));
+    assert_eq!(page["truncated"], true);
+    assert_eq!(page["omitted_bytes"], 150);
+}
+
+#[test]
+fn test_bad_cursors_are_rejected() {
+    assert!(Cursor::parse("items:next").is_err());
+    assert_eq!(
+        Cursor::parse("file:///ws/a.rs:3").unwrap(),
+        Cursor {
+            field: Some("file:///ws/a.rs".to_string()),
+            offset: 3
+        }
+    );
+
+    let text = json!({ "items": [1, 2, 3] }).to_string();
+    let cursor = Cursor::parse("symbols:1").unwrap();
+    assert!(limit_output(&text, 1000, Some(&cursor), false).is_err());
+    assert!(limit_output("No hover information", 1000, Some(&cursor), false).is_err());
+}
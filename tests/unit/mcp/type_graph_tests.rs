@@ -0,0 +1,288 @@
+use serde_json::{json, Value};
+use std::path::Path;
+
+use rust_analyzer_server::mcp::{
+    handle_tool_call,
+    type_graph::{impl_header, supertrait_bounds, ImplHeader},
+    RustAnalyzerMCPServer,
+};
+use test_support::MockBackend;
+
+#[test]
+fn test_supertrait_bounds() {
+    let text =
+        "pub trait Shape<T>: Named + std::fmt::Debug + ?Sized + 'static + Into<T> where T: Copy {}";
+    assert_eq!(supertrait_bounds(text, 0, 10), [(0, 20), (0, 38), (0, 65)]);
+    assert!(supertrait_bounds("trait Named {}", 0, 6).is_empty());
+    assert_eq!(
+        supertrait_bounds("trait Handler:\n    Fn(u32) -> u32\n    + Send\n{", 0, 6),
+        [(1, 4), (2, 6)]
+    );
+}
+
+#[test]
+fn test_impl_header() {
+    assert_eq!(
+        impl_header("impl<T: Clone> geometry::Shape<T> for Vec<T> {}", 0, 0),
+        Some(ImplHeader {
+            trait_name: Some((0, 25)),
+            self_type: Some((0, 38)),
+        })
+    );
+    // From the type rust-analyzer points at, a line below `impl`.
+    assert_eq!(
+        impl_header("unsafe impl Send\n    for &'a mut Square\n{", 1, 15),
+        Some(ImplHeader {
+            trait_name: Some((0, 12)),
+            self_type: Some((1, 16)),
+        })
+    );
+    assert_eq!(
+        impl_header("impl Square {", 0, 5),
+        Some(ImplHeader {
+            trait_name: None,
+            self_type: Some((0, 5)),
+        })
+    );
+    assert_eq!(
+        impl_header("impl !Sync for Square {}", 0, 0),
+        Some(ImplHeader {
+            trait_name: None,
+            self_type: Some((0, 15)),
+        })
+    );
+    assert_eq!(impl_header("fn main() {}", 0, 0), None);
+}
+
+fn write(root: &Path, path: &str, content: &str) -> String {
+    let file = root.join(path);
+    std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+    std::fs::write(&file, content).unwrap();
+    format!("file://{}", file.display())
+}
+
+fn location(uri: &str, line: u32, character: u32) -> Value {
+    let position = json!({ "line": line, "character": character });
+    json!({ "uri": uri, "range": { "start": position, "end": position } })
+}
+
+/// A workspace of two members: `geometry`, with the traits `Named` and
+/// `Shape: Named` implemented for `Square`, and `app`, implementing
+/// `Shape` for `Circle`. Each name used is on a line of its own, since the
+/// mock answers definitions by line. Returns the URIs of their sources.
+fn workspace(root: &Path) -> (String, String) {
+    write(
+        root,
+        "Cargo.toml",
+        "[workspace]\nmembers = [\"geometry\", \"app\"]\nresolver = \"2\"\n",
+    );
+    write(
+        root,
+        "geometry/Cargo.toml",
+        "[package]\nname = \"geometry\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+    );
+    let lib = write(
+        root,
+        "geometry/src/lib.rs",
+        "pub trait Named {}\npub trait Shape:\n    Named\n{\n    fn area(&self) -> u32;\n}\npub struct Square;\nimpl Shape\n    for Square\n{\n    fn area(&self) -> u32 { 4 }\n}\nimpl Named for Square {}\n",
+    );
+    write(
+        root,
+        "app/Cargo.toml",
+        "[package]\nname = \"app\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\ngeometry = { path = \"../geometry\" }\n",
+    );
+    let main = write(
+        root,
+        "app/src/main.rs",
+        "struct Circle;\nimpl geometry::Shape\n    for Circle {}\n",
+    );
+    (lib, main)
+}
+
+/// Definitions and implementations of the names in [`workspace`].
+fn backend(lib: &str, main: &str) -> MockBackend {
+    let definition = "textDocument/definition";
+    let implementation = "textDocument/implementation";
+    MockBackend::new()
+        .respond_at(definition, lib, 1, json!([location(lib, 1, 10)]))
+        .respond_at(definition, lib, 2, json!([location(lib, 0, 10)]))
+        .respond_at(definition, lib, 6, json!([location(lib, 6, 11)]))
+        .respond_at(definition, lib, 7, json!([location(lib, 1, 10)]))
+        .respond_at(definition, lib, 8, json!([location(lib, 6, 11)]))
+        .respond_at(definition, lib, 12, json!([location(lib, 0, 10)]))
+        .respond_at(definition, main, 1, json!([location(lib, 1, 10)]))
+        .respond_at(definition, main, 2, json!([location(main, 0, 7)]))
+        .respond_at(
+            implementation,
+            lib,
+            1,
+            json!([location(lib, 7, 0), location(main, 1, 0)]),
+        )
+        .respond_at(
+            implementation,
+            lib,
+            6,
+            json!([location(lib, 7, 0), location(lib, 12, 0)]),
+        )
+}
+
+async fn type_graph(root: &Path, backend: &MockBackend, args: Value) -> String {
+    let mut server = RustAnalyzerMCPServer::with_workspace(root.to_path_buf());
+    server
+        .start_with_backend(Box::new(backend.clone()))
+        .await
+        .unwrap();
+    let result = handle_tool_call(&mut server, "rust_analyzer_type_graph", args)
+        .await
+        .unwrap();
+    result.content[0].text.clone()
+}
+
+#[tokio::test]
+async fn test_type_graph_of_a_trait_as_dot() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let (lib, main) = workspace(&root);
+    let backend = backend(&lib, &main);
+
+    let args = json!({
+        "file_path": root.join("geometry/src/lib.rs").to_string_lossy(),
+        "line": 1,
+        "character": 10
+    });
+    let dot = type_graph(&root, &backend, args).await;
+    assert_eq!(
+        dot,
+        [
+            "digraph types {",
+            "  rankdir=LR;",
+            "  node [shape=box, fontname=\"monospace\"];",
+            "  subgraph cluster_0 {",
+            "    label=\"app\";",
+            "    n3 [label=\"struct Circle\\napp/src/main.rs:0\"];",
+            "  }",
+            "  subgraph cluster_1 {",
+            "    label=\"geometry\";",
+            "    n0 [label=\"trait Shape\\ngeometry/src/lib.rs:1\", style=bold];",
+            "    n1 [label=\"trait Named\\ngeometry/src/lib.rs:0\"];",
+            "    n2 [label=\"struct Square\\ngeometry/src/lib.rs:6\"];",
+            "  }",
+            "  n0 -> n1 [label=\"supertrait\"];",
+            "  n2 -> n0 [label=\"implements\"];",
+            "  n3 -> n0 [label=\"implements\"];",
+            "}",
+        ]
+        .join("\n")
+    );
+}
+
+#[tokio::test]
+async fn test_type_graph_of_a_type_as_json() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let (lib, main) = workspace(&root);
+    let backend = backend(&lib, &main);
+
+    let args = json!({
+        "file_path": root.join("geometry/src/lib.rs").to_string_lossy(),
+        "line": 6,
+        "character": 11,
+        "direction": "supertypes",
+        "format": "json"
+    });
+    let graph: Value = serde_json::from_str(&type_graph(&root, &backend, args).await).unwrap();
+    let node = |id: &str, name: &str, kind: &str, line: u32| {
+        json!({
+            "id": id,
+            "name": name,
+            "kind": kind,
+            "location": format!("geometry/src/lib.rs:{}", line),
+            "crate": "geometry"
+        })
+    };
+    let edge = |from: &str, to: &str, relation: &str| json!({ "from": from, "to": to, "relation": relation });
+    assert_eq!(
+        graph,
+        json!({
+            "root": "n0",
+            "nodes": [
+                node("n0", "Square", "struct", 6),
+                node("n1", "Shape", "trait", 1),
+                node("n2", "Named", "trait", 0)
+            ],
+            "edges": [
+                edge("n0", "n1", "implements"),
+                edge("n0", "n2", "implements"),
+                edge("n1", "n2", "supertrait")
+            ]
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_type_graph_uses_type_hierarchy_when_answered() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let (lib, main) = workspace(&root);
+    let item = |name: &str, kind: u32, line: u32, character: u32| {
+        let range = location(&lib, line, character)["range"].clone();
+        json!({ "name": name, "kind": kind, "uri": lib, "range": range, "selectionRange": range })
+    };
+    let backend = backend(&lib, &main)
+        .respond(
+            "textDocument/prepareTypeHierarchy",
+            json!([item("Square", 23, 6, 11)]),
+        )
+        .respond(
+            "typeHierarchy/supertypes",
+            json!([item("Shape", 11, 1, 10)]),
+        );
+
+    let args = json!({
+        "file_path": root.join("geometry/src/lib.rs").to_string_lossy(),
+        "line": 6,
+        "character": 11,
+        "direction": "supertypes",
+        "depth": 1,
+        "format": "json"
+    });
+    let graph: Value = serde_json::from_str(&type_graph(&root, &backend, args).await).unwrap();
+    assert_eq!(
+        graph["edges"],
+        json!([{ "from": "n0", "to": "n1", "relation": "implements" }])
+    );
+    assert_eq!(graph["nodes"][1]["kind"], "trait");
+    assert_eq!(
+        backend.requests("typeHierarchy/supertypes"),
+        [json!({ "item": item("Square", 23, 6, 11) })]
+    );
+    assert!(backend.requests("textDocument/implementation").is_empty());
+}
+
+#[tokio::test]
+async fn test_type_graph_needs_a_trait_or_type() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let (lib, main) = workspace(&root);
+    let backend = backend(&lib, &main).respond_at(
+        "textDocument/definition",
+        &lib,
+        4,
+        json!([location(&lib, 4, 7)]),
+    );
+    let mut server = RustAnalyzerMCPServer::with_workspace(root.clone());
+    server
+        .start_with_backend(Box::new(backend.clone()))
+        .await
+        .unwrap();
+
+    let args = json!({
+        "file_path": root.join("geometry/src/lib.rs").to_string_lossy(),
+        "line": 4,
+        "character": 7
+    });
+    let error = handle_tool_call(&mut server, "rust_analyzer_type_graph", args)
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("No trait or type at 4:7"));
+}
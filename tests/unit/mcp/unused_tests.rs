@@ -0,0 +1,151 @@
+use serde_json::{json, Value};
+use std::path::Path;
+
+use rust_analyzer_server::mcp::{handle_tool_call, RustAnalyzerMCPServer};
+use test_support::MockBackend;
+
+fn write(root: &Path, path: &str, content: &str) -> String {
+    let file = root.join(path);
+    std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+    std::fs::write(&file, content).unwrap();
+    format!("file://{}", file.display())
+}
+
+fn at(line: u32, character: u32) -> Value {
+    json!({
+        "start": { "line": line, "character": character },
+        "end": { "line": line, "character": character + 3 }
+    })
+}
+
+fn diagnostic(code: &str, severity: u32, line: u32, character: u32, message: &str) -> Value {
+    json!({ "code": code, "severity": severity, "range": at(line, character), "message": message })
+}
+
+async fn unused(server: &mut RustAnalyzerMCPServer, args: Value) -> Value {
+    let result = handle_tool_call(server, "rust_analyzer_unused", args)
+        .await
+        .unwrap();
+    serde_json::from_str(&result.content[0].text).unwrap()
+}
+
+#[tokio::test]
+async fn test_unused_ranks_items_and_confirms_dead_code() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let lib = write(
+        &root,
+        "src/lib.rs",
+        "use std::fmt;\n\nfn orphan() {}\n\nstruct Point {\n    a: i32,\n    b: i32,\n}\n\n#[cfg(test)]\nmod tests {\n    fn uses() { super::orphan(); }\n}\n",
+    );
+    let util = write(&root, "src/util.rs", "pub(crate) fn helper() {}\n");
+    let main = write(&root, "src/main.rs", "fn main() { helper(); }\n");
+
+    let mut hint = diagnostic("dead_code", 4, 4, 7, "fields in this struct");
+    hint["relatedInformation"] = json!([{
+        "location": { "uri": lib, "range": at(5, 4) },
+        "message": "fields `a` and `b` are never read"
+    }]);
+    let diagnostics = json!({
+        lib.clone(): [
+            diagnostic("unused_imports", 2, 0, 4, "unused import: `std::fmt`"),
+            diagnostic("dead_code", 2, 2, 3, "function `orphan` is never used"),
+            diagnostic("dead_code", 2, 5, 4, "fields `a` and `b` are never read"),
+            diagnostic("dead_code", 2, 6, 4, "fields `a` and `b` are never read"),
+            hint,
+            diagnostic("clippy::needless_return", 2, 8, 0, "unneeded `return` statement")
+        ],
+        util.clone(): [
+            diagnostic("dead_code", 2, 0, 14, "function `helper` is never used")
+        ]
+    });
+    let backend = MockBackend::new()
+        .respond("workspace/diagnostic", diagnostics)
+        // `orphan` and the fields are only used by the test module.
+        .respond(
+            "textDocument/references",
+            json!([{ "uri": lib, "range": at(11, 23) }]),
+        )
+        // `helper` is used from the binary, which the lint can't see.
+        .respond_for(
+            "textDocument/references",
+            &util,
+            json!([{ "uri": main, "range": at(0, 12) }]),
+        );
+    let mut server = RustAnalyzerMCPServer::with_workspace(root.clone());
+    server
+        .start_with_backend(Box::new(backend.clone()))
+        .await
+        .unwrap();
+
+    let lib_path = root.join("src/lib.rs").display().to_string();
+    let util_path = root.join("src/util.rs").display().to_string();
+    let result = unused(&mut server, json!({})).await;
+    assert_eq!(result["confirmed"], false);
+    assert_eq!(
+        result["summary"],
+        json!({
+            "items": 4,
+            "diagnostics": 6,
+            "files": 2,
+            "by_code": { "dead_code": 5, "unused_imports": 1 }
+        })
+    );
+    assert_eq!(
+        result["files"],
+        json!([
+            { "file": lib_path, "items": 3, "clears": 5 },
+            { "file": util_path, "items": 1, "clears": 1 }
+        ])
+    );
+    let items = result["items"].as_array().unwrap();
+    assert_eq!(
+        items[0],
+        json!({
+            "item": "a, b",
+            "code": "dead_code",
+            "location": format!("{}:5:4", lib_path),
+            "clears": 3,
+            "confidence": "low",
+            "message": "fields `a` and `b` are never read"
+        })
+    );
+    let names: Vec<&str> = items.iter().map(|i| i["item"].as_str().unwrap()).collect();
+    assert_eq!(names, ["a, b", "std::fmt", "orphan", "helper"]);
+    assert_eq!(items[1]["confidence"], "high");
+    assert!(backend.requests("textDocument/references").is_empty());
+
+    let confirmed = unused(&mut server, json!({ "min_confidence": "high" })).await;
+    assert_eq!(confirmed["confirmed"], true);
+    let names: Vec<&str> = confirmed["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| i["item"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["a, b", "std::fmt", "orphan"]);
+    assert_eq!(confirmed["items"][2]["used_in_tests"], 1);
+    assert_eq!(confirmed["items"][2]["confidence"], "high");
+    assert_eq!(backend.requests("textDocument/references").len(), 3);
+
+    let main_path = root.join("src/main.rs").display().to_string();
+    assert_eq!(
+        confirmed["used_elsewhere"],
+        json!([{
+            "item": "helper",
+            "location": format!("{}:0:14", util_path),
+            "used_at": [format!("{}:0:12", main_path)]
+        }])
+    );
+
+    let error = handle_tool_call(
+        &mut server,
+        "rust_analyzer_unused",
+        json!({ "min_confidence": "certain" }),
+    )
+    .await
+    .unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("Unknown min_confidence 'certain'"));
+}
@@ -0,0 +1,121 @@
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use rust_analyzer_server::mcp::{handle_tool_call, RustAnalyzerMCPServer};
+use test_support::MockBackend;
+
+fn reference(uri: &str, line: u32) -> Value {
+    let position = json!({ "line": line, "character": 4 });
+    json!({ "uri": uri, "range": { "start": position, "end": position } })
+}
+
+/// Flat symbols for `content`, one item per line named by its second word.
+fn line_symbols(uri: &str, content: &str) -> Value {
+    json!(content
+        .lines()
+        .enumerate()
+        .map(|(line, text)| {
+            let name = text.split_whitespace().nth(2).unwrap_or_default();
+            let name = name.trim_end_matches("()");
+            let end = json!({ "line": line, "character": text.len() });
+            json!({
+                "name": name,
+                "kind": 12,
+                "location": {
+                    "uri": uri,
+                    "range": { "start": { "line": line, "character": 0 }, "end": end }
+                }
+            })
+        })
+        .collect::<Vec<_>>())
+}
+
+#[tokio::test]
+async fn test_usage_stats_counts_references_per_public_item() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    let mut content = "pub fn popular() {}\npub fn local_only() {}\nfn private() {}\n".to_string();
+    for index in 0..10 {
+        content.push_str(&format!("pub fn spare{}() {{}}\n", index));
+    }
+    let file = root.join("api.rs");
+    std::fs::write(&file, &content).unwrap();
+    let uri = format!("file://{}", file.display());
+    let other = format!("file://{}", root.join("main.rs").display());
+
+    let backend = MockBackend::new()
+        .respond(
+            "textDocument/documentSymbol",
+            line_symbols(&uri, &content.replace("fn private", "fn x private")),
+        )
+        .respond("textDocument/references", json!([]))
+        .respond_at(
+            "textDocument/references",
+            &uri,
+            0,
+            json!([
+                reference(&uri, 5),
+                reference(&other, 1),
+                reference(&other, 2)
+            ]),
+        )
+        .respond_at(
+            "textDocument/references",
+            &uri,
+            1,
+            json!([reference(&uri, 7)]),
+        )
+        .delay("textDocument/references", Duration::from_millis(20));
+    let mut server = RustAnalyzerMCPServer::with_workspace(root.clone());
+    server
+        .start_with_backend(Box::new(backend.clone()))
+        .await
+        .unwrap();
+
+    let args = json!({ "file_path": file.to_string_lossy(), "max_symbols": 10 });
+    let result = handle_tool_call(&mut server, "rust_analyzer_usage_stats", args)
+        .await
+        .unwrap();
+    let result: Value = serde_json::from_str(&result.content[0].text).unwrap();
+    assert_eq!(result["public_items"], 12);
+    assert_eq!(result["analyzed"], 10);
+    assert_eq!(result["truncated"], true);
+
+    let symbols = result["symbols"].as_array().unwrap();
+    assert_eq!(
+        symbols[9],
+        json!({
+            "symbol": "popular",
+            "kind": "function",
+            "location": format!("{}:0:7", file.display()),
+            "references": 3,
+            "files": 2,
+            "local": false
+        })
+    );
+    assert_eq!(symbols[8]["symbol"], "local_only");
+    assert_eq!(
+        (&symbols[8]["references"], &symbols[8]["local"]),
+        (&json!(1), &json!(true))
+    );
+    assert_eq!(symbols[0]["references"], 0);
+    assert!(symbols.iter().all(|symbol| symbol["symbol"] != "private"));
+
+    assert_eq!(backend.requests("textDocument/references").len(), 10);
+    let most = backend.max_in_flight();
+    assert!(
+        most > 1 && most <= 8,
+        "{} references requests at once",
+        most
+    );
+
+    let args = json!({ "file_path": file.to_string_lossy(), "symbols": ["local_only"] });
+    let result = handle_tool_call(&mut server, "rust_analyzer_usage_stats", args)
+        .await
+        .unwrap();
+    let result: Value = serde_json::from_str(&result.content[0].text).unwrap();
+    assert_eq!(
+        (&result["analyzed"], &result["truncated"]),
+        (&json!(1), &json!(false))
+    );
+}
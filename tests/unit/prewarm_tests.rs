@@ -0,0 +1,115 @@
+use serde_json::json;
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+use rust_analyzer_server::{
+    config::Config,
+    mcp::cargo_metadata::SharedMetadataCache,
+    prewarm::{hot_files, prewarm, spawn, SharedPrewarmStatus},
+    RustAnalyzerMCPServer,
+};
+use test_support::MockBackend;
+
+fn fixture() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("test-project")
+        .canonicalize()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_hot_files_default_to_member_roots() {
+    let workspace = fixture();
+    let cache = SharedMetadataCache::default();
+    assert_eq!(
+        hot_files(&workspace, &[], &cache).await,
+        [workspace.join("src/lib.rs"), workspace.join("src/main.rs")]
+    );
+    assert_eq!(
+        hot_files(
+            &workspace,
+            &[
+                PathBuf::from("src/utils.rs"),
+                PathBuf::from("src/missing.rs")
+            ],
+            &cache
+        )
+        .await,
+        [workspace.join("src/utils.rs")]
+    );
+}
+
+#[tokio::test]
+async fn test_prewarm_queries_symbols_and_hot_files() {
+    let backend = MockBackend::new().respond("workspace/symbol", json!([]));
+    let config = Config {
+        prewarm_files: vec![PathBuf::from("src/lib.rs")],
+        ..Config::default()
+    };
+    let mut server = RustAnalyzerMCPServer::with_config(fixture(), config);
+    server
+        .start_with_backend(Box::new(backend.clone()))
+        .await
+        .unwrap();
+    let server = Mutex::new(server);
+    let status = SharedPrewarmStatus::default();
+
+    prewarm(&server, &status).await;
+
+    let status = status.lock().unwrap().clone();
+    assert_eq!(status.runs, 1);
+    assert!(!status.running);
+    assert_eq!(status.files, [fixture().join("src/lib.rs")]);
+    assert_eq!(status.last_error, None);
+    assert_eq!(backend.requests("workspace/symbol").len(), 1);
+    assert_eq!(backend.notifications("textDocument/didOpen").len(), 1);
+    let symbols = backend.requests("textDocument/documentSymbol");
+    assert_eq!(
+        symbols[0]["textDocument"]["uri"],
+        format!("file://{}", fixture().join("src/lib.rs").display())
+    );
+}
+
+#[tokio::test]
+async fn test_prewarm_runs_when_indexing_ends_not_after_cargo_check() {
+    let backend = MockBackend::new().respond("workspace/symbol", json!([]));
+    let config = Config {
+        prewarm_files: vec![PathBuf::from("src/lib.rs")],
+        retry_interval_millis: 10,
+        ..Config::default()
+    };
+    let mut server = RustAnalyzerMCPServer::with_config(fixture(), config);
+    server
+        .start_with_backend(Box::new(backend.clone()))
+        .await
+        .unwrap();
+    let progress = server.progress_handle().unwrap();
+    let status = spawn(Arc::new(Mutex::new(server)));
+    let runs = || status.lock().unwrap().runs;
+
+    let flycheck = "rust-analyzer/flycheck/0".to_string();
+    progress
+        .lock()
+        .await
+        .begin(flycheck.clone(), "cargo check".to_string(), None, None);
+    progress.lock().await.end(&flycheck);
+    // Past the quiet period, with nothing to warm after.
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    assert_eq!(runs(), 0);
+
+    let indexing = "rustAnalyzer/Indexing".to_string();
+    progress
+        .lock()
+        .await
+        .begin(indexing.clone(), "Indexing".to_string(), None, None);
+    progress.lock().await.end(&indexing);
+    let start = Instant::now();
+    while runs() == 0 && start.elapsed() < Duration::from_secs(10) {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(runs(), 1);
+}
@@ -0,0 +1,142 @@
+use anyhow::Context;
+use serde_json::json;
+use std::time::Duration;
+
+use rust_analyzer_server::{
+    lsp::RequestTimeout,
+    mcp::{handle_tool_call, RustAnalyzerMCPServer},
+    protocol::{ErrorCode, ToolError},
+};
+use test_support::MockBackend;
+
+#[test]
+fn test_error_json_shape() {
+    let error = ToolError::new(ErrorCode::InvalidParams, "Missing line");
+    assert_eq!(
+        error.to_json(),
+        json!({ "code": "invalid_params", "message": "Missing line", "retryable": false })
+    );
+
+    let error = ToolError::new(ErrorCode::Indexing, "Still indexing")
+        .with_details(json!({ "waited_secs": 30 }));
+    assert_eq!(
+        error.to_json(),
+        json!({
+            "code": "indexing",
+            "message": "Still indexing",
+            "retryable": true,
+            "details": { "waited_secs": 30 }
+        })
+    );
+}
+
+#[test]
+fn test_retryable_codes() {
+    let retryable: Vec<ErrorCode> = [
+        ErrorCode::InvalidParams,
+        ErrorCode::Indexing,
+        ErrorCode::FileNotFound,
+        ErrorCode::BackendUnavailable,
+        ErrorCode::Timeout,
+        ErrorCode::UnknownTool,
+        ErrorCode::Forbidden,
+        ErrorCode::Internal,
+    ]
+    .into_iter()
+    .filter(|code| code.retryable())
+    .collect();
+    assert_eq!(
+        retryable,
+        [
+            ErrorCode::Indexing,
+            ErrorCode::BackendUnavailable,
+            ErrorCode::Timeout
+        ]
+    );
+}
+
+#[test]
+fn test_from_anyhow_keeps_code_under_context() {
+    let error = anyhow::Error::from(ToolError::new(ErrorCode::FileNotFound, "No such file"));
+    let error = Err::<(), _>(error).context("Opening lib.rs").unwrap_err();
+
+    let classified = ToolError::from_anyhow(&error);
+    assert_eq!(classified.code, ErrorCode::FileNotFound);
+    assert_eq!(classified.message, "Opening lib.rs");
+}
+
+#[test]
+fn test_from_anyhow_classifies_timeouts_and_others() {
+    let timeout = anyhow::Error::from(RequestTimeout {
+        method: "textDocument/hover".to_string(),
+        timeout: Duration::from_secs(5),
+    });
+    let classified = ToolError::from_anyhow(&timeout);
+    assert_eq!(classified.code, ErrorCode::Timeout);
+    assert_eq!(classified.message, timeout.to_string());
+    assert_eq!(
+        classified.details,
+        Some(json!({ "method": "textDocument/hover", "timeout_secs": 5.0 }))
+    );
+
+    let other = anyhow::anyhow!("Something broke");
+    assert_eq!(
+        ToolError::from_anyhow(&other),
+        ToolError::new(ErrorCode::Internal, "Something broke")
+    );
+}
+
+#[tokio::test]
+async fn test_unknown_tool_suggests_the_closest() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut server = RustAnalyzerMCPServer::with_workspace(dir.path().to_path_buf());
+    server
+        .start_with_backend(Box::new(MockBackend::new()))
+        .await
+        .unwrap();
+
+    let error = handle_tool_call(&mut server, "rust_analyzer_hovr", json!({}))
+        .await
+        .unwrap_err();
+    let error = ToolError::from_anyhow(&error).to_json();
+    assert_eq!(error["code"], "unknown_tool");
+    assert_eq!(error["retryable"], false);
+    assert_eq!(error["details"]["suggestion"], "hover");
+}
+
+#[test]
+fn test_http_status_by_code() {
+    assert_eq!(ErrorCode::InvalidParams.http_status(), 400);
+    assert_eq!(ErrorCode::UnknownTool.http_status(), 404);
+    assert_eq!(ErrorCode::FileNotFound.http_status(), 404);
+    assert_eq!(ErrorCode::Indexing.http_status(), 503);
+    assert_eq!(ErrorCode::BackendUnavailable.http_status(), 503);
+    assert_eq!(ErrorCode::Timeout.http_status(), 504);
+    assert_eq!(ErrorCode::Forbidden.http_status(), 403);
+    assert_eq!(ErrorCode::Internal.http_status(), 500);
+}
+
+#[test]
+fn test_retry_after_follows_indexing_progress() {
+    let indexing = |details| ToolError::new(ErrorCode::Indexing, "").with_details(details);
+    assert_eq!(
+        indexing(json!({ "waited_secs": 0, "percent": 40 })).retry_after_secs(),
+        Some(6)
+    );
+    assert_eq!(
+        indexing(json!({ "waited_secs": 0, "percent": 99 })).retry_after_secs(),
+        Some(1)
+    );
+    assert_eq!(
+        indexing(json!({ "waited_secs": 0 })).retry_after_secs(),
+        Some(5)
+    );
+    assert_eq!(
+        ToolError::new(ErrorCode::BackendUnavailable, "").retry_after_secs(),
+        Some(5)
+    );
+    assert_eq!(
+        ToolError::new(ErrorCode::Timeout, "").retry_after_secs(),
+        None
+    );
+}
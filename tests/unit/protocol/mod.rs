@@ -1,2 +1,3 @@
+mod error_tests;
 mod request_tests;
 mod tool_tests;
\ No newline at end of file
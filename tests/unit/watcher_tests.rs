@@ -0,0 +1,37 @@
+use std::path::Path;
+
+use rust_analyzer_server::watcher::is_relevant;
+
+#[test]
+fn test_rust_sources_and_manifests_are_relevant() {
+    let root = Path::new("/ws");
+    assert!(is_relevant(root, Path::new("/ws/src/lib.rs")));
+    assert!(is_relevant(root, Path::new("/ws/Cargo.toml")));
+    assert!(is_relevant(root, Path::new("/ws/crates/core/Cargo.toml")));
+}
+
+#[test]
+fn test_other_files_are_ignored() {
+    let root = Path::new("/ws");
+    assert!(!is_relevant(root, Path::new("/ws/README.md")));
+    assert!(!is_relevant(root, Path::new("/ws/Cargo.lock")));
+    assert!(!is_relevant(
+        root,
+        Path::new("/ws/target/debug/build/out.rs")
+    ));
+    assert!(!is_relevant(
+        root,
+        Path::new("/ws/.git/hooks/pre-commit.rs")
+    ));
+    assert!(!is_relevant(root, Path::new("/elsewhere/src/lib.rs")));
+}
+
+#[test]
+fn test_only_workspace_relative_components_are_checked() {
+    // A workspace that itself lives under a directory named `target`.
+    let root = Path::new("/home/me/target/ws");
+    assert!(is_relevant(
+        root,
+        Path::new("/home/me/target/ws/src/main.rs")
+    ));
+}
@@ -1,6 +1,65 @@
 mod unit {
+    mod client {
+        mod args_tests;
+    }
+    mod config_tests;
+    mod diagnostics_tests;
+    mod doctor_tests;
+    mod install_tests;
+    mod http {
+        mod history_tests;
+        mod openapi_tests;
+        mod stats_tests;
+    }
+    mod logging_tests;
+    mod mcp {
+        mod call_graph_tests;
+        mod cargo_check_tests;
+        mod cargo_metadata_tests;
+        mod context_tests;
+        mod describe_tests;
+        mod diagnostics_for_tests;
+        mod docs_links_tests;
+        mod find_references_tests;
+        mod glob_imports_tests;
+        mod handlers_tests;
+        mod impact_tests;
+        mod markdown_tests;
+        mod module_tree_tests;
+        mod rename_tests;
+        mod run_test_tests;
+        mod scip_tests;
+        mod snapshot_tests;
+        mod stdio_tests;
+        mod tags_tests;
+        mod toolchain_tests;
+        mod tools_tests;
+        mod trait_impls_tests;
+        mod truncate_tests;
+        mod type_graph_tests;
+        mod unused_tests;
+        mod usage_stats_tests;
+    }
+    mod lsp {
+        #[cfg(unix)]
+        mod binary_tests;
+        #[cfg(unix)]
+        mod cancel_tests;
+        mod completion_tests;
+        mod diagnostic_store_tests;
+        mod edits_tests;
+        mod messages_tests;
+        mod paths_tests;
+        mod position_tests;
+        mod progress_tests;
+        mod registrations_tests;
+        mod runnables_tests;
+    }
+    mod prewarm_tests;
     mod protocol {
+        mod error_tests;
         mod request_tests;
         mod tool_tests;
     }
+    mod watcher_tests;
 }